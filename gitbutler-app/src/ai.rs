@@ -0,0 +1,650 @@
+//! Generating commit messages, branch names and pull requests with a model hosted locally,
+//! with Ollama or llama.cpp's server, for the diffs not to leave the machine, with any API
+//! like OpenAI's, like Azure OpenAI or a corporate proxy, or with Anthropic's, each behind a
+//! [`Provider`]. It's the one the `AiProvider` of the project says, else the one of the
+//! feature in the app settings, else the one of the app settings, GitButler's servers are
+//! asked from the UI otherwise.
+//!
+//! What a model is asked is a prompt template filled in with what it generates from, see
+//! [`projects::render_prompt`]. The default ones are in the `ai/prompts` directory, and a
+//! project can replace them. The options of a generation are given on top of it, as the
+//! system prompt. The diff is cut down for the whole to fit in the token budget of the app
+//! settings, see [`budget`].
+//!
+//! Commit messages and pull requests can be streamed as they're written, and stopped where
+//! they are, see [`Stream`].
+
+mod budget;
+pub mod commands;
+mod provider;
+
+use std::{collections::HashMap, sync};
+
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+pub use provider::{from_settings, OnText, Provider};
+
+use crate::{
+    projects::{self, CommitMessageProfile, CommitMessageStyle, PromptKind, PromptTemplates},
+    virtual_branches::{to_branch_name, PullRequestContext},
+};
+
+const COMMIT_MESSAGE_TEMPLATE: &str = include_str!("ai/prompts/commit_message.md");
+const BRANCH_NAME_TEMPLATE: &str = include_str!("ai/prompts/branch_name.md");
+const PULL_REQUEST_TEMPLATE: &str = include_str!("ai/prompts/pull_request.md");
+
+const SYSTEM_PROMPT: &str = "You write the commit messages, branch names and pull requests of a \
+git repository, and reply with what you are asked for only.";
+const BRIEF_PROMPT: &str = "Keep it short: the summary line only, no description.";
+const EMOJI_PROMPT: &str = "Start the summary with the gitmoji of the change.";
+const CONVENTIONAL_PROMPT: &str = "Follow Conventional Commits: the summary is `type(scope): \
+summary`, with a type like feat, fix, docs, refactor, test or chore, and the scope of the change \
+if there's one.";
+const DETAILED_PROMPT: &str = "Write the description as a list of bullets, one per change, each \
+saying what changed and why.";
+const CHECK_PROMPT: &str = "Reply with OK.";
+const BRANCH_NAMES_PROMPT: &str = "Instead of one branch name, write three different ones, one \
+per line.";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("generating with a local model is not configured")]
+    NotConfigured,
+    #[error("{0} is not a valid url")]
+    InvalidUrl(String),
+    #[error("{0} is not on this machine, a local model is served on localhost")]
+    NotLocal(String),
+    #[error("failed to reach {endpoint}: {source}")]
+    Unreachable {
+        endpoint: String,
+        source: reqwest::Error,
+    },
+    #[error("{endpoint} responded with {status}: {body}")]
+    Rejected {
+        endpoint: String,
+        status: reqwest::StatusCode,
+        body: String,
+    },
+    #[error("{endpoint} failed to reply: {message}")]
+    Failed { endpoint: String, message: String },
+    #[error("the model replied with nothing")]
+    EmptyReply,
+    #[error("the generation was cancelled")]
+    Cancelled,
+    #[error("a generation is already streamed to {0}")]
+    StreamInUse(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// The title and description of a pull request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestContent {
+    pub title: String,
+    pub body: String,
+}
+
+/// What a prompt template is filled in with, see [`PromptKind::variables`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PromptContext {
+    pub diff: String,
+    pub files: Vec<String>,
+    pub branch_name: String,
+    pub commits: Vec<String>,
+}
+
+/// A prompt template of a project, with what it replaces.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplate {
+    pub kind: PromptKind,
+    /// The template of the project, the default one if it has none.
+    pub template: String,
+    pub default_template: String,
+    pub variables: &'static [&'static str],
+}
+
+/// The default template of `kind`.
+pub fn default_template(kind: PromptKind) -> &'static str {
+    match kind {
+        PromptKind::CommitMessage => COMMIT_MESSAGE_TEMPLATE,
+        PromptKind::BranchName => BRANCH_NAME_TEMPLATE,
+        PromptKind::PullRequest => PULL_REQUEST_TEMPLATE,
+    }
+}
+
+/// The prompt templates of `templates`, and the default ones where it has none.
+pub fn prompt_templates(templates: &PromptTemplates) -> Vec<PromptTemplate> {
+    PromptKind::ALL
+        .into_iter()
+        .map(|kind| PromptTemplate {
+            kind,
+            template: templates
+                .get(kind)
+                .unwrap_or(default_template(kind))
+                .to_string(),
+            default_template: default_template(kind).to_string(),
+            variables: kind.variables(),
+        })
+        .collect()
+}
+
+// the template of `kind` filled in with `context`, and the `extra` variables of the kind,
+// with as much of the diff as fits in `max_tokens` of `provider` with the `system` prompt
+fn prompt(
+    provider: &dyn Provider,
+    templates: &PromptTemplates,
+    kind: PromptKind,
+    context: &PromptContext,
+    extra: &[(&str, &str)],
+    system: &str,
+    max_tokens: usize,
+) -> String {
+    let template = templates.get(kind).unwrap_or(default_template(kind));
+    let files = context.files.join("\n");
+    let commits = context.commits.join("\n\n");
+    let render = |diff: &str| {
+        let mut variables = vec![
+            ("diff", diff),
+            ("files", files.as_str()),
+            ("branch_name", context.branch_name.as_str()),
+            ("commits", commits.as_str()),
+        ];
+        variables.extend_from_slice(extra);
+        projects::render_prompt(template, &variables)
+    };
+    let left = max_tokens
+        .saturating_sub(provider.count_tokens(system))
+        .saturating_sub(provider.count_tokens(&render("")));
+    render(&budget::fit(&context.diff, left))
+}
+
+/// Where a generation that is streamed goes: the reply so far is given to `on_text` as it's
+/// written, and cancelling `cancel` stops it, with what's been written so far as the reply.
+pub struct Stream<'a> {
+    pub on_text: &'a OnText<'a>,
+    pub cancel: &'a CancellationToken,
+}
+
+/// The generations that are streamed, by the id of their stream, for them to be cancelled
+/// from the UI.
+#[derive(Debug, Default)]
+pub struct Streams {
+    streams: sync::Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl Streams {
+    /// The token that cancels the generation streamed to `stream_id`, which no other
+    /// generation may be streamed to until it's finished.
+    pub fn start(&self, stream_id: &str) -> Result<CancellationToken, Error> {
+        let mut streams = self.streams.lock().unwrap();
+        if streams.contains_key(stream_id) {
+            return Err(Error::StreamInUse(stream_id.to_string()));
+        }
+        let cancel = CancellationToken::new();
+        streams.insert(stream_id.to_string(), cancel.clone());
+        Ok(cancel)
+    }
+
+    pub fn finish(&self, stream_id: &str) {
+        self.streams.lock().unwrap().remove(stream_id);
+    }
+
+    /// Cancels the generation streamed to `stream_id`, if it isn't done already.
+    pub fn cancel(&self, stream_id: &str) {
+        if let Some(cancel) = self.streams.lock().unwrap().remove(stream_id) {
+            cancel.cancel();
+        }
+    }
+}
+
+// what `provider` replies, streamed to `stream` if any. A stream that is cancelled before
+// anything is written fails with `Error::Cancelled`.
+async fn reply(
+    provider: &dyn Provider,
+    system: &str,
+    prompt: &str,
+    stream: Option<&Stream<'_>>,
+) -> Result<String, Error> {
+    let Some(stream) = stream else {
+        return provider.generate(system, prompt).await;
+    };
+    let so_far = sync::Mutex::new(String::new());
+    let on_text = |text: &str| {
+        text.clone_into(&mut so_far.lock().unwrap());
+        (stream.on_text)(text);
+    };
+    // dropping the stream drops the request, for the model to stop too
+    tokio::select! {
+        reply = provider.stream(system, prompt, &on_text) => reply,
+        () = stream.cancel.cancelled() => {
+            let so_far = so_far.lock().unwrap().trim().to_string();
+            if so_far.is_empty() {
+                Err(Error::Cancelled)
+            } else {
+                Ok(so_far)
+            }
+        }
+    }
+}
+
+/// How a commit message is written.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommitMessageOptions<'a> {
+    /// A summary line only, without the description under it.
+    pub brief: bool,
+    /// The summary starts with the gitmoji of the change.
+    pub emoji: bool,
+    pub profile: Option<&'a CommitMessageProfile>,
+}
+
+/// A commit message for the changes of `context`, written like `options` say, streamed to
+/// `stream` if any.
+pub async fn generate_commit_message(
+    provider: &dyn Provider,
+    templates: &PromptTemplates,
+    context: &PromptContext,
+    options: CommitMessageOptions<'_>,
+    max_tokens: usize,
+    stream: Option<&Stream<'_>>,
+) -> Result<String, Error> {
+    let system = commit_message_instructions(options.brief, options.emoji, options.profile);
+    let prompt = prompt(
+        provider,
+        templates,
+        PromptKind::CommitMessage,
+        context,
+        &[],
+        &system,
+        max_tokens,
+    );
+    let message = reply(provider, &system, &prompt, stream).await?;
+    Ok(strip_fences(&message).to_string())
+}
+
+// the instructions of the profile come last, for them to have the last word
+fn commit_message_instructions(
+    brief: bool,
+    emoji: bool,
+    profile: Option<&CommitMessageProfile>,
+) -> String {
+    let mut prompt = vec![SYSTEM_PROMPT];
+    match profile.map(|profile| profile.style) {
+        Some(CommitMessageStyle::Conventional) => prompt.push(CONVENTIONAL_PROMPT),
+        Some(CommitMessageStyle::OneLine) => prompt.push(BRIEF_PROMPT),
+        Some(CommitMessageStyle::Detailed) if !brief => prompt.push(DETAILED_PROMPT),
+        Some(CommitMessageStyle::Detailed) | None => {}
+    }
+    if brief && !prompt.contains(&BRIEF_PROMPT) {
+        prompt.push(BRIEF_PROMPT);
+    }
+    if emoji {
+        prompt.push(EMOJI_PROMPT);
+    }
+    if let Some(profile) = profile.filter(|profile| !profile.instructions.is_empty()) {
+        prompt.push(&profile.instructions);
+    }
+    prompt.join(" ")
+}
+
+/// A branch name for the changes of `context`, in kebab-case.
+pub async fn generate_branch_name(
+    provider: &dyn Provider,
+    templates: &PromptTemplates,
+    context: &PromptContext,
+    max_tokens: usize,
+) -> Result<String, Error> {
+    let prompt = prompt(
+        provider,
+        templates,
+        PromptKind::BranchName,
+        context,
+        &[],
+        SYSTEM_PROMPT,
+        max_tokens,
+    );
+    let name = provider.generate(SYSTEM_PROMPT, &prompt).await?;
+    let name = strip_fences(&name)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_matches(|c: char| c == '`' || c == '"' || c == '\'' || c.is_whitespace())
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-");
+    if name.is_empty() {
+        return Err(Error::EmptyReply);
+    }
+    Ok(name)
+}
+
+/// Up to three different branch names for the changes of `context`, see [`to_branch_name`].
+pub async fn suggest_branch_names(
+    provider: &dyn Provider,
+    templates: &PromptTemplates,
+    context: &PromptContext,
+    max_tokens: usize,
+) -> Result<Vec<String>, Error> {
+    let system = format!("{SYSTEM_PROMPT} {BRANCH_NAMES_PROMPT}");
+    let prompt = prompt(
+        provider,
+        templates,
+        PromptKind::BranchName,
+        context,
+        &[],
+        &system,
+        max_tokens,
+    );
+    let reply = provider.generate(&system, &prompt).await?;
+    let names = branch_names(strip_fences(&reply));
+    if names.is_empty() {
+        return Err(Error::EmptyReply);
+    }
+    Ok(names)
+}
+
+// the names of the lines of `reply`, without the numbers or bullets models list them with
+fn branch_names(reply: &str) -> Vec<String> {
+    let mut names = vec![];
+    for line in reply.lines() {
+        let line = line.trim_start();
+        let line =
+            line.strip_prefix(['-', '*'])
+                .unwrap_or_else(|| match line.split_once(['.', ')']) {
+                    Some((number, rest))
+                        if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) =>
+                    {
+                        rest
+                    }
+                    _ => line,
+                });
+        let name = to_branch_name(line);
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.truncate(3);
+    names
+}
+
+/// The title and description of the pull request of `context`, the description following its
+/// template, streamed to `stream` if any. The reply so far is its title on the first line and
+/// its description after it.
+pub async fn generate_pr_content(
+    provider: &dyn Provider,
+    templates: &PromptTemplates,
+    context: &PullRequestContext,
+    max_tokens: usize,
+    stream: Option<&Stream<'_>>,
+) -> Result<PullRequestContent, Error> {
+    let prompt = prompt(
+        provider,
+        templates,
+        PromptKind::PullRequest,
+        &PromptContext {
+            diff: context.diff_summary.clone(),
+            files: context
+                .files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+            branch_name: context.branch_name.clone(),
+            commits: context.commit_messages.clone(),
+        },
+        &[("template", context.template.as_str())],
+        SYSTEM_PROMPT,
+        max_tokens,
+    );
+    let reply = reply(provider, SYSTEM_PROMPT, &prompt, stream).await?;
+    pr_content(strip_fences(&reply)).ok_or(Error::EmptyReply)
+}
+
+// the first line of `reply` is the title, what follows is the description
+fn pr_content(reply: &str) -> Option<PullRequestContent> {
+    let (title, body) = reply.trim().split_once('\n').unwrap_or((reply.trim(), ""));
+    let title = title.trim_start_matches('#').trim();
+    let title = title
+        .strip_prefix("Title:")
+        .unwrap_or(title)
+        .trim()
+        .trim_matches(|c: char| c == '"' || c == '*' || c == '`')
+        .to_string();
+    if title.is_empty() {
+        return None;
+    }
+    let body = body.trim();
+    let body = body
+        .strip_prefix("Description:")
+        .unwrap_or(body)
+        .trim()
+        .to_string();
+    Some(PullRequestContent { title, body })
+}
+
+/// What `provider` replies to a prompt asking for nothing, to check that it's reachable and
+/// accepts the credentials it's given.
+pub async fn check(provider: &dyn Provider) -> Result<String, Error> {
+    provider.generate(CHECK_PROMPT, "").await
+}
+
+// models like to wrap what they write in a code block
+fn strip_fences(reply: &str) -> &str {
+    let reply = reply.trim();
+    match reply
+        .strip_prefix("```")
+        .and_then(|reply| reply.strip_suffix("```"))
+    {
+        // the language of the block, if any, is on the line of the opening fence
+        Some(inner) => inner
+            .split_once('\n')
+            .map_or(inner, |(_, inner)| inner)
+            .trim(),
+        None => reply,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{AiProvider, LocalModelApi};
+
+    fn provider() -> Box<dyn Provider> {
+        from_settings(&AiProvider::Local {
+            api: LocalModelApi::Ollama,
+            endpoint: "http://localhost:11434".to_string(),
+            model: "llama3".to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn commit_message_instructions_of_profiles() {
+        assert_eq!(
+            commit_message_instructions(false, false, None),
+            SYSTEM_PROMPT
+        );
+        let profile = CommitMessageProfile {
+            name: "tickets".to_string(),
+            style: CommitMessageStyle::Detailed,
+            instructions: "Mention the ticket.".to_string(),
+        };
+        assert_eq!(
+            commit_message_instructions(false, true, Some(&profile)),
+            format!("{SYSTEM_PROMPT} {DETAILED_PROMPT} {EMOJI_PROMPT} Mention the ticket.")
+        );
+        // a brief message has no description to detail
+        assert_eq!(
+            commit_message_instructions(true, false, Some(&profile)),
+            format!("{SYSTEM_PROMPT} {BRIEF_PROMPT} Mention the ticket.")
+        );
+        let one_line = CommitMessageProfile {
+            style: CommitMessageStyle::OneLine,
+            instructions: String::new(),
+            ..profile
+        };
+        assert_eq!(
+            commit_message_instructions(true, false, Some(&one_line)),
+            format!("{SYSTEM_PROMPT} {BRIEF_PROMPT}")
+        );
+    }
+
+    #[test]
+    fn default_templates_are_valid() {
+        for kind in PromptKind::ALL {
+            assert_eq!(kind.validate(default_template(kind)), Ok(()));
+        }
+        let context = PromptContext {
+            diff: "+login".to_string(),
+            files: vec!["src/login.rs".to_string(), "README.md".to_string()],
+            ..Default::default()
+        };
+        let templates = PromptTemplates {
+            branch_name: Some("Name {{files}}: {{diff}}".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            prompt(
+                provider().as_ref(),
+                &templates,
+                PromptKind::BranchName,
+                &context,
+                &[],
+                "",
+                1000
+            ),
+            "Name src/login.rs\nREADME.md: +login"
+        );
+        assert!(prompt(
+            provider().as_ref(),
+            &templates,
+            PromptKind::CommitMessage,
+            &context,
+            &[],
+            "",
+            1000
+        )
+        .ends_with("\n\n+login\n"));
+    }
+
+    #[test]
+    fn prompts_fit_the_budget() {
+        let context = PromptContext {
+            diff: format!(
+                "--- a/src/login.rs\n+++ b/src/login.rs\n@@ -1,0 +1,1000 @@\n{}",
+                "+    redirect();\n".repeat(1000)
+            ),
+            ..Default::default()
+        };
+        let prompt = prompt(
+            provider().as_ref(),
+            &PromptTemplates::default(),
+            PromptKind::CommitMessage,
+            &context,
+            &[],
+            SYSTEM_PROMPT,
+            500,
+        );
+        assert!(budget::estimate_tokens(SYSTEM_PROMPT) + budget::estimate_tokens(&prompt) <= 500);
+        assert!(prompt.contains("+++ b/src/login.rs\n@@ -1,0 +1,1000 @@\n+    redirect();\n"));
+        assert!(prompt.contains("lines left out]"));
+    }
+
+    #[test]
+    fn branch_names_of_a_list() {
+        assert_eq!(
+            branch_names(
+                "1. fix-login-redirect\n2) `Redirect after login`\n- fix-login-redirect\n404-page"
+            ),
+            vec!["fix-login-redirect", "redirect-after-login", "404-page"]
+        );
+    }
+
+    #[test]
+    fn pr_content_of_a_reply() {
+        assert_eq!(
+            pr_content("# Title: Redirect after login\n\nDescription:\n## Summary\n\nRedirects.\n"),
+            Some(PullRequestContent {
+                title: "Redirect after login".to_string(),
+                body: "## Summary\n\nRedirects.".to_string(),
+            })
+        );
+        assert_eq!(
+            pr_content("Redirect after login"),
+            Some(PullRequestContent {
+                title: "Redirect after login".to_string(),
+                body: String::new(),
+            })
+        );
+        assert_eq!(pr_content("  \n"), None);
+    }
+
+    #[test]
+    fn strips_code_fences() {
+        assert_eq!(strip_fences("```\nAdd login\n```"), "Add login");
+        assert_eq!(
+            strip_fences("```text\nAdd login\n\nWith a form\n```\n"),
+            "Add login\n\nWith a form"
+        );
+        assert_eq!(strip_fences(" Add login "), "Add login");
+    }
+
+    // writes the start of a reply, if any, and never the rest
+    struct Unfinished(&'static str);
+
+    #[async_trait::async_trait]
+    impl Provider for Unfinished {
+        async fn generate(&self, _system: &str, _prompt: &str) -> Result<String, Error> {
+            std::future::pending().await
+        }
+
+        async fn stream(
+            &self,
+            _system: &str,
+            _prompt: &str,
+            on_text: &OnText,
+        ) -> Result<String, Error> {
+            if !self.0.is_empty() {
+                on_text(self.0);
+            }
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_streams_reply_with_what_is_written() {
+        let streams = Streams::default();
+        let cancel = streams.start("stream").unwrap();
+        assert!(matches!(
+            streams.start("stream"),
+            Err(Error::StreamInUse(_))
+        ));
+        let on_text = |text: &str| {
+            assert_eq!(text, "Add login\n\nWith");
+            streams.cancel("stream");
+        };
+        let stream = Stream {
+            on_text: &on_text,
+            cancel: &cancel,
+        };
+        let reply = reply(&Unfinished("Add login\n\nWith"), "", "", Some(&stream))
+            .await
+            .unwrap();
+        assert_eq!(reply, "Add login\n\nWith");
+
+        // cancelled before anything is written
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let on_text = |_: &str| {};
+        let stream = Stream {
+            on_text: &on_text,
+            cancel: &cancel,
+        };
+        assert!(matches!(
+            reply(&Unfinished(""), "", "", Some(&stream)).await,
+            Err(Error::Cancelled)
+        ));
+    }
+}
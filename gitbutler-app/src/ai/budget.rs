@@ -0,0 +1,378 @@
+//! Cutting diffs down to a number of tokens, for prompts not to overflow the context of the
+//! model. When a diff doesn't fit, its lines are kept in the order of their [`Rank`], and the
+//! ones left out are replaced by a line saying how many they were, so the model still sees
+//! every file, the functions the changes are in, and as many of the changes as fit. Lines are
+//! kept from the start of every hunk first, for the changes of every file to be seen.
+
+use std::fmt::Write;
+
+// models average about four bytes of code per token
+const BYTES_PER_TOKEN: usize = 4;
+
+// files that are generated, whose changes say little about the change
+const GENERATED_FILES: &[&str] = &[
+    "Cargo.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "yarn.lock",
+];
+const GENERATED_EXTENSIONS: &[&str] = &[".lock", ".map", ".min.css", ".min.js", ".snap"];
+
+// what the lines that define something start with, in common languages
+const SIGNATURE_PREFIXES: &[&str] = &[
+    "async ",
+    "class ",
+    "def ",
+    "enum ",
+    "export ",
+    "fn ",
+    "func ",
+    "function ",
+    "impl ",
+    "impl<",
+    "interface ",
+    "module ",
+    "pub ",
+    "pub(",
+    "struct ",
+    "trait ",
+    "type ",
+];
+
+/// How much a line of a diff says about it, the most first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    /// The headers of files and hunks, that say what changed and in which function.
+    Header,
+    /// The lines added or removed that define a function or a type.
+    Signature,
+    /// The other lines added or removed.
+    Change,
+    /// The lines around the changes.
+    Context,
+    /// The lines of generated files, like lock files.
+    Generated,
+}
+
+struct Line<'a> {
+    text: &'a str,
+    rank: Rank,
+    // the line of its hunk it is, for the start of every hunk to be kept first
+    offset: usize,
+}
+
+/// How many tokens `text` is, about.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(BYTES_PER_TOKEN)
+}
+
+/// `diff` cut down to about `max_tokens`, see the [module docs](self).
+pub fn fit(diff: &str, max_tokens: usize) -> String {
+    if estimate_tokens(diff) <= max_tokens {
+        return diff.to_string();
+    }
+    let max_len = max_tokens * BYTES_PER_TOKEN;
+    let lines = parse(diff);
+
+    let mut order = (0..lines.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| (lines[*i].rank, lines[*i].offset, *i));
+    // a tenth is left for the lines that say what is left out
+    let mut len = max_len / 10;
+    let mut kept = vec![false; lines.len()];
+    for i in &order {
+        let line_len = lines[*i].text.len() + 1;
+        if len + line_len <= max_len {
+            len += line_len;
+            kept[*i] = true;
+        }
+    }
+
+    // lines left out here and there can take more than their tenth, the least important lines
+    // that are kept make room for them then. what leaving one more out saves is worked out from
+    // the runs of left out lines around it, without rendering again
+    let mut runs = Runs::new(&kept);
+    let mut len = render(&lines, &kept).len();
+    for i in order.iter().rev().copied() {
+        if len <= max_len {
+            break;
+        }
+        if kept[i] {
+            kept[i] = false;
+            let (before, after) = runs.leave_out(i);
+            len = len - (lines[i].text.len() + 1) - before + after;
+        }
+    }
+    render(&lines, &kept)
+}
+
+// the runs of lines that are left out, each rendered as one line saying how many they are.
+// a run is known by its ends: `starts` is set at its last line, `ends` at its first.
+struct Runs {
+    starts: Vec<usize>,
+    ends: Vec<usize>,
+    left_out: Vec<bool>,
+}
+
+impl Runs {
+    fn new(kept: &[bool]) -> Self {
+        let mut runs = Self {
+            starts: (0..kept.len()).collect(),
+            ends: (0..kept.len()).collect(),
+            left_out: vec![false; kept.len()],
+        };
+        for (i, kept) in kept.iter().enumerate() {
+            if !kept {
+                runs.leave_out(i);
+            }
+        }
+        runs
+    }
+
+    // leaves line `i` out, joining the runs around it, and returns how long the lines saying
+    // how many are left out there were before, and how long the one that replaces them is
+    fn leave_out(&mut self, i: usize) -> (usize, usize) {
+        let start = match i.checked_sub(1) {
+            Some(previous) if self.left_out[previous] => self.starts[previous],
+            _ => i,
+        };
+        let next = i + 1;
+        let end = if next < self.left_out.len() && self.left_out[next] {
+            self.ends[next]
+        } else {
+            i
+        };
+        self.left_out[i] = true;
+        self.starts[end] = start;
+        self.ends[start] = end;
+        (
+            left_out_len(i - start) + left_out_len(end - i),
+            left_out_len(end - start + 1),
+        )
+    }
+}
+
+// the length of the line saying `count` lines are left out, see `push_left_out`
+fn left_out_len(count: usize) -> usize {
+    let mut line = String::new();
+    let mut count = count;
+    push_left_out(&mut line, &mut count);
+    line.len()
+}
+
+fn render(lines: &[Line], kept: &[bool]) -> String {
+    let mut fitted = String::new();
+    let mut left_out = 0;
+    for (line, kept) in lines.iter().zip(kept) {
+        if !kept {
+            left_out += 1;
+            continue;
+        }
+        push_left_out(&mut fitted, &mut left_out);
+        fitted.push_str(line.text);
+        fitted.push('\n');
+    }
+    push_left_out(&mut fitted, &mut left_out);
+    fitted
+}
+
+fn push_left_out(fitted: &mut String, left_out: &mut usize) {
+    match *left_out {
+        0 => {}
+        1 => fitted.push_str("[1 line left out]\n"),
+        n => {
+            let _ = writeln!(fitted, "[{n} lines left out]");
+        }
+    }
+    *left_out = 0;
+}
+
+// the lines of `diff` with their rank. Lines are in a hunk for as many lines as its header
+// says, the others are headers.
+fn parse(diff: &str) -> Vec<Line<'_>> {
+    let mut lines = vec![];
+    let mut generated = false;
+    let (mut old_left, mut new_left) = (0, 0);
+    let mut offset = 0;
+    let mut rows = diff.lines().peekable();
+    while let Some(text) = rows.next() {
+        if text.starts_with("@@ -") {
+            (old_left, new_left) = hunk_lengths(text).unwrap_or((0, 0));
+            offset = 0;
+            lines.push(Line {
+                text,
+                rank: Rank::Header,
+                offset,
+            });
+            continue;
+        }
+        if old_left == 0 && new_left == 0 {
+            let starts_file = text.starts_with("diff --git ")
+                || (text.starts_with("--- ")
+                    && rows.peek().map_or(false, |next| next.starts_with("+++ ")));
+            if starts_file {
+                generated = false;
+            }
+            if let Some(path) = text.strip_prefix("+++ ") {
+                generated = is_generated(path.strip_prefix("b/").unwrap_or(path));
+            }
+            lines.push(Line {
+                text,
+                rank: Rank::Header,
+                offset: 0,
+            });
+            continue;
+        }
+
+        offset += 1;
+        let rank = match text.chars().next() {
+            Some('+') => {
+                new_left -= 1;
+                if is_signature(text.get(1..).unwrap_or_default()) {
+                    Rank::Signature
+                } else {
+                    Rank::Change
+                }
+            }
+            Some('-') => {
+                old_left -= 1;
+                if is_signature(text.get(1..).unwrap_or_default()) {
+                    Rank::Signature
+                } else {
+                    Rank::Change
+                }
+            }
+            // `\ No newline at end of file`
+            Some('\\') => Rank::Context,
+            _ => {
+                old_left -= 1;
+                new_left -= 1;
+                Rank::Context
+            }
+        };
+        lines.push(Line {
+            text,
+            rank: if generated { Rank::Generated } else { rank },
+            offset,
+        });
+        // a hunk that says it's shorter than it is ends early rather than never
+        if old_left < 0 || new_left < 0 {
+            (old_left, new_left) = (0, 0);
+        }
+    }
+    lines
+}
+
+// the number of old and new lines of the hunk of `header`, like `@@ -1,3 +1,4 @@`
+fn hunk_lengths(header: &str) -> Option<(i64, i64)> {
+    let mut ranges = header.strip_prefix("@@ -")?.split_whitespace();
+    let length = |range: &str| match range.split_once(',') {
+        Some((_, length)) => length.parse().ok(),
+        None => Some(1),
+    };
+    let old = length(ranges.next()?)?;
+    let new = length(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+fn is_generated(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    GENERATED_FILES.contains(&name)
+        || GENERATED_EXTENSIONS
+            .iter()
+            .any(|extension| name.ends_with(extension))
+}
+
+fn is_signature(line: &str) -> bool {
+    let line = line.trim_start();
+    SIGNATURE_PREFIXES
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+--- a/src/login.rs
++++ b/src/login.rs
+@@ -1,3 +1,3 @@ mod login;
+ use auth;
+-fn login() {
++fn login(redirect: Redirect) {
+     check();
+@@ -10,1 +10,3 @@ fn logout() {
+     clear();
++    redirect_to(home);
++    log(\"logged out\");
+--- a/Cargo.lock
++++ b/Cargo.lock
+@@ -1 +1 @@
+-version = 1
++version = 2
+";
+
+    #[test]
+    fn diffs_that_fit_are_left_as_they_are() {
+        assert_eq!(fit(DIFF, 1000), DIFF);
+    }
+
+    #[test]
+    fn headers_and_signatures_are_kept_first() {
+        let fitted = fit(DIFF, 75);
+        assert!(estimate_tokens(&fitted) <= 75);
+        assert_eq!(
+            fitted,
+            "\
+--- a/src/login.rs
++++ b/src/login.rs
+@@ -1,3 +1,3 @@ mod login;
+ use auth;
+-fn login() {
++fn login(redirect: Redirect) {
+[1 line left out]
+@@ -10,1 +10,3 @@ fn logout() {
+     clear();
++    redirect_to(home);
++    log(\"logged out\");
+--- a/Cargo.lock
++++ b/Cargo.lock
+@@ -1 +1 @@
+[2 lines left out]
+"
+        );
+
+        // with less room, the changes are kept over the context
+        let fitted = fit(DIFF, 60);
+        assert!(estimate_tokens(&fitted) <= 60);
+        assert!(fitted.contains("-fn login() {"));
+        assert!(!fitted.contains(" use auth;"));
+        assert!(fitted.contains("+++ b/Cargo.lock\n@@ -1 +1 @@\n"));
+    }
+
+    #[test]
+    fn large_diffs_fit() {
+        let mut diff = String::from("--- a/data.txt\n+++ b/data.txt\n@@ -0,0 +1,20000 @@\n");
+        for i in 0..20_000_u32 {
+            diff.push_str(if i % 3 == 0 {
+                "+fn line()\n"
+            } else {
+                "+line\n"
+            });
+        }
+        let fitted = fit(&diff, 500);
+        assert!(estimate_tokens(&fitted) <= 500);
+        assert!(fitted.starts_with("--- a/data.txt\n+++ b/data.txt\n@@ -0,0 +1,20000 @@\n"));
+    }
+
+    #[test]
+    fn lengths_of_hunks() {
+        assert_eq!(hunk_lengths("@@ -1,4 +1,5 @@ fn main() {"), Some((4, 5)));
+        assert_eq!(hunk_lengths("@@ -1 +0,0 @@"), Some((1, 0)));
+        assert_eq!(hunk_lengths("@@ nonsense"), None);
+    }
+}
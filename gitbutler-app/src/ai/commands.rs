@@ -0,0 +1,528 @@
+use std::sync;
+
+use tauri::{AppHandle, Manager};
+use tokio_util::sync::CancellationToken;
+use tracing::instrument;
+
+use crate::{
+    error::{Code, Error},
+    events,
+    projects::{self, ProjectId, PromptKind},
+    settings, virtual_branches,
+};
+
+impl From<super::Error> for Error {
+    fn from(error: super::Error) -> Self {
+        match error {
+            super::Error::NotConfigured => Error::UserError {
+                code: Code::Validation,
+                message: "Set up a local model in the settings first".to_string(),
+            },
+            super::Error::Cancelled => Error::UserError {
+                code: Code::Cancelled,
+                message: "Generation cancelled".to_string(),
+            },
+            super::Error::StreamInUse(stream_id) => Error::UserError {
+                code: Code::Validation,
+                message: format!("A generation is already streamed to {stream_id}"),
+            },
+            super::Error::Other(error) => {
+                tracing::error!(?error, "failed to generate with local model");
+                Error::caused_by(&error)
+            }
+            error => Error::UserError {
+                code: Code::LocalModel,
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+fn project(
+    handle: &AppHandle,
+    project_id: Option<&str>,
+) -> Result<Option<projects::Project>, Error> {
+    let Some(project_id) = project_id else {
+        return Ok(None);
+    };
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    Ok(Some(
+        handle.state::<projects::Controller>().get(&project_id)?,
+    ))
+}
+
+// the provider the app settings pick for `kind` if they pick one, else the one of the
+// project `project_id` if it has one, else the one of the app settings
+fn provider(
+    handle: &AppHandle,
+    project_id: Option<&str>,
+    kind: PromptKind,
+) -> Result<settings::AiProvider, Error> {
+    let settings = handle.state::<settings::Controller>().get()?;
+    if let Some(ai_provider) = settings.ai_feature_providers.value.get(kind) {
+        return Ok(ai_provider.clone());
+    }
+    Ok(project(handle, project_id)?
+        .and_then(|project| project.ai_provider)
+        .unwrap_or(settings.ai_provider.value))
+}
+
+// how many tokens prompts are cut down to, see `AppSettings::ai_token_budget`
+fn token_budget(handle: &AppHandle) -> Result<usize, Error> {
+    let tokens = handle
+        .state::<settings::Controller>()
+        .get()?
+        .ai_token_budget
+        .value;
+    Ok(usize::try_from(tokens).unwrap_or(usize::MAX))
+}
+
+// the prompt templates of the project `project_id`, none for the default ones
+fn project_prompt_templates(
+    handle: &AppHandle,
+    project_id: Option<&str>,
+) -> Result<projects::PromptTemplates, Error> {
+    Ok(project(handle, project_id)?
+        .map(|project| project.prompt_templates)
+        .unwrap_or_default())
+}
+
+// the commit message profile `name` of the project `project_id`, or the built-in one
+fn commit_message_profile(
+    handle: &AppHandle,
+    project_id: Option<&str>,
+    name: &str,
+) -> Result<projects::CommitMessageProfile, Error> {
+    let profile = match project(handle, project_id)? {
+        Some(project) => project.commit_message_profile(name),
+        None => projects::CommitMessageProfile::builtin()
+            .into_iter()
+            .find(|profile| profile.name == name),
+    };
+    profile.ok_or_else(|| Error::UserError {
+        code: Code::Validation,
+        message: format!("There is no commit message profile named {name:?}"),
+    })
+}
+
+// `context` with what mustn't leave the machine redacted, see `virtual_branches::redact`: its
+// diff and commits, its files without those that match the redacted paths, and its branch name
+// replaced if it matches them. Without a project, there's only the built-in rules and no audit
+// log to write to.
+async fn redact(
+    handle: &AppHandle,
+    project_id: Option<&str>,
+    purpose: PromptKind,
+    context: super::PromptContext,
+) -> Result<super::PromptContext, Error> {
+    let (diff, commits, mut paths, branch_name) = match project_id {
+        None => {
+            let redactor = virtual_branches::Redactor::new(
+                virtual_branches::Scanner::new(&[])?,
+                &[] as &[&str],
+                std::env::vars(),
+            );
+            let (diff, redactions) = redactor.redact_diff(&context.diff);
+            if !redactions.is_empty() {
+                tracing::info!(?purpose, ?redactions, "redacted diff");
+            }
+            let commits = context
+                .commits
+                .iter()
+                .map(|text| redactor.redact_text(text).0)
+                .collect();
+            (
+                diff,
+                commits,
+                redactor.redact_paths(&context.files).0,
+                redactor.redact_branch_name(&context.branch_name).0,
+            )
+        }
+        Some(project_id) => {
+            let project_id = project_id.parse().map_err(|_| Error::UserError {
+                code: Code::Validation,
+                message: "Malformed project id".to_string(),
+            })?;
+            let redacted = handle
+                .state::<virtual_branches::Controller>()
+                .redact(
+                    &project_id,
+                    purpose,
+                    &context.diff,
+                    &context.commits,
+                    &context.files,
+                    Some(&context.branch_name),
+                )
+                .await?;
+            (
+                redacted.diff,
+                redacted.texts,
+                redacted.paths,
+                redacted.branch_name.unwrap_or_default(),
+            )
+        }
+    };
+    paths.retain(|path| path != virtual_branches::REDACTED);
+    Ok(super::PromptContext {
+        diff,
+        files: paths,
+        branch_name,
+        commits,
+    })
+}
+
+/// `diff` redacted like the diffs of [`generate_commit_message`] are, for it to be sent to
+/// GitButler's servers, with what was redacted appended to the audit log of the project.
+#[tauri::command(async)]
+#[instrument(skip(handle, diff))]
+pub async fn redact_diff(
+    handle: AppHandle,
+    project_id: Option<&str>,
+    purpose: PromptKind,
+    diff: String,
+) -> Result<String, Error> {
+    let context = super::PromptContext {
+        diff,
+        ..Default::default()
+    };
+    Ok(redact(&handle, project_id, purpose, context).await?.diff)
+}
+
+// a generation streamed to the UI with `ai_reply` events, until it's done or cancelled with
+// `cancel_generation`
+struct ReplyStream<'a> {
+    handle: &'a AppHandle,
+    events: events::Bus,
+    project_id: ProjectId,
+    stream_id: String,
+    cancel: CancellationToken,
+    // the reply so far the UI was sent
+    sent: sync::Mutex<String>,
+}
+
+impl<'a> ReplyStream<'a> {
+    // a generation is streamed when it has a project to send the events of, and a `stream_id`
+    fn start(
+        handle: &'a AppHandle,
+        project_id: Option<&str>,
+        stream_id: Option<String>,
+    ) -> Result<Option<Self>, Error> {
+        let (Some(project_id), Some(stream_id)) = (project_id, stream_id) else {
+            return Ok(None);
+        };
+        let project_id = project_id.parse().map_err(|_| Error::UserError {
+            code: Code::Validation,
+            message: "Malformed project id".to_string(),
+        })?;
+        let cancel = handle.state::<super::Streams>().start(&stream_id)?;
+        Ok(Some(Self {
+            handle,
+            events: handle.state::<events::Bus>().inner().clone(),
+            project_id,
+            stream_id,
+            cancel,
+            sent: sync::Mutex::default(),
+        }))
+    }
+
+    // `reply` is the reply so far, the UI is sent what was added to it since the last time,
+    // or all of it if the start changed
+    fn send(&self, reply: &str) {
+        let mut sent = self.sent.lock().unwrap();
+        let event = match reply.strip_prefix(sent.as_str()) {
+            Some("") => return,
+            Some(delta) => events::Event::ai_reply(&self.project_id, &self.stream_id, delta, false),
+            None => events::Event::ai_reply(&self.project_id, &self.stream_id, reply, true),
+        };
+        reply.clone_into(&mut sent);
+        self.events.publish(event);
+    }
+}
+
+impl Drop for ReplyStream<'_> {
+    fn drop(&mut self) {
+        self.handle
+            .state::<super::Streams>()
+            .finish(&self.stream_id);
+    }
+}
+
+/// A commit message for `diff`, written like the commit message profile named `profile`
+/// says, if any. The prompt template of the project is filled in with `context`, like the
+/// files and the recent commits of the branch. With a `stream_id`, what's added to the message
+/// is sent with `project://<project_id>/ai/reply/<stream_id>` events as it's written, and
+/// [`cancel_generation`] stops it there.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command(async)]
+#[instrument(skip(handle, diff, context))]
+pub async fn generate_commit_message(
+    handle: AppHandle,
+    project_id: Option<&str>,
+    diff: String,
+    brief: bool,
+    emoji: bool,
+    profile: Option<&str>,
+    context: Option<super::PromptContext>,
+    stream_id: Option<String>,
+) -> Result<String, Error> {
+    let reply_stream = ReplyStream::start(&handle, project_id, stream_id)?;
+    let profile = profile
+        .map(|name| commit_message_profile(&handle, project_id, name))
+        .transpose()?;
+    let context = redact(
+        &handle,
+        project_id,
+        PromptKind::CommitMessage,
+        super::PromptContext {
+            diff,
+            ..context.unwrap_or_default()
+        },
+    )
+    .await?;
+    let provider =
+        super::from_settings(&provider(&handle, project_id, PromptKind::CommitMessage)?)?;
+    let on_text = |text: &str| {
+        if let Some(reply_stream) = &reply_stream {
+            reply_stream.send(text);
+        }
+    };
+    let stream = reply_stream.as_ref().map(|stream| super::Stream {
+        on_text: &on_text,
+        cancel: &stream.cancel,
+    });
+    super::generate_commit_message(
+        provider.as_ref(),
+        &project_prompt_templates(&handle, project_id)?,
+        &context,
+        super::CommitMessageOptions {
+            brief,
+            emoji,
+            profile: profile.as_ref(),
+        },
+        token_budget(&handle)?,
+        stream.as_ref(),
+    )
+    .await
+    .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle, diff, context))]
+pub async fn generate_branch_name(
+    handle: AppHandle,
+    project_id: Option<&str>,
+    diff: String,
+    context: Option<super::PromptContext>,
+) -> Result<String, Error> {
+    let context = redact(
+        &handle,
+        project_id,
+        PromptKind::BranchName,
+        super::PromptContext {
+            diff,
+            ..context.unwrap_or_default()
+        },
+    )
+    .await?;
+    super::generate_branch_name(
+        super::from_settings(&provider(&handle, project_id, PromptKind::BranchName)?)?.as_ref(),
+        &project_prompt_templates(&handle, project_id)?,
+        &context,
+        token_budget(&handle)?,
+    )
+    .await
+    .map_err(Into::into)
+}
+
+/// Two or three names for the branch `branch_id`, from its uncommitted changes. They are
+/// generated by the provider of the project if `use_model` and it's not GitButler's servers,
+/// and made up from the directories and words the changes touch the most otherwise.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn suggest_branch_names(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+    use_model: bool,
+) -> Result<Vec<String>, Error> {
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    let provider = provider(&handle, Some(project_id), PromptKind::BranchName)?;
+    let templates = project_prompt_templates(&handle, Some(project_id))?;
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let changes = handle
+        .state::<virtual_branches::Controller>()
+        .list_branch_changes(&project_id, &branch_id)
+        .await?;
+    let suggested = changes.suggest_names();
+    if !use_model || provider == settings::AiProvider::Cloud || changes.is_empty() {
+        return Ok(suggested);
+    }
+    let context = redact(
+        &handle,
+        Some(&project_id.to_string()),
+        PromptKind::BranchName,
+        super::PromptContext {
+            // cut down to the token budget with the rest of the prompt
+            diff: changes.diff(usize::MAX),
+            files: changes
+                .paths()
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let mut names = super::suggest_branch_names(
+        super::from_settings(&provider)?.as_ref(),
+        &templates,
+        &context,
+        token_budget(&handle)?,
+    )
+    .await?;
+    // a model that came up with one name is topped up with the ones made up
+    for name in suggested {
+        if names.len() >= 2 {
+            break;
+        }
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// The title and description of the pull request of the branch `branch_id`, generated by the
+/// provider of the project from the commits of the branch, see
+/// [`virtual_branches::pull_request_context`]. With a `stream_id`, the reply is streamed
+/// like the commit messages of [`generate_commit_message`] are.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn generate_pr_content(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+    stream_id: Option<String>,
+) -> Result<super::PullRequestContent, Error> {
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    let provider = super::from_settings(&provider(
+        &handle,
+        Some(project_id),
+        PromptKind::PullRequest,
+    )?)?;
+    let templates = project_prompt_templates(&handle, Some(project_id))?;
+    let reply_stream = ReplyStream::start(&handle, Some(project_id), stream_id)?;
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let controller = handle.state::<virtual_branches::Controller>();
+    let mut context = controller
+        .pull_request_context(&project_id, &branch_id)
+        .await?;
+    let redacted = redact(
+        &handle,
+        Some(&project_id.to_string()),
+        PromptKind::PullRequest,
+        super::PromptContext {
+            diff: context.diff_summary,
+            files: context
+                .files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+            branch_name: context.branch_name,
+            commits: context.commit_messages,
+        },
+    )
+    .await?;
+    context.diff_summary = redacted.diff;
+    context.files = redacted.files.into_iter().map(Into::into).collect();
+    context.branch_name = redacted.branch_name;
+    context.commit_messages = redacted.commits;
+    let on_text = |text: &str| {
+        if let Some(reply_stream) = &reply_stream {
+            reply_stream.send(text);
+        }
+    };
+    let stream = reply_stream.as_ref().map(|stream| super::Stream {
+        on_text: &on_text,
+        cancel: &stream.cancel,
+    });
+    super::generate_pr_content(
+        provider.as_ref(),
+        &templates,
+        &context,
+        token_budget(&handle)?,
+        stream.as_ref(),
+    )
+    .await
+    .map_err(Into::into)
+}
+
+/// Stops the generation streamed to `stream_id`, which replies with what it's written so far,
+/// or fails with [`Code::Cancelled`] if it's written nothing yet. Generations that are done
+/// are left as they are.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn cancel_generation(handle: AppHandle, stream_id: &str) -> Result<(), Error> {
+    handle.state::<super::Streams>().cancel(stream_id);
+    Ok(())
+}
+
+/// What was redacted from what was sent to models for the project `project_id`, the oldest
+/// first, see [`virtual_branches::redact`].
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_redactions(
+    handle: AppHandle,
+    project_id: &str,
+) -> Result<Vec<virtual_branches::RedactionLogEntry>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<virtual_branches::Controller>()
+        .list_redactions(&project_id)
+        .await
+        .map_err(Into::into)
+}
+
+/// The prompt templates of the project `project_id`, and the default ones they replace.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_prompt_templates(
+    handle: AppHandle,
+    project_id: &str,
+) -> Result<Vec<super::PromptTemplate>, Error> {
+    Ok(super::prompt_templates(&project_prompt_templates(
+        &handle,
+        Some(project_id),
+    )?))
+}
+
+/// Asks `ai_provider` for a reply, to check it before it's saved.
+#[tauri::command(async)]
+#[instrument(skip(ai_provider))]
+pub async fn check_ai_provider(ai_provider: settings::AiProvider) -> Result<String, Error> {
+    ai_provider.validate().map_err(|error| Error::UserError {
+        code: Code::Validation,
+        message: error.to_string(),
+    })?;
+    super::check(super::from_settings(&ai_provider.normalized())?.as_ref())
+        .await
+        .map_err(Into::into)
+}
@@ -0,0 +1,826 @@
+//! The models generating is asked of, behind a [`Provider`]: Ollama's chat API, OpenAI's
+//! chat completions, that llama.cpp's server, Azure OpenAI and most proxies copy, and
+//! Anthropic's messages. GitButler's servers are asked from the UI, they have no provider.
+//!
+//! Streamed replies are read as the lines they're sent in, NDJSON for Ollama and server-sent
+//! events for the others.
+
+use std::time;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{AiProvider, LocalModelApi};
+
+use super::{budget, Error};
+
+// a model loading for its first request takes a while
+const TIMEOUT: time::Duration = time::Duration::from_secs(120);
+
+const ANTHROPIC_URL: &str = "https://api.anthropic.com/v1/messages";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+// anthropic's api wants a limit, this one is more than commit messages and pull requests take
+const MAX_REPLY_TOKENS: u32 = 2048;
+
+/// What's called with the reply so far as it's streamed.
+pub type OnText<'a> = dyn Fn(&str) + Send + Sync + 'a;
+
+#[async_trait]
+pub trait Provider: Send + Sync {
+    /// The reply of the model to `prompt`, following the instructions of `system`.
+    async fn generate(&self, system: &str, prompt: &str) -> Result<String, Error>;
+
+    /// Like [`Provider::generate`], with `on_text` called with the reply so far every time
+    /// the model writes more of it.
+    async fn stream(&self, system: &str, prompt: &str, on_text: &OnText) -> Result<String, Error>;
+
+    /// How many tokens `text` is to the model, about.
+    fn count_tokens(&self, text: &str) -> usize {
+        budget::estimate_tokens(text)
+    }
+}
+
+/// The provider of `settings`, none of GitButler's servers.
+pub fn from_settings(settings: &AiProvider) -> Result<Box<dyn Provider>, Error> {
+    match settings {
+        AiProvider::Cloud => Err(Error::NotConfigured),
+        AiProvider::Local {
+            api: LocalModelApi::Ollama,
+            endpoint,
+            model,
+        } => Ok(Box::new(Ollama {
+            http: Http::local(endpoint, format!("{endpoint}/api/chat"))?,
+            model: model.clone(),
+        })),
+        AiProvider::Local {
+            api: LocalModelApi::OpenAiCompatible,
+            endpoint,
+            model,
+        } => Ok(Box::new(OpenAi {
+            http: Http::local(endpoint, format!("{endpoint}/v1/chat/completions"))?,
+            model: model.clone(),
+        })),
+        AiProvider::OpenAiCompatible {
+            base_url,
+            model,
+            headers,
+        } => Ok(Box::new(OpenAi {
+            http: Http::new(
+                base_url,
+                chat_completions_url(base_url)?,
+                headers
+                    .iter()
+                    .map(|header| (header.name.clone(), header.value.clone()))
+                    .collect(),
+            )?,
+            model: model.clone(),
+        })),
+        AiProvider::Anthropic { model, api_key } => {
+            Ok(Box::new(Anthropic::new(ANTHROPIC_URL, model, api_key)?))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: [Message<'a>; 2],
+    stream: bool,
+}
+
+impl<'a> ChatRequest<'a> {
+    fn new(model: &'a str, system: &'a str, prompt: &'a str, stream: bool) -> Self {
+        Self {
+            model,
+            messages: [
+                Message {
+                    role: "system",
+                    content: system,
+                },
+                Message {
+                    role: "user",
+                    content: prompt,
+                },
+            ],
+            stream,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReplyMessage {
+    content: String,
+}
+
+// ollama's reply, or a line of it when it's streamed
+#[derive(Deserialize)]
+struct ChatResponse {
+    message: ReplyMessage,
+}
+
+struct Ollama {
+    http: Http,
+    model: String,
+}
+
+#[async_trait]
+impl Provider for Ollama {
+    async fn generate(&self, system: &str, prompt: &str) -> Result<String, Error> {
+        let body = self
+            .http
+            .post(&ChatRequest::new(&self.model, system, prompt, false))
+            .await?
+            .text()
+            .await
+            .map_err(|source| self.http.unreachable(source))?;
+        let reply = serde_json::from_str::<ChatResponse>(&body)
+            .context("malformed ollama response")?
+            .message
+            .content;
+        non_empty(&reply)
+    }
+
+    async fn stream(&self, system: &str, prompt: &str, on_text: &OnText) -> Result<String, Error> {
+        let response = self
+            .http
+            .post(&ChatRequest::new(&self.model, system, prompt, true))
+            .await?;
+        let mut reply = String::new();
+        self.http
+            .for_each_line(response, |line| {
+                if line.is_empty() {
+                    return Ok(());
+                }
+                let chunk = serde_json::from_str::<ChatResponse>(line)
+                    .context("malformed ollama response")?;
+                reply.push_str(&chunk.message.content);
+                on_text(&reply);
+                Ok(())
+            })
+            .await?;
+        non_empty(&reply)
+    }
+}
+
+// the reply of openai's api
+#[derive(Deserialize)]
+struct ChatCompletion {
+    choices: Vec<ChatCompletionChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionChoice {
+    message: ReplyMessage,
+}
+
+// a server-sent event of a streamed completion
+#[derive(Deserialize)]
+struct ChatCompletionChunk {
+    choices: Vec<ChatCompletionChunkChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionChunkChoice {
+    delta: ChatCompletionDelta,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionDelta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+struct OpenAi {
+    http: Http,
+    model: String,
+}
+
+#[async_trait]
+impl Provider for OpenAi {
+    async fn generate(&self, system: &str, prompt: &str) -> Result<String, Error> {
+        let body = self
+            .http
+            .post(&ChatRequest::new(&self.model, system, prompt, false))
+            .await?
+            .text()
+            .await
+            .map_err(|source| self.http.unreachable(source))?;
+        let reply = serde_json::from_str::<ChatCompletion>(&body)
+            .context("malformed chat completion")?
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .unwrap_or_default();
+        non_empty(&reply)
+    }
+
+    async fn stream(&self, system: &str, prompt: &str, on_text: &OnText) -> Result<String, Error> {
+        let response = self
+            .http
+            .post(&ChatRequest::new(&self.model, system, prompt, true))
+            .await?;
+        let mut reply = String::new();
+        self.http
+            .for_each_line(response, |line| {
+                let Some(data) = event_data(line).filter(|data| *data != "[DONE]") else {
+                    return Ok(());
+                };
+                let chunk = serde_json::from_str::<ChatCompletionChunk>(data)
+                    .context("malformed chat completion chunk")?;
+                if let Some(content) = chunk
+                    .choices
+                    .into_iter()
+                    .next()
+                    .and_then(|choice| choice.delta.content)
+                {
+                    reply.push_str(&content);
+                    on_text(&reply);
+                }
+                Ok(())
+            })
+            .await?;
+        non_empty(&reply)
+    }
+}
+
+#[derive(Serialize)]
+struct MessagesRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    system: &'a str,
+    messages: [Message<'a>; 1],
+    stream: bool,
+}
+
+impl<'a> MessagesRequest<'a> {
+    fn new(model: &'a str, system: &'a str, prompt: &'a str, stream: bool) -> Self {
+        Self {
+            model,
+            max_tokens: MAX_REPLY_TOKENS,
+            system,
+            messages: [Message {
+                role: "user",
+                content: prompt,
+            }],
+            stream,
+        }
+    }
+}
+
+// the reply of anthropic's api
+#[derive(Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(default)]
+    text: String,
+}
+
+// a server-sent event of a streamed reply, the ones that aren't text or errors are skipped
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum MessagesEvent {
+    ContentBlockDelta {
+        delta: ContentBlock,
+    },
+    Error {
+        error: MessagesError,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct MessagesError {
+    message: String,
+}
+
+struct Anthropic {
+    http: Http,
+    model: String,
+}
+
+impl Anthropic {
+    fn new(url: &str, model: &str, api_key: &str) -> Result<Self, Error> {
+        Ok(Self {
+            http: Http::new(
+                url,
+                url.to_string(),
+                vec![
+                    ("x-api-key".to_string(), api_key.to_string()),
+                    (
+                        "anthropic-version".to_string(),
+                        ANTHROPIC_VERSION.to_string(),
+                    ),
+                ],
+            )?,
+            model: model.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for Anthropic {
+    async fn generate(&self, system: &str, prompt: &str) -> Result<String, Error> {
+        let body = self
+            .http
+            .post(&MessagesRequest::new(&self.model, system, prompt, false))
+            .await?
+            .text()
+            .await
+            .map_err(|source| self.http.unreachable(source))?;
+        let reply = serde_json::from_str::<MessagesResponse>(&body)
+            .context("malformed anthropic response")?
+            .content
+            .into_iter()
+            .map(|block| block.text)
+            .collect::<String>();
+        non_empty(&reply)
+    }
+
+    async fn stream(&self, system: &str, prompt: &str, on_text: &OnText) -> Result<String, Error> {
+        let response = self
+            .http
+            .post(&MessagesRequest::new(&self.model, system, prompt, true))
+            .await?;
+        let mut reply = String::new();
+        self.http
+            .for_each_line(response, |line| {
+                let Some(data) = event_data(line) else {
+                    return Ok(());
+                };
+                match serde_json::from_str::<MessagesEvent>(data)
+                    .context("malformed anthropic event")?
+                {
+                    MessagesEvent::ContentBlockDelta { delta } => {
+                        reply.push_str(&delta.text);
+                        on_text(&reply);
+                    }
+                    MessagesEvent::Error { error } => {
+                        return Err(Error::Failed {
+                            endpoint: self.http.endpoint.clone(),
+                            message: error.message,
+                        })
+                    }
+                    MessagesEvent::Other => {}
+                }
+                Ok(())
+            })
+            .await?;
+        non_empty(&reply)
+    }
+}
+
+// what the providers post with
+struct Http {
+    // what errors say was asked
+    endpoint: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    client: reqwest::Client,
+}
+
+impl Http {
+    fn new(endpoint: &str, url: String, headers: Vec<(String, String)>) -> Result<Self, Error> {
+        Ok(Self {
+            endpoint: endpoint.to_string(),
+            url,
+            headers,
+            client: reqwest::Client::builder()
+                .timeout(TIMEOUT)
+                .build()
+                .context("failed to build http client")?,
+        })
+    }
+
+    // a client of the model at `endpoint` on this machine, which isn't followed anywhere else
+    fn local(endpoint: &str, url: String) -> Result<Self, Error> {
+        if !is_loopback(endpoint)? {
+            return Err(Error::NotLocal(endpoint.to_string()));
+        }
+        Ok(Self {
+            endpoint: endpoint.to_string(),
+            url,
+            headers: vec![],
+            client: reqwest::Client::builder()
+                .timeout(TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .no_proxy()
+                .build()
+                .context("failed to build http client")?,
+        })
+    }
+
+    fn unreachable(&self, source: reqwest::Error) -> Error {
+        Error::Unreachable {
+            endpoint: self.endpoint.clone(),
+            source,
+        }
+    }
+
+    async fn post(&self, body: &(impl Serialize + Sync)) -> Result<reqwest::Response, Error> {
+        let mut request = self.client.post(&self.url).json(body);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|source| self.unreachable(source))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Rejected {
+                endpoint: self.endpoint.clone(),
+                status,
+                body: response
+                    .text()
+                    .await
+                    .map_err(|source| self.unreachable(source))?,
+            });
+        }
+        Ok(response)
+    }
+
+    // calls `on_line` with every line of `response` as it's received, trimmed
+    async fn for_each_line(
+        &self,
+        mut response: reqwest::Response,
+        mut on_line: impl FnMut(&str) -> Result<(), Error> + Send,
+    ) -> Result<(), Error> {
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|source| self.unreachable(source))?
+        {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                let line = buffer.drain(..=end).collect::<Vec<_>>();
+                on_line(String::from_utf8_lossy(&line).trim())?;
+            }
+        }
+        if !buffer.is_empty() {
+            on_line(String::from_utf8_lossy(&buffer).trim())?;
+        }
+        Ok(())
+    }
+}
+
+// the data of a line of server-sent events, the other lines have none
+fn event_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim_start)
+}
+
+fn non_empty(reply: &str) -> Result<String, Error> {
+    let reply = reply.trim();
+    if reply.is_empty() {
+        return Err(Error::EmptyReply);
+    }
+    Ok(reply.to_string())
+}
+
+// whether `endpoint` is on this machine, `localhost` or a loopback address
+fn is_loopback(endpoint: &str) -> Result<bool, Error> {
+    let url = reqwest::Url::parse(endpoint).map_err(|_| Error::InvalidUrl(endpoint.to_string()))?;
+    Ok(match url.host() {
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    })
+}
+
+// `/chat/completions` appended to the path of `base_url`, keeping its query, like the
+// `api-version` of Azure OpenAI
+fn chat_completions_url(base_url: &str) -> Result<String, Error> {
+    let mut url =
+        reqwest::Url::parse(base_url).map_err(|_| Error::InvalidUrl(base_url.to_string()))?;
+    url.path_segments_mut()
+        .map_err(|()| Error::InvalidUrl(base_url.to_string()))?
+        .pop_if_empty()
+        .extend(["chat", "completions"]);
+    Ok(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        task::JoinHandle,
+    };
+
+    use super::*;
+    use crate::settings::HttpHeader;
+
+    // a server on this machine answering one request with `status` and `chunks`, each sent
+    // on its own, that hands back the request it was sent
+    async fn serve(status: &str, chunks: Vec<&'static str>) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let head =
+            format!("HTTP/1.1 {status}\r\ntransfer-encoding: chunked\r\nconnection: close\r\n\r\n");
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut stream).await;
+            stream.write_all(head.as_bytes()).await.unwrap();
+            for chunk in chunks {
+                let chunk = format!("{:x}\r\n{chunk}\r\n", chunk.len());
+                stream.write_all(chunk.as_bytes()).await.unwrap();
+                stream.flush().await.unwrap();
+            }
+            stream.write_all(b"0\r\n\r\n").await.unwrap();
+            request
+        });
+        (url, server)
+    }
+
+    // the head and body of the request sent on `stream`
+    async fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap();
+            assert!(read > 0, "the request was cut short");
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                    .map_or(0, |(_, length)| length.trim().parse().unwrap());
+                if body.len() >= length {
+                    return text.to_ascii_lowercase();
+                }
+            }
+        }
+    }
+
+    // the reply of `provider` streamed, and the reply so far of every time it was told of
+    async fn stream(provider: &dyn Provider) -> (Result<String, Error>, Vec<String>) {
+        let texts = Mutex::new(Vec::new());
+        let reply = provider
+            .stream("be brief", "hello", &|text| {
+                texts.lock().unwrap().push(text.to_string());
+            })
+            .await;
+        (reply, texts.into_inner().unwrap())
+    }
+
+    fn ollama(url: &str) -> Box<dyn Provider> {
+        from_settings(&AiProvider::Local {
+            api: LocalModelApi::Ollama,
+            endpoint: url.to_string(),
+            model: "llama3".to_string(),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn ollama_generate() {
+        let (url, server) = serve(
+            "200 OK",
+            vec![r#"{"message":{"role":"assistant","content":" Add tests\n"},"done":true}"#],
+        )
+        .await;
+        let reply = ollama(&url).generate("be brief", "hello").await.unwrap();
+        assert_eq!(reply, "Add tests");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("post /api/chat "), "{request}");
+        assert!(request.contains(r#""model":"llama3""#), "{request}");
+        assert!(request.contains(r#""stream":false"#), "{request}");
+        assert!(
+            request.contains(r#"{"role":"system","content":"be brief"}"#),
+            "{request}"
+        );
+    }
+
+    #[tokio::test]
+    async fn ollama_stream_of_lines_split_across_chunks() {
+        let (url, server) = serve(
+            "200 OK",
+            vec![
+                "{\"message\":{\"content\":\"Add \"}}\n{\"mess",
+                "age\":{\"content\":\"tests\"}}\n\n",
+                // the last line doesn't have to end
+                r#"{"message":{"content":""},"done":true}"#,
+            ],
+        )
+        .await;
+        let (reply, texts) = stream(ollama(&url).as_ref()).await;
+        assert_eq!(reply.unwrap(), "Add tests");
+        assert_eq!(texts, ["Add ", "Add tests", "Add tests"]);
+        assert!(server.await.unwrap().contains(r#""stream":true"#));
+    }
+
+    #[tokio::test]
+    async fn open_ai_generate() {
+        let (url, server) = serve(
+            "200 OK",
+            vec![r#"{"choices":[{"message":{"role":"assistant","content":"Add tests"}}]}"#],
+        )
+        .await;
+        let provider = from_settings(&AiProvider::OpenAiCompatible {
+            base_url: format!("{url}/v1?api-version=1"),
+            model: "gpt-4".to_string(),
+            headers: vec![HttpHeader {
+                name: "Authorization".to_string(),
+                value: "Bearer secret".to_string(),
+            }],
+        })
+        .unwrap();
+        let reply = provider.generate("be brief", "hello").await.unwrap();
+        assert_eq!(reply, "Add tests");
+
+        let request = server.await.unwrap();
+        assert!(
+            request.starts_with("post /v1/chat/completions?api-version=1 "),
+            "{request}"
+        );
+        assert!(
+            request.contains("\r\nauthorization: bearer secret\r\n"),
+            "{request}"
+        );
+        assert!(request.contains(r#""model":"gpt-4""#), "{request}");
+    }
+
+    #[tokio::test]
+    async fn open_ai_stream() {
+        let (url, server) = serve(
+            "200 OK",
+            vec![
+                "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Add\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\" tests\"}}]}\n\n",
+                "data: [DONE]\n\n",
+            ],
+        )
+        .await;
+        let provider = from_settings(&AiProvider::Local {
+            api: LocalModelApi::OpenAiCompatible,
+            endpoint: url,
+            model: "qwen".to_string(),
+        })
+        .unwrap();
+        let (reply, texts) = stream(provider.as_ref()).await;
+        assert_eq!(reply.unwrap(), "Add tests");
+        assert_eq!(texts, ["Add", "Add tests"]);
+
+        let request = server.await.unwrap();
+        assert!(
+            request.starts_with("post /v1/chat/completions "),
+            "{request}"
+        );
+        assert!(request.contains(r#""stream":true"#), "{request}");
+    }
+
+    #[tokio::test]
+    async fn anthropic_generate() {
+        let (url, server) = serve(
+            "200 OK",
+            vec![r#"{"content":[{"type":"text","text":"Add "},{"type":"text","text":"tests"}]}"#],
+        )
+        .await;
+        let provider = Anthropic::new(&url, "claude-3-5-sonnet-latest", "key").unwrap();
+        let reply = provider.generate("be brief", "hello").await.unwrap();
+        assert_eq!(reply, "Add tests");
+
+        let request = server.await.unwrap();
+        assert!(request.contains("\r\nx-api-key: key\r\n"), "{request}");
+        assert!(
+            request.contains("\r\nanthropic-version: 2023-06-01\r\n"),
+            "{request}"
+        );
+        assert!(request.contains(r#""system":"be brief""#), "{request}");
+        assert!(request.contains(r#""max_tokens":2048"#), "{request}");
+    }
+
+    #[tokio::test]
+    async fn anthropic_stream() {
+        let (url, _server) = serve(
+            "200 OK",
+            vec![
+                "event: message_start\ndata: {\"type\":\"message_start\"}\n\n",
+                "event: ping\ndata: {\"type\":\"ping\"}\n\n",
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Add\"}}\n\n",
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\" tests\"}}\n\n",
+                "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+            ],
+        )
+        .await;
+        let provider = Anthropic::new(&url, "claude-3-5-sonnet-latest", "key").unwrap();
+        let (reply, texts) = stream(&provider).await;
+        assert_eq!(reply.unwrap(), "Add tests");
+        assert_eq!(texts, ["Add", "Add tests"]);
+    }
+
+    #[tokio::test]
+    async fn anthropic_stream_error() {
+        let (url, _server) = serve(
+            "200 OK",
+            vec![
+                "event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n",
+            ],
+        )
+        .await;
+        let provider = Anthropic::new(&url, "claude-3-5-sonnet-latest", "key").unwrap();
+        let (reply, _) = stream(&provider).await;
+        assert!(matches!(reply, Err(Error::Failed { message, .. }) if message == "Overloaded"));
+    }
+
+    #[tokio::test]
+    async fn rejected_requests() {
+        let (url, _server) = serve(
+            "401 Unauthorized",
+            vec![r#"{"type":"error","error":{"type":"authentication_error"}}"#],
+        )
+        .await;
+        let provider = Anthropic::new(&url, "claude-3-5-sonnet-latest", "key").unwrap();
+        let result = provider.generate("be brief", "hello").await;
+        assert!(matches!(
+            result,
+            Err(Error::Rejected { status, body, .. })
+                if status == reqwest::StatusCode::UNAUTHORIZED && body.contains("authentication_error")
+        ));
+    }
+
+    #[test]
+    fn chat_completions_are_under_the_base_url() {
+        assert_eq!(
+            chat_completions_url("https://api.openai.com/v1/").unwrap(),
+            "https://api.openai.com/v1/chat/completions"
+        );
+        assert_eq!(
+            chat_completions_url(
+                "https://x.openai.azure.com/openai/deployments/gpt?api-version=2024-02-01"
+            )
+            .unwrap(),
+            "https://x.openai.azure.com/openai/deployments/gpt/chat/completions?api-version=2024-02-01"
+        );
+    }
+
+    #[test]
+    fn local_models_are_on_this_machine() {
+        for endpoint in [
+            "http://localhost:11434",
+            "http://LOCALHOST:8080/",
+            "http://127.0.0.1:11434",
+            "http://127.1.2.3",
+            "http://[::1]:11434",
+        ] {
+            assert!(is_loopback(endpoint).unwrap(), "{endpoint}");
+        }
+        for endpoint in [
+            "http://192.168.1.10:11434",
+            "http://ollama.example.com",
+            "http://localhost.example.com",
+            "http://[::ffff:10.0.0.1]",
+        ] {
+            assert!(!is_loopback(endpoint).unwrap(), "{endpoint}");
+        }
+        // without a scheme, `localhost` is one
+        assert!(!is_loopback("localhost:11434").unwrap());
+
+        assert!(matches!(
+            from_settings(&AiProvider::Local {
+                api: LocalModelApi::Ollama,
+                endpoint: "http://192.168.1.10:11434".to_string(),
+                model: "llama3".to_string(),
+            }),
+            Err(Error::NotLocal(_))
+        ));
+    }
+
+    #[test]
+    fn anthropic_events() {
+        let delta = r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Add"}}"#;
+        assert!(matches!(
+            serde_json::from_str(delta).unwrap(),
+            MessagesEvent::ContentBlockDelta { delta } if delta.text == "Add"
+        ));
+        assert!(matches!(
+            serde_json::from_str(r#"{"type":"message_stop"}"#).unwrap(),
+            MessagesEvent::Other
+        ));
+        assert_eq!(event_data("data: {}"), Some("{}"));
+        assert_eq!(event_data("event: ping"), None);
+    }
+}
@@ -4,9 +4,9 @@ use anyhow::{Context, Result};
 use tauri::{AppHandle, Manager};
 
 use crate::{
-    gb_repository, git,
+    database, gb_repository, git,
     project_repository::{self, conflicts},
-    projects::{self, ProjectId},
+    projects::{self, ProjectId, Watchers as _},
     reader,
     sessions::{self, SessionId},
     users, watcher,
@@ -42,10 +42,12 @@ impl TryFrom<&AppHandle> for App {
             .context("failed to get app data dir")?;
         Ok(Self {
             local_data_dir: path,
-            projects: projects::Controller::try_from(value)?,
-            users: users::Controller::from(value),
+            projects: value.state::<projects::Controller>().inner().clone(),
+            users: value.state::<users::Controller>().inner().clone(),
             watchers: value.state::<watcher::Watchers>().inner().clone(),
-            sessions_database: sessions::Database::from(value),
+            sessions_database: sessions::Database::from(
+                value.state::<database::Database>().inner().clone(),
+            ),
         })
     }
 }
@@ -1,15 +1,21 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use anyhow::Context;
 use tauri::{generate_context, Manager, Wry};
 
 use gblib::{
-    analytics, app, assets, commands, database, deltas, github, keys, logs, menu, projects, sentry,
-    sessions, storage, users, virtual_branches, watcher, zip,
+    ai, analytics, app, assets, commands, crashes, database, deltas, events, git, github, keys,
+    logs, memory, menu, profiler, projects, sentry, sessions, settings, storage, users,
+    virtual_branches, watcher, zip,
 };
 use tauri_plugin_store::{with_store, JsonValue, StoreCollection};
 
+// how many events the windows can fall behind on before they start missing them
+const EVENTS_CAPACITY: usize = 1024;
+
 fn main() {
+    gitbutler_git::git2::enable_extensions().expect("failed to enable git extensions");
+
     let tauri_context = generate_context!();
 
     let app_name = tauri_context.package_info().name.clone();
@@ -83,69 +89,126 @@ fn main() {
 
                     tracing::info!(version = %app_handle.package_info().version, name = %app_handle.package_info().name, "starting app");
 
-                    let watchers = watcher::Watchers::try_from(&app_handle)
-                        .expect("failed to initialize watchers");
-                    tauri_app.manage(watchers);
+                    let app_data_dir = app_handle
+                        .path_resolver()
+                        .app_data_dir()
+                        .expect("failed to get app data dir");
+                    gitbutler_core::fs::set_case_probes_dir(&app_data_dir);
+
+                    let crashes = crashes::Crashes::from(&app_data_dir);
+                    crashes.capture_panics(&app_version);
+                    tauri_app.manage(crashes);
 
-                    let proxy =
-                        assets::Proxy::try_from(&app_handle).expect("failed to initialize proxy");
+                    let events = events::Bus::new(EVENTS_CAPACITY);
+                    events::emit_to_windows(&events, &app_handle);
+                    tauri_app.manage(events.clone());
+
+                    let watchers = watcher::Watchers::new({
+                        let app_handle = app_handle.clone();
+                        move || watcher_services(&app_handle)
+                    });
+                    tauri_app.manage(watchers.clone());
+
+                    let app_cache_dir = app_handle
+                        .path_resolver()
+                        .app_cache_dir()
+                        .expect("failed to get app cache dir");
+                    std::fs::create_dir_all(&app_cache_dir).expect("failed to create cache dir");
+                    let proxy = assets::Proxy::from(&app_cache_dir.join("images"));
                     tauri_app.manage(proxy);
 
-                    let database = database::Database::try_from(&app_handle)
+                    tauri_app.manage(ai::Streams::default());
+
+                    let database = database::Database::try_from(&app_data_dir)
                         .expect("failed to initialize database");
-                    app_handle.manage(database);
+                    app_handle.manage(database.clone());
 
-                    let storage = storage::Storage::try_from(&app_handle)
-                        .expect("failed to initialize storage");
-                    app_handle.manage(storage);
+                    let storage = storage::Storage::from(&app_data_dir);
+                    app_handle.manage(storage.clone());
 
-                    let zipper = zip::Controller::try_from(&app_handle)
-                        .expect("failed to initialize zipc controller ");
-                    tauri_app.manage(zipper);
+                    let users_controller = users::Controller::from(&storage);
+
+                    let settings_controller = settings::Controller::from(&app_data_dir);
+                    // pick up what was changed on the other installations while this one was off
+                    if let Ok(Some(_)) = settings_controller.sync_repository() {
+                        let settings_controller = settings_controller.clone();
+                        tokio::task::spawn_blocking(move || {
+                            if let Err(error) = settings_controller.sync() {
+                                tracing::warn!(?error, "failed to sync app settings");
+                            }
+                        });
+                    }
+                    app_handle.manage(settings_controller);
+
+                    let keys_controller = keys::Controller::from(&storage);
+                    app_handle.manage(keys_controller.clone());
+
+                    let projects_controller = projects::Controller::new(
+                        &app_data_dir,
+                        &storage,
+                        &users_controller,
+                        Some(Arc::new(watchers)),
+                    );
+                    app_handle.manage(projects_controller.clone());
 
-                    let deltas_controller = deltas::Controller::from(&app_handle);
+                    let deltas_controller =
+                        deltas::Controller::new(&deltas::Database::from(database.clone()));
                     app_handle.manage(deltas_controller);
 
-                    let sessions_controller = sessions::Controller::try_from(&app_handle)
-                        .expect("failed to initialize sessions controller");
+                    let sessions_controller = sessions::Controller::new(
+                        &app_data_dir,
+                        &sessions::Database::from(database),
+                        &projects_controller,
+                        &users_controller,
+                    );
                     app_handle.manage(sessions_controller);
 
-                    let projects_controller = projects::Controller::try_from(&app_handle)
-                        .expect("failed to initialize projects controller");
-                    app_handle.manage(projects_controller);
-
-                    let vbranch_contoller =
-                        virtual_branches::controller::Controller::try_from(&app_handle)
-                            .expect("failed to initialize virtual branches controller");
+                    let vbranch_contoller = virtual_branches::controller::Controller::new(
+                        &app_data_dir,
+                        &projects::Controller::from(&app_data_dir),
+                        &users_controller,
+                        &keys_controller,
+                        &git::credentials::Helper::from(&app_data_dir),
+                    )
+                    .with_events(&events);
                     app_handle.manage(vbranch_contoller);
 
-                    let keys_controller = keys::Controller::from(&app_handle);
-                    app_handle.manage(keys_controller);
-
-                    let users_controller = users::Controller::from(&app_handle);
+                    let zipper = zip::Controller::try_from(&app_handle)
+                        .expect("failed to initialize zipc controller ");
+                    tauri_app.manage(zipper);
 
                     let stores = tauri_app.state::<StoreCollection<Wry>>();
                     if let Some(path) = app_handle.path_resolver().app_config_dir().map(|path| path.join(PathBuf::from("settings.json"))) {
-                        if let Ok((metrics_enabled, error_reporting_enabled)) = with_store(app_handle.clone(), stores, path, |store| {
+                        if let Ok((metrics_enabled, error_reporting_enabled, cache_budgets)) = with_store(app_handle.clone(), stores, path, |store| {
                             let metrics_enabled = store.get("appMetricsEnabled")
                                 .and_then(JsonValue::as_bool)
                                 .unwrap_or(true);
                             let error_reporting_enabled = store.get("appErrorReportingEnabled")
                                 .and_then(JsonValue::as_bool)
                                 .unwrap_or(true);
-                            Ok((metrics_enabled, error_reporting_enabled))
+                            let cache_budgets = store.get("appCacheBudgets")
+                                .cloned()
+                                .and_then(|value| serde_json::from_value::<memory::Budgets>(value).ok())
+                                .unwrap_or_default();
+                            Ok((metrics_enabled, error_reporting_enabled, cache_budgets))
                         }) {
+                            memory::set_budgets(cache_budgets);
+
                             if metrics_enabled {
                                 let analytics_cfg = if cfg!(debug_assertions) {
                                     analytics::Config {
                                         posthog_token: Some("phc_t7VDC9pQELnYep9IiDTxrq2HLseY5wyT7pn0EpHM7rr"),
+                                        app_name: &app_name,
+                                        app_version: &app_version,
                                     }
                                 } else {
                                     analytics::Config {
                                         posthog_token: Some("phc_yJx46mXv6kA5KTuM2eEQ6IwNTgl5YW3feKV5gi7mfGG"),
+                                        app_name: &app_name,
+                                        app_version: &app_version,
                                     }
                                 };
-                                let analytics_client = analytics::Client::new(&app_handle, &analytics_cfg);
+                                let analytics_client = analytics::Client::new(&analytics_cfg);
                                 tauri_app.manage(analytics_client);
                             }
 
@@ -181,23 +244,63 @@ fn main() {
                     commands::git_get_global_config,
                     commands::project_flush_and_push,
                     zip::commands::get_logs_archive_path,
+                    logs::commands::get_log_filter,
+                    logs::commands::set_log_filter,
+                    crashes::commands::list_crash_reports,
+                    crashes::commands::send_crash_report,
+                    crashes::commands::dismiss_crash_report,
+                    profiler::commands::start_profiling,
+                    profiler::commands::stop_profiling,
                     zip::commands::get_project_archive_path,
                     zip::commands::get_project_data_archive_path,
                     users::commands::set_user,
                     users::commands::delete_user,
                     users::commands::get_user,
+                    settings::commands::get_app_settings,
+                    settings::commands::update_app_settings,
+                    settings::commands::get_settings_sync_repository,
+                    settings::commands::set_settings_sync_repository,
+                    settings::commands::get_settings_sync_with_cloud,
+                    settings::commands::set_settings_sync_with_cloud,
+                    settings::commands::merge_cloud_app_settings,
+                    settings::commands::sync_app_settings,
                     projects::commands::add_project,
                     projects::commands::get_project,
                     projects::commands::update_project,
+                    projects::commands::update_project_settings,
                     projects::commands::delete_project,
+                    projects::commands::clone_repository,
+                    projects::commands::list_active_projects,
+                    projects::commands::activate_project,
+                    projects::commands::deactivate_project,
                     projects::commands::list_projects,
+                    projects::commands::list_project_groups,
+                    projects::commands::reorder_projects,
+                    projects::commands::add_project_group,
+                    projects::commands::rename_project_group,
+                    projects::commands::reorder_project_groups,
+                    projects::commands::delete_project_group,
+                    projects::commands::set_project_group,
+                    projects::commands::set_project_ai_provider,
+                    projects::commands::list_commit_message_profiles,
+                    projects::commands::locate_project,
+                    projects::commands::relink_project,
                     sessions::commands::list_sessions,
                     deltas::commands::list_deltas,
                     virtual_branches::commands::list_virtual_branches,
+                    virtual_branches::commands::stream_virtual_branches,
                     virtual_branches::commands::create_virtual_branch,
                     virtual_branches::commands::commit_virtual_branch,
                     virtual_branches::commands::get_base_branch_data,
+                    virtual_branches::commands::get_project_state,
+                    virtual_branches::commands::list_operations,
+                    virtual_branches::commands::cancel_operation,
+                    virtual_branches::commands::get_external_operation,
+                    virtual_branches::commands::finish_external_operation,
+                    virtual_branches::commands::get_workspace_branch,
+                    virtual_branches::commands::set_workspace_branch,
                     virtual_branches::commands::set_base_branch,
+                    virtual_branches::commands::apply_project_preset,
                     virtual_branches::commands::update_base_branch,
                     virtual_branches::commands::merge_virtual_branch_upstream,
                     virtual_branches::commands::update_virtual_branch,
@@ -206,10 +309,47 @@ fn main() {
                     virtual_branches::commands::unapply_branch,
                     virtual_branches::commands::unapply_ownership,
                     virtual_branches::commands::push_virtual_branch,
+                    virtual_branches::commands::check_push,
+                    virtual_branches::commands::scan_secrets,
+                    virtual_branches::commands::list_large_files,
+                    virtual_branches::commands::list_external_changes,
+                    virtual_branches::commands::reconcile_external_change,
+                    virtual_branches::commands::recover_workspace,
+                    virtual_branches::commands::list_trash,
+                    virtual_branches::commands::restore_from_trash,
+                    virtual_branches::commands::purge_trash,
+                    virtual_branches::commands::list_outbox,
+                    virtual_branches::commands::list_audit_entries,
+                    virtual_branches::commands::queue_push,
+                    virtual_branches::commands::queue_pull_request,
+                    virtual_branches::commands::replay_outbox,
+                    virtual_branches::commands::remove_outbox_entry,
                     virtual_branches::commands::create_virtual_branch_from_branch,
+                    virtual_branches::commands::list_lost_commits,
+                    virtual_branches::commands::recover_commit,
+                    virtual_branches::commands::export_bundle,
+                    virtual_branches::commands::import_bundle,
+                    virtual_branches::commands::export_virtual_branches,
+                    virtual_branches::commands::import_virtual_branches,
+                    virtual_branches::commands::repair_project,
                     virtual_branches::commands::can_apply_virtual_branch,
                     virtual_branches::commands::can_apply_remote_branch,
                     virtual_branches::commands::list_remote_commit_files,
+                    virtual_branches::commands::hunk_line_changes,
+                    virtual_branches::commands::blame,
+                    virtual_branches::commands::list_line_endings,
+                    virtual_branches::commands::normalize_line_endings,
+                    virtual_branches::commands::create_tag,
+                    virtual_branches::commands::push_tags,
+                    virtual_branches::commands::list_branch_tags,
+                    virtual_branches::commands::list_tags,
+                    virtual_branches::commands::delete_tag,
+                    virtual_branches::commands::list_commit_notes,
+                    virtual_branches::commands::set_commit_note,
+                    virtual_branches::commands::push_notes,
+                    virtual_branches::commands::fetch_notes,
+                    virtual_branches::commands::push_branch_metadata,
+                    virtual_branches::commands::fetch_branch_metadata,
                     virtual_branches::commands::reset_virtual_branch,
                     virtual_branches::commands::cherry_pick_onto_virtual_branch,
                     virtual_branches::commands::amend_virtual_branch,
@@ -217,8 +357,18 @@ fn main() {
                     virtual_branches::commands::get_remote_branch_data,
                     virtual_branches::commands::squash_branch_commit,
                     virtual_branches::commands::fetch_from_target,
+                    virtual_branches::commands::deepen_history,
                     menu::menu_item_set_enabled,
                     keys::commands::get_public_key,
+                    ai::commands::generate_commit_message,
+                    ai::commands::generate_branch_name,
+                    ai::commands::suggest_branch_names,
+                    ai::commands::generate_pr_content,
+                    ai::commands::list_prompt_templates,
+                    ai::commands::list_redactions,
+                    ai::commands::redact_diff,
+                    ai::commands::cancel_generation,
+                    ai::commands::check_ai_provider,
                     github::commands::init_device_oauth,
                     github::commands::check_auth_status,
                 ])
@@ -248,6 +398,30 @@ fn main() {
         });
 }
 
+// what the watchers run with, resolved from the state of the app once it's set up
+fn watcher_services(handle: &tauri::AppHandle) -> anyhow::Result<watcher::Services> {
+    Ok(watcher::Services {
+        local_data_dir: handle
+            .path_resolver()
+            .app_data_dir()
+            .context("failed to get app data dir")?,
+        database: handle.state::<database::Database>().inner().clone(),
+        projects: handle.state::<projects::Controller>().inner().clone(),
+        users: handle.state::<users::Controller>().inner().clone(),
+        settings: handle.state::<settings::Controller>().inner().clone(),
+        vbranches: handle
+            .state::<virtual_branches::Controller>()
+            .inner()
+            .clone(),
+        assets_proxy: handle.state::<assets::Proxy>().inner().clone(),
+        // only managed when metrics are enabled
+        analytics: handle
+            .try_state::<analytics::Client>()
+            .map_or_else(analytics::Client::default, |client| client.inner().clone()),
+        events: handle.state::<events::Bus>().inner().clone(),
+    })
+}
+
 fn get_window(handle: &tauri::AppHandle) -> Option<tauri::Window> {
     handle.get_window("main")
 }
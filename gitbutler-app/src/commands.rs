@@ -20,7 +20,7 @@ impl From<app::Error> for Error {
             app::Error::OpenProjectRepository(error) => Error::from(error),
             app::Error::Other(error) => {
                 tracing::error!(?error);
-                Error::Unknown
+                Error::caused_by(&error)
             }
         }
     }
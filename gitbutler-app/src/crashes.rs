@@ -0,0 +1,451 @@
+//! What the app was doing when it panicked, kept for the user to send to us or not.
+//!
+//! Reports are stored as they are captured, on this machine only. Nothing is sent unless the
+//! user says so for that crash, and what is sent is scrubbed first: paths and the contents of
+//! strings, which can be those of files, are left out unless the user allows them.
+pub mod commands;
+
+use std::{
+    fs, panic, path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub id: String,
+    /// Milliseconds since the epoch.
+    pub created_at: u128,
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    pub thread: Option<String>,
+    pub message: String,
+    /// Where in the code it panicked, like `gitbutler-core/src/lib.rs:10:5`.
+    pub location: Option<String>,
+    pub backtrace: String,
+}
+
+/// What a report that is sent can keep.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Allow {
+    pub paths: bool,
+    pub contents: bool,
+}
+
+impl Report {
+    /// The report, with what isn't allowed scrubbed out of it.
+    pub fn scrubbed(&self, allow: Allow) -> Self {
+        Self {
+            thread: self.thread.as_deref().map(|thread| scrub(thread, allow)),
+            message: scrub(&self.message, allow),
+            location: self
+                .location
+                .as_deref()
+                .map(|location| scrub(location, allow)),
+            backtrace: scrub(&self.backtrace, allow),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GetError {
+    #[error("crash report not found")]
+    NotFound,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// The crash reports that haven't been sent or dismissed yet.
+#[derive(Clone)]
+pub struct Crashes {
+    dir: path::PathBuf,
+}
+
+impl From<&path::PathBuf> for Crashes {
+    fn from(value: &path::PathBuf) -> Self {
+        Self {
+            dir: value.join("crashes"),
+        }
+    }
+}
+
+impl Crashes {
+    /// Stores a report of every panic from now on, before the panic goes on as it would have.
+    pub fn capture_panics(&self, version: &str) {
+        let crashes = self.clone();
+        let version = version.to_string();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if let Err(error) = crashes.save(&capture(info, &version)) {
+                tracing::error!(?error, "failed to save crash report");
+            }
+            previous(info);
+        }));
+    }
+
+    /// The reports, the most recent first.
+    pub fn list(&self) -> Result<Vec<Report>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut reports = vec![];
+        for entry in fs::read_dir(&self.dir).context("failed to read crash reports")? {
+            let path = entry.context("failed to read crash report entry")?.path();
+            if path
+                .extension()
+                .map_or(true, |extension| extension != "json")
+            {
+                continue;
+            }
+            match fs::read(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| serde_json::from_slice::<Report>(&bytes).map_err(Into::into))
+            {
+                Ok(report) => reports.push(report),
+                Err(error) => {
+                    tracing::warn!(?error, path = %path.display(), "skipping crash report");
+                }
+            }
+        }
+        reports.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(reports)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Report, GetError> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            return Err(GetError::NotFound);
+        }
+        let bytes = fs::read(&path).context("failed to read crash report")?;
+        Ok(serde_json::from_slice(&bytes).context("failed to parse crash report")?)
+    }
+
+    /// Forgets the report `id`, once it has been sent or the user doesn't want to.
+    pub fn dismiss(&self, id: &str) -> Result<(), GetError> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            return Err(GetError::NotFound);
+        }
+        fs::remove_file(&path).context("failed to remove crash report")?;
+        Ok(())
+    }
+
+    fn save(&self, report: &Report) -> Result<()> {
+        fs::create_dir_all(&self.dir).context("failed to create crash reports dir")?;
+        let path = self.path(&report.id)?;
+        fs::write(path, serde_json::to_vec_pretty(report)?).context("failed to write crash report")
+    }
+
+    // ids are made by `capture`, anything else could point outside of the reports
+    fn path(&self, id: &str) -> Result<path::PathBuf, GetError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(GetError::NotFound);
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+// how many panics were captured so far
+static CAPTURED: AtomicUsize = AtomicUsize::new(0);
+
+fn capture(info: &panic::PanicInfo, version: &str) -> Report {
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis());
+    let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = info.payload().downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    };
+    Report {
+        // unique even for panics of the same millisecond
+        id: format!(
+            "{}-{}-{}",
+            created_at,
+            std::process::id(),
+            CAPTURED.fetch_add(1, Ordering::Relaxed)
+        ),
+        created_at,
+        version: version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        thread: std::thread::current().name().map(ToString::to_string),
+        message,
+        location: info.location().map(|location| {
+            format!(
+                "{}:{}:{}",
+                location.file(),
+                location.line(),
+                location.column()
+            )
+        }),
+        backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+    }
+}
+
+fn scrub(text: &str, allow: Allow) -> String {
+    let text = if allow.contents {
+        text.to_string()
+    } else {
+        scrub_strings(text)
+    };
+    if allow.paths {
+        text
+    } else {
+        scrub_paths(&text)
+    }
+}
+
+// replaces what is quoted, in double quotes, single quotes or backticks, like the contents of
+// a file in the debug output of an error. A single quote only quotes at the start of a word,
+// not as the apostrophe of `don't`.
+fn scrub_strings(text: &str) -> String {
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    let mut previous = None;
+    while let Some(c) = chars.next() {
+        scrubbed.push(c);
+        let quotes = match c {
+            '"' | '`' => true,
+            '\'' => previous.map_or(true, |previous: char| !previous.is_alphanumeric()),
+            _ => false,
+        };
+        previous = Some(c);
+        if !quotes {
+            continue;
+        }
+        let mut escaped = false;
+        for next in chars.by_ref() {
+            match next {
+                '\\' if !escaped => escaped = true,
+                next if next == c && !escaped => break,
+                _ => escaped = false,
+            }
+        }
+        scrubbed.push_str("<redacted>");
+        scrubbed.push(c);
+    }
+    scrubbed
+}
+
+// replaces paths, absolute or relative, any word with a `/` in it, and file names. A path
+// goes on past a space when what follows has a separator too, like in
+// `C:\Users\Jane Doe\notes.txt`. Those of the source code the app is built from are kept
+// from the crate on, like `gitbutler-core/src/lib.rs:10:5`, they say where the panic
+// happened and nothing about the user.
+fn scrub_paths(text: &str) -> String {
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() || PATH_ENDS.contains(&c) {
+            scrubbed.push(c);
+            rest = rest.get(c.len_utf8()..).unwrap_or_default();
+            continue;
+        }
+        let word = word_at(rest);
+        if !is_path(word) {
+            scrubbed.push_str(word);
+            rest = rest.get(word.len()..).unwrap_or_default();
+            continue;
+        }
+        let (path, after) = rest.split_at(path_len(rest, word.len()));
+        // like the colon or period of the sentence the path is in
+        let (path, punctuation) = path.split_at(path.trim_end_matches([':', '.']).len());
+        scrubbed.push_str(source_path(path).unwrap_or("<path>"));
+        scrubbed.push_str(punctuation);
+        rest = after;
+    }
+    scrubbed
+}
+
+// what paths end at, besides whitespace
+const PATH_ENDS: &[char] = &[
+    '"', '\'', '`', '(', ')', '[', ']', '{', '}', '<', '>', ',', ';',
+];
+
+// the word `text` starts with
+fn word_at(text: &str) -> &str {
+    let end = text
+        .find(|c: char| c.is_whitespace() || PATH_ENDS.contains(&c))
+        .unwrap_or(text.len());
+    text.get(..end).unwrap_or_default()
+}
+
+// the length of the path `text` starts with, its first word and the words after spaces that
+// have a separator in them
+fn path_len(text: &str, first_word: usize) -> usize {
+    let mut len = first_word;
+    loop {
+        let after = text.get(len..).unwrap_or_default();
+        let spaces = after.len() - after.trim_start_matches(' ').len();
+        if spaces == 0 {
+            return len;
+        }
+        let next = word_at(after.get(spaces..).unwrap_or_default());
+        if !next.contains(['/', '\\']) {
+            return len;
+        }
+        len += spaces + next.len();
+    }
+}
+
+// a word with a `/` in it, a windows path like `\\server` or `C:\`, or a file name. Other
+// backslashes are those of escapes more often than not.
+fn is_path(word: &str) -> bool {
+    let mut chars = word.chars();
+    let drive = matches!(
+        (chars.next(), chars.next(), chars.next()),
+        (Some(letter), Some(':'), Some('\\')) if letter.is_ascii_alphabetic()
+    );
+    word.contains('/') || word.starts_with("\\\\") || drive || is_file_name(word)
+}
+
+// a file name like `.env` or `notes.txt:3`, but not a version like `0.32` nor `e.g.`
+fn is_file_name(word: &str) -> bool {
+    let name = word
+        .split(':')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('.');
+    let Some((stem, extension)) = name.rsplit_once('.') else {
+        return false;
+    };
+    let is_name = |part: &str| {
+        part.chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    !extension.is_empty()
+        && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        && extension.chars().any(|c| c.is_ascii_alphabetic())
+        && is_name(stem)
+        && (stem.is_empty() || stem.len() > 1 || extension.len() > 1)
+}
+
+// the part of a path to a rust source file from its crate on
+fn source_path(path: &str) -> Option<&str> {
+    let normalized = path.replace('\\', "/");
+    let (before, file) = normalized.split_at(normalized.rfind("/src/")?);
+    if !file.split(':').next()?.ends_with(".rs") {
+        return None;
+    }
+    // the separators are one byte either way, the offsets are those of `path`
+    let crate_start = before.rfind('/').map_or(0, |i| i + 1);
+    path.get(crate_start..)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_and_paths_are_scrubbed() {
+        let message = r#"failed to read "secret \"token\"" from /Users/jane/project/.env"#;
+        assert_eq!(
+            scrub(message, Allow::default()),
+            r#"failed to read "<redacted>" from <path>"#
+        );
+        assert_eq!(
+            scrub(
+                message,
+                Allow {
+                    paths: true,
+                    contents: false
+                }
+            ),
+            r#"failed to read "<redacted>" from /Users/jane/project/.env"#
+        );
+        assert_eq!(
+            scrub(
+                message,
+                Allow {
+                    paths: false,
+                    contents: true
+                }
+            ),
+            r#"failed to read "secret \"token\"" from <path>"#
+        );
+    }
+
+    #[test]
+    fn source_paths_are_kept_from_the_crate() {
+        let backtrace = "\
+   3: gitbutler_core::run
+             at /home/builder/gitbutler/gitbutler-core/src/lib.rs:10:5
+   4: gitbutler_app::main
+             at C:\\Users\\builder\\gitbutler-app\\src\\bin.rs:1:1
+";
+        assert_eq!(
+            scrub(backtrace, Allow::default()),
+            "\
+   3: gitbutler_core::run
+             at gitbutler-core/src/lib.rs:10:5
+   4: gitbutler_app::main
+             at gitbutler-app\\src\\bin.rs:1:1
+"
+        );
+        assert_eq!(
+            scrub_paths("(C:\\Users\\jane\\notes.txt) ~/.ssh/id_rsa, \\\\server\\share"),
+            "(<path>) <path>, <path>"
+        );
+        assert_eq!(
+            scrub_paths("failed to open projects/acme/.env or https://gitbutler.com/jane"),
+            "failed to open <path> or <path>"
+        );
+        assert_eq!(
+            scrub_paths("failed to read C:\\Users\\Jane Doe\\notes.txt: denied"),
+            "failed to read <path>: denied"
+        );
+        assert_eq!(
+            scrub_paths("at /home/jane/my projects/acme/notes.md again"),
+            "at <path> again"
+        );
+        assert_eq!(
+            scrub_paths("no .env or notes.txt:3 in gitbutler 0.32, e.g. here."),
+            "no <path> or <path> in gitbutler 0.32, e.g. here."
+        );
+    }
+
+    #[test]
+    fn single_quotes_and_backticks_are_scrubbed() {
+        assert_eq!(
+            scrub_strings("can't find 'acme-secrets' in `config.toml`"),
+            "can't find '<redacted>' in `<redacted>`"
+        );
+    }
+
+    #[test]
+    fn reports_are_stored_until_dismissed() {
+        let dir = tempfile::tempdir().unwrap();
+        let crashes = Crashes::from(&dir.path().to_path_buf());
+        assert!(crashes.list().unwrap().is_empty());
+
+        let report = Report {
+            id: "1-2".to_string(),
+            created_at: 1,
+            version: "0.0.0".to_string(),
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            thread: None,
+            message: "boom".to_string(),
+            location: None,
+            backtrace: String::new(),
+        };
+        crashes.save(&report).unwrap();
+        assert_eq!(crashes.list().unwrap(), vec![report.clone()]);
+        assert_eq!(crashes.get("1-2").unwrap(), report);
+
+        crashes.dismiss("1-2").unwrap();
+        assert!(crashes.list().unwrap().is_empty());
+        assert!(matches!(crashes.get("1-2"), Err(GetError::NotFound)));
+        assert!(matches!(crashes.get("../1-2"), Err(GetError::NotFound)));
+    }
+}
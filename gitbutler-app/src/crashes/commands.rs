@@ -0,0 +1,66 @@
+use anyhow::Context;
+use tauri::{AppHandle, Manager};
+use tracing::instrument;
+
+use crate::{
+    error::{Code, Error},
+    sentry,
+};
+
+use super::{Allow, Crashes, GetError, Report};
+
+impl From<GetError> for Error {
+    fn from(value: GetError) -> Self {
+        match value {
+            GetError::NotFound => Error::UserError {
+                code: Code::Validation,
+                message: "Crash report not found".to_string(),
+            },
+            GetError::Other(error) => {
+                tracing::error!(?error, "failed to get crash report");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+/// The crash reports waiting for the user to send or dismiss them, as they would be sent with
+/// what `allow` says.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_crash_reports(
+    handle: AppHandle,
+    allow: Option<Allow>,
+) -> Result<Vec<Report>, Error> {
+    let allow = allow.unwrap_or_default();
+    let reports = handle.state::<Crashes>().list().map_err(GetError::Other)?;
+    Ok(reports
+        .iter()
+        .map(|report| report.scrubbed(allow))
+        .collect())
+}
+
+/// Sends the crash report `id` with what `allow` says, once the user agreed to, and forgets
+/// it.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn send_crash_report(handle: AppHandle, id: &str, allow: Allow) -> Result<(), Error> {
+    let crashes = handle.state::<Crashes>().inner().clone();
+    let report = crashes.get(id)?.scrubbed(allow);
+    let name = handle.package_info().name.clone();
+    tokio::task::spawn_blocking(move || sentry::send_crash_report(&name, &report))
+        .await
+        .context("failed to join sending crash report")
+        .and_then(|result| result)
+        .map_err(|error| {
+            tracing::error!(?error, "failed to send crash report");
+            Error::caused_by(&error)
+        })?;
+    crashes.dismiss(id).map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn dismiss_crash_report(handle: AppHandle, id: &str) -> Result<(), Error> {
+    handle.state::<Crashes>().dismiss(id).map_err(Into::into)
+}
@@ -5,18 +5,7 @@ use tracing::instrument;
 
 use crate::error::{Code, Error};
 
-use super::{controller::ListError, Controller, Delta};
-
-impl From<ListError> for Error {
-    fn from(value: ListError) -> Self {
-        match value {
-            ListError::Other(error) => {
-                tracing::error!(?error);
-                Error::Unknown
-            }
-        }
-    }
-}
+use super::{Controller, Delta};
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
@@ -1,133 +1,15 @@
-use anyhow::{Context, Result};
-use tauri::{AppHandle, Manager};
-
-use crate::{
-    deltas,
-    projects::ProjectId,
-    reader,
-    sessions::{self, SessionId},
-    virtual_branches,
-};
-
-#[derive(Clone)]
-pub struct Sender {
-    app_handle: tauri::AppHandle,
-}
-
-impl From<&AppHandle> for Sender {
-    fn from(value: &AppHandle) -> Self {
-        Self {
-            app_handle: value.clone(),
-        }
-    }
-}
+pub use gitbutler_core::events::*;
 
-impl Sender {
-    pub fn send(&self, event: &Event) -> Result<()> {
-        self.app_handle
-            .emit_all(&event.name, Some(&event.payload))
-            .context("emit event")?;
-        tracing::debug!(event_name = event.name, "sent event");
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Event {
-    name: String,
-    payload: serde_json::Value,
-    project_id: ProjectId,
-}
-
-impl Event {
-    pub fn name(&self) -> &str {
-        &self.name
-    }
-
-    pub fn project_id(&self) -> &ProjectId {
-        &self.project_id
-    }
-
-    pub fn git_index(project_id: &ProjectId) -> Self {
-        Event {
-            name: format!("project://{}/git/index", project_id),
-            payload: serde_json::json!({}),
-            project_id: *project_id,
-        }
-    }
-
-    pub fn git_fetch(project_id: &ProjectId) -> Self {
-        Event {
-            name: format!("project://{}/git/fetch", project_id),
-            payload: serde_json::json!({}),
-            project_id: *project_id,
-        }
-    }
-
-    pub fn git_head(project_id: &ProjectId, head: &str) -> Self {
-        Event {
-            name: format!("project://{}/git/head", project_id),
-            payload: serde_json::json!({ "head": head }),
-            project_id: *project_id,
-        }
-    }
-
-    pub fn git_activity(project_id: &ProjectId) -> Self {
-        Event {
-            name: format!("project://{}/git/activity", project_id),
-            payload: serde_json::json!({}),
-            project_id: *project_id,
-        }
-    }
-
-    pub fn file(
-        project_id: &ProjectId,
-        session_id: &SessionId,
-        file_path: &str,
-        contents: Option<&reader::Content>,
-    ) -> Self {
-        Event {
-            name: format!("project://{}/sessions/{}/files", project_id, session_id),
-            payload: serde_json::json!({
-                "filePath": file_path,
-                "contents": contents,
-            }),
-            project_id: *project_id,
-        }
-    }
-
-    pub fn session(project_id: &ProjectId, session: &sessions::Session) -> Self {
-        Event {
-            name: format!("project://{}/sessions", project_id),
-            payload: serde_json::to_value(session).unwrap(),
-            project_id: *project_id,
-        }
-    }
-
-    pub fn deltas(
-        project_id: &ProjectId,
-        session_id: &SessionId,
-        deltas: &Vec<deltas::Delta>,
-        relative_file_path: &std::path::Path,
-    ) -> Self {
-        Event {
-            name: format!("project://{}/sessions/{}/deltas", project_id, session_id),
-            payload: serde_json::json!({
-                "deltas": deltas,
-                "filePath": relative_file_path,
-            }),
-            project_id: *project_id,
-        }
-    }
+use tauri::{AppHandle, Manager};
 
-    pub fn virtual_branches(
-        project_id: &ProjectId,
-        virtual_branches: &Vec<virtual_branches::VirtualBranch>,
-    ) -> Self {
-        Event {
-            name: format!("project://{}/virtual-branches", project_id),
-            payload: serde_json::json!(virtual_branches),
-            project_id: *project_id,
+/// Emits the events published on `bus` to the windows of the app, under the names the
+/// frontend listens for. They are emitted as they are published rather than from a channel,
+/// which a burst of events could make the windows miss some of, and stay stale without.
+pub fn emit_to_windows(bus: &Bus, app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    bus.on_publish(move |event| {
+        if let Err(error) = app_handle.emit_all(&event.name(), Some(event.payload())) {
+            tracing::error!(?error, event_name = %event.name(), "failed to emit event");
         }
-    }
+    });
 }
@@ -1,6 +0,0 @@
-mod repository;
-
-#[cfg(test)]
-mod repository_tests;
-
-pub use repository::{Error, RemoteError, Repository};
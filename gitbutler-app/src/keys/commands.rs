@@ -3,24 +3,13 @@ use tracing::instrument;
 
 use crate::error::Error;
 
-use super::{controller, PublicKey};
-
-impl From<controller::GetOrCreateError> for Error {
-    fn from(value: controller::GetOrCreateError) -> Self {
-        match value {
-            controller::GetOrCreateError::Other(error) => {
-                tracing::error!(?error, "failed to get or create key");
-                Error::Unknown
-            }
-        }
-    }
-}
+use super::{Controller, PublicKey};
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
 pub async fn get_public_key(handle: tauri::AppHandle) -> Result<PublicKey, Error> {
     handle
-        .state::<controller::Controller>()
+        .state::<Controller>()
         .get_or_create()
         .map(|key| key.public_key())
         .map_err(Into::into)
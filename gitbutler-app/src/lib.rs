@@ -1,41 +1,57 @@
-#![feature(error_generic_member_access)]
-#![cfg_attr(target_os = "windows", feature(windows_by_handle))]
-
-pub mod analytics;
+pub mod ai;
 pub mod app;
-pub mod assets;
 pub mod commands;
-pub mod database;
-pub mod dedup;
-pub mod deltas;
-pub mod error;
+pub mod crashes;
 pub mod events;
-pub mod fs;
-pub mod gb_repository;
-pub mod git;
 pub mod github;
-pub mod keys;
-pub mod lock;
 pub mod logs;
 pub mod menu;
-pub mod project_repository;
-pub mod projects;
-pub mod reader;
+pub mod profiler;
 pub mod sentry;
-pub mod sessions;
-pub mod ssh;
-pub mod storage;
-pub mod types;
-pub mod users;
-pub mod virtual_branches;
-pub mod watcher;
-#[cfg(target_os = "windows")]
-pub(crate) mod windows;
-pub mod writer;
 pub mod zip;
 
+pub use gitbutler_core::{
+    analytics, assets, database, dedup, error, fs, gb_repository, git, lock, memory,
+    project_repository, reader, ssh, storage, types, watcher, writer,
+};
+
+pub mod deltas {
+    pub use gitbutler_core::deltas::*;
+    pub mod commands;
+}
+
+pub mod keys {
+    pub use gitbutler_core::keys::*;
+    pub mod commands;
+}
+
+pub mod projects {
+    pub use gitbutler_core::projects::*;
+    pub mod commands;
+}
+
+pub mod sessions {
+    pub use gitbutler_core::sessions::*;
+    pub mod commands;
+}
+
+pub mod settings {
+    pub use gitbutler_core::settings::*;
+    pub mod commands;
+}
+
+pub mod users {
+    pub use gitbutler_core::users::*;
+    pub mod commands;
+}
+
+pub mod virtual_branches {
+    pub use gitbutler_core::virtual_branches::*;
+    pub mod commands;
+}
+
 #[cfg(test)]
-pub mod test_utils;
+pub use gitbutler_core::test_utils;
 
 #[deprecated = "use `gitbutler-core` instead"]
 pub mod id {
@@ -1,11 +1,58 @@
-use std::{fs, net::Ipv4Addr, time::Duration};
+pub mod commands;
+pub mod rotation;
 
+use std::{
+    fs,
+    net::Ipv4Addr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
 use tauri::{AppHandle, Manager};
-use tracing::{metadata::LevelFilter, subscriber::set_global_default};
-use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use tracing_subscriber::{fmt::format::FmtSpan, layer::SubscriberExt, Layer};
+use tracing::subscriber::set_global_default;
+use tracing_subscriber::{
+    filter::{EnvFilter, ParseError},
+    fmt::format::FmtSpan,
+    layer::SubscriberExt,
+    reload, Layer, Registry,
+};
+
+use crate::{profiler, sentry};
+
+const DEFAULT_DIRECTIVES: &str = "info";
 
-use crate::sentry;
+/// What is logged to stdout and to the log file, which can be changed while the app runs.
+/// Directives are those of `RUST_LOG`, like `info,gitbutler_core::virtual_branches=debug`.
+#[derive(Clone)]
+pub struct Filter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    directives: Arc<Mutex<String>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SetFilterError {
+    #[error(transparent)]
+    Invalid(#[from] ParseError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl Filter {
+    pub fn directives(&self) -> String {
+        self.directives.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, directives: &str) -> Result<(), SetFilterError> {
+        let filter = EnvFilter::try_new(directives)?;
+        self.handle
+            .reload(filter)
+            .context("failed to reload log filter")?;
+        *self.directives.lock().unwrap() = directives.to_string();
+        tracing::info!(directives, "log filter changed");
+        Ok(())
+    }
+}
 
 pub fn init(app_handle: &AppHandle) {
     let logs_dir = app_handle
@@ -14,13 +61,10 @@ pub fn init(app_handle: &AppHandle) {
         .expect("failed to get logs dir");
     fs::create_dir_all(&logs_dir).expect("failed to create logs dir");
 
-    let file_appender = RollingFileAppender::builder()
-        .rotation(Rotation::DAILY)
-        .max_log_files(14)
-        .filename_prefix("GitButler.log")
-        .build(&logs_dir)
-        .expect("initializing rolling file appender failed");
-    let (file_writer, guard) = tracing_appender::non_blocking(file_appender);
+    let file =
+        rotation::RotatingFile::open(&logs_dir, "GitButler.log", rotation::Policy::default())
+            .expect("failed to open log file");
+    let (file_writer, guard) = tracing_appender::non_blocking(file);
     app_handle.manage(guard); // keep the guard alive for the lifetime of the app
 
     let format_for_humans = tracing_subscriber::fmt::format()
@@ -29,13 +73,32 @@ pub fn init(app_handle: &AppHandle) {
         .with_target(false)
         .compact();
 
-    let log_level_filter = std::env::var("LOG_LEVEL")
-        .unwrap_or("info".to_string())
-        .to_lowercase()
-        .parse()
-        .unwrap_or(LevelFilter::INFO);
+    let directives = std::env::var("LOG_LEVEL")
+        .map(|directives| directives.to_lowercase())
+        .ok()
+        .filter(|directives| EnvFilter::try_new(directives).is_ok())
+        .unwrap_or(DEFAULT_DIRECTIVES.to_string());
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(&directives));
+    app_handle.manage(Filter {
+        handle,
+        directives: Arc::new(Mutex::new(directives)),
+    });
 
     let subscriber = tracing_subscriber::registry()
+        .with(
+            // subscribers that write spans to stdout and to a file
+            tracing_subscriber::fmt::layer()
+                .event_format(format_for_humans.clone())
+                .with_span_events(FmtSpan::CLOSE)
+                .and_then(
+                    tracing_subscriber::fmt::layer()
+                        .event_format(format_for_humans)
+                        .with_ansi(false)
+                        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+                        .with_writer(file_writer),
+                )
+                .with_filter(filter),
+        )
         .with(
             // subscriber for https://github.com/tokio-rs/console
             console_subscriber::ConsoleLayer::builder()
@@ -45,23 +108,9 @@ pub fn init(app_handle: &AppHandle) {
                 .recording_path(logs_dir.join("tokio-console"))
                 .spawn(),
         )
-        .with(
-            // subscriber that writes spans to stdout
-            tracing_subscriber::fmt::layer()
-                .event_format(format_for_humans.clone())
-                .with_span_events(FmtSpan::CLOSE)
-                .with_filter(log_level_filter),
-        )
         .with(sentry::tracing_layer())
-        .with(
-            // subscriber that writes spans to a file
-            tracing_subscriber::fmt::layer()
-                .event_format(format_for_humans)
-                .with_ansi(false)
-                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-                .with_writer(file_writer)
-                .with_filter(log_level_filter),
-        );
+        // records span timings while a profile is being captured
+        .with(profiler::layer());
 
     set_global_default(subscriber).expect("failed to set subscriber");
 }
@@ -0,0 +1,35 @@
+use tauri::{AppHandle, Manager};
+use tracing::instrument;
+
+use crate::error::{Code, Error};
+
+use super::{Filter, SetFilterError};
+
+impl From<SetFilterError> for Error {
+    fn from(value: SetFilterError) -> Self {
+        match value {
+            SetFilterError::Invalid(error) => Error::UserError {
+                code: Code::Validation,
+                message: format!("Invalid log filter: {error}"),
+            },
+            SetFilterError::Other(error) => {
+                tracing::error!(?error, "failed to set log filter");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+/// The directives of what is logged, like `info,gitbutler_core::virtual_branches=debug`.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn get_log_filter(handle: AppHandle) -> Result<String, Error> {
+    Ok(handle.state::<Filter>().directives())
+}
+
+/// Logs what `directives` say from now on, without restarting the app.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn set_log_filter(handle: AppHandle, directives: &str) -> Result<(), Error> {
+    handle.state::<Filter>().set(directives).map_err(Into::into)
+}
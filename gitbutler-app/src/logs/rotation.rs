@@ -0,0 +1,206 @@
+//! A log file that doesn't grow unbounded. Once it is larger than the [`Policy`] allows, it is
+//! renamed after the time it was rotated at and compressed, and the rotated files that are too
+//! old or too many are deleted.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path,
+    time::{Duration, SystemTime},
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    /// The size a log file is rotated at.
+    pub max_bytes: u64,
+    /// How long rotated files are kept for.
+    pub max_age: Duration,
+    /// How many rotated files are kept, the most recent ones.
+    pub max_files: usize,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            max_bytes: 10 * 1024 * 1024,
+            max_age: Duration::from_secs(14 * 24 * 60 * 60),
+            max_files: 20,
+        }
+    }
+}
+
+pub struct RotatingFile {
+    dir: path::PathBuf,
+    name: String,
+    policy: Policy,
+    file: fs::File,
+    len: u64,
+}
+
+impl RotatingFile {
+    /// Opens `name` in `dir` to append to, deleting the rotated files the policy doesn't keep.
+    pub fn open(dir: &path::Path, name: &str, policy: Policy) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(name))?;
+        let len = file.metadata()?.len();
+        let rotating_file = Self {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            policy,
+            file,
+            len,
+        };
+        rotating_file.prune()?;
+        Ok(rotating_file)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let path = self.dir.join(&self.name);
+        let rotated_path = self.dir.join(format!(
+            "{}.{}.gz",
+            self.name,
+            chrono::Utc::now().format("%Y-%m-%dT%H-%M-%S%.3f")
+        ));
+        let mut encoder = GzEncoder::new(fs::File::create(&rotated_path)?, Compression::default());
+        io::copy(&mut fs::File::open(&path)?, &mut encoder)?;
+        encoder.finish()?;
+
+        self.file = fs::File::create(&path)?;
+        self.len = 0;
+        self.prune()
+    }
+
+    // deletes the rotated files that are older than the policy allows, or beyond the most recent
+    // ones it keeps. the logs of before rotations were compressed are rotated files too.
+    fn prune(&self) -> io::Result<()> {
+        let prefix = format!("{}.", self.name);
+        let mut rotated = fs::read_dir(&self.dir)?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+            .filter_map(|entry| {
+                let modified = entry.metadata().and_then(|metadata| metadata.modified());
+                Some((entry.path(), modified.ok()?))
+            })
+            .collect::<Vec<_>>();
+        rotated.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+
+        let now = SystemTime::now();
+        for (i, (path, modified)) in rotated.iter().enumerate() {
+            let too_old = now
+                .duration_since(*modified)
+                .map_or(false, |age| age > self.policy.max_age);
+            if too_old || i >= self.policy.max_files {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.len > 0 && self.len + buf.len() as u64 > self.policy.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn rotated(dir: &path::Path) -> Vec<path::PathBuf> {
+        let mut rotated = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |extension| extension == "gz")
+            })
+            .collect::<Vec<_>>();
+        rotated.sort();
+        rotated
+    }
+
+    #[test]
+    fn rotates_and_compresses_files_that_are_too_large() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = Policy {
+            max_bytes: 10,
+            ..Policy::default()
+        };
+        let mut file = RotatingFile::open(dir.path(), "app.log", policy).unwrap();
+
+        file.write_all(b"first line\n").unwrap();
+        file.write_all(b"second\n").unwrap();
+        file.flush().unwrap();
+
+        let rotated = rotated(dir.path());
+        assert_eq!(rotated.len(), 1);
+        let mut contents = String::new();
+        GzDecoder::new(fs::File::open(&rotated[0]).unwrap())
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "first line\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("app.log")).unwrap(),
+            "second\n"
+        );
+    }
+
+    #[test]
+    fn keeps_the_most_recent_rotated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = Policy {
+            max_bytes: 1,
+            max_files: 2,
+            ..Policy::default()
+        };
+        let mut file = RotatingFile::open(dir.path(), "app.log", policy).unwrap();
+
+        for line in ["a", "b", "c", "d"] {
+            file.write_all(line.as_bytes()).unwrap();
+            // rotated files are named after the millisecond they were rotated at
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(rotated(dir.path()).len(), 2);
+        assert_eq!(fs::read_to_string(dir.path().join("app.log")).unwrap(), "d");
+    }
+
+    #[test]
+    fn deletes_old_logs_when_opened() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("app.log.2020-01-01");
+        fs::write(&old, "old").unwrap();
+        let a_month_ago = SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60);
+        fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(a_month_ago)
+            .unwrap();
+
+        RotatingFile::open(dir.path(), "app.log", Policy::default()).unwrap();
+
+        assert!(!old.exists());
+        assert!(dir.path().join("app.log").exists());
+    }
+}
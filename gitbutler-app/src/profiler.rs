@@ -0,0 +1,272 @@
+//! Records a window of tracing spans and exports them as collapsed stacks.
+//!
+//! The output format is the one understood by `inferno-flamegraph`, `flamegraph.pl`
+//! and speedscope: one line per unique span stack, `root;child;leaf <self time in µs>`.
+pub mod commands;
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time,
+};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use tracing::{span, Subscriber};
+use tracing_subscriber::{layer, registry::LookupSpan};
+
+lazy_static! {
+    static ref RECORDER: Arc<Recorder> = Arc::new(Recorder::default());
+}
+
+/// Returns a layer that feeds the global recorder.
+pub fn layer() -> Layer {
+    Layer {
+        recorder: Arc::clone(&RECORDER),
+    }
+}
+
+pub fn start() -> Result<(), StartError> {
+    RECORDER.start()
+}
+
+pub fn stop(path: &path::Path) -> Result<Summary, StopError> {
+    RECORDER.stop(path)
+}
+
+pub fn is_recording() -> bool {
+    RECORDER.is_recording()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StartError {
+    #[error("already recording")]
+    AlreadyRecording,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StopError {
+    #[error("not recording")]
+    NotRecording,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    pub path: path::PathBuf,
+    pub duration_ms: u128,
+    pub stacks: usize,
+}
+
+#[derive(Default)]
+struct Window {
+    started_at: Option<time::Instant>,
+    // collapsed stack -> accumulated self time in microseconds
+    samples: HashMap<String, u128>,
+}
+
+#[derive(Default)]
+pub struct Recorder {
+    recording: AtomicBool,
+    window: Mutex<Window>,
+}
+
+impl Recorder {
+    fn start(&self) -> Result<(), StartError> {
+        let mut window = self.window.lock().unwrap();
+        if self.recording.load(Ordering::SeqCst) {
+            return Err(StartError::AlreadyRecording);
+        }
+        window.started_at = Some(time::Instant::now());
+        window.samples.clear();
+        self.recording.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn stop(&self, path: &path::Path) -> Result<Summary, StopError> {
+        let (started_at, samples) = {
+            let mut window = self.window.lock().unwrap();
+            if !self.recording.swap(false, Ordering::SeqCst) {
+                return Err(StopError::NotRecording);
+            }
+            (
+                window.started_at.take(),
+                std::mem::take(&mut window.samples),
+            )
+        };
+
+        write_collapsed(path, &samples).context("failed to write collapsed stacks")?;
+
+        Ok(Summary {
+            path: path.to_path_buf(),
+            duration_ms: started_at.map_or(0, |started_at| started_at.elapsed().as_millis()),
+            stacks: samples.len(),
+        })
+    }
+
+    fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, stack: String, self_time: time::Duration) {
+        let mut window = self.window.lock().unwrap();
+        // the window might have been closed while the span was open
+        if !self.is_recording() {
+            return;
+        }
+        *window.samples.entry(stack).or_default() += self_time.as_micros();
+    }
+}
+
+fn write_collapsed(path: &path::Path, samples: &HashMap<String, u128>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("failed to create directory")?;
+    }
+    let mut lines = samples
+        .iter()
+        .filter(|(_, micros)| **micros > 0)
+        .collect::<Vec<_>>();
+    lines.sort();
+    let mut file = fs::File::create(path).context("failed to create file")?;
+    for (stack, micros) in lines {
+        writeln!(file, "{} {}", stack, micros)?;
+    }
+    Ok(())
+}
+
+// per-span bookkeeping, stored in the span's extensions.
+#[derive(Default)]
+struct Timing {
+    entered_at: Option<time::Instant>,
+    busy: time::Duration,
+    children: time::Duration,
+}
+
+pub struct Layer {
+    recorder: Arc<Recorder>,
+}
+
+impl<S> tracing_subscriber::Layer<S> for Layer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        _attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: layer::Context<'_, S>,
+    ) {
+        if !self.recorder.is_recording() {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Timing::default());
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: layer::Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<Timing>() {
+                timing.entered_at = Some(time::Instant::now());
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: layer::Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<Timing>() {
+                if let Some(entered_at) = timing.entered_at.take() {
+                    timing.busy += entered_at.elapsed();
+                }
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: layer::Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(timing) = span.extensions_mut().remove::<Timing>() else {
+            return;
+        };
+
+        if let Some(parent) = span.parent() {
+            if let Some(parent_timing) = parent.extensions_mut().get_mut::<Timing>() {
+                parent_timing.children += timing.busy;
+            }
+        }
+
+        let stack = span
+            .scope()
+            .from_root()
+            .map(|span| format!("{}::{}", span.metadata().target(), span.name()))
+            .collect::<Vec<_>>()
+            .join(";");
+
+        self.recorder
+            .record(stack, timing.busy.saturating_sub(timing.children));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn test_collapsed_stacks() -> Result<()> {
+        let recorder = Arc::new(Recorder::default());
+        let subscriber = tracing_subscriber::registry().with(Layer {
+            recorder: Arc::clone(&recorder),
+        });
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("before").in_scope(|| {});
+
+            recorder.start().unwrap();
+            assert!(matches!(
+                recorder.start(),
+                Err(StartError::AlreadyRecording)
+            ));
+
+            tracing::info_span!("outer").in_scope(|| {
+                std::thread::sleep(time::Duration::from_millis(2));
+                tracing::info_span!("inner").in_scope(|| {
+                    std::thread::sleep(time::Duration::from_millis(2));
+                });
+            });
+        });
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("profile.folded");
+        let summary = recorder.stop(&path)?;
+        assert_eq!(summary.stacks, 2);
+
+        let contents = fs::read_to_string(&path)?;
+        let stacks = contents
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect::<Vec<_>>();
+        let target = module_path!();
+        assert_eq!(
+            stacks,
+            vec![
+                format!("{target}::outer"),
+                format!("{target}::outer;{target}::inner"),
+            ]
+        );
+
+        assert!(matches!(recorder.stop(&path), Err(StopError::NotRecording)));
+
+        Ok(())
+    }
+}
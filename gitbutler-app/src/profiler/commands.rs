@@ -0,0 +1,54 @@
+use anyhow::Context;
+use tauri::AppHandle;
+use tracing::instrument;
+
+use crate::error::{Code, Error};
+
+use super::{StartError, StopError, Summary};
+
+impl From<StartError> for Error {
+    fn from(value: StartError) -> Self {
+        match value {
+            StartError::AlreadyRecording => Error::UserError {
+                code: Code::Validation,
+                message: "Profiling is already in progress".to_string(),
+            },
+        }
+    }
+}
+
+impl From<StopError> for Error {
+    fn from(value: StopError) -> Self {
+        match value {
+            StopError::NotRecording => Error::UserError {
+                code: Code::Validation,
+                message: "Profiling is not in progress".to_string(),
+            },
+            StopError::Other(error) => {
+                tracing::error!(?error, "failed to export profile");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+#[tauri::command(async)]
+#[instrument]
+pub async fn start_profiling() -> Result<(), Error> {
+    super::start().map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn stop_profiling(handle: AppHandle) -> Result<Summary, Error> {
+    let logs_dir = handle
+        .path_resolver()
+        .app_log_dir()
+        .context("failed to get logs dir")
+        .map_err(StopError::Other)?;
+    let file_name = format!(
+        "profile-{}.folded",
+        chrono::Utc::now().format("%Y-%m-%dT%H-%M-%S")
+    );
+    super::stop(&logs_dir.join("profiles").join(file_name)).map_err(Into::into)
+}
@@ -1,8 +0,0 @@
-mod config;
-pub mod conflicts;
-mod repository;
-
-pub use config::Config;
-pub use repository::{LogUntil, OpenError, RemoteError, Repository};
-
-pub mod signatures;
@@ -1,8 +0,0 @@
-pub mod commands;
-mod controller;
-mod project;
-mod storage;
-
-pub use controller::*;
-pub use project::{ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId};
-pub use storage::UpdateRequest;
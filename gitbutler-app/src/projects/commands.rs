@@ -1,41 +1,15 @@
-use std::path;
+use std::{path, time::Duration};
 
+use anyhow::Context;
 use tauri::Manager;
 use tracing::instrument;
 
 use crate::{
     error::{Code, Error},
-    projects,
+    events, git, project_repository, projects, settings,
 };
 
-use super::controller::{self, Controller};
-
-impl From<controller::UpdateError> for Error {
-    fn from(value: controller::UpdateError) -> Self {
-        match value {
-            controller::UpdateError::Validation(
-                controller::UpdateValidationError::KeyNotFound(path),
-            ) => Error::UserError {
-                code: Code::Projects,
-                message: format!("'{}' not found", path.display()),
-            },
-            controller::UpdateError::Validation(controller::UpdateValidationError::KeyNotFile(
-                path,
-            )) => Error::UserError {
-                code: Code::Projects,
-                message: format!("'{}' is not a file", path.display()),
-            },
-            controller::UpdateError::NotFound => Error::UserError {
-                code: Code::Projects,
-                message: "Project not found".into(),
-            },
-            controller::UpdateError::Other(error) => {
-                tracing::error!(?error, "failed to update project");
-                Error::Unknown
-            }
-        }
-    }
-}
+use super::Controller;
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
@@ -50,33 +24,21 @@ pub async fn update_project(
         .map_err(Into::into)
 }
 
-impl From<controller::AddError> for Error {
-    fn from(value: controller::AddError) -> Self {
-        match value {
-            controller::AddError::NotAGitRepository => Error::UserError {
-                code: Code::Projects,
-                message: "Must be a git directory".to_string(),
-            },
-            controller::AddError::AlreadyExists => Error::UserError {
-                code: Code::Projects,
-                message: "Project already exists".to_string(),
-            },
-            controller::AddError::OpenProjectRepository(error) => error.into(),
-            controller::AddError::NotADirectory => Error::UserError {
-                code: Code::Projects,
-                message: "Not a directory".to_string(),
-            },
-            controller::AddError::PathNotFound => Error::UserError {
-                code: Code::Projects,
-                message: "Path not found".to_string(),
-            },
-            controller::AddError::User(error) => error.into(),
-            controller::AddError::Other(error) => {
-                tracing::error!(?error, "failed to add project");
-                Error::Unknown
-            }
-        }
-    }
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn update_project_settings(
+    handle: tauri::AppHandle,
+    id: &str,
+    settings: projects::SettingsUpdate,
+) -> Result<projects::Project, Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .update_settings(&id, &settings)
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
@@ -84,23 +46,70 @@ impl From<controller::AddError> for Error {
 pub async fn add_project(
     handle: tauri::AppHandle,
     path: &path::Path,
+    scope: Option<path::PathBuf>,
 ) -> Result<projects::Project, Error> {
-    handle.state::<Controller>().add(path).map_err(Into::into)
-}
-
-impl From<controller::GetError> for Error {
-    fn from(value: controller::GetError) -> Self {
-        match value {
-            controller::GetError::NotFound => Error::UserError {
-                code: Code::Projects,
-                message: "Project not found".into(),
-            },
-            controller::GetError::Other(error) => {
-                tracing::error!(?error, "failed to get project");
-                Error::Unknown
-            }
-        }
+    let controller = handle.state::<Controller>();
+    match scope {
+        Some(scope) => controller.add_scoped(path, &scope),
+        None => controller.add(path),
     }
+    .map_err(Into::into)
+}
+
+// how often the progress of a clone is reported
+const CLONE_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Clones `url` into `path` and adds it as a project, reporting the transfer as it goes
+/// with `clone://progress` events.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn clone_repository(
+    handle: tauri::AppHandle,
+    url: String,
+    path: path::PathBuf,
+    options: Option<projects::CloneOptions>,
+) -> Result<projects::Project, Error> {
+    let credentials = git::credentials::Helper::from(
+        &handle
+            .path_resolver()
+            .app_data_dir()
+            .context("failed to get app data dir")?,
+    );
+    let project_id = projects::ProjectId::generate();
+    let events = handle.state::<events::Bus>();
+    let send = |progress: &project_repository::FetchProgress, cloning: bool| {
+        events.publish(events::Event::clone_progress(
+            &project_id,
+            &progress.snapshot(),
+            cloning,
+        ));
+    };
+
+    let progress = project_repository::FetchProgress::default();
+    let mut clone = tokio::task::spawn_blocking({
+        let controller = handle.state::<Controller>().inner().clone();
+        let progress = progress.clone();
+        move || {
+            controller.clone_repository(
+                project_id,
+                &url,
+                &path,
+                &options.unwrap_or_default(),
+                &credentials,
+                &progress,
+            )
+        }
+    });
+    let mut ticks = tokio::time::interval(CLONE_PROGRESS_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            result = &mut clone => break result.context("failed to join clone")?,
+            _ = ticks.tick() => send(&progress, true),
+        }
+    };
+    send(&progress, false);
+
+    result.map_err(Into::into)
 }
 
 #[tauri::command(async)]
@@ -113,34 +122,12 @@ pub async fn get_project(handle: tauri::AppHandle, id: &str) -> Result<projects:
     handle.state::<Controller>().get(&id).map_err(Into::into)
 }
 
-impl From<controller::ListError> for Error {
-    fn from(value: controller::ListError) -> Self {
-        match value {
-            controller::ListError::Other(error) => {
-                tracing::error!(?error, "failed to list projects");
-                Error::Unknown
-            }
-        }
-    }
-}
-
 #[tauri::command(async)]
 #[instrument(skip(handle))]
 pub async fn list_projects(handle: tauri::AppHandle) -> Result<Vec<projects::Project>, Error> {
     handle.state::<Controller>().list().map_err(Into::into)
 }
 
-impl From<controller::DeleteError> for Error {
-    fn from(value: controller::DeleteError) -> Self {
-        match value {
-            controller::DeleteError::Other(error) => {
-                tracing::error!(?error, "failed to delete project");
-                Error::Unknown
-            }
-        }
-    }
-}
-
 #[tauri::command(async)]
 #[instrument(skip(handle))]
 pub async fn delete_project(handle: tauri::AppHandle, id: &str) -> Result<(), Error> {
@@ -154,3 +141,223 @@ pub async fn delete_project(handle: tauri::AppHandle, id: &str) -> Result<(), Er
         .await
         .map_err(Into::into)
 }
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_active_projects(
+    handle: tauri::AppHandle,
+) -> Result<Vec<projects::ProjectId>, Error> {
+    Ok(handle.state::<Controller>().list_active())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn activate_project(handle: tauri::AppHandle, id: &str) -> Result<(), Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .activate(&id)
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn deactivate_project(handle: tauri::AppHandle, id: &str) -> Result<(), Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .deactivate(&id)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_project_groups(
+    handle: tauri::AppHandle,
+) -> Result<Vec<projects::ProjectGroup>, Error> {
+    handle
+        .state::<Controller>()
+        .list_grouped()
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn reorder_projects(handle: tauri::AppHandle, ids: Vec<String>) -> Result<(), Error> {
+    let ids = ids
+        .iter()
+        .map(|id| {
+            id.parse().map_err(|_| Error::UserError {
+                code: Code::Validation,
+                message: "Malformed project id".into(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    handle
+        .state::<Controller>()
+        .reorder(&ids)
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn add_project_group(
+    handle: tauri::AppHandle,
+    name: &str,
+) -> Result<projects::Group, Error> {
+    handle
+        .state::<Controller>()
+        .add_group(name)
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn rename_project_group(
+    handle: tauri::AppHandle,
+    id: &str,
+    name: &str,
+) -> Result<projects::Group, Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed group id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .rename_group(&id, name)
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn reorder_project_groups(
+    handle: tauri::AppHandle,
+    ids: Vec<String>,
+) -> Result<(), Error> {
+    let ids = ids
+        .iter()
+        .map(|id| {
+            id.parse().map_err(|_| Error::UserError {
+                code: Code::Validation,
+                message: "Malformed group id".into(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    handle
+        .state::<Controller>()
+        .reorder_groups(&ids)
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn delete_project_group(handle: tauri::AppHandle, id: &str) -> Result<(), Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed group id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .delete_group(&id)
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn set_project_group(
+    handle: tauri::AppHandle,
+    id: &str,
+    group_id: Option<&str>,
+) -> Result<projects::Project, Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    let group_id = group_id
+        .map(str::parse)
+        .transpose()
+        .map_err(|_| Error::UserError {
+            code: Code::Validation,
+            message: "Malformed group id".into(),
+        })?;
+    handle
+        .state::<Controller>()
+        .set_group(&id, group_id)
+        .map_err(Into::into)
+}
+
+/// Makes `ai_provider` generate for the project instead of the provider of the app
+/// settings, or the app's if it's not set. What the app settings pick a provider for
+/// themselves, like pull requests, still uses theirs.
+#[tauri::command(async)]
+#[instrument(skip(handle, ai_provider))]
+pub async fn set_project_ai_provider(
+    handle: tauri::AppHandle,
+    id: &str,
+    ai_provider: Option<settings::AiProvider>,
+) -> Result<projects::Project, Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .set_ai_provider(&id, ai_provider.as_ref())
+        .map_err(Into::into)
+}
+
+/// The commit message profiles of the project, its own and the built-in ones, see
+/// [`projects::Project::commit_message_profiles`].
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_commit_message_profiles(
+    handle: tauri::AppHandle,
+    id: &str,
+) -> Result<Vec<projects::CommitMessageProfile>, Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    Ok(handle
+        .state::<Controller>()
+        .get(&id)?
+        .commit_message_profiles())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn locate_project(
+    handle: tauri::AppHandle,
+    id: &str,
+) -> Result<projects::Location, Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle.state::<Controller>().locate(&id).map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn relink_project(
+    handle: tauri::AppHandle,
+    id: &str,
+    path: &path::Path,
+) -> Result<projects::Project, Error> {
+    let id = id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .relink(&id, path)
+        .await
+        .map_err(Into::into)
+}
@@ -1,4 +1,7 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
 
 use governor::{
     clock::QuantaClock,
@@ -12,7 +15,12 @@ use sentry_tracing::SentryLayer;
 use tracing::Subscriber;
 use tracing_subscriber::registry::LookupSpan;
 
-use crate::users;
+use crate::{crashes, users};
+
+const DSN: &str =
+    "https://9d407634d26b4d30b6a42d57a136d255@o4504644069687296.ingest.sentry.io/4504649768108032";
+// how long sending a crash report can take
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
 
 static SENTRY_QUOTA: Quota = Quota::per_second(nonzero!(1_u32)); // 1 per second at most.
 static SENTRY_LIMIT: OnceCell<RateLimiter<NotKeyed, InMemoryState, QuantaClock>> = OnceCell::new();
@@ -20,24 +28,102 @@ static SENTRY_LIMIT: OnceCell<RateLimiter<NotKeyed, InMemoryState, QuantaClock>>
 /// Should be called once on application startup, and the returned guard should be kept alive for
 /// the lifetime of the application.
 pub fn init(name: &str, version: String) -> ClientInitGuard {
-    sentry::init(("https://9d407634d26b4d30b6a42d57a136d255@o4504644069687296.ingest.sentry.io/4504649768108032", sentry::ClientOptions {
-        environment: Some(match name {
-            "GitButler" => "production",
-            "GitButler Nightly" => "nightly",
-            "GitButler Dev" => "development",
-            _ => "unknown",
-        }.into()),
-        release: Some(version.into()),
-        before_send: Some({
-            Arc::new(|event| SENTRY_LIMIT.get_or_init(|| RateLimiter::direct(SENTRY_QUOTA)).check().is_ok().then_some(event))}),
-        attach_stacktrace: true,
-        traces_sample_rate: match name {
-            "GitButler Dev" | "GitButler Nightly" => 0.2_f32,
-            _ => 0.05_f32,
+    sentry::init((
+        DSN,
+        sentry::ClientOptions {
+            environment: Some(environment(name).into()),
+            release: Some(version.into()),
+            before_send: Some({
+                // panics are reported by the user, see `crashes`
+                Arc::new(|event| {
+                    (!is_panic(&event)
+                        && SENTRY_LIMIT
+                            .get_or_init(|| RateLimiter::direct(SENTRY_QUOTA))
+                            .check()
+                            .is_ok())
+                    .then_some(event)
+                })
+            }),
+            attach_stacktrace: true,
+            traces_sample_rate: match name {
+                "GitButler Dev" | "GitButler Nightly" => 0.2_f32,
+                _ => 0.05_f32,
+            },
+            default_integrations: true,
+            ..Default::default()
+        },
+    ))
+}
+
+/// Sends `report`, which the user agreed to, whether error reporting is enabled or not.
+pub fn send_crash_report(name: &str, report: &crashes::Report) -> anyhow::Result<()> {
+    send(crash_report_options(name, report), report)
+}
+
+// the options of the client crash reports are sent with, with the transport of sentry but
+// none of its integrations
+fn crash_report_options(name: &str, report: &crashes::Report) -> sentry::ClientOptions {
+    sentry::apply_defaults(sentry::ClientOptions::from((
+        DSN,
+        sentry::ClientOptions {
+            environment: Some(environment(name).into()),
+            release: Some(report.version.clone().into()),
+            default_integrations: false,
+            ..Default::default()
+        },
+    )))
+}
+
+fn send(options: sentry::ClientOptions, report: &crashes::Report) -> anyhow::Result<()> {
+    let client = sentry::Client::from_config(options);
+    let extra = [
+        ("location", report.location.clone()),
+        ("thread", report.thread.clone()),
+        ("backtrace", Some(report.backtrace.clone())),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key.to_string(), value?.into())))
+    .collect();
+    client.capture_event(
+        sentry::protocol::Event {
+            level: sentry::Level::Fatal,
+            message: Some(report.message.clone()),
+            timestamp: UNIX_EPOCH
+                + Duration::from_millis(u64::try_from(report.created_at).unwrap_or(u64::MAX)),
+            tags: [
+                ("os".to_string(), report.os.clone()),
+                ("arch".to_string(), report.arch.clone()),
+            ]
+            .into_iter()
+            .collect(),
+            extra,
+            ..Default::default()
         },
-        default_integrations: true,
-        ..Default::default()
-    }))
+        None,
+    );
+    if !client.flush(Some(SEND_TIMEOUT)) {
+        return Err(anyhow::anyhow!("timed out sending crash report"));
+    }
+    Ok(())
+}
+
+fn environment(name: &str) -> &'static str {
+    match name {
+        "GitButler" => "production",
+        "GitButler Nightly" => "nightly",
+        "GitButler Dev" => "development",
+        _ => "unknown",
+    }
+}
+
+// whether `event` was captured by the panic integration
+fn is_panic(event: &sentry::protocol::Event) -> bool {
+    event.exception.values.iter().any(|exception| {
+        exception
+            .mechanism
+            .as_ref()
+            .map_or(false, |mechanism| mechanism.ty == "panic")
+    })
 }
 
 /// Sets the current user in the Sentry scope.
@@ -71,3 +157,49 @@ where
         _ => sentry_tracing::EventFilter::Ignore,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MockTransport(Mutex<Vec<sentry::Envelope>>);
+
+    impl sentry::Transport for MockTransport {
+        fn send_envelope(&self, envelope: sentry::Envelope) {
+            self.0.lock().unwrap().push(envelope);
+        }
+    }
+
+    #[test]
+    fn crash_reports_are_delivered() {
+        let report = crashes::Report {
+            id: "1-2-0".to_string(),
+            created_at: 1,
+            version: "0.0.0".to_string(),
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            thread: Some("main".to_string()),
+            message: "boom".to_string(),
+            location: None,
+            backtrace: String::new(),
+        };
+        let mut options = crash_report_options("GitButler", &report);
+        assert!(options.transport.is_some());
+        assert!(options.integrations.is_empty());
+
+        let transport = Arc::new(MockTransport::default());
+        options.transport = Some(Arc::new(Arc::clone(&transport)));
+        send(options, &report).unwrap();
+
+        let envelopes = transport.0.lock().unwrap();
+        assert_eq!(envelopes.len(), 1);
+        let event = envelopes[0].event().unwrap();
+        assert_eq!(event.message.as_deref(), Some("boom"));
+        assert_eq!(event.level, sentry::Level::Fatal);
+        assert_eq!(event.tags.get("os").map(String::as_str), Some("linux"));
+        assert_eq!(event.release.as_deref(), Some("0.0.0"));
+    }
+}
@@ -3,24 +3,7 @@ use tracing::instrument;
 
 use crate::error::{Code, Error};
 
-use super::{
-    controller::{Controller, ListError},
-    Session,
-};
-
-impl From<ListError> for Error {
-    fn from(value: ListError) -> Self {
-        match value {
-            ListError::UsersError(error) => Error::from(error),
-            ListError::ProjectsError(error) => Error::from(error),
-            ListError::ProjectRepositoryError(error) => Error::from(error),
-            ListError::Other(error) => {
-                tracing::error!(?error);
-                Error::Unknown
-            }
-        }
-    }
-}
+use super::{Controller, Session};
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
@@ -0,0 +1,98 @@
+use tauri::{AppHandle, Manager};
+use tracing::instrument;
+
+use crate::error::Error;
+
+use super::{
+    AppSettings, AppSettingsUpdate, CloudSyncResult, Controller, SyncRepository, SyncResult,
+};
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn get_app_settings(handle: AppHandle) -> Result<AppSettings, Error> {
+    handle.state::<Controller>().get().map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn update_app_settings(
+    handle: AppHandle,
+    update: AppSettingsUpdate,
+) -> Result<AppSettings, Error> {
+    handle
+        .state::<Controller>()
+        .update(&update)
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn get_settings_sync_repository(
+    handle: AppHandle,
+) -> Result<Option<SyncRepository>, Error> {
+    handle
+        .state::<Controller>()
+        .sync_repository()
+        .map_err(Into::into)
+}
+
+/// Sets the git repository settings are synced through, turning syncing off when
+/// `repository` is not set.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn set_settings_sync_repository(
+    handle: AppHandle,
+    repository: Option<SyncRepository>,
+) -> Result<(), Error> {
+    handle
+        .state::<Controller>()
+        .set_sync_repository(repository.as_ref())
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn sync_app_settings(handle: AppHandle) -> Result<SyncResult, Error> {
+    let controller = handle.state::<Controller>().inner().clone();
+    // it runs git against a remote, which can take a while
+    tokio::task::spawn_blocking(move || controller.sync())
+        .await
+        .map_err(|_| Error::Unknown)?
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn get_settings_sync_with_cloud(handle: AppHandle) -> Result<bool, Error> {
+    handle
+        .state::<Controller>()
+        .sync_with_cloud()
+        .map_err(Into::into)
+}
+
+/// Turns syncing the settings through the user's GitButler account on or off.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn set_settings_sync_with_cloud(
+    handle: AppHandle,
+    sync_with_cloud: bool,
+) -> Result<(), Error> {
+    handle
+        .state::<Controller>()
+        .set_sync_with_cloud(sync_with_cloud)
+        .map_err(Into::into)
+}
+
+/// Merges `remote`, the settings the UI got from the user's GitButler account, into the
+/// ones of this installation.
+#[tauri::command(async)]
+#[instrument(skip(handle, remote))]
+pub async fn merge_cloud_app_settings(
+    handle: AppHandle,
+    remote: Option<AppSettings>,
+) -> Result<CloudSyncResult, Error> {
+    handle
+        .state::<Controller>()
+        .merge_cloud(remote.as_ref())
+        .map_err(Into::into)
+}
@@ -3,21 +3,7 @@ use tracing::instrument;
 
 use crate::{assets, error::Error, sentry};
 
-use super::{
-    controller::{self, Controller, GetError},
-    User,
-};
-
-impl From<GetError> for Error {
-    fn from(value: GetError) -> Self {
-        match value {
-            GetError::Other(error) => {
-                tracing::error!(?error, "failed to get user");
-                Error::Unknown
-            }
-        }
-    }
-}
+use super::{Controller, User};
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
@@ -31,17 +17,6 @@ pub async fn get_user(handle: AppHandle) -> Result<Option<User>, Error> {
     }
 }
 
-impl From<controller::SetError> for Error {
-    fn from(value: controller::SetError) -> Self {
-        match value {
-            controller::SetError::Other(error) => {
-                tracing::error!(?error, "failed to set user");
-                Error::Unknown
-            }
-        }
-    }
-}
-
 #[tauri::command(async)]
 #[instrument(skip(handle))]
 pub async fn set_user(handle: AppHandle, user: User) -> Result<User, Error> {
@@ -55,17 +30,6 @@ pub async fn set_user(handle: AppHandle, user: User) -> Result<User, Error> {
     Ok(proxy.proxy_user(user).await)
 }
 
-impl From<controller::DeleteError> for Error {
-    fn from(value: controller::DeleteError) -> Self {
-        match value {
-            controller::DeleteError::Other(error) => {
-                tracing::error!(?error, "failed to delete user");
-                Error::Unknown
-            }
-        }
-    }
-}
-
 #[tauri::command(async)]
 #[instrument(skip(handle))]
 pub async fn delete_user(handle: AppHandle) -> Result<(), Error> {
@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
 use crate::watcher;
 use anyhow::Context;
@@ -8,29 +8,18 @@ use tracing::instrument;
 use crate::{
     assets,
     error::{Code, Error},
-    git, projects,
+    events, git, project_repository, projects,
 };
 
 use super::{
     branch::BranchId,
     controller::{Controller, ControllerError},
-    BaseBranch, RemoteBranchFile,
+    errors, stream, words, AuditEntry, AuditQuery, BaseBranch, Blame, BranchTags, ExternalChange,
+    FileLineEndings, LargeFile, LostCommit, Note, OperationId, OutboxEntry, PrePushProgress,
+    PrePushReport, QueuedOperation, ReconcileAction, RemoteBranchFile, Replay, RestoredMetadata,
+    SecretFinding, Tag, TagCreateRequest, TrashEntry, TrashEntryId, WorkspaceRecovery,
 };
 
-impl<E: Into<Error>> From<ControllerError<E>> for Error {
-    fn from(value: ControllerError<E>) -> Self {
-        match value {
-            ControllerError::User(error) => error,
-            ControllerError::Action(error) => error.into(),
-            ControllerError::VerifyError(error) => error.into(),
-            ControllerError::Other(error) => {
-                tracing::error!(?error, "failed to verify branch");
-                Error::Unknown
-            }
-        }
-    }
-}
-
 #[tauri::command(async)]
 #[instrument(skip(handle))]
 pub async fn commit_virtual_branch(
@@ -40,6 +29,7 @@ pub async fn commit_virtual_branch(
     message: &str,
     ownership: Option<&str>,
     run_hooks: bool,
+    no_verify: Option<bool>,
 ) -> Result<git::Oid, Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
@@ -56,16 +46,23 @@ pub async fn commit_virtual_branch(
             code: Code::Validation,
             message: "Malformed ownership".to_string(),
         })?;
-    let oid = handle
-        .state::<Controller>()
-        .create_commit(
-            &project_id,
-            &branch_id,
-            message,
-            ownership.as_ref(),
-            run_hooks,
-        )
-        .await?;
+    let controller = handle.state::<Controller>();
+    // skipping the hooks only needs recording when they would have run
+    let oid = if run_hooks && no_verify.unwrap_or(false) {
+        controller
+            .create_commit_no_verify(&project_id, &branch_id, message, ownership.as_ref())
+            .await?
+    } else {
+        controller
+            .create_commit(
+                &project_id,
+                &branch_id,
+                message,
+                ownership.as_ref(),
+                run_hooks,
+            )
+            .await?
+    };
     emit_vbranches(&handle, &project_id).await;
     Ok(oid)
 }
@@ -75,47 +72,1021 @@ pub async fn commit_virtual_branch(
 pub async fn list_virtual_branches(
     handle: AppHandle,
     project_id: &str,
-) -> Result<Vec<super::VirtualBranch>, Error> {
+) -> Result<Vec<super::VirtualBranch>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branches = handle
+        .state::<Controller>()
+        .list_virtual_branches(&project_id)
+        .await?;
+
+    let proxy = handle.state::<assets::Proxy>();
+    let branches = proxy.proxy_virtual_branches(branches).await;
+    Ok(branches)
+}
+
+// same as `list_virtual_branches`, but instead of returning the branches, emits them in
+// chunks as `project://<project_id>/virtual-branches/stream/<stream_id>` events, so that
+// very large diffs never end up in a single payload. Chunks are emitted to the windows
+// one at a time as they are cut, rather than queued on the event bus, so no more than one
+// of them is held on top of the branches.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn stream_virtual_branches(
+    handle: AppHandle,
+    project_id: &str,
+    stream_id: &str,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branches = handle
+        .state::<Controller>()
+        .list_virtual_branches(&project_id)
+        .await?;
+
+    let proxy = handle.state::<assets::Proxy>();
+    let branches = proxy.proxy_virtual_branches(branches).await;
+
+    for chunk in stream::chunks(branches, stream::MAX_CHUNK_BYTES) {
+        let event = events::Event::virtual_branches_chunk(&project_id, stream_id, &chunk);
+        handle
+            .emit_all(&event.name(), Some(event.payload()))
+            .context("failed to emit chunk")?;
+    }
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn create_virtual_branch(
+    handle: AppHandle,
+    project_id: &str,
+    branch: super::branch::BranchCreateRequest,
+) -> Result<BranchId, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = handle
+        .state::<Controller>()
+        .create_virtual_branch(&project_id, &branch)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(branch_id)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn create_virtual_branch_from_branch(
+    handle: AppHandle,
+    project_id: &str,
+    branch: &str,
+) -> Result<BranchId, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch = branch.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch name".to_string(),
+    })?;
+    let branch_id = handle
+        .state::<Controller>()
+        .create_virtual_branch_from_branch(&project_id, &branch)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(branch_id)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_lost_commits(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+) -> Result<Vec<LostCommit>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .list_lost_commits(&project_id, &branch_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn recover_commit(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+    commit_oid: &str,
+) -> Result<BranchId, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    let commit_oid = commit_oid.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed commit oid".to_string(),
+    })?;
+    let branch_id = handle
+        .state::<Controller>()
+        .recover_commit(&project_id, &branch_id, commit_oid)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(branch_id)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn export_bundle(
+    handle: AppHandle,
+    project_id: &str,
+    branch_ids: Vec<String>,
+    path: std::path::PathBuf,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_ids = branch_ids
+        .iter()
+        .map(|branch_id| {
+            branch_id.parse().map_err(|_| Error::UserError {
+                code: Code::Validation,
+                message: "Malformed branch id".to_string(),
+            })
+        })
+        .collect::<Result<Vec<BranchId>, _>>()?;
+    handle
+        .state::<Controller>()
+        .export_bundle(&project_id, &branch_ids, &path)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn import_bundle(
+    handle: AppHandle,
+    project_id: &str,
+    path: std::path::PathBuf,
+) -> Result<Vec<BranchId>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_ids = handle
+        .state::<Controller>()
+        .import_bundle(&project_id, &path)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(branch_ids)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn export_virtual_branches(
+    handle: AppHandle,
+    project_id: &str,
+    path: std::path::PathBuf,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .export_virtual_branches(&project_id, &path)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn import_virtual_branches(
+    handle: AppHandle,
+    project_id: &str,
+    path: std::path::PathBuf,
+) -> Result<Vec<BranchId>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_ids = handle
+        .state::<Controller>()
+        .import_virtual_branches(&project_id, &path)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(branch_ids)
+}
+
+/// Checks the project for problems, fixing the ones that can be fixed safely if `fix` is
+/// set.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn repair_project(
+    handle: AppHandle,
+    project_id: &str,
+    fix: bool,
+) -> Result<Vec<super::Issue>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let issues = handle
+        .state::<Controller>()
+        .repair_project(&project_id, fix)
+        .await?;
+    if issues.iter().any(|issue| issue.fixed) {
+        emit_vbranches(&handle, &project_id).await;
+    }
+    Ok(issues)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn merge_virtual_branch_upstream(
+    handle: AppHandle,
+    project_id: &str,
+    branch: &str,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .merge_virtual_branch_upstream(&project_id, &branch_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn get_base_branch_data(
+    handle: AppHandle,
+    project_id: &str,
+) -> Result<Option<super::BaseBranch>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    if let Some(base_branch) = handle
+        .state::<Controller>()
+        .get_base_branch_data(&project_id)
+        .await?
+    {
+        let proxy = handle.state::<assets::Proxy>();
+        let base_branch = proxy.proxy_base_branch(base_branch).await;
+        Ok(Some(base_branch))
+    } else {
+        Ok(None)
+    }
+}
+
+// returns everything the project view needs at once, instead of making the frontend
+// call (and wait for) list_virtual_branches, get_base_branch_data and list_remote_branches
+// one after another.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn get_project_state(
+    handle: AppHandle,
+    project_id: &str,
+) -> Result<super::ProjectState, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let state = handle
+        .state::<Controller>()
+        .get_project_state(&project_id)
+        .await?;
+
+    let proxy = handle.state::<assets::Proxy>();
+    let virtual_branches = proxy.proxy_virtual_branches(state.virtual_branches).await;
+    let base_branch = match state.base_branch {
+        Some(base_branch) => Some(proxy.proxy_base_branch(base_branch).await),
+        None => None,
+    };
+    Ok(super::ProjectState {
+        base_branch,
+        virtual_branches,
+        ..state
+    })
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_operations(
+    handle: AppHandle,
+    project_id: &str,
+) -> Result<Vec<QueuedOperation>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    Ok(handle
+        .state::<Controller>()
+        .list_operations(&project_id)
+        .await)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn cancel_operation(
+    handle: AppHandle,
+    project_id: &str,
+    operation_id: OperationId,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .cancel_operation(&project_id, operation_id)
+        .await
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn get_external_operation(
+    handle: AppHandle,
+    project_id: &str,
+) -> Result<Option<project_repository::ExternalOperation>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .get_external_operation(&project_id)
+        .await
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn finish_external_operation(
+    handle: AppHandle,
+    project_id: &str,
+    action: project_repository::OperationAction,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .finish_external_operation(&project_id, action)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn get_workspace_branch(handle: AppHandle, project_id: &str) -> Result<String, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .get_workspace_branch(&project_id)
+        .await
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn set_workspace_branch(
+    handle: AppHandle,
+    project_id: &str,
+    name: &str,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .set_workspace_branch(&project_id, name)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn set_base_branch(
+    handle: AppHandle,
+    project_id: &str,
+    branch: &str,
+) -> Result<super::BaseBranch, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_name = format!("refs/remotes/{}", branch)
+        .parse()
+        .context("Invalid branch name")?;
+    let base_branch = handle
+        .state::<Controller>()
+        .set_base_branch(&project_id, &branch_name)
+        .await?;
+    let base_branch = handle
+        .state::<assets::Proxy>()
+        .proxy_base_branch(base_branch)
+        .await;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(base_branch)
+}
+
+/// Sets the project up the way `preset` works, including the target it picks.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn apply_project_preset(
+    handle: AppHandle,
+    project_id: &str,
+    preset: projects::Preset,
+) -> Result<super::BaseBranch, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let projects = handle.state::<projects::Controller>();
+    let (configuration, previous) = projects.apply_preset(&project_id, preset)?;
+    let base_branch = match handle
+        .state::<Controller>()
+        .set_base_branch(&project_id, &configuration.target)
+        .await
+    {
+        Ok(base_branch) => base_branch,
+        Err(error) => {
+            // the project is left as it was
+            if let Err(error) = projects.restore_setup(&project_id, &previous) {
+                tracing::error!(%project_id, ?error, "failed to restore project setup");
+            }
+            return Err(error.into());
+        }
+    };
+    let base_branch = handle
+        .state::<assets::Proxy>()
+        .proxy_base_branch(base_branch)
+        .await;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(base_branch)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn update_base_branch(handle: AppHandle, project_id: &str) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .update_base_branch(&project_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn update_virtual_branch(
+    handle: AppHandle,
+    project_id: &str,
+    branch: super::branch::BranchUpdateRequest,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .update_virtual_branch(&project_id, branch)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn delete_virtual_branch(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .delete_virtual_branch(&project_id, &branch_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn apply_branch(handle: AppHandle, project_id: &str, branch: &str) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .apply_virtual_branch(&project_id, &branch_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn unapply_branch(
+    handle: AppHandle,
+    project_id: &str,
+    branch: &str,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .unapply_virtual_branch(&project_id, &branch_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn unapply_ownership(
+    handle: AppHandle,
+    project_id: &str,
+    ownership: &str,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let ownership = ownership.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed ownership".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .unapply_ownership(&project_id, &ownership)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn push_virtual_branch(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+    with_force: bool,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    let progress = PrePushProgress::default();
+    let push = {
+        let controller = handle.state::<Controller>().inner().clone();
+        let progress = progress.clone();
+        async move {
+            controller
+                .push_virtual_branch_with_progress(&project_id, &branch_id, with_force, &progress)
+                .await
+        }
+    };
+    stream_pre_push_output(&handle, &project_id, &branch_id, &progress, push).await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+/// Runs the pre-push checks of the branch without pushing it, streaming their output.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn check_push(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+    with_force: bool,
+) -> Result<PrePushReport, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    let progress = PrePushProgress::default();
+    let check = {
+        let controller = handle.state::<Controller>().inner().clone();
+        let progress = progress.clone();
+        async move {
+            controller
+                .check_push(&project_id, &branch_id, with_force, &progress)
+                .await
+        }
+    };
+    stream_pre_push_output(&handle, &project_id, &branch_id, &progress, check).await
+}
+
+// how often the output of pre-push checks is sent
+const PRE_PUSH_OUTPUT_INTERVAL: Duration = Duration::from_millis(100);
+
+async fn stream_pre_push_output<T: Send + 'static>(
+    handle: &AppHandle,
+    project_id: &projects::ProjectId,
+    branch_id: &BranchId,
+    progress: &PrePushProgress,
+    task: impl std::future::Future<Output = Result<T, ControllerError<errors::PushError>>>
+        + Send
+        + 'static,
+) -> Result<T, Error> {
+    let events = handle.state::<events::Bus>();
+    let send = || {
+        let lines = progress.take();
+        if lines.is_empty() {
+            return;
+        }
+        events.publish(events::Event::pre_push_output(
+            project_id, branch_id, &lines,
+        ));
+    };
+
+    let mut task = tokio::spawn(task);
+    let mut ticks = tokio::time::interval(PRE_PUSH_OUTPUT_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            result = &mut task => break result.context("failed to join pre-push checks")?,
+            _ = ticks.tick() => send(),
+        }
+    };
+    send();
+    result.map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn scan_secrets(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+) -> Result<Vec<SecretFinding>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .scan_secrets(&project_id, &branch_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_large_files(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+) -> Result<Vec<LargeFile>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .list_large_files(&project_id, &branch_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_external_changes(
+    handle: AppHandle,
+    project_id: &str,
+) -> Result<Vec<ExternalChange>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .list_external_changes(&project_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn reconcile_external_change(
+    handle: AppHandle,
+    project_id: &str,
+    change_id: &str,
+    action: ReconcileAction,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let change_id = change_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed change id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .reconcile_external_change(&project_id, &change_id, action)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn recover_workspace(
+    handle: AppHandle,
+    project_id: &str,
+) -> Result<WorkspaceRecovery, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let recovery = handle
+        .state::<Controller>()
+        .recover_workspace(&project_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(recovery)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_trash(handle: AppHandle, project_id: &str) -> Result<Vec<TrashEntry>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .list_trash(&project_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn restore_from_trash(
+    handle: AppHandle,
+    project_id: &str,
+    entry_id: &str,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let entry_id = entry_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed trash entry id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .restore_from_trash(&project_id, &entry_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn purge_trash(
+    handle: AppHandle,
+    project_id: &str,
+    entry_id: Option<&str>,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let entry_id = entry_id
+        .map(str::parse::<TrashEntryId>)
+        .transpose()
+        .map_err(|_| Error::UserError {
+            code: Code::Validation,
+            message: "Malformed trash entry id".to_string(),
+        })?;
+    handle
+        .state::<Controller>()
+        .purge_trash(&project_id, entry_id.as_ref())
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_outbox(handle: AppHandle, project_id: &str) -> Result<Vec<OutboxEntry>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .list_outbox(&project_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_audit_entries(
+    handle: AppHandle,
+    project_id: &str,
+    query: Option<AuditQuery>,
+) -> Result<Vec<AuditEntry>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .list_audit_entries(&project_id, &query.unwrap_or_default())
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn queue_push(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+    with_force: bool,
+) -> Result<OutboxEntry, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .queue_push(&project_id, &branch_id, with_force)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle, body))]
+pub async fn queue_pull_request(
+    handle: AppHandle,
+    project_id: &str,
+    branch_id: &str,
+    title: String,
+    body: String,
+    draft: bool,
+) -> Result<OutboxEntry, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
+        .state::<Controller>()
+        .queue_pull_request(&project_id, &branch_id, title, body, draft)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn replay_outbox(handle: AppHandle, project_id: &str) -> Result<Vec<Replay>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let replays = handle
+        .state::<Controller>()
+        .replay_outbox(&project_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(replays)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn remove_outbox_entry(
+    handle: AppHandle,
+    project_id: &str,
+    entry_id: &str,
+) -> Result<(), Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branches = handle
+    let entry_id = entry_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed outbox entry id".to_string(),
+    })?;
+    handle
         .state::<Controller>()
-        .list_virtual_branches(&project_id)
-        .await?;
-
-    let proxy = handle.state::<assets::Proxy>();
-    let branches = proxy.proxy_virtual_branches(branches).await;
-    Ok(branches)
+        .remove_outbox_entry(&project_id, &entry_id)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn create_virtual_branch(
+pub async fn can_apply_virtual_branch(
     handle: AppHandle,
     project_id: &str,
-    branch: super::branch::BranchCreateRequest,
-) -> Result<BranchId, Error> {
+    branch_id: &str,
+) -> Result<bool, Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branch_id = handle
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".to_string(),
+    })?;
+    handle
         .state::<Controller>()
-        .create_virtual_branch(&project_id, &branch)
-        .await?;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(branch_id)
+        .can_apply_virtual_branch(&project_id, &branch_id)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn create_virtual_branch_from_branch(
+pub async fn can_apply_remote_branch(
     handle: AppHandle,
     project_id: &str,
     branch: &str,
-) -> Result<BranchId, Error> {
+) -> Result<bool, Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
@@ -124,96 +1095,110 @@ pub async fn create_virtual_branch_from_branch(
         code: Code::Validation,
         message: "Malformed branch name".to_string(),
     })?;
-    let branch_id = handle
+    handle
         .state::<Controller>()
-        .create_virtual_branch_from_branch(&project_id, &branch)
-        .await?;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(branch_id)
+        .can_apply_remote_branch(&project_id, &branch)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn merge_virtual_branch_upstream(
+pub async fn list_remote_commit_files(
     handle: AppHandle,
     project_id: &str,
-    branch: &str,
-) -> Result<(), Error> {
+    commit_oid: &str,
+    options: Option<projects::DiffOptions>,
+) -> Result<Vec<RemoteBranchFile>, Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branch_id = branch.parse().map_err(|_| Error::UserError {
+    let commit_oid = commit_oid.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
-        message: "Malformed branch id".to_string(),
+        message: "Malformed commit oid".to_string(),
     })?;
     handle
         .state::<Controller>()
-        .merge_virtual_branch_upstream(&project_id, &branch_id)
-        .await?;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(())
+        .list_remote_commit_files(&project_id, commit_oid, &options.unwrap_or_default())
+        .await
+        .map_err(Into::into)
+}
+
+/// The words that changed within the lines of the hunk `diff`, asked for the hunks as they
+/// are shown rather than listed with all of them.
+#[tauri::command(async)]
+#[instrument(skip(diff))]
+pub async fn hunk_line_changes(diff: String) -> Result<Vec<words::LineChanges>, Error> {
+    Ok(words::line_changes(&diff))
 }
 
+/// Blames the file at `path` in the commit `rev`, only the lines from `start_line` to
+/// `end_line` if either is given, so that large files can be blamed a part at a time.
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn get_base_branch_data(
+pub async fn blame(
     handle: AppHandle,
     project_id: &str,
-) -> Result<Option<super::BaseBranch>, Error> {
+    path: &str,
+    rev: &str,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+) -> Result<Blame, Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    if let Some(base_branch) = handle
+    let rev = rev.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed commit oid".to_string(),
+    })?;
+    let lines = (start_line.is_some() || end_line.is_some())
+        .then(|| start_line.unwrap_or(1)..=end_line.unwrap_or(usize::MAX));
+    handle
         .state::<Controller>()
-        .get_base_branch_data(&project_id)
-        .await?
-    {
-        let proxy = handle.state::<assets::Proxy>();
-        let base_branch = proxy.proxy_base_branch(base_branch).await;
-        Ok(Some(base_branch))
-    } else {
-        Ok(None)
-    }
+        .blame(&project_id, &git::path::unescape(path), rev, lines)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn set_base_branch(
+pub async fn list_line_endings(
     handle: AppHandle,
     project_id: &str,
-    branch: &str,
-) -> Result<super::BaseBranch, Error> {
+) -> Result<Vec<FileLineEndings>, Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branch_name = format!("refs/remotes/{}", branch)
-        .parse()
-        .context("Invalid branch name")?;
-    let base_branch = handle
+    handle
         .state::<Controller>()
-        .set_base_branch(&project_id, &branch_name)
-        .await?;
-    let base_branch = handle
-        .state::<assets::Proxy>()
-        .proxy_base_branch(base_branch)
-        .await;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(base_branch)
+        .list_line_endings(&project_id)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn update_base_branch(handle: AppHandle, project_id: &str) -> Result<(), Error> {
+pub async fn normalize_line_endings(
+    handle: AppHandle,
+    project_id: &str,
+    paths: Vec<String>,
+) -> Result<(), Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
-        message: "Malformed project id".into(),
+        message: "Malformed project id".to_string(),
     })?;
     handle
         .state::<Controller>()
-        .update_base_branch(&project_id)
+        .normalize_line_endings(
+            &project_id,
+            &paths
+                .iter()
+                .map(|path| git::path::unescape(path))
+                .collect::<Vec<_>>(),
+        )
         .await?;
     emit_vbranches(&handle, &project_id).await;
     Ok(())
@@ -221,201 +1206,224 @@ pub async fn update_base_branch(handle: AppHandle, project_id: &str) -> Result<(
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn update_virtual_branch(
+pub async fn create_tag(
     handle: AppHandle,
     project_id: &str,
-    branch: super::branch::BranchUpdateRequest,
-) -> Result<(), Error> {
+    request: TagCreateRequest,
+) -> Result<Tag, Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
     handle
         .state::<Controller>()
-        .update_virtual_branch(&project_id, branch)
-        .await?;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(())
+        .create_tag(&project_id, &request)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn delete_virtual_branch(
+pub async fn push_tags(
     handle: AppHandle,
     project_id: &str,
-    branch_id: &str,
+    names: Vec<String>,
+    remote: Option<&str>,
 ) -> Result<(), Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
-        code: Code::Validation,
-        message: "Malformed branch id".to_string(),
-    })?;
     handle
         .state::<Controller>()
-        .delete_virtual_branch(&project_id, &branch_id)
-        .await?;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(())
+        .push_tags(&project_id, &names, remote)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn apply_branch(handle: AppHandle, project_id: &str, branch: &str) -> Result<(), Error> {
+pub async fn list_branch_tags(
+    handle: AppHandle,
+    project_id: &str,
+) -> Result<Vec<BranchTags>, Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branch_id = branch.parse().map_err(|_| Error::UserError {
+    handle
+        .state::<Controller>()
+        .list_branch_tags(&project_id)
+        .await
+        .map_err(Into::into)
+}
+
+/// The tags of the project, only the ones matching the glob `pattern` if there is one.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_tags(
+    handle: AppHandle,
+    project_id: &str,
+    pattern: Option<&str>,
+) -> Result<Vec<Tag>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
-        message: "Malformed branch id".to_string(),
+        message: "Malformed project id".to_string(),
     })?;
     handle
         .state::<Controller>()
-        .apply_virtual_branch(&project_id, &branch_id)
-        .await?;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(())
+        .list_tags(&project_id, pattern)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn unapply_branch(
+pub async fn delete_tag(
     handle: AppHandle,
     project_id: &str,
-    branch: &str,
+    name: &str,
+    remote: Option<&str>,
+    delete_remote: bool,
 ) -> Result<(), Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branch_id = branch.parse().map_err(|_| Error::UserError {
-        code: Code::Validation,
-        message: "Malformed branch id".to_string(),
-    })?;
+    // deleting from a remote deletes it for everyone, it's asked for on its own
+    if remote.is_some() != delete_remote {
+        return Err(Error::UserError {
+            code: Code::Validation,
+            message: "Deleting a tag from a remote needs both the remote and deleteRemote"
+                .to_string(),
+        });
+    }
     handle
         .state::<Controller>()
-        .unapply_virtual_branch(&project_id, &branch_id)
-        .await?;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(())
+        .delete_tag(&project_id, name, remote)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn unapply_ownership(
+pub async fn list_commit_notes(
     handle: AppHandle,
     project_id: &str,
-    ownership: &str,
-) -> Result<(), Error> {
+    commit_oid: &str,
+) -> Result<Vec<Note>, Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let ownership = ownership.parse().map_err(|_| Error::UserError {
+    let commit_oid = commit_oid.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
-        message: "Malformed ownership".to_string(),
+        message: "Malformed commit oid".to_string(),
     })?;
     handle
         .state::<Controller>()
-        .unapply_ownership(&project_id, &ownership)
-        .await?;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(())
+        .list_notes(&project_id, commit_oid)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn push_virtual_branch(
+pub async fn set_commit_note(
     handle: AppHandle,
     project_id: &str,
-    branch_id: &str,
-    with_force: bool,
+    commit_oid: &str,
+    notes_ref: Option<&str>,
+    message: &str,
 ) -> Result<(), Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+    let commit_oid = commit_oid.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
-        message: "Malformed branch id".to_string(),
+        message: "Malformed commit oid".to_string(),
     })?;
     handle
         .state::<Controller>()
-        .push_virtual_branch(&project_id, &branch_id, with_force)
-        .await?;
-    emit_vbranches(&handle, &project_id).await;
-    Ok(())
+        .set_note(&project_id, commit_oid, notes_ref, message)
+        .await
+        .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn can_apply_virtual_branch(
+pub async fn push_notes(
     handle: AppHandle,
     project_id: &str,
-    branch_id: &str,
-) -> Result<bool, Error> {
+    remote: Option<&str>,
+) -> Result<(), Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
-        code: Code::Validation,
-        message: "Malformed branch id".to_string(),
-    })?;
     handle
         .state::<Controller>()
-        .can_apply_virtual_branch(&project_id, &branch_id)
+        .push_notes(&project_id, remote)
         .await
         .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn can_apply_remote_branch(
+pub async fn fetch_notes(
     handle: AppHandle,
     project_id: &str,
-    branch: &str,
-) -> Result<bool, Error> {
+    remote: Option<&str>,
+) -> Result<(), Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let branch = branch.parse().map_err(|_| Error::UserError {
-        code: Code::Validation,
-        message: "Malformed branch name".to_string(),
-    })?;
     handle
         .state::<Controller>()
-        .can_apply_remote_branch(&project_id, &branch)
+        .fetch_notes(&project_id, remote)
         .await
         .map_err(Into::into)
 }
 
 #[tauri::command(async)]
 #[instrument(skip(handle))]
-pub async fn list_remote_commit_files(
+pub async fn push_branch_metadata(
     handle: AppHandle,
     project_id: &str,
-    commit_oid: &str,
-) -> Result<Vec<RemoteBranchFile>, Error> {
+    remote: Option<&str>,
+) -> Result<(), Error> {
     let project_id = project_id.parse().map_err(|_| Error::UserError {
         code: Code::Validation,
         message: "Malformed project id".to_string(),
     })?;
-    let commit_oid = commit_oid.parse().map_err(|_| Error::UserError {
-        code: Code::Validation,
-        message: "Malformed commit oid".to_string(),
-    })?;
     handle
         .state::<Controller>()
-        .list_remote_commit_files(&project_id, commit_oid)
+        .push_metadata(&project_id, remote)
         .await
         .map_err(Into::into)
 }
 
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn fetch_branch_metadata(
+    handle: AppHandle,
+    project_id: &str,
+    remote: Option<&str>,
+) -> Result<RestoredMetadata, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".to_string(),
+    })?;
+    let restored = handle
+        .state::<Controller>()
+        .fetch_metadata(&project_id, remote)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(restored)
+}
+
 #[tauri::command(async)]
 #[instrument(skip(handle))]
 pub async fn reset_virtual_branch(
@@ -571,6 +1579,55 @@ pub async fn squash_branch_commit(
     Ok(())
 }
 
+// how often the progress of a fetch is reported
+const FETCH_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Fetches `depth` more generations of the history of the target, or all of it, when the
+/// project is a shallow clone, reporting the transfer as it goes.
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn deepen_history(
+    handle: tauri::AppHandle,
+    project_id: &str,
+    depth: Option<u32>,
+) -> Result<BaseBranch, Error> {
+    let project_id: projects::ProjectId = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    let events = handle.state::<events::Bus>();
+    let send = |progress: &project_repository::FetchProgress, fetching: bool| {
+        events.publish(events::Event::git_deepen_progress(
+            &project_id,
+            &progress.snapshot(),
+            fetching,
+        ));
+    };
+
+    let progress = project_repository::FetchProgress::default();
+    let mut deepen = tokio::spawn({
+        let controller = handle.state::<Controller>().inner().clone();
+        let progress = progress.clone();
+        async move {
+            controller
+                .deepen_history(&project_id, depth, &progress)
+                .await
+        }
+    });
+    let mut ticks = tokio::time::interval(FETCH_PROGRESS_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            result = &mut deepen => break result.context("failed to join deepen history")?,
+            _ = ticks.tick() => send(&progress, true),
+        }
+    };
+    send(&progress, false);
+
+    let base_branch = result?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(base_branch)
+}
+
 #[tauri::command(async)]
 #[instrument(skip(handle))]
 pub async fn fetch_from_target(
@@ -581,12 +1638,15 @@ pub async fn fetch_from_target(
         code: Code::Validation,
         message: "Malformed project id".into(),
     })?;
-    let base_branch = handle
+    let summary = handle
         .state::<Controller>()
-        .fetch_from_target(&project_id)
+        .fetch_all_remotes(&project_id)
         .await?;
+    handle
+        .state::<events::Bus>()
+        .publish(events::Event::git_fetch(&project_id, &summary.remotes));
     emit_vbranches(&handle, &project_id).await;
-    Ok(base_branch)
+    Ok(summary.base_branch)
 }
 
 pub async fn update_commit_message(
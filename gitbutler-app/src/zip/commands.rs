@@ -13,7 +13,7 @@ impl From<controller::ArchiveError> for Error {
             controller::ArchiveError::GetProject(error) => error.into(),
             controller::ArchiveError::Other(error) => {
                 tracing::error!(?error, "failed to archive project");
-                Error::Unknown
+                Error::caused_by(&error)
             }
         }
     }
@@ -41,7 +41,7 @@ impl From<controller::DataArchiveError> for Error {
             controller::DataArchiveError::GetProject(error) => error.into(),
             controller::DataArchiveError::Other(error) => {
                 tracing::error!(?error, "failed to archive project data");
-                Error::Unknown
+                Error::caused_by(&error)
             }
         }
     }
@@ -68,7 +68,7 @@ impl From<controller::LogsArchiveError> for Error {
         match error {
             controller::LogsArchiveError::Other(error) => {
                 tracing::error!(?error, "failed to archive logs");
-                Error::Unknown
+                Error::caused_by(&error)
             }
         }
     }
@@ -1,6 +1,6 @@
 use std::path;
 
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager};
 
 use crate::projects::{self, ProjectId};
 
@@ -29,7 +29,7 @@ impl TryFrom<&AppHandle> for Controller {
             local_data_dir,
             logs_dir,
             zipper: Zipper::try_from(value)?,
-            projects_controller: projects::Controller::try_from(value)?,
+            projects_controller: value.state::<projects::Controller>().inner().clone(),
         })
     }
 }
@@ -0,0 +1,118 @@
+use std::{env, path};
+
+use anyhow::{Context, Result};
+use gitbutler_core::{
+    analytics, assets, database, events, git, keys,
+    projects::{self, Project},
+    settings, users, virtual_branches, watcher,
+};
+
+use crate::args::Args;
+
+/// Identifier of the release app, which is where its data directory is named after.
+const APP_IDENTIFIER: &str = "com.gitbutler.app";
+
+/// Variable to read a token for https remotes from, used instead of the credentials of
+/// the app when set.
+const TOKEN_VARIABLE: &str = "GITBUTLER_TOKEN";
+
+/// Variable to read the host the token is for from, remotes on other hosts never get it.
+const TOKEN_HOST_VARIABLE: &str = "GITBUTLER_TOKEN_HOST";
+
+const DEFAULT_TOKEN_HOST: &str = "github.com";
+
+pub struct App {
+    pub data_dir: path::PathBuf,
+    pub project: Project,
+    pub vbranches: virtual_branches::Controller,
+}
+
+impl App {
+    pub fn new(args: &Args) -> Result<Self> {
+        let data_dir = data_dir(args)?;
+        gitbutler_core::fs::set_case_probes_dir(&data_dir);
+
+        let projects = projects::Controller::from(&data_dir);
+        let project = find_project(&projects, &args.project)?;
+
+        let vbranches = virtual_branches::Controller::new(
+            &data_dir,
+            &projects,
+            &users::Controller::from(&data_dir),
+            &keys::Controller::from(&data_dir),
+            &credentials_helper(&data_dir),
+        );
+
+        Ok(Self {
+            data_dir,
+            project,
+            vbranches,
+        })
+    }
+}
+
+/// Returns the data directory given on the command line or, by default, the one of the
+/// release app.
+pub fn data_dir(args: &Args) -> Result<path::PathBuf> {
+    match &args.data_dir {
+        Some(data_dir) => Ok(data_dir.clone()),
+        None => Ok(dirs::data_dir()
+            .context("failed to find the data directory")?
+            .join(APP_IDENTIFIER)),
+    }
+}
+
+pub fn credentials_helper(data_dir: &path::PathBuf) -> git::credentials::Helper {
+    let token = env::var(TOKEN_VARIABLE)
+        .ok()
+        .filter(|token| !token.is_empty());
+    let host = env::var(TOKEN_HOST_VARIABLE)
+        .ok()
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| DEFAULT_TOKEN_HOST.to_string());
+    git::credentials::Helper::from(data_dir).with_token(token, &host)
+}
+
+/// Returns what the watchers run with, publishing their events on `events`.
+pub fn watcher_services(
+    data_dir: &path::Path,
+    vbranches: &virtual_branches::Controller,
+    events: &events::Bus,
+) -> Result<watcher::Services> {
+    let data_dir = data_dir.to_path_buf();
+    Ok(watcher::Services {
+        database: database::Database::try_from(&data_dir).context("failed to open database")?,
+        projects: projects::Controller::from(&data_dir),
+        users: users::Controller::from(&data_dir),
+        settings: settings::Controller::from(&data_dir),
+        vbranches: vbranches.clone(),
+        assets_proxy: assets::Proxy::from(&data_dir),
+        analytics: analytics::Client::default(),
+        events: events.clone(),
+        local_data_dir: data_dir,
+    })
+}
+
+/// Returns the innermost project that contains `path`.
+fn find_project(projects: &projects::Controller, path: &path::Path) -> Result<Project> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {}", path.display()))?;
+    projects
+        .list()
+        .context("failed to list projects")?
+        .into_iter()
+        .filter(|project| {
+            project
+                .path
+                .canonicalize()
+                .map_or(false, |project_path| path.starts_with(project_path))
+        })
+        .max_by_key(|project| project.path.components().count())
+        .with_context(|| {
+            format!(
+                "{} is not in a GitButler project, add it in the app first",
+                path.display()
+            )
+        })
+}
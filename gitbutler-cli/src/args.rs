@@ -0,0 +1,250 @@
+use std::{net, path};
+
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "but",
+    about = "Drive GitButler virtual branches from the terminal"
+)]
+pub struct Args {
+    /// Path inside of the project to operate on.
+    #[arg(long, short = 'C', global = true, default_value = ".")]
+    pub project: path::PathBuf,
+
+    /// Directory the app keeps its data in. Defaults to the one of the release app.
+    #[arg(long, global = true, env = "GITBUTLER_DATA_DIR")]
+    pub data_dir: Option<path::PathBuf>,
+
+    /// Print versioned JSON instead of text, including errors.
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Fail instead of waiting for input, for CI and other unattended use.
+    #[arg(long, global = true, env = "GITBUTLER_NO_PROMPT")]
+    pub no_prompt: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Manage virtual branches.
+    #[command(subcommand)]
+    Branch(Branch),
+    /// Manage tags.
+    #[command(subcommand)]
+    Tag(Tag),
+    /// Commit the changes owned by a virtual branch, signing the commit if configured.
+    Commit {
+        /// Branch to commit, by name or id. Defaults to the one selected for changes.
+        #[arg(long, short)]
+        branch: Option<String>,
+        /// Commit message.
+        #[arg(long, short)]
+        message: String,
+        /// Skip the pre-commit, commit-msg and post-commit hooks, noting it in a trailer of
+        /// the message.
+        #[arg(long, short = 'n')]
+        no_verify: bool,
+    },
+    /// Push a virtual branch to its upstream.
+    Push {
+        /// Branch to push, by name or id.
+        branch: String,
+        /// Allow overwriting the upstream if it diverged.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show the applied branches, through the daemon when it's running.
+    Status {
+        /// Print a single, stable line for shell prompts.
+        #[arg(long)]
+        porcelain: bool,
+    },
+    /// Show what GitButler did to the project, the most recent operations last.
+    Audit {
+        /// Only the operations since this time, like `2024-03-01T14:30:00+01:00` or
+        /// `2024-03-01 14:30` in local time.
+        #[arg(long, value_parser = parse_time)]
+        since: Option<u128>,
+        /// Only the operations until this time, see `--since`.
+        #[arg(long, value_parser = parse_time)]
+        until: Option<u128>,
+        /// Only the operations that changed this branch, by name or id.
+        #[arg(long)]
+        branch: Option<String>,
+        /// Show at most this many operations, the most recent ones.
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+    },
+    /// List, inspect and restore the snapshots taken before every operation.
+    #[command(subcommand)]
+    Snapshot(Snapshot),
+    /// Undo the most recent operation by restoring the snapshot taken before it. An undo is
+    /// an operation too, undoing it again redoes what was undone.
+    Undo,
+    /// Print the events of the project as lines of JSON as they happen, through the daemon
+    /// when it's running.
+    Watch,
+    /// Serve the project's branches to AI agents over the Model Context Protocol, on stdin
+    /// and stdout.
+    Mcp {
+        /// Also offer the tools that create branches and commits.
+        #[arg(long)]
+        allow_write: bool,
+    },
+    /// Serve the API of every project over a local socket, or a named pipe on Windows,
+    /// running the file watchers in the background.
+    Daemon {
+        /// Socket to listen on. Defaults to `daemon.sock` in the data directory, or to the
+        /// pipe `\\.\pipe\gitbutler-daemon-<user>` on Windows.
+        #[arg(long)]
+        socket: Option<path::PathBuf>,
+        /// Socket editor extensions connect to. Defaults to `editor.sock` in the data
+        /// directory, or to the pipe `\\.\pipe\gitbutler-editor-<user>` on Windows.
+        #[arg(long)]
+        editor_socket: Option<path::PathBuf>,
+        /// Also serve `GET /metrics` over TCP on this address, like `127.0.0.1:9464`, for
+        /// Prometheus to scrape. Nothing else is served there.
+        #[arg(long)]
+        metrics_addr: Option<net::SocketAddr>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Branch {
+    /// List virtual branches, applied and unapplied.
+    List {
+        /// Also list files, hunks and commits, and whether unapplied branches would apply
+        /// cleanly.
+        #[arg(long, short)]
+        verbose: bool,
+    },
+    /// Create a new virtual branch.
+    Create {
+        /// Name of the branch. Generated if not given.
+        name: Option<String>,
+    },
+    /// Apply a virtual branch to the working directory.
+    Apply {
+        /// Branch to apply, by name or id.
+        branch: String,
+        /// Only report whether the branch would apply cleanly.
+        #[arg(long)]
+        dry_run: bool,
+        /// Apply the branch even if it conflicts with the applied ones. This unapplies
+        /// them until the conflicts are resolved.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Remove a virtual branch from the working directory, keeping its changes.
+    Unapply {
+        /// Branch to unapply, by name or id.
+        branch: String,
+    },
+    /// Unapply every other branch and apply this one, or leave the workspace as it was if
+    /// that fails.
+    Switch {
+        /// Branch to switch to, by name or id.
+        branch: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Tag {
+    /// List tags, with the commit they point to and their message.
+    List {
+        /// Only list the tags matching this glob, like `v1.*`.
+        pattern: Option<String>,
+    },
+    /// Delete a tag.
+    Delete {
+        /// Tag to delete.
+        name: String,
+        /// The remote `--delete-remote` deletes the tag from.
+        #[arg(long, requires = "delete_remote")]
+        remote: Option<String>,
+        /// Also delete the tag from `--remote`, for everyone who fetches from it.
+        #[arg(long, requires = "remote")]
+        delete_remote: bool,
+        /// Confirm the deletion, which can't be undone.
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Snapshot {
+    /// List the snapshots, the most recent first.
+    List {
+        /// Show at most this many snapshots.
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show what changed in the working directory since a snapshot, what restoring it
+    /// would undo.
+    Diff {
+        /// Snapshot to compare with, by id or a unique beginning of it.
+        snapshot: String,
+    },
+    /// Put the branches, the base branch and the working directory back the way they were
+    /// when a snapshot was taken.
+    Restore {
+        /// Snapshot to restore, by id or a unique beginning of it.
+        snapshot: String,
+    },
+}
+
+/// Milliseconds since the epoch of a time in RFC 3339, or in local time without an offset.
+fn parse_time(value: &str) -> Result<u128, String> {
+    let time = match chrono::DateTime::parse_from_rfc3339(value) {
+        Ok(time) => time.with_timezone(&chrono::Utc),
+        Err(_) => [
+            "%Y-%m-%d %H:%M:%S",
+            "%Y-%m-%d %H:%M",
+            "%Y-%m-%dT%H:%M:%S",
+            "%Y-%m-%dT%H:%M",
+        ]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|time| time.and_local_timezone(chrono::Local).earliest())
+        .ok_or_else(|| format!("{value} is not a time like 2024-03-01 14:30"))?
+        .with_timezone(&chrono::Utc),
+    };
+    u128::try_from(time.timestamp_millis()).map_err(|_| format!("{value} is before the epoch"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleting_a_tag_from_a_remote_is_asked_for_on_its_own() {
+        for args in [
+            ["but", "tag", "delete", "v1", "--remote", "origin"].as_slice(),
+            ["but", "tag", "delete", "v1", "--delete-remote"].as_slice(),
+        ] {
+            assert_eq!(
+                Args::try_parse_from(args).unwrap_err().kind(),
+                clap::error::ErrorKind::MissingRequiredArgument
+            );
+        }
+
+        let args = Args::try_parse_from([
+            "but",
+            "tag",
+            "delete",
+            "v1",
+            "--remote",
+            "origin",
+            "--delete-remote",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Command::Tag(Tag::Delete { remote: Some(remote), delete_remote: true, .. }) if remote == "origin"
+        ));
+    }
+}
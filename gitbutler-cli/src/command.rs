@@ -0,0 +1,58 @@
+mod audit;
+mod branch;
+mod commit;
+mod mcp;
+mod push;
+mod snapshot;
+mod status;
+mod tag;
+mod watch;
+
+use anyhow::{Context, Result};
+use gitbutler_core::{error::Error, virtual_branches::VirtualBranch};
+
+use crate::{app::App, args::Command, output::Output};
+
+pub async fn run(app: &App, out: Output, command: Command) -> Result<()> {
+    match command {
+        Command::Branch(command) => branch::run(app, out, command).await,
+        Command::Tag(command) => tag::run(app, out, command).await,
+        Command::Commit {
+            branch,
+            message,
+            no_verify,
+        } => commit::run(app, out, branch.as_deref(), &message, no_verify).await,
+        Command::Push { branch, force } => push::run(app, out, &branch, force).await,
+        Command::Status { porcelain } => status::run(app, out, porcelain).await,
+        Command::Audit {
+            since,
+            until,
+            branch,
+            limit,
+        } => audit::run(app, out, since, until, branch.as_deref(), limit).await,
+        Command::Snapshot(command) => snapshot::run(app, out, command).await,
+        Command::Undo => snapshot::undo(app, out).await,
+        Command::Watch => watch::run(app).await,
+        Command::Mcp { allow_write } => mcp::run(app, allow_write).await,
+        Command::Daemon { .. } => unreachable!("the daemon is started without a project"),
+    }
+}
+
+async fn list_branches(app: &App) -> Result<Vec<VirtualBranch>> {
+    app.vbranches
+        .list_virtual_branches(&app.project.id)
+        .await
+        .map_err(Error::from)
+        .context("failed to list branches")
+}
+
+/// Finds a virtual branch by id or, failing that, by name.
+async fn find_branch(app: &App, name_or_id: &str) -> Result<VirtualBranch> {
+    let mut branches = list_branches(app).await?;
+    let position = branches
+        .iter()
+        .position(|branch| branch.id.to_string() == name_or_id)
+        .or_else(|| branches.iter().position(|branch| branch.name == name_or_id))
+        .with_context(|| format!("branch {name_or_id} not found"))?;
+    Ok(branches.swap_remove(position))
+}
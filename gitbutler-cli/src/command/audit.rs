@@ -0,0 +1,46 @@
+use anyhow::{Context, Result};
+use gitbutler_core::{
+    error::Error,
+    virtual_branches::{AuditQuery, BranchId},
+};
+
+use crate::{
+    app::App,
+    output::{self, Output},
+};
+
+use super::find_branch;
+
+pub async fn run(
+    app: &App,
+    out: Output,
+    since: Option<u128>,
+    until: Option<u128>,
+    branch: Option<&str>,
+    limit: usize,
+) -> Result<()> {
+    let branch_id = match branch {
+        // a branch that was deleted is only known by its id
+        Some(branch) => Some(match find_branch(app, branch).await {
+            Ok(branch) => branch.id,
+            Err(error) => branch.parse::<BranchId>().map_err(|_| error)?,
+        }),
+        None => None,
+    };
+    let query = AuditQuery {
+        since_ms: since,
+        until_ms: until,
+        branch_id,
+        kind: None,
+        limit: Some(limit),
+    };
+    let entries = app
+        .vbranches
+        .list_audit_entries(&app.project.id, &query)
+        .await
+        .map_err(Error::from)
+        .context("failed to read the audit log")?;
+    out.print(&output::AuditEntries {
+        entries: entries.iter().map(output::AuditEntry::from).collect(),
+    })
+}
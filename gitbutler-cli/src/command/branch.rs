@@ -0,0 +1,164 @@
+use anyhow::{bail, Context, Result};
+use gitbutler_core::{
+    error::{Code, Error},
+    virtual_branches::{branch::BranchCreateRequest, VirtualBranch},
+};
+
+use crate::{
+    app::App,
+    args::Branch,
+    output::{self, Output},
+};
+
+use super::{find_branch, list_branches};
+
+pub async fn run(app: &App, out: Output, command: Branch) -> Result<()> {
+    match command {
+        Branch::List { verbose } => list(app, out, verbose).await,
+        Branch::Create { name } => create(app, out, name).await,
+        Branch::Apply {
+            branch,
+            dry_run,
+            force,
+        } => apply(app, out, &branch, dry_run, force).await,
+        Branch::Unapply { branch } => unapply(app, out, &branch).await,
+        Branch::Switch { branch } => switch(app, out, &branch).await,
+    }
+}
+
+async fn list(app: &App, out: Output, verbose: bool) -> Result<()> {
+    let mut branches = list_branches(app).await?;
+    branches.sort_by_key(|branch| (!branch.active, branch.order));
+
+    let mut views = Vec::with_capacity(branches.len());
+    for branch in &branches {
+        let mut view = output::Branch::from(branch);
+        if verbose && !branch.active {
+            view.can_apply = Some(can_apply(app, branch).await?);
+        }
+        views.push(view);
+    }
+
+    out.print(&output::Branches {
+        branches: views,
+        verbose,
+    })
+}
+
+async fn create(app: &App, out: Output, name: Option<String>) -> Result<()> {
+    let branch_id = app
+        .vbranches
+        .create_virtual_branch(
+            &app.project.id,
+            &BranchCreateRequest {
+                name,
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(Error::from)
+        .context("failed to create branch")?;
+    print_changed(app, out, &branch_id.to_string()).await
+}
+
+async fn apply(app: &App, out: Output, name_or_id: &str, dry_run: bool, force: bool) -> Result<()> {
+    let branch = find_branch(app, name_or_id).await?;
+    let can_apply = can_apply(app, &branch).await?;
+
+    if dry_run {
+        return out.print(&output::Preview {
+            branch_id: branch.id.to_string(),
+            name: branch.name,
+            can_apply,
+        });
+    }
+
+    if !can_apply && !force {
+        bail!(Error::UserError {
+            code: Code::ProjectConflict,
+            message: format!(
+                "{} conflicts with the applied branches, use --force to apply it anyway",
+                branch.name
+            ),
+        });
+    }
+
+    app.vbranches
+        .apply_virtual_branch(&app.project.id, &branch.id)
+        .await
+        .map_err(Error::from)
+        .with_context(|| format!("failed to apply {}", branch.name))?;
+    print_changed(app, out, &branch.id.to_string()).await
+}
+
+async fn unapply(app: &App, out: Output, name_or_id: &str) -> Result<()> {
+    let branch = find_branch(app, name_or_id).await?;
+    app.vbranches
+        .unapply_virtual_branch(&app.project.id, &branch.id)
+        .await
+        .map_err(Error::from)
+        .with_context(|| format!("failed to unapply {}", branch.name))?;
+    print_changed(app, out, &branch.id.to_string()).await
+}
+
+async fn switch(app: &App, out: Output, name_or_id: &str) -> Result<()> {
+    let branch = find_branch(app, name_or_id).await?;
+
+    let mut unapplied = vec![];
+    if let Err(error) = switch_to(app, &branch, &mut unapplied).await {
+        // the workspace is left as it was, with the branches that were unapplied applied again
+        for other in unapplied.iter().rev() {
+            if let Err(error) = app
+                .vbranches
+                .apply_virtual_branch(&app.project.id, &other.id)
+                .await
+            {
+                tracing::error!(?error, branch_id = %other.id, "failed to apply branch again");
+            }
+        }
+        return Err(error);
+    }
+
+    list(app, out, false).await
+}
+
+// unapplies the branches other than `branch`, pushing them onto `unapplied`, then applies it
+async fn switch_to(
+    app: &App,
+    branch: &VirtualBranch,
+    unapplied: &mut Vec<VirtualBranch>,
+) -> Result<()> {
+    for other in list_branches(app).await? {
+        if other.active && other.id != branch.id {
+            app.vbranches
+                .unapply_virtual_branch(&app.project.id, &other.id)
+                .await
+                .map_err(Error::from)
+                .with_context(|| format!("failed to unapply {}", other.name))?;
+            unapplied.push(other);
+        }
+    }
+
+    if !branch.active {
+        app.vbranches
+            .apply_virtual_branch(&app.project.id, &branch.id)
+            .await
+            .map_err(Error::from)
+            .with_context(|| format!("failed to apply {}", branch.name))?;
+    }
+    Ok(())
+}
+
+async fn can_apply(app: &App, branch: &VirtualBranch) -> Result<bool> {
+    app.vbranches
+        .can_apply_virtual_branch(&app.project.id, &branch.id)
+        .await
+        .with_context(|| format!("failed to check if {} can be applied", branch.name))
+}
+
+async fn print_changed(app: &App, out: Output, branch_id: &str) -> Result<()> {
+    let branch = find_branch(app, branch_id).await?;
+    out.print(&output::Changed {
+        branch: (&branch).into(),
+    })
+}
@@ -0,0 +1,63 @@
+use anyhow::{bail, Context, Result};
+use gitbutler_core::error::Error;
+
+use crate::{
+    app::App,
+    output::{self, Output},
+};
+
+use super::{find_branch, list_branches};
+
+pub async fn run(
+    app: &App,
+    out: Output,
+    name_or_id: Option<&str>,
+    message: &str,
+    no_verify: bool,
+) -> Result<()> {
+    let branch = match name_or_id {
+        Some(name_or_id) => find_branch(app, name_or_id).await?,
+        None => list_branches(app)
+            .await?
+            .into_iter()
+            .find(|branch| branch.selected_for_changes)
+            .context("no branch is selected for changes, pick one with --branch")?,
+    };
+
+    if !branch.active {
+        bail!("{} is not applied", branch.name);
+    }
+    if branch.ownership.files.is_empty() {
+        bail!("nothing to commit on {}", branch.name);
+    }
+
+    // pass the ownership we just listed along, so that hunks assigned to the branch in the
+    // meantime don't end up in the commit.
+    let commit_id = if no_verify {
+        app.vbranches
+            .create_commit_no_verify(
+                &app.project.id,
+                &branch.id,
+                message,
+                Some(&branch.ownership),
+            )
+            .await
+    } else {
+        app.vbranches
+            .create_commit(
+                &app.project.id,
+                &branch.id,
+                message,
+                Some(&branch.ownership),
+                true,
+            )
+            .await
+    };
+    let commit_id = commit_id
+        .map_err(Error::from)
+        .with_context(|| format!("failed to commit to {}", branch.name))?;
+    out.print(&output::Committed {
+        branch_id: branch.id.to_string(),
+        commit_id: commit_id.to_string(),
+    })
+}
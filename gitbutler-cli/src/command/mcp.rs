@@ -0,0 +1,111 @@
+//! A Model Context Protocol server for the project, on stdin and stdout.
+//!
+//! Agents talk JSON-RPC 2.0 to it, one message per line. The server offers tools to read
+//! the virtual branches of the project and, only when started with `--allow-write`, tools
+//! to create branches and commit to them.
+
+mod tools;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::app::App;
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Deserialize)]
+struct Request {
+    /// Absent for notifications, which don't get a response.
+    id: Option<serde_json::Value>,
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+pub async fn run(app: &App, allow_write: bool) -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .context("failed to read from stdin")?
+    {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request {
+                id: Some(id),
+                method,
+                params,
+            }) => match handle(app, allow_write, &method, params).await {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+            },
+            Ok(Request { method, .. }) => {
+                tracing::debug!(method, "received notification");
+                continue;
+            }
+            Err(error) => json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": RpcError {
+                    code: PARSE_ERROR,
+                    message: error.to_string(),
+                },
+            }),
+        };
+        println!("{response}");
+    }
+    Ok(())
+}
+
+async fn handle(
+    app: &App,
+    allow_write: bool,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, RpcError> {
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": {
+                "name": "gitbutler",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools::list(allow_write) })),
+        "tools/call" => {
+            let call: ToolCall = serde_json::from_value(params).map_err(|error| RpcError {
+                code: INVALID_PARAMS,
+                message: error.to_string(),
+            })?;
+            Ok(tools::call(app, allow_write, &call.name, call.arguments).await)
+        }
+        method => Err(RpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("method {method} not found"),
+        }),
+    }
+}
@@ -0,0 +1,310 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, Context, Result};
+use gitbutler_core::{
+    error::Error,
+    virtual_branches::{
+        branch::{BranchCreateRequest, Ownership},
+        VirtualBranch,
+    },
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{app::App, output};
+
+use super::super::{find_branch, list_branches};
+
+#[derive(Debug, Clone, Copy)]
+enum Tool {
+    Status,
+    ListBranches,
+    BranchDiff,
+    CreateBranch,
+    Commit,
+}
+
+impl Tool {
+    const ALL: [Tool; 5] = [
+        Tool::Status,
+        Tool::ListBranches,
+        Tool::BranchDiff,
+        Tool::CreateBranch,
+        Tool::Commit,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Tool::Status => "status",
+            Tool::ListBranches => "list_branches",
+            Tool::BranchDiff => "branch_diff",
+            Tool::CreateBranch => "create_branch",
+            Tool::Commit => "commit",
+        }
+    }
+
+    /// Whether the tool changes the project, and so needs `--allow-write`.
+    fn is_write(self) -> bool {
+        matches!(self, Tool::CreateBranch | Tool::Commit)
+    }
+
+    fn definition(self) -> serde_json::Value {
+        let branch = json!({
+            "type": "string",
+            "description": "Name or id of the virtual branch.",
+        });
+        let (description, input_schema) = match self {
+            Tool::Status => (
+                "Lists the applied virtual branches with the files and hunks each of them owns.",
+                json!({ "type": "object", "properties": {} }),
+            ),
+            Tool::ListBranches => (
+                "Lists every virtual branch, applied or not, with its files and commits.",
+                json!({ "type": "object", "properties": {} }),
+            ),
+            Tool::BranchDiff => (
+                "Shows the uncommitted changes owned by a virtual branch as a unified diff.",
+                json!({
+                    "type": "object",
+                    "properties": { "branch": branch },
+                    "required": ["branch"],
+                }),
+            ),
+            Tool::CreateBranch => (
+                "Creates a new virtual branch. New changes are assigned to it once it's selected.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string", "description": "Generated if not given." },
+                    },
+                }),
+            ),
+            Tool::Commit => (
+                "Commits changes owned by an applied virtual branch, running the commit hooks.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "branch": branch,
+                        "message": { "type": "string" },
+                        "ownership": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "`path:start-end,start-end` entries to commit, \
+                                as listed by `status`. Defaults to everything the branch owns.",
+                        },
+                    },
+                    "required": ["branch", "message"],
+                }),
+            ),
+        };
+        json!({
+            "name": self.name(),
+            "description": description,
+            "inputSchema": input_schema,
+        })
+    }
+
+    async fn run(self, app: &App, arguments: serde_json::Value) -> Result<String> {
+        match self {
+            Tool::Status => {
+                let branches = list_branches(app).await?;
+                to_text(
+                    &branches
+                        .iter()
+                        .filter(|branch| branch.active)
+                        .map(output::Branch::from)
+                        .collect::<Vec<_>>(),
+                )
+            }
+            Tool::ListBranches => {
+                let branches = list_branches(app).await?;
+                to_text(
+                    &branches
+                        .iter()
+                        .map(output::Branch::from)
+                        .collect::<Vec<_>>(),
+                )
+            }
+            Tool::BranchDiff => {
+                let arguments: BranchArguments = parse(arguments)?;
+                let branch = find_branch(app, &arguments.branch).await?;
+                Ok(diff(&branch))
+            }
+            Tool::CreateBranch => {
+                let arguments: CreateBranchArguments = parse(arguments)?;
+                let branch_id = app
+                    .vbranches
+                    .create_virtual_branch(
+                        &app.project.id,
+                        &BranchCreateRequest {
+                            name: arguments.name,
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .map_err(Error::from)
+                    .context("failed to create branch")?;
+                let branch = find_branch(app, &branch_id.to_string()).await?;
+                to_text(&output::Branch::from(&branch))
+            }
+            Tool::Commit => {
+                let arguments: CommitArguments = parse(arguments)?;
+                commit(app, arguments).await
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BranchArguments {
+    branch: String,
+}
+
+#[derive(Deserialize)]
+struct CreateBranchArguments {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CommitArguments {
+    branch: String,
+    message: String,
+    ownership: Option<Vec<String>>,
+}
+
+pub fn list(allow_write: bool) -> Vec<serde_json::Value> {
+    Tool::ALL
+        .into_iter()
+        .filter(|tool| allow_write || !tool.is_write())
+        .map(Tool::definition)
+        .collect()
+}
+
+/// Runs a tool. Failures are reported to the agent as the tool's result, so that it can
+/// correct itself.
+pub async fn call(
+    app: &App,
+    allow_write: bool,
+    name: &str,
+    arguments: serde_json::Value,
+) -> serde_json::Value {
+    let result = match Tool::ALL.into_iter().find(|tool| tool.name() == name) {
+        None => Err(anyhow!("unknown tool {name}")),
+        Some(tool) if tool.is_write() && !allow_write => Err(anyhow!(
+            "{name} changes the project, which this server was started without allowing"
+        )),
+        Some(tool) => tool.run(app, arguments).await,
+    };
+    let (text, is_error) = match result {
+        Ok(text) => (text, false),
+        Err(error) => (format!("{error:#}"), true),
+    };
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    })
+}
+
+fn parse<T: serde::de::DeserializeOwned>(arguments: serde_json::Value) -> Result<T> {
+    serde_json::from_value(arguments).context("invalid arguments")
+}
+
+fn to_text<T: serde::Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).context("failed to serialize result")
+}
+
+fn diff(branch: &VirtualBranch) -> String {
+    let mut diff = String::new();
+    for file in &branch.files {
+        let path = file.path.display();
+        let _ = writeln!(diff, "--- a/{path}\n+++ b/{path}");
+        for hunk in &file.hunks {
+            diff.push_str(&hunk.diff);
+            if !hunk.diff.ends_with('\n') {
+                diff.push('\n');
+            }
+        }
+    }
+    diff
+}
+
+async fn commit(app: &App, arguments: CommitArguments) -> Result<String> {
+    let branch = find_branch(app, &arguments.branch).await?;
+    if !branch.active {
+        bail!("{} is not applied", branch.name);
+    }
+
+    // only ever commit what the branch owns right now, so an agent can't sweep up hunks
+    // that belong to another branch.
+    let ownership = match arguments.ownership {
+        Some(entries) => {
+            let ownership = entries
+                .join("\n")
+                .parse::<Ownership>()
+                .context("invalid ownership")?;
+            if !branch.ownership.contains(&ownership) {
+                bail!("{} doesn't own all of the given hunks", branch.name);
+            }
+            ownership
+        }
+        None => branch.ownership.clone(),
+    };
+    if ownership.is_empty() {
+        bail!("nothing to commit on {}", branch.name);
+    }
+
+    let commit_id = app
+        .vbranches
+        .create_commit(
+            &app.project.id,
+            &branch.id,
+            &arguments.message,
+            Some(&ownership),
+            true,
+        )
+        .await
+        .map_err(Error::from)
+        .with_context(|| format!("failed to commit to {}", branch.name))?;
+    to_text(&output::Committed {
+        branch_id: branch.id.to_string(),
+        commit_id: commit_id.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(tools: &[serde_json::Value]) -> Vec<&str> {
+        tools
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn write_tools_only_when_allowed() {
+        assert_eq!(
+            names(&list(false)),
+            ["status", "list_branches", "branch_diff"]
+        );
+        assert_eq!(
+            names(&list(true)),
+            [
+                "status",
+                "list_branches",
+                "branch_diff",
+                "create_branch",
+                "commit"
+            ]
+        );
+    }
+
+    #[test]
+    fn tools_have_an_input_schema() {
+        for tool in list(true) {
+            assert!(tool["description"].is_string(), "{tool}");
+            assert_eq!(tool["inputSchema"]["type"], "object", "{tool}");
+        }
+    }
+}
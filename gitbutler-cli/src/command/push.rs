@@ -0,0 +1,24 @@
+use anyhow::{Context, Result};
+use gitbutler_core::error::Error;
+
+use crate::{
+    app::App,
+    output::{self, Output},
+};
+
+use super::find_branch;
+
+pub async fn run(app: &App, out: Output, name_or_id: &str, force: bool) -> Result<()> {
+    let branch = find_branch(app, name_or_id).await?;
+    app.vbranches
+        .push_virtual_branch(&app.project.id, &branch.id, force)
+        .await
+        .map_err(Error::from)
+        .with_context(|| format!("failed to push {}", branch.name))?;
+
+    let branch = find_branch(app, &branch.id.to_string()).await?;
+    out.print(&output::Pushed {
+        branch_id: branch.id.to_string(),
+        upstream: branch.upstream.map(|upstream| upstream.name.to_string()),
+    })
+}
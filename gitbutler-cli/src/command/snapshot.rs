@@ -0,0 +1,86 @@
+use anyhow::{bail, Context, Result};
+use gitbutler_core::{
+    error::{Code, Error},
+    virtual_branches::Snapshot,
+};
+
+use crate::{
+    app::App,
+    args,
+    output::{self, Output},
+};
+
+pub async fn run(app: &App, out: Output, command: args::Snapshot) -> Result<()> {
+    match command {
+        args::Snapshot::List { limit } => list(app, out, limit).await,
+        args::Snapshot::Diff { snapshot } => diff(app, out, &snapshot).await,
+        args::Snapshot::Restore { snapshot } => {
+            let snapshot = find_snapshot(app, &snapshot).await?;
+            restore(app, out, snapshot).await
+        }
+    }
+}
+
+pub async fn undo(app: &App, out: Output) -> Result<()> {
+    let Some(snapshot) = list_snapshots(app, Some(1)).await?.pop() else {
+        bail!(Error::UserError {
+            code: Code::Validation,
+            message: "there is nothing to undo".to_string(),
+        });
+    };
+    restore(app, out, snapshot).await
+}
+
+async fn list(app: &App, out: Output, limit: usize) -> Result<()> {
+    let snapshots = list_snapshots(app, Some(limit)).await?;
+    out.print(&output::Snapshots {
+        snapshots: snapshots.iter().map(output::Snapshot::from).collect(),
+    })
+}
+
+async fn diff(app: &App, out: Output, snapshot: &str) -> Result<()> {
+    let snapshot = find_snapshot(app, snapshot).await?;
+    let diff = app
+        .vbranches
+        .snapshot_diff(&app.project.id, snapshot.id)
+        .await
+        .map_err(Error::from)
+        .context("failed to diff snapshot")?;
+    out.print(&output::SnapshotDiff::new(&diff))
+}
+
+async fn restore(app: &App, out: Output, snapshot: Snapshot) -> Result<()> {
+    app.vbranches
+        .restore_snapshot(&app.project.id, snapshot.id)
+        .await
+        .map_err(Error::from)
+        .context("failed to restore snapshot")?;
+    out.print(&output::SnapshotRestored {
+        snapshot: output::Snapshot::from(&snapshot),
+    })
+}
+
+async fn list_snapshots(app: &App, limit: Option<usize>) -> Result<Vec<Snapshot>> {
+    app.vbranches
+        .list_snapshots(&app.project.id, limit)
+        .await
+        .map_err(Error::from)
+        .context("failed to list snapshots")
+}
+
+/// Finds a snapshot by its id or the beginning of it, which must be unique.
+async fn find_snapshot(app: &App, id: &str) -> Result<Snapshot> {
+    let mut snapshots = list_snapshots(app, None).await?;
+    snapshots.retain(|snapshot| snapshot.id.to_string().starts_with(id));
+    match snapshots.len() {
+        0 => bail!(Error::UserError {
+            code: Code::Validation,
+            message: format!("snapshot {id} not found"),
+        }),
+        1 => Ok(snapshots.swap_remove(0)),
+        _ => bail!(Error::UserError {
+            code: Code::Validation,
+            message: format!("{id} is the beginning of more than one snapshot"),
+        }),
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::Result;
+
+use crate::{
+    app::App,
+    output::{self, Output},
+};
+
+use super::list_branches;
+
+pub async fn run(app: &App, out: Output, porcelain: bool) -> Result<()> {
+    let mut status = match from_daemon(app).await {
+        Some(status) => status,
+        None => {
+            let branches = list_branches(app).await?;
+            output::Status::new(
+                branches
+                    .iter()
+                    .filter(|branch| branch.active)
+                    .map(output::BranchStatus::from)
+                    .collect(),
+            )
+        }
+    };
+    status.porcelain = porcelain;
+    out.print(&status)
+}
+
+/// Asks a running daemon, which keeps the status of every project up to date and so
+/// answers without computing the diff of the workspace.
+async fn from_daemon(app: &App) -> Option<output::Status> {
+    let socket = crate::daemon::default_socket(&app.data_dir);
+    let uri = format!("/v1/projects/{}/status", app.project.id);
+    match crate::daemon::client::get(&socket, &uri).await {
+        Ok(status) => Some(status),
+        Err(error) => {
+            tracing::debug!(?error, "failed to get status from the daemon");
+            None
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{bail, Context, Result};
+use gitbutler_core::error::{Code, Error};
+
+use crate::{
+    app::App,
+    args::Tag,
+    output::{self, Output},
+};
+
+pub async fn run(app: &App, out: Output, command: Tag) -> Result<()> {
+    match command {
+        Tag::List { pattern } => list(app, out, pattern.as_deref()).await,
+        Tag::Delete {
+            name,
+            remote,
+            delete_remote,
+            yes,
+        } => {
+            // clap requires the flag and the remote together
+            let remote = remote.filter(|_| delete_remote);
+            delete(app, out, &name, remote, yes).await
+        }
+    }
+}
+
+async fn list(app: &App, out: Output, pattern: Option<&str>) -> Result<()> {
+    let tags = app
+        .vbranches
+        .list_tags(&app.project.id, pattern)
+        .await
+        .map_err(Error::from)
+        .context("failed to list tags")?;
+    out.print(&output::Tags {
+        tags: tags.iter().map(output::Tag::from).collect(),
+    })
+}
+
+async fn delete(
+    app: &App,
+    out: Output,
+    name: &str,
+    remote: Option<String>,
+    yes: bool,
+) -> Result<()> {
+    // there is nobody to ask in scripts, the confirmation is a flag
+    if !yes {
+        let from = remote
+            .as_deref()
+            .map(|remote| format!(" here and from {remote}"))
+            .unwrap_or_default();
+        bail!(Error::UserError {
+            code: Code::Validation,
+            message: format!("deleting {name}{from} can't be undone, use --yes to confirm"),
+        });
+    }
+
+    app.vbranches
+        .delete_tag(&app.project.id, name, remote.as_deref())
+        .await
+        .map_err(Error::from)
+        .with_context(|| format!("failed to delete {name}"))?;
+    out.print(&output::TagDeleted {
+        name: name.to_string(),
+        remote,
+    })
+}
@@ -0,0 +1,61 @@
+use anyhow::{Context, Result};
+use gitbutler_core::{events, projects::Watchers as _, watcher};
+use tokio::sync::broadcast;
+
+use crate::{
+    app::{self, App},
+    output,
+};
+
+// how many events printing can fall behind before it starts missing them
+const EVENTS_CAPACITY: usize = 1024;
+
+pub async fn run(app: &App) -> Result<()> {
+    let socket = crate::daemon::default_socket(&app.data_dir);
+    if crate::daemon::connect(&socket).await.is_ok() {
+        let uri = format!("/v1/events?projectId={}", app.project.id);
+        return crate::daemon::client::stream_lines(&socket, &uri, |line| {
+            println!("{line}");
+            Ok(())
+        })
+        .await;
+    }
+
+    // without a daemon, run the watchers of the project here
+    let events = events::Bus::new(EVENTS_CAPACITY);
+    let mut rx = events.subscribe_project(&app.project.id);
+    let vbranches = app.vbranches.clone().with_events(&events);
+    let watchers =
+        watcher::Watchers::from(app::watcher_services(&app.data_dir, &vbranches, &events)?);
+    watchers
+        .watch(&app.project)
+        .context("failed to watch project")?;
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(event) => print!("{}", output::Event::from(&event)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "fell behind on events");
+                    // for the latest branches to be printed again
+                    if let Err(error) = watchers
+                        .post(watcher::Event::CalculateVirtualBranches(app.project.id))
+                        .await
+                    {
+                        tracing::error!(?error, "failed to post refresh");
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            result = tokio::signal::ctrl_c() => {
+                result.context("failed to listen for ctrl-c")?;
+                break;
+            }
+        }
+    }
+
+    watchers
+        .stop(&app.project.id)
+        .await
+        .context("failed to stop watching")
+}
@@ -0,0 +1,83 @@
+//! A long running process that owns the virtual branch engine of every project.
+//!
+//! It runs the same watchers as the app and serves a JSON-over-HTTP API on a unix socket
+//! that only the current user can connect to, a named pipe on Windows, so editors, agents
+//! and scripts can share one authoritative process instead of each embedding the engine.
+//! Editor extensions get a protocol of their own on a second socket, see [`editor`]. The
+//! [`metrics`] can be served over TCP as well, the only thing that ever is.
+
+mod api;
+pub mod client;
+mod editor;
+mod metrics;
+mod transport;
+
+use std::{net::SocketAddr, path};
+
+use anyhow::{Context, Result};
+
+pub use transport::connect;
+
+/// Where the daemon listens unless told otherwise.
+pub fn default_socket(data_dir: &path::Path) -> path::PathBuf {
+    transport::default_address(data_dir, "daemon")
+}
+
+/// Where the daemon listens for editors unless told otherwise.
+pub fn default_editor_socket(data_dir: &path::Path) -> path::PathBuf {
+    transport::default_address(data_dir, "editor")
+}
+
+pub async fn run(
+    data_dir: &path::Path,
+    socket: Option<&path::Path>,
+    editor_socket: Option<&path::Path>,
+    metrics_addr: Option<SocketAddr>,
+) -> Result<()> {
+    let daemon = api::Daemon::new(data_dir)?;
+
+    let socket = socket.map_or_else(|| default_socket(data_dir), path::Path::to_path_buf);
+    let listener = transport::Listener::bind(&socket)?;
+    tracing::info!(socket = %socket.display(), "daemon listening");
+
+    let editor_socket =
+        editor_socket.map_or_else(|| default_editor_socket(data_dir), path::Path::to_path_buf);
+    let editor_listener = transport::Listener::bind(&editor_socket)?;
+    tracing::info!(socket = %editor_socket.display(), "listening for editors");
+    let editors = tokio::spawn(editor::serve(editor_listener, daemon.clone()));
+
+    let metrics = match metrics_addr {
+        Some(addr) => {
+            let server = axum::Server::try_bind(&addr)
+                .with_context(|| format!("failed to bind to {addr}"))?
+                .serve(api::metrics_router(daemon.clone()).into_make_service());
+            tracing::info!(%addr, "serving metrics");
+            Some(tokio::spawn(async move {
+                if let Err(error) = server.await {
+                    tracing::error!(%error, "failed to serve metrics");
+                }
+            }))
+        }
+        None => None,
+    };
+
+    let result = axum::Server::builder(listener.incoming())
+        .serve(api::router(daemon).into_make_service())
+        .with_graceful_shutdown(async {
+            if let Err(error) = tokio::signal::ctrl_c().await {
+                tracing::error!(%error, "failed to listen for ctrl-c");
+            }
+        })
+        .await
+        .context("failed to serve the api");
+
+    editors.abort();
+    if let Some(metrics) = metrics {
+        metrics.abort();
+    }
+    for socket in [&socket, &editor_socket] {
+        transport::remove(socket);
+    }
+
+    result
+}
@@ -0,0 +1,572 @@
+//! The routes of the daemon.
+//!
+//! Bodies and responses are the same JSON the app's frontend exchanges with the engine.
+//! Failures respond with the serialized `gitbutler_core::error::Error`, `400` for errors the user
+//! can act on and `500` for everything else. `GET /v1/events` streams the events the
+//! watchers emit as newline delimited JSON, and `GET /metrics` serves the [`Metrics`] of the
+//! daemon to Prometheus.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use anyhow::Result;
+use axum::{
+    body::StreamBody,
+    extract::{MatchedPath, Path, Query, State},
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use gitbutler_core::{
+    error::{Code, Error},
+    events, git, keys,
+    projects::{self, Project, ProjectId, Watchers as _},
+    users,
+    virtual_branches::{
+        self,
+        branch::{BranchCreateRequest, Ownership},
+        BaseBranch, BranchId, VirtualBranch,
+    },
+    watcher,
+};
+use serde::Deserialize;
+use tokio::sync::broadcast;
+
+use super::metrics::Metrics;
+use crate::{app, output};
+
+// how many events a slow subscriber can fall behind before it starts missing them
+const EVENTS_CAPACITY: usize = 1024;
+
+#[derive(Clone)]
+pub struct Daemon {
+    projects: projects::Controller,
+    vbranches: virtual_branches::Controller,
+    watchers: watcher::Watchers,
+    events: events::Bus,
+    statuses: Statuses,
+    metrics: Metrics,
+}
+
+/// The latest status of every project, kept up to date from the events of the watchers as
+/// they are published, which no subscriber falling behind can miss.
+type Statuses = Arc<Mutex<HashMap<ProjectId, output::Status>>>;
+
+impl Daemon {
+    pub fn new(data_dir: &path::Path) -> Result<Self> {
+        let data_dir = data_dir.to_path_buf();
+
+        let events = events::Bus::new(EVENTS_CAPACITY);
+        let metrics = Metrics::default();
+        gitbutler_core::lock::set_on_wait({
+            let metrics = metrics.clone();
+            Arc::new(move |lock, took| metrics.observe_lock_wait(lock, took))
+        });
+        gitbutler_core::fs::set_case_probes_dir(&data_dir);
+        let projects = projects::Controller::from(&data_dir);
+        let vbranches = virtual_branches::Controller::new(
+            &data_dir,
+            &projects,
+            &users::Controller::from(&data_dir),
+            &keys::Controller::from(&data_dir),
+            &app::credentials_helper(&data_dir),
+        )
+        .with_events(&events)
+        .on_operation_finished({
+            let metrics = metrics.clone();
+            Arc::new(move |operation| metrics.observe_operation(operation))
+        });
+
+        let watchers =
+            watcher::Watchers::from(app::watcher_services(&data_dir, &vbranches, &events)?);
+
+        // a project that can't be watched, like one whose directory is gone, doesn't keep
+        // the others from being served
+        for project in projects.list().map_err(Error::from)? {
+            if let Err(error) = watchers.watch(&project) {
+                tracing::error!(?error, project_id = %project.id, "failed to watch project");
+            }
+        }
+
+        let statuses = Statuses::default();
+        events.on_publish({
+            let statuses = Arc::clone(&statuses);
+            move |event| {
+                if let Some(status) = status_from_event(event) {
+                    statuses.lock().unwrap().insert(*event.project_id(), status);
+                }
+            }
+        });
+
+        Ok(Self {
+            projects,
+            vbranches,
+            watchers,
+            events,
+            statuses,
+            metrics,
+        })
+    }
+
+    /// Makes the watcher recompute the branches of `project_id`, so that subscribers see
+    /// the outcome of a change made through the api.
+    pub async fn refresh(&self, project_id: ProjectId) {
+        if let Err(error) = self
+            .watchers
+            .post(watcher::Event::CalculateVirtualBranches(project_id))
+            .await
+        {
+            tracing::error!(?error, %project_id, "failed to post refresh");
+        }
+    }
+
+    /// Refreshes `project_id`, or every project, for a subscriber that fell behind on their
+    /// events and might have missed the latest branches to get them again.
+    pub async fn catch_up(&self, project_id: Option<&ProjectId>) {
+        let project_ids = match project_id {
+            Some(project_id) => vec![*project_id],
+            None => match self.projects.list() {
+                Ok(projects) => projects.iter().map(|project| project.id).collect(),
+                Err(error) => {
+                    tracing::error!(?error, "failed to list projects to catch up on");
+                    return;
+                }
+            },
+        };
+        for project_id in project_ids {
+            self.refresh(project_id).await;
+        }
+    }
+
+    /// The events of the watchers, from now on. Those of every project, or of `project_id`
+    /// only, which no other project can make the subscriber fall behind on.
+    pub fn subscribe(&self, project_id: Option<&ProjectId>) -> broadcast::Receiver<events::Event> {
+        match project_id {
+            Some(project_id) => self.events.subscribe_project(project_id),
+            None => self.events.subscribe(),
+        }
+    }
+
+    pub async fn branches(&self, project_id: ProjectId) -> Result<Vec<VirtualBranch>, Error> {
+        self.vbranches
+            .list_virtual_branches(&project_id)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The status of `project_id`, computed only if no event of the watchers reported it
+    /// yet.
+    pub async fn status(&self, project_id: ProjectId) -> Result<output::Status, Error> {
+        let cached = self.statuses.lock().unwrap().get(&project_id).cloned();
+        if let Some(status) = cached {
+            return Ok(status);
+        }
+
+        let branches = self.branches(project_id).await?;
+        let status = output::Status::new(
+            branches
+                .iter()
+                .filter(|branch| branch.active)
+                .map(output::BranchStatus::from)
+                .collect(),
+        );
+        self.statuses
+            .lock()
+            .unwrap()
+            .insert(project_id, status.clone());
+        Ok(status)
+    }
+
+    pub async fn commit(
+        &self,
+        project_id: ProjectId,
+        branch_id: BranchId,
+        commit: &CommitRequest,
+    ) -> Result<git::Oid, Error> {
+        let commit_oid = if commit.run_hooks && commit.no_verify {
+            self.vbranches
+                .create_commit_no_verify(
+                    &project_id,
+                    &branch_id,
+                    &commit.message,
+                    commit.ownership.as_ref(),
+                )
+                .await
+        } else {
+            self.vbranches
+                .create_commit(
+                    &project_id,
+                    &branch_id,
+                    &commit.message,
+                    commit.ownership.as_ref(),
+                    commit.run_hooks,
+                )
+                .await
+        }
+        .map_err(Error::from)?;
+        self.refresh(project_id).await;
+        Ok(commit_oid)
+    }
+}
+
+pub fn router(daemon: Daemon) -> Router {
+    Router::new()
+        .route("/v1/projects", get(list_projects))
+        .route(
+            "/v1/projects/:project_id/branches",
+            get(list_branches).post(create_branch),
+        )
+        .route(
+            "/v1/projects/:project_id/branches/:branch_id/apply",
+            post(apply_branch),
+        )
+        .route(
+            "/v1/projects/:project_id/branches/:branch_id/unapply",
+            post(unapply_branch),
+        )
+        .route(
+            "/v1/projects/:project_id/branches/:branch_id/commit",
+            post(commit),
+        )
+        .route(
+            "/v1/projects/:project_id/branches/:branch_id/push",
+            post(push),
+        )
+        .route("/v1/projects/:project_id/status", get(status))
+        .route("/v1/projects/:project_id/base-branch", get(base_branch))
+        .route("/v1/projects/:project_id/fetch", post(fetch))
+        .route("/v1/events", get(stream_events))
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(daemon.clone(), track))
+        .with_state(daemon)
+}
+
+/// Only `GET /metrics`, for the TCP address Prometheus scrapes.
+pub fn metrics_router(daemon: Daemon) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(daemon)
+}
+
+/// Counts and times every request by the route it matched, with the code of the error it
+/// failed with, if any.
+async fn track<B>(
+    State(daemon): State<Daemon>,
+    matched_path: MatchedPath,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let started_at = Instant::now();
+    let response = next.run(request).await;
+    daemon.metrics.observe_request(
+        matched_path.as_str(),
+        response.status().as_u16(),
+        response.extensions().get::<Code>().copied(),
+        started_at.elapsed(),
+    );
+    response
+}
+
+struct ApiError(Error);
+
+impl From<Error> for ApiError {
+    fn from(value: Error) -> Self {
+        Self(value)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self.0 {
+            Error::UserError { .. } | Error::UserErrorWithContext { .. } => StatusCode::BAD_REQUEST,
+            Error::Unknown => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let code = self.0.code();
+        let mut response = (status, Json(self.0)).into_response();
+        // for `track` to count errors by their code
+        response.extensions_mut().insert(code);
+        response
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+async fn list_projects(State(daemon): State<Daemon>) -> ApiResult<Vec<Project>> {
+    Ok(Json(daemon.projects.list().map_err(Error::from)?))
+}
+
+async fn list_branches(
+    State(daemon): State<Daemon>,
+    Path(project_id): Path<ProjectId>,
+) -> ApiResult<Vec<VirtualBranch>> {
+    Ok(Json(daemon.branches(project_id).await?))
+}
+
+async fn create_branch(
+    State(daemon): State<Daemon>,
+    Path(project_id): Path<ProjectId>,
+    Json(create): Json<BranchCreateRequest>,
+) -> ApiResult<BranchId> {
+    let branch_id = daemon
+        .vbranches
+        .create_virtual_branch(&project_id, &create)
+        .await
+        .map_err(Error::from)?;
+    daemon.refresh(project_id).await;
+    Ok(Json(branch_id))
+}
+
+async fn apply_branch(
+    State(daemon): State<Daemon>,
+    Path((project_id, branch_id)): Path<(ProjectId, BranchId)>,
+) -> ApiResult<()> {
+    daemon
+        .vbranches
+        .apply_virtual_branch(&project_id, &branch_id)
+        .await
+        .map_err(Error::from)?;
+    daemon.refresh(project_id).await;
+    Ok(Json(()))
+}
+
+async fn unapply_branch(
+    State(daemon): State<Daemon>,
+    Path((project_id, branch_id)): Path<(ProjectId, BranchId)>,
+) -> ApiResult<()> {
+    daemon
+        .vbranches
+        .unapply_virtual_branch(&project_id, &branch_id)
+        .await
+        .map_err(Error::from)?;
+    daemon.refresh(project_id).await;
+    Ok(Json(()))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitRequest {
+    message: String,
+    /// What to commit. Defaults to everything the branch owns.
+    ownership: Option<Ownership>,
+    #[serde(default = "run_hooks_by_default")]
+    run_hooks: bool,
+    /// Skip the hooks that would run, noting it in a trailer of the message.
+    #[serde(default)]
+    no_verify: bool,
+}
+
+fn run_hooks_by_default() -> bool {
+    true
+}
+
+async fn commit(
+    State(daemon): State<Daemon>,
+    Path((project_id, branch_id)): Path<(ProjectId, BranchId)>,
+    Json(commit): Json<CommitRequest>,
+) -> ApiResult<git::Oid> {
+    Ok(Json(daemon.commit(project_id, branch_id, &commit).await?))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct PushRequest {
+    with_force: bool,
+}
+
+async fn push(
+    State(daemon): State<Daemon>,
+    Path((project_id, branch_id)): Path<(ProjectId, BranchId)>,
+    push: Option<Json<PushRequest>>,
+) -> ApiResult<()> {
+    let push = push.map(|Json(push)| push).unwrap_or_default();
+    daemon
+        .vbranches
+        .push_virtual_branch(&project_id, &branch_id, push.with_force)
+        .await
+        .map_err(Error::from)?;
+    daemon.refresh(project_id).await;
+    Ok(Json(()))
+}
+
+async fn status(
+    State(daemon): State<Daemon>,
+    Path(project_id): Path<ProjectId>,
+) -> ApiResult<output::Status> {
+    Ok(Json(daemon.status(project_id).await?))
+}
+
+async fn base_branch(
+    State(daemon): State<Daemon>,
+    Path(project_id): Path<ProjectId>,
+) -> ApiResult<Option<BaseBranch>> {
+    let base_branch = daemon
+        .vbranches
+        .get_base_branch_data(&project_id)
+        .await
+        .map_err(Error::from)?;
+    Ok(Json(base_branch))
+}
+
+async fn fetch(
+    State(daemon): State<Daemon>,
+    Path(project_id): Path<ProjectId>,
+) -> ApiResult<BaseBranch> {
+    let base_branch = daemon
+        .vbranches
+        .fetch_from_target(&project_id)
+        .await
+        .map_err(Error::from)?;
+    daemon.refresh(project_id).await;
+    Ok(Json(base_branch))
+}
+
+async fn metrics(State(daemon): State<Daemon>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        daemon.metrics.render(),
+    )
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventsQuery {
+    /// Only stream the events of this project.
+    project_id: Option<ProjectId>,
+}
+
+async fn stream_events(
+    State(daemon): State<Daemon>,
+    Query(query): Query<EventsQuery>,
+) -> impl IntoResponse {
+    let rx = daemon.subscribe(query.project_id.as_ref());
+    let project_id = query.project_id;
+    let lines = futures::stream::unfold((rx, daemon), move |(mut rx, daemon)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let line = output::Event::from(&event).to_string();
+                    return Some((Ok::<_, Infallible>(line), (rx, daemon)));
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "events subscriber fell behind");
+                    daemon.catch_up(project_id.as_ref()).await;
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        StreamBody::new(lines),
+    )
+}
+
+/// The status of the project reported by `event`, if it's a virtual branches event.
+pub fn status_from_event(event: &events::Event) -> Option<output::Status> {
+    let events::Event::VirtualBranches { branches, .. } = event else {
+        return None;
+    };
+    Some(output::Status::new(
+        branches
+            .iter()
+            .filter(|branch| branch.active)
+            .map(|branch| output::BranchStatus {
+                name: branch.name.clone(),
+                selected_for_changes: branch.selected_for_changes,
+                files: branch.files.len(),
+                conflicted: branch.conflicted,
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::daemon::{client, transport};
+
+    // serves the api of the daemon of `data_dir` on a socket in it
+    fn serve(data_dir: &path::Path) -> path::PathBuf {
+        let daemon = Daemon::new(data_dir).unwrap();
+        let socket = transport::default_address(data_dir, "daemon");
+        let listener = transport::Listener::bind(&socket).unwrap();
+        tokio::spawn(
+            axum::Server::builder(listener.incoming()).serve(router(daemon).into_make_service()),
+        );
+        socket
+    }
+
+    #[tokio::test]
+    async fn serve_projects_that_fail_to_watch() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let repository = tempfile::tempdir().unwrap();
+        git::Repository::init(repository.path()).unwrap();
+        let project = projects::Controller::from(&data_dir.path().to_path_buf())
+            .add(repository.path())
+            .unwrap();
+        drop(repository);
+
+        let socket = serve(data_dir.path());
+        let projects: Vec<serde_json::Value> = client::get(&socket, "/v1/projects").await.unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0]["id"], project.id.to_string());
+    }
+
+    #[tokio::test]
+    async fn unknown_project_is_a_user_error() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let socket = serve(data_dir.path());
+
+        let error = client::get::<serde_json::Value>(
+            &socket,
+            &format!("/v1/projects/{}/branches", ProjectId::generate()),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("400"), "{error:#}");
+    }
+
+    #[tokio::test]
+    async fn metrics_router_serves_only_metrics() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let data_dir = tempfile::tempdir().unwrap();
+        let daemon = Daemon::new(data_dir.path()).unwrap();
+        daemon
+            .metrics
+            .observe_lock_wait("vbranches.lock", std::time::Duration::from_millis(250));
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+            .serve(metrics_router(daemon).into_make_service());
+        let address = server.local_addr();
+        tokio::spawn(server);
+
+        let get = |path: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+            stream
+                .write_all(format!("GET {path} HTTP/1.0\r\n\r\n").as_bytes())
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let response = get("/metrics").await;
+        assert_eq!(response.split(' ').nth(1), Some("200"), "{response}");
+        assert!(response
+            .to_lowercase()
+            .contains("content-type: text/plain; version=0.0.4"));
+        assert!(response
+            .contains("gitbutler_lock_wait_duration_seconds_count{lock=\"vbranches.lock\"} 1"));
+
+        let response = get("/v1/projects").await;
+        assert_eq!(response.split(' ').nth(1), Some("404"), "{response}");
+    }
+}
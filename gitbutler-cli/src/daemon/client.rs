@@ -0,0 +1,71 @@
+use std::path;
+
+use anyhow::{bail, Context, Result};
+use hyper::{body::HttpBody, header, Body, Request, Response};
+use serde::de::DeserializeOwned;
+
+/// Gets `uri` from the daemon listening on `socket`.
+pub async fn get<T: DeserializeOwned>(socket: &path::Path, uri: &str) -> Result<T> {
+    let response = request(socket, uri).await?;
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .context("failed to read response")?;
+    serde_json::from_slice(&body).context("failed to parse response")
+}
+
+/// Gets `uri` from the daemon listening on `socket`, calling `on_line` with every line
+/// of the response as soon as it arrives.
+pub async fn stream_lines(
+    socket: &path::Path,
+    uri: &str,
+    mut on_line: impl FnMut(&str) -> Result<()>,
+) -> Result<()> {
+    let mut body = request(socket, uri).await?.into_body();
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        buffer.extend_from_slice(&chunk.context("failed to read response")?);
+        while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line = buffer.drain(..=end).collect::<Vec<_>>();
+            on_line(
+                std::str::from_utf8(&line)
+                    .context("response is not utf-8")?
+                    .trim_end(),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+async fn request(socket: &path::Path, uri: &str) -> Result<Response<Body>> {
+    let stream = super::connect(socket)
+        .await
+        .with_context(|| format!("failed to connect to {}", socket.display()))?;
+    let (mut sender, connection) = hyper::client::conn::handshake(stream)
+        .await
+        .context("failed to connect to the daemon")?;
+    tokio::spawn(async move {
+        if let Err(error) = connection.await {
+            tracing::debug!(%error, "daemon connection failed");
+        }
+    });
+
+    let request = Request::get(uri)
+        .header(header::HOST, "localhost")
+        .body(Body::empty())
+        .context("failed to build request")?;
+    let response = sender
+        .send_request(request)
+        .await
+        .context("failed to send request")?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .context("failed to read response")?;
+        bail!(
+            "daemon responded with {status}: {}",
+            String::from_utf8_lossy(&body)
+        );
+    }
+    Ok(response)
+}
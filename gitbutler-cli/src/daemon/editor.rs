@@ -0,0 +1,542 @@
+//! The protocol editor extensions talk to the daemon with, on a socket of its own.
+//!
+//! Every message is a JSON object on a line. The client sends requests,
+//! `{"id": …, "method": …, "params": …}`, and the daemon answers each with
+//! `{"id": …, "result": …}` or `{"id": …, "error": {"code": …, "message": …}}`. The daemon
+//! also sends notifications, `{"method": …, "params": …}`, for what the client subscribed to.
+//!
+//! The first request has to be `hello`, with the protocol versions the client speaks and
+//! the capabilities it wants:
+//!
+//! ```json
+//! {"id": 0, "method": "hello", "params": {"versions": [1], "capabilities": ["status", "diffs", "commits"]}}
+//! ```
+//!
+//! The daemon answers with the highest version both speak and the capabilities it grants,
+//! or with an error before closing the connection if they have no version in common.
+//! Methods of capabilities that weren't granted fail.
+//!
+//! | capability | methods |
+//! |---|---|
+//! | `status` | `status {projectId}`, and `subscribe {projectId}` after which `status {projectId, status}` notifications arrive whenever the branches change, until `unsubscribe {projectId}` |
+//! | `diffs` | `diff {projectId, path?}`, the uncommitted hunks of the applied branches |
+//! | `commits` | `commit {projectId, branchId, message, ownership?, runHooks?}`, the id of the commit |
+//!
+//! Within a version, fields and capabilities may be added but not renamed or removed.
+
+use std::{
+    collections::HashSet,
+    path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use gitbutler_core::{
+    error::{Code, Error},
+    projects::ProjectId,
+    virtual_branches::BranchId,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+    sync::{broadcast, mpsc},
+};
+
+use super::{
+    api::{self, CommitRequest, Daemon},
+    transport,
+};
+use crate::output;
+
+/// The versions of the protocol the daemon speaks, oldest first.
+const VERSIONS: &[u32] = &[1];
+
+/// How many lines are queued for an editor that doesn't read them, beyond that answering
+/// and notifying it wait for it to.
+const QUEUED_LINES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Capability {
+    Status,
+    Diffs,
+    Commits,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    id: serde_json::Value,
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Hello {
+    versions: Vec<u32>,
+    /// Capabilities the daemon doesn't know are left out of the answer rather than failing
+    /// the handshake, so that newer clients can talk to older daemons.
+    #[serde(default)]
+    capabilities: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectParams {
+    project_id: ProjectId,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiffParams {
+    project_id: ProjectId,
+    /// Only the hunks of this file, relative to the project.
+    path: Option<path::PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommitParams {
+    project_id: ProjectId,
+    branch_id: BranchId,
+    #[serde(flatten)]
+    commit: CommitRequest,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileDiff {
+    path: String,
+    branch_id: String,
+    branch_name: String,
+    hunks: Vec<HunkDiff>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HunkDiff {
+    id: String,
+    start: u32,
+    end: u32,
+    diff: String,
+    /// Commit the hunk depends on, if it can't move to another branch.
+    locked_to: Option<String>,
+}
+
+/// Accepts editors on `listener` until the task is dropped.
+pub async fn serve(mut listener: transport::Listener, daemon: Daemon) {
+    loop {
+        match listener.accept().await {
+            Ok(stream) => {
+                let daemon = daemon.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = Connection::run(stream, daemon).await {
+                        tracing::debug!(?error, "editor connection failed");
+                    }
+                });
+            }
+            Err(error) => tracing::error!(%error, "failed to accept editor connection"),
+        }
+    }
+}
+
+struct Connection {
+    daemon: Daemon,
+    capabilities: HashSet<Capability>,
+    subscriptions: Arc<Mutex<HashSet<ProjectId>>>,
+    tx: mpsc::Sender<String>,
+}
+
+impl Connection {
+    async fn run(stream: impl transport::Stream, daemon: Daemon) -> Result<()> {
+        let (reader, mut writer) = tokio::io::split(stream);
+
+        // requests and notifications are written from one place so that lines don't interleave
+        let (tx, mut rx) = mpsc::channel::<String>(QUEUED_LINES);
+        let writing = tokio::spawn(async move {
+            while let Some(mut line) = rx.recv().await {
+                line.push('\n');
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        let mut connection = Self {
+            daemon,
+            capabilities: HashSet::new(),
+            subscriptions: Arc::default(),
+            tx,
+        };
+        let notifying = tokio::spawn(notify(
+            connection.daemon.clone(),
+            Arc::clone(&connection.subscriptions),
+            connection.tx.clone(),
+        ));
+
+        let result = connection.read(reader).await;
+        notifying.abort();
+        drop(connection);
+        // flush what's left before closing
+        let _ = writing.await;
+        result
+    }
+
+    async fn read(&mut self, reader: impl AsyncRead + Unpin) -> Result<()> {
+        let mut lines = BufReader::new(reader).lines();
+        let mut greeted = false;
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .context("failed to read from editor")?
+        {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => request,
+                Err(error) => {
+                    self.send(json!({
+                        "id": null,
+                        "error": invalid(format!("invalid request: {error}")),
+                    }))
+                    .await;
+                    continue;
+                }
+            };
+
+            let result = if greeted {
+                self.handle(&request.method, request.params).await
+            } else if request.method == "hello" {
+                let result = self.hello(request.params);
+                greeted = result.is_ok();
+                result
+            } else {
+                Err(invalid("the first request must be hello".to_string()))
+            };
+
+            let failed_handshake = !greeted;
+            self.send(match result {
+                Ok(result) => json!({ "id": request.id, "result": result }),
+                Err(error) => json!({ "id": request.id, "error": error }),
+            })
+            .await;
+            if failed_handshake {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    async fn send(&self, message: serde_json::Value) {
+        // the writer only stops when the editor went away, which reading notices too
+        let _ = self.tx.send(message.to_string()).await;
+    }
+
+    fn hello(&mut self, params: serde_json::Value) -> Result<serde_json::Value, Error> {
+        let hello: Hello = parse(params)?;
+        let Some(version) = VERSIONS
+            .iter()
+            .rev()
+            .find(|version| hello.versions.contains(version))
+        else {
+            return Err(invalid(format!(
+                "no common protocol version, the daemon speaks {VERSIONS:?}"
+            )));
+        };
+        self.capabilities = hello
+            .capabilities
+            .into_iter()
+            .filter_map(|capability| serde_json::from_value(capability).ok())
+            .collect();
+        Ok(json!({
+            "version": version,
+            "capabilities": self.capabilities,
+            "server": {
+                "name": "gitbutler",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        }))
+    }
+
+    async fn handle(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, Error> {
+        match method {
+            "status" => {
+                self.require(Capability::Status)?;
+                let ProjectParams { project_id } = parse(params)?;
+                Ok(json!(self.daemon.status(project_id).await?))
+            }
+            "subscribe" => {
+                self.require(Capability::Status)?;
+                let ProjectParams { project_id } = parse(params)?;
+                self.subscriptions.lock().unwrap().insert(project_id);
+                Ok(json!(self.daemon.status(project_id).await?))
+            }
+            "unsubscribe" => {
+                self.require(Capability::Status)?;
+                let ProjectParams { project_id } = parse(params)?;
+                self.subscriptions.lock().unwrap().remove(&project_id);
+                Ok(serde_json::Value::Null)
+            }
+            "diff" => {
+                self.require(Capability::Diffs)?;
+                let DiffParams { project_id, path } = parse(params)?;
+                Ok(json!(self.diff(project_id, path.as_deref()).await?))
+            }
+            "commit" => {
+                self.require(Capability::Commits)?;
+                let CommitParams {
+                    project_id,
+                    branch_id,
+                    commit,
+                } = parse(params)?;
+                let commit_oid = self.daemon.commit(project_id, branch_id, &commit).await?;
+                Ok(json!(commit_oid.to_string()))
+            }
+            method => Err(invalid(format!("method {method} not found"))),
+        }
+    }
+
+    fn require(&self, capability: Capability) -> Result<(), Error> {
+        if self.capabilities.contains(&capability) {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "capability {} was not negotiated",
+                json!(capability)
+            )))
+        }
+    }
+
+    async fn diff(
+        &self,
+        project_id: ProjectId,
+        path: Option<&path::Path>,
+    ) -> Result<Vec<FileDiff>, Error> {
+        let branches = self.daemon.branches(project_id).await?;
+        Ok(branches
+            .iter()
+            .filter(|branch| branch.active)
+            .flat_map(|branch| {
+                branch
+                    .files
+                    .iter()
+                    .filter(|file| path.map_or(true, |path| file.path == path))
+                    .map(|file| FileDiff {
+                        path: file.path.display().to_string(),
+                        branch_id: branch.id.to_string(),
+                        branch_name: branch.name.clone(),
+                        hunks: file
+                            .hunks
+                            .iter()
+                            .map(|hunk| HunkDiff {
+                                id: hunk.id.clone(),
+                                start: hunk.start,
+                                end: hunk.end,
+                                diff: hunk.diff.clone(),
+                                locked_to: hunk.locked_to.map(|oid| oid.to_string()),
+                            })
+                            .collect(),
+                    })
+            })
+            .collect())
+    }
+}
+
+/// Sends the status of the subscribed projects whenever the watchers report it.
+async fn notify(
+    daemon: Daemon,
+    subscriptions: Arc<Mutex<HashSet<ProjectId>>>,
+    tx: mpsc::Sender<String>,
+) {
+    let mut rx = daemon.subscribe(None);
+    loop {
+        let event = match rx.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "editor fell behind on events");
+                let subscribed = subscriptions.lock().unwrap().clone();
+                for project_id in subscribed {
+                    daemon.catch_up(Some(&project_id)).await;
+                }
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let project_id = *event.project_id();
+        if !subscriptions.lock().unwrap().contains(&project_id) {
+            continue;
+        }
+        let Some(status) = api::status_from_event(&event) else {
+            continue;
+        };
+        let notification = Notification {
+            method: "status",
+            params: StatusParams { project_id, status },
+        };
+        let Ok(line) = serde_json::to_string(&notification) else {
+            continue;
+        };
+        if tx.send(line).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Notification<T> {
+    method: &'static str,
+    params: T,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusParams {
+    project_id: ProjectId,
+    status: output::Status,
+}
+
+fn parse<T: serde::de::DeserializeOwned>(params: serde_json::Value) -> Result<T, Error> {
+    serde_json::from_value(params).map_err(|error| invalid(format!("invalid params: {error}")))
+}
+
+fn invalid(message: String) -> Error {
+    Error::UserError {
+        code: Code::Validation,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{DuplexStream, Lines, ReadHalf, WriteHalf};
+
+    use super::*;
+
+    struct Editor {
+        lines: Lines<BufReader<ReadHalf<DuplexStream>>>,
+        writer: WriteHalf<DuplexStream>,
+    }
+
+    impl Editor {
+        fn connect(data_dir: &path::Path) -> Self {
+            let daemon = Daemon::new(data_dir).unwrap();
+            let (client, server) = tokio::io::duplex(4096);
+            tokio::spawn(Connection::run(server, daemon));
+            let (reader, writer) = tokio::io::split(client);
+            Self {
+                lines: BufReader::new(reader).lines(),
+                writer,
+            }
+        }
+
+        async fn request(&mut self, request: serde_json::Value) -> serde_json::Value {
+            self.writer
+                .write_all(format!("{request}\n").as_bytes())
+                .await
+                .unwrap();
+            let line = self.lines.next_line().await.unwrap().unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+
+        async fn is_closed(&mut self) -> bool {
+            self.lines.next_line().await.unwrap().is_none()
+        }
+    }
+
+    #[tokio::test]
+    async fn hello_negotiates_version_and_capabilities() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut editor = Editor::connect(data_dir.path());
+
+        let response = editor
+            .request(json!({
+                "id": 0,
+                "method": "hello",
+                "params": { "versions": [1, 2], "capabilities": ["status", "telepathy"] },
+            }))
+            .await;
+        assert_eq!(response["id"], 0);
+        assert_eq!(response["result"]["version"], 1);
+        assert_eq!(response["result"]["capabilities"], json!(["status"]));
+
+        // the methods of a capability that wasn't asked for fail
+        let response = editor
+            .request(json!({
+                "id": 1,
+                "method": "diff",
+                "params": { "projectId": ProjectId::generate() },
+            }))
+            .await;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], Code::Validation.to_string());
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("diffs"));
+
+        let response = editor.request(json!({ "id": 2, "method": "dance" })).await;
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("dance"));
+    }
+
+    #[tokio::test]
+    async fn first_request_must_be_hello() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut editor = Editor::connect(data_dir.path());
+
+        let response = editor
+            .request(json!({
+                "id": 0,
+                "method": "status",
+                "params": { "projectId": ProjectId::generate() },
+            }))
+            .await;
+        assert_eq!(response["id"], 0);
+        assert_eq!(response["error"]["code"], Code::Validation.to_string());
+        assert!(editor.is_closed().await);
+    }
+
+    #[tokio::test]
+    async fn no_common_version() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut editor = Editor::connect(data_dir.path());
+
+        let response = editor
+            .request(json!({ "id": 0, "method": "hello", "params": { "versions": [99] } }))
+            .await;
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("no common protocol version"));
+        assert!(editor.is_closed().await);
+    }
+
+    #[tokio::test]
+    async fn invalid_request_is_answered() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut editor = Editor::connect(data_dir.path());
+
+        editor.writer.write_all(b"not json\n").await.unwrap();
+        let line = editor.lines.next_line().await.unwrap().unwrap();
+        let response: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(response["id"], serde_json::Value::Null);
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request"));
+
+        // and the connection is still waiting for hello
+        let response = editor
+            .request(json!({ "id": 0, "method": "hello", "params": { "versions": [1] } }))
+            .await;
+        assert_eq!(response["result"]["version"], 1);
+    }
+}
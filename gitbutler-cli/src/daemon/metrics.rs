@@ -0,0 +1,293 @@
+//! What the daemon measures, served at `GET /metrics` in the text format of Prometheus for
+//! self-hosters to monitor it like any other service.
+//!
+//! - `gitbutler_requests_total{route,status}` and `gitbutler_request_errors_total{route,code}`
+//!   count the requests to the api, and the errors they failed with by their code.
+//! - `gitbutler_request_duration_seconds{route}` is how long requests took.
+//! - `gitbutler_fetch_duration_seconds{kind}` is how long fetches took, whoever asked for them.
+//! - `gitbutler_status_compute_duration_seconds` is how long computing the branches and status
+//!   of a project took, mostly by the watchers after a change.
+//! - `gitbutler_queue_wait_duration_seconds{kind}` is how long operations waited for their
+//!   turn, while the one before them on the same project ran.
+//! - `gitbutler_lock_wait_duration_seconds{lock}` is how long acquiring the lock files of the
+//!   data of projects took, which other processes like the app hold too.
+//!
+//! The daemon serves them on its socket, and over TCP with `--metrics-addr` for Prometheus,
+//! which can't scrape a socket.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use gitbutler_core::{error::Code, virtual_branches::FinishedOperation};
+
+// upper bounds of the buckets of histograms, in seconds
+const BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    requests: BTreeMap<(String, u16), u64>,
+    request_errors: BTreeMap<(String, String), u64>,
+    request_durations: BTreeMap<String, Histogram>,
+    fetch_durations: BTreeMap<&'static str, Histogram>,
+    status_compute_durations: Histogram,
+    queue_waits: BTreeMap<&'static str, Histogram>,
+    lock_waits: BTreeMap<String, Histogram>,
+}
+
+struct Histogram {
+    // as many as there are buckets, not cumulative
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.counts[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels}le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels}le=\"+Inf\"}} {}", self.count);
+        let labels = labels.trim_end_matches(',');
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+impl Metrics {
+    /// A request to `route`, like `/v1/projects/:project_id/status`, that responded with
+    /// `status`, failing with `code` if it failed.
+    pub fn observe_request(&self, route: &str, status: u16, code: Option<Code>, took: Duration) {
+        let mut inner = self.inner();
+        *inner
+            .requests
+            .entry((route.to_string(), status))
+            .or_default() += 1;
+        if let Some(code) = code {
+            *inner
+                .request_errors
+                .entry((route.to_string(), code.to_string()))
+                .or_default() += 1;
+        }
+        inner
+            .request_durations
+            .entry(route.to_string())
+            .or_default()
+            .observe(took);
+    }
+
+    /// An operation of the virtual branches of a project, how long it waited for its turn and,
+    /// for fetches and listing the branches, how long it ran.
+    pub fn observe_operation(&self, operation: &FinishedOperation) {
+        let mut inner = self.inner();
+        inner
+            .queue_waits
+            .entry(operation.kind)
+            .or_default()
+            .observe(operation.waited);
+        if operation.kind == "list_virtual_branches" {
+            inner.status_compute_durations.observe(operation.ran);
+        }
+        if operation.kind.starts_with("fetch_") {
+            inner
+                .fetch_durations
+                .entry(operation.kind)
+                .or_default()
+                .observe(operation.ran);
+        }
+    }
+
+    /// Acquiring the lock file `lock`, like `vbranches.lock`.
+    pub fn observe_lock_wait(&self, lock: &str, took: Duration) {
+        self.inner()
+            .lock_waits
+            .entry(lock.to_string())
+            .or_default()
+            .observe(took);
+    }
+
+    pub fn render(&self) -> String {
+        let inner = self.inner();
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "# HELP gitbutler_requests_total Requests to the api.\n# TYPE gitbutler_requests_total counter"
+        );
+        for ((route, status), count) in &inner.requests {
+            let _ = writeln!(
+                out,
+                "gitbutler_requests_total{{route=\"{}\",status=\"{status}\"}} {count}",
+                escape(route)
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP gitbutler_request_errors_total Requests to the api that failed, by error code.\n# TYPE gitbutler_request_errors_total counter"
+        );
+        for ((route, code), count) in &inner.request_errors {
+            let _ = writeln!(
+                out,
+                "gitbutler_request_errors_total{{route=\"{}\",code=\"{}\"}} {count}",
+                escape(route),
+                escape(code)
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP gitbutler_request_duration_seconds How long requests to the api took.\n# TYPE gitbutler_request_duration_seconds histogram"
+        );
+        for (route, histogram) in &inner.request_durations {
+            histogram.render(
+                &mut out,
+                "gitbutler_request_duration_seconds",
+                &format!("route=\"{}\",", escape(route)),
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP gitbutler_fetch_duration_seconds How long fetches took.\n# TYPE gitbutler_fetch_duration_seconds histogram"
+        );
+        for (kind, histogram) in &inner.fetch_durations {
+            histogram.render(
+                &mut out,
+                "gitbutler_fetch_duration_seconds",
+                &format!("kind=\"{kind}\","),
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP gitbutler_status_compute_duration_seconds How long computing the status of a project took.\n# TYPE gitbutler_status_compute_duration_seconds histogram"
+        );
+        inner.status_compute_durations.render(
+            &mut out,
+            "gitbutler_status_compute_duration_seconds",
+            "",
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP gitbutler_queue_wait_duration_seconds How long operations waited for their turn.\n# TYPE gitbutler_queue_wait_duration_seconds histogram"
+        );
+        for (kind, histogram) in &inner.queue_waits {
+            histogram.render(
+                &mut out,
+                "gitbutler_queue_wait_duration_seconds",
+                &format!("kind=\"{kind}\","),
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP gitbutler_lock_wait_duration_seconds How long acquiring the lock files of projects took.\n# TYPE gitbutler_lock_wait_duration_seconds histogram"
+        );
+        for (lock, histogram) in &inner.lock_waits {
+            histogram.render(
+                &mut out,
+                "gitbutler_lock_wait_duration_seconds",
+                &format!("lock=\"{}\",", escape(lock)),
+            );
+        }
+
+        out
+    }
+
+    fn inner(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// label values are quoted, with their backslashes, quotes and newlines escaped
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets() {
+        let metrics = Metrics::default();
+        metrics.observe_lock_wait("vbranches.lock", Duration::from_millis(250));
+        metrics.observe_lock_wait("vbranches.lock", Duration::from_secs(2));
+        // past the last bucket, only counted in +Inf
+        metrics.observe_lock_wait("vbranches.lock", Duration::from_secs(90));
+
+        let rendered = metrics.render();
+        let lines = rendered
+            .lines()
+            .filter(|line| line.starts_with("gitbutler_lock_wait_duration_seconds"))
+            .collect::<Vec<_>>();
+        let bucket = |le: &str| {
+            format!("gitbutler_lock_wait_duration_seconds_bucket{{lock=\"vbranches.lock\",le=\"{le}\"}}")
+        };
+        assert_eq!(lines.len(), BUCKETS.len() + 3);
+        assert!(lines.contains(&format!("{} 0", bucket("0.1")).as_str()));
+        assert!(lines.contains(&format!("{} 1", bucket("0.25")).as_str()));
+        assert!(lines.contains(&format!("{} 1", bucket("1")).as_str()));
+        assert!(lines.contains(&format!("{} 2", bucket("2.5")).as_str()));
+        assert!(lines.contains(&format!("{} 2", bucket("60")).as_str()));
+        assert!(lines.contains(&format!("{} 3", bucket("+Inf")).as_str()));
+        assert!(lines
+            .contains(&"gitbutler_lock_wait_duration_seconds_sum{lock=\"vbranches.lock\"} 92.25"));
+        assert!(lines
+            .contains(&"gitbutler_lock_wait_duration_seconds_count{lock=\"vbranches.lock\"} 3"));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let metrics = Metrics::default();
+        metrics.observe_request(
+            "/a\"quoted\\route\n",
+            400,
+            Some(Code::Validation),
+            Duration::ZERO,
+        );
+
+        let rendered = metrics.render();
+        assert!(rendered.lines().any(|line| line
+            == "gitbutler_requests_total{route=\"/a\\\"quoted\\\\route\\n\",status=\"400\"} 1"));
+        assert!(rendered.lines().any(|line| line.starts_with(
+            "gitbutler_request_errors_total{route=\"/a\\\"quoted\\\\route\\n\",code="
+        )));
+    }
+}
@@ -0,0 +1,203 @@
+//! The local connections the daemon is reached by: unix sockets, that only the current user
+//! can connect to, and named pipes on Windows, like `\\.\pipe\gitbutler-daemon-<user>`,
+//! that only accept clients of this machine.
+
+use std::{
+    io, path,
+    pin::Pin,
+    task::{self, Poll},
+};
+
+use anyhow::{Context, Result};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
+};
+
+/// How many connections are accepted ahead of the server taking them.
+const ACCEPT_BACKLOG: usize = 16;
+
+/// Where the daemon is reached by for what `name` is, like `daemon` or `editor`, with the
+/// data in `data_dir`.
+#[cfg(unix)]
+pub fn default_address(data_dir: &path::Path, name: &str) -> path::PathBuf {
+    data_dir.join(format!("{name}.sock"))
+}
+
+/// Where the daemon is reached by for what `name` is, like `daemon` or `editor`. Pipes
+/// are named for the whole machine, so they are named after the user too.
+#[cfg(windows)]
+pub fn default_address(_data_dir: &path::Path, name: &str) -> path::PathBuf {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    path::PathBuf::from(format!(r"\\.\pipe\gitbutler-{name}-{user}"))
+}
+
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Stream for T {}
+
+#[cfg(unix)]
+pub type Connection = tokio::net::UnixStream;
+
+#[cfg(windows)]
+pub type Connection = tokio::net::windows::named_pipe::NamedPipeServer;
+
+#[cfg(unix)]
+pub struct Listener(tokio::net::UnixListener);
+
+#[cfg(windows)]
+pub struct Listener {
+    name: path::PathBuf,
+    // the instance of the pipe the next client connects to
+    next: tokio::net::windows::named_pipe::NamedPipeServer,
+}
+
+impl Listener {
+    /// Listens on the socket `address`, replacing it if it's left over from a daemon that
+    /// didn't shut down cleanly.
+    #[cfg(unix)]
+    pub fn bind(address: &path::Path) -> Result<Self> {
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        use nix::sys::stat;
+
+        if address.exists() {
+            if std::os::unix::net::UnixStream::connect(address).is_ok() {
+                anyhow::bail!("a daemon is already listening on {}", address.display());
+            }
+            fs::remove_file(address)
+                .with_context(|| format!("failed to remove stale socket {}", address.display()))?;
+        }
+        if let Some(parent) = address.parent() {
+            fs::create_dir_all(parent).context("failed to create socket directory")?;
+        }
+
+        // the socket is created with the permissions the umask leaves, so no other user can
+        // connect to it before it's restricted
+        let umask = stat::umask(stat::Mode::from_bits_truncate(0o077));
+        let listener = tokio::net::UnixListener::bind(address);
+        stat::umask(umask);
+        let listener =
+            listener.with_context(|| format!("failed to bind to {}", address.display()))?;
+        fs::set_permissions(address, fs::Permissions::from_mode(0o600))
+            .context("failed to restrict socket permissions")?;
+        Ok(Self(listener))
+    }
+
+    /// Listens on the pipe `address`. Creating its first instance fails if another process
+    /// already did, a daemon that's running or one that is pretending to be.
+    #[cfg(windows)]
+    pub fn bind(address: &path::Path) -> Result<Self> {
+        let next = tokio::net::windows::named_pipe::ServerOptions::new()
+            .first_pipe_instance(true)
+            .reject_remote_clients(true)
+            .create(address)
+            .with_context(|| format!("failed to create pipe {}", address.display()))?;
+        Ok(Self {
+            name: address.to_path_buf(),
+            next,
+        })
+    }
+
+    #[cfg(unix)]
+    pub async fn accept(&mut self) -> io::Result<Connection> {
+        let (stream, _) = self.0.accept().await?;
+        Ok(stream)
+    }
+
+    #[cfg(windows)]
+    pub async fn accept(&mut self) -> io::Result<Connection> {
+        self.next.connect().await?;
+        let next = tokio::net::windows::named_pipe::ServerOptions::new()
+            .reject_remote_clients(true)
+            .create(&self.name)?;
+        Ok(std::mem::replace(&mut self.next, next))
+    }
+
+    /// The connections to the listener, as the server of the api takes them.
+    pub fn incoming(mut self) -> Incoming {
+        let (tx, rx) = mpsc::channel(ACCEPT_BACKLOG);
+        tokio::spawn(async move {
+            loop {
+                let connection = self.accept().await;
+                if tx.send(connection).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Incoming(rx)
+    }
+}
+
+/// Removes the socket `address` once the daemon stopped listening on it.
+#[cfg(unix)]
+pub fn remove(address: &path::Path) {
+    if let Err(error) = std::fs::remove_file(address) {
+        tracing::warn!(%error, socket = %address.display(), "failed to remove socket");
+    }
+}
+
+/// Pipes are gone with their last instance, there is nothing to remove.
+#[cfg(windows)]
+pub fn remove(_address: &path::Path) {}
+
+/// Connects to the daemon listening on `address`.
+#[cfg(unix)]
+pub async fn connect(address: &path::Path) -> io::Result<impl Stream> {
+    tokio::net::UnixStream::connect(address).await
+}
+
+/// Connects to the daemon listening on `address`.
+#[cfg(windows)]
+#[allow(clippy::unused_async)]
+pub async fn connect(address: &path::Path) -> io::Result<impl Stream> {
+    tokio::net::windows::named_pipe::ClientOptions::new().open(address)
+}
+
+pub struct Incoming(mpsc::Receiver<io::Result<Connection>>);
+
+impl hyper::server::accept::Accept for Incoming {
+    type Conn = Connection;
+    type Error = io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn only_the_user_can_connect() {
+        let dir = tempfile::tempdir().unwrap();
+        let address = default_address(dir.path(), "daemon");
+
+        let listener = Listener::bind(&address).unwrap();
+        let mode = std::fs::metadata(&address).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        assert!(connect(&address).await.is_ok());
+        // another daemon can't take over the socket
+        assert!(Listener::bind(&address).is_err());
+        drop(listener);
+    }
+
+    #[tokio::test]
+    async fn replace_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let address = default_address(dir.path(), "daemon");
+
+        drop(Listener::bind(&address).unwrap());
+        assert!(address.exists());
+
+        let _listener = Listener::bind(&address).unwrap();
+        assert!(connect(&address).await.is_ok());
+    }
+}
@@ -0,0 +1,81 @@
+//! Exit codes, so that scripts can tell failures apart without parsing messages.
+//!
+//! `2` is left to clap, which exits with it when the arguments don't parse.
+
+use std::process::ExitCode;
+
+use gitbutler_core::error::{Code, Error};
+
+/// Anything that doesn't fall in one of the classes below.
+const FAILURE: u8 = 1;
+/// The remote rejected the credentials, or there were none to try.
+const AUTH: u8 = 3;
+/// The remote couldn't be reached.
+const NETWORK: u8 = 4;
+/// The change conflicts with the workspace or with the applied branches.
+const CONFLICT: u8 = 5;
+/// A git hook rejected the commit or the push.
+const HOOK: u8 = 6;
+
+/// Returns the exit code for `error`, classified by the engine's error code it carries.
+pub fn code(error: &anyhow::Error) -> ExitCode {
+    ExitCode::from(class(error))
+}
+
+fn class(error: &anyhow::Error) -> u8 {
+    error
+        .chain()
+        .find_map(|cause| {
+            cause
+                .downcast_ref::<Error>()
+                .map(Error::code)
+                .filter(|code| *code != Code::Unknown)
+        })
+        .map_or(FAILURE, |code| match code {
+            Code::ProjectGitAuth => AUTH,
+            Code::ProjectGitRemote | Code::Network => NETWORK,
+            Code::ProjectConflict => CONFLICT,
+            Code::PreCommitHook | Code::CommitMsgHook | Code::PrePushHook => HOOK,
+            _ => FAILURE,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    fn user_error(code: Code) -> anyhow::Error {
+        Error::UserError {
+            code,
+            message: "message".to_string(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn classified_by_the_code_of_the_engine() {
+        assert_eq!(class(&user_error(Code::ProjectGitAuth)), AUTH);
+        assert_eq!(class(&user_error(Code::Network)), NETWORK);
+        assert_eq!(class(&user_error(Code::ProjectGitRemote)), NETWORK);
+        assert_eq!(class(&user_error(Code::ProjectConflict)), CONFLICT);
+        assert_eq!(class(&user_error(Code::PrePushHook)), HOOK);
+        assert_eq!(class(&user_error(Code::Validation)), FAILURE);
+        assert_eq!(class(&anyhow::anyhow!("failure")), FAILURE);
+    }
+
+    #[test]
+    fn classified_under_context() {
+        let error = Err::<(), _>(user_error(Code::ProjectConflict))
+            .context("failed to apply branch")
+            .unwrap_err();
+        assert_eq!(class(&error), CONFLICT);
+
+        // an unknown error doesn't hide a known one under it
+        let error = Err::<(), _>(user_error(Code::Network))
+            .map_err(|error| error.context(Error::Unknown))
+            .unwrap_err();
+        assert_eq!(class(&error), NETWORK);
+    }
+}
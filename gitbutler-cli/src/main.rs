@@ -0,0 +1,90 @@
+//! # GitButler CLI
+//!
+//! `but` drives the GitButler virtual branch engine from the terminal.
+//! It reads and writes the same project data as the desktop app, so
+//! both can be used on the same project interchangeably.
+//!
+//! Projects have to be added to GitButler (through the app) before the
+//! CLI can operate on them.
+//!
+//! For unattended use, `--no-prompt` keeps git and credential helpers from waiting for
+//! input, `GITBUTLER_TOKEN` authenticates with https remotes on `GITBUTLER_TOKEN_HOST`
+//! (github.com by default) instead of the credentials of the app, and the exit code tells
+//! the class of a failure, see [`exit`].
+
+mod app;
+mod args;
+mod command;
+mod daemon;
+mod exit;
+mod output;
+
+use std::process::ExitCode;
+
+use anyhow::Context;
+use clap::Parser;
+
+fn main() -> anyhow::Result<ExitCode> {
+    gitbutler_git::git2::enable_extensions().context("failed to enable git extensions")?;
+
+    let args = args::Args::parse();
+    // the environment is only changed while there is no other thread to read it
+    if args.no_prompt {
+        disable_prompts();
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("failed to start runtime")?
+        .block_on(start(args))
+}
+
+async fn start(args: args::Args) -> anyhow::Result<ExitCode> {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_env("GITBUTLER_LOG")
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn")),
+        )
+        .init();
+
+    let out = output::Output::new(args.json);
+    let result = run(args, out).await;
+    match result {
+        Ok(()) => Ok(ExitCode::SUCCESS),
+        Err(error) => {
+            out.print_error(&error)?;
+            Ok(exit::code(&error))
+        }
+    }
+}
+
+/// Keeps git and credential helpers from asking for input. With nobody there to answer,
+/// they would hang instead of failing.
+fn disable_prompts() {
+    std::env::set_var("GIT_TERMINAL_PROMPT", "0");
+    std::env::set_var("GCM_INTERACTIVE", "never");
+}
+
+async fn run(args: args::Args, out: output::Output) -> anyhow::Result<()> {
+    // the daemon serves every project, so there is none to resolve
+    if let args::Command::Daemon {
+        socket,
+        editor_socket,
+        metrics_addr,
+    } = &args.command
+    {
+        let data_dir = app::data_dir(&args)?;
+        return daemon::run(
+            &data_dir,
+            socket.as_deref(),
+            editor_socket.as_deref(),
+            *metrics_addr,
+        )
+        .await;
+    }
+
+    let app = app::App::new(&args)?;
+    command::run(&app, out, args.command).await
+}
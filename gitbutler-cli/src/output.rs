@@ -0,0 +1,758 @@
+//! What commands print.
+//!
+//! Every command prints a single value, as text for humans or, with `--json`, as a JSON
+//! object tagged with [`SCHEMA_VERSION`]. The JSON structures are defined here rather than
+//! borrowed from the engine so that they only change on purpose: fields may be added
+//! within a version, but renaming or removing one bumps it.
+
+use std::{collections::HashMap, fmt, io::Write, path};
+
+use anyhow::Result;
+use chrono::TimeZone;
+use gitbutler_core::{events, git, virtual_branches};
+use serde::{Deserialize, Serialize};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    json: bool,
+}
+
+impl Output {
+    pub fn new(json: bool) -> Self {
+        Self { json }
+    }
+
+    pub fn is_json(self) -> bool {
+        self.json
+    }
+
+    pub fn print<T: Serialize + fmt::Display>(self, value: &T) -> Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string(&Versioned::new(value))?);
+        } else {
+            print!("{value}");
+        }
+        Ok(())
+    }
+
+    /// Prints `error` on stderr or, in JSON mode, on stdout like any other outcome.
+    pub fn print_error(self, error: &anyhow::Error) -> Result<()> {
+        if !self.json {
+            writeln!(std::io::stderr(), "error: {error:#}")?;
+            return Ok(());
+        }
+
+        let code = error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<gitbutler_core::error::Error>())
+            .map(|error| error.code().to_string());
+        let failure = Failure {
+            error: ErrorDetails {
+                code,
+                message: format!("{error:#}"),
+            },
+        };
+        println!("{}", serde_json::to_string(&Versioned::new(&failure))?);
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Versioned<'a, T> {
+    schema_version: u32,
+    #[serde(flatten)]
+    value: &'a T,
+}
+
+impl<'a, T> Versioned<'a, T> {
+    fn new(value: &'a T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            value,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Failure {
+    error: ErrorDetails,
+}
+
+#[derive(Serialize)]
+struct ErrorDetails {
+    /// The engine's error code, when the error comes from it.
+    code: Option<String>,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Branch {
+    pub id: String,
+    pub name: String,
+    pub applied: bool,
+    pub selected_for_changes: bool,
+    pub conflicted: bool,
+    pub head: String,
+    pub upstream: Option<String>,
+    /// One `path:hunk,hunk` entry per owned file, the same notation the engine uses.
+    pub ownership: Vec<String>,
+    pub files: Vec<File>,
+    pub commits: Vec<Commit>,
+    /// Whether an unapplied branch would apply without conflicts. Only computed on request.
+    pub can_apply: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct File {
+    pub path: String,
+    pub conflicted: bool,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hunk {
+    pub id: String,
+    pub start: u32,
+    pub end: u32,
+    /// Commit the hunk depends on, if it can't move to another branch.
+    pub locked_to: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Commit {
+    pub id: String,
+    pub description: String,
+    pub is_remote: bool,
+    pub is_integrated: bool,
+}
+
+impl From<&virtual_branches::VirtualBranch> for Branch {
+    fn from(branch: &virtual_branches::VirtualBranch) -> Self {
+        Self {
+            id: branch.id.to_string(),
+            name: branch.name.clone(),
+            applied: branch.active,
+            selected_for_changes: branch.selected_for_changes,
+            conflicted: branch.conflicted,
+            head: branch.head.to_string(),
+            upstream: branch
+                .upstream
+                .as_ref()
+                .map(|upstream| upstream.name.to_string()),
+            ownership: branch
+                .ownership
+                .files
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            files: branch.files.iter().map(Into::into).collect(),
+            commits: branch.commits.iter().map(Into::into).collect(),
+            can_apply: None,
+        }
+    }
+}
+
+impl From<&virtual_branches::VirtualBranchFile> for File {
+    fn from(file: &virtual_branches::VirtualBranchFile) -> Self {
+        Self {
+            path: file.path.display().to_string(),
+            conflicted: file.conflicted,
+            hunks: file
+                .hunks
+                .iter()
+                .map(|hunk| Hunk {
+                    id: hunk.id.clone(),
+                    start: hunk.start,
+                    end: hunk.end,
+                    locked_to: hunk.locked_to.map(|oid| oid.to_string()),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&virtual_branches::VirtualBranchCommit> for Commit {
+    fn from(commit: &virtual_branches::VirtualBranchCommit) -> Self {
+        Self {
+            id: commit.id.to_string(),
+            description: commit.description.clone(),
+            is_remote: commit.is_remote,
+            is_integrated: commit.is_integrated,
+        }
+    }
+}
+
+/// Prints a line per branch, and with `{:#}` the files, hunks and commits under it too.
+impl fmt::Display for Branch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = if self.selected_for_changes { '*' } else { ' ' };
+        let state = if self.conflicted {
+            "conflicted"
+        } else if self.applied {
+            "applied"
+        } else {
+            "unapplied"
+        };
+        writeln!(
+            f,
+            "{marker} {} {:<10} {} ({} files, {} commits)",
+            self.id,
+            state,
+            self.name,
+            self.files.len(),
+            self.commits.len()
+        )?;
+        if !f.alternate() {
+            return Ok(());
+        }
+        match self.can_apply {
+            Some(true) => writeln!(f, "    applies cleanly")?,
+            Some(false) => writeln!(f, "    conflicts with the applied branches")?,
+            None => {}
+        }
+        for file in &self.files {
+            let marker = if file.conflicted { 'C' } else { 'M' };
+            writeln!(f, "    {marker} {}", file.path)?;
+            for hunk in &file.hunks {
+                match &hunk.locked_to {
+                    Some(commit_id) => writeln!(
+                        f,
+                        "        @{}-{} locked to {}",
+                        hunk.start,
+                        hunk.end,
+                        short(commit_id)
+                    )?,
+                    None => writeln!(f, "        @{}-{}", hunk.start, hunk.end)?,
+                }
+            }
+        }
+        for commit in &self.commits {
+            let summary = commit.description.lines().next().unwrap_or_default();
+            let state = if commit.is_integrated {
+                " (integrated)"
+            } else if commit.is_remote {
+                " (pushed)"
+            } else {
+                ""
+            };
+            writeln!(f, "    {} {summary}{state}", short(&commit.id))?;
+        }
+        Ok(())
+    }
+}
+
+fn short(id: &str) -> &str {
+    id.get(..7).unwrap_or(id)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Branches {
+    pub branches: Vec<Branch>,
+    #[serde(skip)]
+    pub verbose: bool,
+}
+
+impl fmt::Display for Branches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for branch in &self.branches {
+            if self.verbose {
+                write!(f, "{branch:#}")?;
+            } else {
+                write!(f, "{branch}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A branch as it is after a command changed it.
+#[derive(Debug, Serialize)]
+pub struct Changed {
+    pub branch: Branch,
+}
+
+impl fmt::Display for Changed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.branch.fmt(f)
+    }
+}
+
+/// Whether a branch would apply, without applying it.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Preview {
+    pub branch_id: String,
+    pub name: String,
+    pub can_apply: bool,
+}
+
+impl fmt::Display for Preview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.can_apply {
+            writeln!(f, "{} applies cleanly", self.name)
+        } else {
+            writeln!(f, "{} conflicts with the applied branches", self.name)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Committed {
+    pub branch_id: String,
+    pub commit_id: String,
+}
+
+impl fmt::Display for Committed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.commit_id)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pushed {
+    pub branch_id: String,
+    pub upstream: Option<String>,
+}
+
+impl fmt::Display for Pushed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.upstream {
+            Some(upstream) => writeln!(f, "pushed to {upstream}"),
+            None => writeln!(f, "pushed"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub name: String,
+    /// The commit the tag points to.
+    pub target: String,
+    pub message: Option<String>,
+    pub signed: bool,
+}
+
+impl From<&virtual_branches::Tag> for Tag {
+    fn from(tag: &virtual_branches::Tag) -> Self {
+        Self {
+            name: tag.name.clone(),
+            target: tag.target.to_string(),
+            message: tag.message.clone(),
+            signed: tag.signed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tags {
+    pub tags: Vec<Tag>,
+}
+
+impl fmt::Display for Tags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for tag in &self.tags {
+            write!(f, "{} {}", tag.name, short(&tag.target))?;
+            if let Some(subject) = tag.message.as_deref().and_then(|m| m.lines().next()) {
+                write!(f, " {subject}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// When the operation started, in milliseconds since the epoch.
+    pub timestamp_ms: u128,
+    /// The name of the operation, like `create_commit`.
+    pub kind: String,
+    /// Whether it was asked for by the user, or by GitButler itself.
+    pub background: bool,
+    pub user: Option<String>,
+    pub branches: Vec<AuditBranch>,
+    /// The commit the base branch moved to, if it did.
+    pub base: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditBranch {
+    pub id: String,
+    pub name: String,
+    /// The head of the branch before, none if the operation created it.
+    pub before: Option<String>,
+    /// The head of the branch after, none if the operation deleted it.
+    pub after: Option<String>,
+    /// The commits the operation made on the branch, the most recent first.
+    pub commits: Vec<String>,
+}
+
+impl From<&virtual_branches::AuditEntry> for AuditEntry {
+    fn from(entry: &virtual_branches::AuditEntry) -> Self {
+        Self {
+            timestamp_ms: entry.timestamp_ms,
+            kind: entry.kind.clone(),
+            background: entry.requested_by == virtual_branches::Requester::Background,
+            user: entry.user.clone(),
+            branches: entry
+                .branches
+                .iter()
+                .map(|change| AuditBranch {
+                    id: change.branch_id.to_string(),
+                    name: change
+                        .after
+                        .as_ref()
+                        .or(change.before.as_ref())
+                        .map(|state| state.name.clone())
+                        .unwrap_or_default(),
+                    before: change.before.as_ref().map(|state| state.head.to_string()),
+                    after: change.after.as_ref().map(|state| state.head.to_string()),
+                    commits: change.commits.iter().map(ToString::to_string).collect(),
+                })
+                .collect(),
+            base: entry
+                .base
+                .as_ref()
+                .and_then(|base| base.after)
+                .map(|after| after.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditEntries {
+    pub entries: Vec<AuditEntry>,
+}
+
+impl fmt::Display for AuditEntries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            let time = i64::try_from(entry.timestamp_ms)
+                .ok()
+                .and_then(|millis| chrono::Local.timestamp_millis_opt(millis).single())
+                .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            write!(f, "{time} {}", entry.kind)?;
+            if entry.background {
+                write!(f, " (background)")?;
+            }
+            writeln!(f)?;
+            if let Some(base) = &entry.base {
+                writeln!(f, "  base moved to {}", short(base))?;
+            }
+            for branch in &entry.branches {
+                write!(f, "  {}: ", branch.name)?;
+                match (&branch.before, &branch.after) {
+                    (None, Some(after)) => write!(f, "created at {}", short(after))?,
+                    (Some(before), None) => write!(f, "deleted at {}", short(before))?,
+                    (Some(before), Some(after)) if before != after => {
+                        write!(f, "{}..{}", short(before), short(after))?;
+                    }
+                    _ => write!(f, "updated")?,
+                }
+                match branch.commits.len() {
+                    0 => {}
+                    1 => write!(f, ", made {}", short(&branch.commits[0]))?,
+                    commits => write!(f, ", made {commits} commits")?,
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagDeleted {
+    pub name: String,
+    pub remote: Option<String>,
+}
+
+impl fmt::Display for TagDeleted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.remote {
+            Some(remote) => writeln!(f, "deleted {} here and from {remote}", self.name),
+            None => writeln!(f, "deleted {}", self.name),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub id: String,
+    /// The operation the snapshot was taken before, like `create_commit`.
+    pub kind: String,
+    /// When it was taken, in milliseconds since the epoch.
+    pub timestamp_ms: u128,
+}
+
+impl From<&virtual_branches::Snapshot> for Snapshot {
+    fn from(snapshot: &virtual_branches::Snapshot) -> Self {
+        Self {
+            id: snapshot.id.to_string(),
+            kind: snapshot.kind.clone(),
+            timestamp_ms: snapshot.created_timestamp_ms,
+        }
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = i64::try_from(self.timestamp_ms)
+            .ok()
+            .and_then(|millis| chrono::Local.timestamp_millis_opt(millis).single())
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        write!(f, "{} {time} before {}", short(&self.id), self.kind)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Snapshots {
+    pub snapshots: Vec<Snapshot>,
+}
+
+impl fmt::Display for Snapshots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for snapshot in &self.snapshots {
+            writeln!(f, "{snapshot}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What changed in the working directory since a snapshot, file by file.
+#[derive(Debug, Serialize)]
+pub struct SnapshotDiff {
+    pub files: Vec<SnapshotFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotFile {
+    pub path: String,
+    /// The diff of every hunk, with its `@@` header.
+    pub hunks: Vec<String>,
+}
+
+impl SnapshotDiff {
+    pub fn new(diff: &HashMap<path::PathBuf, Vec<git::diff::Hunk>>) -> Self {
+        let mut files = diff
+            .iter()
+            .map(|(path, hunks)| SnapshotFile {
+                path: path.display().to_string(),
+                hunks: hunks.iter().map(|hunk| hunk.diff.clone()).collect(),
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Self { files }
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for file in &self.files {
+            writeln!(f, "--- a/{}\n+++ b/{}", file.path, file.path)?;
+            for hunk in &file.hunks {
+                write!(f, "{hunk}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotRestored {
+    pub snapshot: Snapshot,
+}
+
+impl fmt::Display for SnapshotRestored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "restored {}", self.snapshot)
+    }
+}
+
+/// The applied branches at a glance, for shell prompts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub branches: Vec<BranchStatus>,
+    #[serde(skip)]
+    pub porcelain: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchStatus {
+    pub name: String,
+    pub selected_for_changes: bool,
+    /// How many files have uncommitted changes owned by the branch.
+    pub files: usize,
+    pub conflicted: bool,
+}
+
+impl Status {
+    pub fn new(branches: Vec<BranchStatus>) -> Self {
+        Self {
+            branches,
+            porcelain: false,
+        }
+    }
+}
+
+impl From<&virtual_branches::VirtualBranch> for BranchStatus {
+    fn from(branch: &virtual_branches::VirtualBranch) -> Self {
+        Self {
+            name: branch.name.clone(),
+            selected_for_changes: branch.selected_for_changes,
+            files: branch.files.len(),
+            conflicted: branch.conflicted,
+        }
+    }
+}
+
+/// With `porcelain`, prints a single line with an entry per branch, `[*]name:files[!]`,
+/// where `*` marks the branch selected for changes and `!` a conflicted one. Whitespace
+/// in names is replaced by `_` so that the line can be split on spaces. The format is
+/// stable.
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.porcelain {
+            let entries = self
+                .branches
+                .iter()
+                .map(|branch| {
+                    format!(
+                        "{}{}:{}{}",
+                        if branch.selected_for_changes { "*" } else { "" },
+                        branch.name.replace(char::is_whitespace, "_"),
+                        branch.files,
+                        if branch.conflicted { "!" } else { "" },
+                    )
+                })
+                .collect::<Vec<_>>();
+            return writeln!(f, "{}", entries.join(" "));
+        }
+
+        for branch in &self.branches {
+            let marker = if branch.selected_for_changes {
+                '*'
+            } else {
+                ' '
+            };
+            let conflicted = if branch.conflicted {
+                ", conflicted"
+            } else {
+                ""
+            };
+            writeln!(
+                f,
+                "{marker} {} ({} files{conflicted})",
+                branch.name, branch.files
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An event of the engine, printed by `watch` as a line of JSON as soon as it happens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    pub name: String,
+    pub project_id: String,
+    pub payload: serde_json::Value,
+}
+
+impl From<&events::Event> for Event {
+    fn from(event: &events::Event) -> Self {
+        Self {
+            name: event.name(),
+            project_id: event.project_id().to_string(),
+            payload: event.payload(),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        writeln!(f, "{line}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gitbutler_core::projects::ProjectId;
+
+    use super::*;
+
+    fn branch(
+        name: &str,
+        selected_for_changes: bool,
+        files: usize,
+        conflicted: bool,
+    ) -> BranchStatus {
+        BranchStatus {
+            name: name.to_string(),
+            selected_for_changes,
+            files,
+            conflicted,
+        }
+    }
+
+    #[test]
+    fn porcelain_status() {
+        let mut status = Status::new(vec![
+            branch("my feature", true, 2, false),
+            branch("fix", false, 0, true),
+        ]);
+        status.porcelain = true;
+        assert_eq!(status.to_string(), "*my_feature:2 fix:0!\n");
+
+        // no branch applied is an empty line, for prompts to show nothing
+        let mut status = Status::new(vec![]);
+        status.porcelain = true;
+        assert_eq!(status.to_string(), "\n");
+    }
+
+    #[test]
+    fn status() {
+        let status = Status::new(vec![
+            branch("feature", true, 2, false),
+            branch("fix", false, 1, true),
+        ]);
+        assert_eq!(
+            status.to_string(),
+            "* feature (2 files)\n  fix (1 files, conflicted)\n"
+        );
+    }
+
+    #[test]
+    fn event_is_a_line_of_json() {
+        let project_id = ProjectId::generate();
+        let line =
+            Event::from(&events::Event::git_head(&project_id, "refs/heads/main")).to_string();
+        assert!(line.ends_with('\n'));
+        assert_eq!(line.matches('\n').count(), 1);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+            serde_json::json!({
+                "name": format!("project://{project_id}/git/head"),
+                "projectId": project_id.to_string(),
+                "payload": { "head": "refs/heads/main" },
+            })
+        );
+    }
+}
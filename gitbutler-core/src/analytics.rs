@@ -1,13 +1,14 @@
 use std::{fmt, str, sync::Arc};
 
-use tauri::AppHandle;
-
 use crate::{projects::ProjectId, users::User};
 
 mod posthog;
 
 pub struct Config<'c> {
     pub posthog_token: Option<&'c str>,
+    /// The name and version of the app the events are sent from.
+    pub app_name: &'c str,
+    pub app_version: &'c str,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -62,13 +63,13 @@ pub struct Client {
 }
 
 impl Client {
-    pub fn new(app_handle: &AppHandle, config: &Config) -> Self {
+    pub fn new(config: &Config) -> Self {
         let client: Box<dyn posthog::Client + Sync + Send> =
             if let Some(posthog_token) = config.posthog_token {
                 let real = posthog::real::Client::new(posthog::real::ClientOptions {
                     api_key: posthog_token.to_string(),
-                    app_name: app_handle.package_info().name.clone(),
-                    app_version: app_handle.package_info().version.to_string(),
+                    app_name: config.app_name.to_string(),
+                    app_version: config.app_version.to_string(),
                 });
                 let real_with_retry = posthog::retry::Client::new(real);
                 Box::new(real_with_retry)
@@ -1,8 +1,7 @@
-use std::{collections::HashMap, fs, path, sync};
+use std::{collections::HashMap, path, sync};
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use futures::future::join_all;
-use tauri::AppHandle;
 use tokio::sync::Semaphore;
 use url::Url;
 
@@ -29,21 +28,6 @@ impl From<&path::PathBuf> for Proxy {
     }
 }
 
-impl TryFrom<&AppHandle> for Proxy {
-    type Error = anyhow::Error;
-
-    fn try_from(handle: &AppHandle) -> Result<Self, Self::Error> {
-        let app_cache_dir = handle
-            .path_resolver()
-            .app_cache_dir()
-            .context("failed to get cache dir")?;
-        fs::create_dir_all(&app_cache_dir).context("failed to create cache dir")?;
-        let cache_dir = app_cache_dir.join("images");
-
-        Ok(Self::from(&cache_dir))
-    }
-}
-
 const ASSET_SCHEME: &str = "asset";
 
 impl Proxy {
@@ -6,7 +6,6 @@ use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use refinery::config::Config;
 use rusqlite::Transaction;
-use tauri::AppHandle;
 
 mod embedded {
     use refinery::embed_migrations;
@@ -27,18 +26,6 @@ impl TryFrom<&path::PathBuf> for Database {
     }
 }
 
-impl TryFrom<&AppHandle> for Database {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> Result<Self, Self::Error> {
-        let path = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Self::try_from(&path)
-    }
-}
-
 impl Database {
     fn open<P: AsRef<path::Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
@@ -6,8 +6,6 @@ mod operations;
 mod reader;
 mod writer;
 
-pub mod commands;
-
 pub use controller::Controller;
 pub use database::Database;
 pub use delta::Delta;
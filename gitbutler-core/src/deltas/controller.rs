@@ -1,8 +1,6 @@
 use std::collections::HashMap;
 
-use tauri::AppHandle;
-
-use crate::{projects::ProjectId, sessions::SessionId};
+use crate::{error::Error, projects::ProjectId, sessions::SessionId};
 
 use super::{database, Delta};
 
@@ -10,21 +8,20 @@ pub struct Controller {
     database: database::Database,
 }
 
-impl From<&AppHandle> for Controller {
-    fn from(handle: &AppHandle) -> Self {
-        Self {
-            database: database::Database::from(handle),
-        }
-    }
-}
-
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum ListError {
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
 impl Controller {
+    pub fn new(database: &database::Database) -> Self {
+        Self {
+            database: database.clone(),
+        }
+    }
+
     pub fn list_by_session_id(
         &self,
         project_id: &ProjectId,
@@ -36,3 +33,14 @@ impl Controller {
             .map_err(Into::into)
     }
 }
+
+impl From<ListError> for Error {
+    fn from(value: ListError) -> Self {
+        match value {
+            ListError::Other(error) => {
+                tracing::error!(?error);
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
@@ -1,9 +1,8 @@
 use std::{collections::HashMap, path};
 
 use anyhow::{Context, Result};
-use tauri::{AppHandle, Manager};
 
-use crate::{database, projects::ProjectId, sessions::SessionId};
+use crate::{database, git, projects::ProjectId, sessions::SessionId};
 
 use super::{delta, operations};
 
@@ -18,12 +17,6 @@ impl From<database::Database> for Database {
     }
 }
 
-impl From<&AppHandle> for Database {
-    fn from(value: &AppHandle) -> Self {
-        Self::from(value.state::<database::Database>().inner().clone())
-    }
-}
-
 impl Database {
     pub fn insert(
         &self,
@@ -41,7 +34,7 @@ impl Database {
                 stmt.execute(rusqlite::named_params! {
                     ":project_id": project_id,
                     ":session_id": session_id,
-                    ":file_path": file_path.display().to_string(),
+                    ":file_path": git::path::escape(file_path),
                     ":timestamp_ms": timestamp_ms,
                     ":operations": operations,
                 })
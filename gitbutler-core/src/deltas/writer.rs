@@ -17,7 +17,7 @@ impl<'writer> DeltasWriter<'writer> {
     pub fn write<P: AsRef<std::path::Path>>(&self, path: P, deltas: &Vec<Delta>) -> Result<()> {
         self.repository.mark_active_session()?;
 
-        let _lock = self.repository.lock();
+        let _lock = self.repository.lock(gb_repository::LockShard::Sessions);
 
         let path = path.as_ref();
         let raw_deltas = serde_json::to_string(&deltas)?;
@@ -37,7 +37,7 @@ impl<'writer> DeltasWriter<'writer> {
     pub fn remove_wd_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
         self.repository.mark_active_session()?;
 
-        let _lock = self.repository.lock();
+        let _lock = self.repository.lock(gb_repository::LockShard::Sessions);
 
         let path = path.as_ref();
         self.writer
@@ -55,7 +55,7 @@ impl<'writer> DeltasWriter<'writer> {
     pub fn write_wd_file<P: AsRef<std::path::Path>>(&self, path: P, contents: &str) -> Result<()> {
         self.repository.mark_active_session()?;
 
-        let _lock = self.repository.lock();
+        let _lock = self.repository.lock(gb_repository::LockShard::Sessions);
 
         let path = path.as_ref();
         self.writer
@@ -324,10 +324,20 @@ pub mod gb {
 //)]
 mod legacy {
     use core::fmt;
+    use std::collections::BTreeMap;
 
     use serde::{ser::SerializeMap, Serialize};
 
-    #[derive(Debug)]
+    use crate::{git, reader};
+
+    const UNKNOWN_MESSAGE: &str = "Something went wrong";
+
+    /// What the UI tells the errors of commands apart by, their `code`.
+    ///
+    /// Codes are stable across releases: they are only ever added, never renamed or given to
+    /// other errors, for the UI to keep handling the errors of the releases it knows.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
     pub enum Code {
         Unknown,
         Validation,
@@ -337,9 +347,24 @@ mod legacy {
         ProjectGitRemote,
         ProjectConflict,
         ProjectHead,
+        ProjectShallow,
+        ProjectReadOnly,
         Menu,
         PreCommitHook,
         CommitMsgHook,
+        PrePushHook,
+        SecretsFound,
+        LargeFiles,
+        ConflictMarkers,
+        ProjectExternalOperation,
+        Cancelled,
+        LocalModel,
+        /// A remote or a model couldn't be reached.
+        Network,
+        /// The repository is locked by another git process, like its index.
+        ProjectLocked,
+        /// What GitButler stores about the project can't be read.
+        CorruptState,
     }
 
     impl fmt::Display for Code {
@@ -354,9 +379,21 @@ mod legacy {
                 Code::ProjectGitRemote => write!(f, "errors.projects.git.remote"),
                 Code::ProjectHead => write!(f, "errors.projects.head"),
                 Code::ProjectConflict => write!(f, "errors.projects.conflict"),
+                Code::ProjectShallow => write!(f, "errors.projects.shallow"),
+                Code::ProjectReadOnly => write!(f, "errors.projects.read_only"),
                 //TODO: rename js side to be more precise what kind of hook error this is
                 Code::PreCommitHook => write!(f, "errors.hook"),
                 Code::CommitMsgHook => write!(f, "errors.hooks.commit.msg"),
+                Code::PrePushHook => write!(f, "errors.hooks.push.pre"),
+                Code::SecretsFound => write!(f, "errors.secrets"),
+                Code::LargeFiles => write!(f, "errors.large_files"),
+                Code::ConflictMarkers => write!(f, "errors.conflict_markers"),
+                Code::ProjectExternalOperation => write!(f, "errors.projects.external_operation"),
+                Code::Cancelled => write!(f, "errors.cancelled"),
+                Code::LocalModel => write!(f, "errors.local_model"),
+                Code::Network => write!(f, "errors.network"),
+                Code::ProjectLocked => write!(f, "errors.projects.locked"),
+                Code::CorruptState => write!(f, "errors.corrupt_state"),
             }
         }
     }
@@ -365,26 +402,148 @@ mod legacy {
     pub enum Error {
         #[error("[{code}]: {message}")]
         UserError { code: Code, message: String },
+        /// A [`Error::UserError`] with what it's about, like the ids of its project and branch,
+        /// sent as its `context` for the UI not to have to make them out of the message.
+        #[error("[{code}]: {message}")]
+        UserErrorWithContext {
+            code: Code,
+            message: String,
+            context: BTreeMap<&'static str, String>,
+        },
         #[error("[errors.unknown]: Something went wrong")]
         Unknown,
     }
 
+    impl Error {
+        pub fn code(&self) -> Code {
+            match self {
+                Error::UserError { code, .. } | Error::UserErrorWithContext { code, .. } => *code,
+                Error::Unknown => Code::Unknown,
+            }
+        }
+
+        /// The error with `key` set to `value` in its context, see
+        /// [`Error::UserErrorWithContext`]. Keys are in camelCase, like the fields of the UI.
+        #[must_use]
+        pub fn with_context(self, key: &'static str, value: impl ToString) -> Self {
+            let (code, message, mut context) = match self {
+                Error::UserError { code, message } => (code, message, BTreeMap::new()),
+                Error::UserErrorWithContext {
+                    code,
+                    message,
+                    context,
+                } => (code, message, context),
+                Error::Unknown => (Code::Unknown, UNKNOWN_MESSAGE.to_string(), BTreeMap::new()),
+            };
+            context.insert(key, value.to_string());
+            Error::UserErrorWithContext {
+                code,
+                message,
+                context,
+            }
+        }
+
+        /// The error of a command that failed with `error`: the one of what caused it if it's
+        /// known, like a network error or a repository locked by another git process, and
+        /// [`Error::Unknown`] otherwise. What caused it is only logged, its message can have
+        /// paths, urls and what was read in it.
+        pub fn caused_by(error: &anyhow::Error) -> Self {
+            let Some(code) = error.chain().find_map(code_of) else {
+                return Error::Unknown;
+            };
+            let message = match code {
+                Code::Network => "The remote can't be reached, check the connection and try again",
+                Code::ProjectGitAuth => "Project remote authentication error",
+                Code::ProjectLocked => {
+                    "The repository is locked by another git process, try again once it's done"
+                }
+                Code::CorruptState => {
+                    "What GitButler stores about the project can't be read, repair the project"
+                }
+                _ => UNKNOWN_MESSAGE,
+            };
+            Error::UserError {
+                code,
+                message: message.to_string(),
+            }
+        }
+    }
+
+    /// A file GitButler keeps its state in, like its projects or the outbox of a project,
+    /// that can't be parsed. The errors it causes are [`Code::CorruptState`], unlike those of
+    /// parsing what was received, like the replies of a forge or of a model.
+    #[derive(Debug, thiserror::Error)]
+    #[error("failed to parse {file}")]
+    pub struct CorruptStateError {
+        file: String,
+        #[source]
+        source: serde_json::Error,
+    }
+
+    impl CorruptStateError {
+        /// Wraps the error of parsing `file`, for `map_err`.
+        pub fn of(file: &str) -> impl FnOnce(serde_json::Error) -> Self + '_ {
+            |source| Self {
+                file: file.to_string(),
+                source,
+            }
+        }
+    }
+
+    // the code of the errors caused by `cause`, if it's one the UI tells apart
+    fn code_of(cause: &(dyn std::error::Error + 'static)) -> Option<Code> {
+        if let Some(error) = cause.downcast_ref::<git::Error>() {
+            return match error {
+                git::Error::Network(_) => Some(Code::Network),
+                git::Error::Auth(_) => Some(Code::ProjectGitAuth),
+                git::Error::NotFound(error)
+                | git::Error::Http(error)
+                | git::Error::Other(error) => git_code_of(error),
+                _ => None,
+            };
+        }
+        if let Some(error) = cause.downcast_ref::<git2::Error>() {
+            return git_code_of(error);
+        }
+        if let Some(reader::Error::From(_)) = cause.downcast_ref::<reader::Error>() {
+            return Some(Code::CorruptState);
+        }
+        if cause.is::<CorruptStateError>() {
+            return Some(Code::CorruptState);
+        }
+        None
+    }
+
+    fn git_code_of(error: &git2::Error) -> Option<Code> {
+        match (error.class(), error.code()) {
+            (_, git2::ErrorCode::Locked) => Some(Code::ProjectLocked),
+            (git2::ErrorClass::Net, _) => Some(Code::Network),
+            (_, git2::ErrorCode::Auth) => Some(Code::ProjectGitAuth),
+            _ => None,
+        }
+    }
+
     impl Serialize for Error {
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
             S: serde::Serializer,
         {
-            let (code, message) = match self {
-                Error::UserError { code, message } => (code.to_string(), message.to_string()),
-                Error::Unknown => (
-                    Code::Unknown.to_string(),
-                    "Something went wrong".to_string(),
-                ),
+            let (code, message, context) = match self {
+                Error::UserError { code, message } => (code.to_string(), message.as_str(), None),
+                Error::UserErrorWithContext {
+                    code,
+                    message,
+                    context,
+                } => (code.to_string(), message.as_str(), Some(context)),
+                Error::Unknown => (Code::Unknown.to_string(), UNKNOWN_MESSAGE, None),
             };
 
-            let mut map = serializer.serialize_map(Some(2))?;
+            let mut map = serializer.serialize_map(Some(2 + usize::from(context.is_some())))?;
             map.serialize_entry("code", &code)?;
-            map.serialize_entry("message", &message)?;
+            map.serialize_entry("message", message)?;
+            if let Some(context) = context {
+                map.serialize_entry("context", context)?;
+            }
             map.end()
         }
     }
@@ -392,7 +551,94 @@ mod legacy {
     impl From<anyhow::Error> for Error {
         fn from(error: anyhow::Error) -> Self {
             tracing::error!(?error);
-            Error::Unknown
+            Error::caused_by(&error)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn codes_are_stable() {
+            let codes = [
+                (Code::Unknown, "errors.unknown"),
+                (Code::Validation, "errors.validation"),
+                (Code::Projects, "errors.projects"),
+                (Code::Branches, "errors.branches"),
+                (Code::ProjectGitAuth, "errors.projects.git.auth"),
+                (Code::ProjectGitRemote, "errors.projects.git.remote"),
+                (Code::ProjectConflict, "errors.projects.conflict"),
+                (Code::ProjectHead, "errors.projects.head"),
+                (Code::ProjectShallow, "errors.projects.shallow"),
+                (Code::ProjectReadOnly, "errors.projects.read_only"),
+                (Code::Menu, "errors.menu"),
+                (Code::PreCommitHook, "errors.hook"),
+                (Code::CommitMsgHook, "errors.hooks.commit.msg"),
+                (Code::PrePushHook, "errors.hooks.push.pre"),
+                (Code::SecretsFound, "errors.secrets"),
+                (Code::LargeFiles, "errors.large_files"),
+                (Code::ConflictMarkers, "errors.conflict_markers"),
+                (
+                    Code::ProjectExternalOperation,
+                    "errors.projects.external_operation",
+                ),
+                (Code::Cancelled, "errors.cancelled"),
+                (Code::LocalModel, "errors.local_model"),
+                (Code::Network, "errors.network"),
+                (Code::ProjectLocked, "errors.projects.locked"),
+                (Code::CorruptState, "errors.corrupt_state"),
+            ];
+            for (code, name) in codes {
+                assert_eq!(code.to_string(), name);
+            }
+        }
+
+        #[test]
+        fn errors_with_context() {
+            let error = Error::UserError {
+                code: Code::Branches,
+                message: "branch 1 not found".to_string(),
+            }
+            .with_context("branchId", 1);
+            assert_eq!(
+                serde_json::to_value(&error).unwrap(),
+                serde_json::json!({
+                    "code": "errors.branches",
+                    "message": "branch 1 not found",
+                    "context": { "branchId": "1" },
+                })
+            );
+            assert_eq!(
+                serde_json::to_value(Error::Unknown).unwrap(),
+                serde_json::json!({ "code": "errors.unknown", "message": "Something went wrong" })
+            );
+        }
+
+        #[test]
+        fn errors_caused_by_what_the_ui_tells_apart() {
+            let locked = git2::Error::new(
+                git2::ErrorCode::Locked,
+                git2::ErrorClass::Index,
+                "index is locked",
+            );
+            let error = anyhow::Error::from(git::Error::from(locked)).context("failed to commit");
+            assert_eq!(Error::caused_by(&error).code(), Code::ProjectLocked);
+            // the cause isn't sent, only its code
+            assert!(matches!(Error::caused_by(&error), Error::UserError { .. }));
+
+            let corrupt = serde_json::from_str::<u32>("{").unwrap_err();
+            let error = anyhow::Error::from(CorruptStateError::of("projects.json")(corrupt))
+                .context("failed to read projects");
+            assert_eq!(Error::caused_by(&error).code(), Code::CorruptState);
+
+            // nor is what was received
+            let malformed = serde_json::from_str::<u32>("{").unwrap_err();
+            let error = anyhow::Error::from(malformed).context("malformed chat completion");
+            assert!(matches!(Error::caused_by(&error), Error::Unknown));
+
+            let error = anyhow::anyhow!("something else");
+            assert!(matches!(Error::caused_by(&error), Error::Unknown));
         }
     }
 }
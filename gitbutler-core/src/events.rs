@@ -0,0 +1,564 @@
+//! What happens in the engine, like the virtual branches of a project changing or a fetch
+//! being over, published on a [`Bus`] for whoever follows it: the windows of the app, the
+//! daemon of the CLI, or tests. Events are typed for subscribers to match on them, and have
+//! the name and the JSON payload the frontend listens for.
+
+use std::{
+    collections::HashMap,
+    path,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::broadcast;
+
+use crate::{
+    deltas, git, project_repository,
+    projects::ProjectId,
+    reader,
+    sessions::{self, SessionId},
+    virtual_branches::{self, BranchId},
+};
+
+/// Where events are published, with a channel for every project and one for all of them, so
+/// that several projects can be followed at once without a busy one making the subscribers
+/// of the others fall behind. Clones publish to the same channels.
+///
+/// Subscribers that can't afford to miss any event, like the windows of the app, are handed
+/// them as they are published by [`Bus::on_publish`] instead.
+#[derive(Clone)]
+pub struct Bus {
+    capacity: usize,
+    all: broadcast::Sender<Event>,
+    by_project_id: Arc<Mutex<HashMap<ProjectId, broadcast::Sender<Event>>>>,
+    sinks: Arc<Mutex<Vec<Sink>>>,
+}
+
+type Sink = Arc<dyn Fn(&Event) + Send + Sync>;
+
+impl Bus {
+    /// A bus that subscribers can fall `capacity` events behind on before they start missing
+    /// them.
+    pub fn new(capacity: usize) -> Self {
+        let (all, _) = broadcast::channel(capacity);
+        Self {
+            capacity,
+            all,
+            by_project_id: Arc::default(),
+            sinks: Arc::default(),
+        }
+    }
+
+    /// Calls `sink` with every event from now on, as it's published. It's called by whoever
+    /// publishes, so it has to be quick.
+    pub fn on_publish(&self, sink: impl Fn(&Event) + Send + Sync + 'static) {
+        self.sinks.lock().unwrap().push(Arc::new(sink));
+    }
+
+    /// The events of every project, from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.all.subscribe()
+    }
+
+    /// The events of `project_id`, from now on.
+    pub fn subscribe_project(&self, project_id: &ProjectId) -> broadcast::Receiver<Event> {
+        self.project(project_id).subscribe()
+    }
+
+    pub fn publish(&self, event: Event) {
+        tracing::debug!(event_name = %event.name(), "published event");
+        {
+            let mut by_project_id = self.by_project_id.lock().unwrap();
+            if let Some(tx) = by_project_id.get(event.project_id()) {
+                // the channel of a project nobody follows anymore is dropped
+                if tx.send(event.clone()).is_err() {
+                    by_project_id.remove(event.project_id());
+                }
+            }
+        }
+        let sinks = self.sinks.lock().unwrap().clone();
+        for sink in sinks {
+            sink(&event);
+        }
+        // nobody listening is not an error, events are fire and forget
+        let _ = self.all.send(event);
+    }
+
+    fn project(&self, project_id: &ProjectId) -> broadcast::Sender<Event> {
+        let mut by_project_id = self.by_project_id.lock().unwrap();
+        // the channels of projects nobody follows anymore are dropped, not only when they
+        // publish again, which deleted projects never do
+        by_project_id.retain(|_, tx| tx.receiver_count() > 0);
+        by_project_id
+            .entry(*project_id)
+            .or_insert_with(|| broadcast::channel(self.capacity).0)
+            .clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    GitIndex {
+        project_id: ProjectId,
+    },
+    /// A fetch is over. `remotes` has the outcome for every remote when the fetch was done by
+    /// us, it is empty if someone else fetched.
+    GitFetch {
+        project_id: ProjectId,
+        remotes: Vec<virtual_branches::RemoteFetch>,
+    },
+    /// The progress of a clone, under the id of the project it becomes.
+    CloneProgress {
+        project_id: ProjectId,
+        progress: project_repository::FetchProgressSnapshot,
+        cloning: bool,
+    },
+    GitDeepenProgress {
+        project_id: ProjectId,
+        progress: project_repository::FetchProgressSnapshot,
+        fetching: bool,
+    },
+    /// Lines the pre-push checks of a branch wrote since the last event.
+    PrePushOutput {
+        project_id: ProjectId,
+        branch_id: BranchId,
+        lines: Vec<virtual_branches::OutputLine>,
+    },
+    /// What a model added to its reply since the last event, to the generation that asked for
+    /// it as `stream_id`. With `replace`, it's the whole reply so far instead, replacing what
+    /// was sent before.
+    AiReply {
+        project_id: ProjectId,
+        stream_id: String,
+        delta: String,
+        replace: bool,
+    },
+    GitHead {
+        project_id: ProjectId,
+        head: String,
+    },
+    GitActivity {
+        project_id: ProjectId,
+    },
+    File {
+        project_id: ProjectId,
+        session_id: SessionId,
+        file_path: String,
+        contents: Option<reader::Content>,
+    },
+    Session {
+        project_id: ProjectId,
+        session: sessions::Session,
+    },
+    Deltas {
+        project_id: ProjectId,
+        session_id: SessionId,
+        deltas: Vec<deltas::Delta>,
+        file_path: path::PathBuf,
+    },
+    VirtualBranches {
+        project_id: ProjectId,
+        branches: Vec<virtual_branches::VirtualBranch>,
+    },
+    VirtualBranchesChunk {
+        project_id: ProjectId,
+        stream_id: String,
+        chunk: virtual_branches::stream::Chunk,
+    },
+    /// Progress of a virtual branches recomputation. `computing` is false once the
+    /// recomputation is over, `timed_out` is true when it has been running for longer than
+    /// the frontend should wait for, in which case the last known branches should be shown.
+    VirtualBranchesProgress {
+        project_id: ProjectId,
+        progress: virtual_branches::ProgressSnapshot,
+        computing: bool,
+        timed_out: bool,
+    },
+    /// A fetch or a push failed for a network error and is tried again.
+    GitRetry(project_repository::Retry),
+    /// What replaying the outbox did after the network came back, the pull requests that are
+    /// ready for the frontend to create in particular.
+    OutboxReplay {
+        project_id: ProjectId,
+        replays: Vec<virtual_branches::Replay>,
+    },
+}
+
+impl Event {
+    /// The name the frontend listens for the event with.
+    pub fn name(&self) -> String {
+        match self {
+            Event::GitIndex { project_id } => format!("project://{}/git/index", project_id),
+            Event::GitFetch { project_id, .. } => format!("project://{}/git/fetch", project_id),
+            // the same for every clone, the project doesn't exist yet for the frontend to
+            // listen for its id
+            Event::CloneProgress { .. } => "clone://progress".to_string(),
+            Event::GitDeepenProgress { project_id, .. } => {
+                format!("project://{}/git/deepen", project_id)
+            }
+            Event::PrePushOutput { project_id, .. } => {
+                format!("project://{}/pre-push", project_id)
+            }
+            Event::AiReply {
+                project_id,
+                stream_id,
+                ..
+            } => format!("project://{}/ai/reply/{}", project_id, stream_id),
+            Event::GitHead { project_id, .. } => format!("project://{}/git/head", project_id),
+            Event::GitActivity { project_id } => {
+                format!("project://{}/git/activity", project_id)
+            }
+            Event::File {
+                project_id,
+                session_id,
+                ..
+            } => format!("project://{}/sessions/{}/files", project_id, session_id),
+            Event::Session { project_id, .. } => format!("project://{}/sessions", project_id),
+            Event::Deltas {
+                project_id,
+                session_id,
+                ..
+            } => format!("project://{}/sessions/{}/deltas", project_id, session_id),
+            Event::VirtualBranches { project_id, .. } => {
+                format!("project://{}/virtual-branches", project_id)
+            }
+            Event::VirtualBranchesChunk {
+                project_id,
+                stream_id,
+                ..
+            } => format!(
+                "project://{}/virtual-branches/stream/{}",
+                project_id, stream_id
+            ),
+            Event::VirtualBranchesProgress { project_id, .. } => {
+                format!("project://{}/virtual-branches/progress", project_id)
+            }
+            Event::GitRetry(retry) => format!("project://{}/git/retry", retry.project_id),
+            Event::OutboxReplay { project_id, .. } => {
+                format!("project://{}/outbox/replay", project_id)
+            }
+        }
+    }
+
+    /// What the frontend is sent with the event.
+    pub fn payload(&self) -> serde_json::Value {
+        match self {
+            Event::GitIndex { .. } | Event::GitActivity { .. } => serde_json::json!({}),
+            Event::GitFetch { remotes, .. } => serde_json::json!({ "remotes": remotes }),
+            Event::CloneProgress {
+                project_id,
+                progress,
+                cloning,
+            } => serde_json::json!({
+                "projectId": project_id,
+                "receivedObjects": progress.received_objects,
+                "totalObjects": progress.total_objects,
+                "receivedBytes": progress.received_bytes,
+                "cloning": cloning,
+            }),
+            Event::GitDeepenProgress {
+                progress, fetching, ..
+            } => serde_json::json!({
+                "receivedObjects": progress.received_objects,
+                "totalObjects": progress.total_objects,
+                "receivedBytes": progress.received_bytes,
+                "fetching": fetching,
+            }),
+            Event::PrePushOutput {
+                branch_id, lines, ..
+            } => serde_json::json!({
+                "branchId": branch_id,
+                "lines": lines,
+            }),
+            Event::AiReply { delta, replace, .. } => {
+                serde_json::json!({ "delta": delta, "replace": replace })
+            }
+            Event::GitHead { head, .. } => serde_json::json!({ "head": head }),
+            Event::File {
+                file_path,
+                contents,
+                ..
+            } => serde_json::json!({
+                "filePath": file_path,
+                "contents": contents,
+            }),
+            Event::Session { session, .. } => serde_json::json!(session),
+            Event::Deltas {
+                deltas, file_path, ..
+            } => serde_json::json!({
+                "deltas": deltas,
+                "filePath": git::path::escape(file_path),
+            }),
+            Event::VirtualBranches { branches, .. } => serde_json::json!(branches),
+            Event::VirtualBranchesChunk { chunk, .. } => serde_json::json!(chunk),
+            Event::VirtualBranchesProgress {
+                progress,
+                computing,
+                timed_out,
+                ..
+            } => serde_json::json!({
+                "filesScanned": progress.files_scanned,
+                "filesTotal": progress.files_total,
+                "computing": computing,
+                "timedOut": timed_out,
+            }),
+            Event::GitRetry(retry) => serde_json::json!(retry),
+            Event::OutboxReplay { replays, .. } => serde_json::json!({ "replays": replays }),
+        }
+    }
+
+    pub fn project_id(&self) -> &ProjectId {
+        match self {
+            Event::GitRetry(retry) => &retry.project_id,
+            Event::GitIndex { project_id }
+            | Event::GitFetch { project_id, .. }
+            | Event::CloneProgress { project_id, .. }
+            | Event::GitDeepenProgress { project_id, .. }
+            | Event::PrePushOutput { project_id, .. }
+            | Event::AiReply { project_id, .. }
+            | Event::GitHead { project_id, .. }
+            | Event::GitActivity { project_id }
+            | Event::File { project_id, .. }
+            | Event::Session { project_id, .. }
+            | Event::Deltas { project_id, .. }
+            | Event::VirtualBranches { project_id, .. }
+            | Event::VirtualBranchesChunk { project_id, .. }
+            | Event::VirtualBranchesProgress { project_id, .. }
+            | Event::OutboxReplay { project_id, .. } => project_id,
+        }
+    }
+
+    pub fn git_index(project_id: &ProjectId) -> Self {
+        Event::GitIndex {
+            project_id: *project_id,
+        }
+    }
+
+    pub fn git_fetch(project_id: &ProjectId, remotes: &[virtual_branches::RemoteFetch]) -> Self {
+        Event::GitFetch {
+            project_id: *project_id,
+            remotes: remotes.to_vec(),
+        }
+    }
+
+    pub fn clone_progress(
+        project_id: &ProjectId,
+        progress: &project_repository::FetchProgressSnapshot,
+        cloning: bool,
+    ) -> Self {
+        Event::CloneProgress {
+            project_id: *project_id,
+            progress: *progress,
+            cloning,
+        }
+    }
+
+    pub fn git_deepen_progress(
+        project_id: &ProjectId,
+        progress: &project_repository::FetchProgressSnapshot,
+        fetching: bool,
+    ) -> Self {
+        Event::GitDeepenProgress {
+            project_id: *project_id,
+            progress: *progress,
+            fetching,
+        }
+    }
+
+    pub fn pre_push_output(
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        lines: &[virtual_branches::OutputLine],
+    ) -> Self {
+        Event::PrePushOutput {
+            project_id: *project_id,
+            branch_id: *branch_id,
+            lines: lines.to_vec(),
+        }
+    }
+
+    pub fn ai_reply(project_id: &ProjectId, stream_id: &str, delta: &str, replace: bool) -> Self {
+        Event::AiReply {
+            project_id: *project_id,
+            stream_id: stream_id.to_string(),
+            delta: delta.to_string(),
+            replace,
+        }
+    }
+
+    pub fn git_head(project_id: &ProjectId, head: &str) -> Self {
+        Event::GitHead {
+            project_id: *project_id,
+            head: head.to_string(),
+        }
+    }
+
+    pub fn git_activity(project_id: &ProjectId) -> Self {
+        Event::GitActivity {
+            project_id: *project_id,
+        }
+    }
+
+    pub fn file(
+        project_id: &ProjectId,
+        session_id: &SessionId,
+        file_path: &str,
+        contents: Option<&reader::Content>,
+    ) -> Self {
+        Event::File {
+            project_id: *project_id,
+            session_id: *session_id,
+            file_path: file_path.to_string(),
+            contents: contents.cloned(),
+        }
+    }
+
+    pub fn session(project_id: &ProjectId, session: &sessions::Session) -> Self {
+        Event::Session {
+            project_id: *project_id,
+            session: session.clone(),
+        }
+    }
+
+    pub fn deltas(
+        project_id: &ProjectId,
+        session_id: &SessionId,
+        deltas: &[deltas::Delta],
+        relative_file_path: &path::Path,
+    ) -> Self {
+        Event::Deltas {
+            project_id: *project_id,
+            session_id: *session_id,
+            deltas: deltas.to_vec(),
+            file_path: relative_file_path.to_path_buf(),
+        }
+    }
+
+    pub fn virtual_branches(
+        project_id: &ProjectId,
+        virtual_branches: &[virtual_branches::VirtualBranch],
+    ) -> Self {
+        Event::VirtualBranches {
+            project_id: *project_id,
+            branches: virtual_branches.to_vec(),
+        }
+    }
+
+    pub fn virtual_branches_chunk(
+        project_id: &ProjectId,
+        stream_id: &str,
+        chunk: &virtual_branches::stream::Chunk,
+    ) -> Self {
+        Event::VirtualBranchesChunk {
+            project_id: *project_id,
+            stream_id: stream_id.to_string(),
+            chunk: chunk.clone(),
+        }
+    }
+
+    pub fn virtual_branches_progress(
+        project_id: &ProjectId,
+        progress: &virtual_branches::ProgressSnapshot,
+        computing: bool,
+        timed_out: bool,
+    ) -> Self {
+        Event::VirtualBranchesProgress {
+            project_id: *project_id,
+            progress: *progress,
+            computing,
+            timed_out,
+        }
+    }
+
+    pub fn git_retry(retry: &project_repository::Retry) -> Self {
+        Event::GitRetry(retry.clone())
+    }
+
+    pub fn outbox_replay(project_id: &ProjectId, replays: &[virtual_branches::Replay]) -> Self {
+        Event::OutboxReplay {
+            project_id: *project_id,
+            replays: replays.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_per_project() {
+        let bus = Bus::new(16);
+        let (one, other) = (ProjectId::generate(), ProjectId::generate());
+        let mut all = bus.subscribe();
+        let mut of_one = bus.subscribe_project(&one);
+        let mut of_other = bus.subscribe_project(&other);
+
+        bus.publish(Event::git_index(&one));
+        bus.publish(Event::git_index(&other));
+
+        assert_eq!(all.try_recv().unwrap().project_id(), &one);
+        assert_eq!(all.try_recv().unwrap().project_id(), &other);
+        assert_eq!(of_one.try_recv().unwrap().project_id(), &one);
+        assert!(of_one.try_recv().is_err());
+        assert_eq!(of_other.try_recv().unwrap().project_id(), &other);
+        assert!(of_other.try_recv().is_err());
+    }
+
+    #[test]
+    fn events_are_typed_and_named_for_the_frontend() {
+        let bus = Bus::new(16);
+        let project_id = ProjectId::generate();
+        let mut events = bus.subscribe_project(&project_id);
+
+        bus.publish(Event::git_head(&project_id, "refs/heads/main"));
+
+        let event = events.try_recv().unwrap();
+        let Event::GitHead { head, .. } = &event else {
+            unreachable!("not a head event: {event:?}");
+        };
+        assert_eq!(head, "refs/heads/main");
+        assert_eq!(event.name(), format!("project://{}/git/head", project_id));
+        assert_eq!(
+            event.payload(),
+            serde_json::json!({ "head": "refs/heads/main" })
+        );
+    }
+
+    #[test]
+    fn sinks_miss_nothing() {
+        let bus = Bus::new(1);
+        let project_id = ProjectId::generate();
+        let seen = Arc::new(Mutex::new(0));
+        bus.on_publish({
+            let seen = Arc::clone(&seen);
+            move |_| *seen.lock().unwrap() += 1
+        });
+
+        for _ in 0..10 {
+            bus.publish(Event::git_index(&project_id));
+        }
+
+        assert_eq!(*seen.lock().unwrap(), 10);
+    }
+
+    #[test]
+    fn channels_of_unfollowed_projects_are_dropped() {
+        let bus = Bus::new(16);
+        let project_id = ProjectId::generate();
+        drop(bus.subscribe_project(&project_id));
+
+        bus.publish(Event::git_index(&project_id));
+
+        assert!(bus.by_project_id.lock().unwrap().is_empty());
+
+        // one that never publishes again is dropped once someone follows another
+        let deleted = ProjectId::generate();
+        drop(bus.subscribe_project(&deleted));
+        let _rx = bus.subscribe_project(&project_id);
+        assert_eq!(
+            bus.by_project_id.lock().unwrap().keys().collect::<Vec<_>>(),
+            vec![&project_id]
+        );
+    }
+}
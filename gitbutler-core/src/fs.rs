@@ -0,0 +1,482 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+/// Appends `entry` to the log at `path`, one JSON entry per line, creating it if needed. The
+/// line is written at once, so that a log read while it's appended to is only ever missing
+/// its last line.
+pub fn append_json_line<T: Serialize>(path: &Path, entry: &T) -> Result<()> {
+    let mut line = serde_json::to_string(entry).context("failed to serialize log entry")?;
+    line.push('\n');
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("failed to append to {}", path.display()))
+}
+
+// Returns an ordered list of relative paths for files inside a directory recursively.
+pub fn list_files<P: AsRef<Path>>(dir_path: P, ignore_prefixes: &[P]) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let dir_path = dir_path.as_ref();
+    if !dir_path.exists() {
+        return Ok(files);
+    }
+    for entry in WalkDir::new(dir_path) {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            let path = entry.path();
+            let path = path.strip_prefix(dir_path)?;
+            let path = path.to_path_buf();
+            if ignore_prefixes
+                .iter()
+                .any(|prefix| path.starts_with(prefix.as_ref()))
+            {
+                continue;
+            }
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// what the filesystems of working directories were probed to be, by their path, and the
+// file they are kept in for the next runs, if any
+static CASE_INSENSITIVE: Lazy<Mutex<HashMap<PathBuf, bool>>> = Lazy::new(Mutex::default);
+static CASE_PROBES_FILE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(Mutex::default);
+
+const CASE_PROBES_FILE_NAME: &str = "case-probes.json";
+
+// Keeps what `is_case_insensitive` finds in `dir`, the data directory of the app, so that
+// the filesystem of a working directory is only ever probed once.
+pub fn set_case_probes_dir(dir: &Path) {
+    let file = dir.join(CASE_PROBES_FILE_NAME);
+    if let Ok(probes) = fs::read(&file) {
+        match serde_json::from_slice::<HashMap<PathBuf, bool>>(&probes) {
+            Ok(probes) => CASE_INSENSITIVE.lock().unwrap().extend(probes),
+            Err(error) => tracing::warn!(?error, "failed to read case probes"),
+        }
+    }
+    *CASE_PROBES_FILE.lock().unwrap() = Some(file);
+}
+
+// Returns true if the filesystem `workdir` is on treats names that only differ in case as the
+// same file, as it's usually the case on macOS and Windows. The filesystem is probed by
+// writing a file in `probe_dir`, on the same filesystem but out of the way of the user like
+// the git directory, the first time only.
+pub fn is_case_insensitive(workdir: &Path, probe_dir: &Path) -> io::Result<bool> {
+    if let Some(is_case_insensitive) = CASE_INSENSITIVE.lock().unwrap().get(workdir) {
+        return Ok(*is_case_insensitive);
+    }
+    let is_case_insensitive = probe_case_insensitive(probe_dir)?;
+    let probes = {
+        let mut probes = CASE_INSENSITIVE.lock().unwrap();
+        probes.insert(workdir.to_path_buf(), is_case_insensitive);
+        probes.clone()
+    };
+    if let Some(file) = CASE_PROBES_FILE.lock().unwrap().as_ref() {
+        if let Err(error) = serde_json::to_vec_pretty(&probes)
+            .map_err(io::Error::other)
+            .and_then(|probes| fs::write(file, probes))
+        {
+            tracing::warn!(?error, "failed to write case probes");
+        }
+    }
+    Ok(is_case_insensitive)
+}
+
+fn probe_case_insensitive(dir: &Path) -> io::Result<bool> {
+    let probe = dir.join(format!(".gitbutler-CaseProbe-{}", uuid::Uuid::new_v4()));
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    let file_name = probe
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let is_case_insensitive = dir.join(file_name).exists();
+    fs::remove_file(&probe)?;
+    Ok(is_case_insensitive)
+}
+
+// Returns `path` with every component lowercased, so that paths on case insensitive
+// filesystems can be compared with each other.
+pub fn normalize_case<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref()
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_lowercase())
+        .collect()
+}
+
+// Lists the names of the entries of `dir`, keyed by their normalized case.
+pub fn list_names_by_case(dir: &Path) -> io::Result<HashMap<String, String>> {
+    let mut names = HashMap::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        names.insert(name.to_lowercase(), name);
+    }
+    Ok(names)
+}
+
+// Same as `path.exists()`, without following symlinks, but on case insensitive filesystems it
+// only returns true if the file name on disk has exactly the same case.
+pub fn exists_with_exact_case(path: &Path) -> bool {
+    if path.symlink_metadata().is_err() {
+        return false;
+    }
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return true;
+    };
+    let file_name = file_name.to_string_lossy();
+    list_names_by_case(parent).map_or(true, |names| {
+        names
+            .get(&file_name.to_lowercase())
+            .is_some_and(|name| *name == file_name)
+    })
+}
+
+// Returns `target`, the target of the symlink at `link`, relative to the directory of the
+// link if it's an absolute path inside of `root`, so that it points to the same file wherever
+// the link is checked out.
+pub fn relative_link_target(root: &Path, link: &Path, target: PathBuf) -> PathBuf {
+    let (Ok(target_in_root), Some(Ok(link_dir))) = (
+        target.strip_prefix(root),
+        link.parent().map(|parent| parent.strip_prefix(root)),
+    ) else {
+        return target;
+    };
+    link_dir
+        .components()
+        .map(|_| Path::new(".."))
+        .collect::<PathBuf>()
+        .join(target_in_root)
+}
+
+// Returns the target of a symlink the way git records it, which is with forward slashes
+// on Windows too.
+pub fn link_target_to_git(target: &str) -> String {
+    if cfg!(windows) {
+        target.replace('\\', "/")
+    } else {
+        target.to_string()
+    }
+}
+
+// Same as `fs::canonicalize`, but without the `\\?\` prefix of the verbatim paths it returns
+// on Windows, which git and most tools don't understand, so that the same directory is spelled
+// the same way wherever its path comes from.
+pub fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    let canonical = fs::canonicalize(path)?;
+    if cfg!(windows) {
+        if let Some(stripped) = strip_verbatim(&canonical.to_string_lossy()) {
+            return Ok(PathBuf::from(stripped));
+        }
+    }
+    Ok(canonical)
+}
+
+// `\\?\C:\dir` as `C:\dir` and `\\?\UNC\server\share` as `\\server\share`, `None` if `path`
+// isn't verbatim or can't be written without it.
+fn strip_verbatim(path: &str) -> Option<String> {
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        return Some(format!(r"\\{unc}"));
+    }
+    let rest = path.strip_prefix(r"\\?\")?;
+    let mut chars = rest.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(drive), Some(':'), None | Some('\\')) if drive.is_ascii_alphabetic() => {
+            Some(rest.to_string())
+        }
+        _ => None,
+    }
+}
+
+// The kind of filesystem a directory is on, as far as it changes how it can be watched and
+// locked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilesystemKind {
+    Local,
+    // a share mounted over the network, like SMB or NFS
+    Network,
+    // the files of a WSL distribution seen from Windows, or a Windows drive seen from WSL
+    Wsl,
+}
+
+impl FilesystemKind {
+    // Returns true if the filesystem can be changed without the local kernel noticing and
+    // doesn't reliably share advisory locks, so that it has to be polled for changes and
+    // locked with lock files instead.
+    pub fn is_remote(self) -> bool {
+        self != Self::Local
+    }
+}
+
+// Returns the kind of filesystem `path` is on. Mapped network drives are found by their
+// canonical path, which Windows spells as the share they are mapped to.
+pub fn filesystem_kind(path: &Path) -> FilesystemKind {
+    let path = canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if cfg!(windows) {
+        return unc_kind(&path.to_string_lossy()).unwrap_or(FilesystemKind::Local);
+    }
+    mounts()
+        .and_then(|mounts| mount_kind(&mounts, &path))
+        .unwrap_or(FilesystemKind::Local)
+}
+
+// the kind of filesystem of an UNC path, `\\server\share\dir`, `None` if `path` isn't one
+fn unc_kind(path: &str) -> Option<FilesystemKind> {
+    let path = path.replace('/', "\\").to_lowercase();
+    let rest = match path.strip_prefix(r"\\?\unc\") {
+        Some(rest) => rest,
+        None => path
+            .strip_prefix(r"\\")
+            .filter(|rest| !rest.starts_with(r"?\") && !rest.starts_with(r".\"))?,
+    };
+    match rest.split('\\').next() {
+        Some("wsl$" | "wsl.localhost") => Some(FilesystemKind::Wsl),
+        Some(server) if !server.is_empty() => Some(FilesystemKind::Network),
+        _ => None,
+    }
+}
+
+// the mount points of the system with the type of their filesystem
+fn mounts() -> Option<Vec<(PathBuf, String)>> {
+    if cfg!(target_os = "linux") {
+        fs::read_to_string("/proc/self/mounts")
+            .ok()
+            .map(|mounts| parse_proc_mounts(&mounts))
+    } else if cfg!(target_os = "macos") {
+        let output = std::process::Command::new("/sbin/mount").output().ok()?;
+        Some(parse_mount_output(&String::from_utf8_lossy(&output.stdout)))
+    } else {
+        None
+    }
+}
+
+// `/proc/self/mounts` has a mount per line, `device mount_point type options 0 0`, with the
+// spaces of the mount point escaped as `\040`
+fn parse_proc_mounts(mounts: &str) -> Vec<(PathBuf, String)> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let mount_point = fields.nth(1)?;
+            let fs_type = fields.next()?;
+            Some((
+                PathBuf::from(unescape_octal(mount_point)),
+                fs_type.to_string(),
+            ))
+        })
+        .collect()
+}
+
+// the escapes are of bytes, a path that isn't ascii has its own bytes around them
+fn unescape_octal(field: &str) -> String {
+    let mut unescaped = Vec::with_capacity(field.len());
+    let mut bytes = field.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            unescaped.push(byte);
+            continue;
+        }
+        let digits = bytes.clone().take(3).collect::<Vec<_>>();
+        let escaped = (digits.len() == 3
+            && digits.iter().all(|digit| (b'0'..=b'7').contains(digit)))
+        .then(|| {
+            digits
+                .iter()
+                .fold(0_u32, |value, digit| value * 8 + u32::from(digit - b'0'))
+        })
+        .and_then(|value| u8::try_from(value).ok());
+        match escaped {
+            Some(escaped) => {
+                unescaped.push(escaped);
+                bytes.nth(2);
+            }
+            None => unescaped.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+// `mount` on macOS prints a mount per line, `device on mount_point (type, options)`
+fn parse_mount_output(output: &str) -> Vec<(PathBuf, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, details) = rest.rsplit_once(" (")?;
+            let fs_type = details.split([',', ')']).next()?.trim();
+            Some((PathBuf::from(mount_point), fs_type.to_string()))
+        })
+        .collect()
+}
+
+// the kind of filesystem of the innermost of `mounts` that `path` is in
+fn mount_kind(mounts: &[(PathBuf, String)], path: &Path) -> Option<FilesystemKind> {
+    let (_, fs_type) = mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())?;
+    Some(match fs_type.as_str() {
+        // WSL mounts the drives of Windows as 9p (drvfs in WSL 1)
+        "9p" | "drvfs" => FilesystemKind::Wsl,
+        "nfs" | "nfs4" | "cifs" | "smb3" | "smbfs" | "afpfs" | "webdav" | "afs" | "ceph"
+        | "glusterfs" | "lustre" | "fuse.sshfs" | "fuse.rclone" | "fuse.davfs2" => {
+            FilesystemKind::Network
+        }
+        _ => FilesystemKind::Local,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_case() {
+        assert_eq!(
+            normalize_case(Path::new("Src/Main.RS")),
+            PathBuf::from("src/main.rs")
+        );
+    }
+
+    #[test]
+    fn test_relative_link_target() {
+        let root = Path::new("/project");
+        assert_eq!(
+            relative_link_target(root, &root.join("link"), root.join("a/file")),
+            PathBuf::from("a/file")
+        );
+        assert_eq!(
+            relative_link_target(root, &root.join("b/c/link"), root.join("a/file")),
+            PathBuf::from("../../a/file")
+        );
+        assert_eq!(
+            relative_link_target(root, &root.join("b/link"), PathBuf::from("/elsewhere")),
+            PathBuf::from("/elsewhere")
+        );
+        assert_eq!(
+            relative_link_target(root, &root.join("b/link"), PathBuf::from("../file")),
+            PathBuf::from("../file")
+        );
+    }
+
+    #[test]
+    fn test_strip_verbatim() {
+        assert_eq!(strip_verbatim(r"\\?\C:\dir"), Some(r"C:\dir".to_string()));
+        assert_eq!(
+            strip_verbatim(r"\\?\UNC\server\share\dir"),
+            Some(r"\\server\share\dir".to_string())
+        );
+        assert_eq!(strip_verbatim(r"\\?\Volume{guid}\dir"), None);
+        assert_eq!(strip_verbatim(r"C:\dir"), None);
+    }
+
+    #[test]
+    fn test_unc_kind() {
+        assert_eq!(
+            unc_kind(r"\\wsl$\Ubuntu\home\me\project"),
+            Some(FilesystemKind::Wsl)
+        );
+        assert_eq!(
+            unc_kind(r"\\?\UNC\wsl.localhost\Ubuntu\home"),
+            Some(FilesystemKind::Wsl)
+        );
+        assert_eq!(
+            unc_kind(r"\\nas\projects\app"),
+            Some(FilesystemKind::Network)
+        );
+        assert_eq!(unc_kind(r"\\?\C:\projects\app"), None);
+        assert_eq!(unc_kind(r"C:\projects\app"), None);
+    }
+
+    #[test]
+    fn test_unescape_octal() {
+        assert_eq!(unescape_octal("/home/me/a\\040b"), "/home/me/a b");
+        assert_eq!(
+            unescape_octal("/home/m\u{e9}/x\\011y"),
+            "/home/m\u{e9}/x\ty"
+        );
+        // what isn't an escape is left as it is
+        assert_eq!(unescape_octal("a\\9b\\04"), "a\\9b\\04");
+        assert_eq!(unescape_octal("a\\777"), "a\\777");
+    }
+
+    #[test]
+    fn test_mount_kind() {
+        let mounts = parse_proc_mounts(
+            "/dev/sda1 / ext4 rw 0 0\n\
+             C:\\134 /mnt/c 9p rw,aname=drvfs 0 0\n\
+             nas:/export /home/me/shared\\040drive nfs4 rw 0 0\n\
+             /dev/sdb1 /home/me/shared\\040drive/cache ext4 rw 0 0\n",
+        );
+        assert_eq!(
+            mount_kind(&mounts, Path::new("/home/me/project")),
+            Some(FilesystemKind::Local)
+        );
+        assert_eq!(
+            mount_kind(&mounts, Path::new("/mnt/c/Users/me/project")),
+            Some(FilesystemKind::Wsl)
+        );
+        assert_eq!(
+            mount_kind(&mounts, Path::new("/home/me/shared drive/project")),
+            Some(FilesystemKind::Network)
+        );
+        assert_eq!(
+            mount_kind(&mounts, Path::new("/home/me/shared drive/cache/project")),
+            Some(FilesystemKind::Local)
+        );
+
+        let mounts = parse_mount_output(
+            "/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)\n\
+             //me@nas/projects on /Volumes/projects (smbfs, nodev, nosuid, mounted by me)\n",
+        );
+        assert_eq!(
+            mount_kind(&mounts, Path::new("/Volumes/projects/app")),
+            Some(FilesystemKind::Network)
+        );
+        assert_eq!(
+            mount_kind(&mounts, Path::new("/Users/me/app")),
+            Some(FilesystemKind::Local)
+        );
+    }
+
+    #[test]
+    fn test_exists_with_exact_case() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("Foo.txt"), "foo")?;
+
+        assert!(exists_with_exact_case(&dir.path().join("Foo.txt")));
+        assert!(!exists_with_exact_case(&dir.path().join("foo.txt")));
+        assert!(!exists_with_exact_case(&dir.path().join("bar.txt")));
+
+        // the probe does not leave anything behind
+        let probe_dir = tempfile::tempdir()?;
+        let is_case_insensitive = is_case_insensitive(dir.path(), probe_dir.path())?;
+        assert_eq!(fs::read_dir(probe_dir.path())?.count(), 0);
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+        assert_eq!(dir.path().join("foo.txt").exists(), is_case_insensitive);
+
+        // and is only done once
+        drop(probe_dir);
+        assert_eq!(
+            super::is_case_insensitive(dir.path(), Path::new("/nonexistent"))?,
+            is_case_insensitive
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+mod repository;
+
+#[cfg(test)]
+mod repository_tests;
+
+pub use repository::{Error, LockShard, RemoteError, Repository};
@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashSet,
     fs::File,
     io::{BufReader, Read},
     path, time,
@@ -12,11 +12,10 @@ use std::os::unix::prelude::*;
 
 use anyhow::{anyhow, Context, Result};
 use filetime::FileTime;
-use fslock::LockFile;
 use sha2::{Digest, Sha256};
 
 use crate::{
-    deltas, fs, git, project_repository,
+    deltas, fs, git, lock, project_repository,
     projects::{self, ProjectId},
     reader, sessions,
     sessions::SessionId,
@@ -27,7 +26,28 @@ use crate::{
 pub struct Repository {
     git_repository: git::Repository,
     project: projects::Project,
+    /// The git directory of the project, which isn't its `.git` in linked worktrees.
+    project_git_dir: path::PathBuf,
     lock_path: path::PathBuf,
+    lock_strategy: lock::Strategy,
+}
+
+/// Locks guarding a project's gitbutler data, split by subsystem so that a slow operation
+/// in one of them (e.g. flushing a large session, or fetching) does not block the others.
+///
+/// Locks are not reentrant. When more than one shard is needed at a time they must be
+/// acquired in declaration order, `Sessions` then `VirtualBranches` then `Refs`, and never
+/// the other way around, to avoid deadlocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockShard {
+    /// the current session: its metadata, deltas and working directory snapshot.
+    Sessions,
+    /// virtual branches state.
+    VirtualBranches,
+    /// references of the gitbutler repository.
+    Refs,
+    /// the changes made to the project outside of gitbutler.
+    ExternalChanges,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -51,7 +71,11 @@ impl Repository {
         user: Option<&users::User>,
     ) -> Result<Self, Error> {
         let project = project_repository.project();
-        let project_objects_path = project.path.join(".git/objects");
+        // linked worktrees share the objects of the main one
+        let project_objects_path = project_repository
+            .git_repository
+            .common_dir()
+            .join("objects");
         if !project_objects_path.exists() {
             return Err(Error::ProjectPathNotFound(project_objects_path));
         }
@@ -60,6 +84,7 @@ impl Repository {
 
         let path = projects_dir.join(project.id.to_string());
         let lock_path = projects_dir.join(format!("{}.lock", project.id));
+        let lock_strategy = lock::Strategy::for_dir(root);
 
         if path.exists() {
             let git_repository = git::Repository::open(path.clone())
@@ -72,7 +97,9 @@ impl Repository {
             Result::Ok(Self {
                 git_repository,
                 project: project.clone(),
+                project_git_dir: project_repository.git_repository.path().to_path_buf(),
                 lock_path,
+                lock_strategy,
             })
         } else {
             let git_repository = git::Repository::init_opts(
@@ -91,10 +118,12 @@ impl Repository {
             let gb_repository = Self {
                 git_repository,
                 project: project.clone(),
+                project_git_dir: project_repository.git_repository.path().to_path_buf(),
                 lock_path,
+                lock_strategy,
             };
 
-            let _lock = gb_repository.lock();
+            let _lock = gb_repository.lock(LockShard::Sessions);
             let session = gb_repository.create_current_session(project_repository)?;
             drop(_lock);
 
@@ -171,15 +200,19 @@ impl Repository {
         let headers = &[auth_header.as_str()];
         fetch_opts.custom_headers(headers);
 
+        // only updating the references blocks the others, not downloading what they point to
+        let to_remote_error = |error| match error {
+            git::Error::Network(error) => {
+                tracing::warn!(project_id = %self.project.id, error = %error, "failed to fetch gb repo");
+                RemoteError::Network
+            }
+            error => RemoteError::Other(error.into()),
+        };
         remote
-            .fetch(&["refs/heads/*:refs/remotes/*"], Some(&mut fetch_opts))
-            .map_err(|error| match error {
-                git::Error::Network(error) => {
-                    tracing::warn!(project_id = %self.project.id, error = %error, "failed to fetch gb repo");
-                    RemoteError::Network
-                }
-                error => RemoteError::Other(error.into()),
-            })?;
+            .download(&["refs/heads/*:refs/remotes/*"], Some(&mut fetch_opts))
+            .map_err(to_remote_error)?;
+        let _lock = self.lock(LockShard::Refs);
+        remote.update_tips().map_err(to_remote_error)?;
 
         tracing::info!(
             project_id = %self.project.id,
@@ -225,7 +258,16 @@ impl Repository {
         let headers = &[auth_header.as_str()];
         push_options.custom_headers(headers);
 
-        let remote_refspec = format!("refs/heads/current:refs/heads/{}", self.project.id);
+        // the head is read under the lock, pushing it doesn't keep sessions from being flushed
+        let head = {
+            let _lock = self.lock(LockShard::Refs);
+            self.git_repository
+                .find_reference(&"refs/heads/current".parse().unwrap())
+                .context("failed to find current head")?
+                .target()
+                .context("current head is symbolic")?
+        };
+        let remote_refspec = format!("{head}:refs/heads/{}", self.project.id);
 
         // Push to the remote
         remote
@@ -355,10 +397,15 @@ impl Repository {
         Ok(session)
     }
 
-    pub fn lock(&self) -> LockFile {
-        let mut lockfile = LockFile::open(&self.lock_path).expect("failed to open lock file");
-        lockfile.lock().expect("failed to obtain lock on lock file");
-        lockfile
+    pub fn lock(&self, shard: LockShard) -> lock::FileLock {
+        let lock_path = match shard {
+            LockShard::Sessions => self.lock_path.clone(),
+            LockShard::VirtualBranches => self.lock_path.with_extension("vbranches.lock"),
+            LockShard::Refs => self.lock_path.with_extension("refs.lock"),
+            LockShard::ExternalChanges => self.lock_path.with_extension("external_changes.lock"),
+        };
+        lock::FileLock::acquire(&lock_path, self.lock_strategy)
+            .expect("failed to obtain lock on lock file")
     }
 
     pub fn mark_active_session(&self) -> Result<()> {
@@ -398,7 +445,7 @@ impl Repository {
     }
 
     pub fn get_or_create_current_session(&self) -> Result<sessions::Session> {
-        let _lock = self.lock();
+        let _lock = self.lock(LockShard::Sessions);
 
         let reader = reader::Reader::open(&self.root())?;
         match sessions::Session::try_from(&reader) {
@@ -450,7 +497,7 @@ impl Repository {
             return Err(anyhow!("nothing to flush"));
         }
 
-        let _lock = self.lock();
+        let _lock = self.lock(LockShard::Sessions);
 
         // update last timestamp
         let session_writer =
@@ -459,17 +506,21 @@ impl Repository {
 
         let mut tree_builder = self.git_repository.treebuilder(None);
 
-        tree_builder.upsert(
-            "session",
-            build_session_tree(self).context("failed to build session tree")?,
-            git::FileMode::Tree,
-        );
+        // the working directory is the slow part, build it before blocking virtual branches
         tree_builder.upsert(
             "wd",
             build_wd_tree(self, project_repository)
                 .context("failed to build working directory tree")?,
             git::FileMode::Tree,
         );
+
+        let _vbranches_lock = self.lock(LockShard::VirtualBranches);
+
+        tree_builder.upsert(
+            "session",
+            build_session_tree(self).context("failed to build session tree")?,
+            git::FileMode::Tree,
+        );
         tree_builder.upsert(
             "branches",
             build_branches_tree(self).context("failed to build branches tree")?,
@@ -478,8 +529,10 @@ impl Repository {
 
         let tree_id = tree_builder.write().context("failed to write tree")?;
 
+        let refs_lock = self.lock(LockShard::Refs);
         let commit_oid =
             write_gb_commit(tree_id, self, user).context("failed to write gb commit")?;
+        drop(refs_lock);
 
         tracing::info!(
             project_id = %self.project.id,
@@ -503,7 +556,7 @@ impl Repository {
     }
 
     pub fn get_current_session(&self) -> Result<Option<sessions::Session>> {
-        let _lock = self.lock();
+        let _lock = self.lock(LockShard::Sessions);
         let reader = reader::Reader::open(&self.root())?;
         match sessions::Session::try_from(&reader) {
             Ok(session) => Ok(Some(session)),
@@ -512,15 +565,23 @@ impl Repository {
         }
     }
 
-    pub(crate) fn root(&self) -> std::path::PathBuf {
+    pub fn root(&self) -> std::path::PathBuf {
         self.git_repository.path().join("gitbutler")
     }
 
-    pub(crate) fn session_path(&self) -> std::path::PathBuf {
+    /// What [`Self::root`] is for the project `project_id`, without opening its repository.
+    pub fn root_of(local_data_dir: &path::Path, project_id: &ProjectId) -> path::PathBuf {
+        local_data_dir
+            .join("projects")
+            .join(project_id.to_string())
+            .join("gitbutler")
+    }
+
+    pub fn session_path(&self) -> std::path::PathBuf {
         self.root().join("session")
     }
 
-    pub(crate) fn session_wd_path(&self) -> std::path::PathBuf {
+    pub fn session_wd_path(&self) -> std::path::PathBuf {
         self.session_path().join("wd")
     }
 
@@ -549,7 +610,7 @@ impl Repository {
             "api": self.project.api,
         });
 
-        let gb_file_path = self.project.path.join(".git/gitbutler.json");
+        let gb_file_path = self.project_git_dir.join("gitbutler.json");
         std::fs::write(&gb_file_path, gb_file_content.to_string())?;
 
         tracing::debug!("gitbutler file updated: {:?}", gb_file_path);
@@ -642,7 +703,7 @@ fn build_wd_tree_from_repo(
 ) -> Result<git::Oid> {
     let mut index = git::Index::new()?;
 
-    let mut added: HashMap<String, bool> = HashMap::new();
+    let mut added: HashSet<path::PathBuf> = HashSet::new();
 
     // first, add session/wd files. session/wd are written at the same time as deltas, so it's important to add them first
     // to make sure they are in sync with the deltas
@@ -672,7 +733,7 @@ fn build_wd_tree_from_repo(
                 file_path.display()
             )
         })?;
-        added.insert(file_path.to_string_lossy().to_string(), true);
+        added.insert(file_path);
     }
 
     // finally, add files from the working directory if they aren't already in the index
@@ -684,7 +745,7 @@ fn build_wd_tree_from_repo(
             )
         })?
     {
-        if added.contains_key(&file_path.to_string_lossy().to_string()) {
+        if added.contains(&file_path) {
             continue;
         }
 
@@ -726,7 +787,14 @@ fn add_wd_path(
     rel_file_path: &std::path::Path,
     gb_repository: &Repository,
 ) -> Result<()> {
-    let file_path = dir.join(rel_file_path);
+    // session files are copies of the content that links point to, links are recorded as
+    // they are in the project
+    let project_file_path = gb_repository.project.path.join(rel_file_path);
+    let file_path = if project_file_path.is_symlink() {
+        project_file_path
+    } else {
+        dir.join(rel_file_path)
+    };
 
     let metadata = std::fs::symlink_metadata(&file_path).context("failed to get metadata for")?;
     let modify_time = FileTime::from_last_modification_time(&metadata);
@@ -737,15 +805,17 @@ fn add_wd_path(
     // TODO: size limit should be configurable
     let blob = if metadata.is_symlink() {
         // it's a symlink, make the content the path of the link
-        let link_target = std::fs::read_link(&file_path)?;
-        // if the link target is inside the project repository, make it relative
-        let link_target = link_target.strip_prefix(dir).unwrap_or(&link_target);
-        gb_repository.git_repository.blob(
-            link_target
-                .to_str()
-                .ok_or_else(|| Error::InvalidUnicodePath(link_target.into()))?
-                .as_bytes(),
-        )?
+        let link_target = fs::relative_link_target(
+            &gb_repository.project.path,
+            &file_path,
+            std::fs::read_link(&file_path)?,
+        );
+        let link_target = link_target
+            .to_str()
+            .ok_or_else(|| Error::InvalidUnicodePath(link_target.clone()))?;
+        gb_repository
+            .git_repository
+            .blob(fs::link_target_to_git(link_target).as_bytes())?
     } else if metadata.len() > 100_000_000 {
         tracing::warn!(
             project_id = %gb_repository.project.id,
@@ -787,13 +857,17 @@ fn add_wd_path(
             mtime: modify_time,
             dev: metadata.dev() as u32,
             ino: metadata.ino() as u32,
-            mode: 33188,
+            mode: if metadata.is_symlink() {
+                0o120_000
+            } else {
+                33188
+            },
             uid: metadata.uid(),
             gid: metadata.gid(),
             file_size: metadata.len() as u32,
             flags: 10, // normal flags for normal file (for the curious: https://git-scm.com/docs/index-format)
             flags_extended: 0, // no extended flags
-            path: rel_file_path.to_str().unwrap().to_string().into(),
+            path: git::path::to_bytes(rel_file_path).into(),
             id: blob,
         })
         .with_context(|| format!("failed to add index entry for {}", rel_file_path.display()))?;
@@ -899,7 +973,7 @@ fn add_file_to_index(
             file_size: metadata.len() as u32,
             flags: 10, // normal flags for normal file (for the curious: https://git-scm.com/docs/index-format)
             flags_extended: 0, // no extended flags
-            path: rel_file_path.to_str().unwrap().into(),
+            path: git::path::to_bytes(rel_file_path).into(),
             id: blob,
         })
         .with_context(|| format!("Failed to add file to index: {}", abs_file_path.display()))?;
@@ -1,6 +1,16 @@
+pub mod bundle;
+mod cleaned;
+pub mod commit_graph;
 pub mod credentials;
 pub mod diff;
+pub mod eol;
+pub mod filters;
+pub mod history;
+pub mod lfs;
+pub mod path;
 pub mod show;
+pub mod sparse;
+mod tree_cache;
 
 mod blob;
 pub use blob::*;
@@ -35,6 +45,9 @@ pub use oid::*;
 mod signature;
 pub use signature::*;
 
+mod mailmap;
+pub use mailmap::*;
+
 mod config;
 pub use config::*;
 
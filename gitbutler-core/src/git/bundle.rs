@@ -0,0 +1,83 @@
+//! Git bundles, through the `git bundle` executable.
+//!
+//! libgit2 can neither write nor read bundles, so [`create`] and [`unbundle`] run git
+//! itself. A bundle holds references and the objects they need, minus the ones of its
+//! prerequisites, which the repository a bundle is read into must already have.
+
+use std::{path, process::Command};
+
+use anyhow::{Context, Result};
+
+use super::{Oid, Repository};
+
+/// Writes the bundle `path` with the references `refnames` and the objects they need,
+/// minus the ones reachable from the `basis` commits.
+pub fn create(
+    repository: &Repository,
+    path: &path::Path,
+    refnames: &[String],
+    basis: &[Oid],
+) -> Result<()> {
+    let mut command = command(repository);
+    command
+        .args(["bundle", "create", "--quiet"])
+        .arg(path)
+        .args(refnames)
+        .args(basis.iter().map(|oid| format!("^{oid}")));
+    run(command).map(|_| ())
+}
+
+/// Why the bundle `path` can't be read into `repository`, like a prerequisite it's
+/// missing, or `None` if it can.
+pub fn verify(repository: &Repository, path: &path::Path) -> Result<Option<String>> {
+    let output = command(repository)
+        .args(["bundle", "verify", "--quiet"])
+        .arg(path)
+        .output()
+        .context("failed to run git, is it installed?")?;
+    if output.status.success() {
+        Ok(None)
+    } else {
+        Ok(Some(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// Writes the objects of the bundle `path` to the odb of `repository`, returning the
+/// references of the bundle. No reference of `repository` is updated.
+pub fn unbundle(repository: &Repository, path: &path::Path) -> Result<Vec<(String, Oid)>> {
+    let mut command = command(repository);
+    command.args(["bundle", "unbundle"]).arg(path);
+    let stdout = run(command)?;
+    String::from_utf8_lossy(&stdout)
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(oid, refname)| {
+            let oid = oid
+                .parse()
+                .with_context(|| format!("malformed bundle reference {refname}"))?;
+            Ok((refname.to_string(), oid))
+        })
+        .collect()
+}
+
+fn command(repository: &Repository) -> Command {
+    let mut command = Command::new("git");
+    command.arg("--git-dir").arg(repository.path());
+    command
+}
+
+fn run(mut command: Command) -> Result<Vec<u8>> {
+    let output = command
+        .output()
+        .context("failed to run git, is it installed?")?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(anyhow::anyhow!(
+            "git bundle failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
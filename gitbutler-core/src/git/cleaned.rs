@@ -0,0 +1,73 @@
+//! What contents were cleaned to by [`super::filters`] and [`super::lfs`], so that files that
+//! didn't change since they were last cleaned aren't cleaned again. Cleaned contents are in
+//! the odb, only their ids are kept here, within the memory budget of
+//! [`memory::Cache::CleanedContents`]: once it's over budget, the entries that were cleaned
+//! first are forgotten first.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    mem,
+};
+
+use crate::memory;
+
+use super::Oid;
+
+pub(super) struct Cleaned<K> {
+    entries: HashMap<K, (Oid, usize)>,
+    // insertion order, used for eviction
+    order: VecDeque<K>,
+}
+
+impl<K> Default for Cleaned<K> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash> Cleaned<K> {
+    pub fn get(&self, key: &K) -> Option<Oid> {
+        self.entries.get(key).map(|(id, _)| *id)
+    }
+
+    /// Remembers that the content of `key` was cleaned to `id`. `key_size` is what the heap
+    /// data of the key takes, like the bytes of its paths.
+    pub fn insert(&mut self, key: K, key_size: usize, id: Oid) {
+        // the key is both in the map and in the eviction queue
+        let size = 2 * (mem::size_of::<K>() + key_size) + mem::size_of::<(Oid, usize)>();
+        match self.entries.insert(key.clone(), (id, size)) {
+            Some(_) => return,
+            None => memory::charge(memory::Cache::CleanedContents, size),
+        }
+        self.order.push_back(key);
+
+        while memory::is_over_budget(memory::Cache::CleanedContents) {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some((_, size)) = self.entries.remove(&oldest) {
+                memory::release(memory::Cache::CleanedContents, size);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembers_what_was_cleaned() {
+        let id = "a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2"
+            .parse::<Oid>()
+            .unwrap();
+        let mut cleaned = Cleaned::default();
+        cleaned.insert("content".to_string(), 7, id);
+        assert_eq!(cleaned.get(&"content".to_string()), Some(id));
+        assert_eq!(cleaned.get(&"other".to_string()), None);
+    }
+}
@@ -49,6 +49,11 @@ impl<'repo> Commit<'repo> {
         Ok(parents)
     }
 
+    /// The ids of the parents, which unlike [`Self::parents`] doesn't need them to be there.
+    pub fn parent_ids(&self) -> Vec<Oid> {
+        self.commit.parent_ids().map(Into::into).collect()
+    }
+
     pub fn parent(&self, n: usize) -> Result<Commit<'repo>> {
         self.commit.parent(n).map(Into::into).map_err(Into::into)
     }
@@ -0,0 +1,238 @@
+//! Reads the commits git writes to `objects/info/commit-graph`.
+//!
+//! The commit-graph lists the parents and the time of every commit in it, so walking the
+//! history with it doesn't read and parse each commit from the object database. Like libgit2,
+//! only a single file is read, not split chains, and only with sha1 ids. Commits that aren't
+//! in it, like the ones made since it was written, are read from the object database.
+
+use anyhow::{Context, Result};
+
+use super::{Oid, Repository};
+
+const SIGNATURE: &[u8] = b"CGPH";
+const HEADER_LEN: usize = 8;
+const CHUNK_ENTRY_LEN: usize = 12;
+const OID_LEN: usize = 20;
+// the tree, the first two parents, and the generation and time
+const COMMIT_DATA_LEN: usize = OID_LEN + 16;
+
+const OID_FANOUT: &[u8] = b"OIDF";
+const OID_LOOKUP: &[u8] = b"OIDL";
+const COMMIT_DATA: &[u8] = b"CDAT";
+const EXTRA_EDGES: &[u8] = b"EDGE";
+
+const PARENT_NONE: u32 = 0x7000_0000;
+const EXTRA_EDGES_NEEDED: u32 = 0x8000_0000;
+const LAST_EDGE: u32 = 0x8000_0000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitGraph {
+    data: Vec<u8>,
+    commits: usize,
+    fanout: usize,
+    oids: usize,
+    commit_data: usize,
+    extra_edges: Option<usize>,
+}
+
+/// A commit of the graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphCommit {
+    /// The commit time, in seconds since the epoch.
+    pub time: i64,
+    pub parents: Vec<Oid>,
+}
+
+impl CommitGraph {
+    /// The commit-graph of the repository, `None` if it has none, if `core.commitGraph` is
+    /// off, or if it is one this can't read.
+    pub fn open(repository: &Repository) -> Result<Option<Self>> {
+        let config = repository.config().context("failed to get config")?;
+        if config.get_bool("core.commitGraph")? == Some(false) {
+            return Ok(None);
+        }
+        let path = repository
+            .common_dir()
+            .join("objects")
+            .join("info")
+            .join("commit-graph");
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+        let graph = Self::parse(data);
+        if graph.is_none() {
+            tracing::warn!(path = %path.display(), "ignoring commit-graph that can't be read");
+        }
+        Ok(graph)
+    }
+
+    fn parse(data: Vec<u8>) -> Option<Self> {
+        // version 1, sha1, and no base graphs
+        let header = data.get(..HEADER_LEN)?;
+        if header.get(..4)? != SIGNATURE
+            || header.get(4) != Some(&1)
+            || header.get(5) != Some(&1)
+            || header.get(7) != Some(&0)
+        {
+            return None;
+        }
+        let chunk_count = usize::from(*header.get(6)?);
+
+        let mut fanout = None;
+        let mut oids = None;
+        let mut commit_data = None;
+        let mut extra_edges = None;
+        for index in 0..chunk_count {
+            let entry = data.get(HEADER_LEN + index * CHUNK_ENTRY_LEN..)?;
+            let offset = usize::try_from(read_u64(entry.get(4..)?)?).ok()?;
+            match entry.get(..4)? {
+                OID_FANOUT => fanout = Some(offset),
+                OID_LOOKUP => oids = Some(offset),
+                COMMIT_DATA => commit_data = Some(offset),
+                EXTRA_EDGES => extra_edges = Some(offset),
+                _ => {}
+            }
+        }
+        let (fanout, oids, commit_data) = (fanout?, oids?, commit_data?);
+
+        let commits = usize::try_from(read_u32(data.get(fanout + 255 * 4..)?)?).ok()?;
+        // every id and every commit must be there
+        data.get(oids..oids + commits * OID_LEN)?;
+        data.get(commit_data..commit_data + commits * COMMIT_DATA_LEN)?;
+        Some(Self {
+            data,
+            commits,
+            fanout,
+            oids,
+            commit_data,
+            extra_edges,
+        })
+    }
+
+    /// The commit with the id, `None` if it isn't in the graph.
+    pub fn commit(&self, id: Oid) -> Option<GraphCommit> {
+        let position = self.position(id)?;
+        let entry = self
+            .data
+            .get(self.commit_data + position * COMMIT_DATA_LEN + OID_LEN..)?;
+
+        let mut parents = vec![];
+        let first = read_u32(entry)?;
+        if first != PARENT_NONE {
+            parents.push(self.oid(usize::try_from(first).ok()?)?);
+        }
+        let second = read_u32(entry.get(4..)?)?;
+        if second & EXTRA_EDGES_NEEDED != 0 {
+            // an octopus merge, the parents after the first are listed in the edges chunk
+            let mut edge = usize::try_from(second & !EXTRA_EDGES_NEEDED).ok()?;
+            loop {
+                let value = read_u32(self.data.get(self.extra_edges? + edge * 4..)?)?;
+                parents.push(self.oid(usize::try_from(value & !LAST_EDGE).ok()?)?);
+                if value & LAST_EDGE != 0 {
+                    break;
+                }
+                edge += 1;
+            }
+        } else if second != PARENT_NONE {
+            parents.push(self.oid(usize::try_from(second).ok()?)?);
+        }
+
+        // the two lowest bits of the generation are the highest of the 34 bit time
+        let high = i64::from(read_u32(entry.get(8..)?)? & 0b11);
+        let low = i64::from(read_u32(entry.get(12..)?)?);
+        Some(GraphCommit {
+            time: (high << 32) | low,
+            parents,
+        })
+    }
+
+    // the ids are sorted, the fanout tells how many of them start with a byte up to each one
+    fn position(&self, id: Oid) -> Option<usize> {
+        let id = git2::Oid::from(id);
+        let bytes = id.as_bytes();
+        let first = usize::from(*bytes.first()?);
+        let end = usize::try_from(read_u32(self.data.get(self.fanout + first * 4..)?)?).ok()?;
+        let start = match first.checked_sub(1) {
+            Some(previous) => {
+                usize::try_from(read_u32(self.data.get(self.fanout + previous * 4..)?)?).ok()?
+            }
+            None => 0,
+        };
+        let (mut low, mut high) = (start, end.min(self.commits));
+        while low < high {
+            let middle = low + ((high - low) >> 1);
+            let offset = self.oids + middle * OID_LEN;
+            match self.data.get(offset..offset + OID_LEN)?.cmp(bytes) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return Some(middle),
+            }
+        }
+        None
+    }
+
+    fn oid(&self, position: usize) -> Option<Oid> {
+        if position >= self.commits {
+            return None;
+        }
+        let start = self.oids + position * OID_LEN;
+        let bytes = self.data.get(start..start + OID_LEN)?;
+        git2::Oid::from_bytes(bytes).ok().map(Into::into)
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?))
+}
+
+fn read_u64(bytes: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(..8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils;
+
+    use super::*;
+
+    #[test]
+    fn reads_what_git_writes() {
+        let repository = test_utils::test_repository();
+        let first = test_utils::commit_all(&repository);
+        let second = test_utils::commit_all(&repository);
+        assert_eq!(CommitGraph::open(&repository).unwrap(), None);
+
+        let status = std::process::Command::new("git")
+            .arg("--git-dir")
+            .arg(repository.path())
+            .args(["commit-graph", "write", "--reachable", "--no-progress"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let third = test_utils::commit_all(&repository);
+
+        let graph = CommitGraph::open(&repository).unwrap().unwrap();
+        let commit = repository.find_commit(second).unwrap();
+        assert_eq!(
+            graph.commit(second),
+            Some(GraphCommit {
+                time: commit.time().seconds(),
+                parents: vec![first],
+            })
+        );
+        let root = repository
+            .find_commit(first)
+            .unwrap()
+            .parent_ids()
+            .first()
+            .copied()
+            .unwrap();
+        assert_eq!(graph.commit(root).unwrap().parents, vec![]);
+        // made after the graph was written
+        assert_eq!(graph.commit(third), None);
+    }
+}
@@ -31,6 +31,14 @@ impl Config {
             .map_err(Into::into)
     }
 
+    /// Removes the entry `key`, if there is one.
+    pub fn remove(&mut self, key: &str) -> Result<()> {
+        match self.config.remove(key).map_err(Into::into) {
+            Ok(()) | Err(Error::NotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn get_string(&self, key: &str) -> Result<Option<String>> {
         match self.config.get_string(key).map_err(Into::into) {
             Ok(value) => Ok(Some(value)),
@@ -46,6 +54,27 @@ impl Config {
             Err(e) => Err(e),
         }
     }
+
+    /// Integers can have a `k`, `m` or `g` suffix, like in git.
+    pub fn get_i64(&self, key: &str) -> Result<Option<i64>> {
+        match self.config.get_i64(key).map_err(Into::into) {
+            Ok(value) => Ok(Some(value)),
+            Err(Error::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The names of the entries that match `regexp`.
+    pub fn names(&self, regexp: &str) -> Result<Vec<String>> {
+        let entries = self.config.entries(Some(regexp))?;
+        let mut names = vec![];
+        for entry in &entries {
+            if let Some(name) = entry?.name() {
+                names.push(name.to_string());
+            }
+        }
+        Ok(names)
+    }
 }
 
 #[cfg(test)]
@@ -1,7 +1,5 @@
 use std::{env, path};
 
-use tauri::AppHandle;
-
 use crate::{keys, project_repository, projects, users};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -74,14 +72,8 @@ pub struct Helper {
     keys: keys::Controller,
     users: users::Controller,
     home_dir: Option<path::PathBuf>,
-}
-
-impl From<&AppHandle> for Helper {
-    fn from(value: &AppHandle) -> Self {
-        let keys = keys::Controller::from(value);
-        let users = users::Controller::from(value);
-        Self::new(keys, users, env::var_os("HOME").map(path::PathBuf::from))
-    }
+    // the token, and the host it's for
+    token: Option<(String, String)>,
 }
 
 impl From<&path::PathBuf> for Helper {
@@ -137,9 +129,18 @@ impl Helper {
             keys,
             users,
             home_dir,
+            token: None,
         }
     }
 
+    /// Authenticates with `token` over https instead of with the keys and credentials
+    /// known to the app, for environments like CI where nobody logged in. The token is only
+    /// sent to remotes on `host`, the others are authenticated as usual.
+    pub fn with_token(mut self, token: Option<String>, host: &str) -> Self {
+        self.token = token.map(|token| (token, host.to_string()));
+        self
+    }
+
     pub fn help<'a>(
         &'a self,
         project_repository: &'a project_repository::Repository,
@@ -153,6 +154,27 @@ impl Helper {
             return Ok(vec![(remote, vec![Credential::Noop])]);
         }
 
+        let token = self.token.as_ref().filter(|(_, host)| {
+            remote_url
+                .host
+                .as_ref()
+                .is_some_and(|remote_host| remote_host.eq_ignore_ascii_case(host))
+        });
+        if let Some((token, _)) = token {
+            let https_remote = if remote_url.scheme == super::Scheme::Https {
+                Ok(remote)
+            } else {
+                let url = remote_url.as_https()?;
+                project_repository.git_repository.remote_anonymous(&url)
+            }?;
+            return Ok(vec![(
+                https_remote,
+                vec![Credential::Https(HttpsCredential::GitHubToken(
+                    token.clone(),
+                ))],
+            )]);
+        }
+
         match &project_repository.project().preferred_key {
             projects::AuthKey::Local {
                 private_key_path,
@@ -413,6 +435,7 @@ mod tests {
         github_access_token: Option<&'a str>,
         preferred_key: projects::AuthKey,
         home_dir: Option<path::PathBuf>,
+        token: Option<(&'a str, &'a str)>,
     }
 
     impl TestCase<'_> {
@@ -427,7 +450,10 @@ mod tests {
             users.set_user(&user).unwrap();
 
             let keys = keys::Controller::from(&local_app_data);
-            let helper = Helper::new(keys, users, self.home_dir.clone());
+            let helper = Helper::new(keys, users, self.home_dir.clone()).with_token(
+                self.token.map(|(token, _)| token.to_string()),
+                self.token.map_or("", |(_, host)| host),
+            );
 
             let repo = test_repository();
             repo.remote(
@@ -717,4 +743,60 @@ mod tests {
             }
         }
     }
+
+    mod with_token {
+        use super::*;
+
+        #[test]
+        fn ssh() {
+            let test_case = TestCase {
+                remote_url: "git@github.com:gitbutlerapp/gitbutler.git",
+                github_access_token: Some("stored"),
+                preferred_key: projects::AuthKey::Local {
+                    private_key_path: path::PathBuf::from("/tmp/id_rsa"),
+                    passphrase: None,
+                },
+                token: Some(("token", "github.com")),
+                ..Default::default()
+            };
+            let flow = test_case.run();
+            assert_eq!(flow.len(), 1);
+            assert_eq!(
+                flow[0].0,
+                "https://github.com/gitbutlerapp/gitbutler.git".to_string(),
+            );
+            assert_eq!(
+                flow[0].1,
+                vec![Credential::Https(HttpsCredential::GitHubToken(
+                    "token".to_string()
+                ))]
+            );
+        }
+
+        #[test]
+        fn other_host() {
+            let test_case = TestCase {
+                remote_url: "git@gitlab.com:test-gitbutler/test.git",
+                preferred_key: projects::AuthKey::Local {
+                    private_key_path: path::PathBuf::from("/tmp/id_rsa"),
+                    passphrase: None,
+                },
+                token: Some(("token", "github.com")),
+                ..Default::default()
+            };
+            let flow = test_case.run();
+            assert_eq!(flow.len(), 1);
+            assert_eq!(
+                flow[0].0,
+                "git@gitlab.com:test-gitbutler/test.git".to_string(),
+            );
+            assert_eq!(
+                flow[0].1,
+                vec![Credential::Ssh(SshCredential::Keyfile {
+                    key_path: path::PathBuf::from("/tmp/id_rsa"),
+                    passphrase: None,
+                })]
+            );
+        }
+    }
 }
@@ -0,0 +1,1655 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    fs, mem, path, str,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time,
+};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{git, projects::UnmanagedPaths};
+
+use super::{tree_cache::TreeCache, Repository};
+
+/// The type of change
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeType {
+    /// Entry does not exist in old version
+    Added,
+    /// Entry does not exist in new version
+    Deleted,
+    /// Entry content changed between old and new
+    Modified,
+}
+impl From<git2::Delta> for ChangeType {
+    fn from(v: git2::Delta) -> Self {
+        use git2::Delta as D;
+        use ChangeType as C;
+        match v {
+            D::Untracked | D::Added => C::Added,
+            D::Modified
+            | D::Unmodified
+            | D::Renamed
+            | D::Copied
+            | D::Typechange
+            | D::Conflicted => C::Modified,
+            D::Ignored | D::Unreadable | D::Deleted => C::Deleted,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub diff: String,
+    pub binary: bool,
+    /// true if the file is marked as `linguist-generated` in `.gitattributes`.
+    /// generated files are never diffed as text: like binary files, they are
+    /// represented by a single hunk holding the id of the new blob.
+    pub generated: bool,
+    pub change_type: ChangeType,
+}
+
+pub struct Options {
+    pub context_lines: u32,
+    /// Whether lines that only differ in whitespace are the same, like `git diff -w`.
+    pub ignore_whitespace: bool,
+    /// Whether lines that are added or removed and are blank are left out of the hunks.
+    pub ignore_blank_lines: bool,
+    /// How lines are matched, `diff.algorithm` of the git config if it's not set.
+    pub algorithm: Option<Algorithm>,
+    /// Whether new files that are similar to a file of the tree, changed or not, are
+    /// diffed as a copy of it. Finding them means comparing with every file of the tree,
+    /// so it's opt-in.
+    pub detect_copies: bool,
+    /// The paths that are left out of the diff, the files there are never read.
+    pub unmanaged: UnmanagedPaths,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            context_lines: 3,
+            ignore_whitespace: false,
+            ignore_blank_lines: false,
+            algorithm: None,
+            detect_copies: false,
+            unmanaged: UnmanagedPaths::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Algorithm {
+    Myers,
+    /// Myers, spending extra time to find the smallest diff.
+    Minimal,
+    Patience,
+    /// libgit2 has no histogram diff, it is diffed with patience, which histogram extends.
+    Histogram,
+}
+
+impl Algorithm {
+    /// The algorithm `diff.algorithm` of the git config of `repository` names, if it's set
+    /// to one that is known.
+    pub fn from_config(repository: &Repository) -> Result<Option<Self>> {
+        let config = repository.config().context("failed to get config")?;
+        let algorithm = config
+            .get_string("diff.algorithm")
+            .context("failed to read diff.algorithm")?;
+        Ok(
+            algorithm.and_then(|algorithm| match algorithm.to_lowercase().as_str() {
+                "default" | "myers" => Some(Self::Myers),
+                "minimal" => Some(Self::Minimal),
+                "patience" => Some(Self::Patience),
+                "histogram" => Some(Self::Histogram),
+                _ => None,
+            }),
+        )
+    }
+
+    // the algorithm of `options`, falling back to the one of the config
+    fn resolve(repository: &Repository, options: &Options) -> Result<Self> {
+        match options.algorithm {
+            Some(algorithm) => Ok(algorithm),
+            None => Ok(Self::from_config(repository)?.unwrap_or(Self::Myers)),
+        }
+    }
+
+    fn apply(self, diff_opts: &mut git2::DiffOptions) {
+        diff_opts
+            .minimal(self == Self::Minimal)
+            .patience(matches!(self, Self::Patience | Self::Histogram));
+    }
+}
+
+pub fn workdir(
+    repository: &Repository,
+    commit_oid: &git::Oid,
+) -> Result<HashMap<path::PathBuf, Vec<Hunk>>> {
+    workdir_with_progress(repository, commit_oid, &Options::default(), &|_, _| {})
+}
+
+/// Same as `workdir`, but with `options`, and calls `progress` with the number of files
+/// scanned so far and the total number of changed files as the diff is being computed.
+pub fn workdir_with_progress(
+    repository: &Repository,
+    commit_oid: &git::Oid,
+    options: &Options,
+    progress: &dyn Fn(usize, usize),
+) -> Result<HashMap<path::PathBuf, Vec<Hunk>>> {
+    let commit = repository
+        .find_commit(*commit_oid)
+        .context("failed to find commit")?;
+    let tree = commit.tree().context("failed to find tree")?;
+    let algorithm = Algorithm::resolve(repository, options)?;
+
+    let mut diff_opts = git2::DiffOptions::new();
+    algorithm.apply(&mut diff_opts);
+    diff_opts
+        .recurse_untracked_dirs(true)
+        .include_untracked(true)
+        .show_untracked_content(true)
+        .ignore_submodules(true)
+        .ignore_whitespace(options.ignore_whitespace)
+        .ignore_blank_lines(options.ignore_blank_lines)
+        // unchanged files are only there to copy from
+        .include_unmodified(options.detect_copies)
+        .context_lines(0);
+    if let Some(scope) = options.unmanaged.scope() {
+        // nothing outside of the scope is walked
+        diff_opts.pathspec(scope);
+    }
+
+    let mut diff = repository.diff_tree_to_workdir(Some(&tree), Some(&mut diff_opts))?;
+    // new files come from the working directory, only the tree side can be missing
+    fetch_missing_blobs(repository, &diff, false);
+    find_similar(&mut diff, options)?;
+
+    let mut hunks = hunks_by_filepath(repository, &diff, &options.unmanaged, progress)?;
+    if repository.ignore_case() {
+        apply_case_only_renames(repository, &tree, algorithm, options, &mut hunks)
+            .context("failed to detect case only renames")?;
+    }
+    apply_conversions(repository, &tree, algorithm, options, &mut hunks)
+        .context("failed to convert files as they are committed")?;
+    hunks.extend(submodule_hunks(repository, &tree).context("failed to diff submodules")?);
+    if let Some(cone) =
+        git::sparse::Cone::read(repository).context("failed to read sparse checkout")?
+    {
+        // files outside of the sparse checkout aren't deleted, they just aren't checked out
+        hunks.retain(|path, hunks| {
+            cone.contains(path)
+                || !hunks
+                    .iter()
+                    .all(|hunk| matches!(hunk.change_type, ChangeType::Deleted))
+        });
+    }
+    if repository.uses_lfs() {
+        apply_lfs_pointers(repository, &tree, &mut hunks);
+    }
+    Ok(hunks)
+}
+
+// libgit2 compares the content of lfs files on disk with the pointers that are committed in
+// their place, so they show up as changed whenever they are checked out. they are compared by
+// the sha256 of the pointers instead, and a change is a single hunk holding the id of the new
+// pointer, like for binary files.
+fn apply_lfs_pointers(
+    repository: &Repository,
+    tree: &git::Tree,
+    hunks: &mut HashMap<path::PathBuf, Vec<Hunk>>,
+) {
+    let Some(workdir) = repository.workdir() else {
+        return;
+    };
+    let lfs_paths = hunks
+        .keys()
+        .filter(|path| repository.is_path_lfs(path).unwrap_or(false))
+        .filter_map(|path| Some((path.clone(), stamp(&workdir.join(path))?)))
+        .collect::<Vec<_>>();
+
+    // taken out while files are cleaned, which other repositories don't wait for
+    let pointers = lfs_pointers()
+        .remove(repository.path(), tree.id())
+        .unwrap_or_default();
+    let mut compared = HashMap::new();
+    for (path, stamp) in lfs_paths {
+        let pointer = match pointers.get(&path) {
+            Some((cached_stamp, pointer)) if *cached_stamp == stamp => *pointer,
+            _ => match lfs_pointer(repository, tree, &path) {
+                Some(pointer) => pointer,
+                None => continue,
+            },
+        };
+        compared.insert(path.clone(), (stamp, pointer));
+        let LfsPointer::Cleaned(pointer_oid) = pointer else {
+            hunks.remove(&path);
+            continue;
+        };
+        let committed = tree.get_path(&path).ok().map(|entry| entry.id());
+        if committed == Some(pointer_oid) {
+            hunks.remove(&path);
+            continue;
+        }
+        hunks.insert(
+            path,
+            vec![Hunk {
+                old_start: 0,
+                old_lines: 0,
+                new_start: 0,
+                new_lines: 0,
+                diff: pointer_oid.to_string(),
+                binary: true,
+                generated: false,
+                change_type: if committed.is_some() {
+                    ChangeType::Modified
+                } else {
+                    ChangeType::Added
+                },
+            }],
+        );
+    }
+    let size = compared
+        .keys()
+        .map(|path| {
+            path.as_os_str().len() + mem::size_of::<(path::PathBuf, (FileStamp, LfsPointer))>()
+        })
+        .sum();
+    lfs_pointers().insert(repository.path().to_path_buf(), tree.id(), compared, size);
+}
+
+// the pointers of the lfs files checked out in every repository, by its git directory, for
+// the tree they were last compared with. only the files that changed on disk since are
+// hashed again
+type LfsPointers = HashMap<path::PathBuf, (FileStamp, LfsPointer)>;
+static LFS_POINTERS: Lazy<Mutex<TreeCache<LfsPointers>>> = Lazy::new(Mutex::default);
+
+fn lfs_pointers() -> MutexGuard<'static, TreeCache<LfsPointers>> {
+    LFS_POINTERS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// the size and modification time of a file, which tell whether it changed
+type FileStamp = (u64, Option<time::SystemTime>);
+
+fn stamp(path: &path::Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok().filter(fs::Metadata::is_file)?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+#[derive(Clone, Copy)]
+enum LfsPointer {
+    // the file has the content of the pointer of the tree
+    Committed,
+    Cleaned(git::Oid),
+}
+
+// the pointer of the lfs file at `path`, `None` if it can't be cleaned
+fn lfs_pointer(repository: &Repository, tree: &git::Tree, path: &path::Path) -> Option<LfsPointer> {
+    // most are checked out as they are committed, which needs no cleaning to tell
+    let committed_pointer = tree
+        .get_path(path)
+        .ok()
+        .and_then(|entry| repository.find_blob(entry.id()).ok());
+    if committed_pointer
+        .is_some_and(|pointer| git::lfs::matches_pointer(repository, path, pointer.content()))
+    {
+        return Some(LfsPointer::Committed);
+    }
+    match git::lfs::clean(repository, path) {
+        Ok(pointer_oid) => Some(LfsPointer::Cleaned(pointer_oid)),
+        Err(error) => {
+            tracing::warn!(?error, path = %path.display(), "failed to clean lfs file");
+            None
+        }
+    }
+}
+
+const SUBMODULE_PREFIX: &str = "Subproject commit ";
+
+impl Hunk {
+    /// Whether the hunk is a change of the commit a submodule points to, in the format git
+    /// uses for it, `Subproject commit <id>`.
+    pub fn is_submodule(&self) -> bool {
+        let mut lines = self
+            .diff
+            .lines()
+            .filter(|line| !line.starts_with("@@"))
+            .peekable();
+        lines.peek().is_some()
+            && lines.all(|line| {
+                line.strip_prefix(['-', '+'])
+                    .is_some_and(|line| line.starts_with(SUBMODULE_PREFIX))
+            })
+    }
+
+    /// The commit a submodule points to after the change of the hunk, `None` if the
+    /// submodule is removed.
+    pub fn submodule_commit(&self) -> Option<git::Oid> {
+        self.diff
+            .lines()
+            .find_map(|line| line.strip_prefix('+')?.strip_prefix(SUBMODULE_PREFIX))
+            .and_then(|oid| oid.trim().parse().ok())
+    }
+
+    /// The modes before and after the hunk if it changes the mode of its file, like
+    /// `chmod +x` does, see [`mode_change`].
+    pub fn mode_change(&self) -> Option<(git::FileMode, git::FileMode)> {
+        mode_change(&self.diff)
+    }
+
+    /// The path the file was at before if the hunk renames it, see [`renamed_from`].
+    pub fn renamed_from(&self) -> Option<path::PathBuf> {
+        renamed_from(&self.diff)
+    }
+
+    /// The path of the file the file is a copy of if the hunk copies it, see
+    /// [`copied_from`].
+    pub fn copied_from(&self) -> Option<path::PathBuf> {
+        copied_from(&self.diff)
+    }
+
+    /// Whether the hunk is only a header, a change of mode, a rename or a copy, see
+    /// [`is_header`].
+    pub fn is_header(&self) -> bool {
+        is_header(&self.diff)
+    }
+}
+
+/// Whether the diff of a hunk is only a header, which has no lines to add context to or to
+/// patch the file with: a change of mode, a rename or a copy.
+pub fn is_header(diff: &str) -> bool {
+    mode_change(diff).is_some() || renamed_from(diff).is_some() || copied_from(diff).is_some()
+}
+
+// the two lines of a header, like `rename from` and `rename to`, without their prefixes
+fn header_lines<'d>(
+    diff: &'d str,
+    first_prefix: &str,
+    second_prefix: &str,
+) -> Option<(&'d str, &'d str)> {
+    let mut lines = diff.lines();
+    let first = lines.next()?.strip_prefix(first_prefix)?;
+    let second = lines.next()?.strip_prefix(second_prefix)?;
+    lines.next().is_none().then_some((first, second))
+}
+
+fn header_hunk(diff: String) -> Hunk {
+    Hunk {
+        old_start: 0,
+        old_lines: 0,
+        new_start: 0,
+        new_lines: 0,
+        diff,
+        binary: false,
+        generated: false,
+        change_type: ChangeType::Modified,
+    }
+}
+
+// a header is the first hunk of its file. the empty hunk of a file without changed lines is
+// superseded.
+fn insert_header_hunk(
+    hunks: &mut HashMap<path::PathBuf, Vec<Hunk>>,
+    path: &path::Path,
+    hunk: Hunk,
+) {
+    let file_hunks = hunks.entry(path.to_path_buf()).or_default();
+    file_hunks.retain(|hunk| hunk.binary || !hunk.diff.is_empty());
+    file_hunks.insert(0, hunk);
+}
+
+const RENAME_FROM_PREFIX: &str = "rename from ";
+const RENAME_TO_PREFIX: &str = "rename to ";
+
+const COPY_FROM_PREFIX: &str = "copy from ";
+const COPY_TO_PREFIX: &str = "copy to ";
+
+/// Parses the diff of a hunk that renames a file, which is written like the header git uses
+/// for it:
+///
+/// ```text
+/// rename from src/old.rs
+/// rename to src/new.rs
+/// ```
+pub fn renamed_from(diff: &str) -> Option<path::PathBuf> {
+    source_path(diff, RENAME_FROM_PREFIX, RENAME_TO_PREFIX)
+}
+
+/// Same as [`renamed_from`], for a hunk that copies a file, written with `copy from` and
+/// `copy to`.
+pub fn copied_from(diff: &str) -> Option<path::PathBuf> {
+    source_path(diff, COPY_FROM_PREFIX, COPY_TO_PREFIX)
+}
+
+fn source_path(diff: &str, from_prefix: &str, to_prefix: &str) -> Option<path::PathBuf> {
+    header_lines(diff, from_prefix, to_prefix).map(|(old_path, _)| path::PathBuf::from(old_path))
+}
+
+fn source_hunk(
+    from_prefix: &str,
+    to_prefix: &str,
+    old_path: &path::Path,
+    new_path: &path::Path,
+) -> Hunk {
+    header_hunk(format!(
+        "{from_prefix}{}\n{to_prefix}{}\n",
+        old_path.display(),
+        new_path.display()
+    ))
+}
+
+// like a change of mode, a rename or a copy has no lines. it is the first hunk of the file,
+// the others are relative to the file it was renamed or copied from.
+fn apply_renames(diff: &git2::Diff, hunks: &mut HashMap<path::PathBuf, Vec<Hunk>>) {
+    for delta in diff.deltas() {
+        let (from_prefix, to_prefix) = match delta.status() {
+            git2::Delta::Renamed => (RENAME_FROM_PREFIX, RENAME_TO_PREFIX),
+            git2::Delta::Copied => (COPY_FROM_PREFIX, COPY_TO_PREFIX),
+            _ => continue,
+        };
+        let (Some(old_path), Some(new_path)) = (delta.old_file().path(), delta.new_file().path())
+        else {
+            continue;
+        };
+        insert_header_hunk(
+            hunks,
+            new_path,
+            source_hunk(from_prefix, to_prefix, old_path, new_path),
+        );
+    }
+}
+
+const OLD_MODE_PREFIX: &str = "old mode ";
+const NEW_MODE_PREFIX: &str = "new mode ";
+
+/// Parses the diff of a hunk that changes the mode of a file, which is written like the
+/// header git uses for it:
+///
+/// ```text
+/// old mode 100644
+/// new mode 100755
+/// ```
+pub fn mode_change(diff: &str) -> Option<(git::FileMode, git::FileMode)> {
+    let parse = |mode: &str| {
+        i32::from_str_radix(mode.trim(), 8)
+            .ok()
+            .and_then(|mode| git::FileMode::try_from(mode).ok())
+    };
+    let (old, new) = header_lines(diff, OLD_MODE_PREFIX, NEW_MODE_PREFIX)?;
+    Some((parse(old)?, parse(new)?))
+}
+
+fn mode_hunk(old: git::FileMode, new: git::FileMode) -> Hunk {
+    header_hunk(format!(
+        "{OLD_MODE_PREFIX}{:o}\n{NEW_MODE_PREFIX}{:o}\n",
+        i32::from(old),
+        i32::from(new)
+    ))
+}
+
+// a change of the mode of a file has no lines, so it is a hunk of its own, first among the
+// hunks of the file, that can be owned and committed like the others.
+fn apply_mode_changes(diff: &git2::Diff, hunks: &mut HashMap<path::PathBuf, Vec<Hunk>>) {
+    for delta in diff.deltas() {
+        if !matches!(
+            delta.status(),
+            git2::Delta::Modified | git2::Delta::Renamed | git2::Delta::Copied
+        ) {
+            continue;
+        }
+        let mode = |file: git2::DiffFile| match git::FileMode::try_from(i32::from(file.mode())) {
+            Ok(mode @ (git::FileMode::Blob | git::FileMode::BlobExecutable)) => Some(mode),
+            _ => None,
+        };
+        let (Some(old), Some(new), Some(path)) = (
+            mode(delta.old_file()),
+            mode(delta.new_file()),
+            delta.new_file().path(),
+        ) else {
+            continue;
+        };
+        if old == new {
+            continue;
+        }
+        insert_header_hunk(hunks, path, mode_hunk(old, new));
+    }
+}
+
+// submodules are left out of the workdir diff, so that their content is never scanned. only
+// the commit checked out in each of them is compared with the one `tree` records, which is
+// all a commit can change about a submodule.
+fn submodule_hunks(
+    repository: &Repository,
+    tree: &git::Tree,
+) -> Result<HashMap<path::PathBuf, Vec<Hunk>>> {
+    let workdir = repository.workdir().context("repository has no workdir")?;
+
+    let recorded = gitlinks(repository, tree)?;
+    let mut paths = recorded.keys().cloned().collect::<HashSet<_>>();
+    paths.extend(
+        repository
+            .submodules()?
+            .iter()
+            .map(|submodule| submodule.path().to_path_buf()),
+    );
+
+    let mut hunks = HashMap::new();
+    for path in paths {
+        let full_path = workdir.join(&path);
+        let checked_out = if full_path.exists() {
+            let Some(head) = Repository::open(&full_path)
+                .ok()
+                .and_then(|submodule| submodule.head().ok()?.target())
+            else {
+                // not initialized, there is nothing checked out to compare
+                continue;
+            };
+            Some(head)
+        } else {
+            None
+        };
+        let old = recorded.get(&path).copied();
+        if old != checked_out {
+            hunks.insert(path, vec![submodule_hunk(old, checked_out)]);
+        }
+    }
+    Ok(hunks)
+}
+
+// the submodules of the last tree every repository was compared with, by its git directory,
+// with the commits the tree records for them, so that the tree is walked once rather than
+// on every status
+type Gitlinks = Arc<HashMap<path::PathBuf, git::Oid>>;
+static GITLINKS: Lazy<Mutex<TreeCache<Gitlinks>>> = Lazy::new(Mutex::default);
+
+fn gitlinks(repository: &Repository, tree: &git::Tree) -> Result<Gitlinks> {
+    let cached = GITLINKS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(repository.path(), tree.id())
+        .map(Arc::clone);
+    if let Some(gitlinks) = cached {
+        return Ok(gitlinks);
+    }
+
+    let mut gitlinks = HashMap::new();
+    tree.walk(|root, entry| {
+        if entry.filemode() == i32::from(git::FileMode::Commit) {
+            if let Some(name) = entry.name() {
+                gitlinks.insert(path::Path::new(root).join(name), entry.id());
+            }
+        }
+        git::TreeWalkResult::Continue
+    })?;
+    let size = gitlinks
+        .keys()
+        .map(|path| path.as_os_str().len() + mem::size_of::<(path::PathBuf, git::Oid)>())
+        .sum();
+    let gitlinks = Arc::new(gitlinks);
+    GITLINKS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(
+            repository.path().to_path_buf(),
+            tree.id(),
+            Arc::clone(&gitlinks),
+            size,
+        );
+    Ok(gitlinks)
+}
+
+fn submodule_hunk(old: Option<git::Oid>, new: Option<git::Oid>) -> Hunk {
+    let old_lines = u32::from(old.is_some());
+    let new_lines = u32::from(new.is_some());
+    let range = |lines| if lines == 0 { "0,0" } else { "1" };
+    let mut diff = format!("@@ -{} +{} @@\n", range(old_lines), range(new_lines));
+    if let Some(old) = old {
+        let _ = writeln!(diff, "-{SUBMODULE_PREFIX}{old}");
+    }
+    if let Some(new) = new {
+        let _ = writeln!(diff, "+{SUBMODULE_PREFIX}{new}");
+    }
+    Hunk {
+        old_start: old_lines,
+        old_lines,
+        new_start: new_lines,
+        new_lines,
+        diff,
+        binary: false,
+        generated: false,
+        change_type: match (old, new) {
+            (None, _) => ChangeType::Added,
+            (_, None) => ChangeType::Deleted,
+            _ => ChangeType::Modified,
+        },
+    }
+}
+
+struct CaseOnlyRename {
+    old_path: path::PathBuf,
+    new_path: path::PathBuf,
+    filemode: git::FileMode,
+}
+
+// the entries of the last tree every repository was looked for case only renames against, by
+// its git directory, so that the tree is walked once rather than on every status
+static TREE_ENTRIES: Lazy<Mutex<TreeCache<Arc<[TreeEntry]>>>> = Lazy::new(Mutex::default);
+
+// an entry of a tree, every directory coming before what's in it
+struct TreeEntry {
+    dir: path::PathBuf,
+    name: String,
+    filemode: Option<git::FileMode>,
+}
+
+fn tree_entries(repository: &Repository, tree: &git::Tree) -> Result<Arc<[TreeEntry]>> {
+    let cached = TREE_ENTRIES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(repository.path(), tree.id())
+        .map(Arc::clone);
+    if let Some(entries) = cached {
+        return Ok(entries);
+    }
+
+    let mut entries = vec![];
+    tree.walk(|root, entry| {
+        if let Some(name) = entry.name() {
+            entries.push(TreeEntry {
+                dir: path::PathBuf::from(root),
+                name: name.to_string(),
+                filemode: git::FileMode::try_from(entry.filemode()).ok(),
+            });
+        }
+        git::TreeWalkResult::Continue
+    })?;
+    let size = entries
+        .iter()
+        .map(|entry| entry.dir.as_os_str().len() + entry.name.len() + mem::size_of::<TreeEntry>())
+        .sum();
+    let entries = Arc::<[TreeEntry]>::from(entries);
+    TREE_ENTRIES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(
+            repository.path().to_path_buf(),
+            tree.id(),
+            Arc::clone(&entries),
+            size,
+        );
+    Ok(entries)
+}
+
+// finds files of `tree` whose name on disk only differs in case, like `Foo.txt` that has
+// been renamed to `foo.txt`. directories are listed at most once, and only if they are
+// part of the tree.
+fn case_only_renames(repository: &Repository, tree: &git::Tree) -> Result<Vec<CaseOnlyRename>> {
+    let workdir = repository.workdir().context("repository has no workdir")?;
+
+    // directory in the tree -> the same directory on disk, which might differ in case
+    let mut disk_dirs = HashMap::from([(path::PathBuf::new(), path::PathBuf::new())]);
+    let mut listings: HashMap<path::PathBuf, HashMap<String, String>> = HashMap::new();
+    let mut renames = vec![];
+
+    let entries = tree_entries(repository, tree)?;
+    for entry in &*entries {
+        // what's in directories that aren't on disk is deleted, the regular diff takes care
+        // of it
+        let Some(disk_dir) = disk_dirs.get(&entry.dir).cloned() else {
+            continue;
+        };
+        let listing = listings.entry(disk_dir.clone()).or_insert_with(|| {
+            crate::fs::list_names_by_case(&workdir.join(&disk_dir)).unwrap_or_default()
+        });
+        let Some(disk_name) = listing.get(&entry.name.to_lowercase()).cloned() else {
+            continue;
+        };
+
+        let old_path = entry.dir.join(&entry.name);
+        let new_path = disk_dir.join(disk_name);
+        match entry.filemode {
+            Some(git::FileMode::Tree) => {
+                disk_dirs.insert(old_path, new_path);
+            }
+            Some(git::FileMode::Commit) | None => {}
+            Some(filemode) => {
+                if old_path != new_path {
+                    renames.push(CaseOnlyRename {
+                        old_path,
+                        new_path,
+                        filemode,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(renames)
+}
+
+// on case insensitive filesystems, the workdir diff matches tree entries with files on disk
+// regardless of case, so a rename that only changes the case of a file name is either not
+// reported at all, or only as a content change. this reports such renames as the deletion of
+// the old path and the addition of the new one instead.
+fn apply_case_only_renames(
+    repository: &Repository,
+    tree: &git::Tree,
+    algorithm: Algorithm,
+    options: &Options,
+    hunks: &mut HashMap<path::PathBuf, Vec<Hunk>>,
+) -> Result<()> {
+    let renames = case_only_renames(repository, tree)?;
+    if renames.is_empty() {
+        return Ok(());
+    }
+
+    let workdir = repository.workdir().context("repository has no workdir")?;
+    let mut builder = repository.treebuilder(Some(tree));
+    let mut diff_opts = git2::DiffOptions::new();
+    algorithm.apply(&mut diff_opts);
+    diff_opts
+        .disable_pathspec_match(true)
+        .ignore_submodules(true)
+        .ignore_whitespace(options.ignore_whitespace)
+        .ignore_blank_lines(options.ignore_blank_lines)
+        .context_lines(0);
+    for rename in &renames {
+        let committed = tree
+            .get_path(&rename.old_path)
+            .ok()
+            .map(|entry| repository.find_blob(entry.id()))
+            .transpose()?;
+        let blob_oid = git::filters::blob(
+            repository,
+            &rename.new_path,
+            committed.as_ref().map(git::Blob::content),
+        )
+        .with_context(|| format!("failed to read {}", rename.new_path.display()))?;
+        builder.remove(&rename.old_path);
+        builder.upsert(&rename.new_path, blob_oid, rename.filemode);
+        diff_opts.pathspec(rename.old_path.as_path());
+        diff_opts.pathspec(rename.new_path.as_path());
+    }
+    let renamed_tree = repository.find_tree(builder.write()?)?;
+
+    let diff =
+        repository.diff_tree_to_tree(Some(tree), Some(&renamed_tree), Some(&mut diff_opts))?;
+    fetch_missing_blobs(repository, &diff, false);
+    let renamed_hunks =
+        hunks_by_filepath(repository, &diff, &UnmanagedPaths::default(), &|_, _| {})?;
+
+    // whatever the workdir diff reported under either of the names is superseded
+    let renamed_paths = renames
+        .iter()
+        .map(|rename| crate::fs::normalize_case(&rename.old_path))
+        .collect::<HashSet<_>>();
+    hunks.retain(|path, _| !renamed_paths.contains(&crate::fs::normalize_case(path)));
+    hunks.extend(renamed_hunks);
+
+    Ok(())
+}
+
+// libgit2 compares files with the tree as they are on disk, without running their filter
+// drivers and, depending on the platform, without converting their line endings. so a file
+// that is encrypted when it's committed, or a text file that is checked out with \r\n and
+// committed with \n, like with `core.autocrlf` on windows, has every line changed. such files
+// are diffed again with their content as `git add` would commit it.
+fn apply_conversions(
+    repository: &Repository,
+    tree: &git::Tree,
+    algorithm: Algorithm,
+    options: &Options,
+    hunks: &mut HashMap<path::PathBuf, Vec<Hunk>>,
+) -> Result<()> {
+    let workdir = repository.workdir().context("repository has no workdir")?;
+    let mut builder = repository.treebuilder(Some(tree));
+    let mut diff_opts = git2::DiffOptions::new();
+    algorithm.apply(&mut diff_opts);
+    diff_opts
+        .disable_pathspec_match(true)
+        .ignore_submodules(true)
+        .ignore_whitespace(options.ignore_whitespace)
+        .ignore_blank_lines(options.ignore_blank_lines)
+        .context_lines(0);
+    let mut unchanged_paths = vec![];
+    let mut converted_paths = vec![];
+    for (path, file_hunks) in hunks.iter() {
+        let cleaned = git::filters::is_cleaned(repository, path)?;
+        // renames and copies are left as they are, diffing them again would lose their source.
+        // what a filter cleans is often binary, like encrypted files.
+        if file_hunks.iter().any(|hunk| {
+            (hunk.binary && !cleaned)
+                || hunk.is_submodule()
+                || hunk.renamed_from().is_some()
+                || hunk.copied_from().is_some()
+                || matches!(hunk.change_type, ChangeType::Deleted)
+        }) {
+            continue;
+        }
+        if (!cleaned && repository.eol_conversion(path)? == git::eol::Conversion::None)
+            || !std::fs::symlink_metadata(workdir.join(path))
+                .is_ok_and(|metadata| metadata.is_file())
+        {
+            continue;
+        }
+        let entry = tree.get_path(path).ok();
+        let committed = entry
+            .as_ref()
+            .map(|entry| repository.find_blob(entry.id()))
+            .transpose()?;
+        let content = std::fs::read(workdir.join(path))
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let converted = git::filters::to_odb(
+            repository,
+            path,
+            &content,
+            committed.as_ref().map(git::Blob::content),
+        )?;
+        if committed
+            .as_ref()
+            .is_some_and(|committed| committed.content() == converted.as_slice())
+        {
+            unchanged_paths.push(path.clone());
+        } else if converted != content {
+            let filemode = file_hunks
+                .iter()
+                .find_map(Hunk::mode_change)
+                .map(|(_, new_filemode)| new_filemode)
+                .or_else(|| {
+                    entry
+                        .as_ref()
+                        .and_then(|entry| git::FileMode::try_from(entry.filemode()).ok())
+                })
+                .unwrap_or(git::FileMode::Blob);
+            builder.upsert(path, repository.blob(&converted)?, filemode);
+            diff_opts.pathspec(path.as_path());
+            converted_paths.push(path.clone());
+        }
+    }
+    for path in &unchanged_paths {
+        hunks.remove(path);
+    }
+    if converted_paths.is_empty() {
+        return Ok(());
+    }
+
+    let converted_tree = repository.find_tree(builder.write()?)?;
+    let diff =
+        repository.diff_tree_to_tree(Some(tree), Some(&converted_tree), Some(&mut diff_opts))?;
+    let converted_hunks =
+        hunks_by_filepath(repository, &diff, &UnmanagedPaths::default(), &|_, _| {})?;
+    // files that only differed in how they are converted have no hunks left
+    for path in &converted_paths {
+        hunks.remove(path);
+    }
+    hunks.extend(converted_hunks);
+
+    Ok(())
+}
+
+pub fn trees(
+    repository: &Repository,
+    old_tree: &git::Tree,
+    new_tree: &git::Tree,
+) -> Result<HashMap<path::PathBuf, Vec<Hunk>>> {
+    trees_with_options(repository, old_tree, new_tree, &Options::default())
+}
+
+/// Same as `trees`, but with `options`.
+pub fn trees_with_options(
+    repository: &Repository,
+    old_tree: &git::Tree,
+    new_tree: &git::Tree,
+    options: &Options,
+) -> Result<HashMap<path::PathBuf, Vec<Hunk>>> {
+    // trees only record the commit of a submodule, so unlike for the workdir there is no
+    // content to skip
+    let mut diff_opts = git2::DiffOptions::new();
+    Algorithm::resolve(repository, options)?.apply(&mut diff_opts);
+    diff_opts
+        .recurse_untracked_dirs(true)
+        .include_untracked(true)
+        .ignore_whitespace(options.ignore_whitespace)
+        .ignore_blank_lines(options.ignore_blank_lines)
+        .context_lines(0)
+        .show_untracked_content(true);
+
+    let mut diff =
+        repository.diff_tree_to_tree(Some(old_tree), Some(new_tree), Some(&mut diff_opts))?;
+    fetch_missing_blobs(repository, &diff, true);
+    find_similar(&mut diff, options)?;
+
+    hunks_by_filepath(repository, &diff, &options.unmanaged, &|_, _| {})
+}
+
+// pairs deleted files with added ones that are similar enough, untracked files included, so
+// that a moved file is one renamed file with the lines that changed rather than all of its
+// lines deleted and added again. copies are found the same way, from the unchanged files too.
+fn find_similar(diff: &mut git2::Diff, options: &Options) -> Result<()> {
+    let mut find_opts = git2::DiffFindOptions::new();
+    find_opts.renames(true).for_untracked(true);
+    if options.detect_copies {
+        find_opts
+            .copies(true)
+            .copies_from_unmodified(true)
+            .remove_unmodified(true);
+    }
+    diff.find_similar(Some(&mut find_opts))
+        .context("failed to find renames")
+}
+
+// in partial clones, blobs are not necessarily present locally. deltas are computed without
+// reading any content, so this fetches the blobs the hunks are going to be read from, all at
+// once, before anything tries to read them.
+fn fetch_missing_blobs(repository: &Repository, diff: &git2::Diff, include_new_files: bool) {
+    let oids = diff
+        .deltas()
+        .flat_map(|delta| {
+            let mut files = vec![delta.old_file()];
+            if include_new_files {
+                files.push(delta.new_file());
+            }
+            files
+        })
+        .filter(|file| file.mode() != git2::FileMode::Commit)
+        .map(|file| file.id().into())
+        .collect::<Vec<git::Oid>>();
+    if let Err(error) = repository.fetch_missing_objects(&oids) {
+        tracing::warn!(?error, "failed to fetch missing blobs");
+    }
+}
+
+fn delta_path<'a>(delta: &git2::DiffDelta<'a>) -> &'a path::Path {
+    delta.new_file().path().unwrap_or_else(|| {
+        delta
+            .old_file()
+            .path()
+            .expect("failed to get file name from diff")
+    })
+}
+
+fn hunks_by_filepath(
+    repository: &Repository,
+    diff: &git2::Diff,
+    unmanaged: &UnmanagedPaths,
+    progress: &dyn Fn(usize, usize),
+) -> Result<HashMap<path::PathBuf, Vec<Hunk>>> {
+    let total = diff.deltas().len();
+    progress(0, total);
+
+    // find all the hunks
+    let mut hunks_by_filepath: HashMap<path::PathBuf, Vec<Hunk>> = HashMap::new();
+    // binary and generated files are reduced to a single marker hunk as soon as they are seen,
+    // the rest of their lines are skipped.
+    let mut opaque_filepaths: HashSet<path::PathBuf> = HashSet::new();
+    let mut generated_by_filepath: HashMap<path::PathBuf, bool> = HashMap::new();
+
+    let mut on_line = |delta: git2::DiffDelta<'_>,
+                       hunk: Option<git2::DiffHunk<'_>>,
+                       line: git2::DiffLine<'_>| {
+        let change_type: ChangeType = delta.status().into();
+        let file_path = delta_path(&delta);
+
+        if opaque_filepaths.contains(file_path) {
+            return true;
+        }
+
+        let is_generated = *generated_by_filepath
+                .entry(file_path.to_path_buf())
+                .or_insert_with(|| {
+                    repository
+                        .is_path_generated(file_path)
+                        .unwrap_or_else(|error| {
+                            tracing::warn!(?error, path = %file_path.display(), "failed to read gitattributes");
+                            false
+                        })
+                });
+
+        if is_generated || delta.flags().is_binary() {
+            opaque_filepaths.insert(file_path.to_path_buf());
+            hunks_by_filepath.insert(
+                file_path.to_path_buf(),
+                vec![Hunk {
+                    old_start: 0,
+                    old_lines: 0,
+                    new_start: 0,
+                    new_lines: 0,
+                    diff: new_blob_id(repository, &delta, file_path),
+                    binary: true,
+                    generated: is_generated,
+                    change_type,
+                }],
+            );
+            return true;
+        }
+
+        hunks_by_filepath
+            .entry(file_path.to_path_buf())
+            .or_default();
+
+        let new_start = hunk.as_ref().map_or(0, git2::DiffHunk::new_start);
+        let new_lines = hunk.as_ref().map_or(0, git2::DiffHunk::new_lines);
+        let old_start = hunk.as_ref().map_or(0, git2::DiffHunk::old_start);
+        let old_lines = hunk.as_ref().map_or(0, git2::DiffHunk::old_lines);
+
+        if let Some((line, is_binary)) = match line.origin() {
+            '+' | '-' | ' ' => {
+                if let Ok(content) = str::from_utf8(line.content()) {
+                    Some((format!("{}{}", line.origin(), content), false))
+                } else {
+                    Some((new_blob_id(repository, &delta, file_path), true))
+                }
+            }
+            'B' => Some((new_blob_id(repository, &delta, file_path), true)),
+            'F' => None,
+            _ => {
+                if let Ok(content) = str::from_utf8(line.content()) {
+                    Some((content.to_string(), false))
+                } else {
+                    Some((new_blob_id(repository, &delta, file_path), true))
+                }
+            }
+        } {
+            let hunks = hunks_by_filepath
+                .entry(file_path.to_path_buf())
+                .or_default();
+
+            if let Some(previous_hunk) = hunks.last_mut() {
+                let hunk_did_not_change = previous_hunk.old_start == old_start
+                    && previous_hunk.old_lines == old_lines
+                    && previous_hunk.new_start == new_start
+                    && previous_hunk.new_lines == new_lines;
+
+                if hunk_did_not_change {
+                    if is_binary {
+                        // binary overrides the diff
+                        previous_hunk.binary = true;
+                        previous_hunk.old_start = 0;
+                        previous_hunk.old_lines = 0;
+                        previous_hunk.new_start = 0;
+                        previous_hunk.new_lines = 0;
+                        previous_hunk.diff = line;
+                    } else if !previous_hunk.binary {
+                        // append non binary hunks
+                        previous_hunk.diff.push_str(&line);
+                    }
+                } else {
+                    hunks.push(Hunk {
+                        old_start,
+                        old_lines,
+                        new_start,
+                        new_lines,
+                        diff: line,
+                        binary: is_binary,
+                        generated: false,
+                        change_type,
+                    });
+                }
+            } else {
+                hunks.push(Hunk {
+                    old_start,
+                    old_lines,
+                    new_start,
+                    new_lines,
+                    diff: line,
+                    binary: is_binary,
+                    generated: false,
+                    change_type,
+                });
+            }
+        }
+
+        true
+    };
+
+    // the patches are made one file at a time, those of unmanaged files never are
+    for (index, delta) in diff.deltas().enumerate() {
+        progress(index, total);
+        if !unmanaged.is_empty() && unmanaged.contains(delta_path(&delta)) {
+            continue;
+        }
+        if let Some(mut patch) =
+            git2::Patch::from_diff(diff, index).context("failed to make patch")?
+        {
+            patch.print(&mut on_line).context("failed to print diff")?;
+        }
+    }
+
+    progress(total, total);
+
+    let mut hunks_by_filepath = hunks_by_filepath
+        .into_iter()
+        .map(|(k, v)| {
+            if let Some(binary_hunk) = v.iter().find(|hunk| hunk.binary) {
+                if v.len() > 1 {
+                    // if there are multiple hunks with binary among them, then the binary hunk
+                    // takes precedence
+                    (
+                        k,
+                        vec![Hunk {
+                            old_start: 0,
+                            old_lines: 0,
+                            new_start: 0,
+                            new_lines: 0,
+                            diff: binary_hunk.diff.clone(),
+                            binary: true,
+                            generated: binary_hunk.generated,
+                            change_type: binary_hunk.change_type,
+                        }],
+                    )
+                } else {
+                    (k, v)
+                }
+            } else if v.is_empty() {
+                // this is a new file
+                (
+                    k,
+                    vec![Hunk {
+                        old_start: 0,
+                        old_lines: 0,
+                        new_start: 0,
+                        new_lines: 0,
+                        diff: String::new(),
+                        binary: false,
+                        generated: false,
+                        change_type: ChangeType::Modified,
+                    }],
+                )
+            } else {
+                (k, v)
+            }
+        })
+        .collect();
+    apply_mode_changes(diff, &mut hunks_by_filepath);
+    apply_renames(diff, &mut hunks_by_filepath);
+    Ok(hunks_by_filepath)
+}
+
+// returns the id of the new version of a binary (or generated) file, which is used as the diff
+// of it's hunk.
+//
+// if the file exists in the working directory, it is saved to the odb, so that the tree can later be
+// written from the id alone.
+fn new_blob_id(repository: &Repository, delta: &git2::DiffDelta, file_path: &path::Path) -> String {
+    let new_file_id = delta.new_file().id();
+    if delta.status() == git2::Delta::Deleted {
+        return new_file_id.to_string();
+    }
+
+    let full_path = repository.workdir().unwrap().join(file_path);
+    if !full_path.exists() {
+        return new_file_id.to_string();
+    }
+
+    match repository.blob_path(full_path.as_path()) {
+        // without binary patches, libgit2 does not always hash untracked files, so the id
+        // of the blob we have just written is used in that case.
+        Ok(blob_id) if new_file_id.is_zero() => blob_id.to_string(),
+        Ok(_) => new_file_id.to_string(),
+        Err(error) => {
+            tracing::warn!(?error, path = %full_path.display(), "failed to save binary file");
+            new_file_id.to_string()
+        }
+    }
+}
+
+// returns None if cannot reverse the patch header
+fn reverse_patch_header(header: &str) -> Option<String> {
+    use itertools::Itertools;
+
+    let mut parts = header.split_whitespace();
+
+    match parts.next() {
+        Some("@@") => {}
+        _ => return None,
+    };
+
+    let old_range = parts.next()?;
+    let new_range = parts.next()?;
+
+    match parts.next() {
+        Some("@@") => {}
+        _ => return None,
+    };
+
+    Some(format!(
+        "@@ {} {} @@ {}",
+        new_range.replace('+', "-"),
+        old_range.replace('-', "+"),
+        parts.join(" ")
+    ))
+}
+
+fn reverse_patch(patch: &str) -> Option<String> {
+    let mut reversed = String::new();
+    for line in patch.lines() {
+        if line.starts_with("@@") {
+            if let Some(header) = reverse_patch_header(line) {
+                reversed.push_str(&header);
+                reversed.push('\n');
+            } else {
+                return None;
+            }
+        } else if line.starts_with('+') {
+            reversed.push_str(&line.replace('+', "-"));
+            reversed.push('\n');
+        } else if line.starts_with('-') {
+            reversed.push_str(&line.replace('-', "+"));
+            reversed.push('\n');
+        } else {
+            reversed.push_str(line);
+            reversed.push('\n');
+        }
+    }
+    Some(reversed)
+}
+
+// returns None if cannot reverse the hunk
+pub fn reverse_hunk(hunk: &Hunk) -> Option<Hunk> {
+    if hunk.renamed_from().is_some() || hunk.copied_from().is_some() {
+        // the file is removed or put back as a whole, see `unapply_ownership`
+        None
+    } else if let Some((old, new)) = hunk.mode_change() {
+        Some(mode_hunk(new, old))
+    } else if hunk.binary {
+        None
+    } else {
+        reverse_patch(&hunk.diff).map(|diff| Hunk {
+            old_start: hunk.new_start,
+            old_lines: hunk.new_lines,
+            new_start: hunk.old_start,
+            new_lines: hunk.old_lines,
+            diff,
+            binary: hunk.binary,
+            generated: hunk.generated,
+            change_type: hunk.change_type,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils;
+
+    use super::*;
+
+    #[test]
+    fn diff_simple_text() {
+        let repository = test_utils::test_repository();
+        std::fs::write(repository.workdir().unwrap().join("file"), "hello").unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let diff = workdir(&repository, &head_commit_id).unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(
+            diff[&path::PathBuf::from("file")],
+            vec![Hunk {
+                old_start: 0,
+                old_lines: 0,
+                new_start: 1,
+                new_lines: 1,
+                diff: "@@ -0,0 +1 @@\n+hello\n\\ No newline at end of file\n".to_string(),
+                binary: false,
+                generated: false,
+                change_type: ChangeType::Added,
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_empty_file() {
+        let repository = test_utils::test_repository();
+        std::fs::write(repository.workdir().unwrap().join("first"), "").unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let diff = workdir(&repository, &head_commit_id).unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(
+            diff[&path::PathBuf::from("first")],
+            vec![Hunk {
+                old_start: 0,
+                old_lines: 0,
+                new_start: 0,
+                new_lines: 0,
+                diff: String::new(),
+                binary: false,
+                generated: false,
+                change_type: ChangeType::Modified,
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_multiple_empty_files() {
+        let repository = test_utils::test_repository();
+        std::fs::write(repository.workdir().unwrap().join("first"), "").unwrap();
+        std::fs::write(repository.workdir().unwrap().join("second"), "").unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let diff = workdir(&repository, &head_commit_id).unwrap();
+        assert_eq!(diff.len(), 2);
+        assert_eq!(
+            diff[&path::PathBuf::from("first")],
+            vec![Hunk {
+                old_start: 0,
+                old_lines: 0,
+                new_start: 0,
+                new_lines: 0,
+                diff: String::new(),
+                binary: false,
+                generated: false,
+                change_type: ChangeType::Modified,
+            }]
+        );
+        assert_eq!(
+            diff[&path::PathBuf::from("second")],
+            vec![Hunk {
+                old_start: 0,
+                old_lines: 0,
+                new_start: 0,
+                new_lines: 0,
+                diff: String::new(),
+                binary: false,
+                generated: false,
+                change_type: ChangeType::Modified,
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_binary() {
+        let repository = test_utils::test_repository();
+        std::fs::write(
+            repository.workdir().unwrap().join("image"),
+            [
+                255, 0, 0, // Red pixel
+                0, 0, 255, // Blue pixel
+                255, 255, 0, // Yellow pixel
+                0, 255, 0, // Green pixel
+            ],
+        )
+        .unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let diff = workdir(&repository, &head_commit_id).unwrap();
+        assert_eq!(
+            diff[&path::PathBuf::from("image")],
+            vec![Hunk {
+                old_start: 0,
+                old_lines: 0,
+                new_start: 0,
+                new_lines: 0,
+                diff: "71ae6e216f38164b6633e25d35abb043c3785af6".to_string(),
+                binary: true,
+                generated: false,
+                change_type: ChangeType::Added,
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_generated() {
+        let repository = test_utils::test_repository();
+        std::fs::write(
+            repository.workdir().unwrap().join(".gitattributes"),
+            "schema.json linguist-generated\n",
+        )
+        .unwrap();
+        std::fs::write(
+            repository.workdir().unwrap().join("schema.json"),
+            "{\n  \"generated\": true\n}\n",
+        )
+        .unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let diff = workdir(&repository, &head_commit_id).unwrap();
+        assert_eq!(
+            diff[&path::PathBuf::from("schema.json")],
+            vec![Hunk {
+                old_start: 0,
+                old_lines: 0,
+                new_start: 0,
+                new_lines: 0,
+                diff: repository
+                    .blob("{\n  \"generated\": true\n}\n".as_bytes())
+                    .unwrap()
+                    .to_string(),
+                binary: true,
+                generated: true,
+                change_type: ChangeType::Added,
+            }]
+        );
+        assert!(!diff[&path::PathBuf::from(".gitattributes")][0].generated);
+    }
+
+    #[test]
+    fn diff_some_lines_are_binary() {
+        let repository = test_utils::test_repository();
+        std::fs::write(
+            repository.workdir().unwrap().join("file"),
+            [
+                // butler/test/fixtures/git/1/8e/18ec9df5-65c5-4828-97ba-d91ec4903a74/objects/1f/9d7d5dd0d3d3ced66cee36bf1dd42bd33d0aa8
+                120, 1, 101, 144, 79, 75, 195, 64, 16, 197, 61, 239, 167, 120, 160, 224, 165, 77, 3,
+                5, 17, 111, 42, 42, 245, 162, 135, 22, 60, 118, 155, 76, 179, 75, 55, 59, 97, 103,
+                182, 177, 223, 222, 77, 244, 38, 204, 97, 254, 188, 247, 155, 97, 14, 129, 15, 88,
+                223, 213, 87, 215, 120, 243, 250, 148, 53, 80, 194, 110, 131, 103, 142, 13, 13, 42,
+                198, 60, 10, 54, 183, 61, 34, 163, 99, 110, 97, 21, 175, 190, 235, 237, 98, 238,
+                102, 241, 177, 195, 214, 250, 48, 250, 216, 66, 25, 71, 223, 229, 68, 224, 172, 24,
+                93, 17, 111, 48, 218, 168, 80, 71, 5, 187, 218, 125, 77, 154, 192, 124, 66, 240,
+                39, 170, 176, 117, 94, 80, 98, 154, 147, 21, 79, 82, 124, 246, 50, 169, 90, 134,
+                215, 9, 36, 190, 45, 192, 35, 62, 131, 189, 116, 137, 115, 108, 23, 56, 20, 190,
+                78, 94, 103, 5, 103, 74, 226, 57, 162, 225, 168, 137, 67, 101, 204, 123, 46, 156,
+                148, 227, 172, 121, 48, 102, 191, 223, 155, 27, 196, 225, 27, 250, 119, 107, 35,
+                130, 165, 71, 181, 242, 113, 200, 90, 205, 37, 151, 82, 199, 223, 124, 57, 90, 109,
+                92, 49, 13, 23, 117, 28, 215, 88, 246, 112, 170, 67, 37, 148, 202, 62, 220, 215,
+                117, 61, 99, 205, 71, 90, 64, 184, 167, 114, 78, 249, 5, 5, 161, 202, 188, 156, 41,
+                162, 79, 76, 255, 38, 63, 226, 30, 123, 106,
+            ],
+        )
+        .unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let diff = workdir(&repository, &head_commit_id).unwrap();
+        assert_eq!(
+            diff[&path::PathBuf::from("file")],
+            vec![Hunk {
+                old_start: 0,
+                old_lines: 0,
+                new_start: 0,
+                new_lines: 0,
+                diff: "3fc41b9ae6836a94f41c78b4ce69d78b6e7080f1".to_string(),
+                binary: true,
+                generated: false,
+                change_type: ChangeType::Added,
+            }]
+        );
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn diff_mode_change() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let repository = test_utils::test_repository();
+        let path = repository.workdir().unwrap().join("file");
+        std::fs::write(&path, "hello\n").unwrap();
+        let commit_id = test_utils::commit_all(&repository);
+
+        std::fs::write(&path, "hello\nworld\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let diff = workdir(&repository, &commit_id).unwrap();
+        let hunks = &diff[&path::PathBuf::from("file")];
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(
+            hunks[0].mode_change(),
+            Some((git::FileMode::Blob, git::FileMode::BlobExecutable))
+        );
+        assert_eq!(hunks[1].mode_change(), None);
+        assert_eq!(hunks[1].diff, "@@ -1,0 +2 @@\n+world\n");
+
+        let reversed = reverse_hunk(&hunks[0]).unwrap();
+        assert_eq!(reversed.diff, "old mode 100755\nnew mode 100644\n");
+    }
+
+    #[test]
+    fn diff_crlf_checkout() {
+        let repository = test_utils::test_repository();
+        let path = repository.workdir().unwrap().join("file");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        let commit_id = test_utils::commit_all(&repository);
+        repository
+            .config()
+            .unwrap()
+            .set_str("core.autocrlf", "true")
+            .unwrap();
+
+        std::fs::write(&path, "one\r\ntwo\r\n").unwrap();
+        let diff = workdir(&repository, &commit_id).unwrap();
+        assert!(diff.is_empty());
+
+        std::fs::write(&path, "one\r\nthree\r\n").unwrap();
+        let diff = workdir(&repository, &commit_id).unwrap();
+        let hunks = &diff[&path::PathBuf::from("file")];
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].diff, "@@ -2 +2 @@\n-two\n+three\n");
+    }
+
+    #[test]
+    fn diff_cleaned_by_filter_driver() {
+        let repository = test_utils::test_repository();
+        let dir = repository.workdir().unwrap();
+        std::fs::write(dir.join(".gitattributes"), "*.txt filter=upper\n").unwrap();
+        std::fs::write(dir.join("file.txt"), "HELLO\n").unwrap();
+        let commit_id = test_utils::commit_all(&repository);
+        repository
+            .config()
+            .unwrap()
+            .set_str("filter.upper.clean", "tr '[:lower:]' '[:upper:]'")
+            .unwrap();
+
+        // checked out as the driver would have smudged it
+        std::fs::write(dir.join("file.txt"), "hello\n").unwrap();
+        let diff = workdir(&repository, &commit_id).unwrap();
+        assert!(diff.is_empty());
+
+        std::fs::write(dir.join("file.txt"), "hello\nworld\n").unwrap();
+        let diff = workdir(&repository, &commit_id).unwrap();
+        let hunks = &diff[&path::PathBuf::from("file.txt")];
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].diff, "@@ -1,0 +2 @@\n+WORLD\n");
+    }
+
+    #[test]
+    fn algorithm_from_config() {
+        let repository = test_utils::test_repository();
+        assert_eq!(Algorithm::from_config(&repository).unwrap(), None);
+
+        let mut config = repository.config().unwrap();
+        config.set_str("diff.algorithm", "histogram").unwrap();
+        assert_eq!(
+            Algorithm::from_config(&repository).unwrap(),
+            Some(Algorithm::Histogram)
+        );
+        config.set_str("diff.algorithm", "default").unwrap();
+        assert_eq!(
+            Algorithm::from_config(&repository).unwrap(),
+            Some(Algorithm::Myers)
+        );
+    }
+
+    #[test]
+    fn diff_trees_ignoring_whitespace() {
+        let repository = test_utils::test_repository();
+        let path = repository.workdir().unwrap().join("file");
+        std::fs::write(&path, "a\nb\nc\nd\n").unwrap();
+        let old_commit_id = test_utils::commit_all(&repository);
+        std::fs::write(&path, "a  \n\nb\nc\nD\n").unwrap();
+        let new_commit_id = test_utils::commit_all(&repository);
+
+        let old_tree = repository
+            .find_commit(old_commit_id)
+            .unwrap()
+            .tree()
+            .unwrap();
+        let new_tree = repository
+            .find_commit(new_commit_id)
+            .unwrap()
+            .tree()
+            .unwrap();
+
+        let diff = trees(&repository, &old_tree, &new_tree).unwrap();
+        assert!(diff[&path::PathBuf::from("file")].len() > 1);
+
+        // only the line that really changed is left
+        let options = Options {
+            ignore_whitespace: true,
+            ignore_blank_lines: true,
+            ..Default::default()
+        };
+        let diff = trees_with_options(&repository, &old_tree, &new_tree, &options).unwrap();
+        let hunks = &diff[&path::PathBuf::from("file")];
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].diff, "@@ -4 +5 @@\n-d\n+D\n");
+    }
+
+    #[test]
+    fn diff_workdir_ignoring_whitespace() {
+        let repository = test_utils::test_repository();
+        let path = repository.workdir().unwrap().join("file");
+        std::fs::write(&path, "a\nb\nc\nd\n").unwrap();
+        let commit_id = test_utils::commit_all(&repository);
+        std::fs::write(&path, "a  \n\nb\nc\nD\n").unwrap();
+
+        let diff = workdir(&repository, &commit_id).unwrap();
+        assert!(diff[&path::PathBuf::from("file")].len() > 1);
+
+        // only the line that really changed is left
+        let options = Options {
+            ignore_whitespace: true,
+            ignore_blank_lines: true,
+            ..Default::default()
+        };
+        let diff = workdir_with_progress(&repository, &commit_id, &options, &|_, _| {}).unwrap();
+        let hunks = &diff[&path::PathBuf::from("file")];
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].diff, "@@ -4 +5 @@\n-d\n+D\n");
+    }
+
+    #[test]
+    fn diff_leaves_out_unmanaged() {
+        let repository = test_utils::test_repository();
+        let workdir = repository.workdir().unwrap();
+        std::fs::create_dir_all(workdir.join("vendor")).unwrap();
+        std::fs::write(workdir.join("vendor/lib"), "vendored").unwrap();
+        std::fs::write(workdir.join("file"), "hello").unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let options = Options {
+            unmanaged: UnmanagedPaths::new(&["vendor"]),
+            ..Default::default()
+        };
+        let diff =
+            workdir_with_progress(&repository, &head_commit_id, &options, &|_, _| {}).unwrap();
+        assert_eq!(
+            diff.into_keys().collect::<Vec<_>>(),
+            vec![path::PathBuf::from("file")]
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Line ending conversion of text files.
+//!
+//! With `core.autocrlf`, or the `text` and `eol` attributes, text files are committed with
+//! `\n` line endings whatever they are checked out with. [`to_odb`] converts the line endings
+//! of a file in the working directory the way `git add` does, see [`super::filters::to_odb`]
+//! for the whole conversion, and [`to_workdir`] converts them back the way `git checkout`
+//! does. [`churn`] tells files that only differ in their line endings or byte order mark from
+//! files that really changed.
+
+use std::{borrow::Cow, path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use super::Repository;
+
+const BYTE_ORDER_MARK: &[u8] = b"\xef\xbb\xbf";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    /// Line endings are left as they are.
+    None,
+    /// The file is text, `\r\n` is converted to `\n`.
+    Text,
+    /// The file is converted if its content is text and it isn't committed with `\r\n` already.
+    Auto,
+}
+
+/// The content of the file at `path`, relative to the working directory, with its line
+/// endings as they are committed.
+/// `committed` is the content the file has in the tree it is compared with, if it has one.
+pub fn to_odb<'content>(
+    repository: &Repository,
+    path: &path::Path,
+    content: &'content [u8],
+    committed: Option<&[u8]>,
+) -> Result<Cow<'content, [u8]>> {
+    let convert = match repository
+        .eol_conversion(path)
+        .with_context(|| format!("failed to read the attributes of {}", path.display()))?
+    {
+        Conversion::None => false,
+        Conversion::Text => true,
+        // like git, files that already have carriage returns in the repository are left alone,
+        // converting them would change every line
+        Conversion::Auto => !is_binary(content) && !committed.is_some_and(|c| c.contains(&b'\r')),
+    };
+    if !convert || !content.windows(2).any(|window| window == b"\r\n") {
+        return Ok(Cow::Borrowed(content));
+    }
+
+    let mut converted = Vec::with_capacity(content.len());
+    let mut bytes = content.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        if byte == b'\r' && bytes.peek() == Some(&&b'\n') {
+            continue;
+        }
+        converted.push(byte);
+    }
+    Ok(Cow::Owned(converted))
+}
+
+/// The content of the file at `path`, relative to the working directory, with the line
+/// endings it is checked out with. `content` is as it is committed.
+pub fn to_workdir<'content>(
+    repository: &Repository,
+    path: &path::Path,
+    content: &'content [u8],
+) -> Result<Cow<'content, [u8]>> {
+    let convert = match repository
+        .eol_conversion(path)
+        .with_context(|| format!("failed to read the attributes of {}", path.display()))?
+    {
+        Conversion::None => false,
+        Conversion::Text => true,
+        // a file committed with carriage returns is checked out as it is
+        Conversion::Auto => !is_binary(content) && !content.contains(&b'\r'),
+    };
+    if !convert || !content.contains(&b'\n') || !repository.checks_out_crlf(path)? {
+        return Ok(Cow::Borrowed(content));
+    }
+
+    let mut converted = Vec::with_capacity(content.len());
+    let mut previous = None;
+    for &byte in content {
+        if byte == b'\n' && previous != Some(b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(byte);
+        previous = Some(byte);
+    }
+    Ok(Cow::Owned(converted))
+}
+
+/// How a file only differs from the way it is committed in its line endings or byte order
+/// mark, which git shows as a change of every line or of the first one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Churn {
+    pub line_endings: bool,
+    pub byte_order_mark: bool,
+}
+
+/// How `content` differs from `committed`, if the only differences are line endings and the
+/// utf-8 byte order mark.
+pub fn churn(committed: &[u8], content: &[u8]) -> Option<Churn> {
+    if committed == content || is_binary(committed) || is_binary(content) {
+        return None;
+    }
+    let (committed_bom, committed) = strip_byte_order_mark(committed);
+    let (content_bom, content) = strip_byte_order_mark(content);
+    let line_endings = committed != content;
+    if line_endings && !lines(committed).eq(lines(content)) {
+        return None;
+    }
+    Some(Churn {
+        line_endings,
+        byte_order_mark: committed_bom != content_bom,
+    })
+}
+
+fn strip_byte_order_mark(content: &[u8]) -> (bool, &[u8]) {
+    match content.strip_prefix(BYTE_ORDER_MARK) {
+        Some(stripped) => (true, stripped),
+        None => (false, content),
+    }
+}
+
+// the lines of `content` without their `\n` or `\r\n`
+fn lines(content: &[u8]) -> impl Iterator<Item = &[u8]> {
+    content
+        .split(|&byte| byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+}
+
+// git looks for a nul byte in the beginning of the file
+fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|&byte| byte == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils;
+
+    use super::*;
+
+    #[test]
+    fn text_attribute() {
+        let repository = test_utils::test_repository();
+        let workdir = repository.workdir().unwrap();
+        std::fs::write(workdir.join(".gitattributes"), "*.txt text\n*.bin -text\n").unwrap();
+
+        let content = b"one\r\ntwo\r\n";
+        assert_eq!(
+            to_odb(&repository, path::Path::new("file.txt"), content, None).unwrap(),
+            &b"one\ntwo\n"[..]
+        );
+        assert_eq!(
+            to_odb(&repository, path::Path::new("file.bin"), content, None).unwrap(),
+            &content[..]
+        );
+    }
+
+    #[test]
+    fn autocrlf() {
+        let repository = test_utils::test_repository();
+        let content = b"one\r\ntwo\r\n";
+        let path = path::Path::new("file");
+        assert_eq!(
+            to_odb(&repository, path, content, None).unwrap(),
+            &content[..]
+        );
+
+        repository
+            .config()
+            .unwrap()
+            .set_str("core.autocrlf", "true")
+            .unwrap();
+        assert_eq!(
+            to_odb(&repository, path, content, None).unwrap(),
+            &b"one\ntwo\n"[..]
+        );
+        // committed with crlf, it stays that way
+        assert_eq!(
+            to_odb(&repository, path, content, Some(b"one\r\n")).unwrap(),
+            &content[..]
+        );
+    }
+
+    #[test]
+    fn checkout_line_endings() {
+        let repository = test_utils::test_repository();
+        let content = b"one\ntwo\n";
+        let path = path::Path::new("file.txt");
+        assert_eq!(
+            to_workdir(&repository, path, content).unwrap(),
+            &content[..]
+        );
+
+        repository
+            .config()
+            .unwrap()
+            .set_str("core.autocrlf", "true")
+            .unwrap();
+        assert_eq!(
+            to_workdir(&repository, path, content).unwrap(),
+            &b"one\r\ntwo\r\n"[..]
+        );
+        // committed with crlf, it stays that way
+        assert_eq!(
+            to_workdir(&repository, path, b"one\r\ntwo\n").unwrap(),
+            &b"one\r\ntwo\n"[..]
+        );
+
+        let workdir = repository.workdir().unwrap();
+        std::fs::write(workdir.join(".gitattributes"), "*.txt eol=lf\n").unwrap();
+        assert_eq!(
+            to_workdir(&repository, path, content).unwrap(),
+            &content[..]
+        );
+    }
+
+    #[test]
+    fn line_ending_and_bom_churn() {
+        let committed = b"one\ntwo\n";
+        assert_eq!(
+            churn(committed, b"one\r\ntwo\r\n"),
+            Some(Churn {
+                line_endings: true,
+                byte_order_mark: false,
+            })
+        );
+        assert_eq!(
+            churn(committed, b"\xef\xbb\xbfone\ntwo\n"),
+            Some(Churn {
+                line_endings: false,
+                byte_order_mark: true,
+            })
+        );
+        assert_eq!(
+            churn(committed, b"\xef\xbb\xbfone\r\ntwo\n"),
+            Some(Churn {
+                line_endings: true,
+                byte_order_mark: true,
+            })
+        );
+        assert_eq!(churn(committed, b"one\r\nthree\r\n"), None);
+        assert_eq!(churn(committed, committed), None);
+    }
+}
@@ -0,0 +1,319 @@
+//! Filter drivers, like git-crypt's.
+//!
+//! libgit2 doesn't run the filter drivers files are assigned with the `filter` attribute,
+//! so `git` is made to run them, the way it does for its own commands: with its shell, its
+//! quoting of `%f` and the `filter.<driver>.process` protocol, for [`clean`] on content read
+//! from the working directory before it is compared with or written as a blob, and for
+//! [`smudge_paths`] on the files a checkout wrote. What content cleans to is kept by its
+//! id, so that files that didn't change since they were last cleaned aren't cleaned again.
+//! LFS is left to [`super::lfs`].
+
+use std::{
+    borrow::Cow,
+    io::Write,
+    path,
+    process::{Command, Stdio},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+
+use super::{cleaned::Cleaned, eol, Oid, Repository};
+
+// what contents were cleaned to, by the git directory of the repository, the command they
+// were cleaned with and the id of the content
+type CleanedKey = (path::PathBuf, String, git2::Oid);
+static CLEANED: Lazy<Mutex<Cleaned<CleanedKey>>> = Lazy::new(Mutex::default);
+
+fn cleaned() -> MutexGuard<'static, Cleaned<CleanedKey>> {
+    CLEANED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Driver {
+    clean: Option<String>,
+    smudge: Option<String>,
+    process: Option<String>,
+}
+
+impl Driver {
+    fn cleans(&self) -> Option<&str> {
+        self.process.as_deref().or(self.clean.as_deref())
+    }
+
+    fn smudges(&self) -> Option<&str> {
+        self.process.as_deref().or(self.smudge.as_deref())
+    }
+}
+
+/// The content of the file at `path`, relative to the working directory, cleaned by its
+/// filter driver, if it has one that cleans, with its line endings converted like git does
+/// along with it. A driver that fails leaves the content as it is, unless it's
+/// `filter.<driver>.required`.
+pub fn clean<'content>(
+    repository: &Repository,
+    path: &path::Path,
+    content: &'content [u8],
+) -> Result<Cow<'content, [u8]>> {
+    let Some(driver) = driver(repository, path)? else {
+        return Ok(Cow::Borrowed(content));
+    };
+    let Some(command) = driver.cleans() else {
+        return Ok(Cow::Borrowed(content));
+    };
+    let key = (
+        repository.path().to_path_buf(),
+        command.to_string(),
+        git2::Oid::hash_object(git2::ObjectType::Blob, content)?,
+    );
+    let cached = cleaned().get(&key);
+    if let Some(cleaned) = cached.and_then(|id| repository.find_blob(id).ok()) {
+        return Ok(Cow::Owned(cleaned.content().to_vec()));
+    }
+    let id = hash_object(repository, path, content)?;
+    let key_size = key.0.as_os_str().len() + key.1.len();
+    cleaned().insert(key, key_size, id);
+    let cleaned = repository
+        .find_blob(id)
+        .with_context(|| format!("failed to find cleaned {}", path.display()))?;
+    Ok(Cow::Owned(cleaned.content().to_vec()))
+}
+
+/// The content of the file at `path`, relative to the working directory, as `git add` would
+/// commit it: cleaned by its filter driver, then with its line endings converted.
+/// `committed` is the content the file has in the tree it is compared with, if it has one.
+pub fn to_odb(
+    repository: &Repository,
+    path: &path::Path,
+    content: &[u8],
+    committed: Option<&[u8]>,
+) -> Result<Vec<u8>> {
+    let cleaned = clean(repository, path, content)?;
+    Ok(eol::to_odb(repository, path, &cleaned, committed)?.into_owned())
+}
+
+/// Writes the file at `path`, relative to the working directory, to the odb as `git add`
+/// would, see [`to_odb`].
+pub fn blob(repository: &Repository, path: &path::Path, committed: Option<&[u8]>) -> Result<Oid> {
+    let workdir = repository.workdir().context("repository has no workdir")?;
+    let content = std::fs::read(workdir.join(path))
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let content = to_odb(repository, path, &content, committed)?;
+    repository.blob(&content).map_err(Into::into)
+}
+
+/// Whether the file at `path` has a filter driver with a `clean` command.
+pub fn is_cleaned(repository: &Repository, path: &path::Path) -> Result<bool> {
+    if !has_drivers(repository)? {
+        return Ok(false);
+    }
+    Ok(driver(repository, path)?.is_some_and(|driver| driver.cleans().is_some()))
+}
+
+/// Rewrites the files among `files`, the paths a checkout wrote with the ids of the blobs
+/// it wrote there, that have a filter driver that smudges, with what git checks the blob
+/// out as. The files of drivers that fail are written unfiltered, unless they are required.
+pub fn smudge_paths(
+    repository: &Repository,
+    files: impl IntoIterator<Item = (path::PathBuf, Oid)>,
+) -> Result<()> {
+    if !has_drivers(repository)? {
+        return Ok(());
+    }
+    let workdir = repository.workdir().context("repository has no workdir")?;
+    for (path, id) in files {
+        if !driver(repository, &path)?.is_some_and(|driver| driver.smudges().is_some()) {
+            continue;
+        }
+        let full_path = workdir.join(&path);
+        if !std::fs::symlink_metadata(&full_path).is_ok_and(|metadata| metadata.is_file()) {
+            continue;
+        }
+        let smudged = cat_file(repository, &path, id)?;
+        std::fs::write(&full_path, smudged)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Writes `stdin` to `command` and returns what it wrote on its stdout, failing if it fails.
+pub(super) fn pipe(mut command: Command, stdin: &[u8]) -> Result<Vec<u8>> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {program}"))?;
+
+    // write from another thread, the command might not read all of stdin before writing
+    let mut child_stdin = child.stdin.take().context("failed to open stdin")?;
+    let input = stdin.to_vec();
+    let writer = std::thread::spawn(move || child_stdin.write_all(&input));
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("failed to run {program}"))?;
+    writer
+        .join()
+        .map_err(|_| anyhow::anyhow!("failed to write to {program}"))?
+        .with_context(|| format!("failed to write to {program}"))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+fn driver(repository: &Repository, path: &path::Path) -> Result<Option<Driver>> {
+    let Some(name) = repository
+        .filter_attribute(path)
+        .with_context(|| format!("failed to read the attributes of {}", path.display()))?
+    else {
+        return Ok(None);
+    };
+    if name == "lfs" {
+        return Ok(None);
+    }
+    let config = repository.config().context("failed to get config")?;
+    let driver = Driver {
+        clean: config.get_string(&format!("filter.{name}.clean"))?,
+        smudge: config.get_string(&format!("filter.{name}.smudge"))?,
+        process: config.get_string(&format!("filter.{name}.process"))?,
+    };
+    Ok((driver.cleans().is_some() || driver.smudges().is_some()).then_some(driver))
+}
+
+// most repositories have no drivers, this saves reading the attributes of every file
+fn has_drivers(repository: &Repository) -> Result<bool> {
+    let config = repository.config().context("failed to get config")?;
+    Ok(config
+        .names(r"^filter\..*\.(clean|smudge|process)$")?
+        .iter()
+        .any(|name| !name.starts_with("filter.lfs.")))
+}
+
+fn git(repository: &Repository) -> Command {
+    let mut command = Command::new("git");
+    command.current_dir(repository.workdir().unwrap_or(repository.path()));
+    command
+}
+
+// writes `content` cleaned by git, as if it was the file at `path`, to the odb
+fn hash_object(repository: &Repository, path: &path::Path, content: &[u8]) -> Result<Oid> {
+    let mut command = git(repository);
+    command
+        .args(["hash-object", "-w", "--stdin", "--path"])
+        .arg(path);
+    let id =
+        pipe(command, content).with_context(|| format!("failed to clean {}", path.display()))?;
+    String::from_utf8_lossy(&id)
+        .trim()
+        .parse()
+        .with_context(|| format!("failed to read the cleaned id of {}", path.display()))
+}
+
+// the blob `id` as git checks it out at `path`
+fn cat_file(repository: &Repository, path: &path::Path, id: Oid) -> Result<Vec<u8>> {
+    let mut command = git(repository);
+    command
+        .args(["cat-file", "--filters", "--path"])
+        .arg(path)
+        .arg(id.to_string());
+    pipe(command, &[]).with_context(|| format!("failed to smudge {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils;
+
+    use super::*;
+
+    #[test]
+    fn clean_with_driver() {
+        let repository = test_utils::test_repository();
+        let workdir = repository.workdir().unwrap();
+        std::fs::write(workdir.join(".gitattributes"), "*.txt filter=upper\n").unwrap();
+        let mut config = repository.config().unwrap();
+        config
+            .set_str("filter.upper.clean", "tr '[:lower:]' '[:upper:]'")
+            .unwrap();
+
+        assert_eq!(
+            clean(&repository, path::Path::new("file.txt"), b"hello\n").unwrap(),
+            &b"HELLO\n"[..]
+        );
+        assert_eq!(
+            clean(&repository, path::Path::new("file.md"), b"hello\n").unwrap(),
+            &b"hello\n"[..]
+        );
+    }
+
+    #[test]
+    fn cleans_unchanged_content_once() {
+        let repository = test_utils::test_repository();
+        let workdir = repository.workdir().unwrap();
+        std::fs::write(workdir.join(".gitattributes"), "*.txt filter=counted\n").unwrap();
+        let mut config = repository.config().unwrap();
+        config
+            .set_str(
+                "filter.counted.clean",
+                "echo >> cleaned; tr '[:lower:]' '[:upper:]'",
+            )
+            .unwrap();
+
+        let path = path::Path::new("file.txt");
+        for _ in 0..2 {
+            assert_eq!(
+                clean(&repository, path, b"hello\n").unwrap(),
+                &b"HELLO\n"[..]
+            );
+        }
+        assert_eq!(std::fs::read(workdir.join("cleaned")).unwrap().len(), 1);
+
+        clean(&repository, path, b"changed\n").unwrap();
+        assert_eq!(std::fs::read(workdir.join("cleaned")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn smudge_with_driver() {
+        let repository = test_utils::test_repository();
+        let workdir = repository.workdir().unwrap();
+        std::fs::write(workdir.join(".gitattributes"), "*.txt filter=lower\n").unwrap();
+        let mut config = repository.config().unwrap();
+        // %f is quoted by git, for the shell of git
+        config
+            .set_str(
+                "filter.lower.smudge",
+                "test -f %f && tr '[:upper:]' '[:lower:]'",
+            )
+            .unwrap();
+
+        let path = path::Path::new("it's a file.txt");
+        let id = repository.blob(b"HELLO\n").unwrap();
+        std::fs::write(workdir.join(path), b"HELLO\n").unwrap();
+        smudge_paths(&repository, [(path.to_path_buf(), id)]).unwrap();
+        assert_eq!(std::fs::read(workdir.join(path)).unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn failing_driver() {
+        let repository = test_utils::test_repository();
+        let workdir = repository.workdir().unwrap();
+        std::fs::write(workdir.join(".gitattributes"), "*.txt filter=broken\n").unwrap();
+        let mut config = repository.config().unwrap();
+        config.set_str("filter.broken.clean", "exit 1").unwrap();
+
+        let path = path::Path::new("file.txt");
+        assert_eq!(
+            clean(&repository, path, b"hello\n").unwrap(),
+            &b"hello\n"[..]
+        );
+
+        config.set_bool("filter.broken.required", true).unwrap();
+        assert!(clean(&repository, path, b"hello\n").is_err());
+    }
+}
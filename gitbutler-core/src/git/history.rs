@@ -0,0 +1,479 @@
+//! History walks that see the history git shows.
+//!
+//! libgit2 doesn't know about `refs/replace`, and in shallow clones its walks run into the
+//! parents that were never fetched. [`History`] reads commits through their replacements like
+//! git does, and takes the commits listed in `shallow` and the ones with missing parents as
+//! roots, telling when a walk was cut short by one of them. The parents and times of commits
+//! come from the [`CommitGraph`] when they are in it, like they do in git and libgit2 walks.
+//! Repositories with neither are left to libgit2, see [`History::is_plain`].
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use anyhow::{Context, Result};
+
+use super::{
+    commit_graph::{CommitGraph, GraphCommit},
+    Commit, Error, Oid, Repository,
+};
+
+const REPLACE_REFS: &str = "refs/replace/";
+
+// git gives up on replacements of replacements after as many
+const MAX_REPLACE_DEPTH: usize = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct History {
+    replacements: HashMap<Oid, Oid>,
+    shallow: HashSet<Oid>,
+    graph: Option<CommitGraph>,
+}
+
+/// The commits of a walk, the newest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Log {
+    pub commits: Vec<Oid>,
+    /// Whether the walk got to the end of a shallow history before it was done, the commits
+    /// past it being unknown.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AheadBehind {
+    pub ahead: usize,
+    pub behind: usize,
+    /// Whether either count stopped at the end of a shallow history.
+    pub truncated: bool,
+}
+
+impl History {
+    pub fn open(repository: &Repository) -> Result<Self> {
+        let replacements = replacements(repository)?;
+        let shallow = shallow(repository)?;
+        // plain histories are walked by libgit2, which reads the graph itself
+        let graph = if replacements.is_empty() && shallow.is_empty() {
+            None
+        } else {
+            CommitGraph::open(repository).context("failed to read commit-graph")?
+        };
+        Ok(Self {
+            replacements,
+            shallow,
+            graph,
+        })
+    }
+
+    /// Whether nothing is replaced and nothing is cut, libgit2 walks the same history then.
+    pub fn is_plain(&self) -> bool {
+        self.replacements.is_empty() && self.shallow.is_empty()
+    }
+
+    /// The commits reachable from `from` and not from `hide`.
+    pub fn log(&self, repository: &Repository, from: Oid, hide: Option<Oid>) -> Result<Log> {
+        self.walk(repository, from, hide, |_| Ok(false))
+    }
+
+    /// The commits reachable from `from`, up to the first one `until` is true for.
+    pub fn log_until(
+        &self,
+        repository: &Repository,
+        from: Oid,
+        until: impl FnMut(Oid) -> Result<bool>,
+    ) -> Result<Log> {
+        self.walk(repository, from, None, until)
+    }
+
+    /// The number of commits reachable from `local` and not from `upstream`, and the other way
+    /// around.
+    pub fn ahead_behind(
+        &self,
+        repository: &Repository,
+        local: Oid,
+        upstream: Oid,
+    ) -> Result<AheadBehind> {
+        let ahead = self.log(repository, local, Some(upstream))?;
+        let behind = self.log(repository, upstream, Some(local))?;
+        Ok(AheadBehind {
+            ahead: ahead.commits.len(),
+            behind: behind.commits.len(),
+            truncated: ahead.truncated || behind.truncated,
+        })
+    }
+
+    /// Whether `ancestor` is one of the ancestors of `commit`, which isn't one of its own.
+    pub fn is_descendant_of(
+        &self,
+        repository: &Repository,
+        commit: Oid,
+        ancestor: Oid,
+    ) -> Result<bool> {
+        if commit == ancestor {
+            return Ok(false);
+        }
+        let mut found = false;
+        self.log_until(repository, commit, |id| {
+            found = id == ancestor;
+            Ok(found)
+        })?;
+        Ok(found)
+    }
+
+    /// The number of generations of commits from `from` down to the end of its history, the
+    /// depth a shallow fetch of `from` has to have to get all the history there is of it now.
+    pub fn depth(&self, repository: &Repository, from: Oid) -> Result<usize> {
+        let mut depth = 0;
+        let mut seen = HashSet::from([from]);
+        let mut generation = vec![from];
+        while !generation.is_empty() {
+            depth += 1;
+            let mut next = vec![];
+            for id in generation {
+                for (parent, _) in self.parents(repository, id)?.unwrap_or_default() {
+                    if seen.insert(parent) {
+                        next.push(parent);
+                    }
+                }
+            }
+            generation = next;
+        }
+        Ok(depth)
+    }
+
+    // like git, commits are walked the newest first, and the ones reachable from `hide` are
+    // walked too until only those are left
+    fn walk(
+        &self,
+        repository: &Repository,
+        from: Oid,
+        hide: Option<Oid>,
+        mut until: impl FnMut(Oid) -> Result<bool>,
+    ) -> Result<Log> {
+        let mut walk = Walk::default();
+        for (id, hidden) in [(from, false)].into_iter().chain(hide.map(|id| (id, true))) {
+            let commit = self
+                .graph_commit(repository, id)?
+                .with_context(|| format!("failed to find commit {id}"))?;
+            if walk.seen.contains(&id) {
+                walk.hide(id);
+            } else {
+                walk.push(id, commit.time, hidden);
+            }
+        }
+
+        let mut log = Log::default();
+        while walk.interesting > 0 {
+            let Some(Entry { id, .. }) = walk.queue.pop() else {
+                break;
+            };
+            walk.queued.remove(&id);
+            let hidden = walk.hidden.contains(&id);
+            if !hidden {
+                walk.interesting -= 1;
+                log.commits.push(id);
+                if hide.is_none() && until(id)? {
+                    return Ok(log);
+                }
+            }
+
+            let Some(parents) = self.parents(repository, id)? else {
+                log.truncated |= !hidden;
+                continue;
+            };
+            walk.parents
+                .insert(id, parents.iter().map(|(parent, _)| *parent).collect());
+            for (parent, time) in parents {
+                if walk.seen.contains(&parent) {
+                    if hidden {
+                        walk.hide(parent);
+                    }
+                } else {
+                    walk.push(parent, time, hidden);
+                }
+            }
+        }
+
+        // commits can turn out to be reachable from `hide` after they were walked
+        log.commits.retain(|id| !walk.hidden.contains(id));
+        Ok(log)
+    }
+
+    // the parents of the commit and their times, none if the history is cut there
+    fn parents(&self, repository: &Repository, id: Oid) -> Result<Option<Vec<(Oid, i64)>>> {
+        let replaced = self.replaced(id);
+        if self.shallow.contains(&id) || self.shallow.contains(&replaced) {
+            return Ok(None);
+        }
+        let commit = self
+            .graph_commit(repository, id)?
+            .with_context(|| format!("failed to find commit {id}"))?;
+        let mut parents = vec![];
+        for parent in commit.parents {
+            match self.graph_commit(repository, parent)? {
+                Some(commit) => parents.push((parent, commit.time)),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(parents))
+    }
+
+    // the parents and time of the commit, from the commit-graph if it's in it
+    fn graph_commit(&self, repository: &Repository, id: Oid) -> Result<Option<GraphCommit>> {
+        if let Some(commit) = self
+            .graph
+            .as_ref()
+            .and_then(|graph| graph.commit(self.replaced(id)))
+        {
+            return Ok(Some(commit));
+        }
+        Ok(self.read(repository, id)?.map(|commit| GraphCommit {
+            time: commit.time().seconds(),
+            parents: commit.parent_ids(),
+        }))
+    }
+
+    fn read<'repo>(&self, repository: &'repo Repository, id: Oid) -> Result<Option<Commit<'repo>>> {
+        match repository.find_commit(self.replaced(id)) {
+            Ok(commit) => Ok(Some(commit)),
+            Err(Error::NotFound(_)) => Ok(None),
+            Err(error) => Err(error).with_context(|| format!("failed to read commit {id}")),
+        }
+    }
+
+    fn replaced(&self, id: Oid) -> Oid {
+        let mut replaced = id;
+        for _ in 0..MAX_REPLACE_DEPTH {
+            match self.replacements.get(&replaced) {
+                Some(replacement) => replaced = *replacement,
+                None => break,
+            }
+        }
+        replaced
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    time: i64,
+    order: usize,
+    id: Oid,
+}
+
+// the newest first, and the first pushed first when they are as old
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time
+            .cmp(&other.time)
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Default)]
+struct Walk {
+    queue: BinaryHeap<Entry>,
+    queued: HashSet<Oid>,
+    seen: HashSet<Oid>,
+    hidden: HashSet<Oid>,
+    /// The parents of the commits that were walked.
+    parents: HashMap<Oid, Vec<Oid>>,
+    /// The number of queued commits that aren't hidden, the walk is done when there are none.
+    interesting: usize,
+}
+
+impl Walk {
+    fn push(&mut self, id: Oid, time: i64, hidden: bool) {
+        self.seen.insert(id);
+        self.queued.insert(id);
+        self.queue.push(Entry {
+            time,
+            order: self.seen.len(),
+            id,
+        });
+        if hidden {
+            self.hidden.insert(id);
+        } else {
+            self.interesting += 1;
+        }
+    }
+
+    // hides the commit and the ancestors of it that were already seen
+    fn hide(&mut self, id: Oid) {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if !self.hidden.insert(id) {
+                continue;
+            }
+            if self.queued.contains(&id) {
+                self.interesting -= 1;
+            }
+            if let Some(parents) = self.parents.get(&id) {
+                stack.extend(parents.iter().copied());
+            }
+        }
+    }
+}
+
+// `refs/replace/<id>` points to the object that is read instead of `<id>`
+fn replacements(repository: &Repository) -> Result<HashMap<Oid, Oid>> {
+    if std::env::var_os("GIT_NO_REPLACE_OBJECTS").is_some() {
+        return Ok(HashMap::new());
+    }
+    let config = repository.config().context("failed to get config")?;
+    if config.get_bool("core.useReplaceRefs")? == Some(false) {
+        return Ok(HashMap::new());
+    }
+
+    let mut replacements = HashMap::new();
+    for reference in repository
+        .references_glob(&format!("{REPLACE_REFS}*"))
+        .context("failed to list replace refs")?
+    {
+        let reference = reference.context("failed to read replace ref")?;
+        let name = String::from_utf8_lossy(reference.name_bytes()).to_string();
+        let Some(original) = name
+            .strip_prefix(REPLACE_REFS)
+            .and_then(|id| id.parse::<Oid>().ok())
+        else {
+            continue;
+        };
+        if let Some(replacement) = reference.target() {
+            replacements.insert(original, replacement);
+        }
+    }
+    Ok(replacements)
+}
+
+// the commits a shallow clone stops at, one id per line
+fn shallow(repository: &Repository) -> Result<HashSet<Oid>> {
+    let path = repository.common_dir().join("shallow");
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", path.display()))
+        }
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils;
+
+    use super::*;
+
+    #[test]
+    fn replaced_parents() {
+        let repository = test_utils::test_repository();
+        let first = test_utils::commit_all(&repository);
+        let second = test_utils::commit_all(&repository);
+        let third = test_utils::commit_all(&repository);
+
+        let history = History::open(&repository).unwrap();
+        assert!(history.is_plain());
+        assert!(history.is_descendant_of(&repository, third, first).unwrap());
+
+        // the second commit becomes a root
+        let signature = crate::git::Signature::now("test", "test@email.com").unwrap();
+        let tree = repository
+            .find_commit(second)
+            .and_then(|commit| commit.tree())
+            .unwrap();
+        let replacement = repository
+            .commit(None, &signature, &signature, "root", &tree, &[])
+            .unwrap();
+        repository
+            .reference(
+                &format!("{REPLACE_REFS}{second}").parse().unwrap(),
+                replacement,
+                false,
+                "replace",
+            )
+            .unwrap();
+
+        let history = History::open(&repository).unwrap();
+        assert!(!history.is_plain());
+        assert_eq!(
+            history.log(&repository, third, None).unwrap(),
+            Log {
+                commits: vec![third, second],
+                truncated: false,
+            }
+        );
+        assert!(!history.is_descendant_of(&repository, third, first).unwrap());
+    }
+
+    #[test]
+    fn shallow_boundary() {
+        let repository = test_utils::test_repository();
+        let first = test_utils::commit_all(&repository);
+        let second = test_utils::commit_all(&repository);
+        let third = test_utils::commit_all(&repository);
+        std::fs::write(repository.path().join("shallow"), format!("{second}\n")).unwrap();
+
+        let history = History::open(&repository).unwrap();
+        assert_eq!(
+            history.log(&repository, third, None).unwrap(),
+            Log {
+                commits: vec![third, second],
+                truncated: true,
+            }
+        );
+        assert_eq!(history.depth(&repository, third).unwrap(), 2);
+        // the cut isn't reached when the walk stops before it
+        assert_eq!(
+            history.log(&repository, third, Some(second)).unwrap(),
+            Log {
+                commits: vec![third],
+                truncated: false,
+            }
+        );
+        // past the cut, the first and initial commits aren't ancestors of the third
+        assert_eq!(
+            history.ahead_behind(&repository, third, first).unwrap(),
+            AheadBehind {
+                ahead: 2,
+                behind: 2,
+                truncated: true,
+            }
+        );
+    }
+
+    #[test]
+    fn commit_graph() {
+        let repository = test_utils::test_repository();
+        test_utils::commit_all(&repository);
+        let second = test_utils::commit_all(&repository);
+        let third = test_utils::commit_all(&repository);
+        let status = std::process::Command::new("git")
+            .arg("--git-dir")
+            .arg(repository.path())
+            .args(["commit-graph", "write", "--reachable", "--no-progress"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        // not in the graph, read from the object database
+        let fourth = test_utils::commit_all(&repository);
+        std::fs::write(repository.path().join("shallow"), format!("{second}\n")).unwrap();
+
+        let history = History::open(&repository).unwrap();
+        assert!(history.graph.is_some());
+        assert_eq!(
+            history.log(&repository, fourth, None).unwrap(),
+            Log {
+                commits: vec![fourth, third, second],
+                truncated: true,
+            }
+        );
+    }
+}
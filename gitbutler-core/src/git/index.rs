@@ -89,6 +89,11 @@ impl Index {
         self.index.write().map_err(Into::into)
     }
 
+    /// re-reads the index from disk, if `force` is false only when it changed.
+    pub fn read(&mut self, force: bool) -> Result<()> {
+        self.index.read(force).map_err(Into::into)
+    }
+
     pub fn add_path(&mut self, path: &path::Path) -> Result<()> {
         self.index.add_path(path).map_err(Into::into)
     }
@@ -0,0 +1,226 @@
+//! Git LFS, through the `git lfs` executable.
+//!
+//! libgit2 doesn't run filter drivers, so for files with the `filter=lfs` attribute they
+//! are run from here: [`clean`] turns the content of a file into the pointer that is
+//! committed in its place, and [`smudge_paths`] turns the pointers a checkout wrote back
+//! into content. Objects are transferred around pushes and fetches with [`push`] and
+//! [`fetch`]. Nothing runs in repositories that don't use LFS.
+//!
+//! Files are compared with their pointers by the sha256 the pointers have, without running
+//! LFS, which is only run to clean content it wasn't run on yet.
+
+use std::{
+    fmt::Write,
+    fs,
+    io::{self, Read},
+    path,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+
+use super::{cleaned::Cleaned, filters, Oid, Repository};
+
+const POINTER_PREFIX: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+// pointers are small text files, anything bigger is content
+const MAX_POINTER_SIZE: u64 = 1024;
+
+// the pointers contents were cleaned to, by the git directory shared by the worktrees of the
+// repository and the sha256 of the content
+static CLEANED: Lazy<Mutex<Cleaned<(path::PathBuf, String)>>> = Lazy::new(Mutex::default);
+
+/// Writes the pointer of the file at `path` to the odb, returning its id. LFS only runs the
+/// first time the file has its content, which it stores for it to be pushed.
+pub fn clean(repository: &Repository, path: &path::Path) -> Result<Oid> {
+    let workdir = repository.workdir().context("repository has no workdir")?;
+    let full_path = workdir.join(path);
+    let (sha256, _) =
+        sha256(&full_path).with_context(|| format!("failed to read {}", path.display()))?;
+    let key = (repository.common_dir().to_path_buf(), sha256);
+    let cached = CLEANED.lock().unwrap().get(&key);
+    if let Some(pointer) = cached.filter(|pointer| repository.find_blob(*pointer).is_ok()) {
+        return Ok(pointer);
+    }
+    let content =
+        fs::read(&full_path).with_context(|| format!("failed to read {}", path.display()))?;
+    let pointer = filter(repository, "clean", path, &content)?;
+    let pointer = repository.blob(&pointer)?;
+    let key_size = key.0.as_os_str().len() + key.1.len();
+    CLEANED.lock().unwrap().insert(key, key_size, pointer);
+    Ok(pointer)
+}
+
+/// Whether the file at `path` has the content `pointer` points to, compared by their size
+/// and sha256 without running LFS. False if `pointer` isn't one.
+pub fn matches_pointer(repository: &Repository, path: &path::Path, pointer: &[u8]) -> bool {
+    let Some((pointer_sha256, pointer_size)) = parse_pointer(pointer) else {
+        return false;
+    };
+    let Some(workdir) = repository.workdir() else {
+        return false;
+    };
+    let full_path = workdir.join(path);
+    if !fs::metadata(&full_path).is_ok_and(|metadata| metadata.len() == pointer_size) {
+        return false;
+    }
+    sha256(&full_path).is_ok_and(|(sha256, _)| sha256 == pointer_sha256)
+}
+
+// the sha256 and size of the content `pointer` points to, `None` if it isn't a pointer
+fn parse_pointer(pointer: &[u8]) -> Option<(String, u64)> {
+    if !pointer.starts_with(POINTER_PREFIX) || pointer.len() as u64 > MAX_POINTER_SIZE {
+        return None;
+    }
+    let pointer = std::str::from_utf8(pointer).ok()?;
+    let mut sha256 = None;
+    let mut size = None;
+    for line in pointer.lines() {
+        if let Some(oid) = line.strip_prefix("oid sha256:") {
+            sha256 = Some(oid.trim().to_lowercase());
+        } else if let Some(value) = line.strip_prefix("size ") {
+            size = value.trim().parse().ok();
+        }
+    }
+    Some((sha256?, size?))
+}
+
+// the sha256 of the file at `path` in hex, and its size
+fn sha256(path: &path::Path) -> io::Result<(String, u64)> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    let mut hex = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    Ok((hex, size))
+}
+
+/// Replaces the LFS files among `paths` that are checked out as pointers with their content,
+/// downloading it if it's not there yet. Files that fail are logged and left as pointers.
+pub fn smudge_paths(
+    repository: &Repository,
+    paths: impl IntoIterator<Item = path::PathBuf>,
+) -> Result<()> {
+    if !repository.uses_lfs() {
+        return Ok(());
+    }
+    let workdir = repository.workdir().context("repository has no workdir")?;
+    for path in paths {
+        if !repository.is_path_lfs(&path).unwrap_or(false) {
+            continue;
+        }
+        let full_path = workdir.join(&path);
+        let Some(pointer) = read_pointer(&full_path) else {
+            continue;
+        };
+        let content = filter(repository, "smudge", &path, &pointer)
+            .and_then(|content| std::fs::write(&full_path, content).map_err(Into::into));
+        if let Err(error) = content {
+            tracing::warn!(?error, path = %path.display(), "failed to smudge lfs file");
+        }
+    }
+    Ok(())
+}
+
+/// Uploads the LFS objects of the commits up to `head` that `remote` doesn't have.
+pub fn push(repository: &Repository, remote: &str, head: Oid) -> Result<()> {
+    if !repository.uses_lfs() {
+        return Ok(());
+    }
+    transfer(repository, &["push", remote, &head.to_string()])
+}
+
+/// Downloads the LFS objects of what is checked out from `remote`.
+pub fn fetch(repository: &Repository, remote: &str) -> Result<()> {
+    if !repository.uses_lfs() {
+        return Ok(());
+    }
+    transfer(repository, &["fetch", remote])
+}
+
+fn read_pointer(path: &path::Path) -> Option<Vec<u8>> {
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_POINTER_SIZE {
+        return None;
+    }
+    let content = std::fs::read(path).ok()?;
+    content.starts_with(POINTER_PREFIX).then_some(content)
+}
+
+fn command(repository: &Repository) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("lfs")
+        .current_dir(repository.workdir().unwrap_or(repository.path()));
+    command
+}
+
+// runs a filter, which reads the file on stdin and writes the result on stdout
+fn filter(repository: &Repository, name: &str, path: &path::Path, stdin: &[u8]) -> Result<Vec<u8>> {
+    let mut command = command(repository);
+    command.arg(name).arg("--").arg(path);
+    filters::pipe(command, stdin)
+        .with_context(|| format!("git lfs {name} failed, is it installed?"))
+}
+
+// runs a transfer, reporting its progress in the logs as it goes
+fn transfer(repository: &Repository, args: &[&str]) -> Result<()> {
+    let mut child = command(repository)
+        .args(args)
+        .env("GIT_LFS_FORCE_PROGRESS", "1")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run git lfs, is it installed?")?;
+
+    let mut stderr = child.stderr.take().context("failed to open stderr")?;
+    let mut output = vec![];
+    let mut pending = vec![];
+    let mut buffer = [0; 1024];
+    loop {
+        let read = stderr
+            .read(&mut buffer)
+            .context("failed to read git lfs output")?;
+        if read == 0 {
+            break;
+        }
+        output.extend_from_slice(&buffer[..read]);
+        pending.extend_from_slice(&buffer[..read]);
+        // progress is redrawn with carriage returns, every redraw is a line
+        while let Some(end) = pending
+            .iter()
+            .position(|byte| matches!(byte, b'\r' | b'\n'))
+        {
+            let line = pending.drain(..=end).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            if !line.trim().is_empty() {
+                tracing::info!(command = args[0], progress = line.trim(), "git lfs");
+            }
+        }
+    }
+
+    let status = child.wait().context("failed to run git lfs")?;
+    if !status.success() {
+        anyhow::bail!(
+            "git lfs {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output).trim()
+        );
+    }
+    Ok(())
+}
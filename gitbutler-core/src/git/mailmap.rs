@@ -0,0 +1,21 @@
+use super::{Result, Signature};
+
+/// The mailmap of a repository, mapping the names and emails commits were made with to the
+/// ones their authors go by, so that someone who committed as several people shows up as one.
+pub struct Mailmap(git2::Mailmap);
+
+impl From<git2::Mailmap> for Mailmap {
+    fn from(value: git2::Mailmap) -> Self {
+        Self(value)
+    }
+}
+
+impl Mailmap {
+    /// The name and email `signature` goes by, itself when the mailmap doesn't mention it.
+    pub fn resolve(&self, signature: &Signature<'_>) -> Result<Signature<'static>> {
+        self.0
+            .resolve_signature(signature.into())
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+}
@@ -53,3 +53,9 @@ impl From<Oid> for git2::Oid {
         oid.oid
     }
 }
+
+impl Oid {
+    pub fn is_zero(&self) -> bool {
+        self.oid.is_zero()
+    }
+}
@@ -0,0 +1,161 @@
+//! Paths in git are bytes, and on Linux they don't have to be valid UTF-8. They are carried
+//! as [`BString`]s, or as paths built from the same bytes, and only turned into strings
+//! where they leave for the frontend or are written as text.
+//!
+//! Those strings are escaped: the bytes that aren't valid UTF-8 are written as `\xNN` and
+//! backslashes as `\\`, so that every path has a string it can be read back from.
+
+use std::{fmt::Write, path};
+
+use bstr::{BStr, BString, ByteSlice};
+
+/// The bytes git stores `path` as.
+pub fn to_bytes(path: &path::Path) -> BString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        BString::from(path.as_os_str().as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        // paths elsewhere are unicode, which git stores as UTF-8
+        BString::from(path.to_string_lossy().as_bytes())
+    }
+}
+
+/// The path git stores as `bytes`.
+pub fn from_bytes(bytes: &BStr) -> path::PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        path::PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        path::PathBuf::from(bytes.to_str_lossy().into_owned())
+    }
+}
+
+/// `path` as a string that [`unescape`] reads it back from.
+pub fn escape(path: &path::Path) -> String {
+    let bytes = to_bytes(path);
+    let mut escaped = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c == '\\' {
+                escaped.push_str("\\\\");
+            } else {
+                escaped.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            let _ = write!(escaped, "\\x{byte:02x}");
+        }
+    }
+    escaped
+}
+
+/// The path `escaped` is the [`escape`]d string of. Backslashes that don't start an escape
+/// are kept as they are, like in strings written before paths were escaped.
+pub fn unescape(escaped: &str) -> path::PathBuf {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'\\' {
+            if let Some(tail) = tail.strip_prefix(b"\\") {
+                bytes.push(b'\\');
+                rest = tail;
+                continue;
+            }
+            if let Some(hex) = tail.strip_prefix(b"x").and_then(|tail| tail.get(..2)) {
+                if let Some(byte) = std::str::from_utf8(hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    bytes.push(byte);
+                    rest = &tail[3..];
+                    continue;
+                }
+            }
+        }
+        bytes.push(byte);
+        rest = tail;
+    }
+    from_bytes(bytes.as_bstr())
+}
+
+/// Serializes paths as [`escape`]d strings, for `#[serde(with = "...")]`.
+pub mod escaped {
+    use std::path;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(path: &path::Path, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::escape(path))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<path::PathBuf, D::Error> {
+        String::deserialize(deserializer).map(|escaped| super::unescape(&escaped))
+    }
+
+    /// The same, for optional paths.
+    pub mod option {
+        use std::path;
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            path: &Option<path::PathBuf>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match path {
+                Some(path) => serializer.serialize_some(&super::super::escape(path)),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<path::PathBuf>, D::Error> {
+            Option::<String>::deserialize(deserializer)
+                .map(|escaped| escaped.map(|escaped| super::super::unescape(&escaped)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_round_trip() {
+        for path in [
+            "src/main.rs",
+            "dir\\with\\backslashes",
+            "ünïcode/ファイル",
+            "",
+        ] {
+            let path = path::Path::new(path);
+            assert_eq!(unescape(&escape(path)), path);
+        }
+        assert_eq!(escape(path::Path::new("a\\b")), "a\\\\b");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_utf8() {
+        let path = from_bytes(b"caf\xe9/\xff\xfe.txt".as_bstr());
+        let escaped = escape(&path);
+        assert_eq!(escaped, "caf\\xe9/\\xff\\xfe.txt");
+        assert_eq!(unescape(&escaped), path);
+        assert_eq!(to_bytes(&path), b"caf\xe9/\xff\xfe.txt".as_bstr());
+    }
+
+    #[test]
+    fn unescaped_backslashes_are_kept() {
+        assert_eq!(unescape("a\\b\\x"), path::Path::new("a\\b\\x"));
+        assert_eq!(unescape("a\\xzz"), path::Path::new("a\\xzz"));
+    }
+}
@@ -28,6 +28,13 @@ impl<'repo> Reference<'repo> {
         self.reference.target().map(Into::into)
     }
 
+    /// The reference this one points at, if it's a symbolic reference like `HEAD`.
+    pub fn symbolic_target(&self) -> Option<Refname> {
+        self.reference
+            .symbolic_target()
+            .and_then(|name| name.parse().ok())
+    }
+
     pub fn peel_to_commit(&self) -> Result<Commit<'repo>> {
         self.reference
             .peel_to_commit()
@@ -0,0 +1,98 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::virtual_branches::normalize_branch_name;
+use crate::virtual_branches::Branch;
+
+use super::error::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Refname {
+    // the projects of a repository but one keep their references apart, under
+    // `refs/gitbutler/<namespace>/`, see `project_repository::Repository::refs_namespace`
+    namespace: Option<String>,
+    // contains slug of the virtual branch name
+    branch: String,
+}
+
+impl Refname {
+    pub fn branch(&self) -> &str {
+        &self.branch
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// The reference in `namespace`. A namespace has an `@`, that no branch has.
+    pub fn within(self, namespace: Option<&str>) -> Self {
+        Self {
+            namespace: namespace.map(str::to_string),
+            ..self
+        }
+    }
+}
+
+impl From<&Branch> for Refname {
+    fn from(value: &Branch) -> Self {
+        let branch = normalize_branch_name(&value.name);
+        // refs/gitbutler/metadata holds the metadata of all branches
+        let branch = if branch == "metadata" {
+            "metadata-branch".to_string()
+        } else {
+            branch
+        };
+        Self {
+            namespace: None,
+            branch,
+        }
+    }
+}
+
+impl Serialize for Refname {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'d> Deserialize<'d> for Refname {
+    fn deserialize<D: serde::Deserializer<'d>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.as_str().parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Refname {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.namespace {
+            Some(namespace) => write!(f, "refs/gitbutler/{}/{}", namespace, self.branch),
+            None => write!(f, "refs/gitbutler/{}", self.branch),
+        }
+    }
+}
+
+impl FromStr for Refname {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if !value.starts_with("refs/gitbutler/") {
+            return Err(Error::NotLocal(value.to_string()));
+        }
+
+        if let Some(branch) = value.strip_prefix("refs/gitbutler/") {
+            match branch.split_once('/') {
+                Some((namespace, branch)) if namespace.contains('@') => Ok(Self {
+                    namespace: Some(namespace.to_string()),
+                    branch: branch.to_string(),
+                }),
+                _ => Ok(Self {
+                    namespace: None,
+                    branch: branch.to_string(),
+                }),
+            }
+        } else {
+            Err(Error::InvalidName(value.to_string()))
+        }
+    }
+}
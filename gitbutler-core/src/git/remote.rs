@@ -29,6 +29,13 @@ impl<'repo> Remote<'repo> {
         Ok(self.inner.url())
     }
 
+    /// The branch the HEAD of the remote points to, like `refs/heads/main`, as the remote
+    /// told it the last time it was connected to.
+    pub fn default_branch(&self) -> Result<Option<String>> {
+        let branch = self.inner.default_branch()?;
+        Ok(branch.as_str().map(ToString::to_string))
+    }
+
     pub fn push(
         &mut self,
         refspec: &[&str],
@@ -44,4 +51,21 @@ impl<'repo> Remote<'repo> {
     ) -> Result<()> {
         self.inner.fetch(refspec, opts, None).map_err(Into::into)
     }
+
+    /// Downloads what fetching `refspec` would, without updating any reference, see
+    /// [`Self::update_tips`].
+    pub fn download(
+        &mut self,
+        refspec: &[&str],
+        opts: Option<&mut git2::FetchOptions<'_>>,
+    ) -> Result<()> {
+        self.inner.download(refspec, opts).map_err(Into::into)
+    }
+
+    /// Updates the references of the last [`Self::download`] to what it downloaded.
+    pub fn update_tips(&mut self) -> Result<()> {
+        self.inner
+            .update_tips(None, true, git2::AutotagOption::Unspecified, None)
+            .map_err(Into::into)
+    }
 }
@@ -0,0 +1,1091 @@
+use std::{cell::RefCell, path, rc::Rc, str};
+
+use git2::Submodule;
+use git2_hooks::HookResult;
+
+use crate::keys;
+
+use super::{
+    eol, Blob, Branch, Commit, Config, Index, Mailmap, Oid, Reference, Refname, Remote, Result,
+    Signature, Tree, TreeBuilder, Url,
+};
+
+// wrapper around git2::Repository to get control over how it's used.
+pub struct Repository(git2::Repository);
+
+impl<'a> From<&'a Repository> for &'a git2::Repository {
+    fn from(repo: &'a Repository) -> Self {
+        &repo.0
+    }
+}
+
+impl From<git2::Repository> for Repository {
+    fn from(repo: git2::Repository) -> Self {
+        Self(repo)
+    }
+}
+
+impl Repository {
+    #[cfg(test)]
+    pub fn init_bare<P: AsRef<path::Path>>(path: P) -> Result<Self> {
+        let inner = git2::Repository::init_bare(path)?;
+        Ok(Repository(inner))
+    }
+
+    pub fn init<P: AsRef<path::Path>>(path: P) -> Result<Self> {
+        let inner = git2::Repository::init(path)?;
+        Ok(Repository(inner))
+    }
+
+    pub fn open<P: AsRef<path::Path>>(path: P) -> Result<Self> {
+        let inner = git2::Repository::open(path)?;
+        Ok(Repository(inner))
+    }
+
+    pub fn init_opts<P: AsRef<path::Path>>(
+        path: P,
+        opts: &git2::RepositoryInitOptions,
+    ) -> Result<Self> {
+        let inner = git2::Repository::init_opts(path, opts)?;
+        Ok(Repository(inner))
+    }
+
+    pub fn add_disk_alternate(&self, path: &str) -> Result<()> {
+        let alternates_path = self.0.path().join("objects/info/alternates");
+        if !alternates_path.exists() {
+            std::fs::write(alternates_path, format!("{path}\n"))?;
+            self.0.odb().and_then(|odb| odb.refresh())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn add_submodule(&self, url: &Url, path: &path::Path) -> Result<Submodule<'_>> {
+        self.0
+            .submodule(&url.to_string(), path, false)
+            .map_err(Into::into)
+    }
+
+    /// The submodules listed in `.gitmodules` or the index.
+    pub fn submodules(&self) -> Result<Vec<Submodule<'_>>> {
+        self.0.submodules().map_err(Into::into)
+    }
+
+    pub fn rebase(
+        &self,
+        branch_oid: Option<Oid>,
+        upstream_oid: Option<Oid>,
+        onto_oid: Option<Oid>,
+        opts: Option<&mut git2::RebaseOptions<'_>>,
+    ) -> Result<git2::Rebase<'_>> {
+        let annotated_branch = if let Some(branch) = branch_oid {
+            Some(self.0.find_annotated_commit(branch.into())?)
+        } else {
+            None
+        };
+
+        let annotated_upstream = if let Some(upstream) = upstream_oid {
+            Some(self.0.find_annotated_commit(upstream.into())?)
+        } else {
+            None
+        };
+
+        let annotated_onto = if let Some(onto) = onto_oid {
+            Some(self.0.find_annotated_commit(onto.into())?)
+        } else {
+            None
+        };
+
+        self.0
+            .rebase(
+                annotated_branch.as_ref(),
+                annotated_upstream.as_ref(),
+                annotated_onto.as_ref(),
+                opts,
+            )
+            .map_err(Into::into)
+    }
+
+    pub fn is_descendant_of(&self, a: Oid, b: Oid) -> Result<bool> {
+        self.0
+            .graph_descendant_of(a.into(), b.into())
+            .map_err(Into::into)
+    }
+
+    /// returns the number of unique commits on each side, `(ahead, behind)`, of `local`
+    /// relative to `upstream`.
+    pub fn graph_ahead_behind(&self, local: Oid, upstream: Oid) -> Result<(usize, usize)> {
+        self.0
+            .graph_ahead_behind(local.into(), upstream.into())
+            .map_err(Into::into)
+    }
+
+    pub fn merge_base(&self, one: Oid, two: Oid) -> Result<Oid> {
+        self.0
+            .merge_base(one.into(), two.into())
+            .map(Oid::from)
+            .map_err(Into::into)
+    }
+
+    pub fn merge_trees(
+        &self,
+        ancestor_tree: &Tree<'_>,
+        our_tree: &Tree<'_>,
+        their_tree: &Tree<'_>,
+    ) -> Result<Index> {
+        self.fetch_missing_blobs_of_changes(
+            Some(ancestor_tree.into()),
+            &[our_tree.into(), their_tree.into()],
+        )?;
+        self.0
+            .merge_trees(
+                ancestor_tree.into(),
+                our_tree.into(),
+                their_tree.into(),
+                None,
+            )
+            .map(Index::from)
+            .map_err(Into::into)
+    }
+
+    pub fn diff_tree_to_tree(
+        &self,
+        old_tree: Option<&Tree<'_>>,
+        new_tree: Option<&Tree<'_>>,
+        opts: Option<&mut git2::DiffOptions>,
+    ) -> Result<git2::Diff<'_>> {
+        self.0
+            .diff_tree_to_tree(old_tree.map(Into::into), new_tree.map(Into::into), opts)
+            .map_err(Into::into)
+    }
+
+    pub fn diff_tree_to_workdir(
+        &self,
+        old_tree: Option<&Tree<'_>>,
+        opts: Option<&mut git2::DiffOptions>,
+    ) -> Result<git2::Diff<'_>> {
+        self.0
+            .diff_tree_to_workdir(old_tree.map(Into::into), opts)
+            .map_err(Into::into)
+    }
+
+    pub fn reset(
+        &self,
+        commit: &Commit<'_>,
+        kind: git2::ResetType,
+        checkout: Option<&mut git2::build::CheckoutBuilder<'_>>,
+    ) -> Result<()> {
+        let commit: &git2::Commit = commit.into();
+        self.0
+            .reset(commit.as_object(), kind, checkout)
+            .map_err(Into::into)
+    }
+
+    pub fn find_reference(&self, name: &Refname) -> Result<Reference> {
+        self.0
+            .find_reference(&name.to_string())
+            .map(Reference::from)
+            .map_err(Into::into)
+    }
+
+    pub fn head(&self) -> Result<Reference> {
+        self.0.head().map(Reference::from).map_err(Into::into)
+    }
+
+    pub fn find_tree(&self, id: Oid) -> Result<Tree> {
+        self.0
+            .find_tree(id.into())
+            .map(Tree::from)
+            .map_err(Into::into)
+    }
+
+    pub fn find_commit(&self, id: Oid) -> Result<Commit> {
+        self.0
+            .find_commit(id.into())
+            .map(Commit::from)
+            .map_err(Into::into)
+    }
+
+    pub fn find_blob(&self, id: Oid) -> Result<Blob> {
+        self.0
+            .find_blob(id.into())
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn revwalk(&self) -> Result<git2::Revwalk> {
+        self.0.revwalk().map_err(Into::into)
+    }
+
+    pub fn is_path_ignored<P: AsRef<path::Path>>(&self, path: P) -> Result<bool> {
+        self.0.is_path_ignored(path).map_err(Into::into)
+    }
+
+    /// returns true if the path is marked as generated with the `linguist-generated`
+    /// attribute in `.gitattributes`.
+    pub fn is_path_generated<P: AsRef<path::Path>>(&self, path: P) -> Result<bool> {
+        let value = self.0.get_attr(
+            path.as_ref(),
+            "linguist-generated",
+            git2::AttrCheckFlags::FILE_THEN_INDEX,
+        )?;
+        Ok(match git2::AttrValue::from_string(value) {
+            git2::AttrValue::True => true,
+            git2::AttrValue::String(value) => value == "true" || value == "1",
+            _ => false,
+        })
+    }
+
+    /// The length of the conflict markers of the file at `path`, from its
+    /// `conflict-marker-size` attribute, 7 if it doesn't have one.
+    pub fn conflict_marker_size<P: AsRef<path::Path>>(&self, path: P) -> Result<usize> {
+        let value = self.0.get_attr(
+            path.as_ref(),
+            "conflict-marker-size",
+            git2::AttrCheckFlags::FILE_THEN_INDEX,
+        )?;
+        Ok(match git2::AttrValue::from_string(value) {
+            git2::AttrValue::String(size) => size.parse().ok().filter(|size| *size > 0),
+            _ => None,
+        }
+        .unwrap_or(7))
+    }
+
+    /// true if the file at `path` may have conflict markers, with the
+    /// `gitbutler-conflict-markers` attribute.
+    pub fn allows_conflict_markers<P: AsRef<path::Path>>(&self, path: P) -> Result<bool> {
+        let value = self.0.get_attr(
+            path.as_ref(),
+            "gitbutler-conflict-markers",
+            git2::AttrCheckFlags::FILE_THEN_INDEX,
+        )?;
+        Ok(matches!(
+            git2::AttrValue::from_string(value),
+            git2::AttrValue::True
+        ))
+    }
+
+    /// true if the repository has LFS objects, or tracks files with LFS.
+    pub fn uses_lfs(&self) -> bool {
+        self.0.commondir().join("lfs").is_dir()
+            || self.0.workdir().is_some_and(|workdir| {
+                std::fs::read_to_string(workdir.join(".gitattributes"))
+                    .is_ok_and(|attributes| attributes.contains("filter=lfs"))
+            })
+    }
+
+    /// returns true if the path is tracked by LFS, with the `filter=lfs` attribute in
+    /// `.gitattributes`.
+    pub fn is_path_lfs<P: AsRef<path::Path>>(&self, path: P) -> Result<bool> {
+        let value = self.0.get_attr(
+            path.as_ref(),
+            "filter",
+            git2::AttrCheckFlags::FILE_THEN_INDEX,
+        )?;
+        Ok(matches!(
+            git2::AttrValue::from_string(value),
+            git2::AttrValue::String("lfs")
+        ))
+    }
+
+    /// The name of the filter driver of the file at `path`, from its `filter` attribute.
+    pub fn filter_attribute<P: AsRef<path::Path>>(&self, path: P) -> Result<Option<String>> {
+        let value = self.0.get_attr(
+            path.as_ref(),
+            "filter",
+            git2::AttrCheckFlags::FILE_THEN_INDEX,
+        )?;
+        Ok(match git2::AttrValue::from_string(value) {
+            git2::AttrValue::String(name) => Some(name.to_string()),
+            _ => None,
+        })
+    }
+
+    /// How the line endings of the file at `path` are converted when it's committed, from
+    /// its `text` and `eol` attributes, or `core.autocrlf` if it has neither.
+    pub fn eol_conversion<P: AsRef<path::Path>>(&self, path: P) -> Result<eol::Conversion> {
+        let path = path.as_ref();
+        let text = self
+            .0
+            .get_attr(path, "text", git2::AttrCheckFlags::FILE_THEN_INDEX)?;
+        match git2::AttrValue::from_string(text) {
+            git2::AttrValue::True => return Ok(eol::Conversion::Text),
+            git2::AttrValue::False => return Ok(eol::Conversion::None),
+            git2::AttrValue::String("auto") => return Ok(eol::Conversion::Auto),
+            _ => {}
+        }
+        // an eol makes a file text
+        let eol = self
+            .0
+            .get_attr(path, "eol", git2::AttrCheckFlags::FILE_THEN_INDEX)?;
+        if matches!(
+            git2::AttrValue::from_string(eol),
+            git2::AttrValue::String("lf" | "crlf")
+        ) {
+            return Ok(eol::Conversion::Text);
+        }
+        let autocrlf = self.config()?.get_string("core.autocrlf")?;
+        Ok(
+            match autocrlf.as_deref().map(str::to_lowercase).as_deref() {
+                Some("true" | "yes" | "on" | "1" | "input") => eol::Conversion::Auto,
+                _ => eol::Conversion::None,
+            },
+        )
+    }
+
+    /// Whether the file at `path` is checked out with `\r\n` line endings if it's converted,
+    /// from its `eol` attribute, or `core.autocrlf` and `core.eol` if it has none.
+    pub fn checks_out_crlf<P: AsRef<path::Path>>(&self, path: P) -> Result<bool> {
+        let eol = self
+            .0
+            .get_attr(path.as_ref(), "eol", git2::AttrCheckFlags::FILE_THEN_INDEX)?;
+        match git2::AttrValue::from_string(eol) {
+            git2::AttrValue::String("crlf") => return Ok(true),
+            git2::AttrValue::String("lf") => return Ok(false),
+            _ => {}
+        }
+        let config = self.config()?;
+        let autocrlf = config.get_string("core.autocrlf")?;
+        match autocrlf.as_deref().map(str::to_lowercase).as_deref() {
+            Some("true" | "yes" | "on" | "1") => return Ok(true),
+            Some("input") => return Ok(false),
+            _ => {}
+        }
+        Ok(config
+            .get_string("core.eol")?
+            .is_some_and(|eol| eol.eq_ignore_ascii_case("crlf")))
+    }
+
+    pub fn branches(
+        &self,
+        filter: Option<git2::BranchType>,
+    ) -> Result<impl Iterator<Item = Result<(Branch, git2::BranchType)>>> {
+        self.0
+            .branches(filter)
+            .map(|branches| {
+                branches.map(|branch| {
+                    branch
+                        .map(|(branch, branch_type)| (Branch::from(branch), branch_type))
+                        .map_err(Into::into)
+                })
+            })
+            .map_err(Into::into)
+    }
+
+    pub fn index(&self) -> Result<Index> {
+        self.0.index().map(Into::into).map_err(Into::into)
+    }
+
+    pub fn blob_path(&self, path: &path::Path) -> Result<Oid> {
+        self.0.blob_path(path).map(Into::into).map_err(Into::into)
+    }
+
+    pub fn cherry_pick(&self, base: &Commit, target: &Commit) -> Result<Index> {
+        self.0
+            .cherrypick_commit(target.into(), base.into(), 0, None)
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    /// The size of the blob `id`, read from its header rather than from its content.
+    pub fn blob_size(&self, id: Oid) -> Result<u64> {
+        let (size, _) = self.0.odb()?.read_header(id.into())?;
+        Ok(u64::try_from(size).unwrap_or(u64::MAX))
+    }
+
+    pub fn blob(&self, data: &[u8]) -> Result<Oid> {
+        self.0.blob(data).map(Into::into).map_err(Into::into)
+    }
+
+    pub fn commit(
+        &self,
+        update_ref: Option<&Refname>,
+        author: &Signature<'_>,
+        committer: &Signature<'_>,
+        message: &str,
+        tree: &Tree<'_>,
+        parents: &[&Commit<'_>],
+    ) -> Result<Oid> {
+        let parents: Vec<&git2::Commit> = parents
+            .iter()
+            .map(|c| c.to_owned().into())
+            .collect::<Vec<_>>();
+        self.0
+            .commit(
+                update_ref.map(ToString::to_string).as_deref(),
+                author.into(),
+                committer.into(),
+                message,
+                tree.into(),
+                &parents,
+            )
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn commit_signed(
+        &self,
+        author: &Signature<'_>,
+        message: &str,
+        tree: &Tree<'_>,
+        parents: &[&Commit<'_>],
+        key: &keys::PrivateKey,
+    ) -> Result<Oid> {
+        let parents: Vec<&git2::Commit> = parents
+            .iter()
+            .map(|c| c.to_owned().into())
+            .collect::<Vec<_>>();
+        let commit_buffer = self.0.commit_create_buffer(
+            author.into(),
+            // author and committer must be the same
+            // for signed commits
+            author.into(),
+            message,
+            tree.into(),
+            &parents,
+        )?;
+        let commit_buffer = str::from_utf8(&commit_buffer).unwrap();
+        let signature = key.sign(commit_buffer.as_bytes())?;
+        self.0
+            .commit_signed(commit_buffer, &signature, None)
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    /// Creates the tag `name` pointing to `target`, failing if it exists unless `force`.
+    pub fn tag_lightweight(&self, name: &str, target: Oid, force: bool) -> Result<Oid> {
+        let object = self.0.find_object(target.into(), None)?;
+        self.0
+            .tag_lightweight(name, &object, force)
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    /// Creates the annotated tag `name` of `target`, failing if it exists unless `force`.
+    pub fn tag_annotated(
+        &self,
+        name: &str,
+        target: Oid,
+        tagger: &Signature<'_>,
+        message: &str,
+        force: bool,
+    ) -> Result<Oid> {
+        let object = self.0.find_object(target.into(), None)?;
+        self.0
+            .tag(name, &object, tagger.into(), message, force)
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    /// Same as `tag_annotated`, with the tag signed with `key` the way `git tag -s` signs
+    /// with `gpg.format` set to `ssh`.
+    pub fn tag_signed(
+        &self,
+        name: &str,
+        target: Oid,
+        tagger: &Signature<'_>,
+        message: &str,
+        key: &keys::PrivateKey,
+        force: bool,
+    ) -> Result<Oid> {
+        let object = self.0.find_object(target.into(), None)?;
+        let kind = object.kind().unwrap_or(git2::ObjectType::Commit);
+        let tagger: &git2::Signature = tagger.into();
+        let when = tagger.when();
+        let offset = when.offset_minutes().abs();
+        let mut buffer = format!(
+            "object {target}\ntype {kind}\ntag {name}\ntagger {} <{}> {} {}{:02}{:02}\n\n{message}",
+            String::from_utf8_lossy(tagger.name_bytes()),
+            String::from_utf8_lossy(tagger.email_bytes()),
+            when.seconds(),
+            when.sign(),
+            offset / 60,
+            offset % 60,
+        );
+        if !buffer.ends_with('\n') {
+            buffer.push('\n');
+        }
+        // the signature is of everything before it, and is the end of the tag
+        let signature = key.sign(buffer.as_bytes())?;
+        buffer.push_str(&signature);
+        let oid = self
+            .0
+            .odb()?
+            .write(git2::ObjectType::Tag, buffer.as_bytes())?;
+        self.0
+            .reference(&format!("refs/tags/{name}"), oid, force, "signed tag")?;
+        Ok(oid.into())
+    }
+
+    /// The names of the tags, only the ones matching the glob `pattern` if there is one.
+    pub fn tag_names(&self, pattern: Option<&str>) -> Result<Vec<String>> {
+        Ok(self
+            .0
+            .tag_names(pattern)?
+            .iter()
+            .flatten()
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    pub fn tag_delete(&self, name: &str) -> Result<()> {
+        self.0.tag_delete(name).map_err(Into::into)
+    }
+
+    /// The commit the tag `name` points to, and its message if it is an annotated tag.
+    pub fn find_tag(&self, name: &str) -> Result<(Oid, Option<String>)> {
+        let reference = self.0.find_reference(&format!("refs/tags/{name}"))?;
+        let commit = reference.peel_to_commit()?;
+        let message = reference
+            .peel_to_tag()
+            .ok()
+            .and_then(|tag| tag.message().map(ToString::to_string));
+        Ok((commit.id().into(), message))
+    }
+
+    /// The notes ref written to when none is given, `core.notesRef` or `refs/notes/commits`.
+    pub fn note_default_ref(&self) -> Result<String> {
+        self.0.note_default_ref().map_err(Into::into)
+    }
+
+    /// The message of the note of `id` in `notes_ref`, if it has one.
+    pub fn find_note(&self, notes_ref: &str, id: Oid) -> Result<Option<String>> {
+        match self.0.find_note(Some(notes_ref), id.into()) {
+            Ok(note) => Ok(note.message().map(ToString::to_string)),
+            Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Writes `message` as the note of `id` in `notes_ref`, replacing the one it had.
+    pub fn note(
+        &self,
+        signature: &Signature<'_>,
+        notes_ref: &str,
+        id: Oid,
+        message: &str,
+    ) -> Result<Oid> {
+        self.0
+            .note(
+                signature.into(),
+                signature.into(),
+                Some(notes_ref),
+                id.into(),
+                message,
+                true,
+            )
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn note_delete(&self, signature: &Signature<'_>, notes_ref: &str, id: Oid) -> Result<()> {
+        self.0
+            .note_delete(
+                id.into(),
+                Some(notes_ref),
+                signature.into(),
+                signature.into(),
+            )
+            .map_err(Into::into)
+    }
+
+    /// returns the name of the remote missing objects can be fetched from, if this
+    /// is a partial clone.
+    pub fn promisor_remote(&self) -> Result<Option<String>> {
+        self.config()?.get_string("extensions.partialClone")
+    }
+
+    /// makes sure that all of `oids` are present in the object database, fetching the
+    /// missing ones from the promisor remote if this is a partial clone.
+    ///
+    /// libgit2 can not fetch missing objects on demand, so the git cli is used for that.
+    pub fn fetch_missing_objects(&self, oids: &[Oid]) -> Result<()> {
+        let odb = self.0.odb()?;
+        let missing = oids
+            .iter()
+            .filter(|oid| !oid.is_zero() && !odb.exists((**oid).into()))
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let Some(remote) = self.promisor_remote()? else {
+            // not a partial clone, the objects are really missing
+            return Ok(());
+        };
+
+        tracing::debug!(count = missing.len(), %remote, "fetching missing objects");
+
+        let mut child = std::process::Command::new("git")
+            .arg("--git-dir")
+            .arg(self.path())
+            .args([
+                "-c",
+                "fetch.negotiationAlgorithm=noop",
+                "fetch",
+                &remote,
+                "--no-tags",
+                "--no-write-fetch-head",
+                "--recurse-submodules=no",
+                "--filter=blob:none",
+                "--stdin",
+            ])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            use std::io::Write;
+            stdin.write_all(missing.join("\n").as_bytes())?;
+        }
+
+        let output = child.wait_with_output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(super::Error::Other(git2::Error::from_str(&format!(
+                "failed to fetch missing objects: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ))))
+        }
+    }
+
+    // fetches the blobs of what changed between `from` and each of `to`, everything they have
+    // if `from` is not set, for merging or checking them out in a partial clone, which reads
+    // them without being able to fetch them itself. that's all it reads, the blobs of what
+    // stays the same are left where they are
+    fn fetch_missing_blobs_of_changes(
+        &self,
+        from: Option<&git2::Tree>,
+        to: &[&git2::Tree],
+    ) -> Result<()> {
+        if self.promisor_remote()?.is_none() {
+            return Ok(());
+        }
+        let mut oids = vec![];
+        for tree in to {
+            let diff = self.0.diff_tree_to_tree(from, Some(*tree), None)?;
+            oids.extend(
+                diff.deltas()
+                    .flat_map(|delta| [delta.old_file(), delta.new_file()])
+                    .filter(|file| file.mode() != git2::FileMode::Commit)
+                    .map(|file| Oid::from(file.id())),
+            );
+        }
+        self.fetch_missing_objects(&oids)
+    }
+
+    pub fn config(&self) -> Result<Config> {
+        self.0.config().map(Into::into).map_err(Into::into)
+    }
+
+    /// Returns true if checkouts write symlinks as symlinks, as configured in `core.symlinks`.
+    /// Otherwise, which is the default on Windows, they are written as files holding the
+    /// target of the link.
+    pub fn supports_symlinks(&self) -> bool {
+        self.config()
+            .and_then(|config| config.get_bool("core.symlinks"))
+            .ok()
+            .flatten()
+            .unwrap_or(cfg!(unix))
+    }
+
+    /// Returns true if file names in the working directory that only differ in case refer
+    /// to the same file. Git records that in `core.ignorecase` when the repository is created,
+    /// the filesystem is probed once, in the git directory, if it didn't.
+    pub fn ignore_case(&self) -> bool {
+        if let Ok(Some(ignore_case)) = self
+            .config()
+            .and_then(|config| config.get_bool("core.ignorecase"))
+        {
+            return ignore_case;
+        }
+        self.workdir().map_or(false, |workdir| {
+            crate::fs::is_case_insensitive(workdir, self.path()).unwrap_or_else(|error| {
+                tracing::warn!(?error, "failed to probe filesystem case sensitivity");
+                false
+            })
+        })
+    }
+
+    pub fn treebuilder<'repo>(&'repo self, tree: Option<&'repo Tree>) -> TreeBuilder<'repo> {
+        TreeBuilder::new(self, tree)
+    }
+
+    /// The git directory shared by every worktree of the repository, holding objects, refs
+    /// and config. The same as [`Repository::path`] except in linked worktrees.
+    pub fn common_dir(&self) -> &path::Path {
+        self.0.commondir()
+    }
+
+    /// The name of the linked worktree the repository is checked out in, `None` in the main
+    /// one.
+    pub fn worktree_name(&self) -> Option<String> {
+        if !self.0.is_worktree() {
+            return None;
+        }
+        self.0
+            .path()
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+    }
+
+    pub fn path(&self) -> &path::Path {
+        self.0.path()
+    }
+
+    /// The operation the repository is in the middle of, like a merge or a rebase.
+    pub fn state(&self) -> git2::RepositoryState {
+        self.0.state()
+    }
+
+    /// Blames the file at `path`, relative to the working directory, the way `opts` says.
+    pub fn blame(
+        &self,
+        path: &path::Path,
+        opts: &mut git2::BlameOptions,
+    ) -> Result<git2::Blame<'_>> {
+        self.0.blame_file(path, Some(opts)).map_err(Into::into)
+    }
+
+    /// The mailmap of the repository, read from `.mailmap` in the working directory and from
+    /// the `mailmap.file` and `mailmap.blob` config.
+    pub fn mailmap(&self) -> Result<Mailmap> {
+        self.0.mailmap().map(Into::into).map_err(Into::into)
+    }
+
+    /// Whether the repository is a shallow clone, its history ending before the root commits.
+    pub fn is_shallow(&self) -> bool {
+        self.0.is_shallow()
+    }
+
+    pub fn is_bare(&self) -> bool {
+        self.0.is_bare()
+    }
+
+    /// The working directory of the linked worktree `name`, if it has one that still exists.
+    pub fn find_worktree(&self, name: &str) -> Result<Option<path::PathBuf>> {
+        let worktree = match self.0.find_worktree(name) {
+            Ok(worktree) => worktree,
+            Err(error) if error.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        if worktree.validate().is_ok() {
+            return Ok(Some(worktree.path().to_path_buf()));
+        }
+        // the directory is gone, forget about it so that the name can be used again
+        worktree.prune(None)?;
+        Ok(None)
+    }
+
+    /// Adds a linked worktree `name` at `path`, checked out on the local branch `branch`,
+    /// which is created from `HEAD` if it doesn't exist.
+    pub fn add_worktree(&self, name: &str, path: &path::Path, branch: &str) -> Result<()> {
+        let branch = match self.0.find_branch(branch, git2::BranchType::Local) {
+            Ok(branch) => branch,
+            Err(error) if error.code() == git2::ErrorCode::NotFound => {
+                let head = self.0.head()?.peel_to_commit()?;
+                self.0.branch(branch, &head, false)?
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let mut options = git2::WorktreeAddOptions::new();
+        options.reference(Some(branch.get()));
+        self.0.worktree(name, path, Some(&options))?;
+        Ok(())
+    }
+
+    pub fn workdir(&self) -> Option<&path::Path> {
+        self.0.workdir()
+    }
+
+    pub fn branch_upstream_name(&self, branch_name: &str) -> Result<String> {
+        self.0
+            .branch_upstream_name(branch_name)
+            .map(|s| s.as_str().unwrap().to_string())
+            .map_err(Into::into)
+    }
+
+    pub fn branch_remote_name(&self, refname: &str) -> Result<String> {
+        self.0
+            .branch_remote_name(refname)
+            .map(|s| s.as_str().unwrap().to_string())
+            .map_err(Into::into)
+    }
+
+    pub fn branch_upstream_remote(&self, branch_name: &str) -> Result<String> {
+        self.0
+            .branch_upstream_remote(branch_name)
+            .map(|s| s.as_str().unwrap().to_string())
+            .map_err(Into::into)
+    }
+
+    pub fn statuses(
+        &self,
+        options: Option<&mut git2::StatusOptions>,
+    ) -> Result<git2::Statuses<'_>> {
+        self.0.statuses(options).map_err(Into::into)
+    }
+
+    pub fn remotes(&self) -> Result<Vec<String>> {
+        self.0
+            .remotes()
+            .map(|remotes| remotes.iter().flatten().map(String::from).collect())
+            .map_err(Into::into)
+    }
+
+    pub fn remote_anonymous(&self, url: &super::Url) -> Result<Remote> {
+        self.0
+            .remote_anonymous(&url.to_string())
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn find_remote(&self, name: &str) -> Result<Remote> {
+        self.0.find_remote(name).map(Into::into).map_err(Into::into)
+    }
+
+    pub fn find_branch(&self, name: &Refname) -> Result<Branch> {
+        self.0
+            .find_branch(
+                &name.simple_name(),
+                match name {
+                    Refname::Virtual(_) | Refname::Local(_) | Refname::Other(_) => {
+                        git2::BranchType::Local
+                    }
+                    Refname::Remote(_) => git2::BranchType::Remote,
+                },
+            )
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn refname_to_id(&self, name: &str) -> Result<Oid> {
+        self.0
+            .refname_to_id(name)
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn checkout_head(&self, opts: Option<&mut git2::build::CheckoutBuilder>) -> Result<()> {
+        self.0.checkout_head(opts).map_err(Into::into)
+    }
+
+    pub fn checkout_index<'a>(&'a self, index: &'a mut Index) -> CheckoutIndexBuilder {
+        CheckoutIndexBuilder {
+            index: index.into(),
+            repo: self,
+            checkout_builder: git2::build::CheckoutBuilder::new(),
+        }
+    }
+
+    pub fn checkout_tree<'a>(&'a self, tree: &'a Tree<'a>) -> CheckoutTreeBuidler {
+        CheckoutTreeBuidler {
+            tree,
+            repo: self,
+            checkout_builder: git2::build::CheckoutBuilder::new(),
+        }
+    }
+
+    pub fn reference_symbolic(
+        &self,
+        name: &Refname,
+        target: &Refname,
+        force: bool,
+        log_message: &str,
+    ) -> Result<Reference> {
+        self.0
+            .reference_symbolic(&name.to_string(), &target.to_string(), force, log_message)
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn set_head(&self, refname: &Refname) -> Result<()> {
+        self.0.set_head(&refname.to_string()).map_err(Into::into)
+    }
+
+    pub fn set_head_detached(&self, commitish: Oid) -> Result<()> {
+        self.0
+            .set_head_detached(commitish.into())
+            .map_err(Into::into)
+    }
+
+    pub fn reference(
+        &self,
+        name: &Refname,
+        id: Oid,
+        force: bool,
+        log_message: &str,
+    ) -> Result<Reference> {
+        self.0
+            .reference(&name.to_string(), id.into(), force, log_message)
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn get_wd_tree(&self) -> Result<Tree> {
+        let mut index = self.0.index()?;
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+        let oid = index.write_tree()?;
+        self.0.find_tree(oid).map(Into::into).map_err(Into::into)
+    }
+
+    pub fn remote(&self, name: &str, url: &Url) -> Result<Remote> {
+        self.0
+            .remote(name, &url.to_string())
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    /// The commits the reference `name` pointed to according to its reflog, newest first.
+    /// References without a reflog have none.
+    pub fn reflog(&self, name: &str) -> Result<Vec<Oid>> {
+        let reflog = self.0.reflog(name)?;
+        Ok(reflog
+            .iter()
+            .flat_map(|entry| [entry.id_new(), entry.id_old()])
+            .filter(|id| !id.is_zero())
+            .map(Into::into)
+            .collect())
+    }
+
+    pub fn references(&self) -> Result<impl Iterator<Item = Result<Reference>>> {
+        self.0
+            .references()
+            .map(|iter| iter.map(|reference| reference.map(Into::into).map_err(Into::into)))
+            .map_err(Into::into)
+    }
+
+    pub fn references_glob(&self, glob: &str) -> Result<impl Iterator<Item = Result<Reference>>> {
+        self.0
+            .references_glob(glob)
+            .map(|iter| iter.map(|reference| reference.map(Into::into).map_err(Into::into)))
+            .map_err(Into::into)
+    }
+
+    pub fn run_hook_pre_commit(&self) -> Result<HookResult> {
+        let res = git2_hooks::hooks_pre_commit(&self.0, Some(&["../.husky"]))?;
+        Ok(res)
+    }
+
+    pub fn run_hook_commit_msg(&self, msg: &mut String) -> Result<HookResult> {
+        let res = git2_hooks::hooks_commit_msg(&self.0, Some(&["../.husky"]), msg)?;
+        Ok(res)
+    }
+
+    pub fn run_hook_post_commit(&self) -> Result<()> {
+        git2_hooks::hooks_post_commit(&self.0, Some(&["../.husky"]))?;
+        Ok(())
+    }
+}
+
+pub struct CheckoutTreeBuidler<'a> {
+    repo: &'a Repository,
+    tree: &'a Tree<'a>,
+    checkout_builder: git2::build::CheckoutBuilder<'a>,
+}
+
+impl CheckoutTreeBuidler<'_> {
+    pub fn force(&mut self) -> &mut Self {
+        self.checkout_builder.force();
+        self
+    }
+
+    pub fn remove_untracked(&mut self) -> &mut Self {
+        self.checkout_builder.remove_untracked(true);
+        self
+    }
+
+    /// Checks out only `paths`, relative to the root of the working directory, taken as they
+    /// are rather than as patterns.
+    pub fn paths<P: git2::IntoCString>(&mut self, paths: impl IntoIterator<Item = P>) -> &mut Self {
+        for path in paths {
+            self.checkout_builder.path(path);
+        }
+        self.checkout_builder.disable_pathspec_match(true);
+        self
+    }
+
+    pub fn checkout(&mut self) -> Result<()> {
+        let tree: &git2::Tree = self.tree.into();
+        // what differs from the head is written, all of it if there is none yet, like in a
+        // clone
+        let head = self.repo.0.head().and_then(|head| head.peel_to_tree()).ok();
+        self.repo
+            .fetch_missing_blobs_of_changes(head.as_ref(), &[tree])?;
+        let updated = notify_updated(&mut self.checkout_builder);
+        self.repo
+            .0
+            .checkout_tree(tree.as_object(), Some(&mut self.checkout_builder))?;
+        smudge(self.repo, &updated.take());
+        Ok(())
+    }
+}
+
+pub struct CheckoutIndexBuilder<'a> {
+    repo: &'a Repository,
+    index: &'a mut git2::Index,
+    checkout_builder: git2::build::CheckoutBuilder<'a>,
+}
+
+impl CheckoutIndexBuilder<'_> {
+    pub fn force(&mut self) -> &mut Self {
+        self.checkout_builder.force();
+        self
+    }
+
+    pub fn allow_conflicts(&mut self) -> &mut Self {
+        self.checkout_builder.allow_conflicts(true);
+        self
+    }
+
+    pub fn conflict_style_merge(&mut self) -> &mut Self {
+        self.checkout_builder.conflict_style_merge(true);
+        self
+    }
+
+    pub fn checkout(&mut self) -> Result<()> {
+        let updated = notify_updated(&mut self.checkout_builder);
+        self.repo
+            .0
+            .checkout_index(Some(&mut self.index), Some(&mut self.checkout_builder))?;
+        smudge(self.repo, &updated.take());
+        Ok(())
+    }
+}
+
+// the paths the checkout `builder` is about to write, with the blobs it writes there, once
+// it's done
+fn notify_updated(
+    builder: &mut git2::build::CheckoutBuilder<'_>,
+) -> Rc<RefCell<Vec<(path::PathBuf, Oid)>>> {
+    let updated = Rc::<RefCell<Vec<(path::PathBuf, Oid)>>>::default();
+    builder
+        .notify_on(git2::CheckoutNotificationType::UPDATED)
+        .notify({
+            let updated = Rc::clone(&updated);
+            move |_, path, _, target, _| {
+                if let (Some(path), Some(target)) = (path, target) {
+                    updated
+                        .borrow_mut()
+                        .push((path.to_path_buf(), target.id().into()));
+                }
+                true
+            }
+        });
+    updated
+}
+
+// libgit2 checks out the pointers of lfs files and what filter drivers clean files to, not
+// their content, so the files a checkout wrote are smudged after it
+fn smudge(repo: &Repository, files: &[(path::PathBuf, Oid)]) {
+    let paths = files.iter().map(|(path, _)| path.clone());
+    if let Err(error) = super::lfs::smudge_paths(repo, paths) {
+        tracing::warn!(?error, "failed to smudge lfs files");
+    }
+    if let Err(error) = super::filters::smudge_paths(repo, files.to_vec()) {
+        tracing::warn!(?error, "failed to smudge files");
+    }
+}
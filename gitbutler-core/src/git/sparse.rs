@@ -0,0 +1,132 @@
+//! Cone mode sparse checkouts.
+//!
+//! In a sparse checkout only some directories of the tree are in the working directory, and
+//! libgit2 knows nothing about it: every file outside of them looks deleted. [`Cone`] reads
+//! the directories from `info/sparse-checkout` so that those files can be left out.
+//! Sparse checkouts that aren't in cone mode aren't read.
+
+use std::{collections::HashSet, path};
+
+use anyhow::{Context, Result};
+
+use super::Repository;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Cone {
+    /// Directories that are checked out with everything below them.
+    recursive: HashSet<path::PathBuf>,
+    /// Directories of which only the files directly in them are checked out, the parents of
+    /// the recursive ones.
+    parents: HashSet<path::PathBuf>,
+}
+
+impl Cone {
+    /// The sparse checkout of `repository`, if it has one in cone mode.
+    pub fn read(repository: &Repository) -> Result<Option<Self>> {
+        let config = repository.config().context("failed to get config")?;
+        if !config.get_bool("core.sparseCheckout")?.unwrap_or(false)
+            || !config.get_bool("core.sparseCheckoutCone")?.unwrap_or(false)
+        {
+            return Ok(None);
+        }
+        // the patterns are per worktree
+        let patterns_path = repository.path().join("info").join("sparse-checkout");
+        let patterns = match std::fs::read_to_string(&patterns_path) {
+            Ok(patterns) => patterns,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to read {}", patterns_path.display()))
+            }
+        };
+        Ok(Some(Self::parse(&patterns)))
+    }
+
+    // a cone is written as the directories that are included, each followed by the negation
+    // of its subdirectories if it is only a parent:
+    //
+    //   /*
+    //   !/*/
+    //   /src/
+    //   !/src/*/
+    //   /src/app/
+    fn parse(patterns: &str) -> Self {
+        let mut included = HashSet::new();
+        let mut parents = HashSet::new();
+        for pattern in patterns.lines().map(str::trim) {
+            if pattern.is_empty() || pattern.starts_with('#') {
+                continue;
+            }
+            if let Some(negated) = pattern.strip_prefix('!') {
+                if let Some(dir) = negated.strip_suffix("/*/") {
+                    parents.insert(path::PathBuf::from(unescape(dir.trim_start_matches('/'))));
+                }
+            } else if let Some(dir) = pattern.strip_suffix('/') {
+                included.insert(path::PathBuf::from(unescape(dir.trim_start_matches('/'))));
+            }
+        }
+        Self {
+            recursive: included.difference(&parents).cloned().collect(),
+            parents,
+        }
+    }
+
+    /// Whether the file at `path`, relative to the working directory, is checked out.
+    pub fn contains(&self, path: &path::Path) -> bool {
+        let Some(dir) = path.parent() else {
+            return true;
+        };
+        // files at the root always are
+        if dir.as_os_str().is_empty() || self.parents.contains(dir) {
+            return true;
+        }
+        dir.ancestors()
+            .any(|ancestor| self.recursive.contains(ancestor))
+    }
+}
+
+// git escapes the characters of directory names that are special in patterns
+fn unescape(dir: &str) -> String {
+    let mut unescaped = String::with_capacity(dir.len());
+    let mut chars = dir.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                unescaped.push(escaped);
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contains() {
+        let cone = Cone::parse("/*\n!/*/\n/src/\n!/src/*/\n/src/app/\n/docs/\n");
+        assert!(cone.contains(path::Path::new("README.md")));
+        assert!(cone.contains(path::Path::new("src/main.rs")));
+        assert!(cone.contains(path::Path::new("src/app/main.rs")));
+        assert!(cone.contains(path::Path::new("src/app/ui/view.rs")));
+        assert!(cone.contains(path::Path::new("docs/guide/index.md")));
+        assert!(!cone.contains(path::Path::new("src/lib/lib.rs")));
+        assert!(!cone.contains(path::Path::new("tests/test.rs")));
+    }
+
+    #[test]
+    fn root_only() {
+        let cone = Cone::parse("/*\n!/*/\n");
+        assert!(cone.contains(path::Path::new("Cargo.toml")));
+        assert!(!cone.contains(path::Path::new("src/main.rs")));
+    }
+
+    #[test]
+    fn escaped() {
+        let cone = Cone::parse("/*\n!/*/\n/with\\*star/\n");
+        assert!(cone.contains(path::Path::new("with*star/file")));
+    }
+}
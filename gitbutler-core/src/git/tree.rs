@@ -85,12 +85,13 @@ impl<'repo> TreeEntry<'repo> {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum FileMode {
     Blob,
     BlobExecutable,
     Link,
     Tree,
+    Commit,
 }
 
 impl From<FileMode> for git2::FileMode {
@@ -100,6 +101,32 @@ impl From<FileMode> for git2::FileMode {
             FileMode::BlobExecutable => git2::FileMode::BlobExecutable,
             FileMode::Link => git2::FileMode::Link,
             FileMode::Tree => git2::FileMode::Tree,
+            FileMode::Commit => git2::FileMode::Commit,
+        }
+    }
+}
+
+impl From<FileMode> for i32 {
+    fn from(filemode: FileMode) -> Self {
+        git2::FileMode::from(filemode).into()
+    }
+}
+
+impl TryFrom<i32> for FileMode {
+    type Error = super::Error;
+
+    fn try_from(filemode: i32) -> std::result::Result<Self, Self::Error> {
+        match filemode {
+            // an old mode git still reads, it is written as 100644
+            0o100_644 | 0o100_664 => Ok(FileMode::Blob),
+            0o100_755 => Ok(FileMode::BlobExecutable),
+            0o120_000 => Ok(FileMode::Link),
+            0o040_000 => Ok(FileMode::Tree),
+            0o160_000 => Ok(FileMode::Commit),
+            _ => Err(super::Error::Other(git2::Error::from_str(&format!(
+                "unsupported file mode {:o}",
+                filemode
+            )))),
         }
     }
 }
@@ -0,0 +1,90 @@
+//! What was read from the last tree of every repository, by its git directory, so that the
+//! tree is walked once rather than on every status. What's kept counts against the memory
+//! budget of [`memory::Cache::Trees`]: once it's over budget, the repositories cached first
+//! are forgotten first.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path,
+};
+
+use crate::memory;
+
+use super::Oid;
+
+pub(super) struct TreeCache<V> {
+    entries: HashMap<path::PathBuf, (Oid, V, usize)>,
+    // insertion order, used for eviction
+    order: VecDeque<path::PathBuf>,
+}
+
+impl<V> Default for TreeCache<V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl<V> TreeCache<V> {
+    /// What was read from `tree` in `repository`, if it's the tree last read there.
+    pub fn get(&self, repository: &path::Path, tree: Oid) -> Option<&V> {
+        self.entries
+            .get(repository)
+            .filter(|(cached_tree, _, _)| *cached_tree == tree)
+            .map(|(_, value, _)| value)
+    }
+
+    /// Takes out what was last read in `repository`, if it was read from `tree`.
+    pub fn remove(&mut self, repository: &path::Path, tree: Oid) -> Option<V> {
+        let (cached_tree, value, size) = self.entries.remove(repository)?;
+        memory::release(memory::Cache::Trees, size);
+        self.order.retain(|cached| cached != repository);
+        (cached_tree == tree).then_some(value)
+    }
+
+    /// Remembers `value` as what was read from `tree` in `repository`, `size` being about
+    /// what it takes.
+    pub fn insert(&mut self, repository: path::PathBuf, tree: Oid, value: V, size: usize) {
+        self.remove(&repository, tree);
+        let size = size + 2 * repository.as_os_str().len();
+        memory::charge(memory::Cache::Trees, size);
+        self.order.push_back(repository.clone());
+        self.entries.insert(repository, (tree, value, size));
+
+        while memory::is_over_budget(memory::Cache::Trees) {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some((_, _, size)) = self.entries.remove(&oldest) {
+                memory::release(memory::Cache::Trees, size);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembers_the_last_tree() {
+        let first = "a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2"
+            .parse::<Oid>()
+            .unwrap();
+        let second = "b1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2"
+            .parse::<Oid>()
+            .unwrap();
+        let repository = path::Path::new("/repository/.git");
+        let mut cache = TreeCache::default();
+        cache.insert(repository.to_path_buf(), first, "first", 5);
+        assert_eq!(cache.get(repository, first), Some(&"first"));
+        assert_eq!(cache.get(repository, second), None);
+
+        cache.insert(repository.to_path_buf(), second, "second", 6);
+        assert_eq!(cache.get(repository, first), None);
+        assert_eq!(cache.remove(repository, second), Some("second"));
+        assert_eq!(cache.get(repository, second), None);
+    }
+}
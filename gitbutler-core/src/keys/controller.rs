@@ -1,9 +1,8 @@
 use std::path;
 
 use anyhow::Context;
-use tauri::AppHandle;
 
-use crate::storage;
+use crate::{error::Error, storage};
 
 use super::{storage::Storage, PrivateKey};
 
@@ -28,14 +27,6 @@ impl From<&storage::Storage> for Controller {
     }
 }
 
-impl From<&AppHandle> for Controller {
-    fn from(value: &AppHandle) -> Self {
-        Self {
-            storage: Storage::from(value),
-        }
-    }
-}
-
 impl Controller {
     pub fn new(storage: &Storage) -> Self {
         Self {
@@ -55,12 +46,25 @@ impl Controller {
 }
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum GetOrCreateError {
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
 #[cfg(not(target_os = "windows"))]
+
+impl From<GetOrCreateError> for Error {
+    fn from(value: GetOrCreateError) -> Self {
+        match value {
+            GetOrCreateError::Other(error) => {
+                tracing::error!(?error, "failed to get or create key");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -1,7 +1,5 @@
 use std::path;
 
-use tauri::{AppHandle, Manager};
-
 use crate::storage;
 
 use super::PrivateKey;
@@ -27,12 +25,6 @@ impl From<&storage::Storage> for Storage {
     }
 }
 
-impl From<&AppHandle> for Storage {
-    fn from(handle: &AppHandle) -> Self {
-        Self::from(handle.state::<storage::Storage>().inner())
-    }
-}
-
 impl From<&path::PathBuf> for Storage {
     fn from(value: &path::PathBuf) -> Self {
         Self::from(&storage::Storage::from(value))
@@ -1,3 +1,5 @@
+#![feature(error_generic_member_access)]
+#![cfg_attr(target_os = "windows", feature(windows_by_handle))]
 //! # GitButler Core
 //!
 //! This crate contains the core functionality of GitButler.
@@ -7,5 +9,66 @@
 //! For more information, see the
 //! [GitButler organization](https://github.com/gitbutlerapp)
 //! or the [GitButler website](https://gitbutler.com).
+//!
+//! ## Stability
+//!
+//! The public API is what the documentation shows: the controllers of [`projects`],
+//! [`users`], [`keys`], [`settings`], [`sessions`], [`deltas`] and [`virtual_branches`],
+//! the types they take and return, [`error`], the [`events`] of the engine, [`git`], [`id`],
+//! and the [`database`] and [`storage`] handles the controllers are built from. It follows
+//! semver, so until `1.0` a breaking change to it bumps the minor version, and anything else
+//! bumps the patch version.
+//!
+//! - Error enums and [`error::Code`] are `#[non_exhaustive]`, so new variants are not
+//!   breaking. Match them with a wildcard arm.
+//! - Modules hidden from the documentation are the engine's internals. They are public
+//!   for the app and the CLI only, and can change in any release. That includes the
+//!   errors of theirs that covered error enums wrap.
 
+#[doc(hidden)]
+pub mod analytics;
+#[doc(hidden)]
+pub mod assets;
+pub mod database;
+#[doc(hidden)]
+pub mod dedup;
+pub mod deltas;
+pub mod error;
+pub mod events;
+#[doc(hidden)]
+pub mod fs;
+#[doc(hidden)]
+pub mod gb_repository;
+pub mod git;
 pub mod id;
+pub mod keys;
+#[doc(hidden)]
+pub mod lock;
+#[doc(hidden)]
+pub mod memory;
+#[doc(hidden)]
+pub mod project_repository;
+pub mod projects;
+#[doc(hidden)]
+pub mod reader;
+#[doc(hidden)]
+pub mod secret;
+pub mod sessions;
+pub mod settings;
+#[doc(hidden)]
+pub mod ssh;
+pub mod storage;
+#[doc(hidden)]
+pub mod types;
+pub mod users;
+pub mod virtual_branches;
+#[doc(hidden)]
+pub mod watcher;
+#[cfg(target_os = "windows")]
+pub(crate) mod windows;
+#[doc(hidden)]
+pub mod writer;
+
+#[cfg(any(test, feature = "test-utils"))]
+#[doc(hidden)]
+pub mod test_utils;
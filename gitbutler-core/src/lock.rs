@@ -0,0 +1,422 @@
+use std::{
+    collections::HashMap,
+    fs, io, path,
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    thread, time,
+};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+const RETRY_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+/// Called with the name of every lock file that was acquired, like `vbranches.lock`, and how
+/// long acquiring it took.
+pub type OnWait = Arc<dyn Fn(&str, time::Duration) + Send + Sync>;
+
+static ON_WAIT: Lazy<Mutex<Option<OnWait>>> = Lazy::new(Mutex::default);
+
+/// Tells `on_wait` how long the lock files of the process took to acquire, for the daemon to
+/// measure how long operations wait for each other.
+pub fn set_on_wait(on_wait: OnWait) {
+    *ON_WAIT.lock().unwrap_or_else(PoisonError::into_inner) = Some(on_wait);
+}
+
+/// How lock files are locked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// An advisory lock on the file, released by the system if the process dies.
+    Flock,
+    /// Creating the file exclusively and removing it when done. Unlike advisory locks it
+    /// works on network drives and across WSL, but a file left behind by a process that
+    /// died is only taken over once its holder stopped refreshing it for a while.
+    Exclusive,
+}
+
+impl Strategy {
+    /// The strategy for lock files in `dir`, the exclusive one if it's on a remote filesystem.
+    pub fn for_dir(dir: &path::Path) -> Self {
+        static STRATEGIES: Lazy<Mutex<HashMap<path::PathBuf, Strategy>>> =
+            Lazy::new(Mutex::default);
+        let mut strategies = STRATEGIES.lock().unwrap();
+        *strategies.entry(dir.to_path_buf()).or_insert_with(|| {
+            let kind = crate::fs::filesystem_kind(dir);
+            if kind.is_remote() {
+                tracing::info!(dir = %dir.display(), ?kind, "locking with exclusive lock files");
+                Self::Exclusive
+            } else {
+                Self::Flock
+            }
+        })
+    }
+}
+
+/// How long an exclusive lock file is held for without being refreshed. Its holder rewrites
+/// it as long as it holds it, and whoever waits for it takes it over once it didn't change
+/// for `stale_after`, as measured by the clock of the waiter, so neither the clock of the
+/// server the file is on nor the one of the holder matter.
+#[derive(Debug, Clone, Copy)]
+struct Lease {
+    refresh_every: time::Duration,
+    stale_after: time::Duration,
+}
+
+const LEASE: Lease = Lease {
+    refresh_every: time::Duration::from_secs(15),
+    stale_after: time::Duration::from_secs(2 * 60),
+};
+
+/// What an exclusive lock file has in it, for whoever finds it left behind.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Holder {
+    pid: u32,
+    host: String,
+    /// When it was last refreshed, by the clock of the holder.
+    refreshed_at_ms: u128,
+}
+
+impl Holder {
+    fn now() -> Self {
+        static HOST: Lazy<String> = Lazy::new(|| {
+            std::env::var("HOSTNAME")
+                .or_else(|_| std::env::var("COMPUTERNAME"))
+                .or_else(|_| fs::read_to_string("/etc/hostname"))
+                .map(|host| host.trim().to_string())
+                .unwrap_or_default()
+        });
+        Self {
+            pid: std::process::id(),
+            host: HOST.clone(),
+            refreshed_at_ms: time::UNIX_EPOCH.elapsed().unwrap_or_default().as_millis(),
+        }
+    }
+
+    fn write(path: &path::Path) -> Result<(), io::Error> {
+        let holder = serde_json::to_vec(&Self::now()).map_err(io::Error::other)?;
+        fs::write(path, holder)
+    }
+}
+
+/// A lock held on a lock file until it's dropped.
+#[derive(Debug)]
+pub struct FileLock(Held);
+
+#[derive(Debug)]
+enum Held {
+    Flock(fslock::LockFile),
+    Exclusive {
+        path: path::PathBuf,
+        // dropping it stops the refreshing of the file
+        stop_refreshing: Option<mpsc::Sender<()>>,
+        refreshing: Option<thread::JoinHandle<()>>,
+    },
+}
+
+impl FileLock {
+    /// Blocks until the lock on `path` is acquired.
+    pub fn acquire(path: &path::Path, strategy: Strategy) -> Result<Self, io::Error> {
+        let started_at = time::Instant::now();
+        let lock = Self::lock(path, strategy, LEASE)?;
+        let on_wait = ON_WAIT
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Some(on_wait) = on_wait {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            on_wait(&name, started_at.elapsed());
+        }
+        Ok(lock)
+    }
+
+    fn lock(path: &path::Path, strategy: Strategy, lease: Lease) -> Result<Self, io::Error> {
+        match strategy {
+            Strategy::Flock => {
+                let mut file = fslock::LockFile::open(path)?;
+                file.lock()?;
+                Ok(Self(Held::Flock(file)))
+            }
+            Strategy::Exclusive => {
+                // the holder last seen in the file, and since when by the local clock
+                let mut seen: Option<(Vec<u8>, time::Instant)> = None;
+                loop {
+                    match fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(path)
+                    {
+                        Ok(_) => return Ok(Self::hold(path, lease)),
+                        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                            // empty while its holder is still writing it
+                            let holder = fs::read(path).unwrap_or_default();
+                            match &seen {
+                                Some((seen_holder, since)) if *seen_holder == holder => {
+                                    if since.elapsed() > lease.stale_after {
+                                        tracing::warn!(
+                                            path = %path.display(),
+                                            holder = %String::from_utf8_lossy(&holder),
+                                            "taking over stale lock"
+                                        );
+                                        match fs::remove_file(path) {
+                                            Err(error)
+                                                if error.kind() != io::ErrorKind::NotFound =>
+                                            {
+                                                return Err(error)
+                                            }
+                                            _ => {
+                                                seen = None;
+                                                continue;
+                                            }
+                                        }
+                                    }
+                                }
+                                _ => seen = Some((holder, time::Instant::now())),
+                            }
+                            thread::sleep(RETRY_INTERVAL);
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+            }
+        }
+    }
+
+    // holds the exclusive lock file `path`, it was just created
+    fn hold(path: &path::Path, lease: Lease) -> Self {
+        if let Err(error) = Holder::write(path) {
+            tracing::warn!(?error, path = %path.display(), "failed to write lock holder");
+        }
+        let (stop_refreshing, stopped) = mpsc::channel::<()>();
+        let refreshing = thread::Builder::new()
+            .name("lock-refresh".to_string())
+            .spawn({
+                let path = path.to_path_buf();
+                move || {
+                    while let Err(mpsc::RecvTimeoutError::Timeout) =
+                        stopped.recv_timeout(lease.refresh_every)
+                    {
+                        if let Err(error) = Holder::write(&path) {
+                            tracing::warn!(?error, path = %path.display(), "failed to refresh lock");
+                        }
+                    }
+                }
+            });
+        let refreshing = match refreshing {
+            Ok(refreshing) => Some(refreshing),
+            Err(error) => {
+                tracing::warn!(?error, path = %path.display(), "failed to refresh lock");
+                None
+            }
+        };
+        Self(Held::Exclusive {
+            path: path.to_path_buf(),
+            stop_refreshing: Some(stop_refreshing),
+            refreshing,
+        })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // advisory locks are released when their file is closed
+        if let Held::Exclusive {
+            path,
+            stop_refreshing,
+            refreshing,
+        } = &mut self.0
+        {
+            // not to write the file again once it's removed
+            drop(stop_refreshing.take());
+            if let Some(refreshing) = refreshing.take() {
+                let _ = refreshing.join();
+            }
+            if let Err(error) = fs::remove_file(path.as_path()) {
+                tracing::warn!(?error, path = %path.display(), "failed to remove lock file");
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dir {
+    inner: Arc<Inner>,
+}
+
+impl Dir {
+    pub fn new<P: AsRef<std::path::Path>>(path: P) -> Result<Self, std::io::Error> {
+        Inner::new(path).map(Arc::new).map(|inner| Self { inner })
+    }
+
+    pub fn batch<R>(
+        &self,
+        action: impl FnOnce(&std::path::Path) -> R,
+    ) -> Result<R, std::io::Error> {
+        self.inner.batch(action)
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: std::path::PathBuf,
+    lock_path: std::path::PathBuf,
+    strategy: Strategy,
+    // lock files aren't reentrant, instances share them one at a time
+    guard: Mutex<()>,
+}
+
+impl Inner {
+    fn new<P: AsRef<std::path::Path>>(path: P) -> Result<Self, std::io::Error> {
+        let path = path.as_ref().to_path_buf();
+        if !path.exists() {
+            std::fs::create_dir_all(&path)?;
+        } else if !path.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} is not a directory", path.display()),
+            ));
+        }
+        Ok(Self {
+            lock_path: path.with_extension("lock"),
+            strategy: Strategy::for_dir(&path),
+            guard: Mutex::new(()),
+            path,
+        })
+    }
+
+    fn batch<R>(&self, action: impl FnOnce(&std::path::Path) -> R) -> Result<R, std::io::Error> {
+        let _guard = self.guard.lock().unwrap();
+        let _lock = FileLock::acquire(&self.lock_path, self.strategy)?;
+        Ok(action(&self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::temp_dir;
+
+    #[tokio::test]
+    async fn test_lock_same_instance() {
+        let dir_path = temp_dir();
+        std::fs::write(dir_path.join("file.txt"), "").unwrap();
+        let dir = Dir::new(&dir_path).unwrap();
+
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+
+        // spawn a task that will signal right after aquireing the lock
+        let _ = tokio::spawn({
+            let dir = dir.clone();
+            async move {
+                dir.batch(|root| {
+                    tx.send(()).unwrap();
+                    assert_eq!(
+                        std::fs::read_to_string(root.join("file.txt")).unwrap(),
+                        String::new()
+                    );
+                    std::fs::write(root.join("file.txt"), "1")
+                })
+            }
+        })
+        .await
+        .unwrap();
+
+        // then we wait until the lock is aquired
+        rx.recv().unwrap();
+
+        // and immidiately try to lock again
+        dir.batch(|root| {
+            assert_eq!(std::fs::read_to_string(root.join("file.txt")).unwrap(), "1");
+            std::fs::write(root.join("file.txt"), "2")
+        })
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir_path.join("file.txt")).unwrap(),
+            "2"
+        );
+    }
+
+    #[test]
+    fn test_exclusive_lock() {
+        let dir_path = temp_dir();
+        let lock_path = dir_path.join("exclusive.lock");
+
+        let lock = FileLock::acquire(&lock_path, Strategy::Exclusive).unwrap();
+        assert!(lock_path.exists());
+        drop(lock);
+        assert!(!lock_path.exists());
+
+        // a lock file left behind by a process that died is taken over once it's stale, by
+        // how long it didn't change rather than by its time
+        std::fs::write(&lock_path, "0").unwrap();
+        let lease = Lease {
+            refresh_every: time::Duration::from_millis(20),
+            stale_after: time::Duration::from_millis(200),
+        };
+        let lock = FileLock::lock(&lock_path, Strategy::Exclusive, lease).unwrap();
+        let holder: Holder = serde_json::from_slice(&std::fs::read(&lock_path).unwrap()).unwrap();
+        assert_eq!(holder.pid, std::process::id());
+
+        // which one that is held never is, it's refreshed
+        let waiting = thread::spawn({
+            let lock_path = lock_path.clone();
+            move || FileLock::lock(&lock_path, Strategy::Exclusive, lease).unwrap()
+        });
+        thread::sleep(lease.stale_after * 3);
+        assert!(!waiting.is_finished());
+        let refreshed: Holder =
+            serde_json::from_slice(&std::fs::read(&lock_path).unwrap()).unwrap();
+        assert!(refreshed.refreshed_at_ms > holder.refreshed_at_ms);
+        drop(lock);
+        drop(waiting.join().unwrap());
+        assert!(!lock_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_lock_different_instances() {
+        let dir_path = temp_dir();
+        std::fs::write(dir_path.join("file.txt"), "").unwrap();
+
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+
+        // spawn a task that will signal right after aquireing the lock
+        let _ = tokio::spawn({
+            let dir_path = dir_path.clone();
+            async move {
+                // one dir instance is created on a separate thread
+                let dir = Dir::new(&dir_path).unwrap();
+                dir.batch(|root| {
+                    tx.send(()).unwrap();
+                    assert_eq!(
+                        std::fs::read_to_string(root.join("file.txt")).unwrap(),
+                        String::new()
+                    );
+                    std::fs::write(root.join("file.txt"), "1")
+                })
+            }
+        })
+        .await
+        .unwrap();
+
+        // another dir instance is created on the main thread
+        let dir = Dir::new(&dir_path).unwrap();
+
+        // then we wait until the lock is aquired
+        rx.recv().unwrap();
+
+        // and immidiately try to lock again
+        dir.batch(|root| {
+            assert_eq!(std::fs::read_to_string(root.join("file.txt")).unwrap(), "1");
+            std::fs::write(root.join("file.txt"), "2")
+        })
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir_path.join("file.txt")).unwrap(),
+            "2"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! Process wide accounting of memory held by in-process caches.
+//!
+//! Every cache charges the accountant for what it keeps and releases what it drops.
+//! Once a cache goes over its budget it is expected to evict its oldest entries until
+//! it fits again. Budgets are read from the app settings at startup.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+const MB: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cache {
+    MergeBases,
+    Repositories,
+    CleanedContents,
+    Trees,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Budgets {
+    pub merge_bases_mb: usize,
+    pub repositories_mb: usize,
+    pub cleaned_contents_mb: usize,
+    pub trees_mb: usize,
+}
+
+impl Default for Budgets {
+    fn default() -> Self {
+        Self {
+            merge_bases_mb: 4,
+            repositories_mb: 256,
+            cleaned_contents_mb: 4,
+            trees_mb: 16,
+        }
+    }
+}
+
+impl Budgets {
+    fn bytes(&self, cache: Cache) -> usize {
+        let mb = match cache {
+            Cache::MergeBases => self.merge_bases_mb,
+            Cache::Repositories => self.repositories_mb,
+            Cache::CleanedContents => self.cleaned_contents_mb,
+            Cache::Trees => self.trees_mb,
+        };
+        mb.saturating_mul(MB)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Accountant {
+    budgets: Budgets,
+    usage: HashMap<Cache, usize>,
+}
+
+impl Accountant {
+    fn charge(&mut self, cache: Cache, bytes: usize) {
+        let usage = self.usage.entry(cache).or_default();
+        *usage = usage.saturating_add(bytes);
+    }
+
+    fn release(&mut self, cache: Cache, bytes: usize) {
+        let usage = self.usage.entry(cache).or_default();
+        *usage = usage.saturating_sub(bytes);
+    }
+
+    fn usage(&self, cache: Cache) -> usize {
+        self.usage.get(&cache).copied().unwrap_or_default()
+    }
+
+    fn is_over_budget(&self, cache: Cache) -> bool {
+        self.usage(cache) > self.budgets.bytes(cache)
+    }
+}
+
+lazy_static! {
+    static ref ACCOUNTANT: Mutex<Accountant> = Mutex::new(Accountant::default());
+}
+
+pub fn set_budgets(budgets: Budgets) {
+    tracing::info!(?budgets, "setting cache memory budgets");
+    ACCOUNTANT.lock().unwrap().budgets = budgets;
+}
+
+pub fn budgets() -> Budgets {
+    ACCOUNTANT.lock().unwrap().budgets
+}
+
+/// Records that `cache` now holds `bytes` more.
+pub fn charge(cache: Cache, bytes: usize) {
+    ACCOUNTANT.lock().unwrap().charge(cache, bytes);
+}
+
+/// Records that `cache` dropped `bytes`.
+pub fn release(cache: Cache, bytes: usize) {
+    ACCOUNTANT.lock().unwrap().release(cache, bytes);
+}
+
+/// Returns how many bytes `cache` currently holds.
+pub fn usage(cache: Cache) -> usize {
+    ACCOUNTANT.lock().unwrap().usage(cache)
+}
+
+pub fn is_over_budget(cache: Cache) -> bool {
+    ACCOUNTANT.lock().unwrap().is_over_budget(cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let mut accountant = Accountant {
+            budgets: Budgets {
+                merge_bases_mb: 1,
+                repositories_mb: 0,
+                cleaned_contents_mb: 0,
+                trees_mb: 0,
+            },
+            ..Default::default()
+        };
+
+        accountant.charge(Cache::MergeBases, MB);
+        assert!(!accountant.is_over_budget(Cache::MergeBases));
+        accountant.charge(Cache::MergeBases, 1);
+        assert!(accountant.is_over_budget(Cache::MergeBases));
+        accountant.release(Cache::MergeBases, 2);
+        assert_eq!(accountant.usage(Cache::MergeBases), MB - 1);
+
+        accountant.release(Cache::Repositories, 1);
+        assert_eq!(accountant.usage(Cache::Repositories), 0);
+        accountant.charge(Cache::Repositories, 1);
+        assert!(accountant.is_over_budget(Cache::Repositories));
+    }
+
+    #[test]
+    fn test_budgets_from_settings() {
+        let budgets: Budgets = serde_json::from_str(r#"{"repositoriesMb": 64}"#).unwrap();
+        assert_eq!(
+            budgets,
+            Budgets {
+                repositories_mb: 64,
+                ..Budgets::default()
+            }
+        );
+    }
+}
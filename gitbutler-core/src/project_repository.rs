@@ -0,0 +1,23 @@
+mod config;
+pub mod conflicts;
+pub mod hooks;
+mod merge_base_cache;
+mod operation;
+pub mod pool;
+mod progress;
+mod repository;
+mod retry;
+
+pub use config::Config;
+pub use operation::{
+    external_operation, finish_external_operation, ExternalOperation, ExternalOperationKind,
+    FinishOperationError, OperationAction,
+};
+pub use progress::{FetchProgress, FetchProgressSnapshot};
+pub use repository::{
+    Cancellation, LogUntil, OpenError, RemoteError, Repository, ShallowHistoryError, NOTES_REFS,
+    REMOTE_NOTES_REFS,
+};
+pub use retry::{is_transient, NetworkOperation, OnRetry, Retry};
+
+pub mod signatures;
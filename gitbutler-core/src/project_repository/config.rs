@@ -0,0 +1,218 @@
+use crate::git;
+
+pub struct Config<'a> {
+    git_repository: &'a git::Repository,
+}
+
+impl<'a> From<&'a git::Repository> for Config<'a> {
+    fn from(value: &'a git::Repository) -> Self {
+        Self {
+            git_repository: value,
+        }
+    }
+}
+
+impl Config<'_> {
+    /// Whether commits are signed, if the git config says, else it's the app setting.
+    pub fn sign_commits(&self) -> Result<Option<bool>, git::Error> {
+        Ok(self
+            .git_repository
+            .config()?
+            .get_bool("gitbutler.signCommits")
+            .unwrap_or(None))
+    }
+
+    pub fn set_sign_commits(&self, sign_commits: bool) -> Result<(), git::Error> {
+        self.git_repository
+            .config()?
+            .set_bool("gitbutler.signCommits", sign_commits)
+    }
+
+    pub fn unset_sign_commits(&self) -> Result<(), git::Error> {
+        self.git_repository
+            .config()?
+            .remove("gitbutler.signCommits")
+    }
+
+    /// The remote new branches are pushed to, the one of the target if it's not set.
+    pub fn push_remote(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_string("gitbutler.pushRemote")
+    }
+
+    pub fn set_push_remote(&self, remote: &str) -> Result<(), git::Error> {
+        self.git_repository
+            .config()?
+            .set_str("gitbutler.pushRemote", remote)
+    }
+
+    pub fn unset_push_remote(&self) -> Result<(), git::Error> {
+        self.git_repository.config()?.remove("gitbutler.pushRemote")
+    }
+
+    /// Whether what is committed and pushed is scanned for secrets, it is by default.
+    pub fn scan_secrets(&self) -> Result<bool, git::Error> {
+        let scan_secrets = self
+            .git_repository
+            .config()?
+            .get_bool("gitbutler.scanSecrets")
+            .unwrap_or(Some(true))
+            .unwrap_or(true);
+        Ok(scan_secrets)
+    }
+
+    /// Whether commits are refused while they add conflict markers, they are by default.
+    pub fn check_conflict_markers(&self) -> Result<bool, git::Error> {
+        let check_conflict_markers = self
+            .git_repository
+            .config()?
+            .get_bool("gitbutler.checkConflictMarkers")
+            .unwrap_or(Some(true))
+            .unwrap_or(true);
+        Ok(check_conflict_markers)
+    }
+
+    /// The path of the file of secret rules, relative to the root of the repository.
+    pub fn secret_rules(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_string("gitbutler.secretRules")
+    }
+
+    /// What committing large files does: `warn`, the default, `block` or `off`.
+    pub fn large_files(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_string("gitbutler.largeFiles")
+    }
+
+    /// The size, in bytes, above which a file is large.
+    pub fn max_file_size(&self) -> Result<Option<i64>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_i64("gitbutler.maxFileSize")
+    }
+
+    /// The size, in bytes, above which a binary file that isn't tracked by LFS is large.
+    pub fn max_binary_file_size(&self) -> Result<Option<i64>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_i64("gitbutler.maxBinaryFileSize")
+    }
+
+    /// How many days discarded changes are kept in the trash for.
+    pub fn trash_retention_days(&self) -> Result<Option<i64>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_i64("gitbutler.trashRetentionDays")
+    }
+
+    /// How many times a fetch or a push that failed for a network error is tried again.
+    pub fn network_retries(&self) -> Result<Option<i64>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_i64("gitbutler.networkRetries")
+    }
+
+    /// The command a branch has to pass before it's pushed, like `cargo test --quick`.
+    pub fn pre_push_command(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_string("gitbutler.prePushCommand")
+    }
+
+    /// How many seconds the pre-push hook and command may run before they're stopped and
+    /// fail.
+    pub fn pre_push_timeout(&self) -> Result<Option<i64>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_i64("gitbutler.prePushTimeout")
+    }
+
+    /// How many seconds a GitButler hook may run before it's stopped and fails.
+    pub fn hooks_timeout(&self) -> Result<Option<i64>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_i64("gitbutler.hooksTimeout")
+    }
+
+    /// The regex the names branches are pushed with have to match.
+    pub fn branch_name_pattern(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_string("gitbutler.branchNamePattern")
+    }
+
+    /// Whether the signatures of the commits the target gets from upstream are verified.
+    pub fn verify_upstream_signatures(&self) -> Result<bool, git::Error> {
+        let verify = self
+            .git_repository
+            .config()?
+            .get_bool("gitbutler.verifyUpstreamSignatures")
+            .unwrap_or(Some(false))
+            .unwrap_or(false);
+        Ok(verify)
+    }
+
+    /// The path of the file of trusted signers in the tree of the base, relative to the root of
+    /// the repository.
+    pub fn trusted_signers(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_string("gitbutler.trustedSigners")
+    }
+
+    /// The name of the branch the workspace is checked out on, like `gitbutler/integration`.
+    /// Each worktree has its own, `gitbutler.<worktree>.workspaceBranch` in linked ones.
+    pub fn workspace_branch(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository
+            .config()?
+            .get_string(&self.workspace_branch_key())
+    }
+
+    pub fn set_workspace_branch(&self, branch: &str) -> Result<(), git::Error> {
+        self.git_repository
+            .config()?
+            .set_str(&self.workspace_branch_key(), branch)
+    }
+
+    fn workspace_branch_key(&self) -> String {
+        match self.git_repository.worktree_name() {
+            Some(worktree) => format!("gitbutler.{worktree}.workspaceBranch"),
+            None => "gitbutler.workspaceBranch".to_string(),
+        }
+    }
+
+    pub fn write_commit_graph(&self) -> Result<bool, git::Error> {
+        let write_commit_graph = self
+            .git_repository
+            .config()?
+            .get_bool("gitbutler.writeCommitGraph")
+            .unwrap_or(Some(true))
+            .unwrap_or(true);
+        Ok(write_commit_graph)
+    }
+
+    pub fn user_real_comitter(&self) -> Result<bool, git::Error> {
+        let no_comitter = self
+            .git_repository
+            .config()?
+            .get_string("gitbutler.utmostDiscretion")
+            .unwrap_or(Some("0".to_string()))
+            .unwrap_or("0".to_string());
+        Ok(no_comitter == "1")
+    }
+
+    pub fn hooks_path(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository.config()?.get_string("core.hooksPath")
+    }
+
+    pub fn user_name(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository.config()?.get_string("user.name")
+    }
+
+    pub fn user_email(&self) -> Result<Option<String>, git::Error> {
+        self.git_repository.config()?.get_string("user.email")
+    }
+}
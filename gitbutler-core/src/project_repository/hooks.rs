@@ -0,0 +1,378 @@
+//! GitButler hooks: user executables run at points of the virtual branch lifecycle.
+//!
+//! They live next to the git hooks of the repository (in `core.hooksPath`, `.git/hooks` by
+//! default) and are named after the point they run at, e.g. `gitbutler-pre-push`. Each one
+//! receives a JSON description of the event on stdin. A `pre-` hook exiting with a non-zero
+//! status aborts the operation; a failing `post-` hook is only logged.
+//!
+//! A hook that runs for longer than the `gitbutler.hooksTimeout` git config, in seconds,
+//! [`DEFAULT_TIMEOUT`] if it isn't set, is killed along with what it started, and fails.
+
+use std::{
+    io::{Read, Write},
+    path,
+    process::{Child, ChildStdin, Command, Stdio},
+    thread, time,
+};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::{git, projects::ProjectId, virtual_branches::BranchId};
+
+use super::Repository;
+
+pub const DEFAULT_TIMEOUT: time::Duration = time::Duration::from_secs(60);
+// how often a running hook is looked at, to tell whether it exited or timed out
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(50);
+
+#[derive(Debug, Serialize)]
+#[serde(
+    tag = "hook",
+    rename_all = "kebab-case",
+    rename_all_fields = "camelCase"
+)]
+pub enum Event {
+    PostBranchCreate {
+        project_id: ProjectId,
+        branch: Branch,
+    },
+    PrePush {
+        project_id: ProjectId,
+        branch: Branch,
+        remote_branch: git::RemoteRefname,
+        force: bool,
+    },
+    PostIntegration {
+        project_id: ProjectId,
+        base_branch: git::RemoteRefname,
+        previous_sha: git::Oid,
+        sha: git::Oid,
+        /// Branches whose changes all made it into the base branch.
+        integrated_branches: Vec<Branch>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Branch {
+    pub id: BranchId,
+    pub name: String,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::PostBranchCreate { .. } => "post-branch-create",
+            Event::PrePush { .. } => "pre-push",
+            Event::PostIntegration { .. } => "post-integration",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{hook} hook rejected: {output}")]
+    Rejected { hook: &'static str, output: String },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// The command running the hook for `event`, and the payload to write to its stdin, if the
+/// user installed one.
+pub fn command(
+    project_repository: &Repository,
+    event: &Event,
+) -> Result<Option<(Command, Vec<u8>)>, Error> {
+    let Some(hook_path) = find(project_repository, event.name())? else {
+        return Ok(None);
+    };
+    let payload = serde_json::to_vec(event).context("failed to serialize hook payload")?;
+    let mut command = Command::new(hook_path);
+    command.current_dir(project_repository.path());
+    Ok(Some((command, payload)))
+}
+
+/// Writes `payload` to the stdin of a hook.
+pub fn write_payload(stdin: &mut ChildStdin, payload: &[u8]) -> anyhow::Result<()> {
+    match stdin.write_all(payload) {
+        // the hook is free to not read its input at all
+        Err(error) if error.kind() != std::io::ErrorKind::BrokenPipe => {
+            Err(anyhow::Error::from(error).context("failed to write hook payload"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Makes `command` start a process group of its own, for [`kill_group`] to stop what it
+/// starts along with it.
+pub fn own_group(command: &mut Command) -> &mut Command {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        command.process_group(0);
+    }
+    command
+}
+
+/// Kills `child`, started with [`own_group`], and the processes it started, then waits for it.
+pub fn kill_group(child: &mut Child) -> anyhow::Result<()> {
+    let pid = child.id().to_string();
+    #[cfg(unix)]
+    let killed = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{pid}")])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    #[cfg(windows)]
+    let killed = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if !killed.is_ok_and(|status| status.success()) {
+        // the group is gone already, or its kill isn't available
+        child.kill().context("failed to kill")?;
+    }
+    child.wait().context("failed to wait")?;
+    Ok(())
+}
+
+/// Runs the hook for `event`, if the user installed one, and fails if it did.
+pub fn run(project_repository: &Repository, event: &Event) -> Result<(), Error> {
+    let Some((mut command, payload)) = command(project_repository, event)? else {
+        return Ok(());
+    };
+    let timeout = project_repository
+        .config()
+        .hooks_timeout()
+        .context("failed to read hooks timeout config")?
+        .and_then(|seconds| u64::try_from(seconds).ok())
+        .map_or(DEFAULT_TIMEOUT, time::Duration::from_secs);
+
+    let mut child = own_group(&mut command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to spawn the {} hook", event.name()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        write_payload(&mut stdin, &payload)?;
+    }
+    // read while the hook runs, which blocks once it wrote more than a pipe holds
+    let stdout = child
+        .stdout
+        .take()
+        .map(|pipe| thread::spawn(|| read_all(pipe)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|pipe| thread::spawn(|| read_all(pipe)));
+
+    let started_at = time::Instant::now();
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .with_context(|| format!("failed to wait for the {} hook", event.name()))?
+        {
+            break Some(status);
+        }
+        if started_at.elapsed() >= timeout {
+            kill_group(&mut child)
+                .with_context(|| format!("failed to stop the {} hook", event.name()))?;
+            break None;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    // the pipes are closed once the group is gone, what it started included
+    let [stdout, stderr] =
+        [stdout, stderr].map(|reader| reader.and_then(|reader| reader.join().ok()));
+
+    if status.is_some_and(|status| status.success()) {
+        return Ok(());
+    }
+    let mut output = String::from_utf8_lossy(&stderr.unwrap_or_default()).into_owned();
+    output.push_str(&String::from_utf8_lossy(&stdout.unwrap_or_default()));
+    let output = match status {
+        Some(_) => output.trim().to_string(),
+        None => format!("{}\ntimed out after {}s", output.trim(), timeout.as_secs())
+            .trim_start()
+            .to_string(),
+    };
+    Err(Error::Rejected {
+        hook: event.name(),
+        output,
+    })
+}
+
+fn read_all(mut pipe: impl Read) -> Vec<u8> {
+    let mut content = vec![];
+    // what was read until the pipe broke is all there is
+    let _ = pipe.read_to_end(&mut content);
+    content
+}
+
+/// Same as `run`, for hooks that can't stop anything anymore.
+pub fn notify(project_repository: &Repository, event: &Event) {
+    if let Err(error) = run(project_repository, event) {
+        tracing::warn!(project_id = %project_repository.project().id, ?error, "hook failed");
+    }
+}
+
+fn find(project_repository: &Repository, name: &str) -> Result<Option<path::PathBuf>, Error> {
+    let hooks_dir = match project_repository
+        .config()
+        .hooks_path()
+        .context("failed to read core.hooksPath")?
+    {
+        // relative paths are relative to the root of the working directory, like git does
+        Some(hooks_path) => project_repository.path().join(hooks_path),
+        None => project_repository.git_repository.common_dir().join("hooks"),
+    };
+    let hook_path = hooks_dir.join(format!("gitbutler-{}", name));
+    Ok(is_executable(&hook_path).then_some(hook_path))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &path::Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use crate::test_utils::{Case, Suite};
+
+    use super::*;
+
+    fn install(project_repository: &Repository, name: &str, script: &str) {
+        let hooks_dir = project_repository.git_repository.path().join("hooks");
+        fs::create_dir_all(&hooks_dir).unwrap();
+        let hook_path = hooks_dir.join(format!("gitbutler-{}", name));
+        fs::write(&hook_path, script).unwrap();
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    fn post_branch_create(project_repository: &Repository) -> Event {
+        Event::PostBranchCreate {
+            project_id: project_repository.project().id,
+            branch: Branch {
+                id: BranchId::generate(),
+                name: "my branch".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_missing_hook() {
+        let suite = Suite::default();
+        let Case {
+            project_repository, ..
+        } = suite.new_case();
+
+        assert!(run(
+            &project_repository,
+            &post_branch_create(&project_repository)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_payload_on_stdin() {
+        let suite = Suite::default();
+        let Case {
+            project_repository, ..
+        } = suite.new_case();
+        install(
+            &project_repository,
+            "post-branch-create",
+            "#!/bin/sh\ncat > payload.json\n",
+        );
+
+        run(
+            &project_repository,
+            &post_branch_create(&project_repository),
+        )
+        .unwrap();
+
+        let payload: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(project_repository.path().join("payload.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(payload["hook"], "post-branch-create");
+        assert_eq!(payload["branch"]["name"], "my branch");
+        assert_eq!(
+            payload["projectId"],
+            project_repository.project().id.to_string()
+        );
+    }
+
+    #[test]
+    fn test_timed_out() {
+        let suite = Suite::default();
+        let Case {
+            project_repository, ..
+        } = suite.new_case();
+        install(
+            &project_repository,
+            "post-branch-create",
+            "#!/bin/sh\necho 'waiting'\nsleep 30\n",
+        );
+        project_repository
+            .git_repository
+            .config()
+            .unwrap()
+            .set_str("gitbutler.hooksTimeout", "1")
+            .unwrap();
+
+        let started_at = time::Instant::now();
+        let error = run(
+            &project_repository,
+            &post_branch_create(&project_repository),
+        )
+        .unwrap_err();
+        // the sleep it started is killed with it, nothing waits for it
+        assert!(started_at.elapsed() < time::Duration::from_secs(10));
+        let Error::Rejected { output, .. } = error else {
+            unreachable!("not rejected: {error:?}")
+        };
+        assert_eq!(output, "waiting\ntimed out after 1s");
+    }
+
+    #[test]
+    fn test_rejected() {
+        let suite = Suite::default();
+        let Case {
+            project_repository, ..
+        } = suite.new_case();
+        install(
+            &project_repository,
+            "post-branch-create",
+            "#!/bin/sh\necho 'no ticket id'\nexit 1\n",
+        );
+
+        let error = run(
+            &project_repository,
+            &post_branch_create(&project_repository),
+        )
+        .unwrap_err();
+        let Error::Rejected { hook, output } = error else {
+            unreachable!("not rejected: {error:?}")
+        };
+        assert_eq!(hook, "post-branch-create");
+        assert_eq!(output, "no ticket id");
+    }
+}
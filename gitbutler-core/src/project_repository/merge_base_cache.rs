@@ -0,0 +1,128 @@
+//! Process wide cache of merge bases between the default target and branch heads.
+//!
+//! Every status run used to compute the same merge base, and read the same tree,
+//! for every branch. Merge bases of two given commits never change, so entries only
+//! need to be dropped to keep the cache from growing: this happens whenever a project
+//! is fetched or its target changes, or when the cache goes over its memory budget, in
+//! which case the oldest entries are evicted first.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+
+use crate::{git, memory, projects::ProjectId};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub merge_base: git::Oid,
+    pub tree: git::Oid,
+}
+
+type Key = (git::Oid, git::Oid);
+
+// what a single entry costs, both in the map and in the eviction queue
+const ENTRY_SIZE: usize = 2 * (mem::size_of::<ProjectId>() + mem::size_of::<Key>())
+    + mem::size_of::<Entry>()
+    + 2 * mem::size_of::<u64>();
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<ProjectId, HashMap<Key, (Entry, u64)>>,
+    // insertion order, used for eviction. sequence numbers tell apart entries
+    // that have been invalidated and inserted again.
+    order: VecDeque<(ProjectId, Key, u64)>,
+    next_seq: u64,
+}
+
+impl Cache {
+    fn evict_oldest(&mut self) -> bool {
+        let Some((project_id, key, seq)) = self.order.pop_front() else {
+            return false;
+        };
+        if let Some(entries) = self.entries.get_mut(&project_id) {
+            if entries
+                .get(&key)
+                .is_some_and(|(_, entry_seq)| *entry_seq == seq)
+            {
+                entries.remove(&key);
+                memory::release(memory::Cache::MergeBases, ENTRY_SIZE);
+            }
+        }
+        true
+    }
+}
+
+lazy_static! {
+    static ref CACHE: Mutex<Cache> = Mutex::new(Cache::default());
+}
+
+pub fn get(project_id: &ProjectId, target: git::Oid, head: git::Oid) -> Option<Entry> {
+    CACHE
+        .lock()
+        .unwrap()
+        .entries
+        .get(project_id)
+        .and_then(|entries| entries.get(&(target, head)))
+        .map(|(entry, _)| *entry)
+}
+
+pub fn insert(project_id: &ProjectId, target: git::Oid, head: git::Oid, entry: Entry) {
+    let mut cache = CACHE.lock().unwrap();
+    let seq = cache.next_seq;
+    cache.next_seq += 1;
+    if cache
+        .entries
+        .entry(*project_id)
+        .or_default()
+        .insert((target, head), (entry, seq))
+        .is_none()
+    {
+        memory::charge(memory::Cache::MergeBases, ENTRY_SIZE);
+    }
+    cache.order.push_back((*project_id, (target, head), seq));
+
+    while memory::is_over_budget(memory::Cache::MergeBases) {
+        if !cache.evict_oldest() {
+            break;
+        }
+    }
+}
+
+pub fn invalidate(project_id: &ProjectId) {
+    let mut cache = CACHE.lock().unwrap();
+    if let Some(entries) = cache.entries.remove(project_id) {
+        memory::release(memory::Cache::MergeBases, entries.len() * ENTRY_SIZE);
+    }
+    cache.order.retain(|(id, _, _)| id != project_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalidate() {
+        let project_id = ProjectId::generate();
+        let target = "a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2"
+            .parse::<git::Oid>()
+            .unwrap();
+        let head = "f6e5d4c3b2a1f6e5d4c3b2a1f6e5d4c3b2a1f6e5"
+            .parse::<git::Oid>()
+            .unwrap();
+        let entry = Entry {
+            merge_base: target,
+            tree: head,
+        };
+
+        insert(&project_id, target, head, entry);
+        assert_eq!(get(&project_id, target, head).unwrap().tree, head);
+        assert!(get(&project_id, head, target).is_none());
+
+        invalidate(&project_id);
+        assert!(get(&project_id, target, head).is_none());
+    }
+}
@@ -0,0 +1,169 @@
+//! Merges, rebases, cherry-picks, reverts and bisects started outside of GitButler, with `git`
+//! or another client. Until they are finished or aborted, `HEAD`, the index and the working
+//! directory are theirs, and GitButler changing them would corrupt both its state and theirs.
+
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use bstr::ByteSlice;
+use serde::{Deserialize, Serialize};
+
+use crate::git;
+
+use super::Repository;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExternalOperationKind {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+    /// `git am`, applying patches from a mailbox.
+    ApplyMailbox,
+    /// Either `git am` or a rebase with the apply backend, which git can't tell apart once
+    /// neither left the file saying which one it is.
+    ApplyMailboxOrRebase,
+}
+
+impl ExternalOperationKind {
+    // the git command that continues and aborts it, if it is known
+    fn command(self) -> Option<&'static str> {
+        match self {
+            Self::Merge => Some("merge"),
+            Self::Rebase => Some("rebase"),
+            Self::CherryPick => Some("cherry-pick"),
+            Self::Revert => Some("revert"),
+            Self::Bisect => Some("bisect"),
+            Self::ApplyMailbox => Some("am"),
+            Self::ApplyMailboxOrRebase => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalOperation {
+    pub kind: ExternalOperationKind,
+    /// Whether it can be continued, a bisect can only be ended. What's left of an `am` or a
+    /// rebase that git can't tell apart can neither be continued nor aborted from here.
+    pub can_continue: bool,
+    /// The files with conflicts that are left to resolve before it can be continued.
+    pub conflicted_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OperationAction {
+    Continue,
+    Abort,
+}
+
+/// The operation started outside of GitButler that the repository is in the middle of.
+pub fn external_operation(repository: &Repository) -> Result<Option<ExternalOperation>> {
+    let kind = match repository.git_repository.state() {
+        git2::RepositoryState::Clean => return Ok(None),
+        git2::RepositoryState::Merge => ExternalOperationKind::Merge,
+        git2::RepositoryState::Rebase
+        | git2::RepositoryState::RebaseInteractive
+        | git2::RepositoryState::RebaseMerge => ExternalOperationKind::Rebase,
+        git2::RepositoryState::CherryPick | git2::RepositoryState::CherryPickSequence => {
+            ExternalOperationKind::CherryPick
+        }
+        git2::RepositoryState::Revert | git2::RepositoryState::RevertSequence => {
+            ExternalOperationKind::Revert
+        }
+        git2::RepositoryState::Bisect => ExternalOperationKind::Bisect,
+        git2::RepositoryState::ApplyMailbox => ExternalOperationKind::ApplyMailbox,
+        git2::RepositoryState::ApplyMailboxOrRebase => ExternalOperationKind::ApplyMailboxOrRebase,
+    };
+
+    let index = repository
+        .git_repository
+        .index()
+        .context("failed to read index")?;
+    let mut conflicted_files = vec![];
+    for conflict in index.conflicts().context("failed to read conflicts")? {
+        let conflict = conflict.context("failed to read conflict")?;
+        if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+            conflicted_files.push(git::path::escape(&git::path::from_bytes(
+                entry.path.as_bstr(),
+            )));
+        }
+    }
+
+    Ok(Some(ExternalOperation {
+        kind,
+        can_continue: kind.command().is_some() && kind != ExternalOperationKind::Bisect,
+        conflicted_files,
+    }))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FinishOperationError {
+    #[error("there is no operation in progress")]
+    NoOperation,
+    #[error("{0}")]
+    Failed(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<FinishOperationError> for crate::error::Error {
+    fn from(value: FinishOperationError) -> Self {
+        match value {
+            FinishOperationError::NoOperation => crate::error::Error::UserError {
+                code: crate::error::Code::Validation,
+                message: "There is no operation in progress".to_string(),
+            },
+            FinishOperationError::Failed(message) => crate::error::Error::UserError {
+                code: crate::error::Code::ProjectExternalOperation,
+                message,
+            },
+            FinishOperationError::Other(error) => {
+                tracing::error!(?error, "finish external operation error");
+                crate::error::Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+/// Continues or aborts the operation in progress, with the `git` of the user so that it's
+/// finished exactly like it would be on the command line. A bisect is ended either way.
+pub fn finish_external_operation(
+    repository: &Repository,
+    action: OperationAction,
+) -> Result<(), FinishOperationError> {
+    let operation = external_operation(repository)?.ok_or(FinishOperationError::NoOperation)?;
+    let Some(command) = operation.kind.command() else {
+        return Err(FinishOperationError::Failed(
+            "Git can't tell whether patches are being applied or a rebase is in progress, finish it with `git am` or `git rebase`".to_string(),
+        ));
+    };
+    let args = match (operation.kind, action) {
+        (ExternalOperationKind::Bisect, _) => ["bisect", "reset"],
+        (_, OperationAction::Continue) => [command, "--continue"],
+        (_, OperationAction::Abort) => [command, "--abort"],
+    };
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repository.path())
+        // the messages of the commits it makes are taken as they are
+        .env("GIT_EDITOR", "true")
+        .output()
+        .context("failed to run git, is it installed?")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        return Err(FinishOperationError::Failed(
+            if stderr.trim().is_empty() {
+                stdout.trim()
+            } else {
+                stderr.trim()
+            }
+            .to_string(),
+        ));
+    }
+    Ok(())
+}
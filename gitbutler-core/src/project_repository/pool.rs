@@ -0,0 +1,181 @@
+//! Process wide pool of open git repositories, keyed by project path.
+//!
+//! Opening a repository means discovering it, reading its config and setting up the
+//! object database, which is noticeably slow on Windows. Repositories are handed back to
+//! the pool when a `project_repository::Repository` is dropped and reused by the next
+//! `open` for the same project, unless the repository config changed in the meantime or
+//! they have been idle for too long. When the pool goes over its memory budget, the
+//! repositories that have been idle the longest are closed first.
+
+use std::{
+    collections::HashMap,
+    path,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{git, memory};
+
+// repositories that have not been used for that long are closed
+const MAX_IDLE: Duration = Duration::from_secs(5 * 60);
+// how many idle repositories are kept around per project
+const MAX_IDLE_PER_PROJECT: usize = 4;
+// rough cost of an open repository on top of its index: config, odb handles and caches
+const REPOSITORY_OVERHEAD: usize = 2 * 1024 * 1024;
+
+struct Idle {
+    repository: git::Repository,
+    config_modified_at: Option<SystemTime>,
+    released_at: Instant,
+    size: usize,
+}
+
+impl Idle {
+    fn is_expired(&self) -> bool {
+        self.released_at.elapsed() > MAX_IDLE
+    }
+}
+
+// every idle repository that leaves the pool must go through here
+fn close(idle: Idle) -> git::Repository {
+    memory::release(memory::Cache::Repositories, idle.size);
+    idle.repository
+}
+
+lazy_static! {
+    static ref POOL: Mutex<HashMap<path::PathBuf, Vec<Idle>>> = Mutex::new(HashMap::new());
+}
+
+fn config_modified_at(repository: &git::Repository) -> Option<SystemTime> {
+    std::fs::metadata(repository.common_dir().join("config"))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn estimated_size(repository: &git::Repository) -> usize {
+    let index_size = std::fs::metadata(repository.path().join("index"))
+        .map(|metadata| usize::try_from(metadata.len()).unwrap_or(usize::MAX))
+        .unwrap_or_default();
+    REPOSITORY_OVERHEAD.saturating_add(index_size)
+}
+
+// closes the repository that has been idle the longest, across all projects
+fn evict_oldest(pool: &mut HashMap<path::PathBuf, Vec<Idle>>) -> bool {
+    let oldest = pool
+        .iter()
+        .flat_map(|(path, idle)| {
+            idle.iter()
+                .enumerate()
+                .map(move |(i, idle)| (path, i, idle.released_at))
+        })
+        .min_by_key(|(_, _, released_at)| *released_at)
+        .map(|(path, i, _)| (path.clone(), i));
+    if let Some((path, i)) = oldest {
+        if let Some(idle) = pool.get_mut(&path) {
+            close(idle.remove(i));
+        }
+        true
+    } else {
+        false
+    }
+}
+
+/// Takes an idle repository for the project at `path` out of the pool, if there is a
+/// usable one.
+pub fn take(path: &path::Path) -> Option<git::Repository> {
+    let mut pool = POOL.lock().unwrap();
+    let idle = pool.get_mut(path)?;
+    while let Some(candidate) = idle.pop() {
+        let is_stale = candidate.is_expired()
+            || candidate.config_modified_at != config_modified_at(&candidate.repository);
+        let repository = close(candidate);
+        if is_stale {
+            continue;
+        }
+        // the index might have been changed by someone else while the repository was idle
+        if let Err(error) = repository.index().and_then(|mut index| index.read(false)) {
+            tracing::warn!(?error, path = %path.display(), "failed to refresh pooled index");
+            continue;
+        }
+        return Some(repository);
+    }
+    None
+}
+
+/// Hands `repository` back to the pool.
+pub fn release(path: &path::Path, repository: git::Repository) {
+    let config_modified_at = config_modified_at(&repository);
+    let mut pool = POOL.lock().unwrap();
+    let idle = pool.entry(path.to_path_buf()).or_default();
+    let (expired, fresh) = std::mem::take(idle)
+        .into_iter()
+        .partition::<Vec<_>, _>(Idle::is_expired);
+    expired.into_iter().for_each(|idle| {
+        close(idle);
+    });
+    *idle = fresh;
+    if idle.len() >= MAX_IDLE_PER_PROJECT {
+        return;
+    }
+    let size = estimated_size(&repository);
+    memory::charge(memory::Cache::Repositories, size);
+    idle.push(Idle {
+        repository,
+        config_modified_at,
+        released_at: Instant::now(),
+        size,
+    });
+
+    while memory::is_over_budget(memory::Cache::Repositories) {
+        if !evict_oldest(&mut pool) {
+            break;
+        }
+    }
+}
+
+/// Closes all idle repositories of the project at `path`.
+pub fn invalidate(path: &path::Path) {
+    if let Some(idle) = POOL.lock().unwrap().remove(path) {
+        idle.into_iter().for_each(|idle| {
+            close(idle);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils;
+
+    use super::*;
+
+    #[test]
+    fn test_reuse() {
+        let repository = test_utils::test_repository();
+        let path = repository.workdir().unwrap().to_path_buf();
+        let git_dir = repository.path().to_path_buf();
+
+        release(&path, repository);
+        let reused = take(&path).unwrap();
+        assert_eq!(reused.path(), git_dir);
+        assert!(take(&path).is_none());
+    }
+
+    #[test]
+    fn test_config_change_invalidates() {
+        let repository = test_utils::test_repository();
+        let path = repository.workdir().unwrap().to_path_buf();
+        let config_path = repository.path().join("config");
+
+        release(&path, repository);
+
+        filetime::set_file_mtime(
+            &config_path,
+            filetime::FileTime::from_system_time(SystemTime::now() + Duration::from_secs(60)),
+        )
+        .unwrap();
+
+        assert!(take(&path).is_none());
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use serde::Serialize;
+
+/// Progress of a fetch, shared between the thread fetching and whoever is reporting it.
+#[derive(Debug, Clone, Default)]
+pub struct FetchProgress {
+    received_objects: Arc<AtomicUsize>,
+    total_objects: Arc<AtomicUsize>,
+    received_bytes: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchProgressSnapshot {
+    pub received_objects: usize,
+    pub total_objects: usize,
+    pub received_bytes: usize,
+}
+
+impl FetchProgress {
+    pub fn set(&self, progress: &git2::Progress<'_>) {
+        self.total_objects
+            .store(progress.total_objects(), Ordering::Relaxed);
+        self.received_objects
+            .store(progress.received_objects(), Ordering::Relaxed);
+        self.received_bytes
+            .store(progress.received_bytes(), Ordering::Relaxed);
+    }
+
+    /// Sets how many objects of how many were received, for fetches that aren't libgit2's.
+    pub fn set_objects(&self, received_objects: usize, total_objects: usize) {
+        self.total_objects.store(total_objects, Ordering::Relaxed);
+        self.received_objects
+            .store(received_objects, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> FetchProgressSnapshot {
+        FetchProgressSnapshot {
+            received_objects: self.received_objects.load(Ordering::Relaxed),
+            total_objects: self.total_objects.load(Ordering::Relaxed),
+            received_bytes: self.received_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
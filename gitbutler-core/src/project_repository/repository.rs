@@ -0,0 +1,1421 @@
+use std::{
+    path,
+    process::Command,
+    sync::{atomic::AtomicUsize, mpsc, Arc, Mutex, PoisonError},
+    thread,
+};
+
+use anyhow::{Context, Result};
+use tokio_util::sync::CancellationToken;
+use tracing::instrument;
+
+use crate::{
+    git::{self, credentials::HelpError, history, Url},
+    keys, projects, ssh, users,
+    virtual_branches::{normalize_branch_name, Branch},
+};
+
+use super::{
+    conflicts, merge_base_cache, pool,
+    retry::{HttpDecision, NetworkOperation, OnRetry, Retry, RetryPolicy, CANCEL_CHECK_INTERVAL},
+    FetchProgress,
+};
+
+pub struct Repository {
+    pub git_repository: git::Repository,
+    project: projects::Project,
+    cancel: Cancellation,
+    on_retry: Option<OnRetry>,
+    // the replacements and shallow commits of the repository, read once, until it's fetched
+    history: Mutex<Option<Arc<history::History>>>,
+}
+
+/// What stops the network operations of a repository. Cancelled, they return right away,
+/// but libgit2 can only be interrupted when it reports progress, not while it connects: what
+/// is left running on its threads then is lingering, and whoever cancelled it waits for it
+/// to finish before changing the repository again, for a while at most.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    token: CancellationToken,
+    lingering: Arc<Mutex<Vec<thread::JoinHandle<()>>>>,
+}
+
+impl Cancellation {
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// The threads that were still running when the operation was cancelled, see the
+    /// [type docs](Self).
+    pub fn take_lingering(&self) -> Vec<thread::JoinHandle<()>> {
+        std::mem::take(
+            &mut *self
+                .lingering
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        )
+    }
+
+    pub(crate) fn linger(&self, handles: impl IntoIterator<Item = thread::JoinHandle<()>>) {
+        self.lingering
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(handles);
+    }
+}
+
+impl Drop for Repository {
+    fn drop(&mut self) {
+        // hand the repository back to the pool, leaving an empty in-memory one behind
+        let Ok(placeholder) = git2::Odb::new().and_then(git2::Repository::from_odb) else {
+            return;
+        };
+        let git_repository = std::mem::replace(&mut self.git_repository, placeholder.into());
+        pool::release(&self.project.path, git_repository);
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OpenError {
+    #[error("repository not found at {0}")]
+    NotFound(path::PathBuf),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<OpenError> for crate::error::Error {
+    fn from(value: OpenError) -> Self {
+        match value {
+            OpenError::NotFound(path) => crate::error::Error::UserError {
+                code: crate::error::Code::Projects,
+                message: format!("{} not found", path.display()),
+            },
+            OpenError::Other(error) => {
+                tracing::error!(?error);
+                crate::error::Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl Repository {
+    pub fn open(project: &projects::Project) -> Result<Self, OpenError> {
+        if let Some(git_repository) = pool::take(&project.path) {
+            return Ok(Self {
+                git_repository,
+                project: project.clone(),
+                cancel: Cancellation::default(),
+                on_retry: None,
+                history: Mutex::default(),
+            });
+        }
+
+        git::Repository::open(&project.path)
+            .map_err(|error| match error {
+                git::Error::NotFound(_) => OpenError::NotFound(project.path.clone()),
+                other => OpenError::Other(other.into()),
+            })
+            .map(|git_repository| Self {
+                git_repository,
+                project: project.clone(),
+                cancel: Cancellation::default(),
+                on_retry: None,
+                history: Mutex::default(),
+            })
+    }
+
+    pub fn is_resolving(&self) -> bool {
+        conflicts::is_resolving(self)
+    }
+
+    pub fn path(&self) -> &path::Path {
+        path::Path::new(&self.project.path)
+    }
+
+    pub fn config(&self) -> super::Config {
+        super::Config::from(&self.git_repository)
+    }
+
+    pub fn git_signatures<'a>(
+        &self,
+        user: Option<&users::User>,
+    ) -> Result<(git::Signature<'a>, git::Signature<'a>)> {
+        super::signatures::signatures(self, user).context("failed to get signatures")
+    }
+
+    pub fn project(&self) -> &projects::Project {
+        &self.project
+    }
+
+    pub fn set_project(&mut self, project: &projects::Project) {
+        self.project = project.clone();
+    }
+
+    /// Makes the fetches and pushes of the repository fail with [`RemoteError::Cancelled`]
+    /// once `cancel` is cancelled, see [`Cancellation`].
+    pub fn set_cancellation(&mut self, cancel: &Cancellation) {
+        self.cancel = cancel.clone();
+    }
+
+    /// What makes the fetches, pushes and the checks before pushing of the repository stop,
+    /// see [`Self::set_cancellation`].
+    pub fn cancellation(&self) -> &Cancellation {
+        &self.cancel
+    }
+
+    /// Tells `on_retry` about the fetches and pushes that are tried again after a network
+    /// error, see [`super::retry`].
+    pub fn set_on_retry(&mut self, on_retry: &OnRetry) {
+        self.on_retry = Some(Arc::clone(on_retry));
+    }
+
+    // runs `attempt` against `remote_name` as many times as the project retries network errors
+    fn retry<T>(
+        &self,
+        operation: NetworkOperation,
+        remote_name: &str,
+        attempt: impl FnMut() -> Result<T, RemoteError>,
+    ) -> Result<T, RemoteError> {
+        let policy = RetryPolicy::new(
+            self.config()
+                .network_retries()
+                .context("failed to read network retries config")?,
+        );
+        policy.run(
+            |number, delay, error| Retry {
+                project_id: self.project.id,
+                operation,
+                remote: remote_name.to_string(),
+                attempt: number,
+                max_attempts: policy.max_attempts,
+                delay_ms: delay.as_millis(),
+                error: error.to_string(),
+            },
+            self.cancel.token(),
+            self.on_retry.as_ref(),
+            attempt,
+        )
+    }
+
+    // runs `f` with a repository of its own on another thread, git repositories can't be
+    // shared between them. it's cancelled with this one
+    fn spawn(
+        &self,
+        f: impl FnOnce(Result<Self, RemoteError>) + Send + 'static,
+    ) -> thread::JoinHandle<()> {
+        let project = self.project.clone();
+        let cancel = self.cancel.clone();
+        let on_retry = self.on_retry.clone();
+        thread::spawn(move || {
+            f(Self::open(&project)
+                .map_err(|error| RemoteError::Other(error.into()))
+                .map(|mut project_repository| {
+                    project_repository.cancel = cancel;
+                    project_repository.on_retry = on_retry;
+                    project_repository
+                }));
+        })
+    }
+
+    // runs the network operation `f` on another thread, to stop waiting for it as soon as
+    // it's cancelled, also while libgit2 is connecting. it lingers then, see `Cancellation`
+    fn cancellable<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Self) -> Result<T, RemoteError> + Send + 'static,
+    ) -> Result<T, RemoteError> {
+        let (sender, receiver) = mpsc::channel();
+        let handle = self.spawn(move |project_repository| {
+            let _ = sender
+                .send(project_repository.and_then(|project_repository| f(&project_repository)));
+        });
+        loop {
+            match receiver.recv_timeout(CANCEL_CHECK_INTERVAL) {
+                Ok(result) => {
+                    let _ = handle.join();
+                    return result;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) if self.cancel.is_cancelled() => {
+                    self.cancel.linger([handle]);
+                    return Err(RemoteError::Cancelled);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    let _ = handle.join();
+                    return Err(RemoteError::Other(anyhow::anyhow!(
+                        "network thread panicked"
+                    )));
+                }
+            }
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<(), RemoteError> {
+        if self.cancel.is_cancelled() {
+            Err(RemoteError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn get_head(&self) -> Result<git::Reference, git::Error> {
+        let head = self.git_repository.head()?;
+        Ok(head)
+    }
+
+    pub fn get_wd_tree(&self) -> Result<git::Tree> {
+        let tree = self.git_repository.get_wd_tree()?;
+        Ok(tree)
+    }
+
+    /// Returns the merge base between `target` and `head`, reusing a previous result
+    /// for the same pair of commits.
+    pub fn merge_base(&self, target: git::Oid, head: git::Oid) -> Result<git::Oid> {
+        Ok(self.merge_base_entry(target, head)?.merge_base)
+    }
+
+    /// Returns the tree of the merge base between `target` and `head`.
+    pub fn merge_base_tree(&self, target: git::Oid, head: git::Oid) -> Result<git::Tree> {
+        let entry = self.merge_base_entry(target, head)?;
+        self.git_repository
+            .find_tree(entry.tree)
+            .context("failed to find merge base tree")
+    }
+
+    fn merge_base_entry(
+        &self,
+        target: git::Oid,
+        head: git::Oid,
+    ) -> Result<merge_base_cache::Entry> {
+        if let Some(entry) = merge_base_cache::get(&self.project.id, target, head) {
+            return Ok(entry);
+        }
+        let merge_base = match self.git_repository.merge_base(target, head) {
+            Ok(merge_base) => merge_base,
+            Err(git::Error::NotFound(_)) if self.git_repository.is_shallow() => {
+                return Err(ShallowHistoryError { target, head }.into());
+            }
+            Err(error) => return Err(error).context("failed to find merge base"),
+        };
+        let tree = self
+            .git_repository
+            .find_commit(merge_base)
+            .context("failed to find merge base commit")?
+            .tree()
+            .context("failed to get base tree object")?;
+        let entry = merge_base_cache::Entry {
+            merge_base,
+            tree: tree.id(),
+        };
+        merge_base_cache::insert(&self.project.id, target, head, entry);
+        Ok(entry)
+    }
+
+    /// Drops cached merge bases, must be called whenever the target changes.
+    pub fn invalidate_merge_bases(&self) {
+        merge_base_cache::invalidate(&self.project.id);
+    }
+
+    pub fn is_path_ignored<P: AsRef<std::path::Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+        let ignored = self.git_repository.is_path_ignored(path)?;
+        Ok(ignored)
+    }
+
+    pub fn root(&self) -> &std::path::Path {
+        // the git directory of a linked worktree is not in it
+        self.git_repository
+            .workdir()
+            .unwrap_or_else(|| self.git_repository.path().parent().unwrap())
+    }
+
+    pub fn git_remote_branches(&self) -> Result<Vec<git::RemoteRefname>> {
+        self.git_repository
+            .branches(Some(git2::BranchType::Remote))?
+            .flatten()
+            .map(|(branch, _)| branch)
+            .map(|branch| {
+                git::RemoteRefname::try_from(&branch)
+                    .context("failed to convert branch to remote name")
+            })
+            .collect::<Result<Vec<_>>>()
+    }
+
+    /// What keeps the references of the project apart from those of the other projects of
+    /// the repository, `None` for the unscoped project of its main worktree. The other
+    /// worktrees and scopes share its references, `refs/gitbutler/<worktree>@<scope>/` are
+    /// theirs, with the directories of the scope separated by dots.
+    pub fn refs_namespace(&self) -> Option<String> {
+        let worktree = self
+            .git_repository
+            .worktree_name()
+            .map(|worktree| normalize_branch_name(&worktree));
+        let scope = self.project.scope.as_deref().map(|scope| {
+            scope
+                .iter()
+                .map(|name| normalize_branch_name(&name.to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join(".")
+        });
+        if worktree.is_none() && scope.is_none() {
+            return None;
+        }
+        Some(format!(
+            "{}@{}",
+            worktree.unwrap_or_default(),
+            scope.unwrap_or_default()
+        ))
+    }
+
+    /// The `refs/gitbutler/` reference of `branch`, in the [namespace](Self::refs_namespace)
+    /// of the project.
+    pub fn branch_refname(&self, branch: &Branch) -> git::VirtualRefname {
+        branch.refname().within(self.refs_namespace().as_deref())
+    }
+
+    pub fn add_branch_reference(&self, branch: &Branch) -> Result<()> {
+        let (should_write, with_force) = match self
+            .git_repository
+            .find_reference(&self.branch_refname(branch).into())
+        {
+            Ok(reference) => match reference.target() {
+                Some(head_oid) => Ok((head_oid != branch.head, true)),
+                None => Ok((true, true)),
+            },
+            Err(git::Error::NotFound(_)) => Ok((true, false)),
+            Err(error) => Err(error),
+        }
+        .context("failed to lookup reference")?;
+
+        if should_write {
+            self.git_repository
+                .reference(
+                    &self.branch_refname(branch).into(),
+                    branch.head,
+                    with_force,
+                    "new vbranch",
+                )
+                .context("failed to create branch reference")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_branch_reference(&self, branch: &Branch) -> Result<()> {
+        match self
+            .git_repository
+            .find_reference(&self.branch_refname(branch).into())
+        {
+            Ok(mut reference) => {
+                reference
+                    .delete()
+                    .context("failed to delete branch reference")?;
+                Ok(())
+            }
+            Err(git::Error::NotFound(_)) => Ok(()),
+            Err(error) => Err(error),
+        }
+        .context("failed to lookup reference")
+    }
+
+    // returns a list of commit oids from the first oid to the second oid
+    pub fn l(&self, from: git::Oid, to: LogUntil) -> Result<Vec<git::Oid>> {
+        self.history_log(from, to).map(|log| log.commits)
+    }
+
+    /// Same as [`Self::l`], also telling whether the walk stopped at the end of a shallow
+    /// history. Commits are read through their `refs/replace` replacements.
+    pub fn history_log(&self, from: git::Oid, to: LogUntil) -> Result<history::Log> {
+        let history = self.history()?;
+        if history.is_plain() {
+            return self.plain_log(from, to).map(|commits| history::Log {
+                commits,
+                truncated: false,
+            });
+        }
+        let repo = &self.git_repository;
+        match to {
+            LogUntil::Commit(oid) => history.log(repo, from, Some(oid)),
+            LogUntil::Take(0) => Ok(history::Log::default()),
+            LogUntil::Take(n) => {
+                let mut taken = 0;
+                history.log_until(repo, from, |_| {
+                    taken += 1;
+                    Ok(taken >= n)
+                })
+            }
+            LogUntil::When(cond) => history.log_until(repo, from, |oid| {
+                let commit = repo.find_commit(oid).context("failed to find commit")?;
+                cond(&commit).context("failed to check condition")
+            }),
+            LogUntil::End => history.log(repo, from, None),
+        }
+        .context("failed to collect oids")
+    }
+
+    /// The number of commits reachable from `local` and not from `upstream`, and the other way
+    /// around, see [`Self::history_log`].
+    pub fn ahead_behind(
+        &self,
+        local: git::Oid,
+        upstream: git::Oid,
+    ) -> Result<history::AheadBehind> {
+        let history = self.history()?;
+        if history.is_plain() {
+            let (ahead, behind) = self
+                .git_repository
+                .graph_ahead_behind(local, upstream)
+                .context("failed to count commits")?;
+            return Ok(history::AheadBehind {
+                ahead,
+                behind,
+                truncated: false,
+            });
+        }
+        history
+            .ahead_behind(&self.git_repository, local, upstream)
+            .context("failed to count commits")
+    }
+
+    fn history(&self) -> Result<Arc<history::History>> {
+        let mut cached = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(history) = cached.as_ref() {
+            return Ok(Arc::clone(history));
+        }
+        let history = Arc::new(
+            history::History::open(&self.git_repository).context("failed to read replace refs")?,
+        );
+        *cached = Some(Arc::clone(&history));
+        Ok(history)
+    }
+
+    fn plain_log(&self, from: git::Oid, to: LogUntil) -> Result<Vec<git::Oid>> {
+        match to {
+            LogUntil::Commit(oid) => {
+                let mut revwalk = self
+                    .git_repository
+                    .revwalk()
+                    .context("failed to create revwalk")?;
+                revwalk
+                    .push(from.into())
+                    .context(format!("failed to push {}", from))?;
+                revwalk
+                    .hide(oid.into())
+                    .context(format!("failed to hide {}", oid))?;
+                revwalk
+                    .map(|oid| oid.map(Into::into))
+                    .collect::<Result<Vec<_>, _>>()
+            }
+            LogUntil::Take(n) => {
+                let mut revwalk = self
+                    .git_repository
+                    .revwalk()
+                    .context("failed to create revwalk")?;
+                revwalk
+                    .push(from.into())
+                    .context(format!("failed to push {}", from))?;
+                revwalk
+                    .take(n)
+                    .map(|oid| oid.map(Into::into))
+                    .collect::<Result<Vec<_>, _>>()
+            }
+            LogUntil::When(cond) => {
+                let mut revwalk = self
+                    .git_repository
+                    .revwalk()
+                    .context("failed to create revwalk")?;
+                revwalk
+                    .push(from.into())
+                    .context(format!("failed to push {}", from))?;
+                let mut oids: Vec<git::Oid> = vec![];
+                for oid in revwalk {
+                    let oid = oid.context("failed to get oid")?;
+                    oids.push(oid.into());
+
+                    let commit = self
+                        .git_repository
+                        .find_commit(oid.into())
+                        .context("failed to find commit")?;
+
+                    if cond(&commit).context("failed to check condition")? {
+                        break;
+                    }
+                }
+                Ok(oids)
+            }
+            LogUntil::End => {
+                let mut revwalk = self
+                    .git_repository
+                    .revwalk()
+                    .context("failed to create revwalk")?;
+                revwalk
+                    .push(from.into())
+                    .context(format!("failed to push {}", from))?;
+                revwalk
+                    .map(|oid| oid.map(Into::into))
+                    .collect::<Result<Vec<_>, _>>()
+            }
+        }
+        .context("failed to collect oids")
+    }
+
+    // returns a list of commits from the first oid to the second oid
+    pub fn log(&self, from: git::Oid, to: LogUntil) -> Result<Vec<git::Commit>> {
+        self.l(from, to)?
+            .into_iter()
+            .map(|oid| self.git_repository.find_commit(oid))
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to collect commits")
+    }
+
+    // returns the number of commits reachable from `from` but not from `to`.
+    pub fn distance(&self, from: git::Oid, to: git::Oid) -> Result<u32> {
+        Ok(self.ahead_behind(from, to)?.ahead.try_into()?)
+    }
+
+    // returns true if `commit` is `from` or one of its ancestors.
+    pub fn is_reachable_from(&self, from: git::Oid, commit: git::Oid) -> Result<bool> {
+        if from == commit {
+            return Ok(true);
+        }
+        let history = self.history()?;
+        if history.is_plain() {
+            return self
+                .git_repository
+                .is_descendant_of(from, commit)
+                .context("failed to check ancestry");
+        }
+        history
+            .is_descendant_of(&self.git_repository, from, commit)
+            .context("failed to check ancestry")
+    }
+
+    /// Writes git's commit-graph file for the repository in the background.
+    ///
+    /// libgit2 picks up `objects/info/commit-graph` when it is present, which makes
+    /// merge-base, ancestry and ahead/behind queries cheap even in very large
+    /// histories. Split commit-graph chains are not supported by libgit2, so a single
+    /// file is written. Failures are logged and otherwise ignored, the graph is only
+    /// an optimization.
+    pub fn write_commit_graph(&self) {
+        match self.config().write_commit_graph() {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(error) => {
+                tracing::warn!(project_id = %self.project.id, ?error, "failed to read config");
+                return;
+            }
+        }
+
+        let project_id = self.project.id;
+        let git_dir = self.git_repository.path().to_path_buf();
+        std::thread::spawn(move || {
+            let output = std::process::Command::new("git")
+                .arg("--git-dir")
+                .arg(&git_dir)
+                .args(["commit-graph", "write", "--reachable", "--no-progress"])
+                .output();
+            match output {
+                Ok(output) if output.status.success() => {
+                    tracing::debug!(%project_id, "commit-graph written");
+                }
+                Ok(output) => {
+                    tracing::warn!(
+                        %project_id,
+                        stderr = %String::from_utf8_lossy(&output.stderr),
+                        "failed to write commit-graph"
+                    );
+                }
+                Err(error) => {
+                    tracing::warn!(%project_id, ?error, "failed to write commit-graph");
+                }
+            }
+        });
+    }
+
+    pub fn commit(
+        &self,
+        user: Option<&users::User>,
+        message: &str,
+        tree: &git::Tree,
+        parents: &[&git::Commit],
+        signing_key: Option<&keys::PrivateKey>,
+    ) -> Result<git::Oid> {
+        let (author, committer) = self.git_signatures(user)?;
+        if let Some(key) = signing_key {
+            self.git_repository
+                .commit_signed(&author, message, tree, parents, key)
+                .context("failed to commit signed")
+        } else {
+            self.git_repository
+                .commit(None, &author, &committer, message, tree, parents)
+                .context("failed to commit")
+        }
+    }
+
+    #[instrument(skip(self, user), fields(project_id = %self.project.id), level = "debug")]
+    pub fn push_to_gitbutler_server(
+        &self,
+        user: Option<&users::User>,
+        ref_specs: &[&str],
+    ) -> Result<bool, RemoteError> {
+        let url = self
+            .project
+            .api
+            .as_ref()
+            .ok_or(RemoteError::Other(anyhow::anyhow!("api not set")))?
+            .code_git_url
+            .as_ref()
+            .ok_or(RemoteError::Other(anyhow::anyhow!("code_git_url not set")))?
+            .as_str()
+            .parse::<Url>()
+            .map_err(|e| RemoteError::Other(e.into()))?;
+
+        tracing::debug!(
+            project_id = %self.project.id,
+            %url,
+            "pushing code to gb repo",
+        );
+
+        let access_token = user
+            .map(|user| user.access_token.clone())
+            .ok_or(RemoteError::Auth)?;
+
+        let mut callbacks = git2::RemoteCallbacks::new();
+        if self.project.omit_certificate_check.unwrap_or(false) {
+            callbacks.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
+        }
+        let bytes_pushed = Arc::new(AtomicUsize::new(0));
+        let total_objects = Arc::new(AtomicUsize::new(0));
+        {
+            let byte_counter = Arc::<AtomicUsize>::clone(&bytes_pushed);
+            let total_counter = Arc::<AtomicUsize>::clone(&total_objects);
+            callbacks.push_transfer_progress(move |_current, total, bytes| {
+                byte_counter.store(bytes, std::sync::atomic::Ordering::Relaxed);
+                total_counter.store(total, std::sync::atomic::Ordering::Relaxed);
+            });
+        }
+
+        callbacks.push_negotiation(|_| self.negotiate_push());
+        callbacks.sideband_progress(|_| !self.cancel.is_cancelled());
+
+        let mut push_options = git2::PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        let auth_header = format!("Authorization: {}", access_token);
+        let headers = &[auth_header.as_str()];
+        push_options.custom_headers(headers);
+
+        let mut remote = self
+            .git_repository
+            .remote_anonymous(&url)
+            .map_err(|e| RemoteError::Other(e.into()))?;
+
+        remote
+            .push(ref_specs, Some(&mut push_options))
+            .map_err(|error| match error {
+                _ if self.cancel.is_cancelled() => RemoteError::Cancelled,
+                git::Error::Network(error) => {
+                    tracing::warn!(project_id = %self.project.id, ?error, "git push failed",);
+                    RemoteError::Network
+                }
+                git::Error::Auth(error) => {
+                    tracing::warn!(project_id = %self.project.id, ?error, "git push failed",);
+                    RemoteError::Auth
+                }
+                error => RemoteError::Other(error.into()),
+            })?;
+
+        let bytes_pushed = bytes_pushed.load(std::sync::atomic::Ordering::Relaxed);
+        let total_objects_pushed = total_objects.load(std::sync::atomic::Ordering::Relaxed);
+
+        tracing::debug!(
+            project_id = %self.project.id,
+            ref_spec = ref_specs.join(" "),
+            bytes = bytes_pushed,
+            objects = total_objects_pushed,
+            "pushed to gb repo tmp ref",
+        );
+
+        Ok(total_objects_pushed > 0)
+    }
+
+    #[instrument(skip(self, credentials), fields(project_id = %self.project.id), level = "debug")]
+    pub fn push(
+        &self,
+        head: &git::Oid,
+        branch: &git::RemoteRefname,
+        with_force: bool,
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        let refspec = if with_force {
+            format!("+{}:refs/heads/{}", head, branch.branch())
+        } else {
+            format!("{}:refs/heads/{}", head, branch.branch())
+        };
+
+        // like git's pre-push hook for lfs, objects are uploaded before the branch that
+        // points to them
+        self.check_cancelled()?;
+        git::lfs::push(&self.git_repository, branch.remote(), *head)
+            .context("failed to push lfs objects")?;
+
+        self.push_refspecs(branch.remote(), &[refspec.as_str()], credentials)?;
+        tracing::info!(
+            project_id = %self.project.id,
+            remote = %branch.remote(),
+            %head,
+            branch = branch.branch(),
+            "pushed git branch"
+        );
+        Ok(())
+    }
+
+    /// Pushes the tags `names` to `remote_name`, failing if the remote has a different tag
+    /// with one of the names.
+    #[instrument(skip(self, credentials), fields(project_id = %self.project.id), level = "debug")]
+    pub fn push_tags(
+        &self,
+        remote_name: &str,
+        names: &[String],
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        let refspecs = names
+            .iter()
+            .map(|name| format!("refs/tags/{name}:refs/tags/{name}"))
+            .collect::<Vec<_>>();
+        self.push_refspecs(
+            remote_name,
+            &refspecs.iter().map(String::as_str).collect::<Vec<_>>(),
+            credentials,
+        )?;
+        tracing::info!(
+            project_id = %self.project.id,
+            remote = %remote_name,
+            tags = names.join(" "),
+            "pushed git tags"
+        );
+        Ok(())
+    }
+
+    /// Deletes the tags `names` from `remote_name`.
+    #[instrument(skip(self, credentials), fields(project_id = %self.project.id), level = "debug")]
+    pub fn delete_remote_tags(
+        &self,
+        remote_name: &str,
+        names: &[String],
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        let refspecs = names
+            .iter()
+            .map(|name| format!(":refs/tags/{name}"))
+            .collect::<Vec<_>>();
+        // not retried, a remote that deleted them the first time might refuse to again
+        self.push_refspecs_once(
+            remote_name,
+            &refspecs.iter().map(String::as_str).collect::<Vec<_>>(),
+            credentials,
+        )?;
+        tracing::info!(
+            project_id = %self.project.id,
+            remote = %remote_name,
+            tags = names.join(" "),
+            "deleted remote git tags"
+        );
+        Ok(())
+    }
+
+    // pushed again after a network error, which is safe: the refs are pushed to the same
+    // commits every time
+    fn push_refspecs(
+        &self,
+        remote_name: &str,
+        refspecs: &[&str],
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        let remote_name = remote_name.to_string();
+        let refspecs = refspecs.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let credentials = credentials.clone();
+        self.cancellable(move |project_repository| {
+            let refspecs = refspecs.iter().map(String::as_str).collect::<Vec<_>>();
+            project_repository.retry(NetworkOperation::Push, &remote_name, || {
+                project_repository.push_refspecs_once(&remote_name, &refspecs, &credentials)
+            })
+        })
+    }
+
+    // tries every way credentials can be provided for the remote until one is accepted
+    fn push_refspecs_once(
+        &self,
+        remote_name: &str,
+        refspecs: &[&str],
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        let auth_flows = credentials.help(self, remote_name)?;
+        for (mut remote, callbacks) in auth_flows {
+            if let Some(url) = remote.url().context("failed to get remote url")? {
+                if !self.project.omit_certificate_check.unwrap_or(false) {
+                    ssh::check_known_host(&url).context("failed to check known host")?;
+                }
+            }
+            for callback in callbacks {
+                self.check_cancelled()?;
+                let mut cbs: git2::RemoteCallbacks = callback.into();
+                if self.project.omit_certificate_check.unwrap_or(false) {
+                    cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
+                }
+                cbs.push_negotiation(|_| self.negotiate_push());
+                cbs.sideband_progress(|_| !self.cancel.is_cancelled());
+                match remote.push(
+                    refspecs,
+                    Some(&mut git2::PushOptions::new().remote_callbacks(cbs)),
+                ) {
+                    Ok(()) => return Ok(()),
+                    Err(_) if self.cancel.is_cancelled() => return Err(RemoteError::Cancelled),
+                    Err(git::Error::Http(error)) => match HttpDecision::of(&error) {
+                        HttpDecision::Reauthenticate => {
+                            tracing::warn!(project_id = %self.project.id, ?error, "git push failed");
+                            continue;
+                        }
+                        HttpDecision::Retry => {
+                            tracing::warn!(project_id = %self.project.id, ?error, "git push failed");
+                            return Err(RemoteError::Network);
+                        }
+                        HttpDecision::Fail => {
+                            return Err(RemoteError::Other(git::Error::Http(error).into()))
+                        }
+                    },
+                    Err(git::Error::Auth(error)) => {
+                        tracing::warn!(project_id = %self.project.id, ?error, "git push failed");
+                        continue;
+                    }
+                    Err(git::Error::Network(error)) => {
+                        tracing::warn!(project_id = %self.project.id, ?error, "git push failed");
+                        return Err(RemoteError::Network);
+                    }
+                    Err(error) => return Err(RemoteError::Other(error.into())),
+                }
+            }
+        }
+
+        Err(RemoteError::Auth)
+    }
+
+    // the last moment a push can be cancelled, once the remote knows what it gets it's
+    // pushed all the way
+    fn negotiate_push(&self) -> Result<(), git2::Error> {
+        if self.cancel.is_cancelled() {
+            Err(git2::Error::from_str("push cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[instrument(skip(self, credentials), fields(project_id = %self.project.id), level = "debug")]
+    pub fn fetch(
+        &self,
+        remote_name: &str,
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        let (remote, credentials) = (remote_name.to_string(), credentials.clone());
+        self.cancellable(move |project_repository| {
+            project_repository.fetch_remote(&remote, &credentials)
+        })?;
+        self.write_commit_graph();
+        self.fetch_lfs(remote_name);
+        Ok(())
+    }
+
+    // objects are downloaded ahead of time so that checkouts don't wait for them. a failure
+    // isn't one of the fetch, checkouts download what is missing.
+    fn fetch_lfs(&self, remote_name: &str) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        if let Err(error) = git::lfs::fetch(&self.git_repository, remote_name) {
+            tracing::warn!(project_id = %self.project.id, ?error, "failed to fetch lfs objects");
+        }
+    }
+
+    /// Fetches every configured remote at the same time, each one with its own repository
+    /// handle, so that a slow or unreachable remote doesn't hold up the others. Returns the
+    /// outcome of every fetch, keyed by remote name.
+    #[instrument(skip(self, credentials), fields(project_id = %self.project.id), level = "debug")]
+    pub fn fetch_all(
+        &self,
+        credentials: &git::credentials::Helper,
+    ) -> Result<Vec<(String, Result<(), RemoteError>)>> {
+        let remotes = self
+            .git_repository
+            .remotes()
+            .context("failed to list remotes")?;
+
+        // once cancelled the fetches aren't waited for, a hung connection could keep them for
+        // long. they linger, see `Cancellation`
+        let (sender, receiver) = mpsc::channel();
+        let mut handles = Vec::with_capacity(remotes.len());
+        for remote_name in &remotes {
+            let sender = sender.clone();
+            let credentials = credentials.clone();
+            let remote_name = remote_name.clone();
+            handles.push(self.spawn(move |project_repository| {
+                let result = project_repository.and_then(|project_repository| {
+                    project_repository.fetch_remote(&remote_name, &credentials)
+                });
+                // nobody waits for it anymore if the fetch was cancelled
+                let _ = sender.send((remote_name, result));
+            }));
+        }
+        drop(sender);
+
+        let mut finished = Vec::with_capacity(remotes.len());
+        while finished.len() < remotes.len() {
+            match receiver.recv_timeout(CANCEL_CHECK_INTERVAL) {
+                Ok(result) => finished.push(result),
+                Err(mpsc::RecvTimeoutError::Timeout) if self.cancel.is_cancelled() => break,
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        if self.cancel.is_cancelled() {
+            self.cancel.linger(handles);
+        } else {
+            for handle in handles {
+                let _ = handle.join();
+            }
+        }
+        let results = remotes
+            .into_iter()
+            .map(|remote_name| {
+                let result = match finished.iter().position(|(name, _)| *name == remote_name) {
+                    Some(index) => finished.swap_remove(index).1,
+                    None if self.cancel.is_cancelled() => Err(RemoteError::Cancelled),
+                    None => Err(RemoteError::Other(anyhow::anyhow!("fetch thread panicked"))),
+                };
+                (remote_name, result)
+            })
+            .collect::<Vec<_>>();
+
+        if results.iter().any(|(_, result)| result.is_ok()) {
+            self.write_commit_graph();
+        }
+        for (remote_name, _) in results.iter().filter(|(_, result)| result.is_ok()) {
+            self.fetch_lfs(remote_name);
+        }
+
+        Ok(results)
+    }
+
+    /// Fetches more of the history of the shallow clone from `remote_name`: `by` more
+    /// generations of commits below where the history of `head` ends now, or all of it. Does
+    /// nothing if the clone isn't shallow.
+    #[instrument(skip(self, credentials, progress), fields(project_id = %self.project.id), level = "debug")]
+    pub fn deepen(
+        &self,
+        remote_name: &str,
+        head: git::Oid,
+        by: Option<u32>,
+        credentials: &git::credentials::Helper,
+        progress: &FetchProgress,
+    ) -> Result<(), RemoteError> {
+        if !self.git_repository.is_shallow() {
+            return Ok(());
+        }
+        let url = self
+            .git_repository
+            .find_remote(remote_name)
+            .and_then(|remote| remote.url())
+            .context("failed to get remote url")?;
+        if url.is_some_and(|url| url.scheme == git::Scheme::File) {
+            self.deepen_with_git(remote_name, by)?;
+            self.write_commit_graph();
+            return Ok(());
+        }
+        let depth = match by {
+            // libgit2 counts the depth from the fetched tips, not from the end of the history
+            Some(by) => {
+                let depth = self
+                    .history()?
+                    .depth(&self.git_repository, head)
+                    .context("failed to measure the history")?;
+                i32::try_from(depth.saturating_add(usize::try_from(by).unwrap_or(usize::MAX)))
+                    .unwrap_or(UNSHALLOW)
+            }
+            None => UNSHALLOW,
+        };
+        self.fetch_remote_with(
+            remote_name,
+            credentials,
+            &Fetch {
+                depth: Some(depth),
+                progress: Some(progress),
+                ..Fetch::branches(remote_name)
+            },
+        )?;
+        self.write_commit_graph();
+        Ok(())
+    }
+
+    // libgit2 can't fetch shallow from remotes on disk, git deepens from those, `by` more
+    // commits with --deepen or all of them with --unshallow. there is nothing to transfer
+    // over the network, so no progress to report
+    fn deepen_with_git(&self, remote_name: &str, by: Option<u32>) -> Result<(), RemoteError> {
+        let mut fetch = Command::new("git");
+        fetch
+            .arg("--git-dir")
+            .arg(self.git_repository.path())
+            .args(["fetch", "--quiet", "--no-tags", "--no-write-fetch-head"]);
+        match by {
+            Some(by) => fetch.arg(format!("--deepen={by}")),
+            None => fetch.arg("--unshallow"),
+        };
+        let output = fetch
+            .arg(remote_name)
+            .arg(Fetch::branches(remote_name).refspec)
+            .output()
+            .context("failed to run git fetch, is git installed?")?;
+        // where the shallow history ends changed, even if it failed halfway
+        *self.history.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        if !output.status.success() {
+            return Err(RemoteError::Other(anyhow::anyhow!(
+                "git fetch failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        self.invalidate_merge_bases();
+        Ok(())
+    }
+
+    /// Fetches the branches of `remote_name`, `depth` commits deep if it's set, reporting the
+    /// transfer to `progress`. This is how clones get their history.
+    #[instrument(skip(self, credentials, progress), fields(project_id = %self.project.id), level = "debug")]
+    pub fn fetch_with_progress(
+        &self,
+        remote_name: &str,
+        depth: Option<u32>,
+        credentials: &git::credentials::Helper,
+        progress: &FetchProgress,
+    ) -> Result<(), RemoteError> {
+        self.fetch_remote_with(
+            remote_name,
+            credentials,
+            &Fetch {
+                depth: depth.map(|depth| i32::try_from(depth).unwrap_or(UNSHALLOW)),
+                progress: Some(progress),
+                follow_head: true,
+                ..Fetch::branches(remote_name)
+            },
+        )?;
+        self.write_commit_graph();
+        Ok(())
+    }
+
+    fn fetch_remote(
+        &self,
+        remote_name: &str,
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        self.fetch_remote_with(remote_name, credentials, &Fetch::branches(remote_name))
+    }
+
+    /// Fetches the notes refs of `remote_name` into `refs/notes/remotes/<remote_name>/`, to be
+    /// merged into the local ones, which are left as they are.
+    #[instrument(skip(self, credentials), fields(project_id = %self.project.id), level = "debug")]
+    pub fn fetch_notes(
+        &self,
+        remote_name: &str,
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        self.fetch_remote_with(
+            remote_name,
+            credentials,
+            &Fetch {
+                refspec: format!("+{NOTES_REFS}*:{REMOTE_NOTES_REFS}{remote_name}/*"),
+                prune: false,
+                depth: None,
+                progress: None,
+                follow_head: false,
+            },
+        )
+    }
+
+    /// Pushes the notes refs `notes_refs` to `remote_name`, failing if the remote has notes
+    /// that aren't in them.
+    #[instrument(skip(self, credentials), fields(project_id = %self.project.id), level = "debug")]
+    pub fn push_notes(
+        &self,
+        remote_name: &str,
+        notes_refs: &[String],
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        let refspecs = notes_refs
+            .iter()
+            .map(|name| format!("{name}:{name}"))
+            .collect::<Vec<_>>();
+        self.push_refspecs(
+            remote_name,
+            &refspecs.iter().map(String::as_str).collect::<Vec<_>>(),
+            credentials,
+        )?;
+        tracing::info!(
+            project_id = %self.project.id,
+            remote = %remote_name,
+            notes = notes_refs.join(" "),
+            "pushed git notes"
+        );
+        Ok(())
+    }
+
+    /// Fetches the ref `refname` of `remote_name`, replacing the local one.
+    #[instrument(skip(self, credentials), fields(project_id = %self.project.id), level = "debug")]
+    pub fn fetch_metadata(
+        &self,
+        remote_name: &str,
+        refname: &str,
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        self.fetch_remote_with(
+            remote_name,
+            credentials,
+            &Fetch {
+                refspec: format!("+{refname}:{refname}"),
+                prune: false,
+                depth: None,
+                progress: None,
+                follow_head: false,
+            },
+        )
+    }
+
+    /// Pushes the ref `refname` to `remote_name`, replacing the one the remote has.
+    #[instrument(skip(self, credentials), fields(project_id = %self.project.id), level = "debug")]
+    pub fn push_metadata(
+        &self,
+        remote_name: &str,
+        refname: &str,
+        credentials: &git::credentials::Helper,
+    ) -> Result<(), RemoteError> {
+        self.push_refspecs(
+            remote_name,
+            &[format!("+{refname}:{refname}").as_str()],
+            credentials,
+        )?;
+        tracing::info!(
+            project_id = %self.project.id,
+            remote = %remote_name,
+            %refname,
+            "pushed branch metadata"
+        );
+        Ok(())
+    }
+
+    fn fetch_remote_with(
+        &self,
+        remote_name: &str,
+        credentials: &git::credentials::Helper,
+        fetch: &Fetch,
+    ) -> Result<(), RemoteError> {
+        let fetched = self.retry(NetworkOperation::Fetch, remote_name, || {
+            self.fetch_remote_with_once(remote_name, credentials, fetch)
+        });
+        // a fetch can bring replacements and change where a shallow history ends, failed
+        // ones included
+        *self.history.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        fetched
+    }
+
+    fn fetch_remote_with_once(
+        &self,
+        remote_name: &str,
+        credentials: &git::credentials::Helper,
+        fetch: &Fetch,
+    ) -> Result<(), RemoteError> {
+        let refspec = &fetch.refspec;
+        let auth_flows = credentials.help(self, remote_name)?;
+        for (mut remote, callbacks) in auth_flows {
+            if let Some(url) = remote.url().context("failed to get remote url")? {
+                if !self.project.omit_certificate_check.unwrap_or(false) {
+                    ssh::check_known_host(&url).context("failed to check known host")?;
+                }
+            }
+            for callback in callbacks {
+                self.check_cancelled()?;
+                let mut fetch_opts = git2::FetchOptions::new();
+                let mut cbs: git2::RemoteCallbacks = callback.into();
+                if self.project.omit_certificate_check.unwrap_or(false) {
+                    cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
+                }
+                cbs.transfer_progress(|stats| {
+                    if let Some(progress) = fetch.progress {
+                        progress.set(&stats);
+                    }
+                    !self.cancel.is_cancelled()
+                });
+                cbs.sideband_progress(|_| !self.cancel.is_cancelled());
+                // a fetch that lingers after it was cancelled doesn't change the refs anymore
+                cbs.update_tips(|_, _, _| !self.cancel.is_cancelled());
+                fetch_opts.remote_callbacks(cbs);
+                if fetch.prune {
+                    fetch_opts.prune(git2::FetchPrune::On);
+                }
+                if let Some(depth) = fetch.depth {
+                    fetch_opts.depth(depth);
+                }
+                // remotes are fetched concurrently, they would race for FETCH_HEAD. it also
+                // keeps the watcher from reporting our own fetches as external ones.
+                fetch_opts.update_fetchhead(false);
+
+                match remote.fetch(&[refspec], Some(&mut fetch_opts)) {
+                    Ok(()) => {
+                        tracing::info!(project_id = %self.project.id, %refspec, "git fetched");
+                        self.invalidate_merge_bases();
+                        if fetch.follow_head {
+                            self.follow_remote_head(&remote, remote_name)?;
+                        }
+                        return Ok(());
+                    }
+                    Err(_) if self.cancel.is_cancelled() => return Err(RemoteError::Cancelled),
+                    Err(git::Error::Http(error)) => match HttpDecision::of(&error) {
+                        HttpDecision::Reauthenticate => {
+                            tracing::warn!(project_id = %self.project.id, ?error, "fetch failed");
+                            continue;
+                        }
+                        HttpDecision::Retry => {
+                            tracing::warn!(project_id = %self.project.id, ?error, "fetch failed");
+                            return Err(RemoteError::Network);
+                        }
+                        HttpDecision::Fail => {
+                            return Err(RemoteError::Other(git::Error::Http(error).into()))
+                        }
+                    },
+                    Err(git::Error::Auth(error)) => {
+                        tracing::warn!(project_id = %self.project.id, ?error, "fetch failed");
+                        continue;
+                    }
+                    Err(git::Error::Network(error)) => {
+                        tracing::warn!(project_id = %self.project.id, ?error, "fetch failed");
+                        return Err(RemoteError::Network);
+                    }
+                    Err(error) => return Err(RemoteError::Other(error.into())),
+                }
+            }
+        }
+
+        Err(RemoteError::Auth)
+    }
+
+    // points `refs/remotes/<remote_name>/HEAD` at the branch the HEAD of the just fetched
+    // `remote` points to, left as it is if the remote has none, like an empty repository
+    fn follow_remote_head(&self, remote: &git::Remote, remote_name: &str) -> Result<()> {
+        let Some(branch) = remote
+            .default_branch()
+            .ok()
+            .flatten()
+            .and_then(|branch| branch.strip_prefix("refs/heads/").map(ToString::to_string))
+        else {
+            return Ok(());
+        };
+        self.git_repository
+            .reference_symbolic(
+                &git::RemoteRefname::new(remote_name, "HEAD").into(),
+                &git::RemoteRefname::new(remote_name, &branch).into(),
+                true,
+                "fetch: follow remote head",
+            )
+            .context("failed to write remote head")?;
+        Ok(())
+    }
+}
+
+// what libgit2 takes as the depth of a fetch that gets the whole history
+const UNSHALLOW: i32 = i32::MAX;
+
+pub const NOTES_REFS: &str = "refs/notes/";
+/// Where the notes refs of remotes are fetched to, `refs/notes/remotes/<remote>/<name>`.
+pub const REMOTE_NOTES_REFS: &str = "refs/notes/remotes/";
+
+// what a fetch of a remote gets, and how
+struct Fetch<'a> {
+    refspec: String,
+    /// Whether the local refs the remote doesn't have anymore are deleted.
+    prune: bool,
+    depth: Option<i32>,
+    progress: Option<&'a FetchProgress>,
+    /// Whether `refs/remotes/<remote>/HEAD` is pointed at the default branch of the remote
+    /// once it's fetched, like `git clone` does.
+    follow_head: bool,
+}
+
+impl Fetch<'_> {
+    fn branches(remote_name: &str) -> Self {
+        Self {
+            refspec: format!("+refs/heads/*:refs/remotes/{remote_name}/*"),
+            prune: true,
+            depth: None,
+            progress: None,
+            follow_head: false,
+        }
+    }
+}
+
+/// The merge base of `target` and `head` is older than the oldest commit of the shallow clone,
+/// the history has to be deepened to find it.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("the merge base of {target} and {head} is past the end of the shallow clone")]
+pub struct ShallowHistoryError {
+    pub target: git::Oid,
+    pub head: git::Oid,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RemoteError {
+    #[error(transparent)]
+    Help(#[from] HelpError),
+    #[error("network failed")]
+    Network,
+    #[error("authentication failed")]
+    Auth,
+    #[error("cancelled")]
+    Cancelled,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<RemoteError> for crate::error::Error {
+    fn from(value: RemoteError) -> Self {
+        match value {
+            RemoteError::Help(error) => error.into(),
+            RemoteError::Network => crate::error::Error::UserError {
+                code: crate::error::Code::Network,
+                message: "Network error occurred".to_string(),
+            },
+            RemoteError::Auth => crate::error::Error::UserError {
+                code: crate::error::Code::ProjectGitAuth,
+                message: "Project remote authentication error".to_string(),
+            },
+            RemoteError::Cancelled => crate::error::Error::UserError {
+                code: crate::error::Code::Cancelled,
+                message: "The operation was cancelled".to_string(),
+            },
+            RemoteError::Other(error) => {
+                tracing::error!(?error);
+                crate::error::Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+type OidFilter = dyn Fn(&git::Commit) -> Result<bool>;
+
+pub enum LogUntil {
+    Commit(git::Oid),
+    Take(usize),
+    When(Box<OidFilter>),
+    End,
+}
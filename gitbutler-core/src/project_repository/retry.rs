@@ -0,0 +1,272 @@
+//! Fetches and pushes that fail for a reason that might not last, like a dropped connection,
+//! are tried again after a delay that doubles with every attempt. The delay is jittered so
+//! that the projects fetched together don't all retry together too. Only what is safe to do
+//! twice is retried: fetches, and pushes of refs to the commits they are pushed to.
+//!
+//! The number of retries is the `gitbutler.networkRetries` git config, two if it isn't set.
+//!
+//! What is done about a remote that answered with an HTTP error depends on its status, see
+//! [`HttpDecision::of_status`]. The ui calls GitHub with the same table.
+
+use std::{sync::Arc, thread, time};
+
+use anyhow::Context;
+use rand::Rng;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::projects::ProjectId;
+
+use super::RemoteError;
+
+const DEFAULT_RETRIES: u32 = 2;
+const INITIAL_DELAY: time::Duration = time::Duration::from_secs(1);
+const MAX_DELAY: time::Duration = time::Duration::from_secs(30);
+// a cancelled operation stops waiting for its thread within this long
+pub(super) const CANCEL_CHECK_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NetworkOperation {
+    Fetch,
+    Push,
+}
+
+/// An attempt that failed, and the one that's made next.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Retry {
+    pub project_id: ProjectId,
+    pub operation: NetworkOperation,
+    pub remote: String,
+    /// The attempt that's made next, the first retry is the second attempt.
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay_ms: u128,
+    /// Why the previous attempt failed.
+    pub error: String,
+}
+
+/// Told about every retry, before waiting for it.
+pub type OnRetry = Arc<dyn Fn(&Retry) + Send + Sync>;
+
+/// What is done after an attempt failed with an HTTP error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpDecision {
+    /// It's made again after a delay, the server is there but can't answer now.
+    Retry,
+    /// It's made again with the next credentials, if there are any left.
+    Reauthenticate,
+    /// It failed for good.
+    Fail,
+}
+
+impl HttpDecision {
+    /// The decision table of HTTP errors:
+    ///
+    /// | status             | what it means                                  | decision       |
+    /// |--------------------|------------------------------------------------|----------------|
+    /// | 401, 403, 407      | missing or wrong credentials, of a proxy too   | reauthenticate |
+    /// | 408, 425, 429      | the request came too slow, too early or often  | retry          |
+    /// | 500, 502, 503, 504 | the server or a proxy in front of it failed    | retry          |
+    /// | anything else      | like 404 for a repository that isn't there     | fail           |
+    pub fn of_status(status: u16) -> Self {
+        match status {
+            401 | 403 | 407 => Self::Reauthenticate,
+            408 | 425 | 429 | 500 | 502 | 503 | 504 => Self::Retry,
+            _ => Self::Fail,
+        }
+    }
+
+    /// The decision for an HTTP error of libgit2. The ones without a status are about
+    /// credentials, like too many authentication replays.
+    pub fn of(error: &git2::Error) -> Self {
+        status(error).map_or(Self::Reauthenticate, Self::of_status)
+    }
+}
+
+// the status of an HTTP error of libgit2, which only has it in its message
+fn status(error: &git2::Error) -> Option<u16> {
+    let message = error.message().to_lowercase();
+    let (_, status) = message.split_once("status code: ")?;
+    status
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .and_then(|status| status.parse().ok())
+}
+
+/// Whether an attempt that failed with `error` is worth making again.
+pub fn is_transient(error: &RemoteError) -> bool {
+    match error {
+        // the connection couldn't be made, dropped or timed out
+        RemoteError::Network => true,
+        // the right credentials don't come by asking again
+        RemoteError::Auth | RemoteError::Help(_) => false,
+        RemoteError::Cancelled => false,
+        // like a rejected push, or a remote that doesn't exist
+        RemoteError::Other(_) => false,
+    }
+}
+
+pub(super) struct RetryPolicy {
+    pub max_attempts: u32,
+}
+
+impl RetryPolicy {
+    pub fn new(retries: Option<i64>) -> Self {
+        let retries = retries
+            .and_then(|retries| u32::try_from(retries).ok())
+            .unwrap_or(DEFAULT_RETRIES);
+        Self {
+            max_attempts: retries.saturating_add(1),
+        }
+    }
+
+    // the delay before `attempt`, between half of the doubled delay and all of it
+    fn delay(attempt: u32) -> time::Duration {
+        let delay = INITIAL_DELAY
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(2)))
+            .min(MAX_DELAY);
+        rand::thread_rng().gen_range(delay / 2..=delay)
+    }
+
+    /// Runs `attempt` until it succeeds, fails for good or there is no attempt left.
+    pub fn run<T>(
+        &self,
+        retry: impl Fn(u32, time::Duration, &RemoteError) -> Retry,
+        cancel: &CancellationToken,
+        on_retry: Option<&OnRetry>,
+        mut attempt: impl FnMut() -> Result<T, RemoteError>,
+    ) -> Result<T, RemoteError> {
+        let mut number = 1;
+        loop {
+            match attempt() {
+                Err(error) if number < self.max_attempts && is_transient(&error) => {
+                    number += 1;
+                    let delay = Self::delay(number);
+                    let retry = retry(number, delay, &error);
+                    tracing::info!(?retry, "retrying network operation");
+                    if let Some(on_retry) = on_retry {
+                        on_retry(&retry);
+                    }
+                    wait(delay, cancel)?;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// attempts run on a worker of the runtime when the controllers make them, which is handed
+// over to the other tasks while waiting. elsewhere, like on the threads of network operations
+// or in a runtime of a single thread, the wait has a runtime of its own on a thread of its own
+fn wait(delay: time::Duration, cancel: &CancellationToken) -> Result<(), RemoteError> {
+    let sleep = async {
+        tokio::select! {
+            () = cancel.cancelled() => Err(RemoteError::Cancelled),
+            () = tokio::time::sleep(delay) => Ok(()),
+        }
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) if runtime.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| runtime.block_on(sleep))
+        }
+        _ => thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    tokio::runtime::Builder::new_current_thread()
+                        .enable_time()
+                        .build()
+                        .context("failed to start a runtime to wait in")?
+                        .block_on(sleep)
+                })
+                .join()
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("waiting thread panicked").into()))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn retry(attempt: u32, delay: time::Duration, error: &RemoteError) -> Retry {
+        Retry {
+            project_id: ProjectId::generate(),
+            operation: NetworkOperation::Fetch,
+            remote: "origin".to_string(),
+            attempt,
+            max_attempts: 3,
+            delay_ms: delay.as_millis(),
+            error: error.to_string(),
+        }
+    }
+
+    #[test]
+    fn delays_double_with_jitter() {
+        for (attempt, max) in [(2, 1), (3, 2), (4, 4), (10, 30)] {
+            let delay = RetryPolicy::delay(attempt);
+            let max = time::Duration::from_secs(max);
+            assert!(delay >= max / 2 && delay <= max, "{attempt}: {delay:?}");
+        }
+    }
+
+    #[test]
+    fn http_errors_by_status() {
+        for (message, decision) in [
+            ("unexpected http status code: 502", HttpDecision::Retry),
+            ("unexpected HTTP status code: 429", HttpDecision::Retry),
+            (
+                "unexpected http status code: 401",
+                HttpDecision::Reauthenticate,
+            ),
+            ("unexpected http status code: 404", HttpDecision::Fail),
+            (
+                "too many redirects or authentication replays",
+                HttpDecision::Reauthenticate,
+            ),
+        ] {
+            let error = git2::Error::new(
+                git2::ErrorCode::GenericError,
+                git2::ErrorClass::Http,
+                message,
+            );
+            assert_eq!(HttpDecision::of(&error), decision, "{message}");
+        }
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let policy = RetryPolicy { max_attempts: 3 };
+        let cancel = CancellationToken::new();
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(retry, &cancel, None, || {
+            attempts += 1;
+            Err(RemoteError::Auth)
+        });
+        assert!(matches!(result, Err(RemoteError::Auth)));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn cancelling_stops_retrying() {
+        let policy = RetryPolicy { max_attempts: 3 };
+        let cancel = CancellationToken::new();
+        let retries = Arc::new(Mutex::new(vec![]));
+        let on_retry: OnRetry = Arc::new({
+            let retries = Arc::clone(&retries);
+            let cancel = cancel.clone();
+            move |retry| {
+                retries.lock().unwrap().push(retry.attempt);
+                cancel.cancel();
+            }
+        });
+        let result: Result<(), _> = policy.run(retry, &cancel, Some(&on_retry), || {
+            Err(RemoteError::Network)
+        });
+        assert!(matches!(result, Err(RemoteError::Cancelled)));
+        assert_eq!(*retries.lock().unwrap(), vec![2]);
+    }
+}
@@ -0,0 +1,25 @@
+mod clone;
+mod controller;
+mod group;
+mod identity;
+mod preset;
+mod profile;
+mod project;
+mod prompt;
+mod settings;
+mod storage;
+mod unmanaged;
+
+pub use clone::CloneOptions;
+pub use controller::*;
+pub use group::{Group, GroupId, ProjectGroup};
+pub use identity::{Identity, Location};
+pub use preset::{Configuration, Preset, Setup};
+pub use profile::{CommitMessageProfile, CommitMessageStyle};
+pub use project::{
+    ApiProject, AuthKey, CodePushState, DiffOptions, FetchResult, Project, ProjectId,
+};
+pub use prompt::{render_prompt, PromptKind, PromptTemplateError, PromptTemplates};
+pub use settings::{SettingsUpdate, SettingsValidationError, SETTINGS_VERSION};
+pub use storage::UpdateRequest;
+pub use unmanaged::UnmanagedPaths;
@@ -0,0 +1,257 @@
+use std::{
+    fs,
+    io::Read,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::{git, project_repository};
+
+use super::{CloneError, Project};
+
+/// How a repository is cloned.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloneOptions {
+    /// The branch that is checked out, the default branch of the remote if it's not set.
+    pub branch: Option<String>,
+    /// How many commits of history are fetched, all of them if it's not set.
+    pub depth: Option<u32>,
+    /// What the clone leaves out to be fetched when it's needed, like `blob:none`, as
+    /// `git clone --filter` takes it. libgit2 can't filter what it fetches, so partial
+    /// clones are fetched with the git executable, with the credentials git has rather than
+    /// those of GitButler, like their missing objects are later on.
+    pub filter: Option<String>,
+}
+
+const REMOTE_NAME: &str = "origin";
+
+/// Clones `url` into the directory of `project`, which must be empty or not exist yet, then
+/// runs `register` on the clone. The remote is authenticated with the same credentials as
+/// the remotes of projects are. Nothing is left behind if the clone or `register` fails.
+pub(super) fn clone<T>(
+    project: &Project,
+    url: &str,
+    options: &CloneOptions,
+    credentials: &git::credentials::Helper,
+    progress: &project_repository::FetchProgress,
+    register: impl FnOnce() -> Result<T, CloneError>,
+) -> Result<T, CloneError> {
+    let path = &project.path;
+    let existed = path.exists();
+    if existed
+        && fs::read_dir(path)
+            .context("failed to read directory")?
+            .next()
+            .is_some()
+    {
+        return Err(CloneError::PathNotEmpty);
+    }
+    let url = url
+        .parse::<git::Url>()
+        .map_err(|_| CloneError::InvalidUrl)?;
+
+    let result =
+        clone_into(project, &url, options, credentials, progress).and_then(|()| register());
+    if result.is_err() {
+        let cleanup = if existed {
+            fs::read_dir(path).and_then(|entries| {
+                entries.flatten().try_for_each(|entry| {
+                    if entry.file_type()?.is_dir() {
+                        fs::remove_dir_all(entry.path())
+                    } else {
+                        fs::remove_file(entry.path())
+                    }
+                })
+            })
+        } else {
+            fs::remove_dir_all(path)
+        };
+        if let Err(error) = cleanup {
+            tracing::error!(?error, path = %path.display(), "failed to clean up failed clone");
+        }
+    }
+    result
+}
+
+fn clone_into(
+    project: &Project,
+    url: &git::Url,
+    options: &CloneOptions,
+    credentials: &git::credentials::Helper,
+    progress: &project_repository::FetchProgress,
+) -> Result<(), CloneError> {
+    let repository = git::Repository::init(&project.path).context("failed to init repository")?;
+    repository
+        .remote(REMOTE_NAME, url)
+        .context("failed to add remote")?;
+    let project_repository = project_repository::Repository::open(project)
+        .context("failed to open project repository")?;
+    match &options.filter {
+        Some(filter) => fetch_filtered(&repository, filter, options.depth, progress)?,
+        None => project_repository.fetch_with_progress(
+            REMOTE_NAME,
+            options.depth,
+            credentials,
+            progress,
+        )?,
+    }
+
+    let remote_branches = project_repository
+        .git_remote_branches()
+        .context("failed to list remote branches")?
+        .into_iter()
+        .filter(|branch| branch.remote() == REMOTE_NAME && branch.branch() != "HEAD")
+        .collect::<Vec<_>>();
+    let branch = match &options.branch {
+        Some(branch) => remote_branches
+            .iter()
+            .find(|remote_branch| remote_branch.branch() == branch)
+            .ok_or_else(|| CloneError::BranchNotFound(branch.clone()))?,
+        // the branch the head of the remote points to, which an empty repository has none of
+        None => match default_branch(&repository)?
+            .and_then(|name| {
+                remote_branches
+                    .iter()
+                    .find(|branch| branch.branch() == name)
+            })
+            .or_else(|| remote_branches.first())
+        {
+            Some(branch) => branch,
+            // an empty repository, there's nothing to check out
+            None => return Ok(()),
+        },
+    };
+    checkout(&repository, branch)
+}
+
+// the branch `refs/remotes/origin/HEAD` points to, as the fetch left it
+fn default_branch(repository: &git::Repository) -> Result<Option<String>> {
+    let head = match repository.find_reference(&git::RemoteRefname::new(REMOTE_NAME, "HEAD").into())
+    {
+        Ok(head) => head,
+        Err(git::Error::NotFound(_)) => return Ok(None),
+        Err(error) => return Err(error).context("failed to find remote head"),
+    };
+    Ok(match head.symbolic_target() {
+        Some(git::Refname::Remote(branch)) => Some(branch.branch().to_string()),
+        _ => None,
+    })
+}
+
+// fetches the branches of the remote with `git fetch --filter`, making the repository a
+// partial clone that objects it leaves out can be fetched from later
+fn fetch_filtered(
+    repository: &git::Repository,
+    filter: &str,
+    depth: Option<u32>,
+    progress: &project_repository::FetchProgress,
+) -> Result<()> {
+    let mut config = repository.config().context("failed to open config")?;
+    let promisor = format!("remote.{REMOTE_NAME}.promisor");
+    let partial_clone_filter = format!("remote.{REMOTE_NAME}.partialclonefilter");
+    for (key, value) in [
+        ("core.repositoryformatversion", "1"),
+        ("extensions.partialClone", REMOTE_NAME),
+        (promisor.as_str(), "true"),
+        (partial_clone_filter.as_str(), filter),
+    ] {
+        config
+            .set_str(key, value)
+            .with_context(|| format!("failed to set {key}"))?;
+    }
+
+    let mut fetch = Command::new("git");
+    fetch
+        .arg("--git-dir")
+        .arg(repository.path())
+        .args(["fetch", "--progress", "--no-tags", "--no-write-fetch-head"])
+        .arg(format!("--filter={filter}"));
+    if let Some(depth) = depth {
+        fetch.arg(format!("--depth={depth}"));
+    }
+    let mut child = fetch
+        .arg(REMOTE_NAME)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run git fetch")?;
+    let mut stderr = String::new();
+    if let Some(mut output) = child.stderr.take() {
+        let mut buf = [0; 4096];
+        loop {
+            let read = output.read(&mut buf).context("failed to read git fetch")?;
+            if read == 0 {
+                break;
+            }
+            stderr.push_str(&String::from_utf8_lossy(&buf[..read]));
+            if let Some((received, total)) = received_objects(&stderr) {
+                progress.set_objects(received, total);
+            }
+        }
+    }
+    let status = child.wait().context("failed to wait for git fetch")?;
+    if !status.success() {
+        anyhow::bail!("git fetch failed: {}", stderr.trim());
+    }
+
+    // like `git clone`, the head of the remote is followed, a remote without one is empty
+    let set_head = Command::new("git")
+        .arg("--git-dir")
+        .arg(repository.path())
+        .args(["remote", "set-head", REMOTE_NAME, "--auto"])
+        .output()
+        .context("failed to run git remote set-head")?;
+    if !set_head.status.success() {
+        tracing::warn!(
+            stderr = %String::from_utf8_lossy(&set_head.stderr),
+            "failed to follow remote head"
+        );
+    }
+    Ok(())
+}
+
+// the last `Receiving objects:  42% (420/1000)` count git fetch reported
+fn received_objects(stderr: &str) -> Option<(usize, usize)> {
+    let line = stderr
+        .rsplit(['\r', '\n'])
+        .find(|line| line.starts_with("Receiving objects:"))?;
+    let (received, total) = line.split_once('(')?.1.split_once(')')?.0.split_once('/')?;
+    Some((received.parse().ok()?, total.parse().ok()?))
+}
+
+// checks the remote `branch` out as a local branch of the same name that tracks it
+fn checkout(repository: &git::Repository, branch: &git::RemoteRefname) -> Result<(), CloneError> {
+    let commit = repository
+        .find_reference(&branch.into())
+        .context("failed to find branch")?
+        .peel_to_commit()
+        .context("failed to find commit")?;
+    let local = git::LocalRefname::new(branch.branch(), Some(branch.clone()));
+    repository
+        .reference(&(&local).into(), commit.id(), true, "clone")
+        .context("failed to create branch")?;
+    let mut config = repository.config().context("failed to open config")?;
+    config
+        .set_str(&format!("branch.{}.remote", branch.branch()), REMOTE_NAME)
+        .context("failed to set upstream")?;
+    config
+        .set_str(
+            &format!("branch.{}.merge", branch.branch()),
+            &format!("refs/heads/{}", branch.branch()),
+        )
+        .context("failed to set upstream")?;
+    // checked out like the rest of the app does, smudging what filters and lfs track
+    let tree = commit.tree().context("failed to find tree")?;
+    repository
+        .checkout_tree(&tree)
+        .force()
+        .checkout()
+        .context("failed to check out")?;
+    repository
+        .set_head(&local.into())
+        .context("failed to set head")?;
+    Ok(())
+}
@@ -0,0 +1,1116 @@
+use std::{path, sync::Arc};
+
+use anyhow::Context;
+use async_trait::async_trait;
+
+use crate::{
+    error::{Code, Error},
+    fs, gb_repository, git, project_repository,
+    settings::AiProvider,
+    users, virtual_branches,
+};
+
+use super::{
+    identity::{forget_root_commit, search_moved},
+    storage,
+    storage::UpdateRequest,
+    unmanaged, CloneOptions, Configuration, Group, GroupId, Identity, Location, Preset, Project,
+    ProjectGroup, ProjectId, SettingsUpdate, SettingsValidationError, Setup, SETTINGS_VERSION,
+};
+
+/// Whatever keeps track of the projects on disk, told about the projects the controller
+/// adds, updates and deletes.
+#[async_trait]
+pub trait Watchers {
+    fn watch(&self, project: &Project) -> anyhow::Result<()>;
+    async fn stop(&self, project_id: &ProjectId) -> anyhow::Result<()>;
+    /// The projects that are being watched.
+    fn active(&self) -> Vec<ProjectId>;
+    async fn fetch_gb_data(&self, project_id: &ProjectId) -> anyhow::Result<()>;
+    async fn push_gb_data(&self, project_id: &ProjectId) -> anyhow::Result<()>;
+}
+
+#[derive(Clone)]
+pub struct Controller {
+    local_data_dir: path::PathBuf,
+    projects_storage: storage::Storage,
+    users: users::Controller,
+    watchers: Option<Arc<dyn Watchers + Send + Sync>>,
+}
+
+impl From<&path::PathBuf> for Controller {
+    fn from(value: &path::PathBuf) -> Self {
+        Self {
+            local_data_dir: value.clone(),
+            projects_storage: storage::Storage::from(value),
+            users: users::Controller::from(value),
+            watchers: None,
+        }
+    }
+}
+
+impl Controller {
+    pub fn new(
+        data_dir: &path::Path,
+        storage: &crate::storage::Storage,
+        users: &users::Controller,
+        watchers: Option<Arc<dyn Watchers + Send + Sync>>,
+    ) -> Self {
+        Self {
+            local_data_dir: data_dir.to_path_buf(),
+            projects_storage: storage::Storage::from(storage),
+            users: users.clone(),
+            watchers,
+        }
+    }
+
+    pub fn add(&self, path: &path::Path) -> Result<Project, AddError> {
+        self.add_as(ProjectId::generate(), path, None)
+    }
+
+    /// Adds the repository at `path` as a project that only manages the changes in the
+    /// directory `scope`, relative to the root of the repository, like a component of a
+    /// monorepo. A repository can be added once for each scope, the projects of a worktree
+    /// share its workspace branch, each leaving what's outside of its scope as it is.
+    pub fn add_scoped(&self, path: &path::Path, scope: &path::Path) -> Result<Project, AddError> {
+        let scope = unmanaged::normalize_scope(scope)
+            .ok_or_else(|| AddError::InvalidScope(scope.to_path_buf()))?;
+        self.add_as(ProjectId::generate(), path, Some(&scope))
+    }
+
+    /// Clones `url` into `path` and adds it as the project `id`, generated by the caller so
+    /// that it can report the progress of the clone under it. `path` must be empty or not
+    /// exist yet.
+    pub fn clone_repository(
+        &self,
+        id: ProjectId,
+        url: &str,
+        path: &path::Path,
+        options: &CloneOptions,
+        credentials: &git::credentials::Helper,
+        progress: &project_repository::FetchProgress,
+    ) -> Result<Project, CloneError> {
+        let canonical_path = canonical(path);
+        if self
+            .projects_storage
+            .list()
+            .context("failed to list projects from storage")?
+            .iter()
+            .any(|project| project.path == canonical_path)
+        {
+            return Err(CloneError::Add(AddError::AlreadyExists));
+        }
+        super::clone::clone(
+            &Project {
+                id,
+                path: path.to_path_buf(),
+                ..Default::default()
+            },
+            url,
+            options,
+            credentials,
+            progress,
+            || self.add_as(id, path, None).map_err(CloneError::Add),
+        )
+    }
+
+    // adds the repository at `path` as the project `id`, scoped to the normalized `scope`
+    fn add_as(
+        &self,
+        id: ProjectId,
+        path: &path::Path,
+        scope: Option<&path::Path>,
+    ) -> Result<Project, AddError> {
+        // projects are kept at their canonical path, so that a repository is recognized
+        // whether it's reached through a symlink, a mapped drive or a `\\wsl$\` share
+        let path = &canonical(path);
+        let same_scope = self
+            .projects_storage
+            .list()
+            .context("failed to list projects from storage")?
+            .into_iter()
+            .filter(|project| project.scope.as_deref() == scope)
+            .collect::<Vec<_>>();
+        if same_scope.iter().any(|project| {
+            project.path == *path
+                || project
+                    .bare_repository
+                    .as_deref()
+                    .is_some_and(|bare_repository| bare_repository == *path)
+        }) {
+            return Err(AddError::AlreadyExists);
+        }
+        if !path.exists() {
+            return Err(AddError::PathNotFound);
+        }
+        if !path.is_dir() {
+            return Err(AddError::NotADirectory);
+        }
+        let (workdir, bare_repository) = if path.join(".git").exists() {
+            (path.to_path_buf(), None)
+        } else {
+            match git::Repository::open(path) {
+                Ok(repository) if repository.is_bare() => (
+                    workspace_worktree(&repository, path)?,
+                    Some(path.to_path_buf()),
+                ),
+                _ => return Err(AddError::NotAGitRepository),
+            }
+        };
+        if workdir != *path && same_scope.iter().any(|project| project.path == workdir) {
+            return Err(AddError::AlreadyExists);
+        }
+        if let Some(scope) = scope {
+            if !workdir.join(scope).is_dir() {
+                return Err(AddError::ScopeNotFound(scope.to_path_buf()));
+            }
+        }
+
+        // title is the base name of the file, without the .git of bare repositories
+        let title = path.iter().last().map_or_else(
+            || id.to_string(),
+            |p| {
+                let title = p.to_str().unwrap();
+                match &bare_repository {
+                    Some(_) => title.strip_suffix(".git").unwrap_or(title).to_string(),
+                    None => title.to_string(),
+                }
+            },
+        );
+
+        // scoped projects are told apart by their scope
+        let title = match scope {
+            Some(scope) => format!("{title}/{}", scope.display()),
+            None => title,
+        };
+
+        let mut project = Project {
+            id,
+            title,
+            path: workdir,
+            bare_repository,
+            api: None,
+            settings_version: SETTINGS_VERSION,
+            scope: scope.map(path::Path::to_path_buf),
+            ..Default::default()
+        };
+
+        // create all required directories to avoid racing later
+        let user = self.users.get_user()?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        project.identity = identity_of(&project_repository.git_repository);
+        gb_repository::Repository::open(&self.local_data_dir, &project_repository, user.as_ref())
+            .context("failed to open repository")?;
+
+        self.projects_storage
+            .add(&project)
+            .context("failed to add project to storage")?;
+
+        if let Some(watchers) = &self.watchers {
+            watchers.watch(&project)?;
+        }
+
+        Ok(project)
+    }
+
+    pub async fn update(&self, project: &UpdateRequest) -> Result<Project, UpdateError> {
+        if let Some(super::AuthKey::Local {
+            private_key_path, ..
+        }) = &project.preferred_key
+        {
+            use resolve_path::PathResolveExt;
+            let private_key_path = private_key_path.resolve();
+
+            if !private_key_path.exists() {
+                return Err(UpdateError::Validation(UpdateValidationError::KeyNotFound(
+                    private_key_path.to_path_buf(),
+                )));
+            }
+
+            if !private_key_path.is_file() {
+                return Err(UpdateError::Validation(UpdateValidationError::KeyNotFile(
+                    private_key_path.to_path_buf(),
+                )));
+            }
+        }
+
+        let updated = self
+            .projects_storage
+            .update(project)
+            .map_err(|error| match error {
+                super::storage::Error::NotFound => UpdateError::NotFound,
+                error => UpdateError::Other(error.into()),
+            })?;
+        // what fetches and pushes keep track of isn't a change of the project
+        if project.title.is_some()
+            || project.description.is_some()
+            || project.api.is_some()
+            || project.preferred_key.is_some()
+            || project.ok_with_force_push.is_some()
+            || project.omit_certificate_check.is_some()
+            || project.detect_copies.is_some()
+            || project.diff_options.is_some()
+        {
+            self.record(&project.id, "update_project");
+        }
+
+        if let Some(watchers) = &self.watchers {
+            if let Some(api) = &project.api {
+                if api.sync {
+                    if let Err(error) = watchers.fetch_gb_data(&project.id).await {
+                        tracing::error!(
+                            project_id = %project.id,
+                            ?error,
+                            "failed to post fetch project event"
+                        );
+                    }
+                }
+
+                if let Err(error) = watchers.push_gb_data(&project.id).await {
+                    tracing::error!(
+                        project_id = %project.id,
+                        ?error,
+                        "failed to post push project event"
+                    );
+                }
+            }
+        }
+
+        Ok(updated)
+    }
+
+    /// Validates `update` and applies it to the settings of the project `id`, all of it
+    /// or, if any setting is invalid, none of it.
+    pub fn update_settings(
+        &self,
+        id: &ProjectId,
+        update: &SettingsUpdate,
+    ) -> Result<Project, UpdateError> {
+        update.validate().map_err(UpdateError::Settings)?;
+        let project =
+            self.projects_storage
+                .update_settings(id, update)
+                .map_err(|error| match error {
+                    super::storage::Error::NotFound => UpdateError::NotFound,
+                    error => UpdateError::Other(error.into()),
+                })?;
+        self.record(id, "update_project_settings");
+
+        // the watcher only reads the unmanaged paths when it starts
+        if let Some(watchers) = &self.watchers {
+            if update.unmanaged_paths.is_some() && watchers.active().contains(id) {
+                if let Err(error) = watchers.watch(&project) {
+                    tracing::error!(?error, project_id = %id, "failed to restart watcher");
+                }
+            }
+        }
+        Ok(project)
+    }
+
+    /// Makes `ai_provider` generate for the project `id` instead of the provider of the app
+    /// settings, or the app's if it's `None`. Its headers and keys are kept in the keychain.
+    pub fn set_ai_provider(
+        &self,
+        id: &ProjectId,
+        ai_provider: Option<&AiProvider>,
+    ) -> Result<Project, UpdateError> {
+        if let Some(ai_provider) = ai_provider {
+            ai_provider.validate().map_err(|error| {
+                UpdateError::Settings(SettingsValidationError::AiProvider(error))
+            })?;
+        }
+        let project = self
+            .projects_storage
+            .set_ai_provider(id, ai_provider.map(AiProvider::normalized))
+            .map_err(|error| match error {
+                super::storage::Error::NotFound => UpdateError::NotFound,
+                error => UpdateError::Other(error.into()),
+            })?;
+        self.record(id, "set_ai_provider");
+        Ok(project)
+    }
+
+    // records a change of the project `id` in its audit log, see `virtual_branches::audit`
+    fn record(&self, id: &ProjectId, kind: &'static str) {
+        let user = self.users.get_user().ok().flatten();
+        virtual_branches::record_operation(
+            &self.local_data_dir,
+            id,
+            user.as_ref(),
+            kind,
+            virtual_branches::Requester::User,
+        );
+    }
+
+    pub fn get(&self, id: &ProjectId) -> Result<Project, GetError> {
+        self.projects_storage.get(id).map_err(|error| match error {
+            super::storage::Error::NotFound => GetError::NotFound,
+            error => GetError::Other(error.into()),
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<Project>, ListError> {
+        self.projects_storage
+            .list()
+            .map_err(|error| ListError::Other(error.into()))
+    }
+
+    /// The projects that are active, kept up to date as they change. Any number of them
+    /// can be active at once.
+    pub fn list_active(&self) -> Vec<ProjectId> {
+        self.watchers
+            .as_ref()
+            .map(|watchers| watchers.active())
+            .unwrap_or_default()
+    }
+
+    /// Makes the project `id` active, if it isn't already. Projects are active once added,
+    /// this is for the ones that were deactivated or whose watcher stopped, like when their
+    /// path was gone for a while.
+    pub fn activate(&self, id: &ProjectId) -> Result<(), GetError> {
+        let project = self.get(id)?;
+        if let Some(watchers) = &self.watchers {
+            if !watchers.active().contains(id) {
+                watchers.watch(&project)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops keeping the project `id` up to date, until it's activated again.
+    pub async fn deactivate(&self, id: &ProjectId) -> Result<(), GetError> {
+        self.get(id)?;
+        if let Some(watchers) = &self.watchers {
+            watchers.stop(id).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: &ProjectId) -> Result<(), DeleteError> {
+        let project = match self.projects_storage.get(id) {
+            Ok(project) => Ok(project),
+            Err(super::storage::Error::NotFound) => return Ok(()),
+            Err(error) => Err(DeleteError::Other(error.into())),
+        }?;
+
+        if let Some(watchers) = &self.watchers {
+            if let Err(error) = watchers.stop(id).await {
+                tracing::error!(
+                    project_id = %id,
+                    ?error,
+                    "failed to stop watcher for project",
+                );
+            }
+        }
+
+        self.projects_storage
+            .purge(&project.id)
+            .map_err(|error| DeleteError::Other(error.into()))?;
+
+        project_repository::pool::invalidate(&project.path);
+        virtual_branches::forget_project(&project.id);
+        forget_root_commit(&project.path);
+
+        if let Err(error) = std::fs::remove_dir_all(
+            self.local_data_dir
+                .join("projects")
+                .join(project.id.to_string()),
+        ) {
+            tracing::error!(project_id = %id, ?error, "failed to remove project data",);
+        }
+
+        Ok(())
+    }
+
+    /// Where the repository of the project `id` is. If it's not at the path of the project
+    /// anymore, the directories around it are searched for the same repository, which
+    /// [`Controller::relink`] can then point the project at.
+    pub fn locate(&self, id: &ProjectId) -> Result<Location, GetError> {
+        let project = self.get(id)?;
+        if project.path.exists() {
+            // remember what the repository is recognized by, for when it moves
+            if project.identity.is_none() {
+                let identity = git::Repository::open(&project.path)
+                    .ok()
+                    .and_then(|repository| identity_of(&repository));
+                if identity.is_some() {
+                    self.projects_storage
+                        .set_location(id, &project.path, identity)
+                        .map_err(|error| GetError::Other(error.into()))?;
+                }
+            }
+            return Ok(Location::Found);
+        }
+
+        // without an identity there's nothing to tell the repository apart by
+        let Some(identity) = &project.identity else {
+            return Ok(Location::Moved { candidates: vec![] });
+        };
+        let projects = self.list().map_err(|error| GetError::Other(error.into()))?;
+        let candidates = search_moved(&project.path)
+            .into_iter()
+            .filter(|candidate| {
+                !projects
+                    .iter()
+                    .any(|other| other.path == *candidate && other.scope == project.scope)
+            })
+            .filter(|candidate| {
+                git::Repository::open(candidate)
+                    .ok()
+                    .is_some_and(|repository| identity.matches(&repository).unwrap_or(false))
+            })
+            .collect();
+        Ok(Location::Moved { candidates })
+    }
+
+    /// Points the project `id` at the repository at `path`, where it moved to. The virtual
+    /// branches of the project are kept, they are stored by project rather than by path.
+    ///
+    /// The repository must be the one the project was recognized by the last time it was
+    /// opened. Projects that were never opened since identities are recorded can't be
+    /// verified, and are relinked to whatever repository they're pointed at.
+    pub async fn relink(&self, id: &ProjectId, path: &path::Path) -> Result<Project, RelinkError> {
+        let project = self.get(id).map_err(|error| match error {
+            GetError::NotFound => RelinkError::NotFound,
+            GetError::Other(error) => RelinkError::Other(error),
+        })?;
+        if project.bare_repository.is_some() {
+            return Err(RelinkError::BareRepository);
+        }
+        if !path.exists() {
+            return Err(RelinkError::PathNotFound);
+        }
+        let path = &canonical(path);
+        if self
+            .list()
+            .map_err(|error| RelinkError::Other(error.into()))?
+            .iter()
+            .any(|other| {
+                other.id != project.id && other.path == *path && other.scope == project.scope
+            })
+        {
+            return Err(RelinkError::AlreadyExists);
+        }
+        if !path.join(".git").exists() {
+            return Err(RelinkError::NotAGitRepository);
+        }
+        let repository = git::Repository::open(path).map_err(|_| RelinkError::NotAGitRepository)?;
+        if let Some(identity) = &project.identity {
+            if !identity
+                .matches(&repository)
+                .context("failed to read repository identity")?
+            {
+                return Err(RelinkError::NotTheSameRepository);
+            }
+        }
+
+        if let Some(watchers) = &self.watchers {
+            if let Err(error) = watchers.stop(id).await {
+                tracing::error!(project_id = %id, ?error, "failed to stop watcher for project");
+            }
+        }
+        project_repository::pool::invalidate(&project.path);
+        forget_root_commit(&project.path);
+
+        let project = self
+            .projects_storage
+            .set_location(id, path, identity_of(&repository))
+            .context("failed to update project")?;
+        self.record(id, "relink_project");
+
+        if let Some(watchers) = &self.watchers {
+            watchers.watch(&project)?;
+        }
+
+        Ok(project)
+    }
+
+    /// Sets the project `id` up the way `preset` works: its push remote, force-push policy
+    /// and, if the preset cares, whether commits are signed. If that fails halfway, the
+    /// project is set up as it was again.
+    ///
+    /// The target the preset picks is returned, setting it is left to the caller. So is how
+    /// the project was set up before, for [`Controller::restore_setup`] to put it back if
+    /// setting the target fails.
+    pub fn apply_preset(
+        &self,
+        id: &ProjectId,
+        preset: Preset,
+    ) -> Result<(Configuration, Setup), PresetError> {
+        let project = self.get(id).map_err(|error| match error {
+            GetError::NotFound => PresetError::NotFound,
+            GetError::Other(error) => PresetError::Other(error),
+        })?;
+        let project_repository = project_repository::Repository::open(&project)
+            .context("failed to open project repository")?;
+        let configuration = preset.configuration(&project_repository)?;
+
+        let previous = Setup::of(&project, &project_repository)?;
+        if let Err(error) =
+            self.write_setup(id, &project_repository, &previous.with(&configuration))
+        {
+            if let Err(error) = self.write_setup(id, &project_repository, &previous) {
+                tracing::error!(project_id = %id, ?error, "failed to restore project setup");
+            }
+            return Err(error.into());
+        }
+        self.record(id, "apply_preset");
+
+        Ok((configuration, previous))
+    }
+
+    /// Sets the project `id` up as `setup` says, like it was before a preset was applied.
+    pub fn restore_setup(&self, id: &ProjectId, setup: &Setup) -> Result<(), PresetError> {
+        let project = self.get(id).map_err(|error| match error {
+            GetError::NotFound => PresetError::NotFound,
+            GetError::Other(error) => PresetError::Other(error),
+        })?;
+        let project_repository = project_repository::Repository::open(&project)
+            .context("failed to open project repository")?;
+        self.write_setup(id, &project_repository, setup)?;
+        self.record(id, "restore_setup");
+        Ok(())
+    }
+
+    fn write_setup(
+        &self,
+        id: &ProjectId,
+        project_repository: &project_repository::Repository,
+        setup: &Setup,
+    ) -> anyhow::Result<()> {
+        let config = project_repository.config();
+        match &setup.push_remote {
+            Some(push_remote) => config.set_push_remote(push_remote),
+            None => config.unset_push_remote(),
+        }
+        .context("failed to set push remote")?;
+        match setup.sign_commits {
+            Some(sign_commits) => config.set_sign_commits(sign_commits),
+            None => config.unset_sign_commits(),
+        }
+        .context("failed to set commit signing")?;
+        self.projects_storage
+            .update_settings(
+                id,
+                &SettingsUpdate {
+                    ok_with_force_push: Some(setup.ok_with_force_push),
+                    ..Default::default()
+                },
+            )
+            .context("failed to update project")?;
+        Ok(())
+    }
+
+    /// The projects, in the groups they are in. Groups are in their order, with the projects
+    /// that are in no group last, and the projects keep their order within a group. Groups
+    /// without projects are listed too.
+    pub fn list_grouped(&self) -> Result<Vec<ProjectGroup>, ListError> {
+        let projects = self.list()?;
+        let groups = self
+            .projects_storage
+            .list_groups()
+            .map_err(|error| ListError::Other(error.into()))?;
+        let mut grouped = groups
+            .into_iter()
+            .map(|group| ProjectGroup {
+                projects: projects
+                    .iter()
+                    .filter(|project| project.group == Some(group.id))
+                    .cloned()
+                    .collect(),
+                group: Some(group),
+            })
+            .collect::<Vec<_>>();
+        // projects of groups that are gone are in no group
+        let ungrouped = projects
+            .into_iter()
+            .filter(|project| {
+                !grouped
+                    .iter()
+                    .any(|grouped| grouped.group.as_ref().map(|group| group.id) == project.group)
+            })
+            .collect::<Vec<_>>();
+        if !ungrouped.is_empty() {
+            grouped.push(ProjectGroup {
+                group: None,
+                projects: ungrouped,
+            });
+        }
+        Ok(grouped)
+    }
+
+    /// Moves the projects `ids` into the positions they are at between them, in the order
+    /// of `ids`, leaving the other projects where they are.
+    pub fn reorder(&self, ids: &[ProjectId]) -> Result<(), GroupError> {
+        self.projects_storage.reorder(ids).map_err(Into::into)
+    }
+
+    pub fn add_group(&self, name: &str) -> Result<Group, GroupError> {
+        let group = Group {
+            id: GroupId::generate(),
+            name: validate_group_name(name)?,
+        };
+        self.projects_storage.add_group(&group)?;
+        Ok(group)
+    }
+
+    pub fn rename_group(&self, id: &GroupId, name: &str) -> Result<Group, GroupError> {
+        let name = validate_group_name(name)?;
+        self.projects_storage
+            .rename_group(id, &name)
+            .map_err(Into::into)
+    }
+
+    /// Moves the groups `ids` like [`Controller::reorder`] moves projects.
+    pub fn reorder_groups(&self, ids: &[GroupId]) -> Result<(), GroupError> {
+        self.projects_storage
+            .reorder_groups(ids)
+            .map_err(Into::into)
+    }
+
+    /// Deletes the group `id`, leaving its projects in no group.
+    pub fn delete_group(&self, id: &GroupId) -> Result<(), GroupError> {
+        self.projects_storage.purge_group(id).map_err(Into::into)
+    }
+
+    /// Puts the project `id` in the group `group_id`, or in none.
+    pub fn set_group(
+        &self,
+        id: &ProjectId,
+        group_id: Option<GroupId>,
+    ) -> Result<Project, GroupError> {
+        self.projects_storage
+            .set_group(id, group_id)
+            .map_err(Into::into)
+    }
+}
+
+// identities are only a help to relink moved projects, failing to read one isn't an error
+fn identity_of(repository: &git::Repository) -> Option<Identity> {
+    Identity::of(repository)
+        .map_err(|error| tracing::warn!(?error, "failed to read repository identity"))
+        .ok()
+}
+
+fn canonical(path: &path::Path) -> path::PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn validate_group_name(name: &str) -> Result<String, GroupError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(GroupError::EmptyName);
+    }
+    Ok(name.to_string())
+}
+
+/// Name of the worktree of a bare repository that virtual branches are applied in.
+const WORKSPACE_WORKTREE: &str = "gitbutler";
+/// The branch the [`WORKSPACE_WORKTREE`] is checked out on until the workspace is set up. Not
+/// `gitbutler`, whose ref `refs/heads/gitbutler` would keep `gitbutler/integration` from
+/// being created.
+const WORKSPACE_WORKTREE_BRANCH: &str = "gitbutler-workspace";
+
+/// The working directory of the [`WORKSPACE_WORKTREE`] of the bare repository at `path`,
+/// added next to it as `<name>-gitbutler` the first time.
+fn workspace_worktree(
+    repository: &git::Repository,
+    path: &path::Path,
+) -> Result<path::PathBuf, AddError> {
+    if let Some(workdir) = repository
+        .find_worktree(WORKSPACE_WORKTREE)
+        .context("failed to find workspace worktree")?
+    {
+        return Ok(workdir);
+    }
+
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map_or("project", |name| name.strip_suffix(".git").unwrap_or(name));
+    let workdir = path.with_file_name(format!("{name}-{WORKSPACE_WORKTREE}"));
+    if workdir.exists() {
+        return Err(AddError::WorkspaceTaken(workdir));
+    }
+    repository
+        .add_worktree(WORKSPACE_WORKTREE, &workdir, WORKSPACE_WORKTREE_BRANCH)
+        .context("failed to add workspace worktree")?;
+    Ok(workdir)
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DeleteError {
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListError {
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetError {
+    #[error("project not found")]
+    NotFound,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UpdateError {
+    #[error("project not found")]
+    NotFound,
+    #[error(transparent)]
+    Validation(UpdateValidationError),
+    #[error(transparent)]
+    Settings(SettingsValidationError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UpdateValidationError {
+    #[error("{0} not found")]
+    KeyNotFound(path::PathBuf),
+    #[error("{0} is not a file")]
+    KeyNotFile(path::PathBuf),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RelinkError {
+    #[error("project not found")]
+    NotFound,
+    #[error("path not found")]
+    PathNotFound,
+    #[error("not a git repository")]
+    NotAGitRepository,
+    #[error("projects of bare repositories can't be relinked")]
+    BareRepository,
+    #[error("another project is at that path")]
+    AlreadyExists,
+    #[error("not the repository of the project")]
+    NotTheSameRepository,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum PresetError {
+    #[error("project not found")]
+    NotFound,
+    #[error("remote {0} not found")]
+    RemoteNotFound(String),
+    #[error("no default branch found on remote {0}")]
+    DefaultBranchNotFound(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GroupError {
+    #[error("project not found")]
+    ProjectNotFound,
+    #[error("group not found")]
+    GroupNotFound,
+    #[error("group name is empty")]
+    EmptyName,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<storage::Error> for GroupError {
+    fn from(value: storage::Error) -> Self {
+        match value {
+            storage::Error::NotFound => GroupError::ProjectNotFound,
+            storage::Error::GroupNotFound => GroupError::GroupNotFound,
+            error => GroupError::Other(error.into()),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CloneError {
+    #[error("path is not empty")]
+    PathNotEmpty,
+    #[error("invalid url")]
+    InvalidUrl,
+    #[error("branch {0} not found")]
+    BranchNotFound(String),
+    #[error(transparent)]
+    Remote(#[from] project_repository::RemoteError),
+    #[error(transparent)]
+    Add(AddError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum AddError {
+    #[error("not a directory")]
+    NotADirectory,
+    #[error("not a git repository")]
+    NotAGitRepository,
+    #[error("path not found")]
+    PathNotFound,
+    #[error("project already exists")]
+    AlreadyExists,
+    #[error("{0} is in the way of the workspace worktree")]
+    WorkspaceTaken(path::PathBuf),
+    #[error("{0} is not a directory of the repository")]
+    ScopeNotFound(path::PathBuf),
+    #[error("{0} is not a path relative to the root of the repository")]
+    InvalidScope(path::PathBuf),
+    #[error(transparent)]
+    User(#[from] users::GetError),
+    #[error(transparent)]
+    OpenProjectRepository(#[from] project_repository::OpenError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<UpdateError> for Error {
+    fn from(value: UpdateError) -> Self {
+        match value {
+            UpdateError::Validation(UpdateValidationError::KeyNotFound(path)) => Error::UserError {
+                code: Code::Projects,
+                message: format!("'{}' not found", path.display()),
+            },
+            UpdateError::Validation(UpdateValidationError::KeyNotFile(path)) => Error::UserError {
+                code: Code::Projects,
+                message: format!("'{}' is not a file", path.display()),
+            },
+            UpdateError::Settings(SettingsValidationError::KeyNotFound(path)) => Error::UserError {
+                code: Code::Projects,
+                message: format!("'{}' not found", path.display()),
+            },
+            UpdateError::Settings(SettingsValidationError::KeyNotFile(path)) => Error::UserError {
+                code: Code::Projects,
+                message: format!("'{}' is not a file", path.display()),
+            },
+            UpdateError::Settings(
+                error @ (SettingsValidationError::ContextLines(_)
+                | SettingsValidationError::UnmanagedPath(_)
+                | SettingsValidationError::RedactedPath(_)
+                | SettingsValidationError::AiProvider(_)
+                | SettingsValidationError::EmptyProfileName
+                | SettingsValidationError::DuplicateProfile(_)
+                | SettingsValidationError::PromptTemplate { .. }),
+            ) => Error::UserError {
+                code: Code::Validation,
+                message: error.to_string(),
+            },
+            UpdateError::NotFound => Error::UserError {
+                code: Code::Projects,
+                message: "Project not found".into(),
+            },
+            UpdateError::Other(error) => {
+                tracing::error!(?error, "failed to update project");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<AddError> for Error {
+    fn from(value: AddError) -> Self {
+        match value {
+            AddError::NotAGitRepository => Error::UserError {
+                code: Code::Projects,
+                message: "Must be a git directory".to_string(),
+            },
+            AddError::AlreadyExists => Error::UserError {
+                code: Code::Projects,
+                message: "Project already exists".to_string(),
+            },
+            AddError::WorkspaceTaken(path) => Error::UserError {
+                code: Code::Projects,
+                message: format!(
+                    "Can't create the GitButler worktree, {} already exists",
+                    path.display()
+                ),
+            },
+            AddError::ScopeNotFound(scope) => Error::UserError {
+                code: Code::Projects,
+                message: format!("'{}' is not a directory of the repository", scope.display()),
+            },
+            AddError::InvalidScope(scope) => Error::UserError {
+                code: Code::Validation,
+                message: format!(
+                    "'{}' is not a path relative to the root of the repository",
+                    scope.display()
+                ),
+            },
+            AddError::OpenProjectRepository(error) => error.into(),
+            AddError::NotADirectory => Error::UserError {
+                code: Code::Projects,
+                message: "Not a directory".to_string(),
+            },
+            AddError::PathNotFound => Error::UserError {
+                code: Code::Projects,
+                message: "Path not found".to_string(),
+            },
+            AddError::User(error) => error.into(),
+            AddError::Other(error) => {
+                tracing::error!(?error, "failed to add project");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<GetError> for Error {
+    fn from(value: GetError) -> Self {
+        match value {
+            GetError::NotFound => Error::UserError {
+                code: Code::Projects,
+                message: "Project not found".into(),
+            },
+            GetError::Other(error) => {
+                tracing::error!(?error, "failed to get project");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ListError> for Error {
+    fn from(value: ListError) -> Self {
+        match value {
+            ListError::Other(error) => {
+                tracing::error!(?error, "failed to list projects");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<DeleteError> for Error {
+    fn from(value: DeleteError) -> Self {
+        match value {
+            DeleteError::Other(error) => {
+                tracing::error!(?error, "failed to delete project");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<GroupError> for Error {
+    fn from(value: GroupError) -> Self {
+        match value {
+            GroupError::ProjectNotFound => Error::UserError {
+                code: Code::Projects,
+                message: "Project not found".into(),
+            },
+            GroupError::GroupNotFound => Error::UserError {
+                code: Code::Projects,
+                message: "Group not found".into(),
+            },
+            GroupError::EmptyName => Error::UserError {
+                code: Code::Validation,
+                message: "Group name can't be empty".into(),
+            },
+            GroupError::Other(error) => {
+                tracing::error!(?error, "failed to update project groups");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<RelinkError> for Error {
+    fn from(value: RelinkError) -> Self {
+        match value {
+            RelinkError::NotFound => Error::UserError {
+                code: Code::Projects,
+                message: "Project not found".into(),
+            },
+            RelinkError::PathNotFound => Error::UserError {
+                code: Code::Projects,
+                message: "Path not found".to_string(),
+            },
+            RelinkError::NotAGitRepository => Error::UserError {
+                code: Code::Projects,
+                message: "Must be a git directory".to_string(),
+            },
+            RelinkError::BareRepository => Error::UserError {
+                code: Code::Projects,
+                message: "Projects of bare repositories can't be relinked, add the repository again instead".to_string(),
+            },
+            RelinkError::AlreadyExists => Error::UserError {
+                code: Code::Projects,
+                message: "Another project is already at that path".to_string(),
+            },
+            RelinkError::NotTheSameRepository => Error::UserError {
+                code: Code::Projects,
+                message: "That's a different repository than the one of the project".to_string(),
+            },
+            RelinkError::Other(error) => {
+                tracing::error!(?error, "failed to relink project");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<PresetError> for Error {
+    fn from(value: PresetError) -> Self {
+        match value {
+            PresetError::NotFound => Error::UserError {
+                code: Code::Projects,
+                message: "Project not found".into(),
+            },
+            PresetError::RemoteNotFound(remote) => Error::UserError {
+                code: Code::Projects,
+                message: format!("The preset needs a remote named '{remote}'"),
+            },
+            PresetError::DefaultBranchNotFound(remote) => Error::UserError {
+                code: Code::Projects,
+                message: format!(
+                    "No default branch found on '{remote}', fetch it or pick the target instead"
+                ),
+            },
+            PresetError::Other(error) => {
+                tracing::error!(?error, "failed to apply project preset");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<CloneError> for Error {
+    fn from(value: CloneError) -> Self {
+        match value {
+            CloneError::PathNotEmpty => Error::UserError {
+                code: Code::Projects,
+                message: "The directory to clone into must be empty".to_string(),
+            },
+            CloneError::InvalidUrl => Error::UserError {
+                code: Code::Validation,
+                message: "Invalid repository url".to_string(),
+            },
+            CloneError::BranchNotFound(branch) => Error::UserError {
+                code: Code::Projects,
+                message: format!("Branch '{branch}' not found on the remote"),
+            },
+            CloneError::Remote(error) => error.into(),
+            CloneError::Add(error) => error.into(),
+            CloneError::Other(error) => {
+                tracing::error!(?error, "failed to clone repository");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::id::Id;
+
+use super::Project;
+
+pub type GroupId = Id<Group>;
+
+/// A named set of projects, like client work or experiments. Groups are listed in the order
+/// they are stored in, and every project is in at most one of them.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub id: GroupId,
+    pub name: String,
+}
+
+/// The projects of a group, or the ones that are in no group when `group` is `None`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectGroup {
+    pub group: Option<Group>,
+    pub projects: Vec<Project>,
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    path,
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::git;
+
+// how deep below a directory moved repositories are looked for
+const MAX_SEARCH_DEPTH: usize = 3;
+// how many directories are looked into at most, so huge trees don't stall the search
+const MAX_SEARCHED_DIRS: usize = 5_000;
+
+// the root commit of the head of every repository, by its git directory, with the head it's
+// of: walking a history is slow, and it's walked again for every candidate each time a
+// project is located
+static ROOT_COMMITS: Lazy<Mutex<HashMap<path::PathBuf, (git::Oid, git::Oid)>>> =
+    Lazy::new(Mutex::default);
+
+/// What tells a repository apart from others, to find it again once it moved.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Identity {
+    /// The first commit of the first-parent history of `HEAD`, unset in empty repositories
+    /// and in shallow ones, where the history ends wherever it was cut.
+    pub root_commit: Option<git::Oid>,
+    pub remote_urls: Vec<String>,
+}
+
+/// Where the repository of a project is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum Location {
+    /// At the path of the project.
+    Found,
+    /// Not at the path of the project anymore, `candidates` are the repositories found
+    /// elsewhere that are the same one.
+    Moved { candidates: Vec<path::PathBuf> },
+}
+
+impl Identity {
+    pub fn of(repository: &git::Repository) -> Result<Self> {
+        let remote_urls = repository
+            .remotes()
+            .context("failed to list remotes")?
+            .iter()
+            .filter_map(|name| repository.find_remote(name).ok())
+            .filter_map(|remote| remote.url_as_str().ok().flatten().map(normalize_url))
+            .collect();
+        Ok(Self {
+            root_commit: root_commit(repository)?,
+            remote_urls,
+        })
+    }
+
+    /// Whether `repository` is the one this is the identity of: it starts with the same
+    /// commit or, like when that's unknown on either side, shares a remote with it.
+    pub fn matches(&self, repository: &git::Repository) -> Result<bool> {
+        let other = Self::of(repository)?;
+        let same_root_commit = self.root_commit.is_some() && self.root_commit == other.root_commit;
+        Ok(same_root_commit
+            || self
+                .remote_urls
+                .iter()
+                .any(|url| other.remote_urls.contains(url)))
+    }
+}
+
+fn root_commit(repository: &git::Repository) -> Result<Option<git::Oid>> {
+    // how far a shallow history goes depends on how deep it was fetched
+    if repository.is_shallow() {
+        return Ok(None);
+    }
+    let head = match repository.head() {
+        Ok(head) => head.target(),
+        // unborn head, there are no commits yet
+        Err(git::Error::NotFound(_)) => None,
+        Err(git::Error::Other(error)) if error.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(error) => return Err(error).context("failed to get head"),
+    };
+    let Some(head) = head else {
+        return Ok(None);
+    };
+
+    let git_dir = repository.path();
+    let cached = ROOT_COMMITS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(git_dir)
+        .copied();
+    if let Some((_, root_commit)) = cached.filter(|(cached_head, _)| *cached_head == head) {
+        return Ok(Some(root_commit));
+    }
+
+    let mut revwalk = repository.revwalk().context("failed to create revwalk")?;
+    revwalk.push(head.into()).context("failed to push head")?;
+    revwalk
+        .simplify_first_parent()
+        .context("failed to simplify revwalk")?;
+    let Some(root_commit) = revwalk
+        .last()
+        .transpose()
+        .context("failed to walk history")?
+        .map(git::Oid::from)
+    else {
+        return Ok(None);
+    };
+    ROOT_COMMITS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(git_dir.to_path_buf(), (head, root_commit));
+    Ok(Some(root_commit))
+}
+
+/// Forgets the root commit of the repository at `path`, the path of a project that was
+/// deleted or moved away from.
+pub(super) fn forget_root_commit(path: &path::Path) {
+    ROOT_COMMITS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|git_dir, _| !git_dir.starts_with(path));
+}
+
+// the same remote is often spelled with and without the .git suffix
+fn normalize_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url).to_string()
+}
+
+/// The repositories that could be the one that was at `path`: the ones a few levels below
+/// the closest ancestor of `path` that still exists, and the ones at `path` on the other
+/// drives on Windows, where the letter of a drive can change. The repositories with the
+/// name of `path` come first.
+pub(super) fn search_moved(path: &path::Path) -> Vec<path::PathBuf> {
+    let mut found = other_drives(path)
+        .into_iter()
+        .filter(|candidate| is_repository(candidate))
+        .collect::<Vec<_>>();
+
+    if let Some(ancestor) = path.ancestors().skip(1).find(|ancestor| ancestor.is_dir()) {
+        let mut searched = 0;
+        search_below(ancestor, MAX_SEARCH_DEPTH, &mut searched, &mut found);
+        // and below its parent, for repositories moved into a sibling directory
+        if let Some(parent) = ancestor.parent() {
+            search_below(parent, MAX_SEARCH_DEPTH + 1, &mut searched, &mut found);
+        }
+    }
+
+    let name = path.file_name();
+    found.sort_by_key(|candidate| candidate.file_name() != name);
+    found
+}
+
+// the repositories up to `depth` levels below `root`, looking into `MAX_SEARCHED_DIRS`
+// directories at most over all searches, the closest ones first
+fn search_below(
+    root: &path::Path,
+    depth: usize,
+    searched: &mut usize,
+    found: &mut Vec<path::PathBuf>,
+) {
+    let mut pending = std::collections::VecDeque::from([(root.to_path_buf(), 0)]);
+    while let Some((dir, level)) = pending.pop_front() {
+        if *searched >= MAX_SEARCHED_DIRS {
+            return;
+        }
+        *searched += 1;
+        if is_repository(&dir) {
+            if !found.contains(&dir) {
+                found.push(dir);
+            }
+            // nested repositories are submodules or vendored, not moved projects
+            continue;
+        }
+        if level >= depth {
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        pending.extend(
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+                .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+                .map(|entry| (entry.path(), level + 1)),
+        );
+    }
+}
+
+fn is_repository(path: &path::Path) -> bool {
+    path.join(".git").exists()
+}
+
+// `path` on the other drives, if it's on one
+fn other_drives(path: &path::Path) -> Vec<path::PathBuf> {
+    let mut components = path.components();
+    let Some(path::Component::Prefix(prefix)) = components.next() else {
+        return vec![];
+    };
+    let letter = match prefix.kind() {
+        path::Prefix::Disk(letter) | path::Prefix::VerbatimDisk(letter) => letter,
+        _ => return vec![],
+    };
+    let rest = components.as_path();
+    (b'A'..=b'Z')
+        .filter(|other| !other.eq_ignore_ascii_case(&letter))
+        .map(|other| {
+            path::PathBuf::from(format!("{}:\\", char::from(other)))
+                .join(rest.strip_prefix("\\").unwrap_or(rest))
+        })
+        .filter(|candidate| candidate.is_dir())
+        .collect()
+}
@@ -0,0 +1,162 @@
+//! Presets set a project up for a way of working in one step, instead of picking the
+//! target, the push remote, the force-push policy and whether to sign commits one by one.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{git, project_repository};
+
+use super::{PresetError, Project};
+
+/// A common way of working with a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Preset {
+    /// Branches are pushed to a fork, `origin`, and merged into the repository it's a fork
+    /// of, `upstream`.
+    ForkWorkflow,
+    /// Short-lived branches are merged into the default branch of `origin`.
+    TrunkBased,
+    /// Branches are merged into the newest release branch of `origin`, like `release/2.1`.
+    ReleaseBranch,
+}
+
+/// What a [`Preset`] sets up a repository with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Configuration {
+    pub target: git::RemoteRefname,
+    pub push_remote: String,
+    pub ok_with_force_push: bool,
+    /// Whether commits are signed, `None` if it's left as it is.
+    pub sign_commits: Option<bool>,
+}
+
+/// How a project is set up, what a [`Preset`] changes about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setup {
+    pub(super) push_remote: Option<String>,
+    pub(super) ok_with_force_push: bool,
+    pub(super) sign_commits: Option<bool>,
+}
+
+impl Setup {
+    pub(super) fn of(
+        project: &Project,
+        project_repository: &project_repository::Repository,
+    ) -> Result<Self> {
+        let config = project_repository.config();
+        Ok(Self {
+            push_remote: config.push_remote().context("failed to read push remote")?,
+            ok_with_force_push: *project.ok_with_force_push,
+            sign_commits: config
+                .sign_commits()
+                .context("failed to read commit signing")?,
+        })
+    }
+
+    /// `self` with what `configuration` sets up.
+    pub(super) fn with(&self, configuration: &Configuration) -> Self {
+        Self {
+            push_remote: Some(configuration.push_remote.clone()),
+            ok_with_force_push: configuration.ok_with_force_push,
+            sign_commits: configuration.sign_commits.or(self.sign_commits),
+        }
+    }
+}
+
+impl Preset {
+    pub fn configuration(
+        self,
+        project_repository: &project_repository::Repository,
+    ) -> Result<Configuration, PresetError> {
+        let remote_branches = project_repository
+            .git_remote_branches()
+            .context("failed to list remote branches")?;
+        let repository = &project_repository.git_repository;
+        let configuration = match self {
+            // nobody else pushes to a fork, rewriting its branches is fine
+            Preset::ForkWorkflow => Configuration {
+                target: default_branch(repository, &remote_branches, "upstream")?,
+                push_remote: existing_remote(repository, "origin")?,
+                ok_with_force_push: true,
+                sign_commits: None,
+            },
+            // branches are reviewed by others while they are open, so they are only added to
+            Preset::TrunkBased => Configuration {
+                target: default_branch(repository, &remote_branches, "origin")?,
+                push_remote: existing_remote(repository, "origin")?,
+                ok_with_force_push: false,
+                sign_commits: None,
+            },
+            // what's released is audited, it must be known who committed it
+            Preset::ReleaseBranch => Configuration {
+                target: match newest_release_branch(repository, &remote_branches, "origin") {
+                    Some(release_branch) => release_branch,
+                    None => default_branch(repository, &remote_branches, "origin")?,
+                },
+                push_remote: existing_remote(repository, "origin")?,
+                ok_with_force_push: false,
+                sign_commits: Some(true),
+            },
+        };
+        Ok(configuration)
+    }
+}
+
+fn existing_remote(repository: &git::Repository, remote: &str) -> Result<String, PresetError> {
+    let remotes = repository.remotes().context("failed to list remotes")?;
+    if remotes.iter().any(|name| name == remote) {
+        Ok(remote.to_string())
+    } else {
+        Err(PresetError::RemoteNotFound(remote.to_string()))
+    }
+}
+
+// the branch `HEAD` of `remote` points at or, if it wasn't fetched, main or master
+fn default_branch(
+    repository: &git::Repository,
+    remote_branches: &[git::RemoteRefname],
+    remote: &str,
+) -> Result<git::RemoteRefname, PresetError> {
+    existing_remote(repository, remote)?;
+    let head = format!("refs/remotes/{remote}/HEAD")
+        .parse::<git::Refname>()
+        .ok()
+        .and_then(|head| repository.find_reference(&head).ok())
+        .and_then(|head| head.symbolic_target());
+    if let Some(git::Refname::Remote(head)) = head {
+        if remote_branches.contains(&head) {
+            return Ok(head);
+        }
+    }
+    ["main", "master"]
+        .into_iter()
+        .map(|branch| git::RemoteRefname::new(remote, branch))
+        .find(|branch| remote_branches.contains(branch))
+        .ok_or_else(|| PresetError::DefaultBranchNotFound(remote.to_string()))
+}
+
+// the release branch of `remote` that was committed to last
+fn newest_release_branch(
+    repository: &git::Repository,
+    remote_branches: &[git::RemoteRefname],
+    remote: &str,
+) -> Option<git::RemoteRefname> {
+    remote_branches
+        .iter()
+        .filter(|branch| branch.remote() == remote)
+        .filter(|branch| {
+            branch.branch().starts_with("release/") || branch.branch().starts_with("release-")
+        })
+        .filter_map(|branch| {
+            let tip = repository
+                .find_reference(&branch.clone().into())
+                .ok()?
+                .peel_to_commit()
+                .ok()?;
+            Some((tip.time().seconds(), branch))
+        })
+        .max_by_key(|(time, _)| *time)
+        .map(|(_, branch)| branch.clone())
+}
@@ -0,0 +1,114 @@
+//! Profiles say how generated commit messages are written. Every project has the built-in
+//! ones, one per [`CommitMessageStyle`], and can add its own, with instructions of their own
+//! on top of their style, or replace a built-in one by taking its name.
+
+use serde::{Deserialize, Serialize};
+
+use super::Project;
+
+/// The form of a commit message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommitMessageStyle {
+    /// `type(scope): summary`, like `fix(auth): redirect after login`, then a description.
+    Conventional,
+    /// A summary line in the imperative mood, and nothing else.
+    OneLine,
+    /// A summary line, then a list of bullets with what changed and why.
+    Detailed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitMessageProfile {
+    /// What the profile is picked by, unique between the profiles of a project.
+    pub name: String,
+    pub style: CommitMessageStyle,
+    /// Given to the model on top of what the style asks for, like the language to write in
+    /// or the ticket to mention.
+    #[serde(default)]
+    pub instructions: String,
+}
+
+impl CommitMessageProfile {
+    /// A profile of each style, with no instructions.
+    pub fn builtin() -> Vec<Self> {
+        [
+            ("conventional", CommitMessageStyle::Conventional),
+            ("one-line", CommitMessageStyle::OneLine),
+            ("detailed", CommitMessageStyle::Detailed),
+        ]
+        .into_iter()
+        .map(|(name, style)| Self {
+            name: name.to_string(),
+            style,
+            instructions: String::new(),
+        })
+        .collect()
+    }
+
+    pub(super) fn normalized(&self) -> Self {
+        Self {
+            name: self.name.trim().to_string(),
+            style: self.style,
+            instructions: self.instructions.trim().to_string(),
+        }
+    }
+}
+
+impl Project {
+    /// The profiles of the project, and the built-in ones it doesn't replace, in that order.
+    pub fn commit_message_profiles(&self) -> Vec<CommitMessageProfile> {
+        let builtin = CommitMessageProfile::builtin()
+            .into_iter()
+            .filter(|builtin| {
+                !self
+                    .commit_message_profiles
+                    .iter()
+                    .any(|profile| profile.name == builtin.name)
+            })
+            .collect::<Vec<_>>();
+        self.commit_message_profiles
+            .iter()
+            .cloned()
+            .chain(builtin)
+            .collect()
+    }
+
+    pub fn commit_message_profile(&self, name: &str) -> Option<CommitMessageProfile> {
+        self.commit_message_profiles()
+            .into_iter()
+            .find(|profile| profile.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn projects_replace_builtin_profiles() {
+        let project = Project {
+            commit_message_profiles: vec![CommitMessageProfile {
+                name: "detailed".to_string(),
+                style: CommitMessageStyle::Detailed,
+                instructions: "Mention the ticket".to_string(),
+            }],
+            ..Default::default()
+        };
+        let names = project
+            .commit_message_profiles()
+            .into_iter()
+            .map(|profile| profile.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["detailed", "conventional", "one-line"]);
+        assert_eq!(
+            project
+                .commit_message_profile("detailed")
+                .unwrap()
+                .instructions,
+            "Mention the ticket"
+        );
+        assert!(project.commit_message_profile("missing").is_none());
+    }
+}
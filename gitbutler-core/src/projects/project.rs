@@ -0,0 +1,209 @@
+use std::{path, time};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{git, id::Id, settings::AiProvider, types::default_true::DefaultTrue};
+
+use super::{CommitMessageProfile, GroupId, Identity, PromptTemplates, UnmanagedPaths};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthKey {
+    #[default]
+    Default,
+    Generated,
+    GitCredentialsHelper,
+    Local {
+        private_key_path: path::PathBuf,
+        passphrase: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ApiProject {
+    pub name: String,
+    pub description: Option<String>,
+    pub repository_id: String,
+    pub git_url: String,
+    pub code_git_url: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub sync: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum FetchResult {
+    Fetched {
+        timestamp: time::SystemTime,
+    },
+    Error {
+        timestamp: time::SystemTime,
+        error: String,
+    },
+}
+
+impl FetchResult {
+    pub fn timestamp(&self) -> &time::SystemTime {
+        match self {
+            FetchResult::Fetched { timestamp } | FetchResult::Error { timestamp, .. } => timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Copy, Clone)]
+pub struct CodePushState {
+    pub id: git::Oid,
+    pub timestamp: time::SystemTime,
+}
+
+pub type ProjectId = Id<Project>;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Project {
+    pub id: ProjectId,
+    pub title: String,
+    pub description: Option<String>,
+    pub path: path::PathBuf,
+    /// The bare repository the project was added from, in which case `path` is the worktree
+    /// GitButler manages for it.
+    #[serde(default)]
+    pub bare_repository: Option<path::PathBuf>,
+    #[serde(default)]
+    pub preferred_key: AuthKey,
+    /// if ok_with_force_push is true, we'll not try to avoid force pushing
+    /// for example, when updating base branch
+    #[serde(default)]
+    pub ok_with_force_push: DefaultTrue,
+    pub api: Option<ApiProject>,
+    #[serde(default)]
+    pub gitbutler_data_last_fetch: Option<FetchResult>,
+    #[serde(default)]
+    pub gitbutler_code_push_state: Option<CodePushState>,
+    #[serde(default)]
+    pub project_data_last_fetch: Option<FetchResult>,
+    #[serde(default)]
+    pub omit_certificate_check: Option<bool>,
+    /// if detect_copies is true, new files that are similar to an existing file are shown
+    /// as a copy of it with only what changed
+    #[serde(default)]
+    pub detect_copies: Option<bool>,
+    #[serde(default)]
+    pub diff_options: DiffOptions,
+    /// The group the project is in, if any.
+    #[serde(default)]
+    pub group: Option<GroupId>,
+    /// The version of the settings schema the project was last migrated to.
+    #[serde(default)]
+    pub settings_version: u64,
+    /// What the repository was recognized by the last time it was opened, to relink the
+    /// project if it moves.
+    #[serde(default)]
+    pub identity: Option<Identity>,
+    /// Patterns of the paths that are tracked by git but aren't watched, diffed or owned
+    /// by a branch, like vendored trees.
+    #[serde(default)]
+    pub unmanaged_paths: Vec<String>,
+    /// The directory of the repository, relative to its root, the project is scoped to. A
+    /// repository can be added once per scope, and changes outside of it aren't managed.
+    #[serde(default)]
+    pub scope: Option<path::PathBuf>,
+    /// Whether the project is only looked at: its status, diffs and history are shown, but
+    /// changing its branches, commits or base is refused.
+    #[serde(default)]
+    pub read_only: bool,
+    /// The fingerprints of the secrets that were allowed to be committed and pushed.
+    #[serde(default)]
+    pub allowed_secrets: Vec<String>,
+    /// Whether commits and pushes are scanned for secrets, the `gitbutler.scanSecrets` git
+    /// config if it's not set, which the projects of the repository share.
+    #[serde(default)]
+    pub scan_secrets: Option<bool>,
+    /// What generates for the project instead of the provider of the app settings, the
+    /// app's if it's not set. The providers the app settings pick per feature come first.
+    #[serde(default)]
+    pub ai_provider: Option<AiProvider>,
+    /// What generated pull request descriptions follow, the pull request template of the
+    /// repository if it's not set.
+    #[serde(default)]
+    pub pr_template: Option<String>,
+    /// How commit messages can be generated, on top of the built-in ways, see
+    /// [`Project::commit_message_profiles`].
+    #[serde(default)]
+    pub commit_message_profiles: Vec<CommitMessageProfile>,
+    /// What models are asked to generate from, instead of the default prompts.
+    #[serde(default)]
+    pub prompt_templates: PromptTemplates,
+    /// Patterns of the paths whose changes are never sent to a model, like `secrets` or
+    /// `**/*.pem`, the same as [`Project::unmanaged_paths`].
+    #[serde(default)]
+    pub redacted_paths: Vec<String>,
+    /// The fields a newer version of GitButler wrote that this one doesn't know, written
+    /// back as they were.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// How changes are diffed, unset options have their default. Whitespace and blank lines
+/// are only ignored in the changes of commits.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Lines that only differ in whitespace are the same, like `git diff -w`.
+    pub ignore_whitespace: Option<bool>,
+    /// Added or removed blank lines are left out.
+    pub ignore_blank_lines: Option<bool>,
+    /// The number of unchanged lines shown around the changes of a hunk.
+    pub context_lines: Option<u32>,
+    /// How lines are matched, `diff.algorithm` of the git config if it's not set.
+    pub algorithm: Option<git::diff::Algorithm>,
+}
+
+impl DiffOptions {
+    /// These options, with the ones that are set in `overrides` taking precedence.
+    pub fn with(self, overrides: Self) -> Self {
+        Self {
+            ignore_whitespace: overrides.ignore_whitespace.or(self.ignore_whitespace),
+            ignore_blank_lines: overrides.ignore_blank_lines.or(self.ignore_blank_lines),
+            context_lines: overrides.context_lines.or(self.context_lines),
+            algorithm: overrides.algorithm.or(self.algorithm),
+        }
+    }
+}
+
+impl From<DiffOptions> for git::diff::Options {
+    fn from(value: DiffOptions) -> Self {
+        let default = Self::default();
+        Self {
+            ignore_whitespace: value.ignore_whitespace.unwrap_or(default.ignore_whitespace),
+            ignore_blank_lines: value
+                .ignore_blank_lines
+                .unwrap_or(default.ignore_blank_lines),
+            context_lines: value.context_lines.unwrap_or(default.context_lines),
+            algorithm: value.algorithm,
+            ..default
+        }
+    }
+}
+
+impl AsRef<Project> for Project {
+    fn as_ref(&self) -> &Project {
+        self
+    }
+}
+
+impl Project {
+    pub fn is_sync_enabled(&self) -> bool {
+        self.api.as_ref().map(|api| api.sync).unwrap_or_default()
+    }
+
+    pub fn has_code_url(&self) -> bool {
+        self.api
+            .as_ref()
+            .map(|api| api.code_git_url.is_some())
+            .unwrap_or_default()
+    }
+
+    pub fn unmanaged(&self) -> UnmanagedPaths {
+        UnmanagedPaths::new(&self.unmanaged_paths).within(self.scope.as_deref())
+    }
+}
@@ -0,0 +1,178 @@
+//! Templates of what models are asked to generate from, that a project can replace to
+//! enforce its conventions. `{{name}}` in a template is replaced by the variable `name`, the
+//! variables a template can use depend on its [`PromptKind`].
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// What a template is the prompt of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PromptKind {
+    CommitMessage,
+    BranchName,
+    PullRequest,
+}
+
+impl fmt::Display for PromptKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptKind::CommitMessage => write!(f, "commit message"),
+            PromptKind::BranchName => write!(f, "branch name"),
+            PromptKind::PullRequest => write!(f, "pull request"),
+        }
+    }
+}
+
+impl PromptKind {
+    pub const ALL: [PromptKind; 3] = [
+        PromptKind::CommitMessage,
+        PromptKind::BranchName,
+        PromptKind::PullRequest,
+    ];
+
+    /// The variables templates of the kind can use. `diff` is the changes, or their summary
+    /// for pull requests, `files` the paths they touch, one per line, `branch_name` the name of
+    /// the branch, `commits` the messages of its commits, and `template` the template pull
+    /// request descriptions follow.
+    pub fn variables(self) -> &'static [&'static str] {
+        match self {
+            PromptKind::CommitMessage | PromptKind::BranchName => {
+                &["diff", "files", "branch_name", "commits"]
+            }
+            PromptKind::PullRequest => &["diff", "files", "branch_name", "commits", "template"],
+        }
+    }
+
+    /// Checks that every `{{` of `template` is closed, around a variable of the kind, and
+    /// that it includes the `{{diff}}`, without which there's nothing to generate from.
+    pub fn validate(self, template: &str) -> Result<(), PromptTemplateError> {
+        let mut has_diff = false;
+        for name in variables(template)? {
+            if !self.variables().contains(&name) {
+                return Err(PromptTemplateError::UnknownVariable(name.to_string()));
+            }
+            has_diff |= name == "diff";
+        }
+        if !has_diff {
+            return Err(PromptTemplateError::NoDiff);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PromptTemplateError {
+    #[error("a `{{{{` is not closed with `}}}}`")]
+    Unclosed,
+    #[error("{0:?} is not a variable")]
+    UnknownVariable(String),
+    #[error("the `{{{{diff}}}}` is left out")]
+    NoDiff,
+}
+
+/// The templates a project replaces the default ones with, unset for the default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptTemplates {
+    #[serde(default)]
+    pub commit_message: Option<String>,
+    #[serde(default)]
+    pub branch_name: Option<String>,
+    #[serde(default)]
+    pub pull_request: Option<String>,
+}
+
+impl PromptTemplates {
+    pub fn get(&self, kind: PromptKind) -> Option<&str> {
+        match kind {
+            PromptKind::CommitMessage => self.commit_message.as_deref(),
+            PromptKind::BranchName => self.branch_name.as_deref(),
+            PromptKind::PullRequest => self.pull_request.as_deref(),
+        }
+    }
+
+    pub(super) fn get_mut(&mut self, kind: PromptKind) -> &mut Option<String> {
+        match kind {
+            PromptKind::CommitMessage => &mut self.commit_message,
+            PromptKind::BranchName => &mut self.branch_name,
+            PromptKind::PullRequest => &mut self.pull_request,
+        }
+    }
+}
+
+/// `template` with its variables replaced by their value in `variables`, the ones that have
+/// none by nothing.
+pub fn render_prompt(template: &str, variables: &[(&str, &str)]) -> String {
+    let mut prompt = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some((before, after)) = rest.split_once(OPEN) {
+        let Some((name, after)) = after.split_once(CLOSE) else {
+            break;
+        };
+        prompt.push_str(before);
+        let name = name.trim();
+        if let Some((_, value)) = variables.iter().find(|(variable, _)| *variable == name) {
+            prompt.push_str(value);
+        }
+        rest = after;
+    }
+    prompt.push_str(rest);
+    prompt
+}
+
+// the names of the variables of `template`, in order
+fn variables(template: &str) -> Result<Vec<&str>, PromptTemplateError> {
+    let mut names = vec![];
+    let mut rest = template;
+    while let Some((_, after)) = rest.split_once(OPEN) {
+        let (name, after) = after
+            .split_once(CLOSE)
+            .ok_or(PromptTemplateError::Unclosed)?;
+        if name.contains(OPEN) {
+            return Err(PromptTemplateError::Unclosed);
+        }
+        names.push(name.trim());
+        rest = after;
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_variables() {
+        assert_eq!(
+            render_prompt(
+                "Name the branch of {{ files }}:\n{{diff}}{{commits}}",
+                &[("diff", "+login"), ("files", "src/login.rs")]
+            ),
+            "Name the branch of src/login.rs:\n+login"
+        );
+        assert_eq!(render_prompt("no variables", &[]), "no variables");
+    }
+
+    #[test]
+    fn validates_templates() {
+        let kind = PromptKind::CommitMessage;
+        assert_eq!(kind.validate("{{branch_name}}: {{diff}}"), Ok(()));
+        assert_eq!(
+            kind.validate("{{diff}} {{template}}"),
+            Err(PromptTemplateError::UnknownVariable("template".to_string()))
+        );
+        assert_eq!(
+            PromptKind::PullRequest.validate("{{diff}} {{template}}"),
+            Ok(())
+        );
+        assert_eq!(
+            kind.validate("{{diff}} {{files"),
+            Err(PromptTemplateError::Unclosed)
+        );
+        assert_eq!(kind.validate("{{files}}"), Err(PromptTemplateError::NoDiff));
+    }
+}
@@ -0,0 +1,309 @@
+//! The settings of a project are stored with the rest of it. Every project records the
+//! version of the settings schema it was written with, and is migrated forward to
+//! [`SETTINGS_VERSION`] when it's read.
+
+use std::path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::{
+    unmanaged, AuthKey, CommitMessageProfile, DiffOptions, Project, PromptKind,
+    PromptTemplateError, PromptTemplates,
+};
+use crate::settings::AppSettingsValidationError;
+
+/// The version of the settings schema, the number of migrations there are.
+pub const SETTINGS_VERSION: u64 = 2;
+
+// more than this many context lines is a typo rather than a preference
+const MAX_CONTEXT_LINES: u32 = 10_000;
+
+// the migration at index `n` migrates projects from version `n` to `n + 1`
+const MIGRATIONS: [fn(&mut Map<String, Value>); SETTINGS_VERSION as usize] =
+    [pin_defaults, canonicalize_paths];
+
+/// Migrates the stored `project` to [`SETTINGS_VERSION`], returning whether it changed.
+/// Projects written by newer versions are left as they are, the fields they don't know
+/// of are ignored.
+pub(super) fn migrate(project: &mut Value) -> bool {
+    let Some(project) = project.as_object_mut() else {
+        return false;
+    };
+    let version = project
+        .get("settings_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    if version >= SETTINGS_VERSION {
+        return false;
+    }
+    for migration in MIGRATIONS
+        .iter()
+        .skip(usize::try_from(version).unwrap_or(0))
+    {
+        migration(project);
+    }
+    project.insert("settings_version".to_string(), json!(SETTINGS_VERSION));
+    true
+}
+
+// before settings were versioned, the ones that weren't set had implicit defaults. They
+// are written out, so that changing a default doesn't change the projects relying on it.
+fn pin_defaults(project: &mut Map<String, Value>) {
+    for (key, default) in [
+        ("ok_with_force_push", json!(true)),
+        ("omit_certificate_check", json!(false)),
+        ("detect_copies", json!(false)),
+        ("diff_options", json!({})),
+    ] {
+        if project.get(key).map_or(true, Value::is_null) {
+            project.insert(key.to_string(), default);
+        }
+    }
+}
+
+// projects are kept at their canonical path since they're recognized by it, the ones added
+// before are canonicalized once instead of every time they are compared. A path that can't
+// be, like one of a drive that isn't mounted, is left as it is.
+fn canonicalize_paths(project: &mut Map<String, Value>) {
+    for key in ["path", "bare_repository"] {
+        let Some(canonical) = project
+            .get(key)
+            .and_then(Value::as_str)
+            .and_then(|path| std::fs::canonicalize(path).ok())
+            .and_then(|path| path.to_str().map(str::to_string))
+        else {
+            continue;
+        };
+        project.insert(key.to_string(), json!(canonical));
+    }
+}
+
+/// A partial update of the settings of a project, the settings that are unset are left as
+/// they are. Updates are validated as a whole before anything is applied.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SettingsUpdate {
+    pub preferred_key: Option<AuthKey>,
+    pub ok_with_force_push: Option<bool>,
+    pub omit_certificate_check: Option<bool>,
+    pub detect_copies: Option<bool>,
+    /// Merged into the diff options of the project, like [`DiffOptions::with`].
+    pub diff_options: Option<DiffOptions>,
+    /// Replaces the unmanaged paths of the project.
+    pub unmanaged_paths: Option<Vec<String>>,
+    pub read_only: Option<bool>,
+    /// Replaces the fingerprints of the secrets that are allowed.
+    pub allowed_secrets: Option<Vec<String>>,
+    pub scan_secrets: Option<bool>,
+    /// An empty template unsets it, for the one of the repository to be used.
+    pub pr_template: Option<String>,
+    /// Replaces the commit message profiles of the project.
+    pub commit_message_profiles: Option<Vec<CommitMessageProfile>>,
+    /// Replaces the prompt templates that are set, an empty one unsets it for the default
+    /// prompt to be used.
+    pub prompt_templates: Option<PromptTemplates>,
+    /// Replaces the patterns of the paths whose changes are never sent to a model.
+    pub redacted_paths: Option<Vec<String>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SettingsValidationError {
+    #[error("{0} not found")]
+    KeyNotFound(path::PathBuf),
+    #[error("{0} is not a file")]
+    KeyNotFile(path::PathBuf),
+    #[error("{0} context lines is more than the maximum of {MAX_CONTEXT_LINES}")]
+    ContextLines(u32),
+    #[error("{0:?} is not a path pattern relative to the root of the repository")]
+    UnmanagedPath(String),
+    #[error("{0:?} is not a path pattern relative to the root of the repository")]
+    RedactedPath(String),
+    #[error(transparent)]
+    AiProvider(AppSettingsValidationError),
+    #[error("a commit message profile has no name")]
+    EmptyProfileName,
+    #[error("there are several commit message profiles named {0:?}")]
+    DuplicateProfile(String),
+    #[error("the {kind} prompt template is invalid: {source}")]
+    PromptTemplate {
+        kind: PromptKind,
+        source: PromptTemplateError,
+    },
+}
+
+impl SettingsUpdate {
+    pub fn validate(&self) -> Result<(), SettingsValidationError> {
+        if let Some(AuthKey::Local {
+            private_key_path, ..
+        }) = &self.preferred_key
+        {
+            validate_key(private_key_path)?;
+        }
+        if let Some(context_lines) = self
+            .diff_options
+            .and_then(|diff_options| diff_options.context_lines)
+        {
+            if context_lines > MAX_CONTEXT_LINES {
+                return Err(SettingsValidationError::ContextLines(context_lines));
+            }
+        }
+        if let Some(pattern) = self
+            .unmanaged_paths
+            .iter()
+            .flatten()
+            .find(|pattern| !unmanaged::validate(pattern))
+        {
+            return Err(SettingsValidationError::UnmanagedPath(pattern.clone()));
+        }
+        if let Some(pattern) = self
+            .redacted_paths
+            .iter()
+            .flatten()
+            .find(|pattern| !unmanaged::validate(pattern))
+        {
+            return Err(SettingsValidationError::RedactedPath(pattern.clone()));
+        }
+        if let Some(profiles) = &self.commit_message_profiles {
+            let names = profiles
+                .iter()
+                .map(|profile| profile.name.trim())
+                .collect::<Vec<_>>();
+            if names.iter().any(|name| name.is_empty()) {
+                return Err(SettingsValidationError::EmptyProfileName);
+            }
+            if let Some(name) = names
+                .iter()
+                .enumerate()
+                .find_map(|(i, name)| names[..i].contains(name).then_some(*name))
+            {
+                return Err(SettingsValidationError::DuplicateProfile(name.to_string()));
+            }
+        }
+        if let Some(templates) = &self.prompt_templates {
+            for kind in PromptKind::ALL {
+                if let Some(template) = templates
+                    .get(kind)
+                    .map(str::trim)
+                    .filter(|template| !template.is_empty())
+                {
+                    kind.validate(template).map_err(|source| {
+                        SettingsValidationError::PromptTemplate { kind, source }
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn apply(&self, project: &mut Project) {
+        if let Some(preferred_key) = &self.preferred_key {
+            project.preferred_key = preferred_key.clone();
+        }
+        if let Some(ok_with_force_push) = self.ok_with_force_push {
+            *project.ok_with_force_push = ok_with_force_push;
+        }
+        if let Some(omit_certificate_check) = self.omit_certificate_check {
+            project.omit_certificate_check = Some(omit_certificate_check);
+        }
+        if let Some(detect_copies) = self.detect_copies {
+            project.detect_copies = Some(detect_copies);
+        }
+        if let Some(diff_options) = self.diff_options {
+            project.diff_options = project.diff_options.with(diff_options);
+        }
+        if let Some(unmanaged_paths) = &self.unmanaged_paths {
+            project.unmanaged_paths = unmanaged_paths
+                .iter()
+                .map(|pattern| pattern.trim().to_string())
+                .collect();
+        }
+        if let Some(redacted_paths) = &self.redacted_paths {
+            project.redacted_paths = redacted_paths
+                .iter()
+                .map(|pattern| pattern.trim().to_string())
+                .collect();
+        }
+        if let Some(read_only) = self.read_only {
+            project.read_only = read_only;
+        }
+        if let Some(allowed_secrets) = &self.allowed_secrets {
+            project.allowed_secrets = allowed_secrets.clone();
+        }
+        if let Some(scan_secrets) = self.scan_secrets {
+            project.scan_secrets = Some(scan_secrets);
+        }
+        if let Some(pr_template) = &self.pr_template {
+            project.pr_template = Some(pr_template.trim())
+                .filter(|template| !template.is_empty())
+                .map(ToString::to_string);
+        }
+        if let Some(profiles) = &self.commit_message_profiles {
+            project.commit_message_profiles = profiles
+                .iter()
+                .map(CommitMessageProfile::normalized)
+                .collect();
+        }
+        if let Some(templates) = &self.prompt_templates {
+            for kind in PromptKind::ALL {
+                if let Some(template) = templates.get(kind) {
+                    *project.prompt_templates.get_mut(kind) = Some(template.trim())
+                        .filter(|template| !template.is_empty())
+                        .map(ToString::to_string);
+                }
+            }
+        }
+    }
+}
+
+/// Checks that the private key at `path` is a file that exists.
+pub(super) fn validate_key(path: &path::Path) -> Result<(), SettingsValidationError> {
+    use resolve_path::PathResolveExt;
+    let path = path.resolve();
+    if !path.exists() {
+        return Err(SettingsValidationError::KeyNotFound(path.to_path_buf()));
+    }
+    if !path.is_file() {
+        return Err(SettingsValidationError::KeyNotFile(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_pins_defaults() {
+        let mut project =
+            json!({ "id": "x", "detect_copies": true, "omit_certificate_check": null });
+        assert!(migrate(&mut project));
+        assert_eq!(project["settings_version"], json!(SETTINGS_VERSION));
+        assert_eq!(project["ok_with_force_push"], json!(true));
+        assert_eq!(project["omit_certificate_check"], json!(false));
+        assert_eq!(project["detect_copies"], json!(true));
+        assert_eq!(project["diff_options"], json!({}));
+
+        // migrated projects are left alone
+        assert!(!migrate(&mut project));
+        let mut newer = json!({ "id": "x", "settings_version": SETTINGS_VERSION + 1 });
+        assert!(!migrate(&mut newer));
+        assert!(newer.get("ok_with_force_push").is_none());
+    }
+
+    #[test]
+    fn migrate_canonicalizes_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let canonical = std::fs::canonicalize(dir.path()).unwrap();
+        let spelled = dir.path().join("..").join(dir.path().file_name().unwrap());
+        let mut project = json!({
+            "id": "x",
+            "settings_version": 1,
+            "path": spelled.to_str().unwrap(),
+            "bare_repository": "/does/not/exist",
+        });
+        assert!(migrate(&mut project));
+        assert_eq!(project["path"], json!(canonical.to_str().unwrap()));
+        assert_eq!(project["bare_repository"], json!("/does/not/exist"));
+    }
+}
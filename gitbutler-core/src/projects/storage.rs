@@ -0,0 +1,407 @@
+use std::path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::CorruptStateError,
+    projects::{group, project, settings, Identity, ProjectId},
+    settings::AiProvider,
+    storage,
+};
+
+const PROJECTS_FILE: &str = "projects.json";
+const GROUPS_FILE: &str = "project_groups.json";
+
+// what the credentials of the AI provider of the project `id` are kept as in the keychain,
+// rather than in the projects file
+fn ai_provider_handle(id: &ProjectId) -> String {
+    format!("projects/{id}/ai-provider")
+}
+
+#[derive(Debug, Clone)]
+pub struct Storage {
+    storage: storage::Storage,
+}
+
+impl From<&storage::Storage> for Storage {
+    fn from(storage: &storage::Storage) -> Self {
+        Storage {
+            storage: storage.clone(),
+        }
+    }
+}
+
+impl From<&path::PathBuf> for Storage {
+    fn from(value: &path::PathBuf) -> Self {
+        Self::from(&storage::Storage::from(value))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct UpdateRequest {
+    pub id: ProjectId,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub api: Option<project::ApiProject>,
+    pub gitbutler_data_last_fetched: Option<project::FetchResult>,
+    pub preferred_key: Option<project::AuthKey>,
+    pub ok_with_force_push: Option<bool>,
+    pub gitbutler_code_push_state: Option<project::CodePushState>,
+    pub project_data_last_fetched: Option<project::FetchResult>,
+    pub omit_certificate_check: Option<bool>,
+    pub detect_copies: Option<bool>,
+    pub diff_options: Option<project::DiffOptions>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Storage(#[from] storage::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("stored state is corrupt")]
+    Corrupt(#[from] CorruptStateError),
+    #[error("project not found")]
+    NotFound,
+    #[error("group not found")]
+    GroupNotFound,
+}
+
+impl Storage {
+    pub fn list(&self) -> Result<Vec<project::Project>, Error> {
+        let _lock = self.storage.lock(PROJECTS_FILE)?;
+        let (projects, migrated) = self.read()?;
+        if migrated {
+            self.write(&projects)?;
+        }
+        Ok(projects)
+    }
+
+    // the projects, migrated to the current settings version, and whether any was migrated
+    fn read(&self) -> Result<(Vec<project::Project>, bool), Error> {
+        let Some(projects) = self.storage.read(PROJECTS_FILE)? else {
+            return Ok((vec![], false));
+        };
+        let mut all_projects: Vec<serde_json::Value> =
+            serde_json::from_str(&projects).map_err(CorruptStateError::of(PROJECTS_FILE))?;
+        let mut migrated = false;
+        for project in &mut all_projects {
+            migrated |= settings::migrate(project);
+        }
+        let all_projects = all_projects
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<project::Project>, _>>()
+            .map_err(CorruptStateError::of(PROJECTS_FILE))?
+            .into_iter()
+            .map(|mut p| {
+                // backwards compatibility for description field
+                if let Some(api_description) =
+                    p.api.as_ref().and_then(|api| api.description.as_ref())
+                {
+                    p.description = Some(api_description.to_string());
+                }
+                p.ai_provider = p
+                    .ai_provider
+                    .map(|ai_provider| ai_provider.load_credentials(&ai_provider_handle(&p.id)));
+                p
+            })
+            .collect();
+        Ok((all_projects, migrated))
+    }
+
+    fn write(&self, projects: &[project::Project]) -> Result<(), Error> {
+        let projects = projects
+            .iter()
+            .map(|project| {
+                let mut project = project.clone();
+                if let Some(ai_provider) = &project.ai_provider {
+                    project.ai_provider =
+                        Some(ai_provider.store_credentials(&ai_provider_handle(&project.id))?);
+                }
+                Ok(project)
+            })
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        self.storage
+            .write(PROJECTS_FILE, &serde_json::to_string_pretty(&projects)?)?;
+        Ok(())
+    }
+
+    // reads the projects, changes them with `change` and writes them back, all while
+    // holding the lock of the file, so that changes made at the same time by this or another
+    // process don't overwrite each other. Nothing is written if `change` fails.
+    fn modify<T>(
+        &self,
+        change: impl FnOnce(&mut Vec<project::Project>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let _lock = self.storage.lock(PROJECTS_FILE)?;
+        let (mut projects, _) = self.read()?;
+        let result = change(&mut projects)?;
+        self.write(&projects)?;
+        Ok(result)
+    }
+
+    pub fn get(&self, id: &ProjectId) -> Result<project::Project, Error> {
+        let projects = self.list()?;
+        match projects.into_iter().find(|p| p.id == *id) {
+            Some(project) => Ok(project),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    pub fn update(&self, update_request: &UpdateRequest) -> Result<project::Project, Error> {
+        self.modify(|projects| {
+            let project = projects
+                .iter_mut()
+                .find(|p| p.id == update_request.id)
+                .ok_or(Error::NotFound)?;
+
+            if let Some(title) = &update_request.title {
+                project.title = title.clone();
+            }
+
+            if let Some(description) = &update_request.description {
+                project.description = Some(description.clone());
+            }
+
+            if let Some(api) = &update_request.api {
+                project.api = Some(api.clone());
+            }
+
+            if let Some(preferred_key) = &update_request.preferred_key {
+                project.preferred_key = preferred_key.clone();
+            }
+
+            if let Some(gitbutler_data_last_fetched) =
+                update_request.gitbutler_data_last_fetched.as_ref()
+            {
+                project.gitbutler_data_last_fetch = Some(gitbutler_data_last_fetched.clone());
+            }
+
+            if let Some(project_data_last_fetched) =
+                update_request.project_data_last_fetched.as_ref()
+            {
+                project.project_data_last_fetch = Some(project_data_last_fetched.clone());
+            }
+
+            if let Some(state) = update_request.gitbutler_code_push_state {
+                project.gitbutler_code_push_state = Some(state);
+            }
+
+            if let Some(ok_with_force_push) = update_request.ok_with_force_push {
+                *project.ok_with_force_push = ok_with_force_push;
+            }
+
+            if let Some(omit_certificate_check) = update_request.omit_certificate_check {
+                project.omit_certificate_check = Some(omit_certificate_check);
+            }
+
+            if let Some(detect_copies) = update_request.detect_copies {
+                project.detect_copies = Some(detect_copies);
+            }
+
+            if let Some(diff_options) = update_request.diff_options {
+                project.diff_options = diff_options;
+            }
+
+            Ok(project.clone())
+        })
+    }
+
+    /// Applies `update` to the settings of the project `id`, under the same lock as every
+    /// other change, so it applies as a whole or not at all.
+    pub fn update_settings(
+        &self,
+        id: &ProjectId,
+        update: &settings::SettingsUpdate,
+    ) -> Result<project::Project, Error> {
+        self.modify(|projects| {
+            let project = projects
+                .iter_mut()
+                .find(|p| p.id == *id)
+                .ok_or(Error::NotFound)?;
+            update.apply(project);
+            Ok(project.clone())
+        })
+    }
+
+    pub fn purge(&self, id: &ProjectId) -> Result<(), Error> {
+        self.modify(|projects| {
+            projects.retain(|p| p.id != *id);
+            Ok(())
+        })?;
+        AiProvider::forget_credentials(&ai_provider_handle(id));
+        Ok(())
+    }
+
+    pub fn add(&self, project: &project::Project) -> Result<(), Error> {
+        self.modify(|projects| {
+            projects.push(project.clone());
+            Ok(())
+        })
+    }
+
+    /// Points the project `id` at the repository at `path`, recognized by `identity`.
+    pub fn set_location(
+        &self,
+        id: &ProjectId,
+        path: &path::Path,
+        identity: Option<Identity>,
+    ) -> Result<project::Project, Error> {
+        self.modify(|projects| {
+            let project = projects
+                .iter_mut()
+                .find(|p| p.id == *id)
+                .ok_or(Error::NotFound)?;
+            project.path = path.to_path_buf();
+            project.identity = identity;
+            Ok(project.clone())
+        })
+    }
+
+    /// Moves the projects `ids` into the positions they are at between them, in the order
+    /// of `ids`. The other projects stay where they are.
+    pub fn reorder(&self, ids: &[ProjectId]) -> Result<(), Error> {
+        self.modify(|projects| reorder(projects, ids, |project| project.id).ok_or(Error::NotFound))
+    }
+
+    /// Puts the project `id` in the group `group_id`, or in none.
+    pub fn set_group(
+        &self,
+        id: &ProjectId,
+        group_id: Option<group::GroupId>,
+    ) -> Result<project::Project, Error> {
+        if let Some(group_id) = group_id {
+            self.get_group(&group_id)?;
+        }
+        self.modify(|projects| {
+            let project = projects
+                .iter_mut()
+                .find(|p| p.id == *id)
+                .ok_or(Error::NotFound)?;
+            project.group = group_id;
+            Ok(project.clone())
+        })
+    }
+
+    pub fn set_ai_provider(
+        &self,
+        id: &ProjectId,
+        ai_provider: Option<AiProvider>,
+    ) -> Result<project::Project, Error> {
+        let unset = ai_provider.is_none();
+        let project = self.modify(|projects| {
+            let project = projects
+                .iter_mut()
+                .find(|p| p.id == *id)
+                .ok_or(Error::NotFound)?;
+            project.ai_provider = ai_provider;
+            Ok(project.clone())
+        })?;
+        if unset {
+            AiProvider::forget_credentials(&ai_provider_handle(id));
+        }
+        Ok(project)
+    }
+
+    pub fn list_groups(&self) -> Result<Vec<group::Group>, Error> {
+        match self.storage.read(GROUPS_FILE)? {
+            Some(groups) => {
+                Ok(serde_json::from_str(&groups).map_err(CorruptStateError::of(GROUPS_FILE))?)
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn get_group(&self, id: &group::GroupId) -> Result<group::Group, Error> {
+        self.list_groups()?
+            .into_iter()
+            .find(|g| g.id == *id)
+            .ok_or(Error::GroupNotFound)
+    }
+
+    pub fn add_group(&self, group: &group::Group) -> Result<(), Error> {
+        let _lock = self.storage.lock(GROUPS_FILE)?;
+        let mut groups = self.list_groups()?;
+        groups.push(group.clone());
+        self.storage
+            .write(GROUPS_FILE, &serde_json::to_string_pretty(&groups)?)?;
+        Ok(())
+    }
+
+    pub fn rename_group(&self, id: &group::GroupId, name: &str) -> Result<group::Group, Error> {
+        let _lock = self.storage.lock(GROUPS_FILE)?;
+        let mut groups = self.list_groups()?;
+        let group = groups
+            .iter_mut()
+            .find(|g| g.id == *id)
+            .ok_or(Error::GroupNotFound)?;
+        group.name = name.to_string();
+        let group = group.clone();
+        self.storage
+            .write(GROUPS_FILE, &serde_json::to_string_pretty(&groups)?)?;
+        Ok(group)
+    }
+
+    /// Moves the groups `ids` like [`Storage::reorder`] moves projects.
+    pub fn reorder_groups(&self, ids: &[group::GroupId]) -> Result<(), Error> {
+        let _lock = self.storage.lock(GROUPS_FILE)?;
+        let mut groups = self.list_groups()?;
+        reorder(&mut groups, ids, |group| group.id).ok_or(Error::GroupNotFound)?;
+        self.storage
+            .write(GROUPS_FILE, &serde_json::to_string_pretty(&groups)?)?;
+        Ok(())
+    }
+
+    /// Deletes the group `id`, leaving its projects in no group.
+    pub fn purge_group(&self, id: &group::GroupId) -> Result<(), Error> {
+        self.modify(|projects| {
+            for project in projects.iter_mut().filter(|p| p.group == Some(*id)) {
+                project.group = None;
+            }
+            Ok(())
+        })?;
+
+        let _lock = self.storage.lock(GROUPS_FILE)?;
+        let mut groups = self.list_groups()?;
+        if let Some(index) = groups.iter().position(|g| g.id == *id) {
+            groups.remove(index);
+            self.storage
+                .write(GROUPS_FILE, &serde_json::to_string_pretty(&groups)?)?;
+        }
+        Ok(())
+    }
+}
+
+// moves the items with the ids `ids` into the positions they are at between them, in the
+// order of `ids`, or returns `None` if one of them isn't in `items` or is there twice
+fn reorder<T, I: PartialEq + Copy>(
+    items: &mut Vec<T>,
+    ids: &[I],
+    id: impl Fn(&T) -> I,
+) -> Option<()> {
+    let moved = ids
+        .iter()
+        .map(|i| items.iter().position(|item| id(item) == *i))
+        .collect::<Option<Vec<_>>>()?;
+    let mut positions = moved.clone();
+    positions.sort_unstable();
+    positions.dedup();
+    if positions.len() != moved.len() {
+        return None;
+    }
+
+    let mut slots = std::mem::take(items)
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
+    let taken = moved
+        .iter()
+        .filter_map(|position| slots[*position].take())
+        .collect::<Vec<_>>();
+    for (position, item) in positions.into_iter().zip(taken) {
+        slots[position] = Some(item);
+    }
+    *items = slots.into_iter().flatten().collect();
+    Some(())
+}
@@ -0,0 +1,199 @@
+//! Unmanaged paths are tracked by git but left alone by GitButler: they aren't watched,
+//! diffed or owned by a branch. Unlike ignored files, they are still committed by git.
+//!
+//! A pattern is a path relative to the root of the repository, like `vendor` or
+//! `data/*.csv`, and matches what is at that path and everything below it. In a component
+//! of a pattern `*` matches any characters but `/`, and a component that is `**` matches
+//! any number of components.
+//!
+//! The projects that are scoped to a directory of a repository, like a component of a
+//! monorepo, leave everything outside of it unmanaged too.
+
+use std::path;
+
+use bstr::BString;
+
+use crate::git;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UnmanagedPaths {
+    patterns: Vec<Vec<String>>,
+    scope: Option<path::PathBuf>,
+}
+
+impl UnmanagedPaths {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Self {
+        Self {
+            patterns: patterns
+                .iter()
+                .map(|pattern| components(pattern.as_ref()))
+                .filter(|components| !components.is_empty())
+                .collect(),
+            scope: None,
+        }
+    }
+
+    /// These unmanaged paths, and everything outside of `scope` if it's set.
+    pub fn within(self, scope: Option<&path::Path>) -> Self {
+        Self {
+            scope: scope.map(path::Path::to_path_buf),
+            ..self
+        }
+    }
+
+    /// The directory everything outside of is unmanaged, if there is one.
+    pub fn scope(&self) -> Option<&path::Path> {
+        self.scope.as_deref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty() && self.scope.is_none()
+    }
+
+    /// Whether `path`, relative to the root of the repository, is unmanaged.
+    pub fn contains(&self, path: &path::Path) -> bool {
+        if let Some(scope) = &self.scope {
+            if !path.starts_with(scope) {
+                return true;
+            }
+        }
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let path = path
+            .components()
+            .filter_map(|component| match component {
+                path::Component::Normal(name) => Some(git::path::to_bytes(path::Path::new(name))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        self.patterns
+            .iter()
+            .any(|pattern| matches_prefix(pattern, &path))
+    }
+}
+
+/// Checks that `pattern` can be matched against paths of the repository.
+pub(super) fn validate(pattern: &str) -> bool {
+    let trimmed = pattern.trim();
+    !trimmed.is_empty()
+        && !trimmed.starts_with('/')
+        && !trimmed.split('/').any(|component| component == "..")
+}
+
+/// `scope` as a path relative to the root of the repository, `None` if it isn't one or
+/// is the root itself.
+pub(super) fn normalize_scope(scope: &path::Path) -> Option<path::PathBuf> {
+    let mut normalized = path::PathBuf::new();
+    for component in scope.components() {
+        match component {
+            path::Component::Normal(name) => normalized.push(name),
+            path::Component::CurDir => {}
+            path::Component::ParentDir | path::Component::RootDir | path::Component::Prefix(_) => {
+                return None
+            }
+        }
+    }
+    (!normalized.as_os_str().is_empty()).then_some(normalized)
+}
+
+fn components(pattern: &str) -> Vec<String> {
+    pattern
+        .trim()
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .map(ToString::to_string)
+        .collect()
+}
+
+// whether the components of `pattern` match the first components of `path`, so that
+// everything below a matching directory matches too
+fn matches_prefix(pattern: &[String], path: &[BString]) -> bool {
+    match pattern.split_first() {
+        None => true,
+        Some((component, rest)) if component == "**" => {
+            (0..=path.len()).any(|skipped| matches_prefix(rest, &path[skipped..]))
+        }
+        Some((component, rest)) => path.split_first().is_some_and(|(name, path)| {
+            matches_component(component.as_bytes(), name) && matches_prefix(rest, path)
+        }),
+    }
+}
+
+fn matches_component(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => {
+            (0..=name.len()).any(|skipped| matches_component(rest, &name[skipped..]))
+        }
+        Some((c, rest)) => name
+            .split_first()
+            .is_some_and(|(n, name)| n == c && matches_component(rest, name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contains() {
+        let unmanaged = UnmanagedPaths::new(&["vendor/", "data/*.csv", "**/fixtures", "./big"]);
+        assert!(unmanaged.contains(path::Path::new("vendor")));
+        assert!(unmanaged.contains(path::Path::new("vendor/lib/lib.rs")));
+        assert!(unmanaged.contains(path::Path::new("data/2024.csv")));
+        assert!(unmanaged.contains(path::Path::new("data/2024.csv/part")));
+        assert!(unmanaged.contains(path::Path::new("fixtures/a.json")));
+        assert!(unmanaged.contains(path::Path::new("src/app/fixtures/a.json")));
+        assert!(unmanaged.contains(path::Path::new("big/file")));
+        assert!(!unmanaged.contains(path::Path::new("vendored/lib.rs")));
+        assert!(!unmanaged.contains(path::Path::new("data/2024.json")));
+        assert!(!unmanaged.contains(path::Path::new("src/vendor/lib.rs")));
+        assert!(!unmanaged.contains(path::Path::new("README.md")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn contains_invalid_utf8() {
+        use bstr::ByteSlice;
+
+        let unmanaged = UnmanagedPaths::new(&["data/*.bin"]);
+        let path = git::path::from_bytes(b"data/caf\xe9.bin".as_bstr());
+        assert!(unmanaged.contains(&path));
+        let path = git::path::from_bytes(b"src/caf\xe9.bin".as_bstr());
+        assert!(!unmanaged.contains(&path));
+    }
+
+    #[test]
+    fn within_scope() {
+        let unmanaged = UnmanagedPaths::new(&["services/api/vendor"])
+            .within(Some(path::Path::new("services/api")));
+        assert!(!unmanaged.is_empty());
+        assert!(!unmanaged.contains(path::Path::new("services/api/main.rs")));
+        assert!(unmanaged.contains(path::Path::new("services/api/vendor/lib.rs")));
+        assert!(unmanaged.contains(path::Path::new("services/web/main.rs")));
+        assert!(unmanaged.contains(path::Path::new("services/api-v2/main.rs")));
+        assert!(unmanaged.contains(path::Path::new("README.md")));
+    }
+
+    #[test]
+    fn normalize_scopes() {
+        assert_eq!(
+            normalize_scope(path::Path::new("./services/api/")),
+            Some(path::PathBuf::from("services/api"))
+        );
+        assert_eq!(normalize_scope(path::Path::new(".")), None);
+        assert_eq!(normalize_scope(path::Path::new("/services")), None);
+        assert_eq!(normalize_scope(path::Path::new("services/../web")), None);
+    }
+
+    #[test]
+    fn validate_patterns() {
+        assert!(validate("vendor"));
+        assert!(validate("**/*.bin"));
+        assert!(!validate(""));
+        assert!(!validate("  "));
+        assert!(!validate("/vendor"));
+        assert!(!validate("../sibling"));
+    }
+}
@@ -0,0 +1,65 @@
+//! The secrets of this installation, like the api keys of AI providers, which are kept in
+//! the keychain of the system rather than in the files of the app.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+
+// what the secrets are stored under in the keychain
+const SERVICE: &str = "gitbutler";
+
+// the secrets when they are kept in memory instead of the keychain
+static MEMORY: Lazy<Mutex<Option<HashMap<String, String>>>> = Lazy::new(Mutex::default);
+
+/// Keeps the secrets of the process in memory instead of the keychain, for tests to not
+/// touch the keychain of the machine they run on.
+pub fn use_memory() {
+    MEMORY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert_with(HashMap::new);
+}
+
+/// Stores `secret` as `handle`, or deletes what's stored as `handle` if it's `None`.
+pub fn persist(handle: &str, secret: Option<&str>) -> Result<()> {
+    if let Some(memory) = MEMORY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        match secret {
+            Some(secret) => memory.insert(handle.to_string(), secret.to_string()),
+            None => memory.remove(handle),
+        };
+        return Ok(());
+    }
+    let entry = keyring::Entry::new(SERVICE, handle)?;
+    match secret {
+        Some(secret) => entry.set_password(secret)?,
+        None => match entry.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(error) => return Err(error.into()),
+        },
+    }
+    Ok(())
+}
+
+/// The secret stored as `handle`, if there is one.
+pub fn retrieve(handle: &str) -> Result<Option<String>> {
+    if let Some(memory) = MEMORY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        return Ok(memory.get(handle).cloned());
+    }
+    match keyring::Entry::new(SERVICE, handle)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
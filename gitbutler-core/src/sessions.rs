@@ -5,8 +5,6 @@ mod reader;
 mod session;
 mod writer;
 
-pub mod commands;
-
 #[cfg(test)]
 mod tests;
 
@@ -1,9 +1,9 @@
 use std::path;
 
 use anyhow::Context;
-use tauri::AppHandle;
 
 use crate::{
+    error::Error,
     gb_repository, project_repository,
     projects::{self, ProjectId},
     users,
@@ -19,24 +19,8 @@ pub struct Controller {
     users: users::Controller,
 }
 
-impl TryFrom<&AppHandle> for Controller {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> Result<Self, Self::Error> {
-        let path = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Ok(Self {
-            local_data_dir: path,
-            sessions_database: Database::from(value),
-            projects: projects::Controller::try_from(value)?,
-            users: users::Controller::from(value),
-        })
-    }
-}
-
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum ListError {
     #[error(transparent)]
     ProjectsError(#[from] projects::GetError),
@@ -49,6 +33,20 @@ pub enum ListError {
 }
 
 impl Controller {
+    pub fn new(
+        data_dir: &path::Path,
+        sessions_database: &Database,
+        projects: &projects::Controller,
+        users: &users::Controller,
+    ) -> Self {
+        Self {
+            local_data_dir: data_dir.to_path_buf(),
+            sessions_database: sessions_database.clone(),
+            projects: projects.clone(),
+            users: users.clone(),
+        }
+    }
+
     pub fn list(
         &self,
         project_id: &ProjectId,
@@ -92,3 +90,17 @@ impl Controller {
         Ok(sessions)
     }
 }
+
+impl From<ListError> for Error {
+    fn from(value: ListError) -> Self {
+        match value {
+            ListError::UsersError(error) => Error::from(error),
+            ListError::ProjectsError(error) => Error::from(error),
+            ListError::ProjectRepositoryError(error) => Error::from(error),
+            ListError::Other(error) => {
+                tracing::error!(?error);
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use anyhow::{Context, Result};
-use tauri::{AppHandle, Manager};
 
 use crate::{database, projects::ProjectId};
 
@@ -16,14 +15,6 @@ impl From<database::Database> for Database {
     }
 }
 
-impl From<&AppHandle> for Database {
-    fn from(value: &AppHandle) -> Self {
-        Self {
-            database: value.state::<database::Database>().inner().clone(),
-        }
-    }
-}
-
 impl Database {
     pub fn insert(&self, project_id: &ProjectId, sessions: &[&session::Session]) -> Result<()> {
         self.database.transaction(|tx| -> Result<()> {
@@ -0,0 +1,11 @@
+//! The settings of the app that aren't about one project, which can be synced between the
+//! installations of a user through a git repository.
+
+mod app_settings;
+mod controller;
+mod storage;
+mod sync;
+
+pub use app_settings::*;
+pub use controller::*;
+pub use sync::{CloudSyncResult, SyncError, SyncRepository, SyncResult};
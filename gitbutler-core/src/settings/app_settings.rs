@@ -0,0 +1,789 @@
+use std::time;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{projects::PromptKind, secret};
+
+/// The version of the settings schema. Settings written by a newer version are merged, but
+/// not written back, so that the settings it knows of and this one doesn't aren't lost.
+pub const APP_SETTINGS_VERSION: u32 = 4;
+
+const DEFAULT_FETCH_INTERVAL_SECS: u64 = 15 * 60;
+// fetching more often than this is hammering the remote
+const MIN_FETCH_INTERVAL_SECS: u64 = 60;
+// what fits the context of most models served locally, with room for their reply
+const DEFAULT_AI_TOKEN_BUDGET: u32 = 4000;
+// less than this is not enough for a prompt and a diff
+const MIN_AI_TOKEN_BUDGET: u32 = 500;
+// what the credentials of the provider of the app are stored as in the keychain
+const AI_PROVIDER: &str = "ai-provider";
+
+/// A setting, with when it was last changed on any installation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Synced<T> {
+    pub value: T,
+    /// Milliseconds since the epoch, 0 for a setting that was never changed.
+    pub updated_at_ms: u128,
+}
+
+impl<T> Synced<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            updated_at_ms: 0,
+        }
+    }
+}
+
+// for the settings newer than the settings read
+impl<T: Default> Default for Synced<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: PartialEq + Serialize + Clone> Synced<T> {
+    fn set(&mut self, value: T, now_ms: u128) {
+        if self.value != value {
+            self.value = value;
+            self.updated_at_ms = now_ms;
+        }
+    }
+
+    // the most recently changed of the two, or for changes made at the same time, the one
+    // of the greater value, so every installation picks the same one
+    fn merged(&self, other: &Self) -> Self {
+        let ours = (self.updated_at_ms, serde_json::to_string(&self.value).ok());
+        let theirs = (
+            other.updated_at_ms,
+            serde_json::to_string(&other.value).ok(),
+        );
+        if theirs > ours {
+            other.clone()
+        } else {
+            self.clone()
+        }
+    }
+}
+
+/// The kind of forge a host runs, which decides how its pull requests are linked to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ForgeKind {
+    Github,
+    Gitlab,
+    Bitbucket,
+    Gitea,
+}
+
+/// A self-hosted forge, like `github.example.com`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgeHost {
+    pub host: String,
+    pub kind: ForgeKind,
+}
+
+/// The API a locally hosted model is served with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LocalModelApi {
+    /// Ollama's `/api/chat`.
+    Ollama,
+    /// The `/v1/chat/completions` of llama.cpp's server, and of others that copy OpenAI's.
+    OpenAiCompatible,
+}
+
+/// What generates commit messages and branch names.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum AiProvider {
+    /// GitButler's servers, for users who are logged in.
+    #[default]
+    Cloud,
+    /// A model served on this machine, on a loopback address, the diffs don't go anywhere
+    /// else. Models served elsewhere are [`AiProvider::OpenAiCompatible`].
+    #[serde(rename_all = "camelCase")]
+    Local {
+        api: LocalModelApi,
+        /// Like `http://localhost:11434`.
+        endpoint: String,
+        model: String,
+    },
+    /// An API like OpenAI's, at OpenAI, Azure OpenAI or behind a corporate proxy.
+    #[serde(rename_all = "camelCase")]
+    OpenAiCompatible {
+        /// What `/chat/completions` is appended to, with the query it's sent with, like
+        /// `https://api.openai.com/v1`.
+        base_url: String,
+        model: String,
+        /// Sent with every request, like `Authorization` or Azure's `api-key`. They aren't
+        /// synced between installations.
+        #[serde(default)]
+        headers: Vec<HttpHeader>,
+    },
+    /// Anthropic's Messages API.
+    #[serde(rename_all = "camelCase")]
+    Anthropic {
+        model: String,
+        /// It isn't synced between installations.
+        #[serde(default)]
+        api_key: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+impl AiProvider {
+    pub fn validate(&self) -> Result<(), AppSettingsValidationError> {
+        let (url, model) = match self {
+            AiProvider::Cloud => return Ok(()),
+            AiProvider::Local {
+                endpoint, model, ..
+            } => (endpoint, model),
+            AiProvider::OpenAiCompatible {
+                base_url,
+                model,
+                headers,
+            } => {
+                if let Some(header) = headers.iter().find(|header| {
+                    let name = header.name.trim();
+                    name.is_empty()
+                        || name.contains(|c: char| c.is_whitespace() || c == ':')
+                        || header.value.contains(['\r', '\n'])
+                }) {
+                    return Err(AppSettingsValidationError::Header(header.name.clone()));
+                }
+                (base_url, model)
+            }
+            AiProvider::Anthropic { model, api_key } => {
+                if api_key.trim().is_empty() {
+                    return Err(AppSettingsValidationError::ApiKey);
+                }
+                if model.trim().is_empty() {
+                    return Err(AppSettingsValidationError::ModelName);
+                }
+                return Ok(());
+            }
+        };
+        let url = url.trim();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(AppSettingsValidationError::ModelEndpoint(url.to_string()));
+        }
+        if model.trim().is_empty() {
+            return Err(AppSettingsValidationError::ModelName);
+        }
+        Ok(())
+    }
+
+    /// The provider with its urls and names trimmed, like it's stored.
+    pub fn normalized(&self) -> Self {
+        match self {
+            AiProvider::Cloud => AiProvider::Cloud,
+            AiProvider::Local {
+                api,
+                endpoint,
+                model,
+            } => AiProvider::Local {
+                api: *api,
+                endpoint: endpoint.trim().trim_end_matches('/').to_string(),
+                model: model.trim().to_string(),
+            },
+            AiProvider::OpenAiCompatible {
+                base_url,
+                model,
+                headers,
+            } => AiProvider::OpenAiCompatible {
+                base_url: base_url.trim().to_string(),
+                model: model.trim().to_string(),
+                headers: headers
+                    .iter()
+                    .map(|header| HttpHeader {
+                        name: header.name.trim().to_string(),
+                        value: header.value.trim().to_string(),
+                    })
+                    .collect(),
+            },
+            AiProvider::Anthropic { model, api_key } => AiProvider::Anthropic {
+                model: model.trim().to_string(),
+                api_key: api_key.trim().to_string(),
+            },
+        }
+    }
+
+    // the headers and keys are credentials, that only the installation they were set on has
+    fn without_credentials(&self) -> Self {
+        match self {
+            AiProvider::OpenAiCompatible {
+                base_url, model, ..
+            } => AiProvider::OpenAiCompatible {
+                base_url: base_url.clone(),
+                model: model.clone(),
+                headers: vec![],
+            },
+            AiProvider::Anthropic { model, .. } => AiProvider::Anthropic {
+                model: model.clone(),
+                api_key: String::new(),
+            },
+            provider => provider.clone(),
+        }
+    }
+
+    fn has_credentials(&self) -> bool {
+        match self {
+            AiProvider::OpenAiCompatible { headers, .. } => !headers.is_empty(),
+            AiProvider::Anthropic { api_key, .. } => !api_key.is_empty(),
+            AiProvider::Cloud | AiProvider::Local { .. } => false,
+        }
+    }
+
+    /// The provider as it's written to a file, its credentials stored in the keychain as
+    /// the secret `handle`. They stay in the file if the keychain can't be used.
+    pub(crate) fn store_credentials(&self, handle: &str) -> Result<Self, serde_json::Error> {
+        let secret = if self.has_credentials() {
+            Some(serde_json::to_string(self)?)
+        } else {
+            None
+        };
+        match secret::persist(handle, secret.as_deref()) {
+            Ok(()) => Ok(self.without_credentials()),
+            Err(error) => {
+                tracing::warn!(
+                    ?error,
+                    handle,
+                    "failed to store credentials in the keychain"
+                );
+                Ok(self.clone())
+            }
+        }
+    }
+
+    /// The provider read from a file with the credentials stored as the secret `handle`,
+    /// unless the file has credentials of its own.
+    pub(crate) fn load_credentials(self, handle: &str) -> Self {
+        if self.has_credentials() {
+            return self;
+        }
+        let stored = match secret::retrieve(handle) {
+            Ok(Some(stored)) => stored,
+            Ok(None) => return self,
+            Err(error) => {
+                tracing::warn!(
+                    ?error,
+                    handle,
+                    "failed to read credentials from the keychain"
+                );
+                return self;
+            }
+        };
+        match serde_json::from_str::<Self>(&stored) {
+            Ok(stored) => self.with_credentials_of(&stored),
+            Err(error) => {
+                tracing::warn!(?error, handle, "stored credentials are corrupt");
+                self
+            }
+        }
+    }
+
+    /// Deletes the credentials stored as the secret `handle`, of a provider that was unset
+    /// and leaves none behind.
+    pub(crate) fn forget_credentials(handle: &str) {
+        if let Err(error) = secret::persist(handle, None) {
+            tracing::warn!(
+                ?error,
+                handle,
+                "failed to delete credentials from the keychain"
+            );
+        }
+    }
+
+    // `self` with the credentials of `local` if they are of the same endpoint
+    fn with_credentials_of(mut self, local: &Self) -> Self {
+        match (&mut self, local) {
+            (
+                AiProvider::OpenAiCompatible {
+                    base_url, headers, ..
+                },
+                AiProvider::OpenAiCompatible {
+                    base_url: local_base_url,
+                    headers: local_headers,
+                    ..
+                },
+            ) if base_url == local_base_url => headers.clone_from(local_headers),
+            (
+                AiProvider::Anthropic { api_key, .. },
+                AiProvider::Anthropic {
+                    api_key: local_api_key,
+                    ..
+                },
+            ) => api_key.clone_from(local_api_key),
+            _ => {}
+        }
+        self
+    }
+}
+
+/// The providers that generate some things instead of the one of the app settings, like
+/// commit messages with a local model and pull requests with a bigger one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiFeatureProviders {
+    #[serde(default)]
+    pub commit_message: Option<AiProvider>,
+    #[serde(default)]
+    pub branch_name: Option<AiProvider>,
+    #[serde(default)]
+    pub pull_request: Option<AiProvider>,
+}
+
+impl AiFeatureProviders {
+    pub fn get(&self, kind: PromptKind) -> Option<&AiProvider> {
+        match kind {
+            PromptKind::CommitMessage => self.commit_message.as_ref(),
+            PromptKind::BranchName => self.branch_name.as_ref(),
+            PromptKind::PullRequest => self.pull_request.as_ref(),
+        }
+    }
+
+    fn providers_mut(&mut self) -> [&mut Option<AiProvider>; 3] {
+        [
+            &mut self.commit_message,
+            &mut self.branch_name,
+            &mut self.pull_request,
+        ]
+    }
+
+    // the providers with what their credentials are stored as in the keychain
+    fn with_handles_mut(&mut self) -> [(&mut Option<AiProvider>, &'static str); 3] {
+        [
+            (&mut self.commit_message, "ai-provider/commit-message"),
+            (&mut self.branch_name, "ai-provider/branch-name"),
+            (&mut self.pull_request, "ai-provider/pull-request"),
+        ]
+    }
+
+    fn validate(&self) -> Result<(), AppSettingsValidationError> {
+        for provider in [&self.commit_message, &self.branch_name, &self.pull_request]
+            .into_iter()
+            .flatten()
+        {
+            provider.validate()?;
+        }
+        Ok(())
+    }
+
+    fn map(&self, f: impl Fn(&AiProvider) -> AiProvider) -> Self {
+        let mut providers = self.clone();
+        for provider in providers.providers_mut().into_iter().flatten() {
+            *provider = f(provider);
+        }
+        providers
+    }
+
+    fn with_credentials_of(&self, local: &Self) -> Self {
+        let mut providers = self.clone();
+        let mut local = local.clone();
+        for (provider, local) in providers
+            .providers_mut()
+            .into_iter()
+            .zip(local.providers_mut())
+        {
+            if let (Some(provider), Some(local)) = (provider, local) {
+                *provider = provider.clone().with_credentials_of(local);
+            }
+        }
+        providers
+    }
+}
+
+/// The settings of the app that aren't about a project, and are synced between
+/// installations when [`super::SyncRepository`] is set.
+///
+/// Every setting is merged on its own: the one changed last wins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSettings {
+    pub version: u32,
+    /// How often the remotes of projects are fetched.
+    pub fetch_interval_secs: Synced<u64>,
+    /// Whether commits are signed, in the projects whose `gitbutler.signCommits` git config
+    /// doesn't say otherwise.
+    pub sign_commits: Synced<bool>,
+    pub forge_hosts: Synced<Vec<ForgeHost>>,
+    #[serde(default)]
+    pub ai_provider: Synced<AiProvider>,
+    /// How many tokens the prompts of generations are cut down to, for them to fit in the
+    /// context of the model with its reply.
+    #[serde(default = "default_ai_token_budget")]
+    pub ai_token_budget: Synced<u32>,
+    #[serde(default)]
+    pub ai_feature_providers: Synced<AiFeatureProviders>,
+}
+
+fn default_ai_token_budget() -> Synced<u32> {
+    Synced::new(DEFAULT_AI_TOKEN_BUDGET)
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            version: APP_SETTINGS_VERSION,
+            fetch_interval_secs: Synced::new(DEFAULT_FETCH_INTERVAL_SECS),
+            sign_commits: Synced::new(false),
+            forge_hosts: Synced::new(vec![]),
+            ai_provider: Synced::new(AiProvider::Cloud),
+            ai_token_budget: default_ai_token_budget(),
+            ai_feature_providers: Synced::new(AiFeatureProviders::default()),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn fetch_interval(&self) -> time::Duration {
+        time::Duration::from_secs(self.fetch_interval_secs.value)
+    }
+
+    /// The settings of `self` and `other` merged, the setting changed last winning.
+    pub fn merged(&self, other: &Self) -> Self {
+        Self {
+            version: self.version.max(other.version),
+            fetch_interval_secs: self.fetch_interval_secs.merged(&other.fetch_interval_secs),
+            sign_commits: self.sign_commits.merged(&other.sign_commits),
+            forge_hosts: self.forge_hosts.merged(&other.forge_hosts),
+            ai_provider: {
+                let mut ai_provider = self.ai_provider.merged(&other.ai_provider);
+                ai_provider.value = ai_provider
+                    .value
+                    .with_credentials_of(&self.ai_provider.value);
+                ai_provider
+            },
+            ai_token_budget: self.ai_token_budget.merged(&other.ai_token_budget),
+            ai_feature_providers: {
+                let mut providers = self
+                    .ai_feature_providers
+                    .merged(&other.ai_feature_providers);
+                providers.value = providers
+                    .value
+                    .with_credentials_of(&self.ai_feature_providers.value);
+                providers
+            },
+        }
+    }
+
+    /// The settings as they are written, the credentials of the providers stored in the
+    /// keychain.
+    pub(super) fn store_credentials(&self) -> Result<Self, serde_json::Error> {
+        let mut settings = self.clone();
+        settings.ai_provider.value = settings.ai_provider.value.store_credentials(AI_PROVIDER)?;
+        for (provider, handle) in settings.ai_feature_providers.value.with_handles_mut() {
+            match provider {
+                Some(provider) => *provider = provider.store_credentials(handle)?,
+                None => AiProvider::forget_credentials(handle),
+            }
+        }
+        Ok(settings)
+    }
+
+    /// The settings as they are read, with the credentials of the providers stored in the
+    /// keychain.
+    pub(super) fn load_credentials(mut self) -> Self {
+        self.ai_provider.value = self.ai_provider.value.load_credentials(AI_PROVIDER);
+        for (provider, handle) in self.ai_feature_providers.value.with_handles_mut() {
+            if let Some(value) = provider.take() {
+                *provider = Some(value.load_credentials(handle));
+            }
+        }
+        self
+    }
+
+    /// The settings as they are synced, without what only this installation has.
+    pub(super) fn for_sync(&self) -> Self {
+        let mut settings = self.clone();
+        settings.ai_provider.value = settings.ai_provider.value.without_credentials();
+        settings.ai_feature_providers.value = settings
+            .ai_feature_providers
+            .value
+            .map(AiProvider::without_credentials);
+        settings
+    }
+}
+
+/// A partial update of the app settings, the settings that are unset are left as they are.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppSettingsUpdate {
+    pub fetch_interval_secs: Option<u64>,
+    pub sign_commits: Option<bool>,
+    /// Replaces the forge hosts.
+    pub forge_hosts: Option<Vec<ForgeHost>>,
+    pub ai_provider: Option<AiProvider>,
+    pub ai_token_budget: Option<u32>,
+    /// Replaces the providers of the features.
+    pub ai_feature_providers: Option<AiFeatureProviders>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum AppSettingsValidationError {
+    #[error("fetching every {0} seconds is more often than every {MIN_FETCH_INTERVAL_SECS}")]
+    FetchInterval(u64),
+    #[error("{0:?} is not a host name")]
+    ForgeHost(String),
+    #[error("{0:?} is not an http url")]
+    ModelEndpoint(String),
+    #[error("the model name is empty")]
+    ModelName,
+    #[error("{0:?} is not a header name")]
+    Header(String),
+    #[error("the API key is empty")]
+    ApiKey,
+    #[error("{0} tokens is less than the minimum of {MIN_AI_TOKEN_BUDGET}")]
+    AiTokenBudget(u32),
+}
+
+impl AppSettingsUpdate {
+    pub fn validate(&self) -> Result<(), AppSettingsValidationError> {
+        if let Some(secs) = self.fetch_interval_secs {
+            if secs < MIN_FETCH_INTERVAL_SECS {
+                return Err(AppSettingsValidationError::FetchInterval(secs));
+            }
+        }
+        if let Some(invalid) = self.forge_hosts.iter().flatten().find(|forge| {
+            forge.host.is_empty()
+                || forge
+                    .host
+                    .contains(|c: char| c.is_whitespace() || c == '/' || c == '@')
+        }) {
+            return Err(AppSettingsValidationError::ForgeHost(invalid.host.clone()));
+        }
+        if let Some(ai_provider) = &self.ai_provider {
+            ai_provider.validate()?;
+        }
+        if let Some(providers) = &self.ai_feature_providers {
+            providers.validate()?;
+        }
+        if let Some(tokens) = self.ai_token_budget {
+            if tokens < MIN_AI_TOKEN_BUDGET {
+                return Err(AppSettingsValidationError::AiTokenBudget(tokens));
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn apply(&self, settings: &mut AppSettings, now_ms: u128) {
+        if let Some(secs) = self.fetch_interval_secs {
+            settings.fetch_interval_secs.set(secs, now_ms);
+        }
+        if let Some(sign_commits) = self.sign_commits {
+            settings.sign_commits.set(sign_commits, now_ms);
+        }
+        if let Some(forge_hosts) = &self.forge_hosts {
+            let forge_hosts = forge_hosts
+                .iter()
+                .map(|forge| ForgeHost {
+                    host: forge.host.trim().to_lowercase(),
+                    kind: forge.kind,
+                })
+                .collect();
+            settings.forge_hosts.set(forge_hosts, now_ms);
+        }
+        if let Some(ai_provider) = &self.ai_provider {
+            settings.ai_provider.set(ai_provider.normalized(), now_ms);
+        }
+        if let Some(tokens) = self.ai_token_budget {
+            settings.ai_token_budget.set(tokens, now_ms);
+        }
+        if let Some(providers) = &self.ai_feature_providers {
+            settings
+                .ai_feature_providers
+                .set(providers.map(AiProvider::normalized), now_ms);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_takes_the_latest_change_of_every_setting() {
+        let mut ours = AppSettings::default();
+        let mut theirs = AppSettings::default();
+        AppSettingsUpdate {
+            fetch_interval_secs: Some(120),
+            ..Default::default()
+        }
+        .apply(&mut ours, 1);
+        AppSettingsUpdate {
+            fetch_interval_secs: Some(600),
+            sign_commits: Some(true),
+            ..Default::default()
+        }
+        .apply(&mut theirs, 2);
+        AppSettingsUpdate {
+            forge_hosts: Some(vec![ForgeHost {
+                host: " GitHub.Example.com".to_string(),
+                kind: ForgeKind::Github,
+            }]),
+            ..Default::default()
+        }
+        .apply(&mut ours, 3);
+
+        let merged = ours.merged(&theirs);
+        assert_eq!(merged.fetch_interval_secs.value, 600);
+        assert!(merged.sign_commits.value);
+        assert_eq!(merged.forge_hosts.value[0].host, "github.example.com");
+        assert_eq!(merged, theirs.merged(&ours));
+    }
+
+    #[test]
+    fn merge_of_simultaneous_changes_is_the_same_everywhere() {
+        let mut ours = AppSettings::default();
+        let mut theirs = AppSettings::default();
+        ours.fetch_interval_secs.set(120, 1);
+        theirs.fetch_interval_secs.set(600, 1);
+        assert_eq!(ours.merged(&theirs), theirs.merged(&ours));
+    }
+
+    #[test]
+    fn unchanged_settings_keep_their_time() {
+        let mut settings = AppSettings::default();
+        settings.sign_commits.set(false, 5);
+        assert_eq!(settings.sign_commits.updated_at_ms, 0);
+    }
+
+    #[test]
+    fn validate() {
+        assert!(AppSettingsUpdate {
+            fetch_interval_secs: Some(1),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(AppSettingsUpdate {
+            forge_hosts: Some(vec![ForgeHost {
+                host: "https://github.example.com".to_string(),
+                kind: ForgeKind::Github,
+            }]),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(AppSettingsUpdate {
+            fetch_interval_secs: Some(300),
+            forge_hosts: Some(vec![ForgeHost {
+                host: "gitlab.example.com".to_string(),
+                kind: ForgeKind::Gitlab,
+            }]),
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+        assert!(AppSettingsUpdate {
+            ai_provider: Some(AiProvider::Local {
+                api: LocalModelApi::Ollama,
+                endpoint: "localhost:11434".to_string(),
+                model: "llama3".to_string(),
+            }),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(AppSettingsUpdate {
+            ai_provider: Some(AiProvider::Local {
+                api: LocalModelApi::OpenAiCompatible,
+                endpoint: "http://localhost:8080".to_string(),
+                model: " ".to_string(),
+            }),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(AppSettingsUpdate {
+            ai_token_budget: Some(100),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert_eq!(
+            AppSettingsUpdate {
+                ai_feature_providers: Some(AiFeatureProviders {
+                    pull_request: Some(AiProvider::Anthropic {
+                        model: "claude-3-5-sonnet-latest".to_string(),
+                        api_key: " ".to_string(),
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+            .validate(),
+            Err(AppSettingsValidationError::ApiKey)
+        );
+    }
+
+    #[test]
+    fn api_keys_are_not_synced() {
+        let provider = AiProvider::Anthropic {
+            model: "claude-3-5-sonnet-latest".to_string(),
+            api_key: "secret".to_string(),
+        };
+        let mut ours = AppSettings::default();
+        AppSettingsUpdate {
+            ai_provider: Some(provider.clone()),
+            ai_feature_providers: Some(AiFeatureProviders {
+                pull_request: Some(provider.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+        .apply(&mut ours, 1);
+
+        let synced = ours.for_sync();
+        let without_key = AiProvider::Anthropic {
+            model: "claude-3-5-sonnet-latest".to_string(),
+            api_key: String::new(),
+        };
+        assert_eq!(synced.ai_provider.value, without_key);
+        assert_eq!(
+            synced.ai_feature_providers.value.pull_request,
+            Some(without_key.clone())
+        );
+
+        // the installation the key was set on keeps it, the others don't get it
+        let merged = ours.merged(&synced);
+        assert_eq!(merged.ai_provider.value, provider);
+        assert_eq!(
+            merged
+                .ai_feature_providers
+                .value
+                .get(PromptKind::PullRequest),
+            Some(&provider)
+        );
+        let merged = AppSettings::default().merged(&synced);
+        assert_eq!(merged.ai_provider.value, without_key);
+    }
+
+    #[test]
+    fn settings_of_older_versions_get_the_defaults_of_newer_settings() {
+        let settings: AppSettings = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "fetchIntervalSecs": { "value": 600, "updatedAtMs": 1 },
+            "signCommits": { "value": false, "updatedAtMs": 0 },
+            "forgeHosts": { "value": [], "updatedAtMs": 0 },
+        }))
+        .unwrap();
+        assert_eq!(settings.fetch_interval_secs.value, 600);
+        assert_eq!(settings.ai_provider.value, AiProvider::Cloud);
+        assert_eq!(settings.ai_provider.updated_at_ms, 0);
+        assert_eq!(settings.ai_token_budget.value, DEFAULT_AI_TOKEN_BUDGET);
+        assert_eq!(
+            settings.ai_feature_providers.value,
+            AiFeatureProviders::default()
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::{path, time};
+
+use anyhow::Context;
+
+use crate::{
+    error::{Code, Error},
+    git,
+};
+
+use super::{
+    storage::{self, Storage},
+    sync, AppSettings, AppSettingsUpdate, AppSettingsValidationError, CloudSyncResult, SyncError,
+    SyncRepository, SyncResult,
+};
+
+// the local clone of the repository settings are synced through
+const SYNC_DIR: &str = "settings-sync";
+// the signing preference was the global git config before it was a setting
+const SIGN_COMMITS_KEY: &str = "gitbutler.signCommits";
+
+#[derive(Clone)]
+pub struct Controller {
+    local_data_dir: path::PathBuf,
+    storage: Storage,
+}
+
+impl From<&path::PathBuf> for Controller {
+    fn from(value: &path::PathBuf) -> Self {
+        Self {
+            local_data_dir: value.clone(),
+            storage: Storage::from(value),
+        }
+    }
+}
+
+impl Controller {
+    pub fn get(&self) -> Result<AppSettings, GetError> {
+        Ok(self.load()?.settings)
+    }
+
+    fn load(&self) -> anyhow::Result<storage::Stored> {
+        let mut stored = self.storage.get().context("failed to read settings")?;
+        // until it's changed here, it's whatever it was set to before there were settings
+        if stored.settings.sign_commits.updated_at_ms == 0 {
+            if let Some(sign_commits) = global_sign_commits() {
+                stored.settings.sign_commits.value = sign_commits;
+            }
+        }
+        Ok(stored)
+    }
+
+    pub fn update(&self, update: &AppSettingsUpdate) -> Result<AppSettings, UpdateError> {
+        update.validate()?;
+        // updates and syncs read, change and write the settings file, whichever controller
+        // or process they are made through
+        let _lock = self.storage.lock().context("failed to lock settings")?;
+        let mut stored = self.load()?;
+        update.apply(&mut stored.settings, now_ms()?);
+        self.storage
+            .set(&stored)
+            .context("failed to write settings")?;
+        Ok(stored.settings)
+    }
+
+    pub fn sync_repository(&self) -> Result<Option<SyncRepository>, GetError> {
+        Ok(self
+            .storage
+            .get()
+            .context("failed to read settings")?
+            .sync_repository)
+    }
+
+    /// Sets the repository the settings are synced through, or turns syncing off.
+    pub fn set_sync_repository(
+        &self,
+        repository: Option<&SyncRepository>,
+    ) -> Result<(), UpdateError> {
+        if let Some(repository) = repository {
+            repository
+                .url
+                .parse::<git::Url>()
+                .map_err(|_| UpdateError::InvalidUrl(repository.url.clone()))?;
+        }
+        let _lock = self.storage.lock().context("failed to lock settings")?;
+        let mut stored = self.storage.get().context("failed to read settings")?;
+        if stored.sync_repository.as_ref() != repository {
+            // the clone of another repository has nothing to do with the new one
+            let dir = self.local_data_dir.join(SYNC_DIR);
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir).context("failed to remove settings clone")?;
+            }
+        }
+        stored.sync_repository = repository.cloned();
+        self.storage
+            .set(&stored)
+            .context("failed to write settings")
+            .map_err(Into::into)
+    }
+
+    /// Merges the settings of the sync repository into the local ones, and pushes them back.
+    pub fn sync(&self) -> Result<SyncResult, SyncError> {
+        // the remote can take a while, the settings can be changed meanwhile and are merged
+        // with what it has after
+        let _sync_lock = self
+            .storage
+            .lock_sync(SYNC_DIR)
+            .context("failed to lock settings sync")?;
+        let stored = self.load()?;
+        let repository = stored
+            .sync_repository
+            .clone()
+            .ok_or(SyncError::NotConfigured)?;
+        let result = sync::sync(
+            &self.local_data_dir.join(SYNC_DIR),
+            &repository,
+            &stored.settings,
+        )?;
+
+        let _lock = self.storage.lock().context("failed to lock settings")?;
+        let mut stored = self.load()?;
+        stored.settings = stored.settings.merged(&result.settings);
+        self.storage
+            .set(&stored)
+            .context("failed to write settings")?;
+        Ok(SyncResult {
+            settings: stored.settings,
+            pushed: result.pushed,
+        })
+    }
+
+    pub fn sync_with_cloud(&self) -> Result<bool, GetError> {
+        Ok(self
+            .storage
+            .get()
+            .context("failed to read settings")?
+            .sync_with_cloud)
+    }
+
+    /// Turns syncing the settings through the user's GitButler account on or off.
+    pub fn set_sync_with_cloud(&self, sync_with_cloud: bool) -> Result<(), UpdateError> {
+        let _lock = self.storage.lock().context("failed to lock settings")?;
+        let mut stored = self.storage.get().context("failed to read settings")?;
+        stored.sync_with_cloud = sync_with_cloud;
+        self.storage
+            .set(&stored)
+            .context("failed to write settings")
+            .map_err(Into::into)
+    }
+
+    /// Merges the settings of the user's GitButler account, that the UI got from it, into
+    /// the local ones. The result has the settings for the UI to put back if they changed.
+    pub fn merge_cloud(&self, remote: Option<&AppSettings>) -> Result<CloudSyncResult, SyncError> {
+        let _lock = self.storage.lock().context("failed to lock settings")?;
+        let mut stored = self.load()?;
+        if !stored.sync_with_cloud {
+            return Err(SyncError::NotConfigured);
+        }
+        let (merged, upload) = sync::merge(&stored.settings, remote);
+        stored.settings = merged;
+        self.storage
+            .set(&stored)
+            .context("failed to write settings")?;
+        Ok(CloudSyncResult {
+            settings: stored.settings,
+            upload,
+        })
+    }
+}
+
+fn global_sign_commits() -> Option<bool> {
+    git2::Config::open_default()
+        .ok()?
+        .get_bool(SIGN_COMMITS_KEY)
+        .ok()
+}
+
+fn now_ms() -> anyhow::Result<u128> {
+    Ok(time::UNIX_EPOCH
+        .elapsed()
+        .context("failed to get elapsed time")?
+        .as_millis())
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetError {
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UpdateError {
+    #[error(transparent)]
+    Validation(#[from] AppSettingsValidationError),
+    #[error("{0} is not a git url")]
+    InvalidUrl(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<GetError> for Error {
+    fn from(value: GetError) -> Self {
+        match value {
+            GetError::Other(error) => {
+                tracing::error!(?error, "failed to get settings");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<UpdateError> for Error {
+    fn from(value: UpdateError) -> Self {
+        match value {
+            UpdateError::Validation(error) => Error::UserError {
+                code: Code::Validation,
+                message: error.to_string(),
+            },
+            UpdateError::InvalidUrl(url) => Error::UserError {
+                code: Code::Validation,
+                message: format!("{url} is not a git url"),
+            },
+            UpdateError::Other(error) => {
+                tracing::error!(?error, "failed to update settings");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<SyncError> for Error {
+    fn from(value: SyncError) -> Self {
+        match value {
+            SyncError::NotConfigured => Error::UserError {
+                code: Code::Validation,
+                message: "Turn on syncing settings first".to_string(),
+            },
+            SyncError::Remote(message) => Error::UserError {
+                code: Code::ProjectGitRemote,
+                message: format!("Failed to sync settings: {message}"),
+            },
+            SyncError::InvalidRemote => Error::UserError {
+                code: Code::Validation,
+                message: "The settings in the sync repository can't be read".to_string(),
+            },
+            SyncError::Other(error) => {
+                tracing::error!(?error, "failed to sync settings");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
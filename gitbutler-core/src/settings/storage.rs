@@ -0,0 +1,119 @@
+use std::path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::CorruptStateError, lock, storage};
+
+use super::{AppSettings, SyncRepository};
+
+const APP_SETTINGS_FILE: &str = "app_settings.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct Stored {
+    #[serde(default)]
+    pub settings: AppSettings,
+    /// Where the settings are synced, not synced itself.
+    #[serde(default)]
+    pub sync_repository: Option<SyncRepository>,
+    /// Whether the settings are synced through the user's GitButler account, not synced
+    /// itself.
+    #[serde(default)]
+    pub sync_with_cloud: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Storage {
+    storage: storage::Storage,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Storage(#[from] storage::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("stored state is corrupt")]
+    Corrupt(#[from] CorruptStateError),
+}
+
+impl From<&storage::Storage> for Storage {
+    fn from(storage: &storage::Storage) -> Self {
+        Self {
+            storage: storage.clone(),
+        }
+    }
+}
+
+impl From<&path::PathBuf> for Storage {
+    fn from(value: &path::PathBuf) -> Self {
+        Self::from(&storage::Storage::from(value))
+    }
+}
+
+impl Storage {
+    pub(super) fn get(&self) -> Result<Stored, Error> {
+        match self.storage.read(APP_SETTINGS_FILE)? {
+            Some(data) => {
+                let mut stored: Stored = serde_json::from_str(&data)
+                    .map_err(CorruptStateError::of(APP_SETTINGS_FILE))?;
+                stored.settings = stored.settings.load_credentials();
+                Ok(stored)
+            }
+            None => Ok(Stored::default()),
+        }
+    }
+
+    /// The lock of the settings file, held until it's dropped by whoever reads, changes and
+    /// writes it back.
+    pub(super) fn lock(&self) -> Result<lock::FileLock, Error> {
+        self.storage.lock(APP_SETTINGS_FILE).map_err(Into::into)
+    }
+
+    /// The lock of the clone of the sync repository, held across the syncs that talk to it
+    /// instead of the lock of the settings file, for the settings to be changed meanwhile.
+    pub(super) fn lock_sync(&self, dir: &str) -> Result<lock::FileLock, Error> {
+        self.storage.lock(dir).map_err(Into::into)
+    }
+
+    pub(super) fn set(&self, stored: &Stored) -> Result<(), Error> {
+        let stored = Stored {
+            settings: stored.settings.store_credentials()?,
+            sync_repository: stored.sync_repository.clone(),
+            sync_with_cloud: stored.sync_with_cloud,
+        };
+        let data = serde_json::to_string_pretty(&stored)?;
+        self.storage.write(APP_SETTINGS_FILE, &data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{secret, settings::AiProvider};
+
+    #[test]
+    fn api_keys_are_kept_out_of_the_file() {
+        secret::use_memory();
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::from(&dir.path().to_path_buf());
+        let provider = AiProvider::Anthropic {
+            model: "claude-3-5-sonnet-latest".to_string(),
+            api_key: "secret".to_string(),
+        };
+        let mut stored = Stored::default();
+        stored.settings.ai_provider.value = provider.clone();
+        stored.settings.ai_feature_providers.value.pull_request = Some(provider.clone());
+        storage.set(&stored).unwrap();
+
+        let file = std::fs::read_to_string(dir.path().join(APP_SETTINGS_FILE)).unwrap();
+        assert!(!file.contains("secret"));
+        let stored = storage.get().unwrap();
+        assert_eq!(stored.settings.ai_provider.value, provider);
+        assert_eq!(
+            stored.settings.ai_feature_providers.value.pull_request,
+            Some(provider)
+        );
+    }
+}
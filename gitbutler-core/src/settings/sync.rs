@@ -0,0 +1,404 @@
+//! App settings are synced through a git repository the user provides, in the branch
+//! [`SETTINGS_BRANCH`] so that it can be a repository that is used for something else too,
+//! or through the user's GitButler account. Every sync merges the settings of the remote
+//! into the local ones and pushes the result. The repository is only talked to with the
+//! user's own `git` and credentials, GitButler's servers are talked to from the UI.
+
+use std::{path, process::Command};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    git,
+    virtual_branches::integration::{
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL, GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+    },
+};
+
+use super::{AppSettings, APP_SETTINGS_VERSION};
+
+const SETTINGS_BRANCH: &str = "refs/heads/gitbutler-settings";
+const SETTINGS_FILE: &str = "settings.json";
+// how many times a sync starts over when another installation pushed in between
+const MAX_ATTEMPTS: usize = 3;
+
+/// The git repository settings are synced through.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncRepository {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncResult {
+    pub settings: AppSettings,
+    /// Whether the merged settings were pushed, they aren't when the remote had them
+    /// already or they were written by a newer version of GitButler.
+    pub pushed: bool,
+}
+
+/// The settings merged with the ones of the user's GitButler account, which `upload` is
+/// for the UI to put back if they changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudSyncResult {
+    pub settings: AppSettings,
+    pub upload: Option<AppSettings>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SyncError {
+    #[error("settings sync is not set up")]
+    NotConfigured,
+    #[error("failed to reach the settings repository: {0}")]
+    Remote(String),
+    #[error("the settings in the repository can't be read")]
+    InvalidRemote,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Merges the settings of `repository` and `local`, using `dir` as the local clone of it.
+pub(super) fn sync(
+    dir: &path::Path,
+    repository: &SyncRepository,
+    local: &AppSettings,
+) -> Result<SyncResult, SyncError> {
+    let repo = if dir.exists() {
+        git::Repository::open(dir).context("failed to open settings repository")?
+    } else {
+        git::Repository::init_bare(dir).context("failed to init settings repository")?
+    };
+
+    for _ in 0..MAX_ATTEMPTS {
+        let parent = fetch(dir, &repository.url)?;
+        let remote = parent
+            .map(|parent| read_settings(&repo, parent))
+            .transpose()?;
+        let (merged, upload) = merge(local, remote.as_ref());
+        let Some(upload) = upload else {
+            return Ok(SyncResult {
+                settings: merged,
+                pushed: false,
+            });
+        };
+
+        let commit = write_settings(&repo, &upload, parent)?;
+        match push(dir, &repository.url, commit)? {
+            Pushed::Yes => {
+                return Ok(SyncResult {
+                    settings: merged,
+                    pushed: true,
+                })
+            }
+            Pushed::Rejected => {
+                tracing::info!("settings were pushed by another installation, syncing again");
+            }
+        }
+    }
+    Err(SyncError::Remote(
+        "the settings keep being changed by another installation".to_string(),
+    ))
+}
+
+/// `local` merged with the `remote` settings there are, and the settings to write back to
+/// the remote if they changed.
+pub(super) fn merge(
+    local: &AppSettings,
+    remote: Option<&AppSettings>,
+) -> (AppSettings, Option<AppSettings>) {
+    let merged = match remote {
+        Some(remote) => local.merged(remote),
+        None => local.clone(),
+    };
+    // written back, the settings this version doesn't know of would be lost
+    let newer = remote.is_some_and(|remote| remote.version > APP_SETTINGS_VERSION);
+    let upload = merged.for_sync();
+    let changed = remote != Some(&upload);
+    (merged, (changed && !newer).then_some(upload))
+}
+
+// fetches the settings branch of `url`, returning its head if it has one. The url is the
+// user's, it's after `--` for git not to take it for an option
+fn fetch(dir: &path::Path, url: &str) -> Result<Option<git::Oid>, SyncError> {
+    let output = run(command(dir).args(["ls-remote", "--", url, SETTINGS_BRANCH]))?;
+    let Some(oid) = String::from_utf8_lossy(&output)
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+    else {
+        return Ok(None);
+    };
+    run(command(dir).args([
+        "fetch",
+        "--no-tags",
+        "--",
+        url,
+        &format!("+{SETTINGS_BRANCH}:{SETTINGS_BRANCH}"),
+    ]))?;
+    oid.parse()
+        .map(Some)
+        .context("malformed settings branch head")
+        .map_err(Into::into)
+}
+
+fn read_settings(repo: &git::Repository, commit: git::Oid) -> Result<AppSettings, SyncError> {
+    let commit = repo
+        .find_commit(commit)
+        .map_err(|_| SyncError::InvalidRemote)?;
+    let tree = commit.tree().map_err(|_| SyncError::InvalidRemote)?;
+    let entry = tree
+        .get_name(SETTINGS_FILE)
+        .ok_or(SyncError::InvalidRemote)?;
+    let blob = repo
+        .find_blob(entry.id())
+        .map_err(|_| SyncError::InvalidRemote)?;
+    serde_json::from_slice(blob.content()).map_err(|_| SyncError::InvalidRemote)
+}
+
+fn write_settings(
+    repo: &git::Repository,
+    settings: &AppSettings,
+    parent: Option<git::Oid>,
+) -> Result<git::Oid> {
+    let blob = repo
+        .blob(&serde_json::to_vec_pretty(settings).context("failed to serialize settings")?)
+        .context("failed to write settings")?;
+    let mut builder = repo.treebuilder(None);
+    builder.upsert(SETTINGS_FILE, blob, git::FileMode::Blob);
+    let tree = builder.write().context("failed to write settings tree")?;
+    let tree = repo
+        .find_tree(tree)
+        .context("failed to find settings tree")?;
+    let parent = parent
+        .map(|parent| repo.find_commit(parent))
+        .transpose()
+        .context("failed to find settings commit")?;
+    let signature = git::Signature::now(
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL,
+    )
+    .context("failed to create signature")?;
+    repo.commit(
+        None,
+        &signature,
+        &signature,
+        "GitButler settings",
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+    )
+    .context("failed to commit settings")
+}
+
+enum Pushed {
+    Yes,
+    Rejected,
+}
+
+fn push(dir: &path::Path, url: &str, commit: git::Oid) -> Result<Pushed, SyncError> {
+    let output = command(dir)
+        .args([
+            "push",
+            "--porcelain",
+            "--",
+            url,
+            &format!("{commit}:{SETTINGS_BRANCH}"),
+        ])
+        .output()
+        .context("failed to run git, is it installed?")?;
+    if output.status.success() {
+        return Ok(Pushed::Yes);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.lines().any(|line| line.starts_with('!')) {
+        return Ok(Pushed::Rejected);
+    }
+    Err(SyncError::Remote(
+        String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    ))
+}
+
+fn command(dir: &path::Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("--git-dir")
+        .arg(dir)
+        // there's no terminal to ask for credentials in
+        .env("GIT_TERMINAL_PROMPT", "0");
+    command
+}
+
+fn run(command: &mut Command) -> Result<Vec<u8>, SyncError> {
+    let output = command
+        .output()
+        .context("failed to run git, is it installed?")?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(SyncError::Remote(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{AiProvider, AppSettingsUpdate, HttpHeader};
+
+    fn remote() -> (tempfile::TempDir, SyncRepository) {
+        let dir = tempfile::tempdir().unwrap();
+        git::Repository::init_bare(dir.path()).unwrap();
+        let url = dir.path().to_str().unwrap().to_string();
+        (dir, SyncRepository { url })
+    }
+
+    #[test]
+    fn sync_between_installations() {
+        let (_remote_dir, repository) = remote();
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+
+        let mut settings = AppSettings::default();
+        AppSettingsUpdate {
+            fetch_interval_secs: Some(300),
+            ..Default::default()
+        }
+        .apply(&mut settings, 1);
+        let result = sync(&first.path().join("sync"), &repository, &settings).unwrap();
+        assert!(result.pushed);
+
+        let mut other = AppSettings::default();
+        AppSettingsUpdate {
+            sign_commits: Some(true),
+            ..Default::default()
+        }
+        .apply(&mut other, 2);
+        let result = sync(&second.path().join("sync"), &repository, &other).unwrap();
+        assert!(result.pushed);
+        assert_eq!(result.settings.fetch_interval_secs.value, 300);
+        assert!(result.settings.sign_commits.value);
+
+        let result = sync(&first.path().join("sync"), &repository, &settings).unwrap();
+        assert!(!result.pushed);
+        assert!(result.settings.sign_commits.value);
+    }
+
+    #[test]
+    fn newer_settings_are_not_written_back() {
+        let (_remote_dir, repository) = remote();
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+
+        let newer = AppSettings {
+            version: APP_SETTINGS_VERSION + 1,
+            ..Default::default()
+        };
+        sync(&first.path().join("sync"), &repository, &newer).unwrap();
+
+        let mut settings = AppSettings::default();
+        settings.sign_commits.value = true;
+        settings.sign_commits.updated_at_ms = 1;
+        let result = sync(&second.path().join("sync"), &repository, &settings).unwrap();
+        assert!(!result.pushed);
+        assert!(result.settings.sign_commits.value);
+    }
+
+    #[test]
+    fn headers_of_the_ai_provider_are_not_synced() {
+        let (_remote_dir, repository) = remote();
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+
+        let provider = AiProvider::OpenAiCompatible {
+            base_url: "https://llm.example.com/v1".to_string(),
+            model: "gpt-4".to_string(),
+            headers: vec![HttpHeader {
+                name: "Authorization".to_string(),
+                value: "Bearer secret".to_string(),
+            }],
+        };
+        let mut settings = AppSettings::default();
+        AppSettingsUpdate {
+            ai_provider: Some(provider.clone()),
+            ..Default::default()
+        }
+        .apply(&mut settings, 1);
+        let result = sync(&first.path().join("sync"), &repository, &settings).unwrap();
+        assert!(result.pushed);
+        assert_eq!(result.settings.ai_provider.value, provider);
+
+        let result = sync(
+            &second.path().join("sync"),
+            &repository,
+            &AppSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.settings.ai_provider.value,
+            AiProvider::OpenAiCompatible {
+                base_url: "https://llm.example.com/v1".to_string(),
+                model: "gpt-4".to_string(),
+                headers: vec![],
+            }
+        );
+
+        let result = sync(&first.path().join("sync"), &repository, &settings).unwrap();
+        assert!(!result.pushed);
+        assert_eq!(result.settings.ai_provider.value, provider);
+    }
+
+    #[test]
+    fn url_is_not_an_option() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        let repository = SyncRepository {
+            url: format!("--upload-pack=touch {}", marker.display()),
+        };
+        let result = sync(
+            &dir.path().join("sync"),
+            &repository,
+            &AppSettings::default(),
+        );
+        assert!(matches!(result, Err(SyncError::Remote(_))));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn merge_uploads_only_what_changed() {
+        let settings = AppSettings::default();
+        let (merged, upload) = merge(&settings, None);
+        assert_eq!(merged, settings);
+        assert_eq!(upload, Some(settings.for_sync()));
+
+        let (_, upload) = merge(&settings, Some(&settings.for_sync()));
+        assert_eq!(upload, None);
+
+        let mut remote = AppSettings::default();
+        AppSettingsUpdate {
+            fetch_interval_secs: Some(300),
+            ..Default::default()
+        }
+        .apply(&mut remote, 1);
+        let (merged, upload) = merge(&settings, Some(&remote));
+        assert_eq!(merged.fetch_interval_secs.value, 300);
+        assert_eq!(upload, None);
+
+        let newer = AppSettings {
+            version: APP_SETTINGS_VERSION + 1,
+            ..Default::default()
+        };
+        let mut local = AppSettings::default();
+        AppSettingsUpdate {
+            sign_commits: Some(true),
+            ..Default::default()
+        }
+        .apply(&mut local, 2);
+        let (merged, upload) = merge(&local, Some(&newer));
+        assert!(merged.sign_commits.value);
+        assert_eq!(upload, None);
+    }
+}
@@ -4,11 +4,11 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+use crate::lock;
+
 #[cfg(target_family = "unix")]
 use std::os::unix::prelude::*;
 
-use tauri::AppHandle;
-
 #[derive(Debug, Default, Clone)]
 pub struct Storage {
     local_data_dir: Arc<RwLock<PathBuf>>,
@@ -20,20 +20,6 @@ pub enum Error {
     IO(#[from] std::io::Error),
 }
 
-impl TryFrom<&AppHandle> for Storage {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> Result<Self, Self::Error> {
-        let path = value.path_resolver().app_data_dir();
-        match path {
-            Some(path) => Ok(Self::from(&path)),
-            // None => Error::new("failed to get app data dir"),
-            None => Err(anyhow::anyhow!("failed to get app data dir")),
-            // None => Ok(Self::default()),
-        }
-    }
-}
-
 impl From<&path::PathBuf> for Storage {
     fn from(value: &path::PathBuf) -> Self {
         Storage {
@@ -75,6 +61,23 @@ impl Storage {
         Ok(())
     }
 
+    /// Blocks until the lock of the file `path` is acquired, held until it's dropped. It's
+    /// the same lock for every storage of the same directory, in any process, the app as
+    /// well as the CLI and its daemon.
+    pub fn lock<P: AsRef<Path>>(&self, path: P) -> Result<lock::FileLock, Error> {
+        // not held while the lock is waited for, a writer waiting for it would block the
+        // storage for everyone until the lock is released
+        let local_data_dir = self.local_data_dir.read().unwrap().clone();
+        let mut lock_path = local_data_dir.join(path).into_os_string();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        if let Some(dir) = lock_path.parent() {
+            fs::create_dir_all(dir).map_err(Error::IO)?;
+        }
+        lock::FileLock::acquire(&lock_path, lock::Strategy::for_dir(&local_data_dir))
+            .map_err(Error::IO)
+    }
+
     pub fn delete<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
         let local_data_dir = self.local_data_dir.write().unwrap();
         let file_path = local_data_dir.join(path);
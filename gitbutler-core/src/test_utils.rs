@@ -2,7 +2,10 @@ use std::{collections::HashMap, fs, path};
 
 use tempfile::tempdir;
 
-use crate::{database, gb_repository, git, keys, project_repository, projects, storage, users};
+use crate::{
+    database, gb_repository, git, keys, project_repository, projects, storage, users,
+    virtual_branches,
+};
 
 pub struct Suite {
     pub local_app_data: path::PathBuf,
@@ -156,6 +159,34 @@ pub fn test_repository() -> git::Repository {
     repository
 }
 
+pub fn set_test_target(
+    gb_repo: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> anyhow::Result<()> {
+    let remote_repo = empty_bare_repository();
+    let mut remote = project_repository
+        .git_repository
+        .remote(
+            "origin",
+            &remote_repo.path().to_str().unwrap().parse().unwrap(),
+        )
+        .expect("failed to add remote");
+    remote.push(&["refs/heads/master:refs/heads/master"], None)?;
+
+    virtual_branches::target::Writer::new(gb_repo)?
+        .write_default(&virtual_branches::target::Target {
+            branch: "refs/remotes/origin/master".parse().unwrap(),
+            remote_url: remote_repo.path().to_str().unwrap().parse().unwrap(),
+            sha: remote_repo.head().unwrap().target().unwrap(),
+        })
+        .expect("failed to write target");
+
+    virtual_branches::integration::update_gitbutler_integration(gb_repo, project_repository)
+        .expect("failed to update integration");
+
+    Ok(())
+}
+
 pub fn commit_all(repository: &git::Repository) -> git::Oid {
     let mut index = repository.index().expect("failed to get index");
     index
@@ -1,4 +1,3 @@
-pub mod commands;
 mod controller;
 mod storage;
 mod user;
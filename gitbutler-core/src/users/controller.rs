@@ -1,9 +1,8 @@
 use std::path;
 
 use anyhow::Context;
-use tauri::AppHandle;
 
-use crate::storage;
+use crate::{error::Error, storage};
 
 use super::{storage::Storage, User};
 
@@ -28,14 +27,6 @@ impl From<&storage::Storage> for Controller {
     }
 }
 
-impl From<&AppHandle> for Controller {
-    fn from(app: &AppHandle) -> Self {
-        Self {
-            storage: Storage::from(app),
-        }
-    }
-}
-
 impl Controller {
     pub fn get_user(&self) -> Result<Option<User>, GetError> {
         self.storage
@@ -60,19 +51,55 @@ impl Controller {
 }
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum GetError {
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum SetError {
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum DeleteError {
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+impl From<GetError> for Error {
+    fn from(value: GetError) -> Self {
+        match value {
+            GetError::Other(error) => {
+                tracing::error!(?error, "failed to get user");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<SetError> for Error {
+    fn from(value: SetError) -> Self {
+        match value {
+            SetError::Other(error) => {
+                tracing::error!(?error, "failed to set user");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<DeleteError> for Error {
+    fn from(value: DeleteError) -> Self {
+        match value {
+            DeleteError::Other(error) => {
+                tracing::error!(?error, "failed to delete user");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
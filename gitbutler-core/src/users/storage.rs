@@ -1,9 +1,8 @@
 use std::path;
 
 use anyhow::Result;
-use tauri::{AppHandle, Manager};
 
-use crate::{storage, users::user};
+use crate::{error::CorruptStateError, storage, users::user};
 
 const USER_FILE: &str = "user.json";
 
@@ -18,6 +17,8 @@ pub enum Error {
     Storage(#[from] storage::Error),
     #[error(transparent)]
     Json(#[from] serde_json::Error),
+    #[error("stored state is corrupt")]
+    Corrupt(#[from] CorruptStateError),
 }
 
 impl From<&storage::Storage> for Storage {
@@ -28,12 +29,6 @@ impl From<&storage::Storage> for Storage {
     }
 }
 
-impl From<&AppHandle> for Storage {
-    fn from(value: &AppHandle) -> Self {
-        Self::from(value.state::<storage::Storage>().inner())
-    }
-}
-
 impl From<&path::PathBuf> for Storage {
     fn from(value: &path::PathBuf) -> Self {
         Self::from(&storage::Storage::from(value))
@@ -43,7 +38,9 @@ impl From<&path::PathBuf> for Storage {
 impl Storage {
     pub fn get(&self) -> Result<Option<user::User>, Error> {
         match self.storage.read(USER_FILE)? {
-            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Some(data) => Ok(Some(
+                serde_json::from_str(&data).map_err(CorruptStateError::of(USER_FILE))?,
+            )),
             None => Ok(None),
         }
     }
@@ -0,0 +1,130 @@
+pub mod branch;
+pub use branch::{Branch, BranchId};
+pub mod context;
+pub mod target;
+pub mod words;
+pub use context::*;
+
+pub mod errors;
+
+mod files;
+pub use files::*;
+
+mod line_endings;
+pub use line_endings::*;
+
+mod tags;
+pub use tags::*;
+
+mod recovery;
+pub use recovery::*;
+
+mod doctor;
+pub use doctor::*;
+
+mod blame;
+pub use blame::*;
+
+mod notes;
+pub use notes::*;
+
+mod bundle;
+pub use bundle::*;
+
+mod archive;
+pub use archive::*;
+
+mod metadata;
+pub use metadata::*;
+
+mod secrets;
+pub use secrets::*;
+
+mod redaction;
+pub use redaction::{
+    list_redactions, redact, Redacted, Redaction, RedactionKind, RedactionLogEntry, Redactor,
+    REDACTED,
+};
+
+mod large_files;
+pub use large_files::*;
+
+mod names;
+pub use names::{list_branch_changes, to_branch_name, BranchChanges};
+
+mod pull_request;
+pub use pull_request::{pull_request_context, PullRequestContext};
+
+mod conflict_markers;
+pub use conflict_markers::*;
+
+mod signatures;
+pub use signatures::*;
+
+mod reconcile;
+pub use reconcile::{
+    list_external_changes, reconcile_external_change, ExternalChange, ExternalChangeId,
+    ExternalChangeKind, ReconcileAction,
+};
+
+mod workspace;
+pub use workspace::{recover_workspace, WorkspaceRecovery};
+
+mod trash;
+pub use trash::{
+    list_trash, purge_trash, restore_from_trash, TrashEntry, TrashEntryId, TrashedFile,
+};
+
+mod oplog;
+pub use oplog::{list_snapshots, restore_snapshot, snapshot_diff, Snapshot, SNAPSHOTS_KEPT};
+
+mod pre_push;
+pub use pre_push::{
+    CheckResult, CheckStatus, OutputLine, PrePushCheck, PrePushProgress, PrePushReport,
+};
+
+pub(crate) mod integration;
+pub use integration::{
+    is_own_index, set_workspace_branch, workspace_reference, GITBUTLER_INTEGRATION_REFERENCE,
+};
+
+mod base;
+pub use base::*;
+
+mod outbox;
+pub use outbox::{
+    list_outbox, queue_pull_request, queue_push, remove_outbox_entry, replay_outbox, OutboxEntry,
+    OutboxEntryId, OutgoingOperation, Replay, ReplayOutcome,
+};
+
+mod audit;
+pub use audit::{
+    list_audit_entries, record_operation, AuditEntry, AuditQuery, BaseChange, BranchChange,
+    BranchState,
+};
+
+mod queue;
+pub use queue::{FinishedOperation, OnFinished, OperationId, QueuedOperation, Requester};
+
+pub mod controller;
+pub use controller::Controller;
+
+mod iterator;
+pub use iterator::BranchIterator as Iterator;
+
+#[cfg(test)]
+mod tests;
+
+mod r#virtual;
+pub use r#virtual::*;
+
+mod remote;
+pub use remote::*;
+
+mod progress;
+pub use progress::{Progress, ProgressSnapshot};
+
+pub mod stream;
+
+mod state;
+pub use state::{get_project_state, ProjectState};
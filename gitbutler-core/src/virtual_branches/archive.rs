@@ -0,0 +1,290 @@
+//! Archives of the whole virtual branch state of a project, to carry work in progress to
+//! another clone of the repository without pushing it.
+//!
+//! An archive is a git bundle. Next to the `refs/gitbutler/` reference of every branch, with
+//! its uncommitted changes in a WIP commit on top, it has a commit holding the state of the
+//! branches that references can't carry: their notes, ownership, order, upstream and
+//! whether they are applied.
+
+use std::path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dedup::dedup,
+    gb_repository,
+    git::{self, bundle},
+    keys, project_repository, users,
+};
+
+use super::{
+    branch::{self, BranchId, Ownership},
+    bundle::{import_branches, unbundle, write_branch_references},
+    errors,
+    integration::{
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL, GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+    },
+    recovery::read_branches,
+};
+
+// only exists while an archive is written
+const STATE_REFERENCE: &str = "refs/gitbutler-archive/state";
+const STATE_FILE: &str = "virtual-branches.json";
+const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct State {
+    version: u32,
+    target_sha: git::Oid,
+    branches: Vec<ArchivedBranch>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ArchivedBranch {
+    pub(super) id: BranchId,
+    pub(super) name: String,
+    pub(super) notes: String,
+    pub(super) applied: bool,
+    pub(super) upstream: Option<git::RemoteRefname>,
+    pub(super) upstream_head: Option<git::Oid>,
+    pub(super) created_timestamp_ms: u128,
+    pub(super) updated_timestamp_ms: u128,
+    pub(super) tree: git::Oid,
+    pub(super) head: git::Oid,
+    pub(super) ownership: Ownership,
+    pub(super) order: usize,
+    pub(super) selected_for_changes: Option<i64>,
+}
+
+impl From<&branch::Branch> for ArchivedBranch {
+    fn from(branch: &branch::Branch) -> Self {
+        Self {
+            id: branch.id,
+            name: branch.name.clone(),
+            notes: branch.notes.clone(),
+            applied: branch.applied,
+            upstream: branch.upstream.clone(),
+            upstream_head: branch.upstream_head,
+            created_timestamp_ms: branch.created_timestamp_ms,
+            updated_timestamp_ms: branch.updated_timestamp_ms,
+            tree: branch.tree,
+            head: branch.head,
+            ownership: branch.ownership.clone(),
+            order: branch.order,
+            selected_for_changes: branch.selected_for_changes,
+        }
+    }
+}
+
+impl From<ArchivedBranch> for branch::Branch {
+    fn from(archived: ArchivedBranch) -> Self {
+        Self {
+            id: archived.id,
+            name: archived.name,
+            notes: archived.notes,
+            applied: archived.applied,
+            upstream: archived.upstream,
+            upstream_head: archived.upstream_head,
+            created_timestamp_ms: archived.created_timestamp_ms,
+            updated_timestamp_ms: archived.updated_timestamp_ms,
+            tree: archived.tree,
+            head: archived.head,
+            ownership: archived.ownership,
+            order: archived.order,
+            selected_for_changes: archived.selected_for_changes,
+        }
+    }
+}
+
+/// Writes every virtual branch of the project, applied or not, to the archive `path`,
+/// which [`import_virtual_branches`] restores them from.
+///
+/// Like bundles, archives leave out the history of the target, which the repository they
+/// are imported into must have.
+pub fn export_virtual_branches(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    path: &path::Path,
+) -> Result<(), errors::ExportBundleError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::ExportBundleError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+    let branches = read_branches(gb_repository)?;
+    if branches.is_empty() {
+        return Err(errors::ExportBundleError::NoBranches);
+    }
+
+    let repo = &project_repository.git_repository;
+    let mut refnames =
+        write_branch_references(project_repository, &branches, "export virtual branches")?;
+
+    let state = State {
+        version: ARCHIVE_VERSION,
+        target_sha: default_target.sha,
+        branches: branches.iter().map(Into::into).collect(),
+    };
+    let state_reference = git::Refname::Other(STATE_REFERENCE.to_string());
+    write_state(repo, &state, &state_reference)?;
+    refnames.push(STATE_REFERENCE.to_string());
+
+    let result = bundle::create(repo, path, &refnames, &[default_target.sha])
+        .context("failed to create archive");
+    if let Err(error) = repo
+        .find_reference(&state_reference)
+        .and_then(|mut reference| reference.delete())
+    {
+        tracing::warn!(?error, "failed to delete archive state reference");
+    }
+    result.map_err(Into::into)
+}
+
+fn write_state(repo: &git::Repository, state: &State, refname: &git::Refname) -> Result<()> {
+    let committer = &git::Signature::now(
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL,
+    )
+    .context("failed to create signature")?;
+    let blob = repo
+        .blob(&serde_json::to_vec_pretty(state).context("failed to serialize state")?)
+        .context("failed to write state")?;
+    let mut builder = repo.treebuilder(None);
+    builder.upsert(STATE_FILE, blob, git::FileMode::Blob);
+    let tree = builder.write().context("failed to write state tree")?;
+    let tree = repo.find_tree(tree).context("failed to find state tree")?;
+    repo.commit(
+        Some(refname),
+        committer,
+        committer,
+        "GitButler virtual branches",
+        &tree,
+        &[],
+    )
+    .context("failed to commit state")?;
+    Ok(())
+}
+
+/// Restores the virtual branches of the archive `path` next to the ones of the project,
+/// returning their ids. The branches that were applied when they were exported are applied
+/// again with their uncommitted changes, unless they conflict with the workspace.
+pub fn import_virtual_branches(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    path: &path::Path,
+    signing_key: Option<&keys::PrivateKey>,
+    user: Option<&users::User>,
+) -> Result<Vec<BranchId>, errors::ImportBundleError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::ImportBundleError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let repo = &project_repository.git_repository;
+    let heads = unbundle(project_repository, path)?;
+    let Some((_, state_oid)) = heads.iter().find(|(refname, _)| refname == STATE_REFERENCE) else {
+        return Err(errors::ImportBundleError::InvalidBundle(
+            "not an archive of virtual branches".to_string(),
+        ));
+    };
+    let mut state = read_state(repo, *state_oid)?;
+    if state.version > ARCHIVE_VERSION {
+        return Err(errors::ImportBundleError::InvalidBundle(
+            "the archive was written by a newer version of GitButler".to_string(),
+        ));
+    }
+    if state.target_sha != default_target.sha {
+        tracing::info!(
+            project_id = %project_repository.project().id,
+            archive_target = %state.target_sha,
+            target = %default_target.sha,
+            "importing virtual branches exported from another target"
+        );
+    }
+
+    let mut branches = read_branches(gb_repository)?;
+    let had_branches = !branches.is_empty();
+    let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    state.branches.sort_by_key(|branch| branch.order);
+    import_branches(
+        gb_repository,
+        project_repository,
+        signing_key,
+        user,
+        |imported| {
+            for archived in state.branches {
+                repo.find_commit(archived.head)
+                    .and_then(|_| repo.find_tree(archived.tree))
+                    .map_err(|_| {
+                        errors::ImportBundleError::InvalidBundle(format!(
+                            "the archive is missing the commits of {}",
+                            archived.name
+                        ))
+                    })?;
+
+                // the same branch may have been imported, or exported from here, before
+                let id = if branches.iter().any(|branch| branch.id == archived.id) {
+                    BranchId::generate()
+                } else {
+                    archived.id
+                };
+                let mut branch = branch::Branch {
+                    id,
+                    name: dedup(
+                        &branches
+                            .iter()
+                            .map(|branch| branch.name.as_str())
+                            .collect::<Vec<_>>(),
+                        &archived.name,
+                    ),
+                    notes: archived.notes,
+                    applied: false,
+                    upstream: archived.upstream,
+                    // what was pushed from the other clone may not have been fetched here
+                    upstream_head: archived
+                        .upstream_head
+                        .filter(|upstream_head| repo.find_commit(*upstream_head).is_ok()),
+                    created_timestamp_ms: archived.created_timestamp_ms,
+                    updated_timestamp_ms: archived.updated_timestamp_ms,
+                    tree: archived.tree,
+                    head: archived.head,
+                    ownership: archived.ownership,
+                    order: branches.len(),
+                    // the branches of the project keep taking new changes
+                    selected_for_changes: archived.selected_for_changes.filter(|_| !had_branches),
+                };
+                writer
+                    .write(&mut branch)
+                    .context("failed to write branch")?;
+                // pushed before anything else can fail, for it to be deleted again if it does
+                imported.push((branch.id, archived.applied));
+                project_repository.add_branch_reference(&branch)?;
+                branches.push(branch);
+            }
+            Ok(())
+        },
+    )
+}
+
+fn read_state(repo: &git::Repository, oid: git::Oid) -> Result<State, errors::ImportBundleError> {
+    let invalid = || {
+        errors::ImportBundleError::InvalidBundle(
+            "the state of the virtual branches can't be read".to_string(),
+        )
+    };
+    let commit = repo.find_commit(oid).map_err(|_| invalid())?;
+    let tree = commit.tree().map_err(|_| invalid())?;
+    let entry = tree.get_name(STATE_FILE).ok_or_else(invalid)?;
+    let blob = repo.find_blob(entry.id()).map_err(|_| invalid())?;
+    serde_json::from_slice(blob.content()).map_err(|_| invalid())
+}
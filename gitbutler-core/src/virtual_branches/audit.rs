@@ -0,0 +1,394 @@
+//! Every operation that changes a project is recorded in its audit log, to tell what GitButler
+//! did to the repository and when: who asked for it, and the branches and commits it changed.
+//!
+//! The log is only ever appended to, one JSON entry per line in the data of the project, and
+//! never leaves this machine. Operations that failed are recorded too, with what they changed
+//! before failing, which usually is nothing. Failing to read the project before an operation
+//! doesn't fail it, it's recorded without what the branches were before.
+//!
+//! The operations of the virtual branches are recorded by a [`Recorder`] that lives as long as
+//! their turn on the project. The others, like changing the settings of the project or the
+//! watchers flushing its session, are recorded with [`record_operation`].
+
+use std::{collections::HashMap, fs, io, path, time};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    gb_repository, git,
+    project_repository::{self, LogUntil},
+    projects::{self, ProjectId},
+    reader, sessions, users,
+};
+
+use super::{branch, errors, iterator, BranchId, Requester};
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// When the operation started, once it was its turn.
+    pub timestamp_ms: u128,
+    pub duration_ms: u128,
+    /// What the operation is, the name of its command, like `create_commit`.
+    pub kind: String,
+    pub requested_by: Requester,
+    /// The email of the user signed in to GitButler, if any.
+    pub user: Option<String>,
+    /// The branches it created, changed or deleted.
+    pub branches: Vec<BranchChange>,
+    /// Where the base branch moved from and to, if it did.
+    pub base: Option<BaseChange>,
+    /// Whether what the project was before the operation couldn't be read. The branches are
+    /// then all listed as they are after it, without `before`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub before_unknown: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchChange {
+    pub branch_id: BranchId,
+    /// `None` for a branch the operation created.
+    pub before: Option<BranchState>,
+    /// `None` for a branch the operation deleted.
+    pub after: Option<BranchState>,
+    /// The commits the operation made on the branch, the most recent first.
+    pub commits: Vec<git::Oid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchState {
+    pub name: String,
+    pub applied: bool,
+    pub head: git::Oid,
+    pub upstream_head: Option<git::Oid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseChange {
+    pub before: Option<git::Oid>,
+    pub after: Option<git::Oid>,
+}
+
+/// Which entries of the audit log to list, all of them by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuditQuery {
+    /// Only the operations that started at or after this, in milliseconds since the epoch.
+    pub since_ms: Option<u128>,
+    /// Only the operations that started at or before this, in milliseconds since the epoch.
+    pub until_ms: Option<u128>,
+    /// Only the operations that changed this branch.
+    pub branch_id: Option<BranchId>,
+    /// Only the operations of this kind, like `create_commit`.
+    pub kind: Option<String>,
+    /// Only the most recent entries that match, at most this many.
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.since_ms
+            .map_or(true, |since| entry.timestamp_ms >= since)
+            && self
+                .until_ms
+                .map_or(true, |until| entry.timestamp_ms <= until)
+            && self.branch_id.map_or(true, |branch_id| {
+                entry
+                    .branches
+                    .iter()
+                    .any(|change| change.branch_id == branch_id)
+            })
+            && self.kind.as_ref().map_or(true, |kind| entry.kind == *kind)
+    }
+}
+
+/// The entries of the audit log that match `query`, in the order the operations ran.
+pub fn list_audit_entries(
+    gb_repository: &gb_repository::Repository,
+    query: &AuditQuery,
+) -> Result<Vec<AuditEntry>, errors::AuditError> {
+    let log = match fs::read_to_string(gb_repository.root().join(AUDIT_FILE)) {
+        Ok(log) => log,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => {
+            return Err(anyhow::Error::from(error)
+                .context("failed to read audit log")
+                .into())
+        }
+    };
+    let mut entries = log
+        .lines()
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| match serde_json::from_str::<AuditEntry>(line) {
+            Ok(entry) => Some(entry),
+            Err(error) => {
+                // like the last line, if the app was killed while writing it
+                tracing::warn!(?error, "skipping malformed audit entry");
+                None
+            }
+        })
+        .filter(|entry| query.matches(entry))
+        .collect::<Vec<_>>();
+    if let Some(limit) = query.limit {
+        entries.drain(..entries.len().saturating_sub(limit));
+    }
+    Ok(entries)
+}
+
+/// Records an operation that changed the project without touching its branches in its audit
+/// log, an entry without branches that took no time. Failing to is only logged, it doesn't
+/// fail the operation.
+pub fn record_operation(
+    local_data_dir: &path::Path,
+    project_id: &ProjectId,
+    user: Option<&users::User>,
+    kind: &'static str,
+    requested_by: Requester,
+) {
+    let root = gb_repository::Repository::root_of(local_data_dir, project_id);
+    // a project that was never opened has no data to record it in yet
+    if !root.exists() {
+        return;
+    }
+    let entry = AuditEntry {
+        timestamp_ms: now_ms(),
+        duration_ms: 0,
+        kind: kind.to_string(),
+        requested_by,
+        user: user.map(|user| user.email.clone()),
+        branches: vec![],
+        base: None,
+        before_unknown: false,
+    };
+    if let Err(error) = append(&root, &entry) {
+        tracing::error!(?error, %project_id, kind, "failed to record operation in audit log");
+    }
+}
+
+fn now_ms() -> u128 {
+    time::UNIX_EPOCH
+        .elapsed()
+        .map_or(0, |elapsed| elapsed.as_millis())
+}
+
+fn append(root: &path::Path, entry: &AuditEntry) -> Result<()> {
+    crate::fs::append_json_line(&root.join(AUDIT_FILE), entry)
+}
+
+/// Records an operation in the audit log of its project when dropped, with what changed since
+/// it was started. Dropped before the permit of the operation, it sees what the operation did
+/// and nothing else.
+#[must_use]
+pub(super) struct Recorder {
+    local_data_dir: path::PathBuf,
+    project: projects::Project,
+    user: Option<users::User>,
+    kind: &'static str,
+    requested_by: Requester,
+    timestamp_ms: u128,
+    started_at: time::Instant,
+    // `None` if it couldn't be read
+    before: Option<Snapshot>,
+    // whether an operation that changed nothing is left out, like the listings of the
+    // branches that only sometimes update them
+    only_changes: bool,
+}
+
+impl Recorder {
+    pub fn start(
+        local_data_dir: &path::Path,
+        project_repository: &project_repository::Repository,
+        user: Option<&users::User>,
+        kind: &'static str,
+        requested_by: Requester,
+    ) -> Self {
+        let before = gb_repository::Repository::open(local_data_dir, project_repository, user)
+            .context("failed to open gitbutler repository")
+            .and_then(|gb_repository| Snapshot::read(&gb_repository))
+            .map_err(|error| {
+                tracing::error!(
+                    ?error,
+                    project_id = %project_repository.project().id,
+                    kind,
+                    "failed to read the project before the operation, recording it without"
+                );
+            })
+            .ok();
+        Self {
+            local_data_dir: local_data_dir.to_path_buf(),
+            project: project_repository.project().clone(),
+            user: user.cloned(),
+            kind,
+            requested_by,
+            timestamp_ms: now_ms(),
+            started_at: time::Instant::now(),
+            before,
+            only_changes: false,
+        }
+    }
+
+    /// Leaves the operation out of the log if it changed neither the branches nor the base.
+    pub fn only_changes(mut self) -> Self {
+        self.only_changes = true;
+        self
+    }
+
+    fn record(&self) -> Result<()> {
+        let project_repository = project_repository::Repository::open(&self.project)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            self.user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        let after = Snapshot::read(&gb_repository)?;
+        let Some(snapshot) = &self.before else {
+            let mut branches = after
+                .branches
+                .into_iter()
+                .map(|(branch_id, state)| BranchChange {
+                    branch_id,
+                    before: None,
+                    after: Some(state),
+                    commits: vec![],
+                })
+                .collect::<Vec<_>>();
+            branches.sort_by_key(|change| change.branch_id);
+            let entry = AuditEntry {
+                timestamp_ms: self.timestamp_ms,
+                duration_ms: self.started_at.elapsed().as_millis(),
+                kind: self.kind.to_string(),
+                requested_by: self.requested_by,
+                user: self.user.as_ref().map(|user| user.email.clone()),
+                branches,
+                base: None,
+                before_unknown: true,
+            };
+            return append(&gb_repository.root(), &entry);
+        };
+
+        let mut branches = vec![];
+        for (branch_id, state) in &after.branches {
+            let before = snapshot.branches.get(branch_id);
+            if before == Some(state) {
+                continue;
+            }
+            branches.push(BranchChange {
+                branch_id: *branch_id,
+                before: before.cloned(),
+                after: Some(state.clone()),
+                commits: made_commits(
+                    &project_repository,
+                    state.head,
+                    before.map(|before| before.head),
+                    after.base,
+                )?,
+            });
+        }
+        for (branch_id, state) in &snapshot.branches {
+            if !after.branches.contains_key(branch_id) {
+                branches.push(BranchChange {
+                    branch_id: *branch_id,
+                    before: Some(state.clone()),
+                    after: None,
+                    commits: vec![],
+                });
+            }
+        }
+        // the same order for the same changes
+        branches.sort_by_key(|change| change.branch_id);
+        if self.only_changes && branches.is_empty() && snapshot.base == after.base {
+            return Ok(());
+        }
+
+        let entry = AuditEntry {
+            timestamp_ms: self.timestamp_ms,
+            duration_ms: self.started_at.elapsed().as_millis(),
+            kind: self.kind.to_string(),
+            requested_by: self.requested_by,
+            user: self.user.as_ref().map(|user| user.email.clone()),
+            branches,
+            base: (snapshot.base != after.base).then_some(BaseChange {
+                before: snapshot.base,
+                after: after.base,
+            }),
+            before_unknown: false,
+        };
+        append(&gb_repository.root(), &entry)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(error) = self.record() {
+            tracing::error!(
+                ?error,
+                project_id = %self.project.id,
+                kind = self.kind,
+                "failed to record operation in audit log"
+            );
+        }
+    }
+}
+
+struct Snapshot {
+    branches: HashMap<BranchId, BranchState>,
+    base: Option<git::Oid>,
+}
+
+impl Snapshot {
+    fn read(gb_repository: &gb_repository::Repository) -> Result<Self> {
+        let session = gb_repository
+            .get_or_create_current_session()
+            .context("failed to get or create current session")?;
+        let session_reader = sessions::Reader::open(gb_repository, &session)
+            .context("failed to open current session")?;
+        let branches = iterator::BranchIterator::new(&session_reader)
+            .context("failed to create branch iterator")?
+            .collect::<Result<Vec<branch::Branch>, reader::Error>>()
+            .context("failed to read virtual branches")?
+            .into_iter()
+            .map(|branch| {
+                (
+                    branch.id,
+                    BranchState {
+                        name: branch.name,
+                        applied: branch.applied,
+                        head: branch.head,
+                        upstream_head: branch.upstream_head,
+                    },
+                )
+            })
+            .collect();
+        let base = super::get_default_target(&session_reader)
+            .context("failed to read default target")?
+            .map(|target| target.sha);
+        Ok(Self { branches, base })
+    }
+}
+
+// the commits of `head` that neither the branch had before nor the base branch has, like
+// those a rebase onto a new base brought in
+fn made_commits(
+    project_repository: &project_repository::Repository,
+    head: git::Oid,
+    before: Option<git::Oid>,
+    base: Option<git::Oid>,
+) -> Result<Vec<git::Oid>> {
+    let mut commits = match (before, base) {
+        (Some(before), _) => project_repository.l(head, LogUntil::Commit(before))?,
+        (None, Some(base)) => project_repository.l(head, LogUntil::Commit(base))?,
+        (None, None) => return Ok(vec![]),
+    };
+    if let (Some(_), Some(base)) = (before, base) {
+        let not_in_base = project_repository.l(head, LogUntil::Commit(base))?;
+        commits.retain(|commit| not_in_base.contains(commit));
+    }
+    Ok(commits)
+}
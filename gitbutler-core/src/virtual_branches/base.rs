@@ -1,4 +1,4 @@
-use std::time;
+use std::{collections::HashMap, path, time};
 
 use anyhow::{Context, Result};
 use serde::Serialize;
@@ -16,9 +16,7 @@ use crate::{
     virtual_branches::branch::Ownership,
 };
 
-use super::{
-    branch, errors, integration::GITBUTLER_INTEGRATION_REFERENCE, target, BranchId, RemoteCommit,
-};
+use super::{branch, errors, target, BranchId, RemoteCommit};
 
 #[derive(Debug, Serialize, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -32,6 +30,23 @@ pub struct BaseBranch {
     pub upstream_commits: Vec<RemoteCommit>,
     pub recent_commits: Vec<RemoteCommit>,
     pub last_fetched_ms: Option<u128>,
+    /// Whether the upstream or recent commits stopped at the end of a shallow history.
+    pub history_truncated: bool,
+}
+
+/// Outcome of fetching one of the remotes of a project.
+#[derive(Debug, Serialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteFetch {
+    pub remote: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchSummary {
+    pub base_branch: BaseBranch,
+    pub remotes: Vec<RemoteFetch>,
 }
 
 pub fn get_base_branch_data(
@@ -124,84 +139,40 @@ pub fn set_base_branch(
     let target_writer =
         target::Writer::new(gb_repository).context("failed to create target writer")?;
     target_writer.write_default(&target)?;
+    project_repository.invalidate_merge_bases();
 
     let head_name: git::Refname = current_head
         .name()
         .context("Failed to get HEAD reference name")?;
     if !head_name
         .to_string()
-        .eq(&GITBUTLER_INTEGRATION_REFERENCE.to_string())
+        .eq(&super::integration::workspace_reference(project_repository)?.to_string())
     {
         // if there are any commits on the head branch or uncommitted changes in the working directory, we need to
         // put them into a virtual branch
 
-        let wd_diff = diff::workdir(repo, &current_head_commit.id())?;
+        let wd_diff = diff::workdir_with_progress(
+            repo,
+            &current_head_commit.id(),
+            &diff::Options {
+                unmanaged: project_repository.project().unmanaged(),
+                ..Default::default()
+            },
+            &|_, _| {},
+        )?;
         if !wd_diff.is_empty() || current_head_commit.id() != target.sha {
-            let hunks_by_filepath =
-                super::virtual_hunks_by_filepath(&project_repository.project().path, &wd_diff);
-
-            // assign ownership to the branch
-            let ownership = hunks_by_filepath.values().flatten().fold(
-                Ownership::default(),
-                |mut ownership, hunk| {
-                    ownership.put(
-                        &format!("{}:{}", hunk.file_path.display(), hunk.id)
-                            .parse()
-                            .unwrap(),
-                    );
-                    ownership
-                },
-            );
-
-            let now_ms = time::UNIX_EPOCH
-                .elapsed()
-                .context("failed to get elapsed time")?
-                .as_millis();
-
-            let (upstream, upstream_head) = if let git::Refname::Local(head_name) = &head_name {
-                let upstream_name = target_branch_ref.with_branch(head_name.branch());
-                if upstream_name.eq(target_branch_ref) {
-                    (None, None)
-                } else {
-                    match repo.find_reference(&git::Refname::from(&upstream_name)) {
-                        Ok(upstream) => {
-                            let head = upstream
-                                .peel_to_commit()
-                                .map(|commit| commit.id())
-                                .context(format!(
-                                    "failed to peel upstream {} to commit",
-                                    upstream.name().unwrap()
-                                ))?;
-                            Ok((Some(upstream_name), Some(head)))
-                        }
-                        Err(git::Error::NotFound(_)) => Ok((None, None)),
-                        Err(error) => Err(error),
-                    }
-                    .context(format!("failed to find upstream for {}", head_name))?
-                }
-            } else {
-                (None, None)
-            };
-
-            let mut branch = branch::Branch {
-                id: BranchId::generate(),
-                name: head_name.to_string().replace("refs/heads/", ""),
-                notes: String::new(),
-                applied: true,
-                upstream,
-                upstream_head,
-                created_timestamp_ms: now_ms,
-                updated_timestamp_ms: now_ms,
-                head: current_head_commit.id(),
-                tree: super::write_tree_onto_commit(
-                    project_repository,
-                    current_head_commit.id(),
-                    &wd_diff,
-                )?,
-                ownership,
-                order: 0,
-                selected_for_changes: None,
+            let local_branch = match &head_name {
+                git::Refname::Local(head_name) => Some(head_name),
+                _ => None,
             };
+            let mut branch = branch_from_head(
+                project_repository,
+                target_branch_ref,
+                head_name.to_string().replace("refs/heads/", ""),
+                local_branch,
+                current_head_commit.id(),
+                &wd_diff,
+            )?;
 
             let branch_writer =
                 branch::Writer::new(gb_repository).context("failed to create branch writer")?;
@@ -217,6 +188,82 @@ pub fn set_base_branch(
     Ok(base)
 }
 
+/// An applied branch named `name` made of what's checked out instead of the workspace: the
+/// commit `head`, with the changes `wd_diff` of the working directory, owned by the branch,
+/// on top. It's the upstream of `local_branch` if it has one on the remote of the target.
+pub(super) fn branch_from_head(
+    project_repository: &project_repository::Repository,
+    target_branch_ref: &git::RemoteRefname,
+    name: String,
+    local_branch: Option<&git::LocalRefname>,
+    head: git::Oid,
+    wd_diff: &HashMap<path::PathBuf, Vec<diff::Hunk>>,
+) -> Result<branch::Branch> {
+    let repo = &project_repository.git_repository;
+    let hunks_by_filepath =
+        super::virtual_hunks_by_filepath(&project_repository.project().path, wd_diff);
+
+    // assign ownership to the branch
+    let ownership =
+        hunks_by_filepath
+            .values()
+            .flatten()
+            .fold(Ownership::default(), |mut ownership, hunk| {
+                ownership.put(
+                    &format!("{}:{}", git::path::escape(&hunk.file_path), hunk.id)
+                        .parse()
+                        .unwrap(),
+                );
+                ownership
+            });
+
+    let now_ms = time::UNIX_EPOCH
+        .elapsed()
+        .context("failed to get elapsed time")?
+        .as_millis();
+
+    let (upstream, upstream_head) = if let Some(local_branch) = local_branch {
+        let upstream_name = target_branch_ref.with_branch(local_branch.branch());
+        if upstream_name.eq(target_branch_ref) {
+            (None, None)
+        } else {
+            match repo.find_reference(&git::Refname::from(&upstream_name)) {
+                Ok(upstream) => {
+                    let head = upstream
+                        .peel_to_commit()
+                        .map(|commit| commit.id())
+                        .context(format!(
+                            "failed to peel upstream {} to commit",
+                            upstream.name().unwrap()
+                        ))?;
+                    Ok((Some(upstream_name), Some(head)))
+                }
+                Err(git::Error::NotFound(_)) => Ok((None, None)),
+                Err(error) => Err(error),
+            }
+            .context(format!("failed to find upstream for {}", local_branch))?
+        }
+    } else {
+        (None, None)
+    };
+
+    Ok(branch::Branch {
+        id: BranchId::generate(),
+        name,
+        notes: String::new(),
+        applied: true,
+        upstream,
+        upstream_head,
+        created_timestamp_ms: now_ms,
+        updated_timestamp_ms: now_ms,
+        head,
+        tree: super::write_tree_onto_commit(project_repository, head, wd_diff)?,
+        ownership,
+        order: 0,
+        selected_for_changes: None,
+    })
+}
+
 fn set_exclude_decoration(project_repository: &project_repository::Repository) -> Result<()> {
     let repo = &project_repository.git_repository;
     let mut config = repo.config()?;
@@ -522,12 +569,48 @@ pub fn update_base_branch(
         sha: new_target_commit.id(),
         ..target
     })?;
+    project_repository.invalidate_merge_bases();
 
     super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
 
     Ok(())
 }
 
+/// Fetches more of the history of the target from its remote when the project is a shallow
+/// clone, `by` more generations of commits or all of it, so that branches that forked off
+/// before the start of the clone get a merge base.
+pub fn deepen_history(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    by: Option<u32>,
+    credentials: &git::credentials::Helper,
+    progress: &project_repository::FetchProgress,
+) -> Result<super::BaseBranch, errors::DeepenHistoryError> {
+    let target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::DeepenHistoryError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let head = project_repository
+        .git_repository
+        .find_branch(&target.branch.clone().into())
+        .context(format!("failed to find branch {}", target.branch))?
+        .peel_to_commit()
+        .context(format!("failed to peel branch {} to commit", target.branch))?
+        .id();
+    project_repository
+        .deepen(target.branch.remote(), head, by, credentials, progress)
+        .map_err(errors::DeepenHistoryError::Remote)?;
+
+    target_to_base_branch(project_repository, &target)
+        .context("failed to convert target to base branch")
+        .map_err(Into::into)
+}
+
 pub fn target_to_base_branch(
     project_repository: &project_repository::Repository,
     target: &target::Target,
@@ -538,20 +621,24 @@ pub fn target_to_base_branch(
     let oid = commit.id();
 
     // gather a list of commits between oid and target.sha
-    let upstream_commits = project_repository
-        .log(oid, project_repository::LogUntil::Commit(target.sha))
-        .context("failed to get upstream commits")?
-        .iter()
-        .map(super::commit_to_remote_commit)
-        .collect::<Result<Vec<_>>>()?;
+    let upstream_log = project_repository
+        .history_log(oid, project_repository::LogUntil::Commit(target.sha))
+        .context("failed to get upstream commits")?;
+    let mut upstream_commits = to_remote_commits(repo, &upstream_log.commits)?;
+    if let Some(mut signatures) =
+        super::verify_signatures(project_repository, target.sha, &upstream_log.commits)
+            .context("failed to verify upstream signatures")?
+    {
+        for (oid, commit) in upstream_log.commits.iter().zip(&mut upstream_commits) {
+            commit.signature = signatures.remove(oid);
+        }
+    }
 
     // get some recent commits
-    let recent_commits = project_repository
-        .log(target.sha, LogUntil::Take(20))
-        .context("failed to get recent commits")?
-        .iter()
-        .map(super::commit_to_remote_commit)
-        .collect::<Result<Vec<_>>>()?;
+    let recent_log = project_repository
+        .history_log(target.sha, LogUntil::Take(20))
+        .context("failed to get recent commits")?;
+    let recent_commits = to_remote_commits(repo, &recent_log.commits)?;
 
     let base = super::BaseBranch {
         branch_name: format!("{}/{}", target.branch.remote(), target.branch.branch()),
@@ -569,6 +656,20 @@ pub fn target_to_base_branch(
             .map(FetchResult::timestamp)
             .copied()
             .map(|t| t.duration_since(time::UNIX_EPOCH).unwrap().as_millis()),
+        history_truncated: upstream_log.truncated || recent_log.truncated,
     };
     Ok(base)
 }
+
+fn to_remote_commits(
+    repo: &git::Repository,
+    oids: &[git::Oid],
+) -> Result<Vec<super::RemoteCommit>> {
+    let mailmap = repo.mailmap().context("failed to read mailmap")?;
+    oids.iter()
+        .map(|oid| {
+            let commit = repo.find_commit(*oid).context("failed to find commit")?;
+            super::commit_to_remote_commit(&commit, &mailmap)
+        })
+        .collect()
+}
@@ -0,0 +1,132 @@
+use std::{collections::HashMap, ops::RangeInclusive, path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{git, project_repository};
+
+use super::{errors, Author};
+
+/// Lines of a file that were last changed by the same commit.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameHunk {
+    /// The first of the lines, counting from 1.
+    pub start_line: usize,
+    pub lines: usize,
+    pub commit_id: git::Oid,
+    pub author: Author,
+    /// When the commit was made, in milliseconds.
+    pub created_at: u128,
+    pub summary: String,
+    /// Whether the lines are older than the history there is, in a shallow clone.
+    pub boundary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Blame {
+    pub hunks: Vec<BlameHunk>,
+    /// The number of lines of the file, for the lines that weren't blamed yet to be asked for.
+    pub total_lines: usize,
+}
+
+/// The commits that last changed the lines of the file at `path` as it is in the commit `rev`,
+/// and who made them. Only `lines` are blamed when they are given, counting from 1, the history
+/// of a large file can be walked a screenful of lines at a time that way. Lines past the end of
+/// the file have no blame.
+pub fn blame(
+    project_repository: &project_repository::Repository,
+    path: &path::Path,
+    rev: git::Oid,
+    lines: Option<RangeInclusive<usize>>,
+) -> Result<Blame, errors::BlameError> {
+    let repo = &project_repository.git_repository;
+    let commit = match repo.find_commit(rev) {
+        Ok(commit) => commit,
+        Err(git::Error::NotFound(_)) => return Err(errors::BlameError::CommitNotFound(rev)),
+        Err(error) => Err(error).context("failed to find commit")?,
+    };
+    let tree = commit.tree().context("failed to find tree")?;
+    let entry = match tree.get_path(path) {
+        Ok(entry) => entry,
+        Err(git::Error::NotFound(_)) => {
+            return Err(errors::BlameError::FileNotFound(path.to_path_buf()))
+        }
+        Err(error) => Err(error).context("failed to find file")?,
+    };
+    let blob = repo.find_blob(entry.id()).context("failed to find blob")?;
+    let total_lines = count_lines(blob.content());
+
+    let mut opts = git2::BlameOptions::new();
+    opts.newest_commit(rev.into());
+    if let Some(lines) = &lines {
+        let (start, end) = (*lines.start(), *lines.end());
+        if start == 0 || start > end {
+            return Err(errors::BlameError::InvalidLines { start, end });
+        }
+        // past the end, there is nothing to blame
+        if start > total_lines {
+            return Ok(Blame {
+                hunks: vec![],
+                total_lines,
+            });
+        }
+        opts.min_line(start).max_line(end.min(total_lines));
+    }
+    if total_lines == 0 {
+        return Ok(Blame {
+            hunks: vec![],
+            total_lines,
+        });
+    }
+    let blame = repo
+        .blame(path, &mut opts)
+        .context("failed to blame file")?;
+
+    let mailmap = repo.mailmap().context("failed to read mailmap")?;
+    let mut commits: HashMap<git::Oid, (Author, u128, String)> = HashMap::new();
+    let mut hunks = Vec::with_capacity(blame.len());
+    for hunk in blame.iter() {
+        let commit_id = git::Oid::from(hunk.final_commit_id());
+        if !commits.contains_key(&commit_id) {
+            let commit = repo
+                .find_commit(commit_id)
+                .context("failed to find blamed commit")?;
+            let created_at = u128::try_from(commit.time().seconds())
+                .context("failed to convert commit time")?
+                * 1000;
+            let summary = commit
+                .message()
+                .unwrap_or_default()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            commits.insert(
+                commit_id,
+                (Author::of(&commit, &mailmap)?, created_at, summary),
+            );
+        }
+        let (author, created_at, summary) = &commits[&commit_id];
+        hunks.push(BlameHunk {
+            start_line: hunk.final_start_line(),
+            lines: hunk.lines_in_hunk(),
+            commit_id,
+            author: author.clone(),
+            created_at: *created_at,
+            summary: summary.clone(),
+            boundary: hunk.is_boundary(),
+        });
+    }
+    Ok(Blame { hunks, total_lines })
+}
+
+// the last line needs no newline to be counted, like git counts them
+fn count_lines(content: &[u8]) -> usize {
+    let newlines = content.iter().filter(|byte| **byte == b'\n').count();
+    match content.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
@@ -88,6 +88,7 @@ impl TryFrom<&crate::reader::Reader<'_>> for Branch {
             "meta/updated_timestamp_ms",
             "meta/ownership",
             "meta/selected_for_changes",
+            "meta/paths_escaped",
         ])?;
 
         let id: String = results[0].clone()?.try_into()?;
@@ -160,8 +161,19 @@ impl TryFrom<&crate::reader::Reader<'_>> for Branch {
         let created_timestamp_ms = results[9].clone()?.try_into()?;
         let updated_timestamp_ms = results[10].clone()?.try_into()?;
 
+        // the ownership of branches written before paths were escaped has them as they are
+        let paths_escaped = match results[13].clone() {
+            Ok(paths_escaped) => paths_escaped.try_into().unwrap_or(false),
+            Err(crate::reader::Error::NotFound) => false,
+            Err(e) => return Err(e),
+        };
         let ownership_string: String = results[11].clone()?.try_into()?;
-        let ownership = ownership_string.parse().map_err(|e| {
+        let ownership = if paths_escaped {
+            ownership_string.parse()
+        } else {
+            Ownership::parse_legacy(&ownership_string)
+        }
+        .map_err(|e| {
             crate::reader::Error::Io(
                 std::io::Error::new(std::io::ErrorKind::Other, format!("meta/ownership: {}", e))
                     .into(),
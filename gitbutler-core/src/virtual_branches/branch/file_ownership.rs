@@ -1,6 +1,8 @@
 use std::{fmt, path, str::FromStr, vec};
 
-use anyhow::{Context, Result};
+use anyhow::Result;
+
+use crate::git;
 
 use super::hunk::Hunk;
 
@@ -14,6 +16,19 @@ impl FromStr for FileOwnership {
     type Err = anyhow::Error;
 
     fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        // paths are escaped, so that they survive as text when they aren't UTF-8
+        Self::parse(value, git::path::unescape)
+    }
+}
+
+impl FileOwnership {
+    /// The ownership `value` as it was written before paths were escaped, with its path as
+    /// it is, which unescaping would change if it had a `\\` or a `\x` in it.
+    pub fn parse_legacy(value: &str) -> Result<Self> {
+        Self::parse(value, path::PathBuf::from)
+    }
+
+    fn parse(value: &str, to_path: fn(&str) -> path::PathBuf) -> Result<Self> {
         let mut file_path_parts = vec![];
         let mut ranges = vec![];
         for part in value.split(':').rev() {
@@ -33,17 +48,12 @@ impl FromStr for FileOwnership {
             Err(anyhow::anyhow!("ownership ranges cannot be empty"))
         } else {
             Ok(Self {
-                file_path: file_path_parts
-                    .join(":")
-                    .parse()
-                    .context(format!("failed to parse file path from {}", value))?,
+                file_path: to_path(&file_path_parts.join(":")),
                 hunks: ranges.clone(),
             })
         }
     }
-}
 
-impl FileOwnership {
     pub fn is_full(&self) -> bool {
         self.hunks.is_empty()
     }
@@ -158,12 +168,12 @@ impl FileOwnership {
 impl fmt::Display for FileOwnership {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
         if self.hunks.is_empty() {
-            write!(f, "{}", self.file_path.display())
+            write!(f, "{}", git::path::escape(&self.file_path))
         } else {
             write!(
                 f,
                 "{}:{}",
-                self.file_path.display(),
+                git::path::escape(&self.file_path),
                 self.hunks
                     .iter()
                     .map(ToString::to_string)
@@ -200,6 +210,38 @@ mod tests {
         });
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn ownership_of_invalid_utf8_path() {
+        use bstr::ByteSlice;
+
+        let ownership = FileOwnership {
+            file_path: git::path::from_bytes(b"caf\xe9.txt".as_bstr()),
+            hunks: vec![(1..=2).into()],
+        };
+        assert_eq!(ownership.to_string(), "caf\\xe9.txt:1-2");
+        assert_eq!(
+            ownership.to_string().parse::<FileOwnership>().unwrap(),
+            ownership
+        );
+    }
+
+    #[test]
+    fn parse_legacy_ownership_as_it_is() {
+        let ownership = FileOwnership::parse_legacy("dir\\\\x41\\x41.txt:1-2").unwrap();
+        assert_eq!(
+            ownership.file_path,
+            path::PathBuf::from("dir\\\\x41\\x41.txt")
+        );
+        assert_eq!(
+            "dir\\\\x41\\x41.txt:1-2"
+                .parse::<FileOwnership>()
+                .unwrap()
+                .file_path,
+            path::PathBuf::from("dir\\x41A.txt")
+        );
+    }
+
     #[test]
     fn parse_ownership_no_ranges() {
         "foo/bar.rs".parse::<FileOwnership>().unwrap_err();
@@ -47,6 +47,16 @@ impl FromStr for Ownership {
 }
 
 impl Ownership {
+    /// The ownership `s` as it was written before paths were escaped, see
+    /// [`FileOwnership::parse_legacy`].
+    pub fn parse_legacy(s: &str) -> anyhow::Result<Self> {
+        let mut ownership = Ownership::default();
+        for line in s.lines() {
+            ownership.files.push(FileOwnership::parse_legacy(line)?);
+        }
+        Ok(ownership)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.files.is_empty()
     }
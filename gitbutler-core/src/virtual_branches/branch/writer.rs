@@ -29,7 +29,9 @@ impl<'writer> BranchWriter<'writer> {
         {
             Ok(_) => {
                 self.repository.mark_active_session()?;
-                let _lock = self.repository.lock();
+                let _lock = self
+                    .repository
+                    .lock(gb_repository::LockShard::VirtualBranches);
                 self.writer.remove(format!("branches/{}", branch.id))?;
                 Ok(())
             }
@@ -48,6 +50,10 @@ impl<'writer> BranchWriter<'writer> {
 
         self.repository.mark_active_session()?;
 
+        let _lock = self
+            .repository
+            .lock(gb_repository::LockShard::VirtualBranches);
+
         branch.updated_timestamp_ms = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)?
             .as_millis();
@@ -127,6 +133,10 @@ impl<'writer> BranchWriter<'writer> {
             format!("branches/{}/meta/ownership", branch.id),
             branch.ownership.to_string(),
         ));
+        batch.push(writer::BatchTask::Write(
+            format!("branches/{}/meta/paths_escaped", branch.id),
+            true.to_string(),
+        ));
 
         if let Some(selected_for_changes) = branch.selected_for_changes {
             batch.push(writer::BatchTask::Write(
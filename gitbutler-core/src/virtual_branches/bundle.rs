@@ -0,0 +1,263 @@
+use std::{path, time};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    dedup::dedup,
+    gb_repository,
+    git::{self, bundle},
+    keys, project_repository, users,
+};
+
+use super::{
+    branch::{self, BranchId},
+    errors,
+    integration::{
+        head_with_wip, is_wip_commit, GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL,
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+    },
+    recovery::{ownership_since_base, read_branches},
+};
+
+/// Writes the virtual branches `branch_ids`, or all of them if it's empty, to the bundle
+/// `path`. Every branch is written as its `refs/gitbutler/` reference, with its uncommitted
+/// changes in a WIP commit on top, so [`import_bundle`] can restore the branch as it was.
+///
+/// The bundle leaves out the history of the target, which the repository it is imported
+/// into must have.
+pub fn export_bundle(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_ids: &[BranchId],
+    path: &path::Path,
+) -> Result<(), errors::ExportBundleError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::ExportBundleError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+    let branches = read_branches(gb_repository)?;
+    if let Some(branch_id) = branch_ids
+        .iter()
+        .find(|branch_id| !branches.iter().any(|branch| branch.id == **branch_id))
+    {
+        return Err(errors::ExportBundleError::BranchNotFound(
+            errors::BranchNotFoundError {
+                project_id: project_repository.project().id,
+                branch_id: *branch_id,
+            },
+        ));
+    }
+    let branches = branches
+        .iter()
+        .filter(|branch| branch_ids.is_empty() || branch_ids.contains(&branch.id))
+        .collect::<Vec<_>>();
+    if branches.is_empty() {
+        return Err(errors::ExportBundleError::NoBranches);
+    }
+
+    let refnames = write_branch_references(project_repository, branches, "export bundle")?;
+    bundle::create(
+        &project_repository.git_repository,
+        path,
+        &refnames,
+        &[default_target.sha],
+    )
+    .context("failed to create bundle")?;
+    Ok(())
+}
+
+// points the `refs/gitbutler/` reference of every branch at its head, with its uncommitted
+// changes in a WIP commit on top, returning the names of the references. references that
+// already point there, as the integration left them, aren't written again
+pub(super) fn write_branch_references<'b>(
+    project_repository: &project_repository::Repository,
+    branches: impl IntoIterator<Item = &'b branch::Branch>,
+    message: &str,
+) -> Result<Vec<String>> {
+    let repo = &project_repository.git_repository;
+    let committer = git::Signature::now(
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL,
+    )
+    .context("failed to create signature")?;
+    let mut refnames = vec![];
+    for branch in branches {
+        let refname = project_repository.branch_refname(branch);
+        let current = repo
+            .find_reference(&refname.clone().into())
+            .and_then(|reference| reference.peel_to_commit())
+            .ok();
+        if !current.is_some_and(|current| is_head_with_wip(&current, branch)) {
+            let head = head_with_wip(repo, branch, &committer).context("failed to write wip")?;
+            repo.reference(&refname.clone().into(), head.id(), true, message)
+                .context("failed to update branch reference")?;
+        }
+        refnames.push(refname.to_string());
+    }
+    Ok(refnames)
+}
+
+// whether `commit` is what `head_with_wip` writes for `branch` as it is now
+fn is_head_with_wip(commit: &git::Commit, branch: &branch::Branch) -> bool {
+    if commit.tree_id() != branch.tree {
+        return false;
+    }
+    commit.id() == branch.head
+        || (is_wip_commit(commit) && commit.parent_ids().first() == Some(&branch.head))
+}
+
+// fetches the references of the bundle `path` into the repository, returning them
+pub(super) fn unbundle(
+    project_repository: &project_repository::Repository,
+    path: &path::Path,
+) -> Result<Vec<(String, git::Oid)>, errors::ImportBundleError> {
+    let repo = &project_repository.git_repository;
+    if let Some(reason) = bundle::verify(repo, path).context("failed to verify bundle")? {
+        return Err(errors::ImportBundleError::InvalidBundle(reason));
+    }
+    bundle::unbundle(repo, path)
+        .context("failed to unbundle")
+        .map_err(Into::into)
+}
+
+// runs `add`, which writes the branches it imports and pushes their ids along with whether
+// to apply them, then applies them. if anything fails, the branches added so far are
+// deleted again so a failed import leaves the project as it was
+pub(super) fn import_branches(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    signing_key: Option<&keys::PrivateKey>,
+    user: Option<&users::User>,
+    add: impl FnOnce(&mut Vec<(BranchId, bool)>) -> Result<(), errors::ImportBundleError>,
+) -> Result<Vec<BranchId>, errors::ImportBundleError> {
+    let mut imported = vec![];
+    let result = add(&mut imported).and_then(|()| {
+        for (branch_id, apply) in &imported {
+            if !apply {
+                continue;
+            }
+            match super::apply_branch(
+                gb_repository,
+                project_repository,
+                branch_id,
+                signing_key,
+                user,
+            ) {
+                // if the branch conflicts with the workspace, it's ok. keep it unapplied
+                Ok(()) | Err(errors::ApplyBranchError::BranchConflicts(_)) => {}
+                Err(error) => return Err(errors::ImportBundleError::ApplyBranch(error)),
+            }
+        }
+        Ok(())
+    });
+    if let Err(error) = result {
+        for (branch_id, _) in &imported {
+            if let Err(error) = super::delete_branch(gb_repository, project_repository, branch_id) {
+                tracing::warn!(%branch_id, ?error, "failed to roll back imported branch");
+            }
+        }
+        return Err(error);
+    }
+    Ok(imported
+        .into_iter()
+        .map(|(branch_id, _)| branch_id)
+        .collect())
+}
+
+/// Adds a virtual branch for every branch of the bundle `path`, the `refs/gitbutler/`
+/// references [`export_bundle`] writes as well as the local branches of bundles git wrote,
+/// returning their ids. Uncommitted changes exported with a branch are uncommitted again.
+///
+/// The branches are applied, unless they conflict with the workspace.
+pub fn import_bundle(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    path: &path::Path,
+    signing_key: Option<&keys::PrivateKey>,
+    user: Option<&users::User>,
+) -> Result<Vec<BranchId>, errors::ImportBundleError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::ImportBundleError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let repo = &project_repository.git_repository;
+    let heads = unbundle(project_repository, path)?;
+
+    let mut branches = read_branches(gb_repository)?;
+    let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    import_branches(
+        gb_repository,
+        project_repository,
+        signing_key,
+        user,
+        |imported| {
+            for (refname, oid) in heads {
+                // exported from a linked worktree, the references are in its namespace
+                let virtual_refname = refname.parse::<git::VirtualRefname>().ok();
+                let Some(name) = virtual_refname
+                    .as_ref()
+                    .map(git::VirtualRefname::branch)
+                    .or_else(|| refname.strip_prefix("refs/heads/"))
+                else {
+                    continue;
+                };
+                let tip = repo.find_commit(oid).context("failed to find commit")?;
+                let tree = tip.tree().context("failed to find tree")?;
+                // only the references export_bundle writes have WIP commits
+                let head = if virtual_refname.is_some() && is_wip_commit(&tip) {
+                    tip.parent(0).context("failed to find parent")?.id()
+                } else {
+                    oid
+                };
+
+                let now = time::UNIX_EPOCH
+                    .elapsed()
+                    .context("failed to get elapsed time")?
+                    .as_millis();
+                let mut branch = branch::Branch {
+                    id: BranchId::generate(),
+                    name: dedup(
+                        &branches
+                            .iter()
+                            .map(|branch| branch.name.as_str())
+                            .collect::<Vec<_>>(),
+                        name,
+                    ),
+                    notes: String::new(),
+                    applied: false,
+                    upstream: None,
+                    upstream_head: None,
+                    tree: tree.id(),
+                    head,
+                    created_timestamp_ms: now,
+                    updated_timestamp_ms: now,
+                    ownership: ownership_since_base(
+                        project_repository,
+                        default_target.sha,
+                        head,
+                        &tree,
+                    )?,
+                    order: branches.len(),
+                    selected_for_changes: None,
+                };
+                writer
+                    .write(&mut branch)
+                    .context("failed to write branch")?;
+                // pushed before anything else can fail, for it to be deleted again if it does
+                imported.push((branch.id, true));
+                project_repository.add_branch_reference(&branch)?;
+                branches.push(branch);
+            }
+            Ok(())
+        },
+    )
+}
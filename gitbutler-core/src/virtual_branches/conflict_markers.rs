@@ -0,0 +1,169 @@
+//! Conflict markers, the `<<<<<<<`, `|||||||`, `=======` and `>>>>>>>` lines resolving a
+//! conflict is supposed to remove, are looked for in the lines a commit adds, which is
+//! refused while there are any.
+//!
+//! Files where they are legitimate, like the tests of a merge tool, have the
+//! `gitbutler-conflict-markers` attribute. Markers are as long as the `conflict-marker-size`
+//! attribute of the file says, like `git diff --check` has them. The check is turned off
+//! with the `gitbutler.checkConflictMarkers` git config.
+
+use std::{collections::HashMap, path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{git::diff, project_repository};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictMarker {
+    #[serde(with = "crate::git::path::escaped")]
+    pub file_path: path::PathBuf,
+    pub line: u32,
+    /// The line of the marker, like `<<<<<<< HEAD`.
+    pub marker: String,
+}
+
+/// The conflict markers `files`, the changes of the working directory, add. Empty if the
+/// project doesn't check for them.
+pub fn find_conflict_markers(
+    project_repository: &project_repository::Repository,
+    files: &HashMap<path::PathBuf, Vec<diff::Hunk>>,
+) -> Result<Vec<ConflictMarker>> {
+    if !project_repository
+        .config()
+        .check_conflict_markers()
+        .context("failed to read conflict markers config")?
+    {
+        return Ok(vec![]);
+    }
+    let repo = &project_repository.git_repository;
+    let mut markers = vec![];
+    for (file_path, hunks) in files {
+        let hunks = hunks
+            .iter()
+            .filter(|hunk| !hunk.binary && !hunk.generated)
+            .collect::<Vec<_>>();
+        if hunks.is_empty()
+            || repo
+                .allows_conflict_markers(file_path)
+                .context("failed to read conflict markers attribute")?
+        {
+            continue;
+        }
+        let size = repo
+            .conflict_marker_size(file_path)
+            .context("failed to read conflict marker size attribute")?;
+        for hunk in hunks {
+            markers.extend(scan_hunk(file_path, hunk, size));
+        }
+    }
+    markers.sort_by(|a, b| (&a.file_path, a.line).cmp(&(&b.file_path, b.line)));
+    Ok(markers)
+}
+
+fn scan_hunk(file_path: &path::Path, hunk: &diff::Hunk, size: usize) -> Vec<ConflictMarker> {
+    let mut markers = vec![];
+    // `=======` and `|||||||` are only markers after a `<<<<<<<`, they underline headings
+    // and draw tables otherwise
+    let mut in_conflict = false;
+    let mut line_number = hunk.new_start;
+    for line in hunk.diff.lines().filter(|line| !line.starts_with("@@")) {
+        let Some(added) = line.strip_prefix('+') else {
+            if line.starts_with(' ') {
+                line_number += 1;
+            }
+            continue;
+        };
+        let is_marker = match marker(added, size) {
+            Some(c @ ('<' | '>')) => {
+                in_conflict = c == '<';
+                true
+            }
+            Some(_) => in_conflict,
+            None => false,
+        };
+        if is_marker {
+            markers.push(ConflictMarker {
+                file_path: file_path.to_path_buf(),
+                line: line_number,
+                marker: added.trim_end().to_string(),
+            });
+        }
+        line_number += 1;
+    }
+    markers
+}
+
+// the character of the marker `line` is, a line of exactly `size` of them that ends there or
+// goes on with a space
+fn marker(line: &str, size: usize) -> Option<char> {
+    let c = line.chars().next()?;
+    if !matches!(c, '<' | '|' | '=' | '>') {
+        return None;
+    }
+    let rest = line.get(size..)?;
+    if !line.get(..size)?.chars().all(|other| other == c) {
+        return None;
+    }
+    match rest.chars().next() {
+        None | Some('\r') => Some(c),
+        Some(' ') if c != '=' => Some(c),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(diff: &str, size: usize) -> Vec<(u32, String)> {
+        let hunk = diff::Hunk {
+            old_start: 1,
+            old_lines: 1,
+            new_start: 1,
+            new_lines: 6,
+            diff: diff.to_string(),
+            binary: false,
+            generated: false,
+            change_type: diff::ChangeType::Modified,
+        };
+        scan_hunk(path::Path::new("file.txt"), &hunk, size)
+            .into_iter()
+            .map(|marker| (marker.line, marker.marker))
+            .collect()
+    }
+
+    #[test]
+    fn finds_markers_in_added_lines() {
+        assert_eq!(
+            scan(
+                "@@ -1 +1,6 @@\n unchanged\n+<<<<<<< HEAD\n+ours\n+=======\n+theirs\n+>>>>>>> feature\n",
+                7
+            ),
+            vec![
+                (2, "<<<<<<< HEAD".to_string()),
+                (4, "=======".to_string()),
+                (6, ">>>>>>> feature".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_lookalikes() {
+        assert!(scan(
+            "@@ -0,0 +1,5 @@\n+Heading\n+=======\n+<<<<<<<< eight\n+<<<<<<<no space\n+-<<<<<<< removed\n",
+            7
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn uses_the_marker_size() {
+        assert!(scan("@@ -0,0 +1 @@\n+<<<<<<< HEAD\n", 10).is_empty());
+        assert_eq!(
+            scan("@@ -0,0 +1 @@\n+<<<<<<<<<< HEAD\n", 10),
+            vec![(1, "<<<<<<<<<< HEAD".to_string())]
+        );
+    }
+}
@@ -23,6 +23,7 @@ pub fn hunk_with_context(
             new_start: hunk_new_start_line as u32,
             new_lines: 0,
             binary: is_binary,
+            generated: false,
             change_type,
         });
     }
@@ -130,6 +131,7 @@ pub fn hunk_with_context(
         new_start: start_line_after as u32,
         new_lines: line_count_after as u32,
         binary: is_binary,
+        generated: false,
         change_type,
     };
     Ok(hunk)
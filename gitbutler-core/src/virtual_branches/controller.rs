@@ -0,0 +1,2996 @@
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::Context;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    error::Error,
+    events, gb_repository, git, keys,
+    project_repository::{self, hooks},
+    projects::{self, ProjectId, PromptKind},
+    sessions, settings, users,
+};
+
+use super::{
+    audit,
+    branch::{self, BranchId, Ownership},
+    errors::{
+        self, FetchFromTargetError, GetBaseBranchDataError, GetRemoteBranchDataError,
+        IsRemoteBranchMergableError, ListRemoteBranchesError,
+    },
+    queue::{self, OperationId, OperationPermit, OperationQueue, QueuedOperation, Requester},
+    target, target_to_base_branch, BaseBranch, FetchSummary, Progress, RemoteBranchFile,
+    RemoteFetch,
+};
+
+#[derive(Clone)]
+pub struct Controller {
+    local_data_dir: path::PathBuf,
+    projects: projects::Controller,
+    users: users::Controller,
+    keys: keys::Controller,
+    helper: git::credentials::Helper,
+    requested_by: Requester,
+    on_retry: Option<project_repository::OnRetry>,
+    on_finished: Option<queue::OnFinished>,
+
+    by_project_id: Arc<tokio::sync::Mutex<HashMap<ProjectId, ControllerInner>>>,
+}
+
+impl Controller {
+    pub fn new(
+        data_dir: &path::Path,
+        projects: &projects::Controller,
+        users: &users::Controller,
+        keys: &keys::Controller,
+        helper: &git::credentials::Helper,
+    ) -> Self {
+        Self {
+            by_project_id: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+
+            local_data_dir: data_dir.to_path_buf(),
+            projects: projects.clone(),
+            users: users.clone(),
+            keys: keys.clone(),
+            helper: helper.clone(),
+            requested_by: Requester::default(),
+            on_retry: None,
+            on_finished: None,
+        }
+    }
+
+    /// The controller, publishing on `events` the fetches and pushes that are tried again
+    /// after a network error.
+    pub fn with_events(self, events: &events::Bus) -> Self {
+        let events = events.clone();
+        Self {
+            on_retry: Some(Arc::new(move |retry| {
+                events.publish(events::Event::git_retry(retry));
+            })),
+            ..self
+        }
+    }
+
+    /// The controller, telling `on_finished` how long the operations of every project waited
+    /// for their turn and how long they ran.
+    pub fn on_operation_finished(self, on_finished: queue::OnFinished) -> Self {
+        Self {
+            on_finished: Some(on_finished),
+            ..self
+        }
+    }
+
+    /// The same controller, with the operations it runs requested by `requested_by` in the
+    /// queue of their project.
+    pub fn requested_by(&self, requested_by: Requester) -> Self {
+        Self {
+            requested_by,
+            ..self.clone()
+        }
+    }
+
+    async fn inner(&self, project_id: &ProjectId) -> ControllerInner {
+        let mut inner = self
+            .by_project_id
+            .lock()
+            .await
+            .entry(*project_id)
+            .or_insert_with(|| {
+                ControllerInner::new(
+                    &self.local_data_dir,
+                    &self.projects,
+                    &self.users,
+                    &self.keys,
+                    &self.helper,
+                )
+                .with_queue(OperationQueue::new().on_finished(self.on_finished.clone()))
+            })
+            .clone();
+        inner.requested_by = self.requested_by;
+        inner.on_retry = self.on_retry.clone();
+        inner
+    }
+
+    /// The operations of the project, the one running and those waiting for their turn, in
+    /// the order they were requested.
+    pub async fn list_operations(&self, project_id: &ProjectId) -> Vec<QueuedOperation> {
+        self.inner(project_id).await.list_operations()
+    }
+
+    /// Cancels the network operation `operation_id` of the project, running or waiting for
+    /// its turn. It fails with [`crate::error::Code::Cancelled`] right away, what libgit2 is
+    /// still doing keeps the next operation waiting, see [`project_repository::Cancellation`].
+    pub async fn cancel_operation(
+        &self,
+        project_id: &ProjectId,
+        operation_id: OperationId,
+    ) -> Result<(), Error> {
+        self.inner(project_id).await.cancel_operation(operation_id)
+    }
+
+    pub async fn create_commit(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        message: &str,
+        ownership: Option<&Ownership>,
+        run_hooks: bool,
+    ) -> Result<git::Oid, ControllerError<errors::CommitError>> {
+        self.inner(project_id)
+            .await
+            .create_commit(project_id, branch_id, message, ownership, run_hooks)
+            .await
+    }
+
+    /// Commits without running the hooks, recording that they were skipped in the message.
+    pub async fn create_commit_no_verify(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        message: &str,
+        ownership: Option<&Ownership>,
+    ) -> Result<git::Oid, ControllerError<errors::CommitError>> {
+        self.inner(project_id)
+            .await
+            .create_commit_no_verify(project_id, branch_id, message, ownership)
+            .await
+    }
+
+    pub async fn can_apply_remote_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_name: &git::RemoteRefname,
+    ) -> Result<bool, ControllerError<IsRemoteBranchMergableError>> {
+        self.inner(project_id)
+            .await
+            .can_apply_remote_branch(project_id, branch_name)
+    }
+
+    pub async fn can_apply_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<bool, Error> {
+        self.inner(project_id)
+            .await
+            .can_apply_virtual_branch(project_id, branch_id)
+    }
+
+    pub async fn list_virtual_branches(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::VirtualBranch>, ControllerError<errors::ListVirtualBranchesError>> {
+        self.inner(project_id)
+            .await
+            .list_virtual_branches(project_id, &CancellationToken::new(), &Progress::default())
+            .await
+    }
+
+    /// Same as `list_virtual_branches`, but gives up as soon as `cancel` is cancelled,
+    /// returning `ListVirtualBranchesError::Cancelled`, and reports how far along the
+    /// status computation is to `progress`.
+    pub async fn list_virtual_branches_cancellable(
+        &self,
+        project_id: &ProjectId,
+        cancel: &CancellationToken,
+        progress: &Progress,
+    ) -> Result<Vec<super::VirtualBranch>, ControllerError<errors::ListVirtualBranchesError>> {
+        self.inner(project_id)
+            .await
+            .list_virtual_branches(project_id, cancel, progress)
+            .await
+    }
+
+    pub async fn create_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        create: &super::branch::BranchCreateRequest,
+    ) -> Result<BranchId, ControllerError<errors::CreateVirtualBranchError>> {
+        self.inner(project_id)
+            .await
+            .create_virtual_branch(project_id, create)
+            .await
+    }
+
+    pub async fn create_virtual_branch_from_branch(
+        &self,
+        project_id: &ProjectId,
+        branch: &git::Refname,
+    ) -> Result<BranchId, ControllerError<errors::CreateVirtualBranchFromBranchError>> {
+        self.inner(project_id)
+            .await
+            .create_virtual_branch_from_branch(project_id, branch)
+            .await
+    }
+
+    /// The commits of the branch a reset or a rebase left behind, see
+    /// [`super::list_lost_commits`].
+    pub async fn list_lost_commits(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Vec<super::LostCommit>, ControllerError<errors::ListLostCommitsError>> {
+        self.inner(project_id)
+            .await
+            .list_lost_commits(project_id, branch_id)
+    }
+
+    /// Creates a virtual branch from a lost commit of the branch.
+    pub async fn recover_commit(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+    ) -> Result<BranchId, ControllerError<errors::RecoverCommitError>> {
+        self.inner(project_id)
+            .await
+            .recover_commit(project_id, branch_id, commit_oid)
+            .await
+    }
+
+    /// Writes the branches `branch_ids`, or all of them if it's empty, to the bundle `path`.
+    pub async fn export_bundle(
+        &self,
+        project_id: &ProjectId,
+        branch_ids: &[BranchId],
+        path: &path::Path,
+    ) -> Result<(), ControllerError<errors::ExportBundleError>> {
+        self.inner(project_id)
+            .await
+            .export_bundle(project_id, branch_ids, path)
+            .await
+    }
+
+    pub async fn import_bundle(
+        &self,
+        project_id: &ProjectId,
+        path: &path::Path,
+    ) -> Result<Vec<BranchId>, ControllerError<errors::ImportBundleError>> {
+        self.inner(project_id)
+            .await
+            .import_bundle(project_id, path)
+            .await
+    }
+
+    /// Writes every virtual branch with its state to the archive `path`, see
+    /// [`super::export_virtual_branches`].
+    pub async fn export_virtual_branches(
+        &self,
+        project_id: &ProjectId,
+        path: &path::Path,
+    ) -> Result<(), ControllerError<errors::ExportBundleError>> {
+        self.inner(project_id)
+            .await
+            .export_virtual_branches(project_id, path)
+            .await
+    }
+
+    /// Restores the virtual branches of the archive `path`, see
+    /// [`super::import_virtual_branches`].
+    pub async fn import_virtual_branches(
+        &self,
+        project_id: &ProjectId,
+        path: &path::Path,
+    ) -> Result<Vec<BranchId>, ControllerError<errors::ImportBundleError>> {
+        self.inner(project_id)
+            .await
+            .import_virtual_branches(project_id, path)
+            .await
+    }
+
+    /// Checks the project for problems and, if `fix` is set, fixes the ones that can be
+    /// fixed safely, see [`super::repair_project`].
+    pub async fn repair_project(
+        &self,
+        project_id: &ProjectId,
+        fix: bool,
+    ) -> Result<Vec<super::Issue>, ControllerError<errors::RepairProjectError>> {
+        self.inner(project_id)
+            .await
+            .repair_project(project_id, fix)
+            .await
+    }
+
+    pub async fn get_base_branch_data(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Option<super::BaseBranch>, ControllerError<GetBaseBranchDataError>> {
+        self.inner(project_id)
+            .await
+            .get_base_branch_data(project_id)
+    }
+
+    /// Reads the base branch, virtual branches and remote branches of the project in one
+    /// go, so that the frontend gets a consistent view of it with a single call.
+    pub async fn get_project_state(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<super::ProjectState, ControllerError<errors::GetProjectStateError>> {
+        self.inner(project_id)
+            .await
+            .get_project_state(project_id)
+            .await
+    }
+
+    /// The merge, rebase or bisect started outside of GitButler the project is in the middle
+    /// of. Nothing that changes the project can be done until it's finished or aborted.
+    pub async fn get_external_operation(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Option<project_repository::ExternalOperation>, Error> {
+        self.inner(project_id)
+            .await
+            .get_external_operation(project_id)
+    }
+
+    /// Continues or aborts the operation started outside of GitButler, see
+    /// [`project_repository::finish_external_operation`].
+    pub async fn finish_external_operation(
+        &self,
+        project_id: &ProjectId,
+        action: project_repository::OperationAction,
+    ) -> Result<(), Error> {
+        self.inner(project_id)
+            .await
+            .finish_external_operation(project_id, action)
+            .await
+    }
+
+    /// The name of the branch the workspace is checked out on, see
+    /// [`super::workspace_reference`].
+    pub async fn get_workspace_branch(&self, project_id: &ProjectId) -> Result<String, Error> {
+        self.inner(project_id)
+            .await
+            .get_workspace_branch(project_id)
+    }
+
+    /// Renames the branch the workspace is checked out on, see [`super::set_workspace_branch`].
+    pub async fn set_workspace_branch(
+        &self,
+        project_id: &ProjectId,
+        name: &str,
+    ) -> Result<(), ControllerError<errors::SetWorkspaceBranchError>> {
+        self.inner(project_id)
+            .await
+            .set_workspace_branch(project_id, name)
+            .await
+    }
+
+    /// The files changed by the commit, diffed with the options of the project, or with
+    /// `options` where they are set.
+    pub async fn list_remote_commit_files(
+        &self,
+        project_id: &ProjectId,
+        commit_oid: git::Oid,
+        options: &projects::DiffOptions,
+    ) -> Result<Vec<RemoteBranchFile>, Error> {
+        self.inner(project_id)
+            .await
+            .list_remote_commit_files(project_id, commit_oid, options)
+    }
+
+    pub async fn blame(
+        &self,
+        project_id: &ProjectId,
+        path: &path::Path,
+        rev: git::Oid,
+        lines: Option<RangeInclusive<usize>>,
+    ) -> Result<super::Blame, ControllerError<errors::BlameError>> {
+        self.inner(project_id)
+            .await
+            .blame(project_id, path, rev, lines)
+    }
+
+    /// The changed files of the project, and which of them only changed their line endings
+    /// or byte order mark.
+    pub async fn list_line_endings(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::FileLineEndings>, ControllerError<errors::ListLineEndingsError>> {
+        self.inner(project_id).await.list_line_endings(project_id)
+    }
+
+    pub async fn normalize_line_endings(
+        &self,
+        project_id: &ProjectId,
+        paths: &[path::PathBuf],
+    ) -> Result<(), ControllerError<errors::NormalizeLineEndingsError>> {
+        self.inner(project_id)
+            .await
+            .normalize_line_endings(project_id, paths)
+            .await
+    }
+
+    pub async fn set_base_branch(
+        &self,
+        project_id: &ProjectId,
+        target_branch: &git::RemoteRefname,
+    ) -> Result<super::BaseBranch, Error> {
+        self.inner(project_id)
+            .await
+            .set_base_branch(project_id, target_branch)
+            .await
+    }
+
+    pub async fn merge_virtual_branch_upstream(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<(), ControllerError<errors::MergeVirtualBranchUpstreamError>> {
+        self.inner(project_id)
+            .await
+            .merge_virtual_branch_upstream(project_id, branch_id)
+            .await
+    }
+
+    pub async fn update_base_branch(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<(), ControllerError<errors::UpdateBaseBranchError>> {
+        self.inner(project_id)
+            .await
+            .update_base_branch(project_id)
+            .await
+    }
+
+    pub async fn update_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_update: super::branch::BranchUpdateRequest,
+    ) -> Result<(), ControllerError<errors::UpdateBranchError>> {
+        self.inner(project_id)
+            .await
+            .update_virtual_branch(project_id, branch_update)
+            .await
+    }
+
+    pub async fn delete_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<(), ControllerError<errors::DeleteBranchError>> {
+        self.inner(project_id)
+            .await
+            .delete_virtual_branch(project_id, branch_id)
+            .await
+    }
+
+    pub async fn apply_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<(), ControllerError<errors::ApplyBranchError>> {
+        self.inner(project_id)
+            .await
+            .apply_virtual_branch(project_id, branch_id)
+            .await
+    }
+
+    pub async fn unapply_ownership(
+        &self,
+        project_id: &ProjectId,
+        ownership: &Ownership,
+    ) -> Result<(), ControllerError<errors::UnapplyOwnershipError>> {
+        self.inner(project_id)
+            .await
+            .unapply_ownership(project_id, ownership)
+            .await
+    }
+
+    pub async fn amend(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        ownership: &Ownership,
+    ) -> Result<git::Oid, ControllerError<errors::AmendError>> {
+        self.inner(project_id)
+            .await
+            .amend(project_id, branch_id, ownership)
+            .await
+    }
+
+    pub async fn reset_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        target_commit_oid: git::Oid,
+    ) -> Result<(), ControllerError<errors::ResetBranchError>> {
+        self.inner(project_id)
+            .await
+            .reset_virtual_branch(project_id, branch_id, target_commit_oid)
+            .await
+    }
+
+    pub async fn unapply_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<(), ControllerError<errors::UnapplyBranchError>> {
+        self.inner(project_id)
+            .await
+            .unapply_virtual_branch(project_id, branch_id)
+            .await
+    }
+
+    pub async fn push_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        with_force: bool,
+    ) -> Result<(), ControllerError<errors::PushError>> {
+        self.push_virtual_branch_with_progress(
+            project_id,
+            branch_id,
+            with_force,
+            &super::PrePushProgress::default(),
+        )
+        .await
+    }
+
+    /// Same as `push_virtual_branch`, with the output of the pre-push checks collected in
+    /// `progress` as they run.
+    pub async fn push_virtual_branch_with_progress(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        with_force: bool,
+        progress: &super::PrePushProgress,
+    ) -> Result<(), ControllerError<errors::PushError>> {
+        self.inner(project_id)
+            .await
+            .push_virtual_branch(project_id, branch_id, with_force, progress)
+            .await
+    }
+
+    /// Runs the checks pushing the branch runs, without pushing it, see
+    /// [`super::check_push`].
+    pub async fn check_push(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        with_force: bool,
+        progress: &super::PrePushProgress,
+    ) -> Result<super::PrePushReport, ControllerError<errors::PushError>> {
+        self.inner(project_id)
+            .await
+            .check_push(project_id, branch_id, with_force, progress)
+            .await
+    }
+
+    /// The secrets committing and pushing the branch would be refused for, see
+    /// [`super::scan_secrets`].
+    pub async fn scan_secrets(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Vec<super::SecretFinding>, ControllerError<errors::ScanSecretsError>> {
+        self.inner(project_id)
+            .await
+            .scan_secrets(project_id, branch_id)
+            .await
+    }
+
+    /// The large files committing the branch would warn about or be refused for, see
+    /// [`super::list_large_files`].
+    pub async fn list_large_files(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Vec<super::LargeFile>, ControllerError<errors::ListLargeFilesError>> {
+        self.inner(project_id)
+            .await
+            .list_large_files(project_id, branch_id)
+            .await
+    }
+
+    /// The uncommitted changes of the branch, to name it after, see
+    /// [`super::list_branch_changes`].
+    pub async fn list_branch_changes(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<super::BranchChanges, ControllerError<errors::ListBranchChangesError>> {
+        self.inner(project_id)
+            .await
+            .list_branch_changes(project_id, branch_id)
+            .await
+    }
+
+    /// What the pull request of the branch is written from, see
+    /// [`super::pull_request_context`].
+    pub async fn pull_request_context(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<super::PullRequestContext, ControllerError<errors::PullRequestContextError>> {
+        self.inner(project_id)
+            .await
+            .pull_request_context(project_id, branch_id)
+    }
+
+    /// `diff`, `texts` and `paths` redacted before they're sent to a model, see
+    /// [`super::redact`].
+    pub async fn redact(
+        &self,
+        project_id: &ProjectId,
+        purpose: PromptKind,
+        diff: &str,
+        texts: &[String],
+        paths: &[String],
+        branch_name: Option<&str>,
+    ) -> Result<super::Redacted, ControllerError<errors::RedactionError>> {
+        self.inner(project_id)
+            .await
+            .redact(project_id, purpose, diff, texts, paths, branch_name)
+    }
+
+    /// What was redacted from what was sent to models, see [`super::list_redactions`].
+    pub async fn list_redactions(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::RedactionLogEntry>, ControllerError<errors::RedactionError>> {
+        self.inner(project_id).await.list_redactions(project_id)
+    }
+
+    /// What was done to the workspace outside of GitButler, see
+    /// [`super::list_external_changes`].
+    pub async fn list_external_changes(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::ExternalChange>, ControllerError<errors::ReconcileError>> {
+        self.inner(project_id)
+            .await
+            .list_external_changes(project_id)
+    }
+
+    /// Does one of the actions of a change made outside of GitButler.
+    pub async fn reconcile_external_change(
+        &self,
+        project_id: &ProjectId,
+        change_id: &super::ExternalChangeId,
+        action: super::ReconcileAction,
+    ) -> Result<(), ControllerError<errors::ReconcileError>> {
+        self.inner(project_id)
+            .await
+            .reconcile_external_change(project_id, change_id, action)
+            .await
+    }
+
+    /// Checks the workspace out again after something else was with `git`, bringing what was
+    /// checked out along as a branch, see [`super::recover_workspace`].
+    pub async fn recover_workspace(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<super::WorkspaceRecovery, ControllerError<errors::RecoverWorkspaceError>> {
+        self.inner(project_id)
+            .await
+            .recover_workspace(project_id)
+            .await
+    }
+
+    /// The discarded changes in the trash, see [`super::list_trash`].
+    pub async fn list_trash(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::TrashEntry>, ControllerError<errors::TrashError>> {
+        self.inner(project_id).await.list_trash(project_id).await
+    }
+
+    /// Puts discarded changes back in the working directory.
+    pub async fn restore_from_trash(
+        &self,
+        project_id: &ProjectId,
+        entry_id: &super::TrashEntryId,
+    ) -> Result<(), ControllerError<errors::TrashError>> {
+        self.inner(project_id)
+            .await
+            .restore_from_trash(project_id, entry_id)
+            .await
+    }
+
+    /// Removes an entry of the trash, or all of them if `entry_id` is `None`.
+    pub async fn purge_trash(
+        &self,
+        project_id: &ProjectId,
+        entry_id: Option<&super::TrashEntryId>,
+    ) -> Result<(), ControllerError<errors::TrashError>> {
+        self.inner(project_id)
+            .await
+            .purge_trash(project_id, entry_id)
+            .await
+    }
+
+    /// The snapshots of the project, the most recent first, see [`super::list_snapshots`].
+    pub async fn list_snapshots(
+        &self,
+        project_id: &ProjectId,
+        limit: Option<usize>,
+    ) -> Result<Vec<super::Snapshot>, ControllerError<errors::SnapshotError>> {
+        self.inner(project_id)
+            .await
+            .list_snapshots(project_id, limit)
+    }
+
+    /// What changed in the working directory since a snapshot was taken.
+    pub async fn snapshot_diff(
+        &self,
+        project_id: &ProjectId,
+        snapshot_id: git::Oid,
+    ) -> Result<HashMap<path::PathBuf, Vec<git::diff::Hunk>>, ControllerError<errors::SnapshotError>>
+    {
+        self.inner(project_id)
+            .await
+            .snapshot_diff(project_id, snapshot_id)
+    }
+
+    /// Puts the project back the way it was when a snapshot was taken.
+    pub async fn restore_snapshot(
+        &self,
+        project_id: &ProjectId,
+        snapshot_id: git::Oid,
+    ) -> Result<(), ControllerError<errors::SnapshotError>> {
+        self.inner(project_id)
+            .await
+            .restore_snapshot(project_id, snapshot_id)
+            .await
+    }
+
+    /// The pushes and pull requests waiting for the network, see [`super::list_outbox`].
+    pub async fn list_outbox(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::OutboxEntry>, ControllerError<errors::OutboxError>> {
+        self.inner(project_id).await.list_outbox(project_id)
+    }
+
+    /// What was done to the project, see [`super::list_audit_entries`].
+    pub async fn list_audit_entries(
+        &self,
+        project_id: &ProjectId,
+        query: &super::AuditQuery,
+    ) -> Result<Vec<super::AuditEntry>, ControllerError<errors::AuditError>> {
+        self.inner(project_id)
+            .await
+            .list_audit_entries(project_id, query)
+    }
+
+    /// Queues a push of the branch while offline, see [`super::queue_push`].
+    pub async fn queue_push(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        with_force: bool,
+    ) -> Result<super::OutboxEntry, ControllerError<errors::OutboxError>> {
+        self.inner(project_id)
+            .await
+            .queue_push(project_id, branch_id, with_force)
+            .await
+    }
+
+    /// Queues a pull request of the branch while offline, see [`super::queue_pull_request`].
+    pub async fn queue_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        title: String,
+        body: String,
+        draft: bool,
+    ) -> Result<super::OutboxEntry, ControllerError<errors::OutboxError>> {
+        self.inner(project_id)
+            .await
+            .queue_pull_request(project_id, branch_id, title, body, draft)
+            .await
+    }
+
+    /// Pushes what was queued while offline, see [`super::replay_outbox`].
+    pub async fn replay_outbox(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::Replay>, ControllerError<errors::OutboxError>> {
+        self.inner(project_id).await.replay_outbox(project_id).await
+    }
+
+    /// Takes an entry out of the outbox, like a pull request that was created.
+    pub async fn remove_outbox_entry(
+        &self,
+        project_id: &ProjectId,
+        entry_id: &super::OutboxEntryId,
+    ) -> Result<(), ControllerError<errors::OutboxError>> {
+        self.inner(project_id)
+            .await
+            .remove_outbox_entry(project_id, entry_id)
+            .await
+    }
+
+    /// Tags a commit of a virtual branch or of the target branch.
+    pub async fn create_tag(
+        &self,
+        project_id: &ProjectId,
+        request: &super::TagCreateRequest,
+    ) -> Result<super::Tag, ControllerError<errors::CreateTagError>> {
+        self.inner(project_id)
+            .await
+            .create_tag(project_id, request)
+            .await
+    }
+
+    pub async fn push_tags(
+        &self,
+        project_id: &ProjectId,
+        names: &[String],
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::PushTagsError>> {
+        self.inner(project_id)
+            .await
+            .push_tags(project_id, names, remote)
+            .await
+    }
+
+    pub async fn list_branch_tags(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::BranchTags>, ControllerError<errors::ListTagsError>> {
+        self.inner(project_id).await.list_branch_tags(project_id)
+    }
+
+    /// The tags of the project, only the ones matching the glob `pattern` if there is one.
+    pub async fn list_tags(
+        &self,
+        project_id: &ProjectId,
+        pattern: Option<&str>,
+    ) -> Result<Vec<super::Tag>, ControllerError<errors::ListTagsError>> {
+        self.inner(project_id).await.list_tags(project_id, pattern)
+    }
+
+    /// Deletes a tag, and also from `remote` if there is one.
+    pub async fn delete_tag(
+        &self,
+        project_id: &ProjectId,
+        name: &str,
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::DeleteTagError>> {
+        self.inner(project_id)
+            .await
+            .delete_tag(project_id, name, remote)
+            .await
+    }
+
+    /// The notes of a commit, one for every notes ref that has one.
+    pub async fn list_notes(
+        &self,
+        project_id: &ProjectId,
+        commit_oid: git::Oid,
+    ) -> Result<Vec<super::Note>, ControllerError<errors::ListNotesError>> {
+        self.inner(project_id)
+            .await
+            .list_notes(project_id, commit_oid)
+    }
+
+    /// Adds or edits the note of a commit, or removes it if `message` is empty.
+    pub async fn set_note(
+        &self,
+        project_id: &ProjectId,
+        commit_oid: git::Oid,
+        notes_ref: Option<&str>,
+        message: &str,
+    ) -> Result<(), ControllerError<errors::SetNoteError>> {
+        self.inner(project_id)
+            .await
+            .set_note(project_id, commit_oid, notes_ref, message)
+            .await
+    }
+
+    pub async fn push_notes(
+        &self,
+        project_id: &ProjectId,
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::SyncNotesError>> {
+        self.inner(project_id)
+            .await
+            .push_notes(project_id, remote)
+            .await
+    }
+
+    pub async fn fetch_notes(
+        &self,
+        project_id: &ProjectId,
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::SyncNotesError>> {
+        self.inner(project_id)
+            .await
+            .fetch_notes(project_id, remote)
+            .await
+    }
+
+    pub async fn push_metadata(
+        &self,
+        project_id: &ProjectId,
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::SyncMetadataError>> {
+        self.inner(project_id)
+            .await
+            .push_metadata(project_id, remote)
+            .await
+    }
+
+    pub async fn fetch_metadata(
+        &self,
+        project_id: &ProjectId,
+        remote: Option<&str>,
+    ) -> Result<super::RestoredMetadata, ControllerError<errors::SyncMetadataError>> {
+        self.inner(project_id)
+            .await
+            .fetch_metadata(project_id, remote)
+            .await
+    }
+
+    pub async fn cherry_pick(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+    ) -> Result<Option<git::Oid>, ControllerError<errors::CherryPickError>> {
+        self.inner(project_id)
+            .await
+            .cherry_pick(project_id, branch_id, commit_oid)
+            .await
+    }
+
+    pub async fn list_remote_branches(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::RemoteBranch>, ControllerError<ListRemoteBranchesError>> {
+        self.inner(project_id)
+            .await
+            .list_remote_branches(project_id)
+    }
+
+    pub async fn get_remote_branch_data(
+        &self,
+        project_id: &ProjectId,
+        refname: &git::Refname,
+    ) -> Result<super::RemoteBranchData, ControllerError<GetRemoteBranchDataError>> {
+        self.inner(project_id)
+            .await
+            .get_remote_branch_data(project_id, refname)
+    }
+
+    pub async fn squash(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+    ) -> Result<(), ControllerError<errors::SquashError>> {
+        self.inner(project_id)
+            .await
+            .squash(project_id, branch_id, commit_oid)
+            .await
+    }
+
+    pub async fn update_commit_message(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+        message: &str,
+    ) -> Result<(), ControllerError<errors::UpdateCommitMessageError>> {
+        self.inner(project_id)
+            .await
+            .update_commit_message(project_id, branch_id, commit_oid, message)
+            .await
+    }
+
+    pub async fn fetch_from_target(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<BaseBranch, ControllerError<errors::FetchFromTargetError>> {
+        self.fetch_all_remotes(project_id)
+            .await
+            .map(|summary| summary.base_branch)
+    }
+
+    /// Fetches more of the history of the target of a shallow clone, reporting the transfer
+    /// to `progress`. See [`super::deepen_history`].
+    pub async fn deepen_history(
+        &self,
+        project_id: &ProjectId,
+        by: Option<u32>,
+        progress: &project_repository::FetchProgress,
+    ) -> Result<BaseBranch, ControllerError<errors::DeepenHistoryError>> {
+        self.inner(project_id)
+            .await
+            .deepen_history(project_id, by, progress)
+            .await
+    }
+
+    /// Fetches all remotes of the project concurrently. Failing to fetch a remote does not
+    /// fail the others: the outcome of each one is part of the returned summary.
+    pub async fn fetch_all_remotes(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<FetchSummary, ControllerError<errors::FetchFromTargetError>> {
+        self.inner(project_id)
+            .await
+            .fetch_all_remotes(project_id)
+            .await
+    }
+}
+
+#[derive(Clone)]
+struct ControllerInner {
+    local_data_dir: path::PathBuf,
+    queue: Arc<OperationQueue>,
+    requested_by: Requester,
+    on_retry: Option<project_repository::OnRetry>,
+    // the version of the last state of the project that was read
+    state_version: Arc<AtomicU64>,
+
+    projects: projects::Controller,
+    users: users::Controller,
+    keys: keys::Controller,
+    helper: git::credentials::Helper,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ControllerError<E>
+where
+    E: Into<Error>,
+{
+    #[error(transparent)]
+    VerifyError(#[from] errors::VerifyError),
+    #[error(transparent)]
+    Action(E),
+    #[error(transparent)]
+    User(#[from] Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl<E: Into<Error>> From<ControllerError<E>> for Error {
+    fn from(value: ControllerError<E>) -> Self {
+        match value {
+            ControllerError::User(error) => error,
+            ControllerError::Action(error) => error.into(),
+            ControllerError::VerifyError(error) => error.into(),
+            ControllerError::Other(error) => {
+                tracing::error!(?error, "failed to verify branch");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<&path::PathBuf> for ControllerInner {
+    fn from(value: &path::PathBuf) -> Self {
+        Self::new(
+            value,
+            &projects::Controller::from(value),
+            &users::Controller::from(value),
+            &keys::Controller::from(value),
+            &git::credentials::Helper::from(value),
+        )
+    }
+}
+
+impl ControllerInner {
+    pub fn new(
+        data_dir: &path::Path,
+        projects: &projects::Controller,
+        users: &users::Controller,
+        keys: &keys::Controller,
+        helper: &git::credentials::Helper,
+    ) -> Self {
+        Self {
+            local_data_dir: data_dir.to_path_buf(),
+            queue: Arc::new(OperationQueue::new()),
+            requested_by: Requester::default(),
+            on_retry: None,
+            state_version: Arc::default(),
+            projects: projects.clone(),
+            users: users.clone(),
+            keys: keys.clone(),
+            helper: helper.clone(),
+        }
+    }
+
+    fn with_queue(self, queue: OperationQueue) -> Self {
+        Self {
+            queue: Arc::new(queue),
+            ..self
+        }
+    }
+
+    pub fn list_operations(&self) -> Vec<QueuedOperation> {
+        self.queue.operations()
+    }
+
+    pub fn cancel_operation(&self, operation_id: OperationId) -> Result<(), Error> {
+        if self.queue.cancel(operation_id) {
+            Ok(())
+        } else {
+            Err(Error::UserError {
+                code: crate::error::Code::Validation,
+                message: "The operation is finished or can't be cancelled".to_string(),
+            })
+        }
+    }
+
+    pub async fn create_commit(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        message: &str,
+        ownership: Option<&Ownership>,
+        run_hooks: bool,
+    ) -> Result<git::Oid, ControllerError<errors::CommitError>> {
+        let permit = self.queue.enqueue("create_commit", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::commit(
+                gb_repository,
+                project_repository,
+                branch_id,
+                message,
+                ownership,
+                signing_key.as_ref(),
+                user,
+                run_hooks,
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub async fn create_commit_no_verify(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        message: &str,
+        ownership: Option<&Ownership>,
+    ) -> Result<git::Oid, ControllerError<errors::CommitError>> {
+        let permit = self
+            .queue
+            .enqueue("create_commit_no_verify", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::commit_no_verify(
+                gb_repository,
+                project_repository,
+                branch_id,
+                message,
+                ownership,
+                signing_key.as_ref(),
+                user,
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub fn can_apply_remote_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_name: &git::RemoteRefname,
+    ) -> Result<bool, ControllerError<IsRemoteBranchMergableError>> {
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        super::is_remote_branch_mergeable(&gb_repository, &project_repository, branch_name)
+            .map_err(ControllerError::Action)
+    }
+
+    pub fn can_apply_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<bool, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        let user = self.users.get_user().context("failed to get user")?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        super::is_virtual_branch_mergeable(&gb_repository, &project_repository, branch_id)
+            .map_err(Into::into)
+    }
+
+    pub async fn list_virtual_branches(
+        &self,
+        project_id: &ProjectId,
+        cancel: &CancellationToken,
+        progress: &Progress,
+    ) -> Result<Vec<super::VirtualBranch>, ControllerError<errors::ListVirtualBranchesError>> {
+        let permit = self
+            .queue
+            .enqueue("list_virtual_branches", self.requested_by)
+            .await;
+
+        if cancel.is_cancelled() {
+            return Err(ControllerError::Action(
+                errors::ListVirtualBranchesError::Cancelled,
+            ));
+        }
+
+        // listing updates the branches with the changes of the working directory
+        let _audit = self.record(project_id, &permit)?.only_changes();
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::list_virtual_branches_cancellable(
+                gb_repository,
+                project_repository,
+                cancel,
+                progress,
+            )
+        })
+    }
+
+    pub async fn create_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        create: &super::branch::BranchCreateRequest,
+    ) -> Result<BranchId, ControllerError<errors::CreateVirtualBranchError>> {
+        let permit = self
+            .queue
+            .enqueue("create_virtual_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            let branch = super::create_virtual_branch(gb_repository, project_repository, create)?;
+            hooks::notify(
+                project_repository,
+                &hooks::Event::PostBranchCreate {
+                    project_id: *project_id,
+                    branch: hooks::Branch {
+                        id: branch.id,
+                        name: branch.name.clone(),
+                    },
+                },
+            );
+            Ok(branch.id)
+        })
+    }
+
+    pub async fn create_virtual_branch_from_branch(
+        &self,
+        project_id: &ProjectId,
+        branch: &git::Refname,
+    ) -> Result<BranchId, ControllerError<errors::CreateVirtualBranchFromBranchError>> {
+        let permit = self
+            .queue
+            .enqueue("create_virtual_branch_from_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            let branch_id = super::create_virtual_branch_from_branch(
+                gb_repository,
+                project_repository,
+                branch,
+                signing_key.as_ref(),
+                user,
+            )?;
+            hooks::notify(
+                project_repository,
+                &hooks::Event::PostBranchCreate {
+                    project_id: *project_id,
+                    branch: hooks::Branch {
+                        id: branch_id,
+                        name: branch.branch().unwrap_or_default().to_string(),
+                    },
+                },
+            );
+            Ok(branch_id)
+        })
+    }
+
+    pub fn list_lost_commits(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Vec<super::LostCommit>, ControllerError<errors::ListLostCommitsError>> {
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::list_lost_commits(gb_repository, project_repository, branch_id)
+        })
+    }
+
+    pub async fn recover_commit(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+    ) -> Result<BranchId, ControllerError<errors::RecoverCommitError>> {
+        let permit = self
+            .queue
+            .enqueue("recover_commit", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::recover_commit(
+                gb_repository,
+                project_repository,
+                branch_id,
+                commit_oid,
+                signing_key.as_ref(),
+                user,
+            )
+        })
+    }
+
+    pub async fn export_bundle(
+        &self,
+        project_id: &ProjectId,
+        branch_ids: &[BranchId],
+        path: &path::Path,
+    ) -> Result<(), ControllerError<errors::ExportBundleError>> {
+        let permit = self.queue.enqueue("export_bundle", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::export_bundle(gb_repository, project_repository, branch_ids, path)
+        })
+    }
+
+    pub async fn import_bundle(
+        &self,
+        project_id: &ProjectId,
+        path: &path::Path,
+    ) -> Result<Vec<BranchId>, ControllerError<errors::ImportBundleError>> {
+        let permit = self.queue.enqueue("import_bundle", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::import_bundle(
+                gb_repository,
+                project_repository,
+                path,
+                signing_key.as_ref(),
+                user,
+            )
+        })
+    }
+
+    pub async fn export_virtual_branches(
+        &self,
+        project_id: &ProjectId,
+        path: &path::Path,
+    ) -> Result<(), ControllerError<errors::ExportBundleError>> {
+        let permit = self
+            .queue
+            .enqueue("export_virtual_branches", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::export_virtual_branches(gb_repository, project_repository, path)
+        })
+    }
+
+    pub async fn import_virtual_branches(
+        &self,
+        project_id: &ProjectId,
+        path: &path::Path,
+    ) -> Result<Vec<BranchId>, ControllerError<errors::ImportBundleError>> {
+        let permit = self
+            .queue
+            .enqueue("import_virtual_branches", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::import_virtual_branches(
+                gb_repository,
+                project_repository,
+                path,
+                signing_key.as_ref(),
+                user,
+            )
+        })
+    }
+
+    pub async fn repair_project(
+        &self,
+        project_id: &ProjectId,
+        fix: bool,
+    ) -> Result<Vec<super::Issue>, ControllerError<errors::RepairProjectError>> {
+        let permit = self
+            .queue
+            .enqueue("repair_project", self.requested_by)
+            .await;
+
+        // checking doesn't change anything, fixing does
+        let _audit = fix
+            .then(|| self.verify_writable_to_repair(project_id, &permit))
+            .transpose()?;
+
+        // the branch isn't verified, a project that fails to verify is what this is for
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        super::repair_project(&gb_repository, &project_repository, fix)
+            .map_err(ControllerError::Action)
+    }
+
+    // the branch isn't verified, in the middle of an operation `HEAD` is rarely the
+    // integration branch
+    pub fn get_external_operation(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Option<project_repository::ExternalOperation>, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        project_repository::external_operation(&project_repository)
+            .context("failed to read repository state")
+            .map_err(Into::into)
+    }
+
+    pub async fn finish_external_operation(
+        &self,
+        project_id: &ProjectId,
+        action: project_repository::OperationAction,
+    ) -> Result<(), Error> {
+        let permit = self
+            .queue
+            .enqueue("finish_external_operation", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable_during_external_operation(project_id, &permit)?;
+
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        project_repository::finish_external_operation(&project_repository, action)
+            .map_err(Into::into)
+    }
+
+    pub fn get_workspace_branch(&self, project_id: &ProjectId) -> Result<String, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        Ok(super::workspace_reference(&project_repository)?
+            .branch()
+            .to_string())
+    }
+
+    pub async fn set_workspace_branch(
+        &self,
+        project_id: &ProjectId,
+        name: &str,
+    ) -> Result<(), ControllerError<errors::SetWorkspaceBranchError>> {
+        let permit = self
+            .queue
+            .enqueue("set_workspace_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        super::set_workspace_branch(&project_repository, name).map_err(ControllerError::Action)
+    }
+
+    pub async fn get_project_state(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<super::ProjectState, ControllerError<errors::GetProjectStateError>> {
+        let _permit = self
+            .queue
+            .enqueue("get_project_state", self.requested_by)
+            .await;
+
+        let version = self.state_version.fetch_add(1, Ordering::SeqCst) + 1;
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::get_project_state(gb_repository, project_repository, version)
+        })
+    }
+
+    pub fn get_base_branch_data(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Option<super::BaseBranch>, ControllerError<GetBaseBranchDataError>> {
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        let base_branch = super::get_base_branch_data(&gb_repository, &project_repository)
+            .map_err(ControllerError::Action)?;
+        Ok(base_branch)
+    }
+
+    pub fn list_remote_commit_files(
+        &self,
+        project_id: &ProjectId,
+        commit_oid: git::Oid,
+        options: &projects::DiffOptions,
+    ) -> Result<Vec<RemoteBranchFile>, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+
+        super::list_remote_commit_files(
+            &project_repository.git_repository,
+            commit_oid,
+            &project.diff_options.with(*options).into(),
+        )
+        .map_err(Into::into)
+    }
+
+    pub fn blame(
+        &self,
+        project_id: &ProjectId,
+        path: &path::Path,
+        rev: git::Oid,
+        lines: Option<RangeInclusive<usize>>,
+    ) -> Result<super::Blame, ControllerError<errors::BlameError>> {
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+
+        super::blame(&project_repository, path, rev, lines).map_err(ControllerError::Action)
+    }
+
+    pub fn list_line_endings(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::FileLineEndings>, ControllerError<errors::ListLineEndingsError>> {
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::list_line_endings(gb_repository, project_repository)
+        })
+    }
+
+    pub async fn normalize_line_endings(
+        &self,
+        project_id: &ProjectId,
+        paths: &[path::PathBuf],
+    ) -> Result<(), ControllerError<errors::NormalizeLineEndingsError>> {
+        let permit = self
+            .queue
+            .enqueue("normalize_line_endings", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::normalize_line_endings(gb_repository, project_repository, paths)
+        })
+    }
+
+    pub async fn set_base_branch(
+        &self,
+        project_id: &ProjectId,
+        target_branch: &git::RemoteRefname,
+    ) -> Result<super::BaseBranch, Error> {
+        let permit = self
+            .queue
+            .enqueue("set_base_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        let project = self.projects.get(project_id)?;
+        let user = self.users.get_user()?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+
+        super::set_base_branch(&gb_repository, &project_repository, target_branch)
+            .map_err(Into::into)
+    }
+
+    pub async fn merge_virtual_branch_upstream(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<(), ControllerError<errors::MergeVirtualBranchUpstreamError>> {
+        let permit = self
+            .queue
+            .enqueue("merge_virtual_branch_upstream", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::merge_virtual_branch_upstream(
+                gb_repository,
+                project_repository,
+                branch_id,
+                signing_key.as_ref(),
+                user,
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub async fn update_base_branch(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<(), ControllerError<errors::UpdateBaseBranchError>> {
+        let permit = self
+            .queue
+            .enqueue("update_base_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            let previous_target = gb_repository
+                .default_target()
+                .context("failed to get default target")?;
+            let previous_branches = read_branches(gb_repository)?;
+
+            super::update_base_branch(
+                gb_repository,
+                project_repository,
+                user,
+                signing_key.as_ref(),
+            )?;
+
+            if let Some(previous_target) = previous_target {
+                if let Err(error) = notify_integration(
+                    gb_repository,
+                    project_repository,
+                    &previous_target,
+                    &previous_branches,
+                ) {
+                    tracing::warn!(%project_id, ?error, "failed to run post-integration hook");
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    pub async fn update_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_update: super::branch::BranchUpdateRequest,
+    ) -> Result<(), ControllerError<errors::UpdateBranchError>> {
+        let permit = self
+            .queue
+            .enqueue("update_virtual_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::update_branch(gb_repository, project_repository, branch_update)?;
+            Ok(())
+        })
+    }
+
+    pub async fn delete_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<(), ControllerError<errors::DeleteBranchError>> {
+        let permit = self
+            .queue
+            .enqueue("delete_virtual_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::delete_branch(gb_repository, project_repository, branch_id)?;
+            Ok(())
+        })
+    }
+
+    pub async fn apply_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<(), ControllerError<errors::ApplyBranchError>> {
+        let permit = self
+            .queue
+            .enqueue("apply_virtual_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::apply_branch(
+                gb_repository,
+                project_repository,
+                branch_id,
+                signing_key.as_ref(),
+                user,
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub async fn unapply_ownership(
+        &self,
+        project_id: &ProjectId,
+        ownership: &Ownership,
+    ) -> Result<(), ControllerError<errors::UnapplyOwnershipError>> {
+        let permit = self
+            .queue
+            .enqueue("unapply_ownership", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::unapply_ownership(gb_repository, project_repository, ownership)
+                .map_err(Into::into)
+        })
+    }
+
+    pub async fn amend(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        ownership: &Ownership,
+    ) -> Result<git::Oid, ControllerError<errors::AmendError>> {
+        let permit = self.queue.enqueue("amend", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::amend(gb_repository, project_repository, branch_id, ownership)
+                .map_err(Into::into)
+        })
+    }
+
+    pub async fn reset_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        target_commit_oid: git::Oid,
+    ) -> Result<(), ControllerError<errors::ResetBranchError>> {
+        let permit = self
+            .queue
+            .enqueue("reset_virtual_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::reset_branch(
+                gb_repository,
+                project_repository,
+                branch_id,
+                target_commit_oid,
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub async fn unapply_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<(), ControllerError<errors::UnapplyBranchError>> {
+        let permit = self
+            .queue
+            .enqueue("unapply_virtual_branch", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::unapply_branch(gb_repository, project_repository, branch_id)
+                .map(|_| ())
+                .map_err(Into::into)
+        })
+    }
+
+    pub async fn push_virtual_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        with_force: bool,
+        progress: &super::PrePushProgress,
+    ) -> Result<(), ControllerError<errors::PushError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("push_virtual_branch", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch_cancellable(
+            project_id,
+            permit.cancellation(),
+            |gb_repository, project_repository, _| {
+                super::push(
+                    project_repository,
+                    gb_repository,
+                    branch_id,
+                    with_force,
+                    &self.helper,
+                    progress,
+                )
+                .map_err(Into::into)
+            },
+        )
+    }
+
+    pub async fn check_push(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        with_force: bool,
+        progress: &super::PrePushProgress,
+    ) -> Result<super::PrePushReport, ControllerError<errors::PushError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("check_push", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        self.with_verify_branch_cancellable(
+            project_id,
+            permit.cancellation(),
+            |gb_repository, project_repository, _| {
+                super::check_push(
+                    project_repository,
+                    gb_repository,
+                    branch_id,
+                    with_force,
+                    progress,
+                )
+            },
+        )
+    }
+
+    pub async fn scan_secrets(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Vec<super::SecretFinding>, ControllerError<errors::ScanSecretsError>> {
+        let _permit = self.queue.enqueue("scan_secrets", self.requested_by).await;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::scan_secrets(gb_repository, project_repository, branch_id)
+        })
+    }
+
+    pub async fn list_large_files(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Vec<super::LargeFile>, ControllerError<errors::ListLargeFilesError>> {
+        let _permit = self
+            .queue
+            .enqueue("list_large_files", self.requested_by)
+            .await;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::list_large_files(gb_repository, project_repository, branch_id)
+        })
+    }
+
+    pub async fn list_branch_changes(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<super::BranchChanges, ControllerError<errors::ListBranchChangesError>> {
+        let _permit = self
+            .queue
+            .enqueue("list_branch_changes", self.requested_by)
+            .await;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::list_branch_changes(gb_repository, project_repository, branch_id)
+        })
+    }
+
+    pub fn pull_request_context(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<super::PullRequestContext, ControllerError<errors::PullRequestContextError>> {
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::pull_request_context(gb_repository, project_repository, branch_id)
+        })
+    }
+
+    pub fn redact(
+        &self,
+        project_id: &ProjectId,
+        purpose: PromptKind,
+        diff: &str,
+        texts: &[String],
+        paths: &[String],
+        branch_name: Option<&str>,
+    ) -> Result<super::Redacted, ControllerError<errors::RedactionError>> {
+        self.without_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::redact(
+                gb_repository,
+                project_repository,
+                purpose,
+                diff,
+                texts,
+                paths,
+                branch_name,
+            )
+        })
+    }
+
+    pub fn list_redactions(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::RedactionLogEntry>, ControllerError<errors::RedactionError>> {
+        self.without_verify_branch(project_id, |gb_repository, _, _| {
+            super::list_redactions(gb_repository)
+        })
+    }
+
+    pub fn list_external_changes(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::ExternalChange>, ControllerError<errors::ReconcileError>> {
+        self.without_verify_branch(project_id, |gb_repository, _, _| {
+            super::list_external_changes(gb_repository)
+        })
+    }
+
+    pub async fn reconcile_external_change(
+        &self,
+        project_id: &ProjectId,
+        change_id: &super::ExternalChangeId,
+        action: super::ReconcileAction,
+    ) -> Result<(), ControllerError<errors::ReconcileError>> {
+        let permit = self
+            .queue
+            .enqueue("reconcile_external_change", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.without_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::reconcile_external_change(gb_repository, project_repository, change_id, action)
+        })
+    }
+
+    pub async fn recover_workspace(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<super::WorkspaceRecovery, ControllerError<errors::RecoverWorkspaceError>> {
+        let permit = self
+            .queue
+            .enqueue("recover_workspace", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable_to_repair(project_id, &permit)?;
+
+        self.without_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::recover_workspace(
+                gb_repository,
+                project_repository,
+                signing_key.as_ref(),
+                user,
+            )
+        })
+    }
+
+    pub async fn list_trash(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::TrashEntry>, ControllerError<errors::TrashError>> {
+        // listing removes the entries that expired
+        let _permit = self.queue.enqueue("list_trash", self.requested_by).await;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::list_trash(gb_repository, project_repository)
+        })
+    }
+
+    pub async fn restore_from_trash(
+        &self,
+        project_id: &ProjectId,
+        entry_id: &super::TrashEntryId,
+    ) -> Result<(), ControllerError<errors::TrashError>> {
+        let permit = self
+            .queue
+            .enqueue("restore_from_trash", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::restore_from_trash(gb_repository, project_repository, entry_id)
+        })
+    }
+
+    pub async fn purge_trash(
+        &self,
+        project_id: &ProjectId,
+        entry_id: Option<&super::TrashEntryId>,
+    ) -> Result<(), ControllerError<errors::TrashError>> {
+        let permit = self.queue.enqueue("purge_trash", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, _, _| {
+            super::purge_trash(gb_repository, entry_id)
+        })
+    }
+
+    pub fn list_snapshots(
+        &self,
+        project_id: &ProjectId,
+        limit: Option<usize>,
+    ) -> Result<Vec<super::Snapshot>, ControllerError<errors::SnapshotError>> {
+        self.without_verify_branch(project_id, |_, project_repository, _| {
+            super::list_snapshots(project_repository, limit)
+        })
+    }
+
+    pub fn snapshot_diff(
+        &self,
+        project_id: &ProjectId,
+        snapshot_id: git::Oid,
+    ) -> Result<HashMap<path::PathBuf, Vec<git::diff::Hunk>>, ControllerError<errors::SnapshotError>>
+    {
+        self.without_verify_branch(project_id, |_, project_repository, _| {
+            super::snapshot_diff(project_repository, snapshot_id)
+        })
+    }
+
+    // works with something else than the workspace checked out, it's what gets things back
+    // to normal when they went wrong
+    pub async fn restore_snapshot(
+        &self,
+        project_id: &ProjectId,
+        snapshot_id: git::Oid,
+    ) -> Result<(), ControllerError<errors::SnapshotError>> {
+        let permit = self
+            .queue
+            .enqueue("restore_snapshot", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.without_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::restore_snapshot(gb_repository, project_repository, snapshot_id)
+        })
+    }
+
+    pub fn list_outbox(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::OutboxEntry>, ControllerError<errors::OutboxError>> {
+        self.without_verify_branch(project_id, |gb_repository, _, _| {
+            super::list_outbox(gb_repository).map_err(Into::into)
+        })
+    }
+
+    pub fn list_audit_entries(
+        &self,
+        project_id: &ProjectId,
+        query: &super::AuditQuery,
+    ) -> Result<Vec<super::AuditEntry>, ControllerError<errors::AuditError>> {
+        self.without_verify_branch(project_id, |gb_repository, _, _| {
+            super::list_audit_entries(gb_repository, query)
+        })
+    }
+
+    pub async fn queue_push(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        with_force: bool,
+    ) -> Result<super::OutboxEntry, ControllerError<errors::OutboxError>> {
+        let permit = self.queue.enqueue("queue_push", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::queue_push(gb_repository, project_repository, branch_id, with_force)
+                .map_err(Into::into)
+        })
+    }
+
+    pub async fn queue_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        title: String,
+        body: String,
+        draft: bool,
+    ) -> Result<super::OutboxEntry, ControllerError<errors::OutboxError>> {
+        let permit = self
+            .queue
+            .enqueue("queue_pull_request", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::queue_pull_request(
+                gb_repository,
+                project_repository,
+                branch_id,
+                title,
+                body,
+                draft,
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub async fn replay_outbox(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::Replay>, ControllerError<errors::OutboxError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("replay_outbox", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch_cancellable(
+            project_id,
+            permit.cancellation(),
+            |gb_repository, project_repository, _| {
+                super::replay_outbox(gb_repository, project_repository, &self.helper)
+                    .map_err(Into::into)
+            },
+        )
+    }
+
+    pub async fn remove_outbox_entry(
+        &self,
+        project_id: &ProjectId,
+        entry_id: &super::OutboxEntryId,
+    ) -> Result<(), ControllerError<errors::OutboxError>> {
+        let permit = self
+            .queue
+            .enqueue("remove_outbox_entry", self.requested_by)
+            .await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.without_verify_branch(project_id, |gb_repository, _, _| {
+            super::remove_outbox_entry(gb_repository, entry_id).map_err(Into::into)
+        })
+    }
+
+    pub async fn create_tag(
+        &self,
+        project_id: &ProjectId,
+        request: &super::TagCreateRequest,
+    ) -> Result<super::Tag, ControllerError<errors::CreateTagError>> {
+        let permit = self.queue.enqueue("create_tag", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = (request.kind == super::TagKind::Signed)
+                .then(|| {
+                    self.keys
+                        .get_or_create()
+                        .context("failed to get private key")
+                })
+                .transpose()?;
+
+            super::create_tag(
+                gb_repository,
+                project_repository,
+                request,
+                signing_key.as_ref(),
+                user,
+            )
+        })
+    }
+
+    pub async fn push_tags(
+        &self,
+        project_id: &ProjectId,
+        names: &[String],
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::PushTagsError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("push_tags", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch_cancellable(
+            project_id,
+            permit.cancellation(),
+            |gb_repository, project_repository, _| {
+                super::push_tags(
+                    gb_repository,
+                    project_repository,
+                    names,
+                    remote,
+                    &self.helper,
+                )
+            },
+        )
+    }
+
+    pub fn list_branch_tags(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::BranchTags>, ControllerError<errors::ListTagsError>> {
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::list_branch_tags(gb_repository, project_repository)
+        })
+    }
+
+    pub fn list_tags(
+        &self,
+        project_id: &ProjectId,
+        pattern: Option<&str>,
+    ) -> Result<Vec<super::Tag>, ControllerError<errors::ListTagsError>> {
+        self.with_verify_branch(project_id, |_, project_repository, _| {
+            super::list_tags(project_repository, pattern)
+        })
+    }
+
+    pub async fn delete_tag(
+        &self,
+        project_id: &ProjectId,
+        name: &str,
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::DeleteTagError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("delete_tag", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch_cancellable(
+            project_id,
+            permit.cancellation(),
+            |_, project_repository, _| {
+                super::delete_tag(project_repository, name, remote, &self.helper)
+            },
+        )
+    }
+
+    pub fn list_notes(
+        &self,
+        project_id: &ProjectId,
+        commit_oid: git::Oid,
+    ) -> Result<Vec<super::Note>, ControllerError<errors::ListNotesError>> {
+        self.with_verify_branch(project_id, |_, project_repository, _| {
+            super::list_notes(project_repository, commit_oid)
+        })
+    }
+
+    pub async fn set_note(
+        &self,
+        project_id: &ProjectId,
+        commit_oid: git::Oid,
+        notes_ref: Option<&str>,
+        message: &str,
+    ) -> Result<(), ControllerError<errors::SetNoteError>> {
+        let permit = self.queue.enqueue("set_note", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |_, project_repository, user| {
+            super::set_note(project_repository, commit_oid, notes_ref, message, user)
+        })
+    }
+
+    pub async fn push_notes(
+        &self,
+        project_id: &ProjectId,
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::SyncNotesError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("push_notes", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch_cancellable(
+            project_id,
+            permit.cancellation(),
+            |gb_repository, project_repository, _| {
+                super::push_notes(gb_repository, project_repository, remote, &self.helper)
+            },
+        )
+    }
+
+    pub async fn fetch_notes(
+        &self,
+        project_id: &ProjectId,
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::SyncNotesError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("fetch_notes", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable_during_external_operation(project_id, &permit)?;
+
+        self.with_verify_branch_cancellable(
+            project_id,
+            permit.cancellation(),
+            |gb_repository, project_repository, user| {
+                super::fetch_notes(
+                    gb_repository,
+                    project_repository,
+                    remote,
+                    &self.helper,
+                    user,
+                )
+            },
+        )
+    }
+
+    pub async fn push_metadata(
+        &self,
+        project_id: &ProjectId,
+        remote: Option<&str>,
+    ) -> Result<(), ControllerError<errors::SyncMetadataError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("push_metadata", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch_cancellable(
+            project_id,
+            permit.cancellation(),
+            |gb_repository, project_repository, user| {
+                super::push_metadata(
+                    gb_repository,
+                    project_repository,
+                    remote,
+                    &self.helper,
+                    user,
+                )
+            },
+        )
+    }
+
+    pub async fn fetch_metadata(
+        &self,
+        project_id: &ProjectId,
+        remote: Option<&str>,
+    ) -> Result<super::RestoredMetadata, ControllerError<errors::SyncMetadataError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("fetch_metadata", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch_cancellable(
+            project_id,
+            permit.cancellation(),
+            |gb_repository, project_repository, _| {
+                super::fetch_metadata(gb_repository, project_repository, remote, &self.helper)
+            },
+        )
+    }
+
+    pub async fn cherry_pick(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+    ) -> Result<Option<git::Oid>, ControllerError<errors::CherryPickError>> {
+        let permit = self.queue.enqueue("cherry_pick", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::cherry_pick(gb_repository, project_repository, branch_id, commit_oid)
+                .map_err(Into::into)
+        })
+    }
+
+    pub fn list_remote_branches(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::RemoteBranch>, ControllerError<ListRemoteBranchesError>> {
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        super::list_remote_branches(&gb_repository, &project_repository)
+            .map_err(ControllerError::Action)
+    }
+
+    pub fn get_remote_branch_data(
+        &self,
+        project_id: &ProjectId,
+        refname: &git::Refname,
+    ) -> Result<super::RemoteBranchData, ControllerError<GetRemoteBranchDataError>> {
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        super::get_branch_data(&gb_repository, &project_repository, refname)
+            .map_err(ControllerError::Action)
+    }
+
+    pub async fn squash(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+    ) -> Result<(), ControllerError<errors::SquashError>> {
+        let permit = self.queue.enqueue("squash", self.requested_by).await;
+
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::squash(gb_repository, project_repository, branch_id, commit_oid)
+                .map_err(Into::into)
+        })
+    }
+
+    pub async fn update_commit_message(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+        message: &str,
+    ) -> Result<(), ControllerError<errors::UpdateCommitMessageError>> {
+        let permit = self
+            .queue
+            .enqueue("update_commit_message", self.requested_by)
+            .await;
+        let _audit = self.verify_writable(project_id, &permit)?;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
+            super::update_commit_message(
+                gb_repository,
+                project_repository,
+                branch_id,
+                commit_oid,
+                message,
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub async fn fetch_all_remotes(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<FetchSummary, ControllerError<errors::FetchFromTargetError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("fetch_from_target", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable_during_external_operation(project_id, &permit)?;
+
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let mut project_repository = self.open_remote(&project, permit.cancellation())?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+
+        let default_target = gb_repository
+            .default_target()
+            .context("failed to get default target")?
+            .ok_or(FetchFromTargetError::DefaultTargetNotSet(
+                errors::DefaultTargetNotSetError {
+                    project_id: *project_id,
+                },
+            ))
+            .map_err(ControllerError::Action)?;
+
+        let fetches = project_repository
+            .fetch_all(&self.helper)
+            .context("failed to fetch remotes")?;
+        if permit.cancellation().is_cancelled() {
+            return Err(ControllerError::Action(FetchFromTargetError::Remote(
+                project_repository::RemoteError::Cancelled,
+            )));
+        }
+
+        let mut project_data_last_fetched = projects::FetchResult::Error {
+            timestamp: std::time::SystemTime::now(),
+            error: format!("remote {} not found", default_target.branch.remote()),
+        };
+        let mut remotes = Vec::with_capacity(fetches.len());
+        for (remote, result) in fetches {
+            let error = result
+                .map_err(errors::FetchFromTargetError::Remote)
+                .err()
+                .map(|error| error.to_string());
+            if let Some(error) = &error {
+                tracing::warn!(%project_id, %remote, %error, "failed to fetch remote");
+            }
+            // the fetch status of the project is the one of its target
+            if remote == default_target.branch.remote() {
+                project_data_last_fetched = match &error {
+                    None => projects::FetchResult::Fetched {
+                        timestamp: std::time::SystemTime::now(),
+                    },
+                    Some(error) => projects::FetchResult::Error {
+                        timestamp: std::time::SystemTime::now(),
+                        error: error.clone(),
+                    },
+                };
+            }
+            remotes.push(RemoteFetch { remote, error });
+        }
+
+        let updated_project = self
+            .projects
+            .update(&projects::UpdateRequest {
+                id: *project_id,
+                project_data_last_fetched: Some(project_data_last_fetched),
+                ..Default::default()
+            })
+            .await
+            .context("failed to update project")?;
+
+        project_repository.set_project(&updated_project);
+
+        let base_branch = target_to_base_branch(&project_repository, &default_target)
+            .context("failed to convert target to base branch")?;
+
+        Ok(FetchSummary {
+            base_branch,
+            remotes,
+        })
+    }
+
+    pub async fn deepen_history(
+        &self,
+        project_id: &ProjectId,
+        by: Option<u32>,
+        progress: &project_repository::FetchProgress,
+    ) -> Result<BaseBranch, ControllerError<errors::DeepenHistoryError>> {
+        let Some(permit) = self
+            .queue
+            .enqueue_cancellable("deepen_history", self.requested_by)
+            .await
+        else {
+            return Err(cancelled());
+        };
+
+        let _audit = self.verify_writable_during_external_operation(project_id, &permit)?;
+
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository = self.open_remote(&project, permit.cancellation())?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+
+        super::deepen_history(
+            &gb_repository,
+            &project_repository,
+            by,
+            &self.helper,
+            progress,
+        )
+        .map_err(ControllerError::Action)
+    }
+}
+
+impl ControllerInner {
+    // read-only projects can be looked at, but nothing that changes them is run. what does
+    // run is snapshotted first, and recorded in the audit log of the project once the
+    // recorder this returns, which the permit outlives, is dropped. an operation that can't
+    // be snapshotted isn't run, there would be no undoing it
+    fn verify_writable(
+        &self,
+        project_id: &ProjectId,
+        permit: &OperationPermit<'_>,
+    ) -> Result<audit::Recorder, Error> {
+        self.verify_writable_snapshotted(project_id, permit, true)
+    }
+
+    // like `verify_writable`, for what repairs a project, which is run even if it can't be
+    // snapshotted, a broken project is what fails to be
+    fn verify_writable_to_repair(
+        &self,
+        project_id: &ProjectId,
+        permit: &OperationPermit<'_>,
+    ) -> Result<audit::Recorder, Error> {
+        self.verify_writable_snapshotted(project_id, permit, false)
+    }
+
+    fn verify_writable_snapshotted(
+        &self,
+        project_id: &ProjectId,
+        permit: &OperationPermit<'_>,
+        snapshot_required: bool,
+    ) -> Result<audit::Recorder, Error> {
+        let project = self.writable_project(project_id)?;
+        // neither are they while a merge or a rebase started outside of gitbutler is in
+        // progress, until it's finished or aborted
+        let project_repository = project_repository::Repository::open(&project)?;
+        if let Some(operation) = project_repository::external_operation(&project_repository)
+            .context("failed to read repository state")?
+        {
+            return Err(errors::ExternalOperationError {
+                project_id: *project_id,
+                operation,
+            }
+            .into());
+        }
+        let user = self.users.get_user()?;
+        // what the operation does can be undone by restoring the snapshot taken before it
+        let snapshot = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")
+        .and_then(|gb_repository| {
+            super::oplog::take_snapshot(&gb_repository, &project_repository, permit.kind())
+        })
+        .context("failed to take a snapshot before the operation");
+        match snapshot {
+            Err(error) if snapshot_required => return Err(error.into()),
+            Err(error) => {
+                tracing::warn!(?error, %project_id, kind = permit.kind(), "failed to take snapshot");
+            }
+            Ok(_) => {}
+        }
+        Ok(self.start_recording(&project_repository, user.as_ref(), permit))
+    }
+
+    // like `verify_writable`, for what leaves the working directory and the branches alone,
+    // with nothing to snapshot: fetches, which run during a merge or a rebase started outside
+    // of gitbutler too, and finishing those
+    fn verify_writable_during_external_operation(
+        &self,
+        project_id: &ProjectId,
+        permit: &OperationPermit<'_>,
+    ) -> Result<audit::Recorder, Error> {
+        let project = self.writable_project(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        let user = self.users.get_user()?;
+        Ok(self.start_recording(&project_repository, user.as_ref(), permit))
+    }
+
+    fn writable_project(&self, project_id: &ProjectId) -> Result<projects::Project, Error> {
+        let project = self.projects.get(project_id)?;
+        if project.read_only {
+            return Err(errors::ProjectReadOnlyError {
+                project_id: *project_id,
+            }
+            .into());
+        }
+        Ok(project)
+    }
+
+    // records the operation in the audit log of the project once the recorder this returns,
+    // which the permit outlives, is dropped, for what only reads the project but sometimes
+    // updates its branches on the way
+    fn record(
+        &self,
+        project_id: &ProjectId,
+        permit: &OperationPermit<'_>,
+    ) -> Result<audit::Recorder, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        let user = self.users.get_user()?;
+        Ok(self.start_recording(&project_repository, user.as_ref(), permit))
+    }
+
+    fn start_recording(
+        &self,
+        project_repository: &project_repository::Repository,
+        user: Option<&users::User>,
+        permit: &OperationPermit<'_>,
+    ) -> audit::Recorder {
+        audit::Recorder::start(
+            &self.local_data_dir,
+            project_repository,
+            user,
+            permit.kind(),
+            permit.requested_by(),
+        )
+    }
+
+    // the key commits are signed with, if the repository is configured to sign them or
+    // doesn't say and the app settings are
+    fn signing_key(
+        &self,
+        project_repository: &project_repository::Repository,
+    ) -> anyhow::Result<Option<keys::PrivateKey>> {
+        let sign_commits = match project_repository
+            .config()
+            .sign_commits()
+            .context("failed to get sign commits option")?
+        {
+            Some(sign_commits) => sign_commits,
+            None => {
+                settings::Controller::from(&self.local_data_dir)
+                    .get()
+                    .context("failed to get settings")?
+                    .sign_commits
+                    .value
+            }
+        };
+        sign_commits
+            .then(|| {
+                self.keys
+                    .get_or_create()
+                    .context("failed to get private key")
+            })
+            .transpose()
+    }
+
+    fn with_verify_branch<T, E: Into<Error>>(
+        &self,
+        project_id: &ProjectId,
+        action: impl FnOnce(
+            &gb_repository::Repository,
+            &project_repository::Repository,
+            Option<&users::User>,
+        ) -> Result<T, E>,
+    ) -> Result<T, ControllerError<E>> {
+        self.with_verify_branch_cancellable(
+            project_id,
+            &project_repository::Cancellation::default(),
+            action,
+        )
+    }
+
+    // opens the repository of `project` for network operations, which fail with
+    // `RemoteError::Cancelled` once `cancel` is and report their retries
+    fn open_remote(
+        &self,
+        project: &projects::Project,
+        cancel: &project_repository::Cancellation,
+    ) -> Result<project_repository::Repository, Error> {
+        let mut project_repository = project_repository::Repository::open(project)?;
+        project_repository.set_cancellation(cancel);
+        if let Some(on_retry) = &self.on_retry {
+            project_repository.set_on_retry(on_retry);
+        }
+        Ok(project_repository)
+    }
+
+    // for network operations, see `open_remote`
+    fn with_verify_branch_cancellable<T, E: Into<Error>>(
+        &self,
+        project_id: &ProjectId,
+        cancel: &project_repository::Cancellation,
+        action: impl FnOnce(
+            &gb_repository::Repository,
+            &project_repository::Repository,
+            Option<&users::User>,
+        ) -> Result<T, E>,
+    ) -> Result<T, ControllerError<E>> {
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository = self.open_remote(&project, cancel)?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        super::integration::verify_branch(&gb_repository, &project_repository)?;
+        action(&gb_repository, &project_repository, user.as_ref()).map_err(ControllerError::Action)
+    }
+
+    // for what has to work when the branch doesn't verify, like with something else than
+    // the workspace checked out
+    fn without_verify_branch<T, E: Into<Error>>(
+        &self,
+        project_id: &ProjectId,
+        action: impl FnOnce(
+            &gb_repository::Repository,
+            &project_repository::Repository,
+            Option<&users::User>,
+        ) -> Result<T, E>,
+    ) -> Result<T, ControllerError<E>> {
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        action(&gb_repository, &project_repository, user.as_ref()).map_err(ControllerError::Action)
+    }
+}
+
+// an operation cancelled before its turn
+fn cancelled<E: Into<Error>>() -> ControllerError<E> {
+    ControllerError::User(project_repository::RemoteError::Cancelled.into())
+}
+
+fn read_branches(gb_repository: &gb_repository::Repository) -> anyhow::Result<Vec<branch::Branch>> {
+    let session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let session_reader = sessions::Reader::open(gb_repository, &session)
+        .context("failed to open current session")?;
+    super::Iterator::new(&session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read virtual branches")
+}
+
+/// Runs the post-integration hook if updating the base branch moved it, with the branches
+/// that got integrated by it: those which were deleted for being fully merged, and those
+/// that lost their upstream for the same reason but still have uncommitted changes.
+fn notify_integration(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    previous_target: &target::Target,
+    previous_branches: &[branch::Branch],
+) -> anyhow::Result<()> {
+    let Some(target) = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+    else {
+        return Ok(());
+    };
+    if target.sha == previous_target.sha {
+        return Ok(());
+    }
+
+    let branches = read_branches(gb_repository)?;
+    let integrated_branches = previous_branches
+        .iter()
+        .filter(
+            |previous| match branches.iter().find(|branch| branch.id == previous.id) {
+                None => true,
+                Some(branch) => previous.upstream.is_some() && branch.upstream.is_none(),
+            },
+        )
+        .map(|branch| hooks::Branch {
+            id: branch.id,
+            name: branch.name.clone(),
+        })
+        .collect();
+
+    hooks::notify(
+        project_repository,
+        &hooks::Event::PostIntegration {
+            project_id: project_repository.project().id,
+            base_branch: target.branch,
+            previous_sha: previous_target.sha,
+            sha: target.sha,
+            integrated_branches,
+        },
+    );
+    Ok(())
+}
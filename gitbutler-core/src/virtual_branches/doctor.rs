@@ -0,0 +1,391 @@
+//! Checks a project for the problems a crash, a bug or a tool working on the repository
+//! behind GitButler's back can leave: GitButler data that can't be read, references that
+//! belong to no virtual branch, ownership of files that have no changes and lock files
+//! nobody holds anymore. The problems that can be fixed without losing anything are fixed
+//! on request.
+
+use std::{collections::HashSet, fs, path, time};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    gb_repository,
+    git::{self, diff},
+    project_repository,
+};
+
+use super::{branch, errors, recovery::read_branches, BranchId};
+
+// git holds its locks for as long as a command runs, older ones were left by a command that
+// didn't finish, unless one is still running in the repository
+const STALE_LOCK_AGE: time::Duration = time::Duration::from_secs(10 * 60);
+
+/// Where the dangling references that are fixed are moved to, rather than deleted, with the
+/// rest of their name: `refs/gitbutler/renamed` is kept as `refs/gitbutler-dangling/renamed`.
+pub const DANGLING_REFERENCES_BACKUP: &str = "refs/gitbutler-dangling/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    /// Nothing is wrong yet, but it may be soon.
+    Info,
+    /// Something doesn't work as it should.
+    Warning,
+    /// The project can't be worked on until it's fixed.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Problem {
+    /// The virtual branches or the target stored for the project can't be read.
+    #[serde(rename_all = "camelCase")]
+    UnreadableData { message: String },
+    /// The target or a virtual branch points at an object that isn't in the repository.
+    #[serde(rename_all = "camelCase")]
+    MissingObject {
+        branch_id: Option<BranchId>,
+        oid: git::Oid,
+    },
+    /// A `refs/gitbutler/` reference that no virtual branch has, left by a rename. Fixing it
+    /// moves it to [`DANGLING_REFERENCES_BACKUP`], what it points at isn't lost.
+    #[serde(rename_all = "camelCase")]
+    DanglingReference { name: String },
+    /// An applied virtual branch owns a file that has no changes.
+    #[serde(rename_all = "camelCase")]
+    OrphanedOwnership {
+        branch_id: BranchId,
+        #[serde(with = "crate::git::path::escaped")]
+        file_path: path::PathBuf,
+    },
+    /// A git lock file that was left behind, in the repository of the project or in the one
+    /// GitButler keeps its data in, by a command that isn't running anymore.
+    #[serde(rename_all = "camelCase")]
+    StaleLock {
+        #[serde(with = "crate::git::path::escaped")]
+        path: path::PathBuf,
+        age_secs: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Issue {
+    pub severity: Severity,
+    pub problem: Problem,
+    /// Whether the problem can be fixed without losing anything.
+    pub fixable: bool,
+    /// Whether the problem was fixed by this check.
+    pub fixed: bool,
+}
+
+/// Checks the project for problems, the most severe first, fixing those that are fixable
+/// if `fix` is set.
+pub fn repair_project(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    fix: bool,
+) -> Result<Vec<Issue>, errors::RepairProjectError> {
+    let mut issues = vec![];
+    issues.extend(check_locks(
+        &[
+            project_repository.git_repository.path(),
+            project_repository.git_repository.common_dir(),
+            gb_repository.git_repository().path(),
+        ],
+        &project_repository.project().path,
+        fix,
+    ));
+
+    let branches = match read_branches(gb_repository) {
+        Ok(branches) => branches,
+        Err(error) => {
+            issues.push(Issue {
+                severity: Severity::Error,
+                problem: Problem::UnreadableData {
+                    message: format!("{error:#}"),
+                },
+                fixable: false,
+                fixed: false,
+            });
+            return Ok(sorted(issues));
+        }
+    };
+    let target = match gb_repository.default_target() {
+        Ok(target) => target,
+        Err(error) => {
+            issues.push(Issue {
+                severity: Severity::Error,
+                problem: Problem::UnreadableData {
+                    message: format!("{error:#}"),
+                },
+                fixable: false,
+                fixed: false,
+            });
+            None
+        }
+    };
+
+    let repo = &project_repository.git_repository;
+    let mut missing = vec![];
+    if let Some(target) = &target {
+        if repo.find_commit(target.sha).is_err() {
+            missing.push((None, target.sha));
+        }
+    }
+    for branch in &branches {
+        if repo.find_commit(branch.head).is_err() {
+            missing.push((Some(branch.id), branch.head));
+        }
+        if repo.find_tree(branch.tree).is_err() {
+            missing.push((Some(branch.id), branch.tree));
+        }
+    }
+    issues.extend(missing.into_iter().map(|(branch_id, oid)| Issue {
+        severity: Severity::Error,
+        problem: Problem::MissingObject { branch_id, oid },
+        fixable: false,
+        fixed: false,
+    }));
+
+    issues.extend(check_references(project_repository, &branches, fix)?);
+    if let Some(target) = &target {
+        if repo.find_commit(target.sha).is_ok() {
+            issues.extend(check_ownership(
+                gb_repository,
+                project_repository,
+                target,
+                branches,
+                fix,
+            )?);
+        }
+    }
+    Ok(sorted(issues))
+}
+
+fn sorted(mut issues: Vec<Issue>) -> Vec<Issue> {
+    issues.sort_by(|a, b| b.severity.cmp(&a.severity));
+    issues
+}
+
+fn check_references(
+    project_repository: &project_repository::Repository,
+    branches: &[branch::Branch],
+    fix: bool,
+) -> Result<Vec<Issue>> {
+    let repo = &project_repository.git_repository;
+    let names = branches
+        .iter()
+        .map(|branch| project_repository.branch_refname(branch).to_string())
+        .collect::<HashSet<_>>();
+    let namespace = project_repository.refs_namespace();
+    let pattern = match &namespace {
+        Some(namespace) => format!("refs/gitbutler/{namespace}/*"),
+        None => "refs/gitbutler/*".to_string(),
+    };
+    let mut issues = vec![];
+    for reference in repo
+        .references_glob(&pattern)
+        .context("failed to list references")?
+    {
+        let mut reference = reference.context("failed to read reference")?;
+        let name = String::from_utf8_lossy(reference.name_bytes()).to_string();
+        if names.contains(&name) || name == super::METADATA_REFERENCE {
+            continue;
+        }
+        // those of the other projects of the repository
+        if name
+            .parse::<git::VirtualRefname>()
+            .is_ok_and(|refname| refname.namespace() != namespace.as_deref())
+        {
+            continue;
+        }
+        let fixed = fix
+            && match back_up(repo, &name, &reference).and_then(|()| {
+                reference
+                    .delete()
+                    .context("failed to delete dangling reference")
+            }) {
+                Ok(()) => true,
+                Err(error) => {
+                    tracing::warn!(?error, name, "failed to fix dangling reference");
+                    false
+                }
+            };
+        issues.push(Issue {
+            severity: Severity::Warning,
+            problem: Problem::DanglingReference { name },
+            fixable: true,
+            fixed,
+        });
+    }
+    Ok(issues)
+}
+
+// the reference as it is under `DANGLING_REFERENCES_BACKUP`, replacing an older backup of it
+fn back_up(repo: &git::Repository, name: &str, reference: &git::Reference) -> Result<()> {
+    let target = reference
+        .target()
+        .context("dangling reference is not a direct reference")?;
+    let backup = format!(
+        "{DANGLING_REFERENCES_BACKUP}{}",
+        name.strip_prefix("refs/gitbutler/").unwrap_or(name)
+    );
+    repo.reference(
+        &git::Refname::Other(backup),
+        target,
+        true,
+        "backup of a dangling reference",
+    )
+    .context("failed to back up dangling reference")?;
+    Ok(())
+}
+
+fn check_ownership(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    target: &super::target::Target,
+    branches: Vec<branch::Branch>,
+    fix: bool,
+) -> Result<Vec<Issue>> {
+    let changed = diff::workdir_with_progress(
+        &project_repository.git_repository,
+        &target.sha,
+        &diff::Options {
+            unmanaged: project_repository.project().unmanaged(),
+            ..Default::default()
+        },
+        &|_, _| {},
+    )
+    .context("failed to diff workdir")?
+    .into_keys()
+    .collect::<HashSet<_>>();
+    let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    let mut issues = vec![];
+    for mut branch in branches.into_iter().filter(|branch| branch.applied) {
+        let orphaned = branch
+            .ownership
+            .files
+            .iter()
+            .filter(|file| !changed.contains(&file.file_path))
+            .map(|file| file.file_path.clone())
+            .collect::<Vec<_>>();
+        if orphaned.is_empty() {
+            continue;
+        }
+        let fixed = fix && {
+            branch
+                .ownership
+                .files
+                .retain(|file| changed.contains(&file.file_path));
+            writer
+                .write(&mut branch)
+                .context("failed to write branch")?;
+            true
+        };
+        issues.extend(orphaned.into_iter().map(|file_path| Issue {
+            severity: Severity::Info,
+            problem: Problem::OrphanedOwnership {
+                branch_id: branch.id,
+                file_path,
+            },
+            fixable: true,
+            fixed,
+        }));
+    }
+    Ok(issues)
+}
+
+// the lock files of the git directories `git_dirs` that are old enough to be no one's,
+// removed if `fix` is set. none are while a git command runs in the repository, or in
+// `workdir`, a long one may still hold them
+fn check_locks(git_dirs: &[&path::Path], workdir: &path::Path, fix: bool) -> Vec<Issue> {
+    let running = git_process_dirs();
+    if running.iter().any(|dir| {
+        dir.starts_with(workdir) || git_dirs.iter().any(|git_dir| dir.starts_with(git_dir))
+    }) {
+        tracing::info!(workdir = %workdir.display(), "git is running, not checking locks");
+        return vec![];
+    }
+
+    let mut visited = HashSet::new();
+    let mut locks = vec![];
+    for git_dir in git_dirs {
+        if !visited.insert(git_dir.to_path_buf()) {
+            continue;
+        }
+        for name in ["index.lock", "HEAD.lock", "config.lock", "packed-refs.lock"] {
+            let path = git_dir.join(name);
+            if path.is_file() {
+                locks.push(path);
+            }
+        }
+        find_locks(&git_dir.join("refs"), &mut locks);
+    }
+
+    let now = time::SystemTime::now();
+    locks
+        .into_iter()
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).and_then(|metadata| metadata.modified());
+            let age = now.duration_since(modified.ok()?).ok()?;
+            if age < STALE_LOCK_AGE {
+                return None;
+            }
+            let fixed = fix
+                && match fs::remove_file(&path) {
+                    Ok(()) => true,
+                    // the command holding it finished after all
+                    Err(error) if error.kind() == std::io::ErrorKind::NotFound => return None,
+                    Err(error) => {
+                        tracing::warn!(?error, path = %path.display(), "failed to remove lock");
+                        false
+                    }
+                };
+            Some(Issue {
+                severity: Severity::Warning,
+                problem: Problem::StaleLock {
+                    path,
+                    age_secs: age.as_secs(),
+                },
+                fixable: true,
+                fixed,
+            })
+        })
+        .collect()
+}
+
+// the working directories of the git commands that are running
+fn git_process_dirs() -> Vec<path::PathBuf> {
+    let mut system = sysinfo::System::new();
+    system.refresh_processes_specifics(
+        sysinfo::ProcessRefreshKind::new().with_cwd(sysinfo::UpdateKind::Always),
+    );
+    system
+        .processes()
+        .values()
+        .filter(|process| matches!(process.name(), "git" | "git.exe"))
+        .filter_map(|process| process.cwd().map(path::Path::to_path_buf))
+        .collect()
+}
+
+fn find_locks(dir: &path::Path, locks: &mut Vec<path::PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => find_locks(&path, locks),
+            Ok(_)
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "lock") =>
+            {
+                locks.push(path);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,1857 @@
+use std::path;
+
+use crate::{
+    error::Error,
+    git,
+    project_repository::{self, RemoteError},
+    projects::ProjectId,
+};
+
+use super::{
+    branch::Ownership, BranchId, CheckStatus, ConflictMarker, ExternalChangeId, LargeFile,
+    OutboxEntryId, PrePushCheck, PrePushReport, ReconcileAction, SecretFinding, TrashEntryId,
+};
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum VerifyError {
+    #[error("head is detached")]
+    DetachedHead { workspace: git::LocalRefname },
+    #[error("head is {head}")]
+    InvalidHead {
+        head: String,
+        workspace: git::LocalRefname,
+    },
+    #[error("integration commit not found")]
+    NoIntegrationCommit,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<VerifyError> for crate::error::Error {
+    fn from(value: VerifyError) -> Self {
+        match value {
+            VerifyError::DetachedHead { workspace } => crate::error::Error::UserError {
+                code: crate::error::Code::ProjectHead,
+                message: format!(
+                    "Project in detached head state. Please checkout {0} to continue.",
+                    workspace.branch()
+                ),
+            },
+            VerifyError::InvalidHead { head, workspace } => crate::error::Error::UserError {
+                code: crate::error::Code::ProjectHead,
+                message: format!(
+                    "Project is on {}. Please checkout {} to continue.",
+                    head,
+                    workspace.branch()
+                ),
+            },
+            VerifyError::NoIntegrationCommit => crate::error::Error::UserError {
+                code: crate::error::Code::ProjectHead,
+                message: "GibButler's integration commit not found on head.".to_string(),
+            },
+            VerifyError::Other(error) => {
+                tracing::error!(?error);
+                crate::error::Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DeleteBranchError {
+    #[error(transparent)]
+    UnapplyBranch(#[from] UnapplyBranchError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ResetBranchError {
+    #[error("commit {0} not in the branch")]
+    CommitNotFoundInBranch(git::Oid),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ApplyBranchError {
+    #[error("project")]
+    Conflict(ProjectConflictError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("branch conflicts with other branches - sorry bro.")]
+    BranchConflicts(BranchId),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UnapplyOwnershipError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflictError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UnapplyBranchError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflictError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum FlushAppliedVbranchesError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListVirtualBranchesError {
+    #[error("project")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("cancelled")]
+    Cancelled,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CreateVirtualBranchError {
+    #[error("project")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum MergeVirtualBranchUpstreamError {
+    #[error("project")]
+    Conflict(ProjectConflictError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CommitError {
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("will not commit conflicted files")]
+    Conflicted(ProjectConflictError),
+    #[error("commit hook rejected")]
+    CommitHookRejected(String),
+    #[error("commit msg hook rejected")]
+    CommitMsgHookRejected(String),
+    #[error("secrets found")]
+    SecretsFound(Vec<SecretFinding>),
+    #[error("large files")]
+    LargeFiles(Vec<LargeFile>),
+    #[error("conflict markers")]
+    ConflictMarkers(Vec<ConflictMarker>),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum PushError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("pre-push hook rejected")]
+    PrePushHookRejected(String),
+    #[error("pre-push checks failed")]
+    ChecksFailed(PrePushReport),
+    #[error("secrets found")]
+    SecretsFound(Vec<SecretFinding>),
+    #[error(transparent)]
+    Remote(#[from] project_repository::RemoteError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ScanSecretsError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListLargeFilesError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListBranchChangesError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum PullRequestContextError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("the branch has no commits")]
+    NoCommits,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum IsRemoteBranchMergableError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(git::RemoteRefname),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IsVirtualBranchMergeable {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug)]
+pub struct ForcePushNotAllowedError {
+    pub project_id: ProjectId,
+}
+
+impl From<ForcePushNotAllowedError> for Error {
+    fn from(_value: ForcePushNotAllowedError) -> Self {
+        Error::UserError {
+            code: crate::error::Code::Branches,
+            message: "Action will lead to force pushing, which is not allowed for this".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum AmendError {
+    #[error("force push not allowed")]
+    ForcePushNotAllowed(ForcePushNotAllowedError),
+    #[error("target ownership not found")]
+    TargetOwnerhshipNotFound(Ownership),
+    #[error("branch has no commits")]
+    BranchHasNoCommits,
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflictError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CherryPickError {
+    #[error("target commit {0} not found ")]
+    CommitNotFound(git::Oid),
+    #[error("can not cherry pick not applied branch")]
+    NotApplied,
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflictError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SquashError {
+    #[error("force push not allowed")]
+    ForcePushNotAllowed(ForcePushNotAllowedError),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("commit {0} not in the branch")]
+    CommitNotFound(git::Oid),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflictError),
+    #[error("can not squash root commit")]
+    CantSquashRootCommit,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum FetchFromTargetError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("failed to fetch")]
+    Remote(RemoteError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<FetchFromTargetError> for Error {
+    fn from(value: FetchFromTargetError) -> Self {
+        match value {
+            FetchFromTargetError::DefaultTargetNotSet(error) => error.into(),
+            FetchFromTargetError::Remote(error) => error.into(),
+            FetchFromTargetError::Other(error) => {
+                tracing::error!(?error, "fetch from target error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UpdateCommitMessageError {
+    #[error("force push not allowed")]
+    ForcePushNotAllowed(ForcePushNotAllowedError),
+    #[error("empty message")]
+    EmptyMessage,
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("commit {0} not in the branch")]
+    CommitNotFound(git::Oid),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflictError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<UpdateCommitMessageError> for Error {
+    fn from(value: UpdateCommitMessageError) -> Self {
+        match value {
+            UpdateCommitMessageError::ForcePushNotAllowed(error) => error.into(),
+            UpdateCommitMessageError::EmptyMessage => Error::UserError {
+                message: "Commit message can not be empty".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            UpdateCommitMessageError::DefaultTargetNotSet(error) => error.into(),
+            UpdateCommitMessageError::CommitNotFound(oid) => Error::UserError {
+                message: format!("Commit {} not found", oid),
+                code: crate::error::Code::Branches,
+            },
+            UpdateCommitMessageError::BranchNotFound(error) => error.into(),
+            UpdateCommitMessageError::Conflict(error) => error.into(),
+            UpdateCommitMessageError::Other(error) => {
+                tracing::error!(?error, "update commit message error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetBaseBranchDataError {
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetProjectStateError {
+    #[error(transparent)]
+    GetBaseBranchData(#[from] GetBaseBranchDataError),
+    #[error(transparent)]
+    ListVirtualBranches(#[from] ListVirtualBranchesError),
+    #[error(transparent)]
+    ListRemoteBranches(#[from] ListRemoteBranchesError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SetBaseBranchError {
+    #[error("branch {0} not found")]
+    BranchNotFound(git::RemoteRefname),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UpdateBaseBranchError {
+    #[error("project is in conflicting state")]
+    Conflict(ProjectConflictError),
+    #[error("no default target set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CreateVirtualBranchFromBranchError {
+    #[error("failed to apply")]
+    ApplyBranch(ApplyBranchError),
+    #[error("can't make branch from default target")]
+    CantMakeBranchFromDefaultTarget,
+    #[error("merge conflict")]
+    MergeConflict,
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("{0} not found")]
+    BranchNotFound(git::Refname),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug)]
+pub struct ProjectConflictError {
+    pub project_id: ProjectId,
+}
+
+impl From<ProjectConflictError> for Error {
+    fn from(value: ProjectConflictError) -> Self {
+        Error::UserError {
+            code: crate::error::Code::ProjectConflict,
+            message: format!("project {} is in a conflicted state", value.project_id),
+        }
+        .with_context("projectId", value.project_id)
+    }
+}
+
+#[derive(Debug)]
+pub struct ProjectReadOnlyError {
+    pub project_id: ProjectId,
+}
+
+impl From<ProjectReadOnlyError> for Error {
+    fn from(value: ProjectReadOnlyError) -> Self {
+        Error::UserError {
+            code: crate::error::Code::ProjectReadOnly,
+            message: format!("project {} is read-only", value.project_id),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ExternalOperationError {
+    pub project_id: ProjectId,
+    pub operation: project_repository::ExternalOperation,
+}
+
+impl From<ExternalOperationError> for Error {
+    fn from(value: ExternalOperationError) -> Self {
+        let operation = match value.operation.kind {
+            project_repository::ExternalOperationKind::Merge => "a merge",
+            project_repository::ExternalOperationKind::Rebase => "a rebase",
+            project_repository::ExternalOperationKind::CherryPick => "a cherry-pick",
+            project_repository::ExternalOperationKind::Revert => "a revert",
+            project_repository::ExternalOperationKind::Bisect => "a bisect",
+            project_repository::ExternalOperationKind::ApplyMailbox => "applying patches",
+            project_repository::ExternalOperationKind::ApplyMailboxOrRebase => {
+                "applying patches or a rebase"
+            }
+        };
+        Error::UserError {
+            code: crate::error::Code::ProjectExternalOperation,
+            message: format!(
+                "project {} is in the middle of {operation} started outside of GitButler, finish or abort it first",
+                value.project_id
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DefaultTargetNotSetError {
+    pub project_id: ProjectId,
+}
+
+impl From<DefaultTargetNotSetError> for Error {
+    fn from(value: DefaultTargetNotSetError) -> Self {
+        Error::UserError {
+            code: crate::error::Code::ProjectConflict,
+            message: format!(
+                "project {} does not have a default target set",
+                value.project_id
+            ),
+        }
+        .with_context("projectId", value.project_id)
+    }
+}
+
+#[derive(Debug)]
+pub struct BranchNotFoundError {
+    pub project_id: ProjectId,
+    pub branch_id: BranchId,
+}
+
+impl From<BranchNotFoundError> for Error {
+    fn from(value: BranchNotFoundError) -> Self {
+        Error::UserError {
+            code: crate::error::Code::Branches,
+            message: format!("branch {} not found", value.branch_id),
+        }
+        .with_context("projectId", value.project_id)
+        .with_context("branchId", value.branch_id)
+    }
+}
+
+impl From<project_repository::ShallowHistoryError> for Error {
+    fn from(value: project_repository::ShallowHistoryError) -> Self {
+        Error::UserError {
+            code: crate::error::Code::ProjectShallow,
+            message: format!(
+                "the history of this shallow clone ends before {} and {} have a common ancestor, fetch more history to find it",
+                value.target, value.head
+            ),
+        }
+    }
+}
+
+// the merge base of a branch can be past the end of a shallow clone, which the user can fix
+// by fetching more history
+fn shallow_history(error: &anyhow::Error) -> Option<Error> {
+    error
+        .downcast_ref::<project_repository::ShallowHistoryError>()
+        .map(|error| error.clone().into())
+}
+
+// the findings are listed one per line, the ui gets their fingerprints to allow them from
+// `scan_secrets`
+fn secrets_found(findings: &[SecretFinding]) -> Error {
+    Error::UserError {
+        code: crate::error::Code::SecretsFound,
+        message: findings
+            .iter()
+            .map(|finding| {
+                format!(
+                    "{}:{}: {} {}",
+                    git::path::escape(&finding.file_path),
+                    finding.line,
+                    finding.description,
+                    finding.excerpt
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UpdateBranchError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<UpdateBranchError> for Error {
+    fn from(value: UpdateBranchError) -> Self {
+        match value {
+            UpdateBranchError::DefaultTargetNotSet(error) => error.into(),
+            UpdateBranchError::BranchNotFound(error) => error.into(),
+            UpdateBranchError::Other(error) => {
+                tracing::error!(?error, "update branch error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<CreateVirtualBranchFromBranchError> for Error {
+    fn from(value: CreateVirtualBranchFromBranchError) -> Self {
+        match value {
+            CreateVirtualBranchFromBranchError::ApplyBranch(error) => error.into(),
+            CreateVirtualBranchFromBranchError::CantMakeBranchFromDefaultTarget => {
+                Error::UserError {
+                    message: "Can not create a branch from default target".to_string(),
+                    code: crate::error::Code::Branches,
+                }
+            }
+            CreateVirtualBranchFromBranchError::DefaultTargetNotSet(error) => error.into(),
+            CreateVirtualBranchFromBranchError::MergeConflict => Error::UserError {
+                message: "Merge conflict".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            CreateVirtualBranchFromBranchError::BranchNotFound(name) => Error::UserError {
+                message: format!("Branch {} not found", name),
+                code: crate::error::Code::Branches,
+            },
+            CreateVirtualBranchFromBranchError::Other(error) => shallow_history(&error)
+                .unwrap_or_else(|| {
+                    tracing::error!(?error, "create virtual branch from branch error");
+                    Error::caused_by(&error)
+                }),
+        }
+    }
+}
+
+impl From<CommitError> for Error {
+    fn from(value: CommitError) -> Self {
+        match value {
+            CommitError::BranchNotFound(error) => error.into(),
+            CommitError::DefaultTargetNotSet(error) => error.into(),
+            CommitError::Conflicted(error) => error.into(),
+            CommitError::CommitHookRejected(error) => Error::UserError {
+                code: crate::error::Code::PreCommitHook,
+                message: error,
+            },
+            CommitError::CommitMsgHookRejected(error) => Error::UserError {
+                code: crate::error::Code::CommitMsgHook,
+                message: error,
+            },
+            CommitError::SecretsFound(findings) => secrets_found(&findings),
+            CommitError::LargeFiles(large_files) => Error::UserError {
+                code: crate::error::Code::LargeFiles,
+                message: large_files
+                    .iter()
+                    .map(|large_file| {
+                        format!(
+                            "{} is {} bytes, track it with LFS: {}",
+                            git::path::escape(&large_file.file_path),
+                            large_file.size,
+                            large_file.lfs_track
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+            CommitError::ConflictMarkers(markers) => Error::UserError {
+                code: crate::error::Code::ConflictMarkers,
+                message: format!(
+                    "Resolve the conflicts before committing, conflict markers are left in:\n{}",
+                    markers
+                        .iter()
+                        .map(|marker| format!(
+                            "{}:{}: {}",
+                            git::path::escape(&marker.file_path),
+                            marker.line,
+                            marker.marker
+                        ))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ),
+            },
+            CommitError::Other(error) => {
+                tracing::error!(?error, "commit error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<IsRemoteBranchMergableError> for Error {
+    fn from(value: IsRemoteBranchMergableError) -> Self {
+        match value {
+            IsRemoteBranchMergableError::BranchNotFound(name) => Error::UserError {
+                message: format!("Remote branch {} not found", name),
+                code: crate::error::Code::Branches,
+            },
+            IsRemoteBranchMergableError::DefaultTargetNotSet(error) => error.into(),
+            IsRemoteBranchMergableError::Other(error) => {
+                tracing::error!(?error, "is remote branch mergable error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<DeleteBranchError> for Error {
+    fn from(value: DeleteBranchError) -> Self {
+        match value {
+            DeleteBranchError::UnapplyBranch(error) => error.into(),
+            DeleteBranchError::Other(error) => {
+                tracing::error!(?error, "delete branch error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ApplyBranchError> for Error {
+    fn from(value: ApplyBranchError) -> Self {
+        match value {
+            ApplyBranchError::DefaultTargetNotSet(error) => error.into(),
+            ApplyBranchError::Conflict(error) => error.into(),
+            ApplyBranchError::BranchNotFound(error) => error.into(),
+            ApplyBranchError::BranchConflicts(id) => Error::UserError {
+                message: format!("Branch {} is in a conflicing state", id),
+                code: crate::error::Code::Branches,
+            },
+            ApplyBranchError::Other(error) => {
+                tracing::error!(?error, "apply branch error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<IsVirtualBranchMergeable> for Error {
+    fn from(value: IsVirtualBranchMergeable) -> Self {
+        match value {
+            IsVirtualBranchMergeable::BranchNotFound(error) => error.into(),
+            IsVirtualBranchMergeable::DefaultTargetNotSet(error) => error.into(),
+            IsVirtualBranchMergeable::Other(error) => {
+                tracing::error!(?error, "is remote branch mergable error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ListVirtualBranchesError> for Error {
+    fn from(value: ListVirtualBranchesError) -> Self {
+        match value {
+            ListVirtualBranchesError::DefaultTargetNotSet(error) => error.into(),
+            ListVirtualBranchesError::Cancelled => {
+                tracing::debug!("list virtual branches cancelled");
+                Error::UserError {
+                    code: crate::error::Code::Cancelled,
+                    message: "Listing the virtual branches was cancelled".to_string(),
+                }
+            }
+            ListVirtualBranchesError::Other(error) => {
+                shallow_history(&error).unwrap_or_else(|| {
+                    tracing::error!(?error, "list virtual branches error");
+                    Error::caused_by(&error)
+                })
+            }
+        }
+    }
+}
+
+impl From<CreateVirtualBranchError> for Error {
+    fn from(value: CreateVirtualBranchError) -> Self {
+        match value {
+            CreateVirtualBranchError::DefaultTargetNotSet(error) => error.into(),
+            CreateVirtualBranchError::Other(error) => {
+                tracing::error!(?error, "create virtual branch error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<GetBaseBranchDataError> for Error {
+    fn from(value: GetBaseBranchDataError) -> Self {
+        match value {
+            GetBaseBranchDataError::Other(error) => shallow_history(&error).unwrap_or_else(|| {
+                tracing::error!(?error, "get base branch data error");
+                Error::caused_by(&error)
+            }),
+        }
+    }
+}
+
+impl From<GetProjectStateError> for Error {
+    fn from(value: GetProjectStateError) -> Self {
+        match value {
+            GetProjectStateError::GetBaseBranchData(error) => error.into(),
+            GetProjectStateError::ListVirtualBranches(error) => error.into(),
+            GetProjectStateError::ListRemoteBranches(error) => error.into(),
+        }
+    }
+}
+
+impl From<ListRemoteCommitFilesError> for Error {
+    fn from(value: ListRemoteCommitFilesError) -> Self {
+        match value {
+            ListRemoteCommitFilesError::CommitNotFound(oid) => Error::UserError {
+                message: format!("Commit {} not found", oid),
+                code: crate::error::Code::Branches,
+            },
+            ListRemoteCommitFilesError::Other(error) => {
+                tracing::error!(?error, "list remote commit files error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<SetBaseBranchError> for Error {
+    fn from(value: SetBaseBranchError) -> Self {
+        match value {
+            SetBaseBranchError::BranchNotFound(name) => Error::UserError {
+                message: format!("remote branch '{}' not found", name),
+                code: crate::error::Code::Branches,
+            },
+            SetBaseBranchError::Other(error) => {
+                tracing::error!(?error, "set base branch error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<MergeVirtualBranchUpstreamError> for Error {
+    fn from(value: MergeVirtualBranchUpstreamError) -> Self {
+        match value {
+            MergeVirtualBranchUpstreamError::DefaultTargetNotSet(error) => error.into(),
+            MergeVirtualBranchUpstreamError::BranchNotFound(error) => error.into(),
+            MergeVirtualBranchUpstreamError::Conflict(error) => error.into(),
+            MergeVirtualBranchUpstreamError::Other(error) => shallow_history(&error)
+                .unwrap_or_else(|| {
+                    tracing::error!(?error, "merge virtual branch upstream error");
+                    Error::caused_by(&error)
+                }),
+        }
+    }
+}
+
+impl From<UpdateBaseBranchError> for Error {
+    fn from(value: UpdateBaseBranchError) -> Self {
+        match value {
+            UpdateBaseBranchError::Conflict(error) => error.into(),
+            UpdateBaseBranchError::DefaultTargetNotSet(error) => error.into(),
+            UpdateBaseBranchError::Other(error) => shallow_history(&error).unwrap_or_else(|| {
+                tracing::error!(?error, "update base branch error");
+                Error::caused_by(&error)
+            }),
+        }
+    }
+}
+
+impl From<UnapplyOwnershipError> for Error {
+    fn from(value: UnapplyOwnershipError) -> Self {
+        match value {
+            UnapplyOwnershipError::DefaultTargetNotSet(error) => error.into(),
+            UnapplyOwnershipError::Conflict(error) => error.into(),
+            UnapplyOwnershipError::Other(error) => {
+                tracing::error!(?error, "unapply ownership error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<AmendError> for Error {
+    fn from(value: AmendError) -> Self {
+        match value {
+            AmendError::ForcePushNotAllowed(error) => error.into(),
+            AmendError::Conflict(error) => error.into(),
+            AmendError::BranchNotFound(error) => error.into(),
+            AmendError::BranchHasNoCommits => Error::UserError {
+                message: "Branch has no commits - there is nothing to amend to".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            AmendError::DefaultTargetNotSet(error) => error.into(),
+            AmendError::TargetOwnerhshipNotFound(_) => Error::UserError {
+                message: "target ownership not found".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            AmendError::Other(error) => {
+                tracing::error!(?error, "amend error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ResetBranchError> for Error {
+    fn from(value: ResetBranchError) -> Self {
+        match value {
+            ResetBranchError::BranchNotFound(error) => error.into(),
+            ResetBranchError::DefaultTargetNotSet(error) => error.into(),
+            ResetBranchError::CommitNotFoundInBranch(oid) => Error::UserError {
+                code: crate::error::Code::Branches,
+                message: format!("commit {} not found", oid),
+            },
+            ResetBranchError::Other(error) => {
+                tracing::error!(?error, "reset branch error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<UnapplyBranchError> for Error {
+    fn from(value: UnapplyBranchError) -> Self {
+        match value {
+            UnapplyBranchError::Conflict(error) => error.into(),
+            UnapplyBranchError::DefaultTargetNotSet(error) => error.into(),
+            UnapplyBranchError::BranchNotFound(error) => error.into(),
+            UnapplyBranchError::Other(error) => {
+                tracing::error!(?error, "unapply branch error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<PushError> for Error {
+    fn from(value: PushError) -> Self {
+        match value {
+            PushError::Remote(error) => error.into(),
+            PushError::BranchNotFound(error) => error.into(),
+            PushError::DefaultTargetNotSet(error) => error.into(),
+            PushError::PrePushHookRejected(error) => Error::UserError {
+                code: crate::error::Code::PrePushHook,
+                message: error,
+            },
+            PushError::ChecksFailed(report) => Error::UserError {
+                code: crate::error::Code::PrePushHook,
+                message: report
+                    .checks
+                    .iter()
+                    .filter(|check| check.status == CheckStatus::Failed)
+                    .map(|check| {
+                        let name = match check.check {
+                            PrePushCheck::Hook => "pre-push hook",
+                            PrePushCheck::Command => "pre-push command",
+                            PrePushCheck::BranchName => "branch name",
+                        };
+                        format!("{name} failed: {}", check.output)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+            PushError::SecretsFound(findings) => secrets_found(&findings),
+            PushError::Other(error) => {
+                tracing::error!(?error, "push error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<project_repository::hooks::Error> for PushError {
+    fn from(value: project_repository::hooks::Error) -> Self {
+        match value {
+            project_repository::hooks::Error::Rejected { output, .. } => {
+                PushError::PrePushHookRejected(output)
+            }
+            project_repository::hooks::Error::Other(error) => PushError::Other(error),
+        }
+    }
+}
+
+impl From<ScanSecretsError> for Error {
+    fn from(value: ScanSecretsError) -> Self {
+        match value {
+            ScanSecretsError::DefaultTargetNotSet(error) => error.into(),
+            ScanSecretsError::BranchNotFound(error) => error.into(),
+            ScanSecretsError::Other(error) => {
+                tracing::error!(?error, "scan secrets error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ListLargeFilesError> for Error {
+    fn from(value: ListLargeFilesError) -> Self {
+        match value {
+            ListLargeFilesError::DefaultTargetNotSet(error) => error.into(),
+            ListLargeFilesError::BranchNotFound(error) => error.into(),
+            ListLargeFilesError::Other(error) => {
+                tracing::error!(?error, "list large files error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ListBranchChangesError> for Error {
+    fn from(value: ListBranchChangesError) -> Self {
+        match value {
+            ListBranchChangesError::DefaultTargetNotSet(error) => error.into(),
+            ListBranchChangesError::BranchNotFound(error) => error.into(),
+            ListBranchChangesError::Other(error) => {
+                tracing::error!(?error, "list branch changes error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<PullRequestContextError> for Error {
+    fn from(value: PullRequestContextError) -> Self {
+        match value {
+            PullRequestContextError::DefaultTargetNotSet(error) => error.into(),
+            PullRequestContextError::BranchNotFound(error) => error.into(),
+            PullRequestContextError::NoCommits => Error::UserError {
+                code: crate::error::Code::Branches,
+                message: "Commit something first, pull requests are written from the commits"
+                    .to_string(),
+            },
+            PullRequestContextError::Other(error) => {
+                tracing::error!(?error, "pull request context error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<FlushAppliedVbranchesError> for Error {
+    fn from(value: FlushAppliedVbranchesError) -> Self {
+        match value {
+            FlushAppliedVbranchesError::DefaultTargetNotSet(error) => error.into(),
+            FlushAppliedVbranchesError::Other(error) => {
+                tracing::error!(?error, "flush workspace error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<CherryPickError> for Error {
+    fn from(value: CherryPickError) -> Self {
+        match value {
+            CherryPickError::NotApplied => Error::UserError {
+                message: "can not cherry pick non applied branch".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            CherryPickError::Conflict(error) => error.into(),
+            CherryPickError::CommitNotFound(oid) => Error::UserError {
+                message: format!("commit {oid} not found"),
+                code: crate::error::Code::Branches,
+            },
+            CherryPickError::Other(error) => {
+                tracing::error!(?error, "cherry pick error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListRemoteCommitFilesError {
+    #[error("failed to find commit {0}")]
+    CommitNotFound(git::Oid),
+    #[error("failed to find commit")]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListLineEndingsError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum NormalizeLineEndingsError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("{0} has changes other than line endings")]
+    NotChurn(path::PathBuf),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CreateTagError {
+    #[error("invalid tag name {0}")]
+    InvalidName(String),
+    #[error("tag {0} already exists")]
+    TagExists(String),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("commit {0} not found")]
+    CommitNotFound(git::Oid),
+    #[error("commit {0} is not on a branch")]
+    CommitNotOnBranch(git::Oid),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum PushTagsError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("tag {0} not found")]
+    TagNotFound(String),
+    #[error(transparent)]
+    Remote(#[from] project_repository::RemoteError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListTagsError {
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DeleteTagError {
+    #[error("tag {0} not found")]
+    TagNotFound(String),
+    #[error(transparent)]
+    Remote(#[from] project_repository::RemoteError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListLostCommitsError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RecoverCommitError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("commit {0} is not a lost commit of the branch")]
+    CommitNotLost(git::Oid),
+    #[error("failed to apply")]
+    ApplyBranch(ApplyBranchError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DeepenHistoryError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("failed to fetch")]
+    Remote(RemoteError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListNotesError {
+    #[error("commit {0} not found")]
+    CommitNotFound(git::Oid),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SetNoteError {
+    #[error("commit {0} not found")]
+    CommitNotFound(git::Oid),
+    #[error("{0} is not a notes ref")]
+    InvalidNotesRef(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SyncNotesError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("failed to sync notes")]
+    Remote(RemoteError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SyncMetadataError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("no branch metadata")]
+    NoMetadata,
+    #[error("invalid branch metadata: {0}")]
+    InvalidMetadata(String),
+    #[error("failed to sync branch metadata")]
+    Remote(RemoteError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ExportBundleError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("no branches to export")]
+    NoBranches,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ImportBundleError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("invalid bundle: {0}")]
+    InvalidBundle(String),
+    #[error("failed to apply")]
+    ApplyBranch(ApplyBranchError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RepairProjectError {
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ReconcileError {
+    #[error("external change {0} not found")]
+    ChangeNotFound(ExternalChangeId),
+    #[error("{0:?} can't be done about the change")]
+    InvalidAction(ReconcileAction),
+    #[error("the change is outdated: {0}")]
+    Outdated(String),
+    #[error("{0}")]
+    Failed(String),
+    #[error(transparent)]
+    RecoverWorkspace(#[from] RecoverWorkspaceError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SetWorkspaceBranchError {
+    #[error("{0} is not a valid branch name")]
+    InvalidName(String),
+    #[error("branch {0} already exists")]
+    BranchExists(git::LocalRefname),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RecoverWorkspaceError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SnapshotError {
+    #[error("snapshot {0} not found")]
+    SnapshotNotFound(git::Oid),
+    #[error("the snapshot was taken by a newer version of GitButler")]
+    NewerVersion,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TrashError {
+    #[error("trash entry {0} not found")]
+    EntryNotFound(TrashEntryId),
+    #[error("files changed since they were discarded: {0:?}")]
+    Conflict(Vec<path::PathBuf>),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RedactionError {
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum AuditError {
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum OutboxError {
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("outbox entry {0} not found")]
+    EntryNotFound(OutboxEntryId),
+    #[error(transparent)]
+    Remote(#[from] project_repository::RemoteError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum BlameError {
+    #[error("commit {0} not found")]
+    CommitNotFound(git::Oid),
+    #[error("file {0} not found")]
+    FileNotFound(path::PathBuf),
+    #[error("lines {start} to {end} are not a range of lines")]
+    InvalidLines { start: usize, end: usize },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListRemoteBranchesError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetRemoteBranchDataError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<GetRemoteBranchDataError> for Error {
+    fn from(value: GetRemoteBranchDataError) -> Self {
+        match value {
+            GetRemoteBranchDataError::DefaultTargetNotSet(error) => error.into(),
+            GetRemoteBranchDataError::Other(error) => {
+                tracing::error!(?error, "get remote branch data error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ListRemoteBranchesError> for Error {
+    fn from(value: ListRemoteBranchesError) -> Self {
+        match value {
+            ListRemoteBranchesError::DefaultTargetNotSet(error) => error.into(),
+            ListRemoteBranchesError::Other(error) => {
+                tracing::error!(?error, "list remote branches error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<SquashError> for Error {
+    fn from(value: SquashError) -> Self {
+        match value {
+            SquashError::ForcePushNotAllowed(error) => error.into(),
+            SquashError::DefaultTargetNotSet(error) => error.into(),
+            SquashError::BranchNotFound(error) => error.into(),
+            SquashError::Conflict(error) => error.into(),
+            SquashError::CantSquashRootCommit => Error::UserError {
+                message: "can not squash root branch commit".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            SquashError::CommitNotFound(oid) => Error::UserError {
+                message: format!("commit {oid} not found"),
+                code: crate::error::Code::Branches,
+            },
+            SquashError::Other(error) => {
+                tracing::error!(?error, "squash error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ListLineEndingsError> for Error {
+    fn from(value: ListLineEndingsError) -> Self {
+        match value {
+            ListLineEndingsError::DefaultTargetNotSet(error) => error.into(),
+            ListLineEndingsError::Other(error) => {
+                tracing::error!(?error, "list line endings error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<NormalizeLineEndingsError> for Error {
+    fn from(value: NormalizeLineEndingsError) -> Self {
+        match value {
+            NormalizeLineEndingsError::DefaultTargetNotSet(error) => error.into(),
+            NormalizeLineEndingsError::NotChurn(path) => Error::UserError {
+                message: format!(
+                    "{} has changes other than line endings, it can't be normalized",
+                    path.display()
+                ),
+                code: crate::error::Code::Branches,
+            },
+            NormalizeLineEndingsError::Other(error) => {
+                tracing::error!(?error, "normalize line endings error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<CreateTagError> for Error {
+    fn from(value: CreateTagError) -> Self {
+        match value {
+            CreateTagError::InvalidName(name) => Error::UserError {
+                message: format!("'{}' is not a valid tag name", name),
+                code: crate::error::Code::Validation,
+            },
+            CreateTagError::TagExists(name) => Error::UserError {
+                message: format!("Tag '{}' already exists", name),
+                code: crate::error::Code::Branches,
+            },
+            CreateTagError::DefaultTargetNotSet(error) => error.into(),
+            CreateTagError::CommitNotFound(oid) => Error::UserError {
+                message: format!("Commit {} not found", oid),
+                code: crate::error::Code::Branches,
+            },
+            CreateTagError::CommitNotOnBranch(oid) => Error::UserError {
+                message: format!(
+                    "Commit {} is not on a virtual branch or the target branch",
+                    oid
+                ),
+                code: crate::error::Code::Branches,
+            },
+            CreateTagError::Other(error) => {
+                tracing::error!(?error, "create tag error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<PushTagsError> for Error {
+    fn from(value: PushTagsError) -> Self {
+        match value {
+            PushTagsError::DefaultTargetNotSet(error) => error.into(),
+            PushTagsError::TagNotFound(name) => Error::UserError {
+                message: format!("Tag '{}' not found", name),
+                code: crate::error::Code::Branches,
+            },
+            PushTagsError::Remote(error) => error.into(),
+            PushTagsError::Other(error) => {
+                tracing::error!(?error, "push tags error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ListTagsError> for Error {
+    fn from(value: ListTagsError) -> Self {
+        match value {
+            ListTagsError::Other(error) => {
+                tracing::error!(?error, "list tags error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<DeleteTagError> for Error {
+    fn from(value: DeleteTagError) -> Self {
+        match value {
+            DeleteTagError::TagNotFound(name) => Error::UserError {
+                message: format!("Tag '{}' not found", name),
+                code: crate::error::Code::Branches,
+            },
+            DeleteTagError::Remote(error) => error.into(),
+            DeleteTagError::Other(error) => {
+                tracing::error!(?error, "delete tag error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ListLostCommitsError> for Error {
+    fn from(value: ListLostCommitsError) -> Self {
+        match value {
+            ListLostCommitsError::DefaultTargetNotSet(error) => error.into(),
+            ListLostCommitsError::BranchNotFound(error) => error.into(),
+            ListLostCommitsError::Other(error) => {
+                tracing::error!(?error, "list lost commits error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<RecoverCommitError> for Error {
+    fn from(value: RecoverCommitError) -> Self {
+        match value {
+            RecoverCommitError::DefaultTargetNotSet(error) => error.into(),
+            RecoverCommitError::BranchNotFound(error) => error.into(),
+            RecoverCommitError::CommitNotLost(oid) => Error::UserError {
+                message: format!("commit {oid} is not a lost commit of the branch"),
+                code: crate::error::Code::Branches,
+            },
+            RecoverCommitError::ApplyBranch(error) => error.into(),
+            RecoverCommitError::Other(error) => {
+                tracing::error!(?error, "recover commit error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<DeepenHistoryError> for Error {
+    fn from(value: DeepenHistoryError) -> Self {
+        match value {
+            DeepenHistoryError::DefaultTargetNotSet(error) => error.into(),
+            DeepenHistoryError::Remote(error) => error.into(),
+            DeepenHistoryError::Other(error) => {
+                tracing::error!(?error, "deepen history error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<BlameError> for Error {
+    fn from(value: BlameError) -> Self {
+        match value {
+            BlameError::CommitNotFound(oid) => Error::UserError {
+                message: format!("commit {oid} not found"),
+                code: crate::error::Code::Validation,
+            },
+            BlameError::FileNotFound(path) => Error::UserError {
+                message: format!("file {} not found in the commit", path.display()),
+                code: crate::error::Code::Validation,
+            },
+            BlameError::InvalidLines { start, end } => Error::UserError {
+                message: format!(
+                    "lines {start} to {end} are not a range of lines, lines count from 1"
+                ),
+                code: crate::error::Code::Validation,
+            },
+            BlameError::Other(error) => {
+                tracing::error!(?error, "blame error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ListNotesError> for Error {
+    fn from(value: ListNotesError) -> Self {
+        match value {
+            ListNotesError::CommitNotFound(oid) => Error::UserError {
+                message: format!("commit {oid} not found"),
+                code: crate::error::Code::Validation,
+            },
+            ListNotesError::Other(error) => {
+                tracing::error!(?error, "list notes error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<SetNoteError> for Error {
+    fn from(value: SetNoteError) -> Self {
+        match value {
+            SetNoteError::CommitNotFound(oid) => Error::UserError {
+                message: format!("commit {oid} not found"),
+                code: crate::error::Code::Validation,
+            },
+            SetNoteError::InvalidNotesRef(name) => Error::UserError {
+                message: format!("{name} is not a notes ref, they start with refs/notes/"),
+                code: crate::error::Code::Validation,
+            },
+            SetNoteError::Other(error) => {
+                tracing::error!(?error, "set note error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<SyncNotesError> for Error {
+    fn from(value: SyncNotesError) -> Self {
+        match value {
+            SyncNotesError::DefaultTargetNotSet(error) => error.into(),
+            SyncNotesError::Remote(error) => error.into(),
+            SyncNotesError::Other(error) => {
+                tracing::error!(?error, "sync notes error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<SyncMetadataError> for Error {
+    fn from(value: SyncMetadataError) -> Self {
+        match value {
+            SyncMetadataError::DefaultTargetNotSet(error) => error.into(),
+            SyncMetadataError::NoMetadata => Error::UserError {
+                message: "No branch metadata was pushed to the remote".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            SyncMetadataError::InvalidMetadata(reason) => Error::UserError {
+                message: format!("The branch metadata can't be restored: {reason}"),
+                code: crate::error::Code::Branches,
+            },
+            SyncMetadataError::Remote(error) => error.into(),
+            SyncMetadataError::Other(error) => {
+                tracing::error!(?error, "sync metadata error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ExportBundleError> for Error {
+    fn from(value: ExportBundleError) -> Self {
+        match value {
+            ExportBundleError::DefaultTargetNotSet(error) => error.into(),
+            ExportBundleError::BranchNotFound(error) => error.into(),
+            ExportBundleError::NoBranches => Error::UserError {
+                message: "There are no branches to export".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            ExportBundleError::Other(error) => {
+                tracing::error!(?error, "export bundle error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ImportBundleError> for Error {
+    fn from(value: ImportBundleError) -> Self {
+        match value {
+            ImportBundleError::DefaultTargetNotSet(error) => error.into(),
+            ImportBundleError::InvalidBundle(reason) => Error::UserError {
+                message: format!("The bundle can't be imported: {reason}"),
+                code: crate::error::Code::Validation,
+            },
+            ImportBundleError::ApplyBranch(error) => error.into(),
+            ImportBundleError::Other(error) => {
+                tracing::error!(?error, "import bundle error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<RepairProjectError> for Error {
+    fn from(value: RepairProjectError) -> Self {
+        match value {
+            RepairProjectError::Other(error) => {
+                tracing::error!(?error, "repair project error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<ReconcileError> for Error {
+    fn from(value: ReconcileError) -> Self {
+        match value {
+            ReconcileError::ChangeNotFound(_) => Error::UserError {
+                message: "The change was already taken care of".to_string(),
+                code: crate::error::Code::Validation,
+            },
+            ReconcileError::InvalidAction(action) => Error::UserError {
+                message: format!("{action:?} can't be done about the change"),
+                code: crate::error::Code::Validation,
+            },
+            ReconcileError::Outdated(reason) => Error::UserError {
+                message: format!("The change can't be undone anymore, {reason}"),
+                code: crate::error::Code::Branches,
+            },
+            ReconcileError::Failed(message) => Error::UserError {
+                message,
+                code: crate::error::Code::Branches,
+            },
+            ReconcileError::RecoverWorkspace(error) => error.into(),
+            ReconcileError::Other(error) => {
+                tracing::error!(?error, "reconcile error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<SetWorkspaceBranchError> for Error {
+    fn from(value: SetWorkspaceBranchError) -> Self {
+        match value {
+            SetWorkspaceBranchError::InvalidName(name) => Error::UserError {
+                message: format!("'{name}' is not a valid branch name"),
+                code: crate::error::Code::Validation,
+            },
+            SetWorkspaceBranchError::BranchExists(reference) => Error::UserError {
+                message: format!("Branch '{}' already exists", reference.branch()),
+                code: crate::error::Code::Validation,
+            },
+            SetWorkspaceBranchError::Other(error) => {
+                tracing::error!(?error, "set workspace branch error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<RecoverWorkspaceError> for Error {
+    fn from(value: RecoverWorkspaceError) -> Self {
+        match value {
+            RecoverWorkspaceError::DefaultTargetNotSet(error) => error.into(),
+            RecoverWorkspaceError::Other(error) => {
+                tracing::error!(?error, "recover workspace error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<SnapshotError> for Error {
+    fn from(value: SnapshotError) -> Self {
+        match value {
+            SnapshotError::SnapshotNotFound(_) => Error::UserError {
+                message: "The snapshot is not in the operation log anymore".to_string(),
+                code: crate::error::Code::Validation,
+            },
+            SnapshotError::NewerVersion => Error::UserError {
+                message: "The snapshot was taken by a newer version of GitButler".to_string(),
+                code: crate::error::Code::Validation,
+            },
+            SnapshotError::Other(error) => {
+                tracing::error!(?error, "snapshot error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<TrashError> for Error {
+    fn from(value: TrashError) -> Self {
+        match value {
+            TrashError::EntryNotFound(_) => Error::UserError {
+                message: "The discarded changes are not in the trash anymore".to_string(),
+                code: crate::error::Code::Validation,
+            },
+            TrashError::Conflict(paths) => Error::UserError {
+                message: format!(
+                    "The discarded changes conflict with what changed since in {}",
+                    paths
+                        .iter()
+                        .map(|path| git::path::escape(path))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                code: crate::error::Code::Branches,
+            },
+            TrashError::Other(error) => {
+                tracing::error!(?error, "trash error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<OutboxError> for Error {
+    fn from(value: OutboxError) -> Self {
+        match value {
+            OutboxError::BranchNotFound(error) => error.into(),
+            OutboxError::Remote(error) => error.into(),
+            OutboxError::EntryNotFound(_) => Error::UserError {
+                message: "The operation is not waiting to be sent anymore".to_string(),
+                code: crate::error::Code::Validation,
+            },
+            OutboxError::Other(error) => {
+                tracing::error!(?error, "outbox error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<AuditError> for Error {
+    fn from(value: AuditError) -> Self {
+        match value {
+            AuditError::Other(error) => {
+                tracing::error!(?error, "audit log error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
+
+impl From<RedactionError> for Error {
+    fn from(value: RedactionError) -> Self {
+        match value {
+            RedactionError::Other(error) => {
+                tracing::error!(?error, "redaction error");
+                Error::caused_by(&error)
+            }
+        }
+    }
+}
@@ -11,14 +11,20 @@ use crate::virtual_branches::context;
 #[derive(Debug, PartialEq, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RemoteBranchFile {
+    #[serde(with = "crate::git::path::escaped")]
     pub path: path::PathBuf,
+    /// The path the file was renamed from, if it was.
+    #[serde(with = "crate::git::path::escaped::option")]
+    pub renamed_from: Option<path::PathBuf>,
     pub hunks: Vec<diff::Hunk>,
     pub binary: bool,
+    pub generated: bool,
 }
 
 pub fn list_remote_commit_files(
     repository: &git::Repository,
     commit_oid: git::Oid,
+    options: &diff::Options,
 ) -> Result<Vec<RemoteBranchFile>, errors::ListRemoteCommitFilesError> {
     let commit = match repository.find_commit(commit_oid) {
         Ok(commit) => Ok(commit),
@@ -35,19 +41,26 @@ pub fn list_remote_commit_files(
     let parent = commit.parent(0).context("failed to get parent commit")?;
     let commit_tree = commit.tree().context("failed to get commit tree")?;
     let parent_tree = parent.tree().context("failed to get parent tree")?;
-    let diff = diff::trees(repository, &parent_tree, &commit_tree)?;
+    let diff = diff::trees_with_options(repository, &parent_tree, &commit_tree, options)?;
 
     let files = diff
         .into_iter()
         .map(|(file_path, hunks)| RemoteBranchFile {
             path: file_path.clone(),
+            renamed_from: hunks.iter().find_map(diff::Hunk::renamed_from),
             hunks: hunks.clone(),
             binary: hunks.iter().any(|h| h.binary),
+            generated: hunks.iter().any(|h| h.generated),
         })
         .collect::<Vec<_>>();
 
-    let files = files_with_hunk_context(repository, &parent_tree, files, 3)
-        .context("failed to add context to hunk")?;
+    let files = files_with_hunk_context(
+        repository,
+        &parent_tree,
+        files,
+        options.context_lines as usize,
+    )
+    .context("failed to add context to hunk")?;
     Ok(files)
 }
 
@@ -62,16 +75,19 @@ fn files_with_hunk_context(
             continue;
         }
         // Get file content as it looked before the diffs
-        let file_content_before =
-            show::show_file_at_tree(repository, file.path.clone(), parent_tree)
-                .context("failed to get file contents at HEAD")?;
+        let file_content_before = show::show_file_at_tree(
+            repository,
+            file.renamed_from.as_ref().unwrap_or(&file.path),
+            parent_tree,
+        )
+        .context("failed to get file contents at HEAD")?;
         let file_lines_before = file_content_before.split('\n').collect::<Vec<_>>();
 
         file.hunks = file
             .hunks
             .iter()
             .map(|hunk| {
-                if hunk.diff.is_empty() {
+                if hunk.diff.is_empty() || hunk.is_header() {
                     // noop on empty diff
                     Ok(hunk.clone())
                 } else {
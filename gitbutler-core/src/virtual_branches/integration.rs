@@ -0,0 +1,578 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Seek, SeekFrom, Write},
+    path,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+
+use crate::{
+    gb_repository,
+    git::{self},
+    project_repository::{self, LogUntil},
+    projects::ProjectId,
+    reader, sessions,
+    virtual_branches::{branch::BranchCreateRequest, normalize_branch_name},
+};
+
+use super::errors;
+
+lazy_static! {
+    pub static ref GITBUTLER_INTEGRATION_REFERENCE: git::LocalRefname =
+        git::LocalRefname::new("gitbutler/integration", None);
+    // the checksum of the index each project was left with by updating its integration
+    static ref WRITTEN_INDEXES: Mutex<HashMap<ProjectId, Vec<u8>>> =
+        Mutex::new(HashMap::new());
+}
+
+pub(crate) const GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME: &str = "GitButler";
+pub(crate) const GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL: &str = "gitbutler@gitbutler.com";
+
+const WIP_COMMIT_TITLE: &str = "GitButler WIP Commit";
+
+/// Whether the index of the project is the one updating its integration last wrote, rather
+/// than one git or someone else did since. Updating it is done by every operation, which
+/// the watchers don't need to see.
+pub fn is_own_index(project_repository: &project_repository::Repository) -> bool {
+    index_checksum(project_repository.git_repository.path()).is_some_and(|checksum| {
+        written_indexes().get(&project_repository.project().id) == Some(&checksum)
+    })
+}
+
+fn written_indexes() -> MutexGuard<'static, HashMap<ProjectId, Vec<u8>>> {
+    WRITTEN_INDEXES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+pub(super) fn forget_written_index(project_id: &ProjectId) {
+    written_indexes().remove(project_id);
+}
+
+// the checksum of the content git writes at the end of the index, which tells indexes apart
+// without reading them whole
+fn index_checksum(git_dir: &path::Path) -> Option<Vec<u8>> {
+    let mut file = fs::File::open(git_dir.join("index")).ok()?;
+    file.seek(SeekFrom::End(-20)).ok()?;
+    let mut checksum = vec![0; 20];
+    file.read_exact(&mut checksum).ok()?;
+    Some(checksum)
+}
+
+/// The branch the workspace is checked out on, `gitbutler.workspaceBranch` of the project or
+/// [`GITBUTLER_INTEGRATION_REFERENCE`] if it isn't set or isn't a valid branch name. A
+/// branch is checked out in one worktree at most, in a linked one it's
+/// `gitbutler/integration-<worktree>` by default.
+pub fn workspace_reference(
+    project_repository: &project_repository::Repository,
+) -> Result<git::LocalRefname> {
+    let Some(name) = project_repository
+        .config()
+        .workspace_branch()
+        .context("failed to read workspace branch config")?
+    else {
+        return Ok(default_workspace_reference(project_repository));
+    };
+    match parse_workspace_branch(&name) {
+        Some(reference) => Ok(reference),
+        None => {
+            tracing::warn!(name, "invalid gitbutler.workspaceBranch, using the default");
+            Ok(default_workspace_reference(project_repository))
+        }
+    }
+}
+
+fn default_workspace_reference(
+    project_repository: &project_repository::Repository,
+) -> git::LocalRefname {
+    match project_repository.git_repository.worktree_name() {
+        Some(worktree) => git::LocalRefname::new(
+            &format!("gitbutler/integration-{}", normalize_branch_name(&worktree)),
+            None,
+        ),
+        None => GITBUTLER_INTEGRATION_REFERENCE.clone(),
+    }
+}
+
+fn parse_workspace_branch(name: &str) -> Option<git::LocalRefname> {
+    let refname = format!("refs/heads/{name}");
+    if name.is_empty() || !git2::Reference::is_valid_name(&refname) {
+        return None;
+    }
+    refname.parse().ok()
+}
+
+/// Makes `name` the branch the workspace is checked out on, renaming the current one, and
+/// checking it out if the workspace was.
+pub fn set_workspace_branch(
+    project_repository: &project_repository::Repository,
+    name: &str,
+) -> Result<(), errors::SetWorkspaceBranchError> {
+    let reference = parse_workspace_branch(name)
+        .ok_or_else(|| errors::SetWorkspaceBranchError::InvalidName(name.to_string()))?;
+    let current = workspace_reference(project_repository)?;
+    if reference == current {
+        return Ok(());
+    }
+
+    let repo = &project_repository.git_repository;
+    match repo.find_reference(&reference.clone().into()) {
+        Ok(_) => return Err(errors::SetWorkspaceBranchError::BranchExists(reference)),
+        Err(git::Error::NotFound(_)) => {}
+        Err(error) => return Err(anyhow::Error::from(error).into()),
+    }
+
+    let on_workspace = repo
+        .head()
+        .ok()
+        .and_then(|head| head.name())
+        .is_some_and(|head| head.to_string() == current.to_string());
+    match repo.find_reference(&current.clone().into()) {
+        Ok(mut current_reference) => {
+            current_reference
+                .rename(&reference.clone().into(), false, "rename workspace branch")
+                .context("failed to rename workspace branch")?;
+            if on_workspace {
+                repo.set_head(&reference.clone().into())
+                    .context("failed to set head")?;
+            }
+        }
+        // there is no workspace yet, it's made under the new name
+        Err(git::Error::NotFound(_)) => {}
+        Err(error) => return Err(anyhow::Error::from(error).into()),
+    }
+
+    project_repository
+        .config()
+        .set_workspace_branch(name)
+        .context("failed to write workspace branch config")?;
+    Ok(())
+}
+
+pub fn update_gitbutler_integration(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<()> {
+    let target = gb_repository
+        .default_target()
+        .context("failed to get target")?
+        .context("no target set")?;
+
+    let repo = &project_repository.git_repository;
+    let workspace_reference = workspace_reference(project_repository)?;
+
+    // the scoped projects of a worktree share its workspace, each leaving what's outside of
+    // its scope as the others made it
+    let scope = project_repository.project().scope.as_deref();
+    let shared_tree = match scope {
+        Some(_) => repo
+            .find_reference(&workspace_reference.clone().into())
+            .ok()
+            .and_then(|reference| reference.peel_to_commit().ok())
+            .filter(is_integration_commit)
+            .as_ref()
+            .map(git::Commit::tree)
+            .transpose()?,
+        None => None,
+    };
+
+    // write the currrent target sha to a temp branch as a parent
+    repo.reference(
+        &workspace_reference.clone().into(),
+        target.sha,
+        true,
+        "update target",
+    )?;
+
+    // get commit object from target.sha
+    let target_commit = repo.find_commit(target.sha)?;
+
+    // get current repo head for reference
+    let head = repo.head()?;
+    // a detached head has no name
+    let mut prev_head = head
+        .name()
+        .map_or_else(|| "HEAD".to_string(), |name| name.to_string());
+    let mut prev_sha = head.target().unwrap().to_string();
+    let integration_file = repo.path().join("integration");
+    if prev_head != workspace_reference.to_string() {
+        // we are moving from a regular branch to our gitbutler integration branch, save the original
+        // write a file to .git/integration with the previous head and name
+        let mut file = std::fs::File::create(integration_file)?;
+        prev_head.push(':');
+        prev_head.push_str(&prev_sha);
+        file.write_all(prev_head.as_bytes())?;
+    } else {
+        // read the .git/integration file
+        if let Ok(mut integration_file) = std::fs::File::open(integration_file) {
+            let mut prev_data = String::new();
+            integration_file.read_to_string(&mut prev_data)?;
+            let parts: Vec<&str> = prev_data.split(':').collect();
+
+            prev_head = parts[0].to_string();
+            prev_sha = parts[1].to_string();
+        }
+    }
+
+    // commit index to temp head for the merge
+    repo.set_head(&workspace_reference.clone().into())
+        .context("failed to set head")?;
+
+    let latest_session = gb_repository
+        .get_latest_session()
+        .context("failed to get latest session")?
+        .context("latest session not found")?;
+    let session_reader = sessions::Reader::open(gb_repository, &latest_session)
+        .context("failed to open current session")?;
+
+    // get all virtual branches, we need to try to update them all
+    let all_virtual_branches = super::iterator::BranchIterator::new(&session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<super::branch::Branch>, reader::Error>>()
+        .context("failed to read virtual branches")?;
+
+    let applied_virtual_branches = all_virtual_branches
+        .iter()
+        .filter(|branch| branch.applied)
+        .collect::<Vec<_>>();
+
+    let base_tree = target_commit.tree()?;
+    let mut final_tree = target_commit.tree()?;
+    for branch in &applied_virtual_branches {
+        // merge this branches tree with our tree
+        let branch_head = repo.find_commit(branch.head)?;
+        let branch_tree = branch_head.tree()?;
+        if let Ok(mut result) = repo.merge_trees(&base_tree, &final_tree, &branch_tree) {
+            if !result.has_conflicts() {
+                let final_tree_oid = result.write_tree_to(repo)?;
+                final_tree = repo.find_tree(final_tree_oid)?;
+            }
+        }
+    }
+
+    if let (Some(scope), Some(shared_tree)) = (scope, &shared_tree) {
+        let mut builder = repo.treebuilder(Some(shared_tree));
+        match final_tree.get_path(scope) {
+            Ok(entry) => builder.upsert(scope, entry.id(), git::FileMode::Tree),
+            Err(_) => builder.remove(scope),
+        }
+        final_tree = repo.find_tree(builder.write()?)?;
+    }
+
+    // message that says how to get back to where they were
+    let mut message = "GitButler Integration Commit".to_string();
+    message.push_str("\n\n");
+    message.push_str(
+        "This is an integration commit for the virtual branches that GitButler is tracking.\n\n",
+    );
+    message.push_str(
+        "Due to GitButler managing multiple virtual branches, you cannot switch back and\n",
+    );
+    message.push_str("forth between git branches and virtual branches easily. \n\n");
+
+    message.push_str("If you switch to another branch, GitButler will need to be reinitialized.\n");
+    message.push_str("If you commit on this branch, GitButler will throw it away.\n\n");
+    message.push_str("Here are the branches that are currently applied:\n");
+    for branch in &applied_virtual_branches {
+        message.push_str(" - ");
+        message.push_str(branch.name.as_str());
+        message.push_str(format!(" ({})", &project_repository.branch_refname(branch)).as_str());
+        message.push('\n');
+
+        if branch.head != target.sha {
+            message.push_str("   branch head: ");
+            message.push_str(&branch.head.to_string());
+            message.push('\n');
+        }
+        for file in &branch.ownership.files {
+            message.push_str("   - ");
+            message.push_str(&file.file_path.display().to_string());
+            message.push('\n');
+        }
+    }
+    message.push_str("\nYour previous branch was: ");
+    message.push_str(&prev_head);
+    message.push_str("\n\n");
+    message.push_str("The sha for that commit was: ");
+    message.push_str(&prev_sha);
+    message.push_str("\n\n");
+    message.push_str("For more information about what we're doing here, check out our docs:\n");
+    message.push_str("https://docs.gitbutler.com/features/virtual-branches/integration-branch\n");
+
+    let committer = git::Signature::now(
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL,
+    )?;
+
+    repo.commit(
+        Some(&workspace_reference.clone().into()),
+        &committer,
+        &committer,
+        &message,
+        &final_tree,
+        &[&target_commit],
+    )?;
+
+    // write final_tree as the current index
+    let mut index = repo.index()?;
+    index.read_tree(&final_tree)?;
+    index.write()?;
+    if let Some(checksum) = index_checksum(repo.path()) {
+        written_indexes().insert(project_repository.project().id, checksum);
+    }
+
+    // finally, update the refs/gitbutler/ heads to the states of the current virtual branches
+    for branch in &all_virtual_branches {
+        let branch_head = head_with_wip(repo, branch, &committer)?;
+        repo.reference(
+            &project_repository.branch_refname(branch).into(),
+            branch_head.id(),
+            true,
+            "update virtual branch",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The head of `branch`, with its uncommitted changes on top in a WIP commit when it has
+/// some. This is what the `refs/gitbutler/` reference of the branch points to.
+pub(crate) fn head_with_wip<'repo>(
+    repo: &'repo git::Repository,
+    branch: &super::Branch,
+    committer: &git::Signature,
+) -> Result<git::Commit<'repo>> {
+    let wip_tree = repo.find_tree(branch.tree)?;
+    let branch_head = repo.find_commit(branch.head)?;
+    let head_tree = branch_head.tree()?;
+
+    // create a wip commit if there is wip
+    if head_tree.id() == wip_tree.id() {
+        return Ok(branch_head);
+    }
+
+    let mut message = WIP_COMMIT_TITLE.to_string();
+    message.push_str("\n\n");
+    message.push_str("This is a WIP commit for the virtual branch '");
+    message.push_str(branch.name.as_str());
+    message.push_str("'\n\n");
+    message.push_str("This commit is used to store the state of the virtual branch\n");
+    message.push_str("while you are working on it. It is not meant to be used for\n");
+    message.push_str("anything else.\n\n");
+    let branch_head_oid = repo.commit(
+        None,
+        committer,
+        committer,
+        &message,
+        &wip_tree,
+        &[&branch_head],
+    )?;
+    repo.find_commit(branch_head_oid).map_err(Into::into)
+}
+
+/// Whether `commit` is a WIP commit written by [`head_with_wip`]: GitButler committed it,
+/// with the WIP title as the title of its message, not merely at its start.
+pub(crate) fn is_wip_commit(commit: &git::Commit) -> bool {
+    commit.parent_count() == 1
+        && commit.message().and_then(|message| message.lines().next()) == Some(WIP_COMMIT_TITLE)
+        && commit.committer().email() == Some(GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL)
+}
+
+pub fn verify_branch(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<(), errors::VerifyError> {
+    verify_head_is_set(gb_repository, project_repository)?;
+    verify_head_is_clean(gb_repository, project_repository)?;
+    super::reconcile::detect_stash(gb_repository, project_repository)
+        .context("failed to look for stashed changes")?;
+    Ok(())
+}
+
+fn verify_head_is_clean(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<(), errors::VerifyError> {
+    let head_commit = project_repository
+        .git_repository
+        .head()
+        .context("failed to get head")?
+        .peel_to_commit()
+        .context("failed to peel to commit")?;
+
+    let mut extra_commits = project_repository
+        .log(
+            head_commit.id(),
+            LogUntil::When(Box::new(|commit| Ok(is_integration_commit(commit)))),
+        )
+        .context("failed to get log")?;
+
+    let integration_commit = extra_commits.pop();
+
+    if integration_commit.is_none() {
+        // no integration commit found
+        return Err(errors::VerifyError::NoIntegrationCommit);
+    }
+
+    if extra_commits.is_empty() {
+        // no extra commits found, so we're good
+        return Ok(());
+    }
+
+    project_repository
+        .git_repository
+        .reset(
+            integration_commit.as_ref().unwrap(),
+            git2::ResetType::Soft,
+            None,
+        )
+        .context("failed to reset to integration commit")?;
+
+    extra_commits.reverse();
+    if let Some(change) =
+        super::reconcile::move_onto_owning_branch(gb_repository, project_repository, &extra_commits)
+            .context("failed to move commits onto their branch")?
+    {
+        super::reconcile::record(gb_repository, change)
+            .context("failed to record external commits")?;
+        return Ok(());
+    }
+
+    let mut new_branch = super::create_virtual_branch(
+        gb_repository,
+        project_repository,
+        &BranchCreateRequest {
+            name: extra_commits
+                .first()
+                .unwrap()
+                .message()
+                .map(ToString::to_string),
+            ..Default::default()
+        },
+    )
+    .context("failed to create virtual branch")?;
+
+    // rebasing the extra commits onto the new branch
+    let writer = super::branch::Writer::new(gb_repository).context("failed to create writer")?;
+    let previous_head = new_branch.head;
+    let mut head = new_branch.head;
+    let mut moved = vec![];
+    for commit in extra_commits {
+        let new_branch_head = project_repository
+            .git_repository
+            .find_commit(head)
+            .context("failed to find new branch head")?;
+
+        let rebased_commit_oid = project_repository
+            .git_repository
+            .commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                commit.message().unwrap(),
+                &commit.tree().unwrap(),
+                &[&new_branch_head],
+            )
+            .context(format!(
+                "failed to rebase commit {} onto new branch",
+                commit.id()
+            ))?;
+
+        let rebased_commit = project_repository
+            .git_repository
+            .find_commit(rebased_commit_oid)
+            .context(format!(
+                "failed to find rebased commit {}",
+                rebased_commit_oid
+            ))?;
+
+        new_branch.head = rebased_commit.id();
+        new_branch.tree = rebased_commit.tree_id();
+        writer
+            .write(&mut new_branch)
+            .context("failed to write branch")?;
+
+        head = rebased_commit.id();
+        moved.push(head);
+    }
+    super::reconcile::record(
+        gb_repository,
+        super::ExternalChangeKind::Commits {
+            commits: moved,
+            branch_id: new_branch.id,
+            previous_head,
+            created_branch: true,
+        },
+    )
+    .context("failed to record external commits")?;
+    Ok(())
+}
+
+fn verify_head_is_set(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<(), errors::VerifyError> {
+    let workspace = workspace_reference(project_repository)?;
+    match project_repository
+        .get_head()
+        .context("failed to get head")
+        .map_err(errors::VerifyError::Other)?
+        .name()
+    {
+        Some(refname) if refname.to_string() == workspace.to_string() => {
+            super::reconcile::detect_checkout(gb_repository, None, vec![])
+                .context("failed to record checkout")?;
+            Ok(())
+        }
+        None => {
+            let unapplied =
+                super::mark_all_unapplied(gb_repository).map_err(errors::VerifyError::Other)?;
+            super::reconcile::detect_checkout(gb_repository, Some("HEAD".to_string()), unapplied)
+                .context("failed to record checkout")?;
+            Err(errors::VerifyError::DetachedHead { workspace })
+        }
+        Some(head_name) => {
+            let unapplied =
+                super::mark_all_unapplied(gb_repository).map_err(errors::VerifyError::Other)?;
+            super::reconcile::detect_checkout(
+                gb_repository,
+                Some(head_name.to_string()),
+                unapplied,
+            )
+            .context("failed to record checkout")?;
+            Err(errors::VerifyError::InvalidHead {
+                head: head_name.to_string(),
+                workspace,
+            })
+        }
+    }
+}
+
+fn is_integration_commit(commit: &git::Commit) -> bool {
+    is_integration_commit_author(commit) && is_integration_commit_message(commit)
+}
+
+fn is_integration_commit_author(commit: &git::Commit) -> bool {
+    is_integration_commit_author_email(commit) && is_integration_commit_author_name(commit)
+}
+
+fn is_integration_commit_author_email(commit: &git::Commit) -> bool {
+    commit.author().email().map_or(false, |email| {
+        email == GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL
+    })
+}
+
+fn is_integration_commit_author_name(commit: &git::Commit) -> bool {
+    commit.author().name().map_or(false, |name| {
+        name == GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME
+    })
+}
+
+fn is_integration_commit_message(commit: &git::Commit) -> bool {
+    commit.message().map_or(false, |message| {
+        message.starts_with("GitButler Integration Commit")
+    })
+}
@@ -0,0 +1,201 @@
+//! Large files are painful to get rid of once they are in the history of a repository. The
+//! files a commit adds over the size limit of the project, and the binary files that aren't
+//! tracked by LFS over the lower limit for those, are warned about, or refused when the
+//! `gitbutler.largeFiles` git config is `block`, with the command tracking them with LFS.
+//! Files are measured as they are committed, after their filter driver cleans them.
+
+use std::{collections::HashMap, fs, path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    git::{self, diff},
+    project_repository,
+};
+
+// what GitHub warns about, it refuses files over 100MB
+const DEFAULT_MAX_FILE_SIZE: u64 = 50 * 1024 * 1024;
+// icons are fine, videos and archives aren't
+const DEFAULT_MAX_BINARY_FILE_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LargeFilesPolicy {
+    Off,
+    /// Large files are committed, the ui warns about them before.
+    Warn,
+    /// Commits with large files are refused.
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LargeFileReason {
+    /// The file is over the size limit.
+    Size,
+    /// The file is binary, not tracked by LFS and over the size limit of binary files.
+    Binary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LargeFile {
+    #[serde(with = "crate::git::path::escaped")]
+    pub file_path: path::PathBuf,
+    pub size: u64,
+    pub reason: LargeFileReason,
+    /// The command that tracks files like it with LFS.
+    pub lfs_track: String,
+}
+
+/// The limits of the project files that are committed are held to.
+pub struct LargeFilesCheck {
+    pub policy: LargeFilesPolicy,
+    pub max_file_size: u64,
+    pub max_binary_file_size: u64,
+}
+
+impl LargeFilesCheck {
+    pub fn open(project_repository: &project_repository::Repository) -> Result<Self> {
+        let config = project_repository.config();
+        let policy = match config
+            .large_files()
+            .context("failed to read large files config")?
+            .as_deref()
+        {
+            Some("off") => LargeFilesPolicy::Off,
+            Some("block") => LargeFilesPolicy::Block,
+            None | Some("warn") => LargeFilesPolicy::Warn,
+            Some(policy) => {
+                tracing::warn!(policy, "unknown gitbutler.largeFiles, warning about them");
+                LargeFilesPolicy::Warn
+            }
+        };
+        let size = |size: Option<i64>, default| {
+            size.and_then(|size| u64::try_from(size).ok())
+                .unwrap_or(default)
+        };
+        Ok(Self {
+            policy,
+            max_file_size: size(
+                config
+                    .max_file_size()
+                    .context("failed to read max file size config")?,
+                DEFAULT_MAX_FILE_SIZE,
+            ),
+            max_binary_file_size: size(
+                config
+                    .max_binary_file_size()
+                    .context("failed to read max binary file size config")?,
+                DEFAULT_MAX_BINARY_FILE_SIZE,
+            ),
+        })
+    }
+
+    /// The large files of `files`, the changes of the working directory. Empty if the
+    /// project doesn't check for them.
+    pub fn check(
+        &self,
+        project_repository: &project_repository::Repository,
+        files: &HashMap<path::PathBuf, Vec<diff::Hunk>>,
+    ) -> Result<Vec<LargeFile>> {
+        if self.policy == LargeFilesPolicy::Off {
+            return Ok(vec![]);
+        }
+        let repo = &project_repository.git_repository;
+        let mut large_files = vec![];
+        for (file_path, hunks) in files {
+            if hunks
+                .iter()
+                .all(|hunk| hunk.change_type == diff::ChangeType::Deleted)
+            {
+                continue;
+            }
+            // what is committed of files tracked by LFS is a pointer to them
+            if repo
+                .is_path_lfs(file_path)
+                .context("failed to read lfs attribute")?
+            {
+                continue;
+            }
+            let Some(size) = blob_size(repo, file_path, hunks)? else {
+                continue;
+            };
+            let reason = if size > self.max_file_size {
+                LargeFileReason::Size
+            } else if size > self.max_binary_file_size && hunks.iter().any(|hunk| hunk.binary) {
+                LargeFileReason::Binary
+            } else {
+                continue;
+            };
+            large_files.push(LargeFile {
+                file_path: file_path.clone(),
+                size,
+                reason,
+                lfs_track: lfs_track(file_path),
+            });
+        }
+        large_files.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        Ok(large_files)
+    }
+}
+
+// the size of `file_path` as it is committed: the size of the blob of a binary file, which
+// the diff already wrote, of what a filter driver cleans the file to, and otherwise of the
+// file, which only its line endings can change. `None` if it isn't there anymore
+fn blob_size(
+    repo: &git::Repository,
+    file_path: &path::Path,
+    hunks: &[diff::Hunk],
+) -> Result<Option<u64>> {
+    if let Some(id) = hunks
+        .iter()
+        .find(|hunk| hunk.binary)
+        .and_then(|hunk| hunk.diff.parse::<git::Oid>().ok())
+    {
+        if let Ok(size) = repo.blob_size(id) {
+            return Ok(Some(size));
+        }
+    }
+    let path = repo
+        .workdir()
+        .context("repository has no workdir")?
+        .join(file_path);
+    if git::filters::is_cleaned(repo, file_path).context("failed to read filter attribute")? {
+        let Ok(content) = fs::read(&path) else {
+            return Ok(None);
+        };
+        let cleaned = git::filters::clean(repo, file_path, &content)?;
+        return Ok(Some(u64::try_from(cleaned.len()).unwrap_or(u64::MAX)));
+    }
+    Ok(fs::symlink_metadata(path)
+        .ok()
+        .map(|metadata| metadata.len()))
+}
+
+// files of the same type are tracked together, the ones without an extension on their own
+fn lfs_track(file_path: &path::Path) -> String {
+    let pattern = match file_path.extension() {
+        Some(extension) => format!("*.{}", extension.to_string_lossy()),
+        None => git::path::escape(file_path),
+    };
+    format!("git lfs track \"{pattern}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lfs_track_by_extension() {
+        assert_eq!(
+            lfs_track(path::Path::new("assets/video.MP4")),
+            "git lfs track \"*.MP4\""
+        );
+        assert_eq!(
+            lfs_track(path::Path::new("bin/tool")),
+            "git lfs track \"bin/tool\""
+        );
+    }
+}
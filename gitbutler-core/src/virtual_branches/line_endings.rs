@@ -0,0 +1,138 @@
+use std::path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    gb_repository,
+    git::{self, diff, eol, filters},
+    project_repository,
+};
+
+use super::{errors, target};
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileLineEndings {
+    #[serde(with = "crate::git::path::escaped")]
+    pub path: path::PathBuf,
+    /// How the file differs from the base branch, if it only differs in its line endings or
+    /// byte order mark.
+    pub churn: Option<eol::Churn>,
+}
+
+/// The files that changed in the working directory, and whether their change is only line
+/// endings or a byte order mark, which editors tend to add without being asked.
+pub fn list_line_endings(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<Vec<FileLineEndings>, errors::ListLineEndingsError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::ListLineEndingsError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let repo = &project_repository.git_repository;
+    let diff = diff::workdir(repo, &default_target.sha).context("failed to diff workdir")?;
+    let mut files = diff
+        .into_iter()
+        .map(|(path, hunks)| {
+            let churn = if hunks.iter().all(is_content_change) {
+                file_churn(repo, &default_target, &path)?
+            } else {
+                None
+            };
+            Ok(FileLineEndings { path, churn })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Writes the files at `paths` back with the byte order mark they have in the base branch, and
+/// the line endings they are checked out with. Files that have other changes are an error, and
+/// none are written.
+pub fn normalize_line_endings(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    paths: &[path::PathBuf],
+) -> Result<(), errors::NormalizeLineEndingsError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::NormalizeLineEndingsError::DefaultTargetNotSet(
+                errors::DefaultTargetNotSetError {
+                    project_id: project_repository.project().id,
+                },
+            )
+        })?;
+
+    let repo = &project_repository.git_repository;
+    let mut normalized = Vec::with_capacity(paths.len());
+    for path in paths {
+        if file_churn(repo, &default_target, path)?.is_none() {
+            return Err(errors::NormalizeLineEndingsError::NotChurn(path.clone()));
+        }
+        let committed = committed(repo, &default_target, path)?;
+        normalized.push((path, eol::to_workdir(repo, path, &committed)?.into_owned()));
+    }
+
+    let workdir = repo.workdir().context("repository has no workdir")?;
+    for (path, content) in normalized {
+        std::fs::write(workdir.join(path), content)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+// renames, mode changes and binary files are left out, their change is more than the content
+fn is_content_change(hunk: &diff::Hunk) -> bool {
+    hunk.change_type == diff::ChangeType::Modified
+        && !hunk.binary
+        && !hunk.is_submodule()
+        && !hunk.is_header()
+}
+
+fn file_churn(
+    repo: &git::Repository,
+    default_target: &target::Target,
+    path: &path::Path,
+) -> Result<Option<eol::Churn>> {
+    // what is committed is the output of the driver, it can't be compared with the workdir
+    if filters::is_cleaned(repo, path)? {
+        return Ok(None);
+    }
+    let committed = committed(repo, default_target, path)?;
+    let workdir = repo.workdir().context("repository has no workdir")?;
+    let full_path = workdir.join(path);
+    if !std::fs::symlink_metadata(&full_path).is_ok_and(|metadata| metadata.is_file()) {
+        return Ok(None);
+    }
+    let content =
+        std::fs::read(&full_path).with_context(|| format!("failed to read {}", path.display()))?;
+    let content = eol::to_odb(repo, path, &content, Some(&committed))?;
+    Ok(eol::churn(&committed, &content))
+}
+
+fn committed(
+    repo: &git::Repository,
+    default_target: &target::Target,
+    path: &path::Path,
+) -> Result<Vec<u8>> {
+    let tree = repo
+        .find_commit(default_target.sha)
+        .and_then(|commit| commit.tree())
+        .context("failed to find target tree")?;
+    let entry = tree
+        .get_path(path)
+        .with_context(|| format!("{} not found in target tree", path.display()))?;
+    let blob = repo
+        .find_blob(entry.id())
+        .with_context(|| format!("failed to find blob of {}", path.display()))?;
+    Ok(blob.content().to_vec())
+}
@@ -0,0 +1,353 @@
+//! The layout of the workspace, shared through the remote: the names, notes, order and
+//! owned files of the virtual branches are written to a commit that
+//! [`METADATA_REFERENCE`] points to, which is pushed and fetched like any other ref.
+//!
+//! Fetching merges the metadata into the branches that are here: what changed here since
+//! the metadata was last written or fetched is kept, the rest is taken from the remote.
+//!
+//! The commits of the branches don't travel with it. Another clone of the repository
+//! restores the branches whose commits it has, like the ones that were pushed and fetched.
+
+use std::time;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dedup::dedup,
+    gb_repository,
+    git::{self, credentials},
+    project_repository, users,
+};
+
+use super::{
+    branch::{self, BranchId, Ownership},
+    errors,
+    recovery::read_branches,
+};
+
+/// The ref the metadata of the virtual branches is written to. No virtual branch has it as
+/// its `refs/gitbutler/` reference.
+pub const METADATA_REFERENCE: &str = "refs/gitbutler/metadata";
+const METADATA_FILE: &str = "branches.json";
+const METADATA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Metadata {
+    version: u32,
+    branches: Vec<BranchMetadata>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BranchMetadata {
+    id: BranchId,
+    name: String,
+    notes: String,
+    applied: bool,
+    order: usize,
+    upstream: Option<git::RemoteRefname>,
+    head: git::Oid,
+    /// The files the branch owned, for the clones that restore it to show what was in it.
+    ownership: Ownership,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoredMetadata {
+    /// The branches that were added or updated.
+    pub restored: Vec<BranchId>,
+    /// The names of the branches whose commits aren't in the repository.
+    pub skipped: Vec<String>,
+}
+
+/// Writes the metadata of the virtual branches to [`METADATA_REFERENCE`], on top of the
+/// metadata that was there.
+pub fn write_metadata(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    user: Option<&users::User>,
+) -> Result<git::Oid> {
+    let mut branches = read_branches(gb_repository)?;
+    branches.sort_by_key(|branch| branch.order);
+    let metadata = Metadata {
+        version: METADATA_VERSION,
+        branches: branches
+            .iter()
+            .map(|branch| BranchMetadata {
+                id: branch.id,
+                name: branch.name.clone(),
+                notes: branch.notes.clone(),
+                applied: branch.applied,
+                order: branch.order,
+                upstream: branch.upstream.clone(),
+                head: branch.head,
+                ownership: branch.ownership.clone(),
+            })
+            .collect(),
+    };
+
+    let repo = &project_repository.git_repository;
+    let blob = repo
+        .blob(&serde_json::to_vec_pretty(&metadata).context("failed to serialize metadata")?)
+        .context("failed to write metadata")?;
+    let mut builder = repo.treebuilder(None);
+    builder.upsert(METADATA_FILE, blob, git::FileMode::Blob);
+    let tree = builder.write().context("failed to write metadata tree")?;
+    let tree = repo
+        .find_tree(tree)
+        .context("failed to find metadata tree")?;
+
+    let refname = metadata_refname();
+    let parent = find_metadata_commit(repo)?;
+    if let Some(parent) = &parent {
+        // nothing changed since it was last written
+        if parent.tree_id() == tree.id() {
+            return Ok(parent.id());
+        }
+    }
+
+    let (author, committer) = project_repository.git_signatures(user)?;
+    repo.commit(
+        Some(&refname),
+        &author,
+        &committer,
+        "GitButler branch metadata",
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+    )
+    .context("failed to commit metadata")
+}
+
+/// Writes the metadata of the virtual branches and pushes it to `remote`, the remote of the
+/// target branch by default, replacing the metadata pushed from other clones.
+pub fn push_metadata(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    remote: Option<&str>,
+    credentials: &credentials::Helper,
+    user: Option<&users::User>,
+) -> Result<(), errors::SyncMetadataError> {
+    let remote = remote_or_target(gb_repository, project_repository, remote)?;
+    write_metadata(gb_repository, project_repository, user)?;
+    project_repository
+        .push_metadata(&remote, METADATA_REFERENCE, credentials)
+        .map_err(errors::SyncMetadataError::Remote)
+}
+
+/// Fetches the metadata of `remote`, the remote of the target branch by default, and
+/// restores the branches it has.
+pub fn fetch_metadata(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    remote: Option<&str>,
+    credentials: &credentials::Helper,
+) -> Result<RestoredMetadata, errors::SyncMetadataError> {
+    let remote = remote_or_target(gb_repository, project_repository, remote)?;
+    // what this clone last wrote or fetched, which the fetch replaces
+    let base = find_metadata_commit(&project_repository.git_repository)?.map(|commit| commit.id());
+    project_repository
+        .fetch_metadata(&remote, METADATA_REFERENCE, credentials)
+        .map_err(errors::SyncMetadataError::Remote)?;
+    restore_metadata(gb_repository, project_repository, base)
+}
+
+/// Restores the branches of the metadata [`METADATA_REFERENCE`] points to.
+///
+/// The branches the project has get the names, notes and order of the metadata, but for
+/// the ones that changed here since `base`, the metadata commit this clone last wrote or
+/// fetched: those are kept. The others are added, unapplied, if their commits are in the
+/// repository.
+pub fn restore_metadata(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    base: Option<git::Oid>,
+) -> Result<RestoredMetadata, errors::SyncMetadataError> {
+    let repo = &project_repository.git_repository;
+    let Some(commit) = find_metadata_commit(repo)? else {
+        return Err(errors::SyncMetadataError::NoMetadata);
+    };
+    let metadata = read_metadata(repo, &commit)?;
+    if metadata.version > METADATA_VERSION {
+        return Err(errors::SyncMetadataError::InvalidMetadata(
+            "the metadata was written by a newer version of GitButler".to_string(),
+        ));
+    }
+    // the metadata `base` has, if it can be read, or else every change here is kept
+    let base = match base {
+        Some(base) if base != commit.id() => repo
+            .find_commit(base)
+            .ok()
+            .and_then(|base| read_metadata(repo, &base).ok())
+            .map(|base| base.branches),
+        Some(_) => Some(metadata.branches.clone()),
+        None => None,
+    }
+    .unwrap_or_default();
+
+    let mut branches = read_branches(gb_repository)?;
+    let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    let mut restored = RestoredMetadata {
+        restored: vec![],
+        skipped: vec![],
+    };
+    for metadata in metadata.branches {
+        if let Some(index) = branches.iter().position(|branch| branch.id == metadata.id) {
+            let base = base.iter().find(|base| base.id == metadata.id);
+            let branch = &branches[index];
+            let name = merge(&branch.name, base.map(|base| &base.name), &metadata.name);
+            let notes = merge(&branch.notes, base.map(|base| &base.notes), &metadata.notes);
+            let order = merge(&branch.order, base.map(|base| &base.order), &metadata.order);
+            let name = if name == branch.name {
+                name
+            } else {
+                dedup(
+                    &branches
+                        .iter()
+                        .filter(|branch| branch.id != metadata.id)
+                        .map(|branch| branch.name.as_str())
+                        .collect::<Vec<_>>(),
+                    &name,
+                )
+            };
+            let branch = &mut branches[index];
+            if branch.name == name && branch.notes == notes && branch.order == order {
+                continue;
+            }
+            if branch.name != name {
+                project_repository.delete_branch_reference(branch)?;
+                branch.name = name;
+                project_repository.add_branch_reference(branch)?;
+            }
+            branch.notes = notes;
+            branch.order = order;
+            writer.write(branch).context("failed to write branch")?;
+            restored.restored.push(branch.id);
+            continue;
+        }
+
+        let Some(head) = find_head(repo, &metadata) else {
+            restored.skipped.push(metadata.name);
+            continue;
+        };
+        let tree = repo
+            .find_commit(head)
+            .context("failed to find head commit")?
+            .tree_id();
+        let now = time::UNIX_EPOCH
+            .elapsed()
+            .context("failed to get elapsed time")?
+            .as_millis();
+        let mut branch = branch::Branch {
+            id: metadata.id,
+            name: dedup(
+                &branches
+                    .iter()
+                    .map(|branch| branch.name.as_str())
+                    .collect::<Vec<_>>(),
+                &metadata.name,
+            ),
+            notes: metadata.notes,
+            applied: false,
+            // where the upstream is known to be, not where the branch is
+            upstream_head: metadata
+                .upstream
+                .as_ref()
+                .and_then(|upstream| find_commit(repo, &upstream.clone().into())),
+            upstream: metadata.upstream,
+            created_timestamp_ms: now,
+            updated_timestamp_ms: now,
+            tree,
+            head,
+            // the branch has no uncommitted changes here
+            ownership: Ownership::default(),
+            order: metadata.order,
+            selected_for_changes: None,
+        };
+        writer
+            .write(&mut branch)
+            .context("failed to write branch")?;
+        project_repository.add_branch_reference(&branch)?;
+        restored.restored.push(branch.id);
+        branches.push(branch);
+    }
+    Ok(restored)
+}
+
+fn metadata_refname() -> git::Refname {
+    git::Refname::Other(METADATA_REFERENCE.to_string())
+}
+
+fn find_metadata_commit(repo: &git::Repository) -> Result<Option<git::Commit>> {
+    match repo.find_reference(&metadata_refname()) {
+        Ok(reference) => reference
+            .peel_to_commit()
+            .map(Some)
+            .context("failed to read metadata commit"),
+        Err(git::Error::NotFound(_)) => Ok(None),
+        Err(error) => Err(error).context("failed to find metadata reference"),
+    }
+}
+
+// the value of the metadata, unless the one here changed since `base`
+fn merge<T: PartialEq + Clone>(local: &T, base: Option<&T>, metadata: &T) -> T {
+    if base == Some(local) {
+        metadata.clone()
+    } else {
+        local.clone()
+    }
+}
+
+fn read_metadata(
+    repo: &git::Repository,
+    commit: &git::Commit,
+) -> Result<Metadata, errors::SyncMetadataError> {
+    let invalid = || {
+        errors::SyncMetadataError::InvalidMetadata(
+            "the metadata of the branches can't be read".to_string(),
+        )
+    };
+    let tree = commit.tree().map_err(|_| invalid())?;
+    let entry = tree.get_name(METADATA_FILE).ok_or_else(invalid)?;
+    let blob = repo.find_blob(entry.id()).map_err(|_| invalid())?;
+    serde_json::from_slice(blob.content()).map_err(|_| invalid())
+}
+
+// the head of the branch if it is here, or else the head of its upstream
+fn find_head(repo: &git::Repository, metadata: &BranchMetadata) -> Option<git::Oid> {
+    if repo.find_commit(metadata.head).is_ok() {
+        return Some(metadata.head);
+    }
+    let upstream = metadata.upstream.as_ref()?;
+    find_commit(repo, &upstream.clone().into())
+}
+
+fn find_commit(repo: &git::Repository, refname: &git::Refname) -> Option<git::Oid> {
+    repo.find_reference(refname)
+        .ok()?
+        .peel_to_commit()
+        .ok()
+        .map(|commit| commit.id())
+}
+
+fn remote_or_target(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    remote: Option<&str>,
+) -> Result<String, errors::SyncMetadataError> {
+    match remote {
+        Some(remote) => Ok(remote.to_string()),
+        None => Ok(gb_repository
+            .default_target()
+            .context("failed to get default target")?
+            .ok_or_else(|| {
+                errors::SyncMetadataError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                    project_id: project_repository.project().id,
+                })
+            })?
+            .branch
+            .remote()
+            .to_string()),
+    }
+}
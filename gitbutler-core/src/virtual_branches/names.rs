@@ -0,0 +1,384 @@
+//! Names for a branch, from the changes it owns. They are suggested without a model from the
+//! directories and the words the changes touch the most, and what a model is asked to name is
+//! [`BranchChanges::diff`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path,
+};
+
+use anyhow::Context;
+
+use crate::{
+    gb_repository,
+    git::diff::{self, ChangeType},
+    project_repository,
+};
+
+use super::{errors, BranchId};
+
+// git allows more, but a name is read in lists of branches
+const MAX_NAME_LENGTH: usize = 50;
+const SUGGESTIONS: usize = 3;
+// words shorter than this are rarely what a change is about
+const MIN_WORD_LENGTH: usize = 4;
+// directories that most files of a project are in, and say nothing about a change
+const GENERIC_DIRECTORIES: &[&str] = &[
+    "src", "lib", "libs", "app", "apps", "pkg", "packages", "crates", "internal", "source",
+];
+// the keywords of common languages, and words every diff has
+const STOP_WORDS: &[&str] = &[
+    "async",
+    "await",
+    "bool",
+    "break",
+    "case",
+    "class",
+    "const",
+    "continue",
+    "crate",
+    "default",
+    "else",
+    "enum",
+    "export",
+    "extern",
+    "false",
+    "from",
+    "func",
+    "function",
+    "impl",
+    "import",
+    "interface",
+    "into",
+    "loop",
+    "match",
+    "none",
+    "null",
+    "option",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "result",
+    "return",
+    "self",
+    "some",
+    "static",
+    "string",
+    "struct",
+    "super",
+    "switch",
+    "test",
+    "tests",
+    "that",
+    "then",
+    "this",
+    "todo",
+    "true",
+    "type",
+    "undefined",
+    "unwrap",
+    "value",
+    "void",
+    "where",
+    "while",
+    "with",
+];
+
+/// The uncommitted changes of a branch, in the hunks it owns.
+#[derive(Debug, Clone, Default)]
+pub struct BranchChanges {
+    files: Vec<(path::PathBuf, Vec<diff::Hunk>)>,
+}
+
+impl From<HashMap<path::PathBuf, Vec<diff::Hunk>>> for BranchChanges {
+    fn from(files: HashMap<path::PathBuf, Vec<diff::Hunk>>) -> Self {
+        let mut files = files.into_iter().collect::<Vec<_>>();
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Self { files }
+    }
+}
+
+impl BranchChanges {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The paths of the files that changed, in alphabetical order.
+    pub fn paths(&self) -> Vec<&path::Path> {
+        self.files.iter().map(|(path, _)| path.as_path()).collect()
+    }
+
+    /// The text diff of the changes, file by file, at most `max_len` bytes of it.
+    pub fn diff(&self, max_len: usize) -> String {
+        let mut diff = String::new();
+        for (path, hunks) in &self.files {
+            let path = path.display();
+            let _ = write!(diff, "--- a/{path}\n+++ b/{path}\n");
+            for hunk in hunks.iter().filter(|hunk| !hunk.binary) {
+                diff.push_str(&hunk.diff);
+                if !hunk.diff.ends_with('\n') {
+                    diff.push('\n');
+                }
+            }
+            if diff.len() >= max_len {
+                break;
+            }
+        }
+        if diff.len() > max_len {
+            let mut end = max_len;
+            while !diff.is_char_boundary(end) {
+                end -= 1;
+            }
+            diff.truncate(end);
+        }
+        diff
+    }
+
+    /// Up to three names for the changes, from what they do to the files, the directory they
+    /// change the most and the words of the lines they add and remove the most. The same
+    /// changes are always suggested the same names.
+    pub fn suggest_names(&self) -> Vec<String> {
+        if self.files.is_empty() {
+            return vec![];
+        }
+
+        let change_types = self
+            .files
+            .iter()
+            .flat_map(|(_, hunks)| hunks.iter().map(|hunk| hunk.change_type))
+            .collect::<Vec<_>>();
+        let verb = if change_types.iter().all(|t| *t == ChangeType::Added) {
+            "add"
+        } else if change_types.iter().all(|t| *t == ChangeType::Deleted) {
+            "remove"
+        } else {
+            "update"
+        };
+
+        let mut subjects = BTreeMap::<String, usize>::new();
+        let mut words = BTreeMap::<String, usize>::new();
+        for (path, hunks) in &self.files {
+            let changed_lines = hunks
+                .iter()
+                .flat_map(|hunk| changed_lines(&hunk.diff))
+                .collect::<Vec<_>>();
+            let weight = changed_lines.len().max(1);
+            if let Some(subject) = subject(path) {
+                *subjects.entry(subject).or_default() += weight;
+            }
+            for word in changed_lines.iter().flat_map(|line| split_words(line)) {
+                *words.entry(word).or_default() += 1;
+            }
+        }
+        let subjects = ranked(subjects);
+        let words = ranked(words)
+            .into_iter()
+            .filter(|word| {
+                !subjects
+                    .iter()
+                    .any(|subject| subject.contains(word.as_str()))
+            })
+            .collect::<Vec<_>>();
+
+        let subject = subjects.first();
+        let mut candidates = vec![];
+        if let Some(subject) = subject {
+            candidates.push(format!("{verb}-{subject}"));
+        }
+        match (subject, words.first()) {
+            (Some(subject), Some(word)) => candidates.push(format!("{subject}-{word}")),
+            (None, Some(word)) => candidates.push(format!("{verb}-{word}")),
+            _ => {}
+        }
+        match (subjects.get(1), words.as_slice()) {
+            (Some(other), _) => candidates.push(format!("{verb}-{}-{other}", subjects[0])),
+            (None, [first, second, ..]) => candidates.push(format!("{verb}-{first}-{second}")),
+            _ => {}
+        }
+
+        let mut names = vec![];
+        for name in candidates.iter().map(|candidate| to_branch_name(candidate)) {
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names.truncate(SUGGESTIONS);
+        names
+    }
+}
+
+/// `text` as a branch name in kebab-case: lowercase letters and digits, with single dashes
+/// between the words, and not too long to read.
+pub fn to_branch_name(text: &str) -> String {
+    let mut name = String::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let word = word.to_lowercase();
+        if !name.is_empty() && name.len() + 1 + word.len() > MAX_NAME_LENGTH {
+            break;
+        }
+        if !name.is_empty() {
+            name.push('-');
+        }
+        name.push_str(&word);
+    }
+    name.chars().take(MAX_NAME_LENGTH).collect()
+}
+
+// the lines a hunk adds or removes, without their `+` or `-`
+fn changed_lines(diff: &str) -> impl Iterator<Item = &str> {
+    diff.lines()
+        .filter(|line| !line.starts_with("+++") && !line.starts_with("---"))
+        .filter_map(|line| line.strip_prefix('+').or_else(|| line.strip_prefix('-')))
+}
+
+// what a file is part of: the innermost directory that says something, or its name if it's
+// in none
+fn subject(path: &path::Path) -> Option<String> {
+    let directory = path
+        .parent()
+        .into_iter()
+        .flat_map(path::Path::components)
+        .filter_map(|component| component.as_os_str().to_str())
+        .filter(|name| !name.starts_with('.') && !GENERIC_DIRECTORIES.contains(name))
+        .last();
+    directory
+        .or_else(|| path.file_stem().and_then(|stem| stem.to_str()))
+        .map(to_branch_name)
+        .filter(|subject| !subject.is_empty())
+}
+
+// the words of the identifiers of `line`, split at underscores and camel humps
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = vec![];
+    for identifier in line.split(|c: char| !c.is_alphanumeric()) {
+        let mut word = String::new();
+        let mut previous_lowercase = false;
+        for c in identifier.chars() {
+            if c.is_uppercase() && previous_lowercase {
+                words.push(std::mem::take(&mut word));
+            }
+            previous_lowercase = c.is_lowercase() || c.is_ascii_digit();
+            word.extend(c.to_lowercase());
+        }
+        words.push(word);
+    }
+    words.retain(|word| {
+        word.len() >= MIN_WORD_LENGTH
+            && word.chars().all(|c| c.is_ascii_alphabetic())
+            && !STOP_WORDS.contains(&word.as_str())
+    });
+    words
+}
+
+// the most counted first, ties in alphabetical order
+fn ranked(counts: BTreeMap<String, usize>) -> Vec<String> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    counts.into_iter().map(|(name, _)| name).collect()
+}
+
+/// The uncommitted changes of the branch `branch_id`, to name it after.
+pub fn list_branch_changes(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<BranchChanges, errors::ListBranchChangesError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::ListBranchChangesError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+    let statuses = super::get_status_by_branch(gb_repository, project_repository)
+        .context("failed to get status by branch")?;
+    let (branch, files) = statuses
+        .iter()
+        .find(|(branch, _)| branch.id == *branch_id)
+        .ok_or_else(|| {
+            errors::ListBranchChangesError::BranchNotFound(errors::BranchNotFoundError {
+                project_id: project_repository.project().id,
+                branch_id: *branch_id,
+            })
+        })?;
+    let files =
+        super::calculate_non_commited_diffs(project_repository, branch, &default_target, files)?;
+    Ok(files.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(diff: &str, change_type: ChangeType) -> diff::Hunk {
+        diff::Hunk {
+            old_start: 1,
+            old_lines: 1,
+            new_start: 1,
+            new_lines: 1,
+            diff: diff.to_string(),
+            binary: false,
+            generated: false,
+            change_type,
+        }
+    }
+
+    fn changes(files: &[(&str, &str, ChangeType)]) -> BranchChanges {
+        files
+            .iter()
+            .map(|(path, diff, change_type)| {
+                (path::PathBuf::from(path), vec![hunk(diff, *change_type)])
+            })
+            .collect::<HashMap<_, _>>()
+            .into()
+    }
+
+    #[test]
+    fn names_from_directories_and_words() {
+        let changes = changes(&[
+            (
+                "src/auth/login.rs",
+                "@@ -1 +1 @@\n-fn login() {}\n+fn login(redirect: Redirect) { redirectTo(redirect) }\n",
+                ChangeType::Modified,
+            ),
+            (
+                "src/auth/session.rs",
+                "@@ -1 +1 @@\n+let redirect = session.redirect;\n",
+                ChangeType::Modified,
+            ),
+        ]);
+        assert_eq!(
+            changes.suggest_names(),
+            vec!["update-auth", "auth-redirect", "update-redirect-login"]
+        );
+        // the same changes are named the same
+        assert_eq!(changes.suggest_names(), changes.suggest_names());
+    }
+
+    #[test]
+    fn names_of_added_files() {
+        let changes = changes(&[(
+            "README.md",
+            "@@ -0,0 +1 @@\n+Getting started\n",
+            ChangeType::Added,
+        )]);
+        assert_eq!(
+            changes.suggest_names(),
+            vec!["add-readme", "readme-getting", "add-getting-started"]
+        );
+        assert!(BranchChanges::default().suggest_names().is_empty());
+    }
+
+    #[test]
+    fn branch_names() {
+        assert_eq!(to_branch_name("Fix Login_redirect!"), "fix-login-redirect");
+        assert_eq!(to_branch_name("`add-tests`"), "add-tests");
+        assert_eq!(to_branch_name(&"word ".repeat(20)).len(), 49);
+    }
+}
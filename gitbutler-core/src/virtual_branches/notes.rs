@@ -0,0 +1,303 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    gb_repository,
+    git::{self, credentials},
+    project_repository::{self, NOTES_REFS, REMOTE_NOTES_REFS},
+    users,
+};
+
+use super::errors;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Note {
+    /// The notes ref the note is in, like `refs/notes/commits`.
+    pub notes_ref: String,
+    pub message: String,
+}
+
+/// The notes of the commit `commit_id`, one for every notes ref that has one, sorted by notes
+/// ref.
+pub fn list_notes(
+    project_repository: &project_repository::Repository,
+    commit_id: git::Oid,
+) -> Result<Vec<Note>, errors::ListNotesError> {
+    let repo = &project_repository.git_repository;
+    match repo.find_commit(commit_id) {
+        Ok(_) => {}
+        Err(git::Error::NotFound(_)) => {
+            return Err(errors::ListNotesError::CommitNotFound(commit_id));
+        }
+        Err(error) => return Err(errors::ListNotesError::Other(error.into())),
+    }
+
+    let mut notes = vec![];
+    for notes_ref in notes_refs(repo)? {
+        if let Some(message) = repo
+            .find_note(&notes_ref, commit_id)
+            .with_context(|| format!("failed to read note in {notes_ref}"))?
+        {
+            notes.push(Note { notes_ref, message });
+        }
+    }
+    Ok(notes)
+}
+
+/// Writes `message` as the note of the commit `commit_id` in `notes_ref`, the default notes
+/// ref when there is none, replacing the note the commit had there. An empty message removes
+/// the note.
+pub fn set_note(
+    project_repository: &project_repository::Repository,
+    commit_id: git::Oid,
+    notes_ref: Option<&str>,
+    message: &str,
+    user: Option<&users::User>,
+) -> Result<(), errors::SetNoteError> {
+    let repo = &project_repository.git_repository;
+    match repo.find_commit(commit_id) {
+        Ok(_) => {}
+        Err(git::Error::NotFound(_)) => {
+            return Err(errors::SetNoteError::CommitNotFound(commit_id));
+        }
+        Err(error) => return Err(errors::SetNoteError::Other(error.into())),
+    }
+    let notes_ref = match notes_ref {
+        Some(notes_ref) => {
+            if !notes_ref.starts_with(NOTES_REFS) || !git2::Reference::is_valid_name(notes_ref) {
+                return Err(errors::SetNoteError::InvalidNotesRef(notes_ref.to_string()));
+            }
+            notes_ref.to_string()
+        }
+        None => repo
+            .note_default_ref()
+            .context("failed to get default notes ref")?,
+    };
+
+    let (author, _) = project_repository.git_signatures(user)?;
+    if message.trim().is_empty() {
+        if repo
+            .find_note(&notes_ref, commit_id)
+            .context("failed to read note")?
+            .is_some()
+        {
+            repo.note_delete(&author, &notes_ref, commit_id)
+                .context("failed to delete note")?;
+        }
+    } else {
+        repo.note(&author, &notes_ref, commit_id, message)
+            .context("failed to write note")?;
+    }
+    Ok(())
+}
+
+/// Pushes every notes ref to `remote`, the remote of the target branch by default.
+pub fn push_notes(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    remote: Option<&str>,
+    credentials: &credentials::Helper,
+) -> Result<(), errors::SyncNotesError> {
+    let remote = remote_or_target(gb_repository, project_repository, remote)?;
+    let notes_refs = notes_refs(&project_repository.git_repository)?;
+    if notes_refs.is_empty() {
+        return Ok(());
+    }
+    project_repository
+        .push_notes(&remote, &notes_refs, credentials)
+        .map_err(errors::SyncNotesError::Remote)
+}
+
+/// Fetches the notes refs of `remote`, the remote of the target branch by default, and merges
+/// them into the local ones. Notes that were changed on both sides are kept one after the
+/// other, like `git notes merge --strategy=union` does.
+pub fn fetch_notes(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    remote: Option<&str>,
+    credentials: &credentials::Helper,
+    user: Option<&users::User>,
+) -> Result<(), errors::SyncNotesError> {
+    let remote = remote_or_target(gb_repository, project_repository, remote)?;
+    project_repository
+        .fetch_notes(&remote, credentials)
+        .map_err(errors::SyncNotesError::Remote)?;
+    merge_fetched_notes(project_repository, &remote, user)?;
+    Ok(())
+}
+
+// merges the notes refs fetched from `remote` into the local ones of the same name
+pub(super) fn merge_fetched_notes(
+    project_repository: &project_repository::Repository,
+    remote: &str,
+    user: Option<&users::User>,
+) -> Result<()> {
+    let repo = &project_repository.git_repository;
+    let fetched_refs = format!("{REMOTE_NOTES_REFS}{remote}/");
+    let fetched = repo
+        .references_glob(&format!("{fetched_refs}*"))
+        .context("failed to list fetched notes refs")?
+        .map(|reference| {
+            let reference = reference.context("failed to read fetched notes ref")?;
+            let name = String::from_utf8_lossy(reference.name_bytes()).to_string();
+            let id = reference
+                .target()
+                .context("fetched notes ref is symbolic")?;
+            Ok((name, id))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (_, committer) = project_repository.git_signatures(user)?;
+    for (name, theirs) in fetched {
+        let Some(notes_ref) = name.strip_prefix(&fetched_refs) else {
+            continue;
+        };
+        merge_notes(
+            repo,
+            &format!("{NOTES_REFS}{notes_ref}"),
+            theirs,
+            &committer,
+        )
+        .with_context(|| format!("failed to merge {name}"))?;
+    }
+    Ok(())
+}
+
+// merges the notes commit `theirs` into `notes_ref`, fast-forwarding it if it can
+fn merge_notes(
+    repo: &git::Repository,
+    notes_ref: &str,
+    theirs: git::Oid,
+    committer: &git::Signature,
+) -> Result<()> {
+    let refname = git::Refname::Other(notes_ref.to_string());
+    let ours = match repo.find_reference(&refname) {
+        Ok(reference) => reference.target(),
+        Err(git::Error::NotFound(_)) => None,
+        Err(error) => return Err(error).context("failed to find notes ref"),
+    };
+    let Some(ours) = ours else {
+        repo.reference(&refname, theirs, false, "notes: fetched")
+            .context("failed to write notes ref")?;
+        return Ok(());
+    };
+    if ours == theirs || repo.is_descendant_of(ours, theirs)? {
+        return Ok(());
+    }
+    if repo.is_descendant_of(theirs, ours)? {
+        repo.reference(&refname, theirs, true, "notes: fast-forward")
+            .context("failed to write notes ref")?;
+        return Ok(());
+    }
+
+    let raw: &git2::Repository = repo.into();
+    let notes_of = |commit: git::Oid| -> Result<HashMap<git::Oid, git2::Oid>> {
+        let tree = raw.find_commit(commit.into())?.tree()?;
+        let mut notes = HashMap::new();
+        // notes are named by the commit they annotate, split into fanout directories
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                let name = format!("{dir}{}", entry.name().unwrap_or_default()).replace('/', "");
+                if let Ok(annotated) = name.parse::<git::Oid>() {
+                    notes.insert(annotated, entry.id());
+                }
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+        Ok(notes)
+    };
+    let base = match repo.merge_base(ours, theirs) {
+        Ok(base) => notes_of(base)?,
+        Err(git::Error::NotFound(_)) => HashMap::new(),
+        Err(error) => return Err(error).context("failed to find merge base"),
+    };
+    let our_notes = notes_of(ours)?;
+    let their_notes = notes_of(theirs)?;
+
+    let mut builder = raw.treebuilder(None)?;
+    let annotated = our_notes
+        .keys()
+        .chain(their_notes.keys())
+        .collect::<BTreeSet<_>>();
+    for annotated in annotated {
+        let (base, ours, theirs) = (
+            base.get(annotated),
+            our_notes.get(annotated),
+            their_notes.get(annotated),
+        );
+        let merged = match (ours, theirs) {
+            (ours, theirs) if ours == theirs || theirs == base => ours.copied(),
+            (ours, theirs) if ours == base => theirs.copied(),
+            // changed on one side, removed on the other
+            (Some(note), None) | (None, Some(note)) => Some(*note),
+            (Some(ours), Some(theirs)) => {
+                let mut content = raw.find_blob(*ours)?.content().to_vec();
+                if !content.ends_with(b"\n") {
+                    content.push(b'\n');
+                }
+                content.push(b'\n');
+                content.extend_from_slice(raw.find_blob(*theirs)?.content());
+                Some(raw.blob(&content)?)
+            }
+            (None, None) => None,
+        };
+        if let Some(note) = merged {
+            builder.insert(annotated.to_string(), note, git2::FileMode::Blob.into())?;
+        }
+    }
+    let tree = raw.find_tree(builder.write()?)?;
+    let parents = [
+        raw.find_commit(ours.into())?,
+        raw.find_commit(theirs.into())?,
+    ];
+    let merged = raw.commit(
+        None,
+        committer.into(),
+        committer.into(),
+        "Notes merged by GitButler",
+        &tree,
+        &parents.iter().collect::<Vec<_>>(),
+    )?;
+    repo.reference(&refname, merged.into(), true, "notes: merged")
+        .context("failed to write notes ref")?;
+    Ok(())
+}
+
+fn remote_or_target(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    remote: Option<&str>,
+) -> Result<String, errors::SyncNotesError> {
+    match remote {
+        Some(remote) => Ok(remote.to_string()),
+        None => Ok(gb_repository
+            .default_target()
+            .context("failed to get default target")?
+            .ok_or_else(|| {
+                errors::SyncNotesError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                    project_id: project_repository.project().id,
+                })
+            })?
+            .branch
+            .remote()
+            .to_string()),
+    }
+}
+
+// the local notes refs, the ones fetched from remotes are only there to be merged into them
+fn notes_refs(repo: &git::Repository) -> Result<Vec<String>> {
+    let mut names = repo
+        .references_glob(&format!("{NOTES_REFS}*"))
+        .context("failed to list notes refs")?
+        .map(|reference| {
+            reference
+                .map(|reference| String::from_utf8_lossy(reference.name_bytes()).to_string())
+                .context("failed to read notes ref")
+        })
+        .collect::<Result<Vec<_>>>()?;
+    names.retain(|name| !name.starts_with(REMOTE_NOTES_REFS));
+    names.sort();
+    Ok(names)
+}
@@ -0,0 +1,381 @@
+//! The operation log of a project: a snapshot of its virtual branches and of its working
+//! directory is taken before every operation that changes them, so that what an operation
+//! did can be undone by restoring the snapshot taken before it.
+//!
+//! A snapshot is a commit of `refs/gitbutler-oplog/<timestamp>`, only the most recent
+//! [`SNAPSHOTS_KEPT`] of them are kept. Its tree has the working directory, untracked files
+//! included, under `workdir`, the uncommitted changes of every branch under `branches`, and
+//! the state of the branches and of the base branch in `virtual-branches.json`. Its parents
+//! are the commits that state refers to, which keeps them from being garbage collected.
+//!
+//! The working directory of a snapshot is the head commit with the changes to it, so only
+//! the files that changed are written, and never those of unmanaged paths, which operations
+//! leave alone. Restoring one checks out only the paths that differ from it.
+//!
+//! An operation that comes after one that changed nothing doesn't take a snapshot of its
+//! own, the last one already has the project as it is.
+
+use std::{collections::HashMap, path, time};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    gb_repository,
+    git::{self, diff},
+    project_repository,
+};
+
+use super::{
+    archive::ArchivedBranch,
+    branch, errors,
+    integration::{
+        self, GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL, GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+    },
+    recovery::read_branches,
+    target,
+};
+
+/// How many snapshots of a project are kept, the oldest ones are removed first.
+pub const SNAPSHOTS_KEPT: usize = 100;
+
+pub(super) const SNAPSHOT_REFS: &str = "refs/gitbutler-oplog/";
+const STATE_FILE: &str = "virtual-branches.json";
+const WORKDIR_TREE: &str = "workdir";
+const BRANCHES_TREE: &str = "branches";
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub id: git::Oid,
+    /// The operation the snapshot was taken before, the name of its command like
+    /// `create_commit`.
+    pub kind: String,
+    pub created_timestamp_ms: u128,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct State {
+    version: u32,
+    target: SnapshotTarget,
+    branches: Vec<ArchivedBranch>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotTarget {
+    branch: git::RemoteRefname,
+    remote_url: String,
+    sha: git::Oid,
+}
+
+/// The snapshots of the project, the most recent first, at most `limit` of them.
+pub fn list_snapshots(
+    project_repository: &project_repository::Repository,
+    limit: Option<usize>,
+) -> Result<Vec<Snapshot>, errors::SnapshotError> {
+    let repo = &project_repository.git_repository;
+    let mut snapshots = vec![];
+    for (created_timestamp_ms, _, id) in snapshot_references(repo)?
+        .into_iter()
+        .take(limit.unwrap_or(usize::MAX))
+    {
+        let commit = repo
+            .find_commit(id)
+            .context("failed to find snapshot commit")?;
+        snapshots.push(Snapshot {
+            id,
+            kind: commit.message().unwrap_or_default().trim().to_string(),
+            created_timestamp_ms,
+        });
+    }
+    Ok(snapshots)
+}
+
+/// What changed in the working directory since `snapshot_id` was taken, which is what
+/// restoring it undoes.
+pub fn snapshot_diff(
+    project_repository: &project_repository::Repository,
+    snapshot_id: git::Oid,
+) -> Result<HashMap<path::PathBuf, Vec<diff::Hunk>>, errors::SnapshotError> {
+    let repo = &project_repository.git_repository;
+    let tree = snapshot_tree(repo, snapshot_id)?;
+    let snapshot_workdir = workdir_of(repo, &tree)?;
+    let workdir = repo
+        .find_tree(workdir_tree(project_repository)?)
+        .context("failed to find working directory tree")?;
+    diff::trees(repo, &snapshot_workdir, &workdir)
+        .context("failed to diff snapshot")
+        .map_err(Into::into)
+}
+
+/// Puts the project back the way it was when `snapshot_id` was taken: its branches, its
+/// base branch and its working directory. The project is snapshotted first, so restoring
+/// can be undone too.
+pub fn restore_snapshot(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    snapshot_id: git::Oid,
+) -> Result<(), errors::SnapshotError> {
+    let repo = &project_repository.git_repository;
+    let tree = snapshot_tree(repo, snapshot_id)?;
+    let state = read_state(repo, &tree)?;
+    let workdir = workdir_of(repo, &tree)?;
+
+    // unlike before other operations, restoring without a way back isn't an option
+    take_snapshot(gb_repository, project_repository, "restore_snapshot")
+        .context("failed to take a snapshot before restoring")?;
+
+    target::Writer::new(gb_repository)
+        .context("failed to create target writer")?
+        .write_default(&target::Target {
+            branch: state.target.branch,
+            remote_url: state.target.remote_url,
+            sha: state.target.sha,
+        })
+        .context("failed to write default target")?;
+
+    let writer = branch::Writer::new(gb_repository).context("failed to create branch writer")?;
+    for branch in read_branches(gb_repository)? {
+        if state
+            .branches
+            .iter()
+            .any(|archived| archived.id == branch.id)
+        {
+            continue;
+        }
+        writer.delete(&branch).context("failed to delete branch")?;
+        project_repository.delete_branch_reference(&branch)?;
+    }
+    for archived in state.branches {
+        let mut branch = branch::Branch::from(archived);
+        writer
+            .write(&mut branch)
+            .context("failed to write branch")?;
+    }
+
+    // the files that were added since are removed, the rest of the working directory,
+    // unmanaged paths and what's ignored, is left as it is
+    let unmanaged = project_repository.project().unmanaged();
+    let current = repo
+        .find_tree(workdir_tree(project_repository)?)
+        .context("failed to find working directory tree")?;
+    let mut paths = diff::trees(repo, &current, &workdir)
+        .context("failed to diff snapshot")?
+        .into_iter()
+        .flat_map(|(path, hunks)| {
+            let renamed_from = hunks.iter().find_map(diff::Hunk::renamed_from);
+            std::iter::once(path).chain(renamed_from)
+        })
+        .filter(|path| !unmanaged.contains(path))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+    if !paths.is_empty() {
+        repo.checkout_tree(&workdir)
+            .force()
+            .remove_untracked()
+            .paths(paths.iter().map(path::PathBuf::as_path))
+            .checkout()
+            .context("failed to checkout snapshot")?;
+    }
+    integration::update_gitbutler_integration(gb_repository, project_repository)?;
+    Ok(())
+}
+
+/// Takes a snapshot of the project before the operation `kind`, returning it unless nothing
+/// changed since the last one. Projects without a base branch have nothing to snapshot.
+pub(super) fn take_snapshot(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    kind: &str,
+) -> Result<Option<git::Oid>> {
+    let Some(target) = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+    else {
+        return Ok(None);
+    };
+    let repo = &project_repository.git_repository;
+    let branches = read_branches(gb_repository)?;
+
+    let mut branches_builder = repo.treebuilder(None);
+    for branch in &branches {
+        branches_builder.upsert(branch.id.to_string(), branch.tree, git::FileMode::Tree);
+    }
+    let state = State {
+        version: SNAPSHOT_VERSION,
+        target: SnapshotTarget {
+            branch: target.branch,
+            remote_url: target.remote_url,
+            sha: target.sha,
+        },
+        branches: branches.iter().map(Into::into).collect(),
+    };
+    let state = repo
+        .blob(&serde_json::to_vec_pretty(&state).context("failed to serialize state")?)
+        .context("failed to write state")?;
+    let mut builder = repo.treebuilder(None);
+    builder.upsert(
+        WORKDIR_TREE,
+        workdir_tree(project_repository)?,
+        git::FileMode::Tree,
+    );
+    builder.upsert(
+        BRANCHES_TREE,
+        branches_builder
+            .write()
+            .context("failed to write branches tree")?,
+        git::FileMode::Tree,
+    );
+    builder.upsert(STATE_FILE, state, git::FileMode::Blob);
+    let tree = builder.write().context("failed to write snapshot tree")?;
+
+    let references = snapshot_references(repo)?;
+    if let Some((_, _, latest)) = references.first() {
+        let latest_tree = repo
+            .find_commit(*latest)
+            .and_then(|commit| commit.tree())
+            .context("failed to find latest snapshot")?;
+        if latest_tree.id() == tree {
+            return Ok(None);
+        }
+    }
+
+    let tree = repo
+        .find_tree(tree)
+        .context("failed to find snapshot tree")?;
+    let mut parents = vec![target.sha];
+    for branch in &branches {
+        if !parents.contains(&branch.head) {
+            parents.push(branch.head);
+        }
+    }
+    let parents = parents
+        .into_iter()
+        .map(|parent| repo.find_commit(parent))
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to find snapshot parents")?;
+    let committer = git::Signature::now(
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL,
+    )
+    .context("failed to create signature")?;
+    let snapshot_id = repo
+        .commit(
+            None,
+            &committer,
+            &committer,
+            kind,
+            &tree,
+            &parents.iter().collect::<Vec<_>>(),
+        )
+        .context("failed to commit snapshot")?;
+
+    // the reference is named after when the snapshot was taken, unique even within a
+    // millisecond
+    let mut timestamp_ms = time::UNIX_EPOCH
+        .elapsed()
+        .context("failed to get elapsed time")?
+        .as_millis();
+    if let Some((latest_ms, _, _)) = references.first() {
+        timestamp_ms = timestamp_ms.max(latest_ms + 1);
+    }
+    repo.reference(
+        &git::Refname::Other(format!("{SNAPSHOT_REFS}{timestamp_ms}")),
+        snapshot_id,
+        false,
+        kind,
+    )
+    .context("failed to write snapshot reference")?;
+
+    for (_, refname, _) in references.iter().skip(SNAPSHOTS_KEPT - 1) {
+        if let Err(error) = repo
+            .find_reference(refname)
+            .and_then(|mut reference| reference.delete())
+        {
+            tracing::warn!(?error, %refname, "failed to delete old snapshot");
+        }
+    }
+    Ok(Some(snapshot_id))
+}
+
+// the references of the snapshots with when they were taken, the most recent first
+fn snapshot_references(repo: &git::Repository) -> Result<Vec<(u128, git::Refname, git::Oid)>> {
+    let mut references = vec![];
+    for reference in repo
+        .references_glob(&format!("{SNAPSHOT_REFS}*"))
+        .context("failed to list snapshots")?
+    {
+        let reference = reference.context("failed to read snapshot reference")?;
+        let (Some(refname), Some(id)) = (reference.name(), reference.target()) else {
+            continue;
+        };
+        let Some(timestamp_ms) = refname
+            .to_string()
+            .strip_prefix(SNAPSHOT_REFS)
+            .and_then(|timestamp_ms| timestamp_ms.parse::<u128>().ok())
+        else {
+            continue;
+        };
+        references.push((timestamp_ms, refname, id));
+    }
+    references.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(references)
+}
+
+fn snapshot_tree(
+    repo: &git::Repository,
+    snapshot_id: git::Oid,
+) -> Result<git::Tree<'_>, errors::SnapshotError> {
+    if !snapshot_references(repo)?
+        .iter()
+        .any(|(_, _, id)| *id == snapshot_id)
+    {
+        return Err(errors::SnapshotError::SnapshotNotFound(snapshot_id));
+    }
+    repo.find_commit(snapshot_id)
+        .and_then(|commit| commit.tree())
+        .context("failed to find snapshot tree")
+        .map_err(Into::into)
+}
+
+fn workdir_of<'repo>(repo: &'repo git::Repository, tree: &git::Tree) -> Result<git::Tree<'repo>> {
+    let entry = tree
+        .get_name(WORKDIR_TREE)
+        .context("snapshot has no working directory")?;
+    repo.find_tree(entry.id())
+        .context("failed to find snapshot working directory")
+}
+
+fn read_state(repo: &git::Repository, tree: &git::Tree) -> Result<State, errors::SnapshotError> {
+    let entry = tree.get_name(STATE_FILE).context("snapshot has no state")?;
+    let blob = repo
+        .find_blob(entry.id())
+        .context("failed to find snapshot state")?;
+    let state: State =
+        serde_json::from_slice(blob.content()).context("failed to parse snapshot state")?;
+    if state.version > SNAPSHOT_VERSION {
+        return Err(errors::SnapshotError::NewerVersion);
+    }
+    Ok(state)
+}
+
+// the working directory as a tree, with its untracked files but without its unmanaged paths
+fn workdir_tree(project_repository: &project_repository::Repository) -> Result<git::Oid> {
+    let repo = &project_repository.git_repository;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .context("failed to get head commit")?;
+    let options = diff::Options {
+        unmanaged: project_repository.project().unmanaged(),
+        ..Default::default()
+    };
+    let files = diff::workdir_with_progress(repo, &head.id(), &options, &|_, _| {})
+        .context("failed to diff working directory")?;
+    super::write_tree_onto_commit(project_repository, head.id(), &files)
+        .context("failed to write working directory tree")
+}
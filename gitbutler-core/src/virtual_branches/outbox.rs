@@ -0,0 +1,374 @@
+//! Pushes and pull requests that can't be made while offline are queued in the outbox of the
+//! project, and replayed in the order they were queued once the network is back.
+//!
+//! Replaying a push checks that it still pushes what was queued: the branch can have more
+//! commits on top, but one that was rewritten since, or whose remote branch got commits it
+//! doesn't have, isn't pushed unless the push is forced. Pull requests are created by the
+//! frontend, replaying hands over those whose branch is pushed, and they are left in the
+//! outbox until the frontend created them. Replaying again meanwhile doesn't hand them over
+//! twice, unless the first one wasn't created after [`HAND_OVER_TIMEOUT`], like because no
+//! window was open to create it.
+
+use std::{fs, io, time};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::CorruptStateError, gb_repository, git, id::Id, project_repository, reader, sessions,
+};
+
+use super::{branch, errors, BranchId, PrePushProgress};
+
+pub type OutboxEntryId = Id<OutboxEntry>;
+
+const OUTBOX_FILE: &str = "outbox.json";
+
+/// How long a pull request handed over for creating isn't handed over again.
+pub const HAND_OVER_TIMEOUT: time::Duration = time::Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxEntry {
+    pub id: OutboxEntryId,
+    pub queued_timestamp_ms: u128,
+    pub branch_id: BranchId,
+    pub operation: OutgoingOperation,
+    /// When the pull request was last handed over for creating.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handed_over_timestamp_ms: Option<u128>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum OutgoingOperation {
+    #[serde(rename_all = "camelCase")]
+    Push {
+        with_force: bool,
+        /// The head of the branch when the push was queued.
+        head: git::Oid,
+    },
+    #[serde(rename_all = "camelCase")]
+    PullRequest {
+        title: String,
+        body: String,
+        draft: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ReplayOutcome {
+    Pushed,
+    /// A pull request whose branch is pushed, for the frontend to create and then take out
+    /// of the outbox.
+    Ready,
+    /// A pull request that was handed over for creating, and may still be being created.
+    HandedOver,
+    /// A pull request of a branch whose push is still in the outbox.
+    Waiting,
+    /// Still offline, the entry is left in the outbox.
+    Offline,
+    /// Replaying it wouldn't do what queueing it meant to, it's left in the outbox.
+    #[serde(rename_all = "camelCase")]
+    Conflict {
+        reason: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Replay {
+    pub entry: OutboxEntry,
+    pub outcome: ReplayOutcome,
+}
+
+fn read_entries(gb_repository: &gb_repository::Repository) -> Result<Vec<OutboxEntry>> {
+    match fs::read(gb_repository.root().join(OUTBOX_FILE)) {
+        Ok(entries) => serde_json::from_slice(&entries)
+            .map_err(CorruptStateError::of(OUTBOX_FILE))
+            .context("malformed outbox"),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(error) => Err(error).context("failed to read outbox"),
+    }
+}
+
+// the outbox is written to a temporary file that then replaces it, so that it's never left
+// half written
+fn write_entries(gb_repository: &gb_repository::Repository, entries: &[OutboxEntry]) -> Result<()> {
+    let path = gb_repository.root().join(OUTBOX_FILE);
+    let temporary = path.with_extension("json.tmp");
+    fs::write(
+        &temporary,
+        serde_json::to_vec(entries).context("failed to serialize outbox")?,
+    )
+    .context("failed to write outbox")?;
+    fs::rename(&temporary, &path).context("failed to replace outbox")
+}
+
+fn now_ms() -> Result<u128> {
+    Ok(time::UNIX_EPOCH
+        .elapsed()
+        .context("failed to get elapsed time")?
+        .as_millis())
+}
+
+fn read_branch(
+    gb_repository: &gb_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<Option<branch::Branch>> {
+    let session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let session_reader = sessions::Reader::open(gb_repository, &session)
+        .context("failed to open current session")?;
+    match branch::Reader::new(&session_reader).read(branch_id) {
+        Ok(branch) => Ok(Some(branch)),
+        Err(reader::Error::NotFound) => Ok(None),
+        Err(error) => Err(error).context("failed to read branch"),
+    }
+}
+
+/// The pushes and pull requests waiting for the network, in the order they were queued.
+pub fn list_outbox(
+    gb_repository: &gb_repository::Repository,
+) -> Result<Vec<OutboxEntry>, errors::OutboxError> {
+    read_entries(gb_repository).map_err(Into::into)
+}
+
+/// Queues a push of the branch `branch_id` as it is now, to be replayed with
+/// [`replay_outbox`].
+pub fn queue_push(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    with_force: bool,
+) -> Result<OutboxEntry, errors::OutboxError> {
+    let branch = find_branch(gb_repository, project_repository, branch_id)?;
+    queue(
+        gb_repository,
+        branch_id,
+        OutgoingOperation::Push {
+            with_force,
+            head: branch.head,
+        },
+    )
+}
+
+/// Queues the creation of a pull request of the branch `branch_id`, handed over to the
+/// frontend by [`replay_outbox`] once the branch is pushed.
+pub fn queue_pull_request(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    title: String,
+    body: String,
+    draft: bool,
+) -> Result<OutboxEntry, errors::OutboxError> {
+    find_branch(gb_repository, project_repository, branch_id)?;
+    queue(
+        gb_repository,
+        branch_id,
+        OutgoingOperation::PullRequest { title, body, draft },
+    )
+}
+
+fn find_branch(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<branch::Branch, errors::OutboxError> {
+    read_branch(gb_repository, branch_id)?.ok_or_else(|| {
+        errors::OutboxError::BranchNotFound(errors::BranchNotFoundError {
+            project_id: project_repository.project().id,
+            branch_id: *branch_id,
+        })
+    })
+}
+
+fn queue(
+    gb_repository: &gb_repository::Repository,
+    branch_id: &BranchId,
+    operation: OutgoingOperation,
+) -> Result<OutboxEntry, errors::OutboxError> {
+    let entry = OutboxEntry {
+        id: OutboxEntryId::generate(),
+        queued_timestamp_ms: now_ms()?,
+        branch_id: *branch_id,
+        operation,
+        handed_over_timestamp_ms: None,
+    };
+    let mut entries = read_entries(gb_repository)?;
+    entries.push(entry.clone());
+    write_entries(gb_repository, &entries)?;
+    Ok(entry)
+}
+
+/// Takes the entry `entry_id` out of the outbox, like a pull request the frontend created.
+pub fn remove_outbox_entry(
+    gb_repository: &gb_repository::Repository,
+    entry_id: &OutboxEntryId,
+) -> Result<(), errors::OutboxError> {
+    let mut entries = read_entries(gb_repository)?;
+    let len = entries.len();
+    entries.retain(|entry| entry.id != *entry_id);
+    if entries.len() == len {
+        return Err(errors::OutboxError::EntryNotFound(*entry_id));
+    }
+    write_entries(gb_repository, &entries).map_err(Into::into)
+}
+
+/// Replays the outbox in order, see the [module docs](self). What's pushed is taken out of
+/// it, the rest is left for later. Replaying stops at the first entry that finds the network
+/// still down.
+pub fn replay_outbox(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    credentials: &git::credentials::Helper,
+) -> Result<Vec<Replay>, errors::OutboxError> {
+    let entries = read_entries(gb_repository)?;
+    let now = now_ms()?;
+    let mut replays: Vec<Replay> = Vec::with_capacity(entries.len());
+    let mut fetched_remotes = vec![];
+    let mut cancelled = false;
+    for mut entry in entries {
+        let offline = replays
+            .last()
+            .is_some_and(|replay| replay.outcome == ReplayOutcome::Offline);
+        let outcome = match &entry.operation {
+            _ if offline => ReplayOutcome::Offline,
+            OutgoingOperation::Push { with_force, head } => match replay_push(
+                gb_repository,
+                project_repository,
+                credentials,
+                &entry.branch_id,
+                *with_force,
+                *head,
+                &mut fetched_remotes,
+            ) {
+                Ok(outcome) => outcome,
+                Err(errors::OutboxError::Remote(project_repository::RemoteError::Cancelled)) => {
+                    cancelled = true;
+                    break;
+                }
+                Err(error) => return Err(error),
+            },
+            OutgoingOperation::PullRequest { .. } => {
+                if replays.iter().any(|replay| {
+                    replay.entry.branch_id == entry.branch_id
+                        && replay.outcome != ReplayOutcome::Pushed
+                        && matches!(replay.entry.operation, OutgoingOperation::Push { .. })
+                }) {
+                    ReplayOutcome::Waiting
+                } else if entry.handed_over_timestamp_ms.is_some_and(|handed_over| {
+                    now.saturating_sub(handed_over) < HAND_OVER_TIMEOUT.as_millis()
+                }) {
+                    ReplayOutcome::HandedOver
+                } else {
+                    entry.handed_over_timestamp_ms = Some(now);
+                    ReplayOutcome::Ready
+                }
+            }
+        };
+        replays.push(Replay { entry, outcome });
+    }
+
+    let left = replays
+        .iter()
+        .filter(|replay| replay.outcome != ReplayOutcome::Pushed)
+        .map(|replay| replay.entry.clone())
+        .collect::<Vec<_>>();
+    // what wasn't replayed, because replaying was cancelled or it was queued meanwhile, is
+    // kept as it is
+    let not_replayed = read_entries(gb_repository)?
+        .into_iter()
+        .filter(|entry| !replays.iter().any(|replay| replay.entry.id == entry.id));
+    write_entries(
+        gb_repository,
+        &left.into_iter().chain(not_replayed).collect::<Vec<_>>(),
+    )?;
+    if cancelled {
+        return Err(project_repository::RemoteError::Cancelled.into());
+    }
+    Ok(replays)
+}
+
+fn replay_push(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    credentials: &git::credentials::Helper,
+    branch_id: &BranchId,
+    with_force: bool,
+    queued_head: git::Oid,
+    fetched_remotes: &mut Vec<String>,
+) -> Result<ReplayOutcome, errors::OutboxError> {
+    let conflict = |reason: &str| -> Result<_, errors::OutboxError> {
+        Ok(ReplayOutcome::Conflict {
+            reason: reason.to_string(),
+        })
+    };
+    let Some(branch) = read_branch(gb_repository, branch_id)? else {
+        return conflict("the branch was deleted");
+    };
+    let repo = &project_repository.git_repository;
+    if !with_force
+        && branch.head != queued_head
+        && !repo
+            .is_descendant_of(branch.head, queued_head)
+            .context("failed to compare heads")?
+    {
+        return conflict("the branch was rewritten since the push was queued");
+    }
+
+    // the remote branch is fetched first, to find out whether the network is back and
+    // whether someone else pushed to it meanwhile
+    if let Some(upstream) = &branch.upstream {
+        let remote = upstream.remote().to_string();
+        if !fetched_remotes.contains(&remote) {
+            match project_repository.fetch(&remote, credentials) {
+                Ok(()) => fetched_remotes.push(remote),
+                Err(project_repository::RemoteError::Network) => return Ok(ReplayOutcome::Offline),
+                Err(error @ project_repository::RemoteError::Cancelled) => return Err(error.into()),
+                Err(error) => {
+                    return Ok(ReplayOutcome::Failed {
+                        error: error.to_string(),
+                    })
+                }
+            }
+        }
+        if let Ok(upstream_head) = repo.refname_to_id(&upstream.to_string()) {
+            if !with_force
+                && upstream_head != branch.head
+                && !repo
+                    .is_descendant_of(branch.head, upstream_head)
+                    .context("failed to compare with the remote branch")?
+            {
+                return conflict("the remote branch has commits the branch doesn't have");
+            }
+        }
+    }
+
+    match super::push(
+        project_repository,
+        gb_repository,
+        branch_id,
+        with_force,
+        credentials,
+        &PrePushProgress::default(),
+    ) {
+        Ok(()) => Ok(ReplayOutcome::Pushed),
+        Err(errors::PushError::Remote(project_repository::RemoteError::Network)) => {
+            Ok(ReplayOutcome::Offline)
+        }
+        Err(errors::PushError::Remote(error @ project_repository::RemoteError::Cancelled)) => {
+            Err(error.into())
+        }
+        Err(error) => Ok(ReplayOutcome::Failed {
+            error: error.to_string(),
+        }),
+    }
+}
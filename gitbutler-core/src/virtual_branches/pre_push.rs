@@ -0,0 +1,459 @@
+//! The checks a branch has to pass before it's pushed: the `gitbutler-pre-push` hook of the
+//! project, the command of the `gitbutler.prePushCommand` git config, like
+//! `cargo test --quick`, and the naming policy of the `gitbutler.branchNamePattern` regex.
+//! All of them run, also after one failed, so that every problem is reported at once, and
+//! any failure blocks the push.
+//!
+//! The command runs in a worktree of its own, at the commit that is pushed, so that it
+//! tests what is pushed rather than the working directory, and doesn't change it. It is
+//! removed once the command is done.
+//!
+//! The hook and the command fail once they ran for longer than the
+//! `gitbutler.prePushTimeout` git config, in seconds, [`DEFAULT_TIMEOUT`] if it isn't set,
+//! and they are stopped when the push is cancelled, along with what they started.
+//!
+//! What the hook and the command write is collected in a [`PrePushProgress`] as they go,
+//! for the ui to show while the checks run.
+
+use std::{
+    ffi, fs,
+    io::{BufRead, BufReader, Read},
+    path,
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread, time,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    git,
+    project_repository::{self, hooks, RemoteError},
+};
+
+use super::{branch, errors};
+
+/// How long the hook and the command may run when `gitbutler.prePushTimeout` isn't set.
+const DEFAULT_TIMEOUT: time::Duration = time::Duration::from_secs(10 * 60);
+
+// how often a running check looks at whether it timed out or was cancelled
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PrePushCheck {
+    Hook,
+    Command,
+    BranchName,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// The project doesn't have the check.
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub check: PrePushCheck,
+    pub status: CheckStatus,
+    pub output: String,
+    pub duration_ms: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrePushReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl PrePushReport {
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Failed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputLine {
+    pub check: PrePushCheck,
+    pub line: String,
+}
+
+/// The output of the checks that are running.
+#[derive(Debug, Clone, Default)]
+pub struct PrePushProgress {
+    lines: Arc<Mutex<Vec<OutputLine>>>,
+}
+
+impl PrePushProgress {
+    fn push(&self, check: PrePushCheck, line: String) {
+        self.lines.lock().unwrap().push(OutputLine { check, line });
+    }
+
+    /// The lines written since they were last taken.
+    pub fn take(&self) -> Vec<OutputLine> {
+        std::mem::take(&mut *self.lines.lock().unwrap())
+    }
+}
+
+/// Runs the checks of pushing `branch` to `remote_branch`, failing with
+/// [`RemoteError::Cancelled`] if the push is cancelled while they run.
+pub(super) fn run(
+    project_repository: &project_repository::Repository,
+    branch: &branch::Branch,
+    remote_branch: &git::RemoteRefname,
+    force: bool,
+    progress: &PrePushProgress,
+) -> Result<PrePushReport, errors::PushError> {
+    let timeout = project_repository
+        .config()
+        .pre_push_timeout()
+        .context("failed to read pre-push timeout config")?
+        .and_then(|seconds| u64::try_from(seconds).ok())
+        .map_or(DEFAULT_TIMEOUT, time::Duration::from_secs);
+    let limits = Limits {
+        timeout,
+        cancel: project_repository.cancellation().token(),
+    };
+    let mut checks = vec![timed(PrePushCheck::Hook, &limits, || {
+        hook(
+            project_repository,
+            branch,
+            remote_branch,
+            force,
+            &limits,
+            progress,
+        )
+    })?];
+    checks.push(timed(PrePushCheck::Command, &limits, || {
+        command(project_repository, branch, remote_branch, &limits, progress)
+    })?);
+    checks.push(timed(PrePushCheck::BranchName, &limits, || {
+        branch_name(project_repository, remote_branch, progress)
+    })?);
+    Ok(PrePushReport { checks })
+}
+
+// how long the hook and the command may run, and what stops them early
+struct Limits<'a> {
+    timeout: time::Duration,
+    cancel: &'a CancellationToken,
+}
+
+fn branch_name(
+    project_repository: &project_repository::Repository,
+    remote_branch: &git::RemoteRefname,
+    progress: &PrePushProgress,
+) -> Result<(CheckStatus, String)> {
+    let Some(pattern) = project_repository
+        .config()
+        .branch_name_pattern()
+        .context("failed to read branch name pattern config")?
+    else {
+        return Ok((CheckStatus::Skipped, String::new()));
+    };
+    let (status, output) = match regex::Regex::new(&pattern) {
+        Ok(regex) if regex.is_match(remote_branch.branch()) => {
+            return Ok((CheckStatus::Passed, String::new()))
+        }
+        Ok(_) => (
+            CheckStatus::Failed,
+            format!("{} doesn't match {pattern}", remote_branch.branch()),
+        ),
+        Err(error) => (
+            CheckStatus::Failed,
+            format!("gitbutler.branchNamePattern is not a regex: {error}"),
+        ),
+    };
+    progress.push(PrePushCheck::BranchName, output.clone());
+    Ok((status, output))
+}
+
+// runs the check `f`, unless the push was cancelled
+fn timed(
+    check: PrePushCheck,
+    limits: &Limits,
+    f: impl FnOnce() -> Result<(CheckStatus, String)>,
+) -> Result<CheckResult, errors::PushError> {
+    if limits.cancel.is_cancelled() {
+        return Err(RemoteError::Cancelled.into());
+    }
+    let started_at = time::Instant::now();
+    let (status, output) = f()?;
+    if limits.cancel.is_cancelled() {
+        return Err(RemoteError::Cancelled.into());
+    }
+    Ok(CheckResult {
+        check,
+        status,
+        output,
+        duration_ms: started_at.elapsed().as_millis(),
+    })
+}
+
+fn hook(
+    project_repository: &project_repository::Repository,
+    branch: &branch::Branch,
+    remote_branch: &git::RemoteRefname,
+    force: bool,
+    limits: &Limits,
+    progress: &PrePushProgress,
+) -> Result<(CheckStatus, String)> {
+    let event = hooks::Event::PrePush {
+        project_id: project_repository.project().id,
+        branch: hooks::Branch {
+            id: branch.id,
+            name: branch.name.clone(),
+        },
+        remote_branch: remote_branch.clone(),
+        force,
+    };
+    let Some((command, payload)) = hooks::command(project_repository, &event)? else {
+        return Ok((CheckStatus::Skipped, String::new()));
+    };
+    run_streamed(
+        command,
+        Some(&payload),
+        PrePushCheck::Hook,
+        limits,
+        progress,
+    )
+    .with_context(|| format!("failed to run the {} hook", event.name()))
+}
+
+fn command(
+    project_repository: &project_repository::Repository,
+    branch: &branch::Branch,
+    remote_branch: &git::RemoteRefname,
+    limits: &Limits,
+    progress: &PrePushProgress,
+) -> Result<(CheckStatus, String)> {
+    let Some(script) = project_repository
+        .config()
+        .pre_push_command()
+        .context("failed to read pre-push command config")?
+        .filter(|script| !script.trim().is_empty())
+    else {
+        return Ok((CheckStatus::Skipped, String::new()));
+    };
+    let worktree = Worktree::add(project_repository, branch)?;
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    command
+        .arg(&script)
+        .current_dir(&worktree.path)
+        .env("GITBUTLER_BRANCH", &branch.name)
+        .env("GITBUTLER_REMOTE_BRANCH", remote_branch.to_string());
+    run_streamed(command, None, PrePushCheck::Command, limits, progress)
+        .with_context(|| format!("failed to run {script}"))
+}
+
+/// A detached worktree of the project at the head of a branch, removed when dropped. Made
+/// with `git` rather than libgit2 for the checkout to go through the filters and the LFS
+/// of the project, like the one of the working directory does.
+struct Worktree {
+    // the working directory of the project, where `git worktree` is run
+    project_path: path::PathBuf,
+    path: path::PathBuf,
+}
+
+impl Worktree {
+    fn add(
+        project_repository: &project_repository::Repository,
+        branch: &branch::Branch,
+    ) -> Result<Self> {
+        let project_path = project_repository.path();
+        let path = project_repository
+            .git_repository
+            .path()
+            .join("gitbutler")
+            .join("pre-push")
+            .join(branch.id.to_string());
+        // left over by a push that was killed while its checks ran
+        if path.exists() {
+            remove_worktree(project_path, &path);
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("failed to create worktrees directory")?;
+        }
+        git(
+            project_path,
+            &[
+                ffi::OsStr::new("worktree"),
+                ffi::OsStr::new("add"),
+                ffi::OsStr::new("--detach"),
+                ffi::OsStr::new("--force"),
+                path.as_os_str(),
+                ffi::OsStr::new(&branch.head.to_string()),
+            ],
+        )
+        .context("failed to add pre-push worktree")?;
+        Ok(Self {
+            project_path: project_path.to_path_buf(),
+            path,
+        })
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        remove_worktree(&self.project_path, &self.path);
+    }
+}
+
+fn remove_worktree(project_path: &path::Path, path: &path::Path) {
+    let removed = git(
+        project_path,
+        &[
+            ffi::OsStr::new("worktree"),
+            ffi::OsStr::new("remove"),
+            ffi::OsStr::new("--force"),
+            path.as_os_str(),
+        ],
+    );
+    if let Err(error) = removed {
+        tracing::warn!(?error, path = %path.display(), "failed to remove pre-push worktree");
+        // still removed, for its name to be free the next time
+        let _ = fs::remove_dir_all(path);
+        let _ = git(
+            project_path,
+            &[ffi::OsStr::new("worktree"), ffi::OsStr::new("prune")],
+        );
+    }
+}
+
+fn git(project_path: &path::Path, args: &[&ffi::OsStr]) -> Result<()> {
+    let output = Command::new("git")
+        .current_dir(project_path)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run git, is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+// runs `command`, collecting what it writes to stdout and stderr in the order it's written.
+// it's killed once it ran for longer than the timeout or is cancelled, which fails the check
+fn run_streamed(
+    mut command: Command,
+    payload: Option<&[u8]>,
+    check: PrePushCheck,
+    limits: &Limits,
+    progress: &PrePushProgress,
+) -> Result<(CheckStatus, String)> {
+    let mut child = hooks::own_group(&mut command)
+        .stdin(if payload.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to spawn")?;
+    if let (Some(mut stdin), Some(payload)) = (child.stdin.take(), payload) {
+        hooks::write_payload(&mut stdin, payload)?;
+    }
+
+    let output = Arc::new(Mutex::new(String::new()));
+    let readers = [
+        child
+            .stdout
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|pipe| {
+        let (progress, output) = (progress.clone(), Arc::clone(&output));
+        thread::spawn(move || read_lines(pipe, check, &progress, &output))
+    })
+    .collect::<Vec<_>>();
+
+    let started_at = time::Instant::now();
+    let stopped = loop {
+        if let Some(status) = child.try_wait().context("failed to wait")? {
+            break Ok(status);
+        }
+        let reason = if limits.cancel.is_cancelled() {
+            "cancelled".to_string()
+        } else if started_at.elapsed() >= limits.timeout {
+            format!("timed out after {}s", limits.timeout.as_secs())
+        } else {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        };
+        hooks::kill_group(&mut child)?;
+        break Err(reason);
+    };
+    // the pipes are closed once the group is gone, what it started included
+    for reader in readers {
+        let _ = reader.join();
+    }
+
+    let mut output = output.lock().unwrap().trim_end().to_string();
+    match stopped {
+        Ok(status) if status.success() => Ok((CheckStatus::Passed, output)),
+        Ok(_) => Ok((CheckStatus::Failed, output)),
+        Err(stopped) => {
+            progress.push(check, stopped.clone());
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            output.push_str(&stopped);
+            Ok((CheckStatus::Failed, output))
+        }
+    }
+}
+
+fn read_lines(
+    pipe: impl Read,
+    check: PrePushCheck,
+    progress: &PrePushProgress,
+    output: &Mutex<String>,
+) {
+    for line in BufReader::new(pipe).split(b'\n') {
+        let Ok(line) = line else {
+            break;
+        };
+        let line = String::from_utf8_lossy(&line)
+            .trim_end_matches('\r')
+            .to_string();
+        {
+            let mut output = output.lock().unwrap();
+            output.push_str(&line);
+            output.push('\n');
+        }
+        progress.push(check, line);
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use serde::Serialize;
+
+use super::VirtualBranch;
+
+/// Progress of a status computation, shared between the thread computing the status
+/// and whoever is reporting it.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    scanned: Arc<AtomicUsize>,
+    total: Arc<AtomicUsize>,
+    // the branches that are done, for whoever can't wait for all of them
+    done: Arc<Mutex<Vec<VirtualBranch>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressSnapshot {
+    pub files_scanned: usize,
+    pub files_total: usize,
+}
+
+impl Progress {
+    pub fn set(&self, scanned: usize, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+        self.scanned.store(scanned, Ordering::Relaxed);
+    }
+
+    /// Tells that `branch` is done, before the locks and the context of its hunks are.
+    pub fn branch_done(&self, branch: &VirtualBranch) {
+        self.done.lock().unwrap().push(branch.clone());
+    }
+
+    /// The branches that are done so far, see [`Progress::branch_done`].
+    pub fn done_branches(&self) -> Vec<VirtualBranch> {
+        self.done.lock().unwrap().clone()
+    }
+
+    pub fn done_count(&self) -> usize {
+        self.done.lock().unwrap().len()
+    }
+
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            files_scanned: self.scanned.load(Ordering::Relaxed),
+            files_total: self.total.load(Ordering::Relaxed),
+        }
+    }
+}
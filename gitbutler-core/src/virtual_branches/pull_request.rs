@@ -0,0 +1,244 @@
+//! What a model is given to write the title and description of the pull request of a branch:
+//! the messages of its commits, a compacted summary of what they change together, and the
+//! template the description follows.
+
+use std::{collections::HashMap, fs, path};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::{
+    gb_repository,
+    git::diff::{self, ChangeType},
+    project_repository::{self, LogUntil},
+    reader, sessions,
+};
+
+use super::{branch, errors, BranchId};
+
+// where forges look for the template of pull requests, in the order GitHub does
+const TEMPLATE_PATHS: &[&str] = &[
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "PULL_REQUEST_TEMPLATE.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+];
+const DEFAULT_TEMPLATE: &str = "## Summary\n\n<what changes and why>\n\n## Changes\n\n<the main \
+changes, as a list>\n";
+// enough for a model to see what changed, not so much it can't see the commits for the diff
+const MAX_SUMMARY_LENGTH: usize = 6000;
+const DIFF_LEFT_OUT: &str = "\n(the rest of the diff is left out)\n";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestContext {
+    pub branch_name: String,
+    /// The messages of the commits of the branch, the oldest first.
+    pub commit_messages: Vec<String>,
+    /// The files the commits change, and the lines they add and remove without context, cut
+    /// short when there are too many.
+    pub diff_summary: String,
+    /// The files the commits change, in alphabetical order.
+    pub files: Vec<path::PathBuf>,
+    /// What the description of the pull request follows: the template of the project, else
+    /// the one of the repository, else a summary and a list of changes.
+    pub template: String,
+}
+
+/// What the pull request of the branch `branch_id` is written from, see the
+/// [module docs](self). Only what's committed is in a pull request.
+pub fn pull_request_context(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<PullRequestContext, errors::PullRequestContextError> {
+    let session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let session_reader = sessions::Reader::open(gb_repository, &session)
+        .context("failed to open current session")?;
+    let default_target = super::get_default_target(&session_reader)
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::PullRequestContextError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+    let branch = branch::Reader::new(&session_reader)
+        .read(branch_id)
+        .map_err(|error| match error {
+            reader::Error::NotFound => {
+                errors::PullRequestContextError::BranchNotFound(errors::BranchNotFoundError {
+                    project_id: project_repository.project().id,
+                    branch_id: *branch_id,
+                })
+            }
+            error => errors::PullRequestContextError::Other(error.into()),
+        })?;
+
+    let repo = &project_repository.git_repository;
+    let merge_base = project_repository
+        .merge_base(default_target.sha, branch.head)
+        .context("failed to find merge base")?;
+    let commits = project_repository
+        .log(branch.head, LogUntil::Commit(merge_base))
+        .context("failed to list commits")?;
+    if commits.is_empty() {
+        return Err(errors::PullRequestContextError::NoCommits);
+    }
+    let commit_messages = commits
+        .iter()
+        .rev()
+        .map(|commit| commit.message().unwrap_or_default().trim().to_string())
+        .collect();
+
+    let base_tree = repo
+        .find_commit(merge_base)
+        .and_then(|commit| commit.tree())
+        .context("failed to find merge base tree")?;
+    let head_tree = repo
+        .find_commit(branch.head)
+        .and_then(|commit| commit.tree())
+        .context("failed to find head tree")?;
+    let files = diff::trees(repo, &base_tree, &head_tree).context("failed to diff branch")?;
+    let mut paths = files.keys().cloned().collect::<Vec<_>>();
+    paths.sort();
+
+    Ok(PullRequestContext {
+        branch_name: branch.name,
+        commit_messages,
+        diff_summary: summarize(&files, MAX_SUMMARY_LENGTH),
+        files: paths,
+        template: template(project_repository),
+    })
+}
+
+fn template(project_repository: &project_repository::Repository) -> String {
+    if let Some(template) = &project_repository.project().pr_template {
+        return template.clone();
+    }
+    TEMPLATE_PATHS
+        .iter()
+        .find_map(|template| fs::read_to_string(project_repository.path().join(template)).ok())
+        .filter(|template| !template.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string())
+}
+
+// a line per file with what happened to it and how many lines it adds and removes, then the
+// hunks without their context lines, as much of them as fits in `max_len`
+fn summarize(files: &HashMap<path::PathBuf, Vec<diff::Hunk>>, max_len: usize) -> String {
+    let mut files = files.iter().collect::<Vec<_>>();
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    // the notes of what is left out count against `max_len` too, the longest one is kept room
+    // for until the last file
+    let files_left_out = |count: usize| {
+        let files = if count == 1 { "file is" } else { "files are" };
+        format!("({count} more {files} left out)\n")
+    };
+    let reserved = files_left_out(files.len()).len();
+
+    let mut summary = String::new();
+    for (index, (path, hunks)) in files.iter().enumerate() {
+        let (mut added, mut removed) = (0, 0);
+        for line in hunks.iter().flat_map(|hunk| hunk.diff.lines().skip(1)) {
+            if line.starts_with('+') {
+                added += 1;
+            } else if line.starts_with('-') {
+                removed += 1;
+            }
+        }
+        let change = match hunks.first().map(|hunk| hunk.change_type) {
+            Some(ChangeType::Added) => "added",
+            Some(ChangeType::Deleted) => "deleted",
+            _ => "modified",
+        };
+        let line = format!("{} ({change}, +{added} -{removed})\n", path.display());
+        let is_last = index + 1 == files.len();
+        if summary.len() + line.len() + if is_last { 0 } else { reserved } > max_len {
+            summary.push_str(&files_left_out(files.len() - index));
+            return summary;
+        }
+        summary.push_str(&line);
+    }
+
+    for (index, (path, hunks)) in files.iter().enumerate() {
+        let mut file = format!("\n--- {}\n", path.display());
+        for hunk in hunks.iter().filter(|hunk| !hunk.binary && !hunk.generated) {
+            for (i, line) in hunk.diff.lines().enumerate() {
+                if i == 0 || line.starts_with('+') || line.starts_with('-') {
+                    file.push_str(line);
+                    file.push('\n');
+                }
+            }
+        }
+        let is_last = index + 1 == files.len();
+        if summary.len() + file.len() + if is_last { 0 } else { DIFF_LEFT_OUT.len() } > max_len {
+            // the last file of the list has no room kept for it
+            if summary.len() + DIFF_LEFT_OUT.len() <= max_len {
+                summary.push_str(DIFF_LEFT_OUT);
+            }
+            break;
+        }
+        summary.push_str(&file);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(diff: &str, change_type: ChangeType) -> diff::Hunk {
+        diff::Hunk {
+            old_start: 1,
+            old_lines: 1,
+            new_start: 1,
+            new_lines: 1,
+            diff: diff.to_string(),
+            binary: false,
+            generated: false,
+            change_type,
+        }
+    }
+
+    #[test]
+    fn summary_without_context() {
+        let files = HashMap::from([
+            (
+                path::PathBuf::from("src/login.rs"),
+                vec![hunk(
+                    "@@ -1,3 +1,3 @@\n fn login() {\n-    redirect();\n+    redirect_to(home);\n }\n",
+                    ChangeType::Modified,
+                )],
+            ),
+            (
+                path::PathBuf::from("README.md"),
+                vec![hunk("@@ -0,0 +1 @@\n+Login\n", ChangeType::Added)],
+            ),
+        ]);
+        assert_eq!(
+            summarize(&files, 1000),
+            "README.md (added, +1 -0)\n\
+             src/login.rs (modified, +1 -1)\n\
+             \n--- README.md\n@@ -0,0 +1 @@\n+Login\n\
+             \n--- src/login.rs\n@@ -1,3 +1,3 @@\n-    redirect();\n+    redirect_to(home);\n"
+        );
+        assert_eq!(
+            summarize(&files, 130),
+            "README.md (added, +1 -0)\n\
+             src/login.rs (modified, +1 -1)\n\
+             \n--- README.md\n@@ -0,0 +1 @@\n+Login\n\
+             \n(the rest of the diff is left out)\n"
+        );
+        // the list of files counts too
+        assert_eq!(
+            summarize(&files, 55),
+            "README.md (added, +1 -0)\n\
+             (1 more file is left out)\n"
+        );
+        assert!(summarize(&files, 130).len() <= 130);
+    }
+}
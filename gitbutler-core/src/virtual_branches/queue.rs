@@ -0,0 +1,432 @@
+//! The operations changing a project run one at a time, in the order they were requested.
+//! Until it's their turn they wait in the queue of the project, which can be listed to show
+//! what an operation is waiting for, like a fetch to finish.
+
+use std::{
+    future::{self, Future},
+    pin::pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    task::Poll,
+    thread, time,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore};
+
+use crate::project_repository::Cancellation;
+
+/// How long the network threads of a cancelled operation hold the queue at most. libgit2 has
+/// no timeout of its own for connecting, a connection that hangs would hold it for good
+/// otherwise. Left running after that, they don't change the repository anymore.
+const MAX_LINGER: time::Duration = time::Duration::from_secs(30);
+
+// how often the threads of a cancelled operation are looked at, for whether they are done
+const LINGER_POLL_INTERVAL: time::Duration = time::Duration::from_millis(50);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Requester {
+    /// The user, from the app.
+    #[default]
+    User,
+    /// GitButler itself, like the periodic fetch or the watcher.
+    Background,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedOperation {
+    /// What cancels it, see [`super::Controller::cancel_operation`].
+    pub id: OperationId,
+    /// What the operation is, the name of its command, like `fetch_from_target`.
+    pub kind: &'static str,
+    pub requested_by: Requester,
+    /// Whether it's the one running, the others wait for it to finish.
+    pub running: bool,
+    /// Whether it can be cancelled, only network operations can.
+    pub cancellable: bool,
+    /// Since it was requested.
+    pub elapsed_ms: u128,
+}
+
+pub type OperationId = u64;
+
+/// An operation that ran, for how long it waited for its turn and then ran to be measured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinishedOperation {
+    pub kind: &'static str,
+    pub requested_by: Requester,
+    pub waited: time::Duration,
+    pub ran: time::Duration,
+}
+
+/// Called with every operation that ran, once it's done.
+pub type OnFinished = Arc<dyn Fn(&FinishedOperation) + Send + Sync>;
+
+struct Entry {
+    id: OperationId,
+    kind: &'static str,
+    requested_by: Requester,
+    requested_at: time::Instant,
+    running: bool,
+    cancel: Option<Cancellation>,
+}
+
+pub(super) struct OperationQueue {
+    // tokio's semaphore is fair, permits are handed out in the order they were asked for
+    semaphore: Arc<Semaphore>,
+    entries: Mutex<Vec<Entry>>,
+    next_id: AtomicU64,
+    on_finished: Option<OnFinished>,
+    max_linger: time::Duration,
+}
+
+impl OperationQueue {
+    pub fn new() -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(1)),
+            entries: Mutex::new(vec![]),
+            next_id: AtomicU64::new(0),
+            on_finished: None,
+            max_linger: MAX_LINGER,
+        }
+    }
+
+    /// The queue, waiting at most `max_linger` for the threads of cancelled operations.
+    pub fn max_linger(self, max_linger: time::Duration) -> Self {
+        Self { max_linger, ..self }
+    }
+
+    /// The queue, telling `on_finished` about the operations that ran.
+    pub fn on_finished(self, on_finished: Option<OnFinished>) -> Self {
+        Self {
+            on_finished,
+            ..self
+        }
+    }
+
+    /// Waits for the operations requested before to finish. The operation runs until the
+    /// permit is dropped.
+    pub async fn enqueue(
+        &self,
+        kind: &'static str,
+        requested_by: Requester,
+    ) -> OperationPermit<'_> {
+        let mut permit = self.push(kind, requested_by, None);
+        let semaphore_permit = Arc::clone(&self.semaphore).acquire_owned().await;
+        permit.start(semaphore_permit);
+        permit
+    }
+
+    /// Like [`Self::enqueue`], for an operation that [`Self::cancel`] cancels the token of,
+    /// see [`OperationPermit::cancellation`]. `None` if it's cancelled before its turn.
+    pub async fn enqueue_cancellable(
+        &self,
+        kind: &'static str,
+        requested_by: Requester,
+    ) -> Option<OperationPermit<'_>> {
+        let cancel = Cancellation::default();
+        let mut permit = self.push(kind, requested_by, Some(cancel.clone()));
+        let mut acquire = pin!(Arc::clone(&self.semaphore).acquire_owned());
+        let mut cancelled = pin!(cancel.token().cancelled());
+        let semaphore_permit = future::poll_fn(|cx| {
+            if let Poll::Ready(semaphore_permit) = acquire.as_mut().poll(cx) {
+                return Poll::Ready(Some(semaphore_permit));
+            }
+            cancelled.as_mut().poll(cx).map(|()| None)
+        })
+        .await?;
+        permit.start(semaphore_permit);
+        Some(permit)
+    }
+
+    /// Cancels the operation `id`, false if there is no such operation or it can't be
+    /// cancelled.
+    pub fn cancel(&self, id: OperationId) -> bool {
+        match self
+            .entries()
+            .iter()
+            .find(|entry| entry.id == id)
+            .and_then(|entry| entry.cancel.as_ref())
+        {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The operations that are running or waiting to, in the order they were requested.
+    pub fn operations(&self) -> Vec<QueuedOperation> {
+        self.entries()
+            .iter()
+            .map(|entry| QueuedOperation {
+                id: entry.id,
+                kind: entry.kind,
+                requested_by: entry.requested_by,
+                running: entry.running,
+                cancellable: entry.cancel.is_some(),
+                elapsed_ms: entry.requested_at.elapsed().as_millis(),
+            })
+            .collect()
+    }
+
+    // takes the entry out of the queue when the permit is dropped, if the caller stops
+    // waiting too
+    fn push(
+        &self,
+        kind: &'static str,
+        requested_by: Requester,
+        cancel: Option<Cancellation>,
+    ) -> OperationPermit<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.entries().push(Entry {
+            id,
+            kind,
+            requested_by,
+            requested_at: time::Instant::now(),
+            running: false,
+            cancel: cancel.clone(),
+        });
+        OperationPermit {
+            queue: self,
+            id,
+            kind,
+            requested_by,
+            cancel: cancel.unwrap_or_default(),
+            started_at: None,
+            permit: None,
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Vec<Entry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The turn of an operation, until it's dropped. The next operation waits longer if this one
+/// was cancelled while it had network threads of its own running, for them to finish, see
+/// [`Cancellation`].
+pub(super) struct OperationPermit<'a> {
+    queue: &'a OperationQueue,
+    id: OperationId,
+    kind: &'static str,
+    requested_by: Requester,
+    cancel: Cancellation,
+    started_at: Option<time::Instant>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl OperationPermit<'_> {
+    /// What is cancelled when the operation is, never for one that can't be.
+    pub fn cancellation(&self) -> &Cancellation {
+        &self.cancel
+    }
+
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    pub fn requested_by(&self) -> Requester {
+        self.requested_by
+    }
+
+    fn start(&mut self, semaphore_permit: Result<OwnedSemaphorePermit, AcquireError>) {
+        self.permit = Some(semaphore_permit.expect("the semaphore of the queue is never closed"));
+        self.started_at = Some(time::Instant::now());
+        if let Some(entry) = self
+            .queue
+            .entries()
+            .iter_mut()
+            .find(|entry| entry.id == self.id)
+        {
+            entry.running = true;
+        }
+    }
+}
+
+impl Drop for OperationPermit<'_> {
+    fn drop(&mut self) {
+        let lingering = self.cancel.take_lingering();
+        if let (Some(permit), false) = (self.permit.take(), lingering.is_empty()) {
+            tracing::info!(
+                kind = self.kind,
+                threads = lingering.len(),
+                "waiting for the network threads of a cancelled operation"
+            );
+            let deadline = time::Instant::now() + self.queue.max_linger;
+            let kind = self.kind;
+            thread::spawn(move || {
+                while !lingering.iter().all(thread::JoinHandle::is_finished) {
+                    if time::Instant::now() >= deadline {
+                        tracing::warn!(
+                            kind,
+                            "network threads of a cancelled operation are still running, \
+                             not waiting for them anymore"
+                        );
+                        break;
+                    }
+                    thread::sleep(LINGER_POLL_INTERVAL);
+                }
+                drop(permit);
+            });
+        }
+
+        let mut entries = self.queue.entries();
+        let Some(index) = entries.iter().position(|entry| entry.id == self.id) else {
+            return;
+        };
+        let entry = entries.remove(index);
+        drop(entries);
+
+        if let (Some(on_finished), Some(started_at)) = (&self.queue.on_finished, self.started_at) {
+            on_finished(&FinishedOperation {
+                kind: entry.kind,
+                requested_by: entry.requested_by,
+                waited: started_at.duration_since(entry.requested_at),
+                ran: started_at.elapsed(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn operations_wait_their_turn() {
+        let queue = Arc::new(OperationQueue::new());
+        let fetch = queue
+            .enqueue("fetch_from_target", Requester::Background)
+            .await;
+
+        let waiting = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move {
+                let _permit = queue.enqueue("create_commit", Requester::User).await;
+            }
+        });
+        while queue.operations().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        let operations = queue.operations();
+        assert_eq!(operations[0].kind, "fetch_from_target");
+        assert!(operations[0].running);
+        assert_eq!(operations[1].kind, "create_commit");
+        assert_eq!(operations[1].requested_by, Requester::User);
+        assert!(!operations[1].running);
+
+        drop(fetch);
+        waiting.await.unwrap();
+        assert!(queue.operations().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancel_waiting_operation() {
+        let queue = Arc::new(OperationQueue::new());
+        let _commit = queue.enqueue("create_commit", Requester::User).await;
+
+        let fetch = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move {
+                queue
+                    .enqueue_cancellable("fetch_from_target", Requester::Background)
+                    .await
+                    .is_some()
+            }
+        });
+        while queue.operations().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        let operations = queue.operations();
+        assert!(!operations[0].cancellable);
+        assert!(!queue.cancel(operations[0].id));
+        assert!(operations[1].cancellable);
+        assert!(queue.cancel(operations[1].id));
+
+        assert!(!fetch.await.unwrap());
+        assert_eq!(queue.operations().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn lingering_threads_hold_the_queue() {
+        let queue = Arc::new(OperationQueue::new());
+        let fetch = queue
+            .enqueue_cancellable("fetch_from_target", Requester::Background)
+            .await
+            .unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        fetch.cancellation().cancel();
+        fetch.cancellation().linger([thread::spawn(move || {
+            let _ = receiver.recv();
+        })]);
+        drop(fetch);
+
+        let commit = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move {
+                let _permit = queue.enqueue("create_commit", Requester::User).await;
+            }
+        });
+        tokio::time::sleep(time::Duration::from_millis(50)).await;
+        assert!(!commit.is_finished());
+
+        sender.send(()).unwrap();
+        commit.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn hung_threads_hold_the_queue_for_a_while() {
+        let queue = Arc::new(OperationQueue::new().max_linger(time::Duration::from_millis(100)));
+        let fetch = queue
+            .enqueue_cancellable("fetch_from_target", Requester::Background)
+            .await
+            .unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        fetch.cancellation().cancel();
+        fetch.cancellation().linger([thread::spawn(move || {
+            let _ = receiver.recv();
+        })]);
+        drop(fetch);
+
+        let commit = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move {
+                let _permit = queue.enqueue("create_commit", Requester::User).await;
+            }
+        });
+        tokio::time::sleep(time::Duration::from_millis(20)).await;
+        assert!(!commit.is_finished());
+        tokio::time::timeout(time::Duration::from_secs(5), commit)
+            .await
+            .unwrap()
+            .unwrap();
+        drop(sender);
+    }
+
+    #[tokio::test]
+    async fn finished_operations_are_measured() {
+        let finished = Arc::new(Mutex::new(vec![]));
+        let queue = OperationQueue::new().on_finished(Some(Arc::new({
+            let finished = Arc::clone(&finished);
+            move |operation: &FinishedOperation| finished.lock().unwrap().push(operation.clone())
+        })));
+
+        let commit = queue.enqueue("create_commit", Requester::User).await;
+        std::thread::sleep(time::Duration::from_millis(10));
+        drop(commit);
+
+        let finished = finished.lock().unwrap();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].kind, "create_commit");
+        assert_eq!(finished[0].requested_by, Requester::User);
+        assert!(finished[0].ran >= time::Duration::from_millis(10));
+    }
+}
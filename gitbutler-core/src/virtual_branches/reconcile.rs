@@ -0,0 +1,410 @@
+//! What `git commit`, `git checkout` and `git stash` run outside of GitButler did to the
+//! workspace, found out when the branch is verified, which the watcher makes happen whenever
+//! `HEAD` or the index change.
+//!
+//! Commits made on top of the workspace are moved onto the applied branch that owns every
+//! file they change, or onto a new branch if there's none. Every change is also recorded, with
+//! what can be done about it, until it's resolved or dismissed: commits can be taken back
+//! into the working directory, the workspace checked out again and stashed changes popped.
+
+use std::{fs, io, process::Command, time};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::CorruptStateError, gb_repository, git, id::Id, project_repository};
+
+use super::{
+    branch::{self, BranchId},
+    errors,
+    recovery::read_branches,
+};
+
+pub type ExternalChangeId = Id<ExternalChange>;
+
+const STATE_FILE: &str = "external_changes.json";
+const STASH_REFERENCE: &str = "refs/stash";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalChange {
+    pub id: ExternalChangeId,
+    pub timestamp_ms: u128,
+    pub kind: ExternalChangeKind,
+    /// What can be done about it, next to dismissing it.
+    pub actions: Vec<ReconcileAction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ExternalChangeKind {
+    /// Commits were made on top of the workspace, and were moved onto the branch `branch_id`.
+    #[serde(rename_all = "camelCase")]
+    Commits {
+        /// The commits, as they are on the branch now, the oldest first.
+        commits: Vec<git::Oid>,
+        branch_id: BranchId,
+        /// The head of the branch before them.
+        previous_head: git::Oid,
+        /// Whether the branch was created for them, no applied branch owning all of their files.
+        created_branch: bool,
+    },
+    /// Something else than the workspace was checked out, and the branches were unapplied.
+    #[serde(rename_all = "camelCase")]
+    Checkout { head: String },
+    /// The uncommitted changes were stashed.
+    #[serde(rename_all = "camelCase")]
+    Stash { stash: git::Oid },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReconcileAction {
+    /// Leaves things as they are.
+    Dismiss,
+    /// Takes the commits off the branch, back into its uncommitted changes.
+    Uncommit,
+    /// Checks the workspace out again, bringing what's checked out along as a branch.
+    ReturnToWorkspace,
+    /// Pops the stash back into the working directory.
+    PopStash,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct State {
+    /// Whether the stash was looked at yet, the stashes made before aren't reported.
+    stash_seen: bool,
+    /// The stash that was on top when it was last looked at, so that a stash is reported
+    /// only once.
+    last_stash: Option<git::Oid>,
+    /// What was checked out instead of the workspace when it was last looked at, so that a
+    /// checkout that was dismissed isn't reported again.
+    last_checkout: Option<String>,
+    /// The branches that were applied when the workspace was checked out last, for them to
+    /// be applied again when it's recovered.
+    unapplied_by_checkout: Vec<BranchId>,
+    changes: Vec<ExternalChange>,
+}
+
+fn read_state(gb_repository: &gb_repository::Repository) -> Result<State> {
+    match fs::read(gb_repository.root().join(STATE_FILE)) {
+        Ok(state) => serde_json::from_slice(&state)
+            .map_err(CorruptStateError::of(STATE_FILE))
+            .context("malformed external changes"),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+        Err(error) => Err(error).context("failed to read external changes"),
+    }
+}
+
+fn write_state(gb_repository: &gb_repository::Repository, state: &State) -> Result<()> {
+    fs::write(
+        gb_repository.root().join(STATE_FILE),
+        serde_json::to_vec(state).context("failed to serialize external changes")?,
+    )
+    .context("failed to write external changes")
+}
+
+// reads the state, changes it with `change` and writes it if it changed, with no other
+// process, like the cli next to the app, changing it in between
+fn update_state<T>(
+    gb_repository: &gb_repository::Repository,
+    change: impl FnOnce(&mut State) -> Result<T>,
+) -> Result<T> {
+    let _lock = gb_repository.lock(gb_repository::LockShard::ExternalChanges);
+    let before = read_state(gb_repository)?;
+    let mut state = before.clone();
+    let result = change(&mut state)?;
+    if state != before {
+        write_state(gb_repository, &state)?;
+    }
+    Ok(result)
+}
+
+pub(super) fn record(
+    gb_repository: &gb_repository::Repository,
+    kind: ExternalChangeKind,
+) -> Result<()> {
+    update_state(gb_repository, |state| record_in(state, kind))
+}
+
+fn record_in(state: &mut State, kind: ExternalChangeKind) -> Result<()> {
+    let actions = match kind {
+        ExternalChangeKind::Commits { .. } => vec![ReconcileAction::Uncommit],
+        ExternalChangeKind::Checkout { .. } => vec![ReconcileAction::ReturnToWorkspace],
+        ExternalChangeKind::Stash { .. } => vec![ReconcileAction::PopStash],
+    };
+    state.changes.push(ExternalChange {
+        id: ExternalChangeId::generate(),
+        timestamp_ms: time::UNIX_EPOCH
+            .elapsed()
+            .context("failed to get elapsed time")?
+            .as_millis(),
+        kind,
+        actions: [actions, vec![ReconcileAction::Dismiss]].concat(),
+    });
+    Ok(())
+}
+
+/// Records that `head` is checked out instead of the workspace, `None` if the workspace is,
+/// and that checking it out unapplied the branches `unapplied`.
+pub(super) fn detect_checkout(
+    gb_repository: &gb_repository::Repository,
+    head: Option<String>,
+    unapplied: Vec<BranchId>,
+) -> Result<()> {
+    update_state(gb_repository, |state| {
+        state.unapplied_by_checkout.extend(unapplied);
+        if state.last_checkout != head {
+            match &head {
+                Some(head) => {
+                    record_in(state, ExternalChangeKind::Checkout { head: head.clone() })?;
+                }
+                // checked out again with git, the branches stay unapplied
+                None => state.unapplied_by_checkout.clear(),
+            }
+            state.last_checkout = head;
+        }
+        Ok(())
+    })
+}
+
+/// The branches that were unapplied by checking something else than the workspace out, and
+/// forgets about that checkout, the workspace being recovered.
+pub(super) fn take_unapplied_by_checkout(
+    gb_repository: &gb_repository::Repository,
+) -> Result<Vec<BranchId>> {
+    update_state(gb_repository, |state| {
+        let unapplied = std::mem::take(&mut state.unapplied_by_checkout);
+        state.last_checkout = None;
+        state
+            .changes
+            .retain(|change| !matches!(change.kind, ExternalChangeKind::Checkout { .. }));
+        Ok(unapplied)
+    })
+}
+
+/// Moves `commits`, made on top of the workspace, onto the applied branch that owns every
+/// file they change. `None` if there's no such branch or they don't apply onto it.
+pub(super) fn move_onto_owning_branch(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    commits: &[git::Commit],
+) -> Result<Option<ExternalChangeKind>> {
+    let repo = &project_repository.git_repository;
+
+    let mut changed_paths = vec![];
+    for commit in commits {
+        let parent_tree = commit
+            .parent(0)
+            .and_then(|parent| parent.tree())
+            .context("failed to get parent tree")?;
+        let tree = commit.tree().context("failed to get commit tree")?;
+        changed_paths.extend(
+            repo.diff_tree_to_tree(Some(&parent_tree), Some(&tree), None)
+                .context("failed to diff commit")?
+                .deltas()
+                .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+                .flatten()
+                .map(ToOwned::to_owned),
+        );
+    }
+
+    let branches = read_branches(gb_repository)?;
+    let Some(mut branch) = branches.into_iter().find(|branch| {
+        branch.applied
+            && changed_paths.iter().all(|path| {
+                branch
+                    .ownership
+                    .files
+                    .iter()
+                    .any(|file| file.file_path == *path)
+            })
+    }) else {
+        return Ok(None);
+    };
+
+    // picked onto the branch in memory first, nothing is written if one of them conflicts
+    let previous_head = branch.head;
+    let mut head = repo
+        .find_commit(branch.head)
+        .context("failed to find branch head")?;
+    let mut moved = vec![];
+    for commit in commits {
+        let parent_tree = commit
+            .parent(0)
+            .and_then(|parent| parent.tree())
+            .context("failed to get parent tree")?;
+        let head_tree = head.tree().context("failed to get branch tree")?;
+        let tree = commit.tree().context("failed to get commit tree")?;
+        let mut merge_index = repo
+            .merge_trees(&parent_tree, &head_tree, &tree)
+            .context("failed to merge commit onto branch")?;
+        if merge_index.has_conflicts() {
+            return Ok(None);
+        }
+        let tree_oid = merge_index
+            .write_tree_to(repo)
+            .context("failed to write tree")?;
+        let tree = repo.find_tree(tree_oid).context("failed to find tree")?;
+        let moved_oid = repo
+            .commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                commit.message().unwrap_or_default(),
+                &tree,
+                &[&head],
+            )
+            .with_context(|| format!("failed to move commit {} onto branch", commit.id()))?;
+        head = repo
+            .find_commit(moved_oid)
+            .context("failed to find moved commit")?;
+        moved.push(moved_oid);
+    }
+
+    // the tree stays, the working directory has what was committed along with the changes
+    // that still aren't
+    branch.head = head.id();
+    branch::Writer::new(gb_repository)
+        .context("failed to create writer")?
+        .write(&mut branch)
+        .context("failed to write branch")?;
+
+    Ok(Some(ExternalChangeKind::Commits {
+        commits: moved,
+        branch_id: branch.id,
+        previous_head,
+        created_branch: false,
+    }))
+}
+
+/// Records a stash made since the last time, a `git stash` run on the workspace.
+pub(super) fn detect_stash(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<()> {
+    let stash = match project_repository
+        .git_repository
+        .refname_to_id(STASH_REFERENCE)
+    {
+        Ok(stash) => Some(stash),
+        Err(git::Error::NotFound(_)) => None,
+        Err(error) => return Err(error).context("failed to read stash"),
+    };
+    update_state(gb_repository, |state| {
+        if state.stash_seen && state.last_stash == stash {
+            return Ok(());
+        }
+        // the first look only takes note of what's there
+        if let Some(stash) = stash.filter(|_| state.stash_seen) {
+            record_in(state, ExternalChangeKind::Stash { stash })?;
+        }
+        state.stash_seen = true;
+        state.last_stash = stash;
+        Ok(())
+    })
+}
+
+/// The changes made outside of GitButler that weren't resolved or dismissed yet.
+pub fn list_external_changes(
+    gb_repository: &gb_repository::Repository,
+) -> Result<Vec<ExternalChange>, errors::ReconcileError> {
+    Ok(read_state(gb_repository)?.changes)
+}
+
+/// Does `action`, one of the actions of the change `change_id`, and takes the change off the
+/// list.
+pub fn reconcile_external_change(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    change_id: &ExternalChangeId,
+    action: ReconcileAction,
+) -> Result<(), errors::ReconcileError> {
+    // not locked while the action runs, which changes the state itself when it recovers the
+    // workspace
+    let change = read_state(gb_repository)?
+        .changes
+        .iter()
+        .find(|change| change.id == *change_id)
+        .cloned()
+        .ok_or(errors::ReconcileError::ChangeNotFound(*change_id))?;
+    if !change.actions.contains(&action) {
+        return Err(errors::ReconcileError::InvalidAction(action));
+    }
+
+    // what's on top of the stash once it's popped
+    let mut last_stash = None;
+    match (&change.kind, action) {
+        (_, ReconcileAction::Dismiss) => {}
+        (
+            ExternalChangeKind::Commits {
+                commits,
+                branch_id,
+                previous_head,
+                ..
+            },
+            ReconcileAction::Uncommit,
+        ) => {
+            let mut branch = read_branches(gb_repository)?
+                .into_iter()
+                .find(|branch| branch.id == *branch_id)
+                .ok_or_else(|| {
+                    errors::ReconcileError::Outdated("the branch was deleted".to_string())
+                })?;
+            if commits.last() != Some(&branch.head) {
+                return Err(errors::ReconcileError::Outdated(
+                    "the branch was committed to since".to_string(),
+                ));
+            }
+            // the working directory still has the changes, they are uncommitted again
+            branch.head = *previous_head;
+            branch::Writer::new(gb_repository)
+                .context("failed to create writer")?
+                .write(&mut branch)
+                .context("failed to write branch")?;
+            super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
+        }
+        (ExternalChangeKind::Checkout { .. }, ReconcileAction::ReturnToWorkspace) => {
+            // recovering takes the checkouts off the list
+            super::recover_workspace(gb_repository, project_repository, None, None)?;
+        }
+        (ExternalChangeKind::Stash { stash }, ReconcileAction::PopStash) => {
+            let top = project_repository
+                .git_repository
+                .refname_to_id(STASH_REFERENCE)
+                .ok();
+            if top != Some(*stash) {
+                return Err(errors::ReconcileError::Outdated(
+                    "the stash isn't on top anymore".to_string(),
+                ));
+            }
+            let output = Command::new("git")
+                .args(["stash", "pop"])
+                .current_dir(project_repository.path())
+                .output()
+                .context("failed to run git, is it installed?")?;
+            if !output.status.success() {
+                return Err(errors::ReconcileError::Failed(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ));
+            }
+            last_stash = Some(
+                project_repository
+                    .git_repository
+                    .refname_to_id(STASH_REFERENCE)
+                    .ok(),
+            );
+        }
+        (_, action) => return Err(errors::ReconcileError::InvalidAction(action)),
+    }
+
+    update_state(gb_repository, |state| {
+        state.changes.retain(|change| change.id != *change_id);
+        if let Some(last_stash) = last_stash {
+            state.last_stash = last_stash;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
@@ -0,0 +1,345 @@
+use std::{collections::HashSet, time};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    dedup::dedup,
+    gb_repository,
+    git::{self, diff},
+    keys, project_repository, reader, sessions, users,
+};
+
+use super::{
+    branch::{self, BranchId},
+    commit_to_remote_commit, errors,
+    integration::{
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL, GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+    },
+    target, Iterator, RemoteCommit,
+};
+
+// how far back the recorded sessions are searched, there is one every few minutes of work
+const MAX_SESSIONS: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LostCommit {
+    pub commit: RemoteCommit,
+    /// Where the commit was found: the reference whose reflog has it, like `HEAD`, or the
+    /// GitButler session that recorded it as the head of the branch.
+    pub source: String,
+}
+
+/// The commits that no branch, virtual or not, nor any other reference leads to anymore, but
+/// that are still in the reflogs of `HEAD`, of the local branches and of the GitButler refs,
+/// or in the history GitButler recorded of the branch `branch_id`, and that were made on top
+/// of its base. Only the newest commit of every lost line of history is listed, the newest
+/// first.
+///
+/// This is what a reset or a rebase done outside of GitButler leaves behind.
+pub fn list_lost_commits(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<Vec<LostCommit>, errors::ListLostCommitsError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::ListLostCommitsError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+    let branches = read_branches(gb_repository)?;
+    let branch = branches
+        .iter()
+        .find(|branch| branch.id == *branch_id)
+        .ok_or_else(|| {
+            errors::ListLostCommitsError::BranchNotFound(errors::BranchNotFoundError {
+                project_id: project_repository.project().id,
+                branch_id: *branch_id,
+            })
+        })?;
+
+    lost_commits(
+        gb_repository,
+        project_repository,
+        &default_target,
+        &branches,
+        branch,
+    )
+    .map_err(Into::into)
+}
+
+/// Creates a virtual branch with the lost commit `commit_id` of the branch `branch_id` as its
+/// head, `commit_id` being one of the commits [`list_lost_commits`] lists. The new branch is applied unless it conflicts with the
+/// applied branches.
+pub fn recover_commit(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    commit_id: git::Oid,
+    signing_key: Option<&keys::PrivateKey>,
+    user: Option<&users::User>,
+) -> Result<BranchId, errors::RecoverCommitError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::RecoverCommitError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+    let branches = read_branches(gb_repository)?;
+    let branch = branches
+        .iter()
+        .find(|branch| branch.id == *branch_id)
+        .ok_or_else(|| {
+            errors::RecoverCommitError::BranchNotFound(errors::BranchNotFoundError {
+                project_id: project_repository.project().id,
+                branch_id: *branch_id,
+            })
+        })?;
+
+    let commit_id_string = commit_id.to_string();
+    let is_lost = lost_commits(
+        gb_repository,
+        project_repository,
+        &default_target,
+        &branches,
+        branch,
+    )?
+    .iter()
+    .any(|lost| lost.commit.id == commit_id_string);
+    if !is_lost {
+        return Err(errors::RecoverCommitError::CommitNotLost(commit_id));
+    }
+
+    let repo = &project_repository.git_repository;
+    let commit = repo
+        .find_commit(commit_id)
+        .context("failed to find commit")?;
+    let tree = commit.tree().context("failed to find tree")?;
+
+    // like branches created from other branches, the new branch owns what its commits changed
+    let ownership = ownership_since_base(project_repository, default_target.sha, commit_id, &tree)?;
+
+    let now = time::UNIX_EPOCH
+        .elapsed()
+        .context("failed to get elapsed time")?
+        .as_millis();
+    let mut recovered = branch::Branch {
+        id: BranchId::generate(),
+        name: dedup(
+            &branches
+                .iter()
+                .map(|branch| branch.name.as_str())
+                .collect::<Vec<_>>(),
+            &format!("{} recovered", branch.name),
+        ),
+        notes: String::new(),
+        applied: false,
+        upstream: None,
+        upstream_head: None,
+        tree: tree.id(),
+        head: commit_id,
+        created_timestamp_ms: now,
+        updated_timestamp_ms: now,
+        ownership,
+        order: branches.len(),
+        selected_for_changes: None,
+    };
+
+    let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    writer
+        .write(&mut recovered)
+        .context("failed to write branch")?;
+    project_repository.add_branch_reference(&recovered)?;
+
+    match super::apply_branch(
+        gb_repository,
+        project_repository,
+        &recovered.id,
+        signing_key,
+        user,
+    ) {
+        // if the branch conflicts with the workspace, it's ok. keep it unapplied
+        Ok(()) | Err(errors::ApplyBranchError::BranchConflicts(_)) => Ok(recovered.id),
+        Err(error) => Err(errors::RecoverCommitError::ApplyBranch(error)),
+    }
+}
+
+/// The ownership of what `tree` changes since the merge base of `head` with the target
+/// `target_sha`.
+pub(super) fn ownership_since_base(
+    project_repository: &project_repository::Repository,
+    target_sha: git::Oid,
+    head: git::Oid,
+    tree: &git::Tree,
+) -> Result<branch::Ownership> {
+    let repo = &project_repository.git_repository;
+    let merge_base = repo
+        .merge_base(target_sha, head)
+        .context("failed to find merge base")?;
+    let merge_base_tree = repo
+        .find_commit(merge_base)
+        .and_then(|commit| commit.tree())
+        .context("failed to find merge base tree")?;
+    let diff = diff::trees(repo, &merge_base_tree, tree).context("failed to diff trees")?;
+    let mut ownership = branch::Ownership::default();
+    for hunk in super::virtual_hunks_by_filepath(&project_repository.project().path, &diff)
+        .values()
+        .flatten()
+    {
+        ownership.put(
+            &format!("{}:{}", git::path::escape(&hunk.file_path), hunk.id)
+                .parse()
+                .context("failed to parse ownership")?,
+        );
+    }
+    Ok(ownership)
+}
+
+fn lost_commits(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    default_target: &target::Target,
+    branches: &[branch::Branch],
+    branch: &branch::Branch,
+) -> Result<Vec<LostCommit>> {
+    let repo = &project_repository.git_repository;
+    let base = repo
+        .merge_base(default_target.sha, branch.head)
+        .context("failed to find merge base")?;
+
+    // what any reference leads to isn't lost. the snapshots of the operation log are left
+    // out, they keep the heads branches had before an operation on purpose.
+    let mut heads = branches
+        .iter()
+        .flat_map(|branch| [Some(branch.head), branch.upstream_head])
+        .chain(Some(Some(default_target.sha)))
+        .flatten()
+        .collect::<HashSet<_>>();
+    let mut reflogs = vec!["HEAD".to_string()];
+    for reference in repo.references().context("failed to list references")? {
+        let reference = reference.context("failed to read reference")?;
+        let name = String::from_utf8_lossy(reference.name_bytes()).to_string();
+        if name.starts_with(super::oplog::SNAPSHOT_REFS) || name == super::METADATA_REFERENCE {
+            continue;
+        }
+        // like tags of trees
+        if let Ok(commit) = reference.peel_to_commit() {
+            heads.insert(commit.id());
+        }
+        if name.starts_with("refs/heads/") || name.starts_with("refs/gitbutler/") {
+            reflogs.push(name);
+        }
+    }
+
+    let mut sources = vec![];
+    for name in reflogs {
+        let reflog = repo
+            .reflog(&name)
+            .with_context(|| format!("failed to read the reflog of {name}"))?;
+        sources.extend(reflog.into_iter().map(|id| (name.clone(), id)));
+    }
+    sources.extend(session_heads(gb_repository, &branch.id)?);
+
+    let mut seen = HashSet::new();
+    let mut candidates = vec![];
+    for (source, id) in sources {
+        if !seen.insert(id) || id == base || heads.contains(&id) {
+            continue;
+        }
+        // objects can be pruned from under the reflog
+        let Ok(commit) = repo.find_commit(id) else {
+            continue;
+        };
+        if is_gitbutler_commit(&commit) || !project_repository.is_reachable_from(id, base)? {
+            continue;
+        }
+        candidates.push((commit, source));
+    }
+
+    // one walk from the candidates to where the references lead finds the commits no
+    // reference reaches. the parents of these are reached by another candidate, only the
+    // tips are kept, the rest comes along with them.
+    let mut walk = repo.revwalk().context("failed to create revwalk")?;
+    for (commit, _) in &candidates {
+        walk.push(commit.id().into())
+            .with_context(|| format!("failed to push {}", commit.id()))?;
+    }
+    for head in &heads {
+        // objects can be pruned from under the upstreams too
+        if repo.find_commit(*head).is_ok() {
+            walk.hide((*head).into())
+                .with_context(|| format!("failed to hide {head}"))?;
+        }
+    }
+    let mut unreachable = HashSet::new();
+    let mut below_candidates = HashSet::new();
+    for id in walk {
+        let id = git::Oid::from(id.context("failed to walk history")?);
+        let commit = repo.find_commit(id).context("failed to find commit")?;
+        below_candidates.extend(commit.parent_ids());
+        unreachable.insert(id);
+    }
+
+    let mailmap = repo.mailmap().context("failed to read mailmap")?;
+    let mut lost = vec![];
+    for (commit, source) in &candidates {
+        if unreachable.contains(&commit.id()) && !below_candidates.contains(&commit.id()) {
+            lost.push(LostCommit {
+                commit: commit_to_remote_commit(commit, &mailmap)?,
+                source: source.clone(),
+            });
+        }
+    }
+    lost.sort_by(|a, b| b.commit.created_at.cmp(&a.commit.created_at));
+    Ok(lost)
+}
+
+// the integration and wip commits GitButler writes aren't the user's, and have the changes of
+// other branches in them
+fn is_gitbutler_commit(commit: &git::Commit) -> bool {
+    let author = commit.author();
+    author.name() == Some(GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME)
+        && author.email() == Some(GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL)
+}
+
+// the heads the branch had in the sessions GitButler recorded, the most recent first
+fn session_heads(
+    gb_repository: &gb_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<Vec<(String, git::Oid)>> {
+    let mut heads = vec![];
+    let iterator = gb_repository
+        .get_sessions_iterator()
+        .context("failed to list sessions")?;
+    for session in iterator.take(MAX_SESSIONS) {
+        let session = session.context("failed to read session")?;
+        let session_reader =
+            sessions::Reader::open(gb_repository, &session).context("failed to open session")?;
+        match branch::Reader::new(&session_reader).read(branch_id) {
+            Ok(branch) => heads.push((format!("session {}", session.id), branch.head)),
+            Err(reader::Error::NotFound) => {}
+            Err(error) => return Err(error).context("failed to read branch"),
+        }
+    }
+    Ok(heads)
+}
+
+pub(super) fn read_branches(
+    gb_repository: &gb_repository::Repository,
+) -> Result<Vec<branch::Branch>> {
+    let session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let session_reader = sessions::Reader::open(gb_repository, &session)
+        .context("failed to open current session")?;
+    Iterator::new(&session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<branch::Branch>, reader::Error>>()
+        .context("failed to read virtual branches")
+}
@@ -6,7 +6,7 @@ use crate::{
     project_repository::{self, LogUntil},
 };
 
-use super::{errors, Author};
+use super::{errors, Author, CommitSignature};
 
 // this struct is a mapping to the view `RemoteBranch` type in Typescript
 // found in src-tauri/src/routes/repo/[project_id]/types.ts
@@ -33,6 +33,8 @@ pub struct RemoteBranchData {
     pub upstream: Option<git::RemoteRefname>,
     pub behind: u32,
     pub commits: Vec<RemoteCommit>,
+    /// Whether the commits or the count stopped at the end of a shallow history.
+    pub history_truncated: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
@@ -42,6 +44,10 @@ pub struct RemoteCommit {
     pub description: String,
     pub created_at: u128,
     pub author: Author,
+    /// Set for the upstream commits of the target, when the project verifies their
+    /// signatures.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<CommitSignature>,
 }
 
 pub fn list_remote_branches(
@@ -108,6 +114,7 @@ pub fn get_branch_data(
             upstream: branch_data.upstream,
             behind: branch_data.behind,
             commits: branch_data.commits,
+            history_truncated: branch_data.history_truncated,
         })
 }
 
@@ -138,14 +145,18 @@ pub fn branch_to_remote_branch_data(
         .target()
         .map(|sha| {
             let ahead = project_repository
-                .log(sha, LogUntil::Commit(base))
+                .history_log(sha, LogUntil::Commit(base))
                 .context("failed to get ahead commits")?;
 
             let name = git::Refname::try_from(branch).context("could not get branch name")?;
 
-            let count_behind = project_repository
-                .distance(base, sha)
+            let behind = project_repository
+                .ahead_behind(base, sha)
                 .context("failed to get behind count")?;
+            let mailmap = project_repository
+                .git_repository
+                .mailmap()
+                .context("failed to read mailmap")?;
 
             Ok(RemoteBranchData {
                 sha,
@@ -155,21 +166,30 @@ pub fn branch_to_remote_branch_data(
                     None
                 },
                 name,
-                behind: count_behind,
+                behind: behind.ahead.try_into()?,
                 commits: ahead
+                    .commits
                     .into_iter()
-                    .map(|commit| commit_to_remote_commit(&commit))
+                    .map(|oid| {
+                        let commit = project_repository.git_repository.find_commit(oid)?;
+                        commit_to_remote_commit(&commit, &mailmap)
+                    })
                     .collect::<Result<Vec<_>>>()?,
+                history_truncated: ahead.truncated || behind.truncated,
             })
         })
         .transpose()
 }
 
-pub fn commit_to_remote_commit(commit: &git::Commit) -> Result<RemoteCommit> {
+pub fn commit_to_remote_commit(
+    commit: &git::Commit,
+    mailmap: &git::Mailmap,
+) -> Result<RemoteCommit> {
     Ok(RemoteCommit {
         id: commit.id().to_string(),
         description: commit.message().unwrap_or_default().to_string(),
         created_at: commit.time().seconds().try_into().unwrap(),
-        author: commit.author().into(),
+        author: Author::of(commit, mailmap)?,
+        signature: None,
     })
 }
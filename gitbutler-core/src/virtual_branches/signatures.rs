@@ -0,0 +1,347 @@
+//! Verification of the signatures of the commits the target gets from upstream, for teams
+//! that only take signed commits. It's on when the `gitbutler.verifyUpstreamSignatures` git
+//! config is, and trusts the keys of the `gitbutler.trustedSigners` file: one key per line,
+//! either the fingerprint of a GPG key or an SSH public key, optionally after a principal
+//! like in the `allowed_signers` files of SSH. Without the file, the trust of the GPG keyring
+//! and `gpg.ssh.allowedSignersFile` is used.
+//!
+//! The file is read as it's committed at the base of the target, not from the working
+//! directory: a branch that is checked out, or the upstream commits being verified, can't
+//! trust the keys they're signed with.
+//!
+//! Signatures are verified by the git of the user, with the GPG and SSH setup it has. What
+//! it says of a commit is kept until the base of the target moves.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path,
+    process::{Command, Stdio},
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::{git, project_repository};
+
+// the allowed signers of the trusted ssh keys, written for git to verify them with
+const ALLOWED_SIGNERS_FILE: &str = "gitbutler-allowed-signers";
+
+// the signatures verified against the trusted signers of a base, by the git directory of the
+// repository. only those of the current base are kept, the trusted signers are read from it,
+// and the commits upstream of it are the only ones verified.
+static VERIFIED: Lazy<Mutex<HashMap<path::PathBuf, Verified>>> = Lazy::new(Mutex::default);
+
+struct Verified {
+    base: git::Oid,
+    trusted_signers: Option<String>,
+    signatures: HashMap<git::Oid, CommitSignature>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SignatureStatus {
+    /// Signed by a trusted key.
+    Trusted,
+    /// Signed by a key that isn't trusted, or expired or was revoked.
+    Untrusted,
+    /// The signature doesn't match the commit.
+    Invalid,
+    Unsigned,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitSignature {
+    pub status: SignatureStatus,
+    /// The fingerprint of the key the commit is signed with.
+    pub key: Option<String>,
+    /// Who the key belongs to.
+    pub signer: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct TrustedSigners {
+    // uppercase, without spaces
+    gpg_fingerprints: Vec<String>,
+    // lines of an allowed signers file
+    ssh_keys: Vec<String>,
+}
+
+impl TrustedSigners {
+    fn parse(list: &str) -> Self {
+        let mut signers = Self::default();
+        for line in list.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.split_whitespace().any(is_ssh_key_type) {
+                // a key without principal is trusted for anyone
+                if line.split_whitespace().next().is_some_and(is_ssh_key_type) {
+                    signers.ssh_keys.push(format!("* {line}"));
+                } else {
+                    signers.ssh_keys.push(line.to_string());
+                }
+            } else {
+                signers
+                    .gpg_fingerprints
+                    .push(line.replace(' ', "").to_uppercase());
+            }
+        }
+        signers
+    }
+}
+
+// the trusted signers of the file at `path` as it's committed in `base`
+fn read_trusted_signers(
+    repo: &git::Repository,
+    base: git::Oid,
+    path: &path::Path,
+) -> Result<TrustedSigners> {
+    let tree = repo
+        .find_commit(base)
+        .and_then(|commit| commit.tree())
+        .context("failed to find base tree")?;
+    let entry = tree
+        .get_path(path)
+        .with_context(|| format!("{} isn't committed in the base", path.display()))?;
+    let blob = repo
+        .find_blob(entry.id())
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(TrustedSigners::parse(&String::from_utf8_lossy(
+        blob.content(),
+    )))
+}
+
+fn is_ssh_key_type(word: &str) -> bool {
+    word.starts_with("ssh-") || word.starts_with("ecdsa-") || word.starts_with("sk-")
+}
+
+/// The signatures of `commits`, `None` if the project doesn't verify them.
+pub fn verify_signatures(
+    project_repository: &project_repository::Repository,
+    base: git::Oid,
+    commits: &[git::Oid],
+) -> Result<Option<HashMap<git::Oid, CommitSignature>>> {
+    let config = project_repository.config();
+    if !config
+        .verify_upstream_signatures()
+        .context("failed to read signature verification config")?
+    {
+        return Ok(None);
+    }
+    if commits.is_empty() {
+        return Ok(Some(HashMap::new()));
+    }
+
+    let repo = &project_repository.git_repository;
+    let trusted_signers = config
+        .trusted_signers()
+        .context("failed to read trusted signers config")?;
+    let cached =
+        |verified: &Verified| verified.base == base && verified.trusted_signers == trusted_signers;
+
+    let mut signatures = HashMap::new();
+    let mut unverified = vec![];
+    {
+        let verified = VERIFIED.lock().unwrap_or_else(PoisonError::into_inner);
+        let verified = verified
+            .get(repo.path())
+            .filter(|verified| cached(verified));
+        for oid in commits {
+            match verified.and_then(|verified| verified.signatures.get(oid)) {
+                Some(signature) => {
+                    signatures.insert(*oid, signature.clone());
+                }
+                None => unverified.push(*oid),
+            }
+        }
+    }
+    if unverified.is_empty() {
+        return Ok(Some(signatures));
+    }
+
+    let trusted = trusted_signers
+        .as_ref()
+        .map(|trusted_signers| read_trusted_signers(repo, base, path::Path::new(trusted_signers)))
+        .transpose()?;
+    let new_signatures = verify(repo, trusted.as_ref(), &unverified)?;
+
+    let mut verified = VERIFIED.lock().unwrap_or_else(PoisonError::into_inner);
+    match verified
+        .get_mut(repo.path())
+        .filter(|verified| cached(verified))
+    {
+        Some(verified) => verified.signatures.extend(new_signatures.clone()),
+        None => {
+            verified.insert(
+                repo.path().to_path_buf(),
+                Verified {
+                    base,
+                    trusted_signers,
+                    signatures: new_signatures.clone(),
+                },
+            );
+        }
+    }
+    signatures.extend(new_signatures);
+    Ok(Some(signatures))
+}
+
+// asks git for the signatures of `commits`, checking them against `trusted` if there are
+// trusted signers
+fn verify(
+    repo: &git::Repository,
+    trusted: Option<&TrustedSigners>,
+    commits: &[git::Oid],
+) -> Result<HashMap<git::Oid, CommitSignature>> {
+    let mut command = Command::new("git");
+    command.arg("--git-dir").arg(repo.path());
+    if let Some(trusted) = trusted {
+        let allowed_signers = repo.path().join(ALLOWED_SIGNERS_FILE);
+        write_allowed_signers(&allowed_signers, &trusted.ssh_keys)?;
+        command.arg("-c").arg(format!(
+            "gpg.ssh.allowedSignersFile={}",
+            allowed_signers.display()
+        ));
+    }
+    let mut child = command
+        .args([
+            "log",
+            "--no-walk=unsorted",
+            "--stdin",
+            // oid, status, fingerprint, primary key fingerprint and signer, of every commit
+            "--format=%H%x1f%G?%x1f%GF%x1f%GP%x1f%GS%x1e",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run git, is it installed?")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let revs = commits
+            .iter()
+            .map(|oid| format!("{oid}\n"))
+            .collect::<String>();
+        stdin
+            .write_all(revs.as_bytes())
+            .context("failed to write commits to verify")?;
+    }
+    let output = child.wait_with_output().context("failed to wait for git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git log failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .split('\x1e')
+        .map(str::trim)
+        .filter(|record| !record.is_empty())
+        .map(|record| {
+            let fields = record.split('\x1f').collect::<Vec<_>>();
+            let [oid, status, key, primary_key, signer] = fields[..] else {
+                anyhow::bail!("malformed signature of {record}");
+            };
+            let oid = oid.parse().context("malformed commit id")?;
+            Ok((oid, signature(status, key, primary_key, signer, trusted)))
+        })
+        .collect()
+}
+
+fn signature(
+    status: &str,
+    key: &str,
+    primary_key: &str,
+    signer: &str,
+    trusted: Option<&TrustedSigners>,
+) -> CommitSignature {
+    let ssh = key.starts_with("SHA256:");
+    let status = match status {
+        "N" => SignatureStatus::Unsigned,
+        "B" => SignatureStatus::Invalid,
+        // ssh keys are verified against the trusted ones by git
+        "G" if ssh => SignatureStatus::Trusted,
+        "G" | "U" if !ssh => match trusted {
+            Some(trusted)
+                if [key, primary_key].iter().any(|key| {
+                    !key.is_empty() && trusted.gpg_fingerprints.contains(&key.to_uppercase())
+                }) =>
+            {
+                SignatureStatus::Trusted
+            }
+            // the keyring trusts it
+            None if status == "G" => SignatureStatus::Trusted,
+            _ => SignatureStatus::Untrusted,
+        },
+        _ => SignatureStatus::Untrusted,
+    };
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    CommitSignature {
+        status,
+        key: non_empty(key),
+        signer: non_empty(signer),
+    }
+}
+
+fn write_allowed_signers(path: &path::Path, ssh_keys: &[String]) -> Result<()> {
+    let mut allowed_signers = ssh_keys.join("\n");
+    allowed_signers.push('\n');
+    fs::write(path, allowed_signers).context("failed to write allowed signers")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_trusted_signers() {
+        let trusted = TrustedSigners::parse(
+            "# release managers\n\
+             3AA5 C343 7156 7BD2\n\
+             ssh-ed25519 AAAAC3Nza me@example.com\n\
+             you@example.com ssh-ed25519 AAAAC3Nzb\n",
+        );
+        assert_eq!(trusted.gpg_fingerprints, vec!["3AA5C34371567BD2"]);
+        assert_eq!(
+            trusted.ssh_keys,
+            vec![
+                "* ssh-ed25519 AAAAC3Nza me@example.com",
+                "you@example.com ssh-ed25519 AAAAC3Nzb"
+            ]
+        );
+    }
+
+    #[test]
+    fn signature_status() {
+        let trusted = TrustedSigners::parse("3aa5c34371567bd2\n");
+        let status = |status, key, primary_key, trusted| {
+            signature(status, key, primary_key, "", trusted).status
+        };
+        assert_eq!(status("N", "", "", None), SignatureStatus::Unsigned);
+        assert_eq!(status("B", "ABCD", "", None), SignatureStatus::Invalid);
+        assert_eq!(status("G", "ABCD", "", None), SignatureStatus::Trusted);
+        assert_eq!(status("U", "ABCD", "", None), SignatureStatus::Untrusted);
+        assert_eq!(
+            status("U", "0000", "3AA5C34371567BD2", Some(&trusted)),
+            SignatureStatus::Trusted
+        );
+        assert_eq!(
+            status("G", "ABCD", "", Some(&trusted)),
+            SignatureStatus::Untrusted
+        );
+        assert_eq!(
+            status("G", "SHA256:abcd", "", Some(&trusted)),
+            SignatureStatus::Trusted
+        );
+        assert_eq!(
+            status("U", "SHA256:abcd", "", Some(&trusted)),
+            SignatureStatus::Untrusted
+        );
+        assert_eq!(status("X", "ABCD", "", None), SignatureStatus::Untrusted);
+    }
+}
@@ -0,0 +1,46 @@
+use serde::Serialize;
+
+use crate::{gb_repository, project_repository};
+
+use super::{errors, BaseBranch, RemoteBranch, VirtualBranch};
+
+/// Everything the frontend needs to render a project, read at once so that all parts
+/// are consistent with each other.
+#[derive(Debug, Serialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectState {
+    /// Increases with every snapshot, so that a response that arrives late can be told
+    /// apart from a fresher one.
+    pub version: u64,
+    pub base_branch: Option<BaseBranch>,
+    pub virtual_branches: Vec<VirtualBranch>,
+    pub remote_branches: Vec<RemoteBranch>,
+}
+
+/// The state of the project as of now, `version` being higher than the one of the snapshots
+/// taken before it.
+pub fn get_project_state(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    version: u64,
+) -> Result<ProjectState, errors::GetProjectStateError> {
+    let Some(base_branch) = super::get_base_branch_data(gb_repository, project_repository)? else {
+        // nothing else can be listed until the base branch is set
+        return Ok(ProjectState {
+            version,
+            base_branch: None,
+            virtual_branches: vec![],
+            remote_branches: vec![],
+        });
+    };
+
+    let virtual_branches = super::list_virtual_branches(gb_repository, project_repository)?;
+    let remote_branches = super::list_remote_branches(gb_repository, project_repository)?;
+
+    Ok(ProjectState {
+        version,
+        base_branch: Some(base_branch),
+        virtual_branches,
+        remote_branches,
+    })
+}
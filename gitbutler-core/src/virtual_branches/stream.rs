@@ -0,0 +1,91 @@
+use serde::Serialize;
+
+use super::{BranchId, VirtualBranch, VirtualBranchFile};
+
+// chunks are cut once this many bytes of hunk diffs have been accumulated
+pub const MAX_CHUNK_BYTES: usize = 512 * 1024;
+
+// the stream the watchers send the branches that are done on, when listing them takes
+// longer than they can wait for
+pub const STATUS_STREAM: &str = "status";
+
+// One piece of a streamed list of virtual branches.
+//
+// A stream starts with `Branches`, the branches with no files, followed by `Files`
+// chunks carrying the files of each branch in order, and ends with `Done`.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Chunk {
+    #[serde(rename_all = "camelCase")]
+    Branches {
+        branches: Vec<VirtualBranch>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Files {
+        branch_id: BranchId,
+        files: Vec<VirtualBranchFile>,
+    },
+    Done,
+}
+
+fn file_size(file: &VirtualBranchFile) -> usize {
+    file.hunks.iter().map(|hunk| hunk.diff.len()).sum()
+}
+
+// splits a list of virtual branches into chunks, none of which carries noticeably more than
+// `max_chunk_bytes` of diffs, unless a single file is larger than that. Chunks are cut as
+// they are taken and the files are moved into them, so that sending them one at a time
+// never takes more memory than the branches and the chunk being sent.
+pub fn chunks(branches: Vec<VirtualBranch>, max_chunk_bytes: usize) -> impl Iterator<Item = Chunk> {
+    let mut skeletons = Vec::with_capacity(branches.len());
+    let mut files = Vec::with_capacity(branches.len());
+    for mut branch in branches {
+        files.push((branch.id, std::mem::take(&mut branch.files)));
+        skeletons.push(branch);
+    }
+    std::iter::once(Chunk::Branches {
+        branches: skeletons,
+    })
+    .chain(
+        files
+            .into_iter()
+            .flat_map(move |(branch_id, files)| FilesChunks {
+                branch_id,
+                files: files.into_iter(),
+                pending: None,
+                max_chunk_bytes,
+            }),
+    )
+    .chain(std::iter::once(Chunk::Done))
+}
+
+// the chunks of the files of a branch
+struct FilesChunks {
+    branch_id: BranchId,
+    files: std::vec::IntoIter<VirtualBranchFile>,
+    // the file that didn't fit in the last chunk
+    pending: Option<VirtualBranchFile>,
+    max_chunk_bytes: usize,
+}
+
+impl Iterator for FilesChunks {
+    type Item = Chunk;
+
+    fn next(&mut self) -> Option<Chunk> {
+        let mut current = vec![];
+        let mut current_size = 0;
+        while let Some(file) = self.pending.take().or_else(|| self.files.next()) {
+            let size = file_size(&file);
+            if !current.is_empty() && current_size + size > self.max_chunk_bytes {
+                self.pending = Some(file);
+                break;
+            }
+            current_size += size;
+            current.push(file);
+        }
+        (!current.is_empty()).then_some(Chunk::Files {
+            branch_id: self.branch_id,
+            files: current,
+        })
+    }
+}
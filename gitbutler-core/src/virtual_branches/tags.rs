@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    gb_repository,
+    git::{self, credentials},
+    keys,
+    project_repository::{self, LogUntil},
+    reader, sessions, users,
+};
+
+use super::{branch, errors, BranchId, Iterator};
+
+// the line ssh, gpg and x509 signatures start with, git appends them to the message
+const SIGNATURE_PREFIX: &str = "\n-----BEGIN ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TagKind {
+    #[default]
+    Lightweight,
+    Annotated,
+    /// An annotated tag signed with the GitButler key.
+    Signed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCreateRequest {
+    pub name: String,
+    /// A commit of a virtual branch or of the target branch.
+    pub target: git::Oid,
+    #[serde(default)]
+    pub kind: TagKind,
+    /// The message of annotated and signed tags.
+    #[serde(default)]
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub name: String,
+    /// The commit the tag points to.
+    pub target: git::Oid,
+    /// The message of annotated tags, without their signature.
+    pub message: Option<String>,
+    pub signed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchTags {
+    pub branch_id: BranchId,
+    /// The tags of the commits reachable from the head of the branch.
+    pub tags: Vec<Tag>,
+}
+
+pub fn create_tag(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    request: &TagCreateRequest,
+    signing_key: Option<&keys::PrivateKey>,
+    user: Option<&users::User>,
+) -> Result<Tag, errors::CreateTagError> {
+    let repo = &project_repository.git_repository;
+    if !git2::Reference::is_valid_name(&format!("refs/tags/{}", request.name)) {
+        return Err(errors::CreateTagError::InvalidName(request.name.clone()));
+    }
+    if repo.find_tag(&request.name).is_ok() {
+        return Err(errors::CreateTagError::TagExists(request.name.clone()));
+    }
+
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::CreateTagError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+    match repo.find_commit(request.target) {
+        Ok(_) => {}
+        Err(git::Error::NotFound(_)) => {
+            return Err(errors::CreateTagError::CommitNotFound(request.target));
+        }
+        Err(error) => return Err(errors::CreateTagError::Other(error.into())),
+    }
+    let heads = read_branches(gb_repository)?
+        .into_iter()
+        .map(|branch| branch.head)
+        .chain(Some(default_target.sha));
+    let mut on_branch = false;
+    for head in heads {
+        if project_repository.is_reachable_from(head, request.target)? {
+            on_branch = true;
+            break;
+        }
+    }
+    if !on_branch {
+        return Err(errors::CreateTagError::CommitNotOnBranch(request.target));
+    }
+
+    let (tagger, _) = project_repository.git_signatures(user)?;
+    match request.kind {
+        TagKind::Lightweight => repo.tag_lightweight(&request.name, request.target, false),
+        TagKind::Annotated => repo.tag_annotated(
+            &request.name,
+            request.target,
+            &tagger,
+            &request.message,
+            false,
+        ),
+        TagKind::Signed => {
+            let key = signing_key.context("signed tags need a signing key")?;
+            repo.tag_signed(
+                &request.name,
+                request.target,
+                &tagger,
+                &request.message,
+                key,
+                false,
+            )
+        }
+    }
+    .context("failed to create tag")?;
+
+    read_tag(repo, &request.name).map_err(Into::into)
+}
+
+/// Pushes the tags `names` to `remote`, the remote of the target branch by default.
+pub fn push_tags(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    names: &[String],
+    remote: Option<&str>,
+    credentials: &credentials::Helper,
+) -> Result<(), errors::PushTagsError> {
+    for name in names {
+        if project_repository.git_repository.find_tag(name).is_err() {
+            return Err(errors::PushTagsError::TagNotFound(name.clone()));
+        }
+    }
+    let remote = match remote {
+        Some(remote) => remote.to_string(),
+        None => gb_repository
+            .default_target()
+            .context("failed to get default target")?
+            .ok_or_else(|| {
+                errors::PushTagsError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                    project_id: project_repository.project().id,
+                })
+            })?
+            .branch
+            .remote()
+            .to_string(),
+    };
+    project_repository
+        .push_tags(&remote, names, credentials)
+        .map_err(Into::into)
+}
+
+/// The tags of the repository, only the ones matching the glob `pattern` if there is one,
+/// sorted by name. Tags of trees and blobs are left out.
+pub fn list_tags(
+    project_repository: &project_repository::Repository,
+    pattern: Option<&str>,
+) -> Result<Vec<Tag>, errors::ListTagsError> {
+    let repo = &project_repository.git_repository;
+    let mut names = repo.tag_names(pattern).context("failed to list tags")?;
+    names.sort();
+    Ok(names
+        .iter()
+        .filter_map(|name| match read_tag(repo, name) {
+            Ok(tag) => Some(tag),
+            Err(error) => {
+                tracing::debug!(?error, %name, "skipping tag");
+                None
+            }
+        })
+        .collect())
+}
+
+/// The tags reachable from the head of every virtual branch.
+pub fn list_branch_tags(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<Vec<BranchTags>, errors::ListTagsError> {
+    let tags = list_tags(project_repository, None)?;
+    read_branches(gb_repository)?
+        .into_iter()
+        .map(|branch| {
+            // one walk of the branch rather than an ancestry check for every tag
+            let commits = project_repository
+                .l(branch.head, LogUntil::End)?
+                .into_iter()
+                .collect::<HashSet<_>>();
+            Ok(BranchTags {
+                branch_id: branch.id,
+                tags: tags
+                    .iter()
+                    .filter(|tag| commits.contains(&tag.target))
+                    .cloned()
+                    .collect(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()
+        .map_err(Into::into)
+}
+
+/// Deletes the tag `name`, from `remote` first if one is given so that a failure to reach
+/// it leaves the tag as it was.
+pub fn delete_tag(
+    project_repository: &project_repository::Repository,
+    name: &str,
+    remote: Option<&str>,
+    credentials: &credentials::Helper,
+) -> Result<(), errors::DeleteTagError> {
+    let repo = &project_repository.git_repository;
+    if repo.find_tag(name).is_err() {
+        return Err(errors::DeleteTagError::TagNotFound(name.to_string()));
+    }
+    if let Some(remote) = remote {
+        project_repository.delete_remote_tags(remote, &[name.to_string()], credentials)?;
+    }
+    repo.tag_delete(name)
+        .with_context(|| format!("failed to delete tag {name}"))?;
+    Ok(())
+}
+
+fn read_tag(repo: &git::Repository, name: &str) -> Result<Tag> {
+    let (target, message) = repo
+        .find_tag(name)
+        .with_context(|| format!("failed to find tag {name}"))?;
+    let (message, signed) = match message {
+        Some(message) => match message.split_once(SIGNATURE_PREFIX) {
+            Some((message, _)) => (Some(format!("{message}\n")), true),
+            None => (Some(message), false),
+        },
+        None => (None, false),
+    };
+    Ok(Tag {
+        name: name.to_string(),
+        target,
+        message,
+        signed,
+    })
+}
+
+fn read_branches(gb_repository: &gb_repository::Repository) -> Result<Vec<branch::Branch>> {
+    let session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let session_reader = sessions::Reader::open(gb_repository, &session)
+        .context("failed to open current session")?;
+    Iterator::new(&session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<branch::Branch>, reader::Error>>()
+        .context("failed to read virtual branches")
+}
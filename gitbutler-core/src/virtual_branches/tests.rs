@@ -12,42 +12,14 @@ use pretty_assertions::{assert_eq, assert_ne};
 use std::os::unix::{fs::symlink, prelude::*};
 
 use crate::{
-    gb_repository, git, project_repository, reader, sessions,
-    test_utils::{self, empty_bare_repository, Case, Suite},
+    gb_repository, git, project_repository, projects, reader, sessions,
+    test_utils::{self, set_test_target, Case, Suite},
     virtual_branches::errors::CommitError,
 };
 
 use super::*;
 use branch::{BranchCreateRequest, Ownership};
 
-pub fn set_test_target(
-    gb_repo: &gb_repository::Repository,
-    project_repository: &project_repository::Repository,
-) -> Result<()> {
-    let remote_repo = empty_bare_repository();
-    let mut remote = project_repository
-        .git_repository
-        .remote(
-            "origin",
-            &remote_repo.path().to_str().unwrap().parse().unwrap(),
-        )
-        .expect("failed to add remote");
-    remote.push(&["refs/heads/master:refs/heads/master"], None)?;
-
-    target::Writer::new(gb_repo)?
-        .write_default(&target::Target {
-            branch: "refs/remotes/origin/master".parse().unwrap(),
-            remote_url: remote_repo.path().to_str().unwrap().parse().unwrap(),
-            sha: remote_repo.head().unwrap().target().unwrap(),
-        })
-        .expect("failed to write target");
-
-    super::integration::update_gitbutler_integration(gb_repo, project_repository)
-        .expect("failed to update integration");
-
-    Ok(())
-}
-
 #[test]
 fn test_commit_on_branch_then_change_file_then_get_status() -> Result<()> {
     let Case {
@@ -304,6 +276,71 @@ fn test_track_binary_files() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_track_lfs_files_by_pointer() -> Result<()> {
+    let has_lfs = std::process::Command::new("git")
+        .args(["lfs", "version"])
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !has_lfs {
+        // needs the git lfs executable
+        return Ok(());
+    }
+
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from(".gitattributes"),
+        "*.bin filter=lfs diff=lfs merge=lfs -text\n",
+    )]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    std::fs::write(
+        std::path::Path::new(&project.path).join("large.bin"),
+        [0_u8, 1, 2, 3].repeat(1024),
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let file = &branches[0].files[0];
+    assert_eq!(file.path, path::Path::new("large.bin"));
+    assert!(file.binary);
+
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "test commit",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    // the pointer is committed instead of the content, which is then unchanged
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    assert!(branches[0].files.is_empty());
+    let commit_obj = project_repository
+        .git_repository
+        .find_commit(branches[0].commits[0].id)?;
+    let files = tree_to_entry_list(&project_repository.git_repository, &commit_obj.tree()?);
+    let (_, _, content, _) = files.iter().find(|(path, ..)| path == "large.bin").unwrap();
+    assert!(content.starts_with("version https://git-lfs.github.com/spec/v1"));
+
+    Ok(())
+}
+
 #[test]
 fn test_create_branch_with_ownership() -> Result<()> {
     let Case {
@@ -1282,33 +1319,26 @@ fn test_unapply_ownership_partial() -> Result<()> {
     Ok(())
 }
 
+const RENAMED_CONTENT: &str = "line1\nline2\nline3\nline4\nline5\nline6\nline7\nline8\n";
+
 #[test]
-fn test_apply_unapply_branch() -> Result<()> {
+fn test_rename_file_then_commit() -> Result<()> {
     let Case {
-        project,
         project_repository,
+        project,
         gb_repository,
         ..
-    } = Suite::default().new_case();
-
-    // create a commit and set the target
-    let file_path = std::path::Path::new("test.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path),
-        "line1\nline2\nline3\nline4\n",
-    )?;
-    test_utils::commit_all(&project_repository.git_repository);
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("old.txt"),
+        RENAMED_CONTENT,
+    )]));
 
     set_test_target(&gb_repository, &project_repository)?;
 
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path),
-        "line1\nline2\nline3\nline4\nbranch1\n",
-    )?;
-    let file_path2 = std::path::Path::new("test2.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path2),
-        "line5\nline6\n",
+    fs::remove_file(project.path.join("old.txt"))?;
+    fs::write(
+        project.path.join("new.txt"),
+        RENAMED_CONTENT.replace("line8", "changed"),
     )?;
 
     let branch1_id = create_virtual_branch(
@@ -1318,106 +1348,74 @@ fn test_apply_unapply_branch() -> Result<()> {
     )
     .expect("failed to create virtual branch")
     .id;
-    let branch2_id = create_virtual_branch(
-        &gb_repository,
-        &project_repository,
-        &BranchCreateRequest::default(),
-    )
-    .expect("failed to create virtual branch")
-    .id;
 
-    update_branch(
-        &gb_repository,
-        &project_repository,
-        branch::BranchUpdateRequest {
-            id: branch2_id,
-            ownership: Some("test2.txt:1-3".parse()?),
-            ..Default::default()
-        },
-    )?;
-
-    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path))?;
+    // one renamed file with the line that changed, rather than a deletion and an addition
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch1.files.len(), 1);
+    let file = &branch1.files[0];
+    assert_eq!(file.path, path::PathBuf::from("new.txt"));
+    assert_eq!(file.renamed_from, Some(path::PathBuf::from("old.txt")));
+    assert_eq!(file.hunks.len(), 2);
     assert_eq!(
-        "line1\nline2\nline3\nline4\nbranch1\n",
-        String::from_utf8(contents)?
+        file.hunks[0].diff,
+        "rename from old.txt\nrename to new.txt\n"
     );
-    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path2))?;
-    assert_eq!("line5\nline6\n", String::from_utf8(contents)?);
-
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-    assert_eq!(branch.files.len(), 1);
-    assert!(branch.active);
-
-    unapply_branch(&gb_repository, &project_repository, &branch1_id)?;
 
-    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path))?;
-    assert_eq!("line1\nline2\nline3\nline4\n", String::from_utf8(contents)?);
-    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path2))?;
-    assert_eq!("line5\nline6\n", String::from_utf8(contents)?);
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "rename",
+        None,
+        None,
+        None,
+        false,
+    )?;
 
     let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-    assert_eq!(branch.files.len(), 1);
-    assert!(!branch.active);
-
-    apply_branch(&gb_repository, &project_repository, &branch1_id, None, None)?;
-    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path))?;
-    assert_eq!(
-        "line1\nline2\nline3\nline4\nbranch1\n",
-        String::from_utf8(contents)?
-    );
-    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path2))?;
-    assert_eq!("line5\nline6\n", String::from_utf8(contents)?);
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert!(branch1.files.is_empty());
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-    assert_eq!(branch.files.len(), 1);
-    assert!(branch.active);
+    let commit = project_repository
+        .git_repository
+        .find_commit(branch1.commits[0].id)?;
+    let list = tree_to_entry_list(&project_repository.git_repository, &commit.tree()?);
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].0, "new.txt");
+    assert_eq!(list[0].2, RENAMED_CONTENT.replace("line8", "changed"));
 
     Ok(())
 }
 
 #[test]
-fn test_apply_unapply_added_deleted_files() -> Result<()> {
+fn test_renamed_file_stays_on_one_branch() -> Result<()> {
     let Case {
-        project,
         project_repository,
+        project,
         gb_repository,
         ..
-    } = Suite::default().new_case();
-
-    // create a commit and set the target
-    let file_path = std::path::Path::new("test.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path),
-        "file1\n",
-    )?;
-    let file_path2 = std::path::Path::new("test2.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path2),
-        "file2\n",
-    )?;
-    test_utils::commit_all(&project_repository.git_repository);
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("old.txt"),
+        RENAMED_CONTENT,
+    )]));
 
     set_test_target(&gb_repository, &project_repository)?;
 
-    // rm file_path2, add file3
-    std::fs::remove_file(std::path::Path::new(&project.path).join(file_path2))?;
-    let file_path3 = std::path::Path::new("test3.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path3),
-        "file3\n",
+    fs::remove_file(project.path.join("old.txt"))?;
+    fs::write(
+        project.path.join("new.txt"),
+        RENAMED_CONTENT.replace("line8", "changed"),
     )?;
 
-    let branch2_id = create_virtual_branch(
+    let branch1_id = create_virtual_branch(
         &gb_repository,
         &project_repository,
         &BranchCreateRequest::default(),
     )
     .expect("failed to create virtual branch")
     .id;
-    let branch3_id = create_virtual_branch(
+    let branch2_id = create_virtual_branch(
         &gb_repository,
         &project_repository,
         &BranchCreateRequest::default(),
@@ -1425,77 +1423,98 @@ fn test_apply_unapply_added_deleted_files() -> Result<()> {
     .expect("failed to create virtual branch")
     .id;
 
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    let changed = &branch1.files[0].hunks[1];
+
+    // moving the change of the renamed file without the rename moves it all
     update_branch(
         &gb_repository,
         &project_repository,
         branch::BranchUpdateRequest {
             id: branch2_id,
-            ownership: Some("test2.txt:0-0".parse()?),
+            ownership: Some(format!("new.txt:{}-{}", changed.start, changed.end).parse()?),
             ..Default::default()
         },
     )?;
-    update_branch(
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let with_file = branches
+        .iter()
+        .filter(|branch| !branch.files.is_empty())
+        .collect::<Vec<_>>();
+    assert_eq!(with_file.len(), 1);
+    assert_eq!(with_file[0].files.len(), 1);
+    assert_eq!(with_file[0].files[0].hunks.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_unapply_renamed_file() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("old.txt"),
+        RENAMED_CONTENT,
+    )]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    fs::rename(project.path.join("old.txt"), project.path.join("new.txt"))?;
+
+    create_virtual_branch(
         &gb_repository,
         &project_repository,
-        branch::BranchUpdateRequest {
-            id: branch3_id,
-            ownership: Some("test3.txt:1-2".parse()?),
-            ..Default::default()
-        },
-    )?;
-
-    unapply_branch(&gb_repository, &project_repository, &branch2_id)?;
-    // check that file2 is back
-    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path2))?;
-    assert_eq!("file2\n", String::from_utf8(contents)?);
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch");
 
-    unapply_branch(&gb_repository, &project_repository, &branch3_id)?;
-    // check that file3 is gone
-    assert!(!std::path::Path::new(&project.path)
-        .join(file_path3)
-        .exists());
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    assert_eq!(branches[0].files.len(), 1);
+    assert_eq!(branches[0].files[0].hunks.len(), 1);
 
-    apply_branch(&gb_repository, &project_repository, &branch2_id, None, None)?;
-    // check that file2 is gone
-    assert!(!std::path::Path::new(&project.path)
-        .join(file_path2)
-        .exists());
+    unapply_ownership(
+        &gb_repository,
+        &project_repository,
+        &"new.txt:0-0".parse().unwrap(),
+    )
+    .unwrap();
 
-    apply_branch(&gb_repository, &project_repository, &branch3_id, None, None)?;
-    // check that file3 is back
-    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path3))?;
-    assert_eq!("file3\n", String::from_utf8(contents)?);
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    assert_eq!(branches[0].files.len(), 0);
+    assert!(!project.path.join("new.txt").exists());
+    assert_eq!(
+        fs::read_to_string(project.path.join("old.txt"))?,
+        RENAMED_CONTENT
+    );
 
     Ok(())
 }
 
 #[test]
-fn test_detect_mergeable_branch() -> Result<()> {
+fn test_copy_file_then_commit() -> Result<()> {
     let Case {
         project,
-        project_repository,
         gb_repository,
         ..
-    } = Suite::default().new_case();
-
-    // create a commit and set the target
-    let file_path = std::path::Path::new("test.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path),
-        "line1\nline2\nline3\nline4\n",
-    )?;
-    test_utils::commit_all(&project_repository.git_repository);
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("a.txt"),
+        RENAMED_CONTENT,
+    )]));
+    let project_repository = project_repository::Repository::open(&projects::Project {
+        detect_copies: Some(true),
+        ..project.clone()
+    })?;
 
     set_test_target(&gb_repository, &project_repository)?;
 
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path),
-        "line1\nline2\nline3\nline4\nbranch1\n",
-    )?;
-    let file_path4 = std::path::Path::new("test4.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path4),
-        "line5\nline6\n",
+    fs::write(
+        project.path.join("b.txt"),
+        RENAMED_CONTENT.replace("line8", "changed"),
     )?;
 
     let branch1_id = create_virtual_branch(
@@ -1505,222 +1524,189 @@ fn test_detect_mergeable_branch() -> Result<()> {
     )
     .expect("failed to create virtual branch")
     .id;
-    let branch2_id = create_virtual_branch(
-        &gb_repository,
-        &project_repository,
-        &BranchCreateRequest::default(),
-    )
-    .expect("failed to create virtual branch")
-    .id;
 
-    let current_session = gb_repository.get_or_create_current_session()?;
-    let current_session_reader = sessions::Reader::open(&gb_repository, &current_session)?;
-    let branch_reader = branch::Reader::new(&current_session_reader);
-    let branch_writer = branch::Writer::new(&gb_repository)?;
+    // only the line that changed from the file it is a copy of
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch1.files.len(), 1);
+    let file = &branch1.files[0];
+    assert_eq!(file.path, path::PathBuf::from("b.txt"));
+    assert_eq!(file.copied_from, Some(path::PathBuf::from("a.txt")));
+    assert_eq!(file.renamed_from, None);
+    assert_eq!(file.hunks.len(), 2);
+    assert_eq!(file.hunks[0].diff, "copy from a.txt\ncopy to b.txt\n");
 
-    update_branch(
+    commit(
         &gb_repository,
         &project_repository,
-        branch::BranchUpdateRequest {
-            id: branch2_id,
-            ownership: Some("test4.txt:1-3".parse()?),
-            ..Default::default()
-        },
-    )
-    .expect("failed to update branch");
+        &branch1_id,
+        "copy",
+        None,
+        None,
+        None,
+        false,
+    )?;
 
-    // unapply both branches and create some conflicting ones
-    unapply_branch(&gb_repository, &project_repository, &branch1_id)?;
-    unapply_branch(&gb_repository, &project_repository, &branch2_id)?;
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert!(branch1.files.is_empty());
 
-    project_repository
-        .git_repository
-        .set_head(&"refs/heads/master".parse().unwrap())?;
-    project_repository
+    let commit = project_repository
         .git_repository
-        .checkout_head(Some(&mut git2::build::CheckoutBuilder::default().force()))?;
+        .find_commit(branch1.commits[0].id)?;
+    let list = tree_to_entry_list(&project_repository.git_repository, &commit.tree()?);
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[0].0, "a.txt");
+    assert_eq!(list[0].2, RENAMED_CONTENT);
+    assert_eq!(list[1].0, "b.txt");
+    assert_eq!(list[1].2, RENAMED_CONTENT.replace("line8", "changed"));
 
-    // create an upstream remote conflicting commit
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path),
-        "line1\nline2\nline3\nline4\nupstream\n",
-    )?;
-    test_utils::commit_all(&project_repository.git_repository);
-    let up_target = project_repository
-        .git_repository
-        .head()
-        .unwrap()
-        .target()
-        .unwrap();
-    project_repository.git_repository.reference(
-        &"refs/remotes/origin/remote_branch".parse().unwrap(),
-        up_target,
-        true,
-        "update target",
-    )?;
+    Ok(())
+}
 
-    // revert content and write a mergeable branch
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path),
-        "line1\nline2\nline3\nline4\n",
-    )?;
-    let file_path3 = std::path::Path::new("test3.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path3),
-        "file3\n",
-    )?;
-    test_utils::commit_all(&project_repository.git_repository);
-    let up_target = project_repository
-        .git_repository
-        .head()
-        .unwrap()
-        .target()
-        .unwrap();
-    project_repository.git_repository.reference(
-        &"refs/remotes/origin/remote_branch2".parse().unwrap(),
-        up_target,
-        true,
-        "update target",
-    )?;
-    // remove file_path3
-    std::fs::remove_file(std::path::Path::new(&project.path).join(file_path3))?;
+#[test]
+fn test_normalize_line_endings() -> Result<()> {
+    let Case {
+        project,
+        project_repository,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([
+        (path::PathBuf::from("crlf.txt"), "line1\nline2\n"),
+        (path::PathBuf::from("changed.txt"), "line1\nline2\n"),
+    ]));
 
-    project_repository
-        .git_repository
-        .set_head(&"refs/heads/gitbutler/integration".parse().unwrap())?;
-    project_repository
-        .git_repository
-        .checkout_head(Some(&mut git2::build::CheckoutBuilder::default().force()))?;
+    set_test_target(&gb_repository, &project_repository)?;
 
-    // create branches that conflict with our earlier branches
-    create_virtual_branch(
-        &gb_repository,
-        &project_repository,
-        &BranchCreateRequest::default(),
-    )
-    .expect("failed to create virtual branch");
-    let branch4_id = create_virtual_branch(
-        &gb_repository,
-        &project_repository,
-        &BranchCreateRequest::default(),
-    )
-    .expect("failed to create virtual branch")
-    .id;
+    fs::write(project.path.join("crlf.txt"), "line1\r\nline2\r\n")?;
+    fs::write(project.path.join("changed.txt"), "line1\r\nline3\r\n")?;
 
-    // branch3 conflicts with branch1 and remote_branch
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path),
-        "line1\nline2\nline3\nline4\nbranch3\n",
-    )?;
+    let files = list_line_endings(&gb_repository, &project_repository)?;
+    assert_eq!(
+        files,
+        vec![
+            FileLineEndings {
+                path: path::PathBuf::from("changed.txt"),
+                churn: None,
+            },
+            FileLineEndings {
+                path: path::PathBuf::from("crlf.txt"),
+                churn: Some(git::eol::Churn {
+                    line_endings: true,
+                    byte_order_mark: false,
+                }),
+            },
+        ]
+    );
 
-    // branch4 conflicts with branch2
-    let file_path2 = std::path::Path::new("test2.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path2),
-        "line1\nline2\nline3\nline4\nbranch4\n",
+    assert!(matches!(
+        normalize_line_endings(
+            &gb_repository,
+            &project_repository,
+            &[
+                path::PathBuf::from("crlf.txt"),
+                path::PathBuf::from("changed.txt")
+            ]
+        ),
+        Err(errors::NormalizeLineEndingsError::NotChurn(_))
+    ));
+    assert_eq!(
+        fs::read_to_string(project.path.join("crlf.txt"))?,
+        "line1\r\nline2\r\n"
+    );
+
+    normalize_line_endings(
+        &gb_repository,
+        &project_repository,
+        &[path::PathBuf::from("crlf.txt")],
     )?;
+    assert_eq!(
+        fs::read_to_string(project.path.join("crlf.txt"))?,
+        "line1\nline2\n"
+    );
+    let files = list_line_endings(&gb_repository, &project_repository)?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, path::PathBuf::from("changed.txt"));
 
-    let mut branch4 = branch_reader.read(&branch4_id)?;
-    branch4.ownership = Ownership {
-        files: vec!["test2.txt:1-6".parse()?],
-    };
-    branch_writer.write(&mut branch4)?;
+    Ok(())
+}
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    assert_eq!(branches.len(), 4);
+#[test]
+fn test_normalize_line_endings_checked_out_with_crlf() -> Result<()> {
+    let Case {
+        project,
+        project_repository,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("bom.txt"),
+        "line1\nline2\n",
+    )]));
 
-    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-    assert!(!branch1.active);
-    assert!(
-        !is_virtual_branch_mergeable(&gb_repository, &project_repository, &branch1.id).unwrap()
-    );
+    set_test_target(&gb_repository, &project_repository)?;
 
-    let branch2 = &branches.iter().find(|b| b.id == branch2_id).unwrap();
-    assert!(!branch2.active);
-    assert!(is_virtual_branch_mergeable(&gb_repository, &project_repository, &branch2.id).unwrap());
+    fs::create_dir_all(project.path.join(".git/info"))?;
+    fs::write(
+        project.path.join(".git/info/attributes"),
+        "*.txt eol=crlf\n",
+    )?;
+    fs::write(project.path.join("bom.txt"), "\u{feff}line1\r\nline2\r\n")?;
 
-    let remotes =
-        list_remote_branches(&gb_repository, &project_repository).expect("failed to list remotes");
-    let remote1 = &remotes
-        .iter()
-        .find(|b| b.name.to_string() == "refs/remotes/origin/remote_branch")
-        .unwrap();
-    assert!(!is_remote_branch_mergeable(
-        &gb_repository,
-        &project_repository,
-        &"refs/remotes/origin/remote_branch".parse().unwrap()
-    )
-    .unwrap());
-    // assert_eq!(remote1.commits.len(), 1);
+    let files = list_line_endings(&gb_repository, &project_repository)?;
+    assert_eq!(
+        files,
+        vec![FileLineEndings {
+            path: path::PathBuf::from("bom.txt"),
+            churn: Some(git::eol::Churn {
+                line_endings: false,
+                byte_order_mark: true,
+            }),
+        }]
+    );
 
-    let remote2 = &remotes
-        .iter()
-        .find(|b| b.name.to_string() == "refs/remotes/origin/remote_branch2")
-        .unwrap();
-    assert!(is_remote_branch_mergeable(
+    normalize_line_endings(
         &gb_repository,
         &project_repository,
-        &"refs/remotes/origin/remote_branch2".parse().unwrap()
-    )
-    .unwrap());
-    // assert_eq!(remote2.commits.len(), 2);
+        &[path::PathBuf::from("bom.txt")],
+    )?;
+    assert_eq!(
+        fs::read_to_string(project.path.join("bom.txt"))?,
+        "line1\r\nline2\r\n"
+    );
+    assert!(list_line_endings(&gb_repository, &project_repository)?.is_empty());
 
     Ok(())
 }
 
 #[test]
-fn test_upstream_integrated_vbranch() -> Result<()> {
-    // ok, we need a vbranch with some work and an upstream target that also includes that work, but the base is behind
-    // plus a branch with work not in upstream so we can see that it is not included in the vbranch
-
+fn test_apply_unapply_branch() -> Result<()> {
     let Case {
-        project_repository,
         project,
+        project_repository,
         gb_repository,
         ..
-    } = Suite::default().new_case_with_files(HashMap::from([
-        (path::PathBuf::from("test.txt"), "file1\n"),
-        (path::PathBuf::from("test2.txt"), "file2\n"),
-        (path::PathBuf::from("test3.txt"), "file3\n"),
-    ]));
-
-    let base_commit = project_repository
-        .git_repository
-        .head()
-        .unwrap()
-        .target()
-        .unwrap();
+    } = Suite::default().new_case();
 
+    // create a commit and set the target
+    let file_path = std::path::Path::new("test.txt");
     std::fs::write(
-        std::path::Path::new(&project.path).join("test.txt"),
-        "file1\nversion2\n",
+        std::path::Path::new(&project.path).join(file_path),
+        "line1\nline2\nline3\nline4\n",
     )?;
     test_utils::commit_all(&project_repository.git_repository);
 
-    let upstream_commit = project_repository
-        .git_repository
-        .head()
-        .unwrap()
-        .target()
-        .unwrap();
-    project_repository.git_repository.reference(
-        &"refs/remotes/origin/master".parse().unwrap(),
-        upstream_commit,
-        true,
-        "update target",
-    )?;
+    set_test_target(&gb_repository, &project_repository)?;
 
-    target::Writer::new(&gb_repository)?.write_default(&target::Target {
-        branch: "refs/remotes/origin/master".parse().unwrap(),
-        remote_url: "http://origin.com/project".to_string(),
-        sha: base_commit,
-    })?;
-    project_repository
-        .git_repository
-        .remote("origin", &"http://origin.com/project".parse().unwrap())?;
-    super::integration::update_gitbutler_integration(&gb_repository, &project_repository)?;
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path),
+        "line1\nline2\nline3\nline4\nbranch1\n",
+    )?;
+    let file_path2 = std::path::Path::new("test2.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path2),
+        "line5\nline6\n",
+    )?;
 
-    // create vbranches, one integrated, one not
     let branch1_id = create_virtual_branch(
         &gb_repository,
         &project_repository,
@@ -1735,211 +1721,1735 @@ fn test_upstream_integrated_vbranch() -> Result<()> {
     )
     .expect("failed to create virtual branch")
     .id;
-    let branch3_id = create_virtual_branch(
+
+    update_branch(
+        &gb_repository,
+        &project_repository,
+        branch::BranchUpdateRequest {
+            id: branch2_id,
+            ownership: Some("test2.txt:1-3".parse()?),
+            ..Default::default()
+        },
+    )?;
+
+    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path))?;
+    assert_eq!(
+        "line1\nline2\nline3\nline4\nbranch1\n",
+        String::from_utf8(contents)?
+    );
+    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path2))?;
+    assert_eq!("line5\nline6\n", String::from_utf8(contents)?);
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch.files.len(), 1);
+    assert!(branch.active);
+
+    unapply_branch(&gb_repository, &project_repository, &branch1_id)?;
+
+    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path))?;
+    assert_eq!("line1\nline2\nline3\nline4\n", String::from_utf8(contents)?);
+    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path2))?;
+    assert_eq!("line5\nline6\n", String::from_utf8(contents)?);
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch.files.len(), 1);
+    assert!(!branch.active);
+
+    apply_branch(&gb_repository, &project_repository, &branch1_id, None, None)?;
+    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path))?;
+    assert_eq!(
+        "line1\nline2\nline3\nline4\nbranch1\n",
+        String::from_utf8(contents)?
+    );
+    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path2))?;
+    assert_eq!("line5\nline6\n", String::from_utf8(contents)?);
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch.files.len(), 1);
+    assert!(branch.active);
+
+    Ok(())
+}
+
+#[test]
+fn test_apply_unapply_added_deleted_files() -> Result<()> {
+    let Case {
+        project,
+        project_repository,
+        gb_repository,
+        ..
+    } = Suite::default().new_case();
+
+    // create a commit and set the target
+    let file_path = std::path::Path::new("test.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path),
+        "file1\n",
+    )?;
+    let file_path2 = std::path::Path::new("test2.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path2),
+        "file2\n",
+    )?;
+    test_utils::commit_all(&project_repository.git_repository);
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    // rm file_path2, add file3
+    std::fs::remove_file(std::path::Path::new(&project.path).join(file_path2))?;
+    let file_path3 = std::path::Path::new("test3.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path3),
+        "file3\n",
+    )?;
+
+    let branch2_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+    let branch3_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    update_branch(
+        &gb_repository,
+        &project_repository,
+        branch::BranchUpdateRequest {
+            id: branch2_id,
+            ownership: Some("test2.txt:0-0".parse()?),
+            ..Default::default()
+        },
+    )?;
+    update_branch(
+        &gb_repository,
+        &project_repository,
+        branch::BranchUpdateRequest {
+            id: branch3_id,
+            ownership: Some("test3.txt:1-2".parse()?),
+            ..Default::default()
+        },
+    )?;
+
+    unapply_branch(&gb_repository, &project_repository, &branch2_id)?;
+    // check that file2 is back
+    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path2))?;
+    assert_eq!("file2\n", String::from_utf8(contents)?);
+
+    unapply_branch(&gb_repository, &project_repository, &branch3_id)?;
+    // check that file3 is gone
+    assert!(!std::path::Path::new(&project.path)
+        .join(file_path3)
+        .exists());
+
+    apply_branch(&gb_repository, &project_repository, &branch2_id, None, None)?;
+    // check that file2 is gone
+    assert!(!std::path::Path::new(&project.path)
+        .join(file_path2)
+        .exists());
+
+    apply_branch(&gb_repository, &project_repository, &branch3_id, None, None)?;
+    // check that file3 is back
+    let contents = std::fs::read(std::path::Path::new(&project.path).join(file_path3))?;
+    assert_eq!("file3\n", String::from_utf8(contents)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_detect_mergeable_branch() -> Result<()> {
+    let Case {
+        project,
+        project_repository,
+        gb_repository,
+        ..
+    } = Suite::default().new_case();
+
+    // create a commit and set the target
+    let file_path = std::path::Path::new("test.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path),
+        "line1\nline2\nline3\nline4\n",
+    )?;
+    test_utils::commit_all(&project_repository.git_repository);
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path),
+        "line1\nline2\nline3\nline4\nbranch1\n",
+    )?;
+    let file_path4 = std::path::Path::new("test4.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path4),
+        "line5\nline6\n",
+    )?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+    let branch2_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    let current_session = gb_repository.get_or_create_current_session()?;
+    let current_session_reader = sessions::Reader::open(&gb_repository, &current_session)?;
+    let branch_reader = branch::Reader::new(&current_session_reader);
+    let branch_writer = branch::Writer::new(&gb_repository)?;
+
+    update_branch(
+        &gb_repository,
+        &project_repository,
+        branch::BranchUpdateRequest {
+            id: branch2_id,
+            ownership: Some("test4.txt:1-3".parse()?),
+            ..Default::default()
+        },
+    )
+    .expect("failed to update branch");
+
+    // unapply both branches and create some conflicting ones
+    unapply_branch(&gb_repository, &project_repository, &branch1_id)?;
+    unapply_branch(&gb_repository, &project_repository, &branch2_id)?;
+
+    project_repository
+        .git_repository
+        .set_head(&"refs/heads/master".parse().unwrap())?;
+    project_repository
+        .git_repository
+        .checkout_head(Some(&mut git2::build::CheckoutBuilder::default().force()))?;
+
+    // create an upstream remote conflicting commit
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path),
+        "line1\nline2\nline3\nline4\nupstream\n",
+    )?;
+    test_utils::commit_all(&project_repository.git_repository);
+    let up_target = project_repository
+        .git_repository
+        .head()
+        .unwrap()
+        .target()
+        .unwrap();
+    project_repository.git_repository.reference(
+        &"refs/remotes/origin/remote_branch".parse().unwrap(),
+        up_target,
+        true,
+        "update target",
+    )?;
+
+    // revert content and write a mergeable branch
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path),
+        "line1\nline2\nline3\nline4\n",
+    )?;
+    let file_path3 = std::path::Path::new("test3.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path3),
+        "file3\n",
+    )?;
+    test_utils::commit_all(&project_repository.git_repository);
+    let up_target = project_repository
+        .git_repository
+        .head()
+        .unwrap()
+        .target()
+        .unwrap();
+    project_repository.git_repository.reference(
+        &"refs/remotes/origin/remote_branch2".parse().unwrap(),
+        up_target,
+        true,
+        "update target",
+    )?;
+    // remove file_path3
+    std::fs::remove_file(std::path::Path::new(&project.path).join(file_path3))?;
+
+    project_repository
+        .git_repository
+        .set_head(&"refs/heads/gitbutler/integration".parse().unwrap())?;
+    project_repository
+        .git_repository
+        .checkout_head(Some(&mut git2::build::CheckoutBuilder::default().force()))?;
+
+    // create branches that conflict with our earlier branches
+    create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch");
+    let branch4_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    // branch3 conflicts with branch1 and remote_branch
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path),
+        "line1\nline2\nline3\nline4\nbranch3\n",
+    )?;
+
+    // branch4 conflicts with branch2
+    let file_path2 = std::path::Path::new("test2.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path2),
+        "line1\nline2\nline3\nline4\nbranch4\n",
+    )?;
+
+    let mut branch4 = branch_reader.read(&branch4_id)?;
+    branch4.ownership = Ownership {
+        files: vec!["test2.txt:1-6".parse()?],
+    };
+    branch_writer.write(&mut branch4)?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    assert_eq!(branches.len(), 4);
+
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert!(!branch1.active);
+    assert!(
+        !is_virtual_branch_mergeable(&gb_repository, &project_repository, &branch1.id).unwrap()
+    );
+
+    let branch2 = &branches.iter().find(|b| b.id == branch2_id).unwrap();
+    assert!(!branch2.active);
+    assert!(is_virtual_branch_mergeable(&gb_repository, &project_repository, &branch2.id).unwrap());
+
+    let remotes =
+        list_remote_branches(&gb_repository, &project_repository).expect("failed to list remotes");
+    let remote1 = &remotes
+        .iter()
+        .find(|b| b.name.to_string() == "refs/remotes/origin/remote_branch")
+        .unwrap();
+    assert!(!is_remote_branch_mergeable(
+        &gb_repository,
+        &project_repository,
+        &"refs/remotes/origin/remote_branch".parse().unwrap()
+    )
+    .unwrap());
+    // assert_eq!(remote1.commits.len(), 1);
+
+    let remote2 = &remotes
+        .iter()
+        .find(|b| b.name.to_string() == "refs/remotes/origin/remote_branch2")
+        .unwrap();
+    assert!(is_remote_branch_mergeable(
+        &gb_repository,
+        &project_repository,
+        &"refs/remotes/origin/remote_branch2".parse().unwrap()
+    )
+    .unwrap());
+    // assert_eq!(remote2.commits.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_upstream_integrated_vbranch() -> Result<()> {
+    // ok, we need a vbranch with some work and an upstream target that also includes that work, but the base is behind
+    // plus a branch with work not in upstream so we can see that it is not included in the vbranch
+
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([
+        (path::PathBuf::from("test.txt"), "file1\n"),
+        (path::PathBuf::from("test2.txt"), "file2\n"),
+        (path::PathBuf::from("test3.txt"), "file3\n"),
+    ]));
+
+    let base_commit = project_repository
+        .git_repository
+        .head()
+        .unwrap()
+        .target()
+        .unwrap();
+
+    std::fs::write(
+        std::path::Path::new(&project.path).join("test.txt"),
+        "file1\nversion2\n",
+    )?;
+    test_utils::commit_all(&project_repository.git_repository);
+
+    let upstream_commit = project_repository
+        .git_repository
+        .head()
+        .unwrap()
+        .target()
+        .unwrap();
+    project_repository.git_repository.reference(
+        &"refs/remotes/origin/master".parse().unwrap(),
+        upstream_commit,
+        true,
+        "update target",
+    )?;
+
+    target::Writer::new(&gb_repository)?.write_default(&target::Target {
+        branch: "refs/remotes/origin/master".parse().unwrap(),
+        remote_url: "http://origin.com/project".to_string(),
+        sha: base_commit,
+    })?;
+    project_repository
+        .git_repository
+        .remote("origin", &"http://origin.com/project".parse().unwrap())?;
+    super::integration::update_gitbutler_integration(&gb_repository, &project_repository)?;
+
+    // create vbranches, one integrated, one not
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+    let branch2_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+    let branch3_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    std::fs::write(
+        std::path::Path::new(&project.path).join("test2.txt"),
+        "file2\nversion2\n",
+    )?;
+
+    std::fs::write(
+        std::path::Path::new(&project.path).join("test3.txt"),
+        "file3\nversion2\n",
+    )?;
+
+    update_branch(
+        &gb_repository,
+        &project_repository,
+        branch::BranchUpdateRequest {
+            id: branch1_id,
+            name: Some("integrated".to_string()),
+            ownership: Some("test.txt:1-2".parse()?),
+            ..Default::default()
+        },
+    )?;
+
+    update_branch(
+        &gb_repository,
+        &project_repository,
+        branch::BranchUpdateRequest {
+            id: branch2_id,
+            name: Some("not integrated".to_string()),
+            ownership: Some("test2.txt:1-2".parse()?),
+            ..Default::default()
+        },
+    )?;
+
+    update_branch(
+        &gb_repository,
+        &project_repository,
+        branch::BranchUpdateRequest {
+            id: branch3_id,
+            name: Some("not committed".to_string()),
+            ownership: Some("test3.txt:1-2".parse()?),
+            ..Default::default()
+        },
+    )?;
+
+    // create a new virtual branch from the remote branch
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "integrated commit",
+        None,
+        None,
+        None,
+        false,
+    )?;
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch2_id,
+        "non-integrated commit",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert!(branch1.commits.iter().any(|c| c.is_integrated));
+    assert_eq!(branch1.files.len(), 0);
+    assert_eq!(branch1.commits.len(), 1);
+
+    let branch2 = &branches.iter().find(|b| b.id == branch2_id).unwrap();
+    assert!(!branch2.commits.iter().any(|c| c.is_integrated));
+    assert_eq!(branch2.files.len(), 0);
+    assert_eq!(branch2.commits.len(), 1);
+
+    let branch3 = &branches.iter().find(|b| b.id == branch3_id).unwrap();
+    assert!(!branch3.commits.iter().any(|c| c.is_integrated));
+    assert_eq!(branch3.files.len(), 1);
+    assert_eq!(branch3.commits.len(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_same_hunk_twice() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("test.txt"),
+            "line1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
+    )]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    std::fs::write(
+            std::path::Path::new(&project.path).join("test.txt"),
+            "line1\npatch1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
+        )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].hunks.len(), 1);
+    assert_eq!(branch.commits.len(), 0);
+
+    // commit
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "first commit to test.txt",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(branch.files.len(), 0, "no files expected");
+
+    assert_eq!(branch.commits.len(), 1, "file should have been commited");
+    assert_eq!(branch.commits[0].files.len(), 1, "hunks expected");
+    assert_eq!(
+        branch.commits[0].files[0].hunks.len(),
+        1,
+        "one hunk should have been commited"
+    );
+
+    // update same lines
+
+    std::fs::write(
+            std::path::Path::new(&project.path).join("test.txt"),
+            "line1\nPATCH1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
+        )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(branch.files.len(), 1, "one file should be changed");
+    assert_eq!(branch.commits.len(), 1, "commit is still there");
+
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "second commit to test.txt",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(
+        branch.files.len(),
+        0,
+        "all changes should have been commited"
+    );
+
+    assert_eq!(branch.commits.len(), 2, "two commits expected");
+    assert_eq!(branch.commits[0].files.len(), 1);
+    assert_eq!(branch.commits[0].files[0].hunks.len(), 1);
+    assert_eq!(branch.commits[1].files.len(), 1);
+    assert_eq!(branch.commits[1].files[0].hunks.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_same_file_twice() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("test.txt"),
+            "line1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
+    )]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    std::fs::write(
+            std::path::Path::new(&project.path).join("test.txt"),
+            "line1\npatch1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
+        )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].hunks.len(), 1);
+    assert_eq!(branch.commits.len(), 0);
+
+    // commit
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "first commit to test.txt",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(branch.files.len(), 0, "no files expected");
+
+    assert_eq!(branch.commits.len(), 1, "file should have been commited");
+    assert_eq!(branch.commits[0].files.len(), 1, "hunks expected");
+    assert_eq!(
+        branch.commits[0].files[0].hunks.len(),
+        1,
+        "one hunk should have been commited"
+    );
+
+    // add second patch
+
+    std::fs::write(
+            std::path::Path::new(&project.path).join("file.txt"),
+            "line1\npatch1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\npatch2\nline11\nline12\n",
+        )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(branch.files.len(), 1, "one file should be changed");
+    assert_eq!(branch.commits.len(), 1, "commit is still there");
+
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "second commit to test.txt",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(
+        branch.files.len(),
+        0,
+        "all changes should have been commited"
+    );
+
+    assert_eq!(branch.commits.len(), 2, "two commits expected");
+    assert_eq!(branch.commits[0].files.len(), 1);
+    assert_eq!(branch.commits[0].files[0].hunks.len(), 1);
+    assert_eq!(branch.commits[1].files.len(), 1);
+    assert_eq!(branch.commits[1].files[0].hunks.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_partial_by_hunk() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("test.txt"),
+            "line1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
+    )]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    std::fs::write(
+            std::path::Path::new(&project.path).join("test.txt"),
+            "line1\npatch1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\npatch2\nline11\nline12\n",
+        )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].hunks.len(), 2);
+    assert_eq!(branch.commits.len(), 0);
+
+    // commit
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "first commit to test.txt",
+        Some(&"test.txt:2-3".parse::<Ownership>().unwrap()),
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].hunks.len(), 1);
+    assert_eq!(branch.commits.len(), 1);
+    assert_eq!(branch.commits[0].files.len(), 1);
+    assert_eq!(branch.commits[0].files[0].hunks.len(), 1);
+
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "second commit to test.txt",
+        Some(&"test.txt:19-20".parse::<Ownership>().unwrap()),
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    assert_eq!(branch.files.len(), 0);
+    assert_eq!(branch.commits.len(), 2);
+    assert_eq!(branch.commits[0].files.len(), 1);
+    assert_eq!(branch.commits[0].files[0].hunks.len(), 1);
+    assert_eq!(branch.commits[1].files.len(), 1);
+    assert_eq!(branch.commits[1].files[0].hunks.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_partial_by_file() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([
+        (path::PathBuf::from("test.txt"), "file1\n"),
+        (path::PathBuf::from("test2.txt"), "file2\n"),
+    ]));
+
+    let commit1_oid = project_repository
+        .git_repository
+        .head()
+        .unwrap()
+        .target()
+        .unwrap();
+    let commit1 = project_repository
+        .git_repository
+        .find_commit(commit1_oid)
+        .unwrap();
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    // remove file
+    std::fs::remove_file(std::path::Path::new(&project.path).join("test2.txt"))?;
+    // add new file
+    let file_path3 = std::path::Path::new("test3.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path3),
+        "file3\n",
+    )?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    // commit
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "branch1 commit",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    // branch one test.txt has just the 1st and 3rd hunks applied
+    let commit2 = &branch1.commits[0].id;
+    let commit2 = project_repository
+        .git_repository
+        .find_commit(*commit2)
+        .expect("failed to get commit object");
+
+    let tree = commit1.tree().expect("failed to get tree");
+    let file_list = tree_to_file_list(&project_repository.git_repository, &tree);
+    assert_eq!(file_list, vec!["test.txt", "test2.txt"]);
+
+    // get the tree
+    let tree = commit2.tree().expect("failed to get tree");
+    let file_list = tree_to_file_list(&project_repository.git_repository, &tree);
+    assert_eq!(file_list, vec!["test.txt", "test3.txt"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_add_and_delete_files() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([
+        (path::PathBuf::from("test.txt"), "file1\n"),
+        (path::PathBuf::from("test2.txt"), "file2\n"),
+    ]));
+
+    let commit1_oid = project_repository
+        .git_repository
+        .head()
+        .unwrap()
+        .target()
+        .unwrap();
+    let commit1 = project_repository
+        .git_repository
+        .find_commit(commit1_oid)
+        .unwrap();
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    // remove file
+    std::fs::remove_file(std::path::Path::new(&project.path).join("test2.txt"))?;
+    // add new file
+    let file_path3 = std::path::Path::new("test3.txt");
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path3),
+        "file3\n",
+    )?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    // commit
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "branch1 commit",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    // branch one test.txt has just the 1st and 3rd hunks applied
+    let commit2 = &branch1.commits[0].id;
+    let commit2 = project_repository
+        .git_repository
+        .find_commit(*commit2)
+        .expect("failed to get commit object");
+
+    let tree = commit1.tree().expect("failed to get tree");
+    let file_list = tree_to_file_list(&project_repository.git_repository, &tree);
+    assert_eq!(file_list, vec!["test.txt", "test2.txt"]);
+
+    // get the tree
+    let tree = commit2.tree().expect("failed to get tree");
+    let file_list = tree_to_file_list(&project_repository.git_repository, &tree);
+    assert_eq!(file_list, vec!["test.txt", "test3.txt"]);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_commit_executable_and_symlinks() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([
+        (path::PathBuf::from("test.txt"), "file1\n"),
+        (path::PathBuf::from("test2.txt"), "file2\n"),
+    ]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    // add symlinked file
+    let file_path3 = std::path::Path::new("test3.txt");
+    let src = std::path::Path::new(&project.path).join("test2.txt");
+    let dst = std::path::Path::new(&project.path).join(file_path3);
+    symlink(src, dst)?;
+
+    // add executable
+    let file_path4 = std::path::Path::new("test4.bin");
+    let exec = std::path::Path::new(&project.path).join(file_path4);
+    std::fs::write(&exec, "exec\n")?;
+    let permissions = fs::metadata(&exec)?.permissions();
+    let new_permissions = Permissions::from_mode(permissions.mode() | 0o111); // Add execute permission
+    fs::set_permissions(&exec, new_permissions)?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    // commit
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "branch1 commit",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+
+    let commit = &branch1.commits[0].id;
+    let commit = project_repository
+        .git_repository
+        .find_commit(*commit)
+        .expect("failed to get commit object");
+
+    let tree = commit.tree().expect("failed to get tree");
+
+    let list = tree_to_entry_list(&project_repository.git_repository, &tree);
+    assert_eq!(list[0].0, "test.txt");
+    assert_eq!(list[0].1, "100644");
+    assert_eq!(list[1].0, "test2.txt");
+    assert_eq!(list[1].1, "100644");
+    assert_eq!(list[2].0, "test3.txt");
+    assert_eq!(list[2].1, "120000");
+    assert_eq!(list[2].2, "test2.txt");
+    assert_eq!(list[3].0, "test4.bin");
+    assert_eq!(list[3].1, "100755");
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_commit_nested_and_dangling_symlinks() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("test.txt"),
+        "file1\n",
+    )]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    // a link from a directory, and a link to nothing
+    fs::create_dir(project.path.join("dir"))?;
+    symlink("../test.txt", project.path.join("dir/link"))?;
+    symlink("missing.txt", project.path.join("dangling"))?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch1.files.len(), 2);
+
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "branch1 commit",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert!(branch1.files.is_empty());
+
+    let commit = project_repository
+        .git_repository
+        .find_commit(branch1.commits[0].id)?;
+    let tree = commit.tree()?;
+    for (path, target) in [("dir/link", "../test.txt"), ("dangling", "missing.txt")] {
+        let entry = tree.get_path(path::Path::new(path))?;
+        assert_eq!(entry.filemode(), i32::from(git::FileMode::Link));
+        let blob = project_repository.git_repository.find_blob(entry.id())?;
+        assert_eq!(blob.content(), target.as_bytes());
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_commit_mode_change() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("script.sh"),
+        "echo hello\n",
+    )]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    let script = project.path.join("script.sh");
+    let permissions = fs::metadata(&script)?.permissions();
+    fs::set_permissions(&script, Permissions::from_mode(permissions.mode() | 0o111))?;
+
+    let branch1_id = create_virtual_branch(
+        &gb_repository,
+        &project_repository,
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch1.files.len(), 1);
+    assert_eq!(branch1.files[0].hunks.len(), 1);
+    assert_eq!(
+        branch1.files[0].hunks[0].diff,
+        "old mode 100644\nnew mode 100755\n"
+    );
+
+    commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "make it executable",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert!(branch1.files.is_empty());
+
+    let commit = project_repository
+        .git_repository
+        .find_commit(branch1.commits[0].id)?;
+    let list = tree_to_entry_list(&project_repository.git_repository, &commit.tree()?);
+    assert_eq!(list[0].0, "script.sh");
+    assert_eq!(list[0].1, "100755");
+    assert_eq!(list[0].2, "echo hello\n");
+
+    Ok(())
+}
+
+fn tree_to_file_list(repository: &git::Repository, tree: &git::Tree) -> Vec<String> {
+    let mut file_list = Vec::new();
+    tree.walk(|_, entry| {
+        let path = entry.name().unwrap();
+        let entry = tree.get_path(std::path::Path::new(path)).unwrap();
+        let object = entry.to_object(repository).unwrap();
+        if object.kind() == Some(git2::ObjectType::Blob) {
+            file_list.push(path.to_string());
+        }
+        git::TreeWalkResult::Continue
+    })
+    .expect("failed to walk tree");
+    file_list
+}
+
+fn tree_to_entry_list(
+    repository: &git::Repository,
+    tree: &git::Tree,
+) -> Vec<(String, String, String, String)> {
+    let mut file_list = Vec::new();
+    tree.walk(|_root, entry| {
+        let path = entry.name().unwrap();
+        let entry = tree.get_path(std::path::Path::new(path)).unwrap();
+        let object = entry.to_object(repository).unwrap();
+        let blob = object.as_blob().expect("failed to get blob");
+        // convert content to string
+        let octal_mode = format!("{:o}", entry.filemode());
+        if let Ok(content) =
+            std::str::from_utf8(blob.content()).context("failed to convert content to string")
+        {
+            file_list.push((
+                path.to_string(),
+                octal_mode,
+                content.to_string(),
+                blob.id().to_string(),
+            ));
+        } else {
+            file_list.push((
+                path.to_string(),
+                octal_mode,
+                "BINARY".to_string(),
+                blob.id().to_string(),
+            ));
+        }
+        git::TreeWalkResult::Continue
+    })
+    .expect("failed to walk tree");
+    file_list
+}
+
+#[test]
+fn test_verify_branch_commits_to_integration() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case();
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    integration::verify_branch(&gb_repository, &project_repository).unwrap();
+
+    //  write two commits
+    let file_path2 = std::path::Path::new("test2.txt");
+    std::fs::write(std::path::Path::new(&project.path).join(file_path2), "file")?;
+    test_utils::commit_all(&project_repository.git_repository);
+    std::fs::write(
+        std::path::Path::new(&project.path).join(file_path2),
+        "update",
+    )?;
+    test_utils::commit_all(&project_repository.git_repository);
+
+    // verify puts commits onto the virtual branch
+    integration::verify_branch(&gb_repository, &project_repository).unwrap();
+
+    // one virtual branch with two commits was created
+    let virtual_branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    assert_eq!(virtual_branches.len(), 1);
+
+    let branch = &virtual_branches.first().unwrap();
+    assert_eq!(branch.commits.len(), 2);
+    assert_eq!(branch.commits.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_branch_not_integration() -> Result<()> {
+    let Case {
+        project_repository,
+        gb_repository,
+        ..
+    } = Suite::default().new_case();
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    integration::verify_branch(&gb_repository, &project_repository).unwrap();
+
+    project_repository
+        .git_repository
+        .set_head(&"refs/heads/master".parse().unwrap())?;
+
+    let verify_result = integration::verify_branch(&gb_repository, &project_repository);
+    assert!(verify_result.is_err());
+    assert_eq!(
+        verify_result.unwrap_err().to_string(),
+        "head is refs/heads/master"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_branch_commits_to_owning_branch() -> Result<()> {
+    let Case {
+        project_repository,
+        project,
+        gb_repository,
+        ..
+    } = Suite::default().new_case();
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    let branch = create_virtual_branch(
         &gb_repository,
         &project_repository,
         &BranchCreateRequest::default(),
     )
-    .expect("failed to create virtual branch")
-    .id;
+    .expect("failed to create virtual branch");
 
-    std::fs::write(
-        std::path::Path::new(&project.path).join("test2.txt"),
-        "file2\nversion2\n",
-    )?;
+    // the branch owns the file before it's committed outside of gitbutler
+    let file_path2 = std::path::Path::new("test2.txt");
+    std::fs::write(std::path::Path::new(&project.path).join(file_path2), "file")?;
+    get_status_by_branch(&gb_repository, &project_repository).expect("failed to get status");
+    test_utils::commit_all(&project_repository.git_repository);
 
-    std::fs::write(
-        std::path::Path::new(&project.path).join("test3.txt"),
-        "file3\nversion2\n",
-    )?;
+    integration::verify_branch(&gb_repository, &project_repository).unwrap();
 
-    update_branch(
-        &gb_repository,
-        &project_repository,
-        branch::BranchUpdateRequest {
-            id: branch1_id,
-            name: Some("integrated".to_string()),
-            ownership: Some("test.txt:1-2".parse()?),
-            ..Default::default()
-        },
-    )?;
+    // the commit was moved onto the branch, no branch was created
+    let virtual_branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    assert_eq!(virtual_branches.len(), 1);
+    assert_eq!(virtual_branches[0].id, branch.id);
+    assert_eq!(virtual_branches[0].commits.len(), 1);
+
+    let changes = list_external_changes(&gb_repository)?;
+    assert_eq!(changes.len(), 1);
+    assert!(matches!(
+        changes[0].kind,
+        ExternalChangeKind::Commits {
+            created_branch: false,
+            ..
+        }
+    ));
 
-    update_branch(
+    // undoing it leaves the change uncommitted on the branch
+    reconcile_external_change(
         &gb_repository,
         &project_repository,
-        branch::BranchUpdateRequest {
-            id: branch2_id,
-            name: Some("not integrated".to_string()),
-            ownership: Some("test2.txt:1-2".parse()?),
-            ..Default::default()
-        },
+        &changes[0].id,
+        ReconcileAction::Uncommit,
     )?;
 
-    update_branch(
+    let virtual_branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    assert_eq!(virtual_branches.len(), 1);
+    assert_eq!(virtual_branches[0].commits.len(), 0);
+    assert_eq!(virtual_branches[0].files.len(), 1);
+    assert!(list_external_changes(&gb_repository)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_pre_commit_hook_rejection() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        gb_repository,
+        project_repository,
+        ..
+    } = suite.new_case_with_files(HashMap::from([
+        (
+            path::PathBuf::from("test.txt"),
+            "line1\nline2\nline3\nline4\n",
+        ),
+        (
+            path::PathBuf::from("test2.txt"),
+            "line5\nline6\nline7\nline8\n",
+        ),
+    ]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    let branch1_id = create_virtual_branch(
         &gb_repository,
         &project_repository,
-        branch::BranchUpdateRequest {
-            id: branch3_id,
-            name: Some("not committed".to_string()),
-            ownership: Some("test3.txt:1-2".parse()?),
-            ..Default::default()
-        },
+        &BranchCreateRequest::default(),
+    )
+    .expect("failed to create virtual branch")
+    .id;
+
+    std::fs::write(
+        std::path::Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
     )?;
 
-    // create a new virtual branch from the remote branch
-    commit(
+    let hook = b"#!/bin/sh
+    echo 'rejected'
+    exit 1
+            ";
+
+    git2_hooks::create_hook(
+        (&project_repository.git_repository).into(),
+        git2_hooks::HOOK_PRE_COMMIT,
+        hook,
+    );
+
+    let res = commit(
         &gb_repository,
         &project_repository,
         &branch1_id,
-        "integrated commit",
-        None,
-        None,
-        None,
-        false,
-    )?;
-    commit(
-        &gb_repository,
-        &project_repository,
-        &branch2_id,
-        "non-integrated commit",
-        None,
+        "test commit",
         None,
+        Some(suite.keys.get_or_create()?).as_ref(),
         None,
-        false,
-    )?;
+        true,
+    );
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let error = res.unwrap_err();
 
-    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-    assert!(branch1.commits.iter().any(|c| c.is_integrated));
-    assert_eq!(branch1.files.len(), 0);
-    assert_eq!(branch1.commits.len(), 1);
+    assert!(matches!(error, CommitError::CommitHookRejected(_)));
 
-    let branch2 = &branches.iter().find(|b| b.id == branch2_id).unwrap();
-    assert!(!branch2.commits.iter().any(|c| c.is_integrated));
-    assert_eq!(branch2.files.len(), 0);
-    assert_eq!(branch2.commits.len(), 1);
+    let CommitError::CommitHookRejected(output) = error else {
+        unreachable!()
+    };
 
-    let branch3 = &branches.iter().find(|b| b.id == branch3_id).unwrap();
-    assert!(!branch3.commits.iter().any(|c| c.is_integrated));
-    assert_eq!(branch3.files.len(), 1);
-    assert_eq!(branch3.commits.len(), 0);
+    assert_eq!(&output, "rejected\n");
 
     Ok(())
 }
 
-#[test]
-fn test_commit_same_hunk_twice() -> Result<()> {
-    let Case {
-        project_repository,
-        project,
-        gb_repository,
-        ..
-    } = Suite::default().new_case_with_files(HashMap::from([(
+// a case with a target, and a virtual branch on it with one commit adding a line to test.txt
+fn new_case_with_commit(suite: &Suite) -> Result<(Case<'_>, BranchId, git::Oid)> {
+    let case = suite.new_case_with_files(HashMap::from([(
         path::PathBuf::from("test.txt"),
-            "line1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
+        "line1\nline2\n",
     )]));
 
-    set_test_target(&gb_repository, &project_repository)?;
+    set_test_target(&case.gb_repository, &case.project_repository)?;
 
-    let branch1_id = create_virtual_branch(
-        &gb_repository,
-        &project_repository,
+    let branch_id = create_virtual_branch(
+        &case.gb_repository,
+        &case.project_repository,
         &BranchCreateRequest::default(),
     )
     .expect("failed to create virtual branch")
     .id;
+    std::fs::write(case.project.path.join("test.txt"), "line0\nline1\nline2\n")?;
+    let commit_id = commit(
+        &case.gb_repository,
+        &case.project_repository,
+        &branch_id,
+        "first",
+        None,
+        None,
+        None,
+        false,
+    )?;
 
-    std::fs::write(
-            std::path::Path::new(&project.path).join("test.txt"),
-            "line1\npatch1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
-        )?;
+    Ok((case, branch_id, commit_id))
+}
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+#[test]
+fn test_commit_no_verify() -> Result<()> {
+    let suite = Suite::default();
+    let (
+        Case {
+            project,
+            gb_repository,
+            project_repository,
+            ..
+        },
+        branch1_id,
+        _,
+    ) = new_case_with_commit(&suite)?;
 
-    assert_eq!(branch.files.len(), 1);
-    assert_eq!(branch.files[0].hunks.len(), 1);
-    assert_eq!(branch.commits.len(), 0);
+    let hook = b"#!/bin/sh
+    echo 'rejected'
+    exit 1
+            ";
+    git2_hooks::create_hook(
+        (&project_repository.git_repository).into(),
+        git2_hooks::HOOK_PRE_COMMIT,
+        hook,
+    );
 
-    // commit
-    commit(
+    std::fs::write(
+        project.path.join("test.txt"),
+        "line0\nline1\nline2\nline3\n",
+    )?;
+    let commit2_id = commit_no_verify(
         &gb_repository,
         &project_repository,
         &branch1_id,
-        "first commit to test.txt",
+        "second",
         None,
         None,
         None,
-        false,
     )?;
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-
-    assert_eq!(branch.files.len(), 0, "no files expected");
+    std::fs::write(
+        project.path.join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
+    let commit3_id = commit_no_verify(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "third\n\nsome details\n\nSigned-off-by: A U Thor <author@example.com>\n",
+        None,
+        None,
+        None,
+    )?;
 
-    assert_eq!(branch.commits.len(), 1, "file should have been commited");
-    assert_eq!(branch.commits[0].files.len(), 1, "hunks expected");
+    let repository = &project_repository.git_repository;
     assert_eq!(
-        branch.commits[0].files[0].hunks.len(),
-        1,
-        "one hunk should have been commited"
+        repository.find_commit(commit2_id)?.message(),
+        Some("second\n\nVerification-Skipped: true")
+    );
+    assert_eq!(
+        repository.find_commit(commit3_id)?.message(),
+        Some(
+            "third\n\nsome details\n\nSigned-off-by: A U Thor <author@example.com>\nVerification-Skipped: true"
+        )
     );
 
-    // update same lines
+    Ok(())
+}
 
-    std::fs::write(
-            std::path::Path::new(&project.path).join("test.txt"),
-            "line1\nPATCH1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
-        )?;
+#[test]
+fn test_create_tags() -> Result<()> {
+    let suite = Suite::default();
+    let (
+        Case {
+            gb_repository,
+            project_repository,
+            ..
+        },
+        branch1_id,
+        commit1_id,
+    ) = new_case_with_commit(&suite)?;
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    let lightweight = create_tag(
+        &gb_repository,
+        &project_repository,
+        &TagCreateRequest {
+            name: "v1".to_string(),
+            target: commit1_id,
+            kind: TagKind::Lightweight,
+            message: String::new(),
+        },
+        None,
+        None,
+    )?;
+    assert_eq!(lightweight.target, commit1_id);
+    assert_eq!(lightweight.message, None);
 
-    assert_eq!(branch.files.len(), 1, "one file should be changed");
-    assert_eq!(branch.commits.len(), 1, "commit is still there");
+    let key = suite.keys.get_or_create()?;
+    let signed = create_tag(
+        &gb_repository,
+        &project_repository,
+        &TagCreateRequest {
+            name: "v2".to_string(),
+            target: commit1_id,
+            kind: TagKind::Signed,
+            message: "release".to_string(),
+        },
+        Some(&key),
+        None,
+    )?;
+    assert_eq!(signed.target, commit1_id);
+    assert_eq!(signed.message.as_deref(), Some("release\n"));
+    assert!(signed.signed);
+
+    assert!(matches!(
+        create_tag(
+            &gb_repository,
+            &project_repository,
+            &TagCreateRequest {
+                name: "v1".to_string(),
+                target: commit1_id,
+                kind: TagKind::Annotated,
+                message: "again".to_string(),
+            },
+            None,
+            None,
+        ),
+        Err(errors::CreateTagError::TagExists(_))
+    ));
+    assert!(matches!(
+        create_tag(
+            &gb_repository,
+            &project_repository,
+            &TagCreateRequest {
+                name: "not valid..".to_string(),
+                target: commit1_id,
+                kind: TagKind::Lightweight,
+                message: String::new(),
+            },
+            None,
+            None,
+        ),
+        Err(errors::CreateTagError::InvalidName(_))
+    ));
 
-    commit(
+    // a commit next to the branch, its tag isn't one of the branch's
+    let repo = &project_repository.git_repository;
+    let first = repo.find_commit(commit1_id)?;
+    let beside_id = repo.commit(
+        None,
+        &first.author(),
+        &first.committer(),
+        "beside",
+        &first.tree()?,
+        &[&first.parent(0)?],
+    )?;
+    repo.tag_lightweight("beside", beside_id, false)?;
+
+    let branch_tags = list_branch_tags(&gb_repository, &project_repository)?;
+    assert_eq!(branch_tags.len(), 1);
+    assert_eq!(branch_tags[0].branch_id, branch1_id);
+    assert_eq!(
+        branch_tags[0]
+            .tags
+            .iter()
+            .map(|tag| tag.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["v1", "v2"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_recover_lost_commit() -> Result<()> {
+    let suite = Suite::default();
+    let (
+        Case {
+            project,
+            gb_repository,
+            project_repository,
+            ..
+        },
+        branch1_id,
+        commit1_id,
+    ) = new_case_with_commit(&suite)?;
+    std::fs::write(
+        project.path.join("test.txt"),
+        "line0\nline1\nline2\nline3\n",
+    )?;
+    let commit2_id = commit(
         &gb_repository,
         &project_repository,
         &branch1_id,
-        "second commit to test.txt",
+        "second",
         None,
         None,
         None,
         false,
     )?;
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert!(list_lost_commits(&gb_repository, &project_repository, &branch1_id)?.is_empty());
+
+    // a branch that had the second commit is reset outside of the app, only its reflog has it
+    let feature: git::Refname = "refs/heads/feature".parse()?;
+    let repo = &project_repository.git_repository;
+    repo.reference(&feature, commit2_id, false, "branch: created")?;
+    repo.reference(&feature, commit1_id, true, "reset: moving to first")?;
+    reset_branch(&gb_repository, &project_repository, &branch1_id, commit1_id)?;
+
+    let lost = list_lost_commits(&gb_repository, &project_repository, &branch1_id)?;
+    assert_eq!(lost.len(), 1);
+    assert_eq!(lost[0].commit.id, commit2_id.to_string());
+    assert_eq!(lost[0].source, "refs/heads/feature");
+
+    // what any other reference leads to isn't lost
+    let tag: git::Refname = "refs/tags/second".parse()?;
+    repo.reference(&tag, commit2_id, false, "tag: second")?;
+    assert!(list_lost_commits(&gb_repository, &project_repository, &branch1_id)?.is_empty());
+    repo.find_reference(&tag)?.delete()?;
+
+    assert!(matches!(
+        recover_commit(
+            &gb_repository,
+            &project_repository,
+            &branch1_id,
+            commit1_id,
+            None,
+            None
+        ),
+        Err(errors::RecoverCommitError::CommitNotLost(_))
+    ));
+    let recovered_id = recover_commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        commit2_id,
+        None,
+        None,
+    )?;
+    let session = gb_repository.get_or_create_current_session()?;
+    let session_reader = sessions::Reader::open(&gb_repository, &session)?;
+    let recovered = branch::Reader::new(&session_reader).read(&recovered_id)?;
+    assert_eq!(recovered.head, commit2_id);
+    assert_eq!(recovered.name, "Virtual branch recovered");
+    assert!(list_lost_commits(&gb_repository, &project_repository, &branch1_id)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_list_and_delete_tags() -> Result<()> {
+    let suite = Suite::default();
+    let (
+        Case {
+            gb_repository,
+            project_repository,
+            credentials,
+            ..
+        },
+        _,
+        commit1_id,
+    ) = new_case_with_commit(&suite)?;
+
+    for (name, kind) in [
+        ("v1.0", TagKind::Annotated),
+        ("v1.1", TagKind::Lightweight),
+        ("nightly", TagKind::Lightweight),
+    ] {
+        create_tag(
+            &gb_repository,
+            &project_repository,
+            &TagCreateRequest {
+                name: name.to_string(),
+                target: commit1_id,
+                kind,
+                message: "release\n\nnotes\n".to_string(),
+            },
+            None,
+            None,
+        )?;
+    }
 
+    let tags = list_tags(&project_repository, Some("v1.*"))?;
     assert_eq!(
-        branch.files.len(),
-        0,
-        "all changes should have been commited"
+        tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>(),
+        vec!["v1.0", "v1.1"]
     );
+    assert!(tags.iter().all(|tag| tag.target == commit1_id));
+    assert_eq!(tags[0].message.as_deref(), Some("release\n\nnotes\n"));
+    assert_eq!(list_tags(&project_repository, None)?.len(), 3);
 
-    assert_eq!(branch.commits.len(), 2, "two commits expected");
-    assert_eq!(branch.commits[0].files.len(), 1);
-    assert_eq!(branch.commits[0].files[0].hunks.len(), 1);
-    assert_eq!(branch.commits[1].files.len(), 1);
-    assert_eq!(branch.commits[1].files[0].hunks.len(), 1);
+    delete_tag(&project_repository, "v1.0", None, &credentials)?;
+    assert_eq!(
+        list_tags(&project_repository, None)?
+            .iter()
+            .map(|tag| tag.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["nightly", "v1.1"]
+    );
+    assert!(matches!(
+        delete_tag(&project_repository, "v1.0", None, &credentials),
+        Err(errors::DeleteTagError::TagNotFound(_))
+    ));
 
     Ok(())
 }
 
 #[test]
-fn test_commit_same_file_twice() -> Result<()> {
+fn test_post_commit_hook() -> Result<()> {
+    let suite = Suite::default();
     let Case {
-        project_repository,
         project,
         gb_repository,
+        project_repository,
         ..
-    } = Suite::default().new_case_with_files(HashMap::from([(
-        path::PathBuf::from("test.txt"),
-            "line1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
-    )]));
+    } = suite.new_case_with_files(HashMap::from([
+        (
+            path::PathBuf::from("test.txt"),
+            "line1\nline2\nline3\nline4\n",
+        ),
+        (
+            path::PathBuf::from("test2.txt"),
+            "line5\nline6\nline7\nline8\n",
+        ),
+    ]));
 
     set_test_target(&gb_repository, &project_repository)?;
 
@@ -1952,95 +3462,63 @@ fn test_commit_same_file_twice() -> Result<()> {
     .id;
 
     std::fs::write(
-            std::path::Path::new(&project.path).join("test.txt"),
-            "line1\npatch1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
-        )?;
-
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-
-    assert_eq!(branch.files.len(), 1);
-    assert_eq!(branch.files[0].hunks.len(), 1);
-    assert_eq!(branch.commits.len(), 0);
-
-    // commit
-    commit(
-        &gb_repository,
-        &project_repository,
-        &branch1_id,
-        "first commit to test.txt",
-        None,
-        None,
-        None,
-        false,
+        std::path::Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
     )?;
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-
-    assert_eq!(branch.files.len(), 0, "no files expected");
+    let hook = b"#!/bin/sh
+    touch hook_ran
+            ";
 
-    assert_eq!(branch.commits.len(), 1, "file should have been commited");
-    assert_eq!(branch.commits[0].files.len(), 1, "hunks expected");
-    assert_eq!(
-        branch.commits[0].files[0].hunks.len(),
-        1,
-        "one hunk should have been commited"
+    git2_hooks::create_hook(
+        (&project_repository.git_repository).into(),
+        git2_hooks::HOOK_POST_COMMIT,
+        hook,
     );
 
-    // add second patch
-
-    std::fs::write(
-            std::path::Path::new(&project.path).join("file.txt"),
-            "line1\npatch1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\npatch2\nline11\nline12\n",
-        )?;
-
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    let hook_ran_proof = project_repository
+        .git_repository
+        .path()
+        .parent()
+        .unwrap()
+        .join("hook_ran");
 
-    assert_eq!(branch.files.len(), 1, "one file should be changed");
-    assert_eq!(branch.commits.len(), 1, "commit is still there");
+    assert!(!hook_ran_proof.exists());
 
     commit(
         &gb_repository,
         &project_repository,
         &branch1_id,
-        "second commit to test.txt",
-        None,
+        "test commit",
         None,
+        Some(suite.keys.get_or_create()?).as_ref(),
         None,
-        false,
+        true,
     )?;
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-
-    assert_eq!(
-        branch.files.len(),
-        0,
-        "all changes should have been commited"
-    );
-
-    assert_eq!(branch.commits.len(), 2, "two commits expected");
-    assert_eq!(branch.commits[0].files.len(), 1);
-    assert_eq!(branch.commits[0].files[0].hunks.len(), 1);
-    assert_eq!(branch.commits[1].files.len(), 1);
-    assert_eq!(branch.commits[1].files[0].hunks.len(), 1);
+    assert!(hook_ran_proof.exists());
 
     Ok(())
 }
 
 #[test]
-fn test_commit_partial_by_hunk() -> Result<()> {
+fn test_commit_msg_hook_rejection() -> Result<()> {
+    let suite = Suite::default();
     let Case {
-        project_repository,
         project,
         gb_repository,
+        project_repository,
         ..
-    } = Suite::default().new_case_with_files(HashMap::from([(
-        path::PathBuf::from("test.txt"),
-            "line1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\nline11\nline12\n",
-    )]));
+    } = suite.new_case_with_files(HashMap::from([
+        (
+            path::PathBuf::from("test.txt"),
+            "line1\nline2\nline3\nline4\n",
+        ),
+        (
+            path::PathBuf::from("test2.txt"),
+            "line5\nline6\nline7\nline8\n",
+        ),
+    ]));
 
     set_test_target(&gb_repository, &project_repository)?;
 
@@ -2053,173 +3531,138 @@ fn test_commit_partial_by_hunk() -> Result<()> {
     .id;
 
     std::fs::write(
-            std::path::Path::new(&project.path).join("test.txt"),
-            "line1\npatch1\nline2\nline3\nline4\nline5\nmiddle\nmiddle\nmiddle\nmiddle\nline6\nline7\nline8\nline9\nline10\nmiddle\nmiddle\nmiddle\npatch2\nline11\nline12\n",
-        )?;
+        std::path::Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    let hook = b"#!/bin/sh
+    echo 'rejected'
+    exit 1
+            ";
 
-    assert_eq!(branch.files.len(), 1);
-    assert_eq!(branch.files[0].hunks.len(), 2);
-    assert_eq!(branch.commits.len(), 0);
+    git2_hooks::create_hook(
+        (&project_repository.git_repository).into(),
+        git2_hooks::HOOK_COMMIT_MSG,
+        hook,
+    );
 
-    // commit
-    commit(
+    let res = commit(
         &gb_repository,
         &project_repository,
         &branch1_id,
-        "first commit to test.txt",
-        Some(&"test.txt:2-3".parse::<Ownership>().unwrap()),
+        "test commit",
         None,
+        Some(suite.keys.get_or_create()?).as_ref(),
         None,
-        false,
-    )?;
-
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+        true,
+    );
 
-    assert_eq!(branch.files.len(), 1);
-    assert_eq!(branch.files[0].hunks.len(), 1);
-    assert_eq!(branch.commits.len(), 1);
-    assert_eq!(branch.commits[0].files.len(), 1);
-    assert_eq!(branch.commits[0].files[0].hunks.len(), 1);
+    let error = res.unwrap_err();
 
-    commit(
-        &gb_repository,
-        &project_repository,
-        &branch1_id,
-        "second commit to test.txt",
-        Some(&"test.txt:19-20".parse::<Ownership>().unwrap()),
-        None,
-        None,
-        false,
-    )?;
+    assert!(matches!(error, CommitError::CommitMsgHookRejected(_)));
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    let CommitError::CommitMsgHookRejected(output) = error else {
+        unreachable!()
+    };
 
-    assert_eq!(branch.files.len(), 0);
-    assert_eq!(branch.commits.len(), 2);
-    assert_eq!(branch.commits[0].files.len(), 1);
-    assert_eq!(branch.commits[0].files[0].hunks.len(), 1);
-    assert_eq!(branch.commits[1].files.len(), 1);
-    assert_eq!(branch.commits[1].files[0].hunks.len(), 1);
+    assert_eq!(&output, "rejected\n");
 
     Ok(())
 }
 
 #[test]
-fn test_commit_partial_by_file() -> Result<()> {
+fn test_write_tree_onto_tree_incremental() -> Result<()> {
     let Case {
-        project_repository,
         project,
+        project_repository,
         gb_repository,
         ..
     } = Suite::default().new_case_with_files(HashMap::from([
-        (path::PathBuf::from("test.txt"), "file1\n"),
-        (path::PathBuf::from("test2.txt"), "file2\n"),
+        (path::PathBuf::from("a/test.txt"), "line1\nline2\n"),
+        (path::PathBuf::from("b/test.txt"), "line3\nline4\n"),
     ]));
 
-    let commit1_oid = project_repository
-        .git_repository
-        .head()
-        .unwrap()
-        .target()
-        .unwrap();
-    let commit1 = project_repository
-        .git_repository
-        .find_commit(commit1_oid)
-        .unwrap();
-
     set_test_target(&gb_repository, &project_repository)?;
+    let default_target = gb_repository.default_target()?.unwrap();
+    let base_tree = project_repository
+        .git_repository
+        .find_commit(default_target.sha)?
+        .tree()?;
 
-    // remove file
-    std::fs::remove_file(std::path::Path::new(&project.path).join("test2.txt"))?;
-    // add new file
-    let file_path3 = std::path::Path::new("test3.txt");
     std::fs::write(
-        std::path::Path::new(&project.path).join(file_path3),
-        "file3\n",
+        std::path::Path::new(&project.path).join("a/test.txt"),
+        "line0\nline1\nline2\n",
     )?;
-
-    let branch1_id = create_virtual_branch(
-        &gb_repository,
-        &project_repository,
-        &BranchCreateRequest::default(),
-    )
-    .expect("failed to create virtual branch")
-    .id;
-
-    // commit
-    commit(
-        &gb_repository,
-        &project_repository,
-        &branch1_id,
-        "branch1 commit",
-        None,
-        None,
-        None,
-        false,
+    std::fs::write(
+        std::path::Path::new(&project.path).join("b/test.txt"),
+        "line3\nline4\nline5\n",
     )?;
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-
-    // branch one test.txt has just the 1st and 3rd hunks applied
-    let commit2 = &branch1.commits[0].id;
-    let commit2 = project_repository
+    let files = git::diff::workdir(&project_repository.git_repository, &default_target.sha)?;
+    let previous_tree_oid = write_tree(&project_repository, &default_target, &files)?;
+    let previous_tree = project_repository
         .git_repository
-        .find_commit(*commit2)
-        .expect("failed to get commit object");
+        .find_tree(previous_tree_oid)?;
 
-    let tree = commit1.tree().expect("failed to get tree");
-    let file_list = tree_to_file_list(&project_repository.git_repository, &tree);
-    assert_eq!(file_list, vec!["test.txt", "test2.txt"]);
+    // nothing changed, the previous tree is reused
+    assert_eq!(
+        write_tree_onto_tree_incremental(
+            &project_repository,
+            &base_tree,
+            &previous_tree,
+            &written_paths(&files),
+            &files
+        )?,
+        previous_tree_oid
+    );
+
+    // revert one of the files
+    std::fs::write(
+        std::path::Path::new(&project.path).join("b/test.txt"),
+        "line3\nline4\n",
+    )?;
+
+    let previously_changed = written_paths(&files);
+    let files = git::diff::workdir(&project_repository.git_repository, &default_target.sha)?;
+    let incremental_tree_oid = write_tree_onto_tree_incremental(
+        &project_repository,
+        &base_tree,
+        &previous_tree,
+        &previously_changed,
+        &files,
+    )?;
+    assert_ne!(incremental_tree_oid, previous_tree_oid);
+    assert_eq!(
+        incremental_tree_oid,
+        write_tree(&project_repository, &default_target, &files)?
+    );
 
-    // get the tree
-    let tree = commit2.tree().expect("failed to get tree");
-    let file_list = tree_to_file_list(&project_repository.git_repository, &tree);
-    assert_eq!(file_list, vec!["test.txt", "test3.txt"]);
+    let incremental_tree = project_repository
+        .git_repository
+        .find_tree(incremental_tree_oid)?;
+    assert_eq!(
+        incremental_tree.get_path(path::Path::new("b"))?.id(),
+        base_tree.get_path(path::Path::new("b"))?.id()
+    );
 
     Ok(())
 }
 
 #[test]
-fn test_commit_add_and_delete_files() -> Result<()> {
+fn test_stream_chunks() -> Result<()> {
     let Case {
-        project_repository,
         project,
+        project_repository,
         gb_repository,
         ..
     } = Suite::default().new_case_with_files(HashMap::from([
-        (path::PathBuf::from("test.txt"), "file1\n"),
-        (path::PathBuf::from("test2.txt"), "file2\n"),
+        (path::PathBuf::from("test.txt"), "line1\nline2\n"),
+        (path::PathBuf::from("test2.txt"), "line3\nline4\n"),
     ]));
 
-    let commit1_oid = project_repository
-        .git_repository
-        .head()
-        .unwrap()
-        .target()
-        .unwrap();
-    let commit1 = project_repository
-        .git_repository
-        .find_commit(commit1_oid)
-        .unwrap();
-
     set_test_target(&gb_repository, &project_repository)?;
 
-    // remove file
-    std::fs::remove_file(std::path::Path::new(&project.path).join("test2.txt"))?;
-    // add new file
-    let file_path3 = std::path::Path::new("test3.txt");
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path3),
-        "file3\n",
-    )?;
-
-    let branch1_id = create_virtual_branch(
+    let branch_id = create_virtual_branch(
         &gb_repository,
         &project_repository,
         &BranchCreateRequest::default(),
@@ -2227,68 +3670,95 @@ fn test_commit_add_and_delete_files() -> Result<()> {
     .expect("failed to create virtual branch")
     .id;
 
-    // commit
-    commit(
-        &gb_repository,
-        &project_repository,
-        &branch1_id,
-        "branch1 commit",
-        None,
-        None,
-        None,
-        false,
+    std::fs::write(
+        std::path::Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\n",
+    )?;
+    std::fs::write(
+        std::path::Path::new(&project.path).join("test2.txt"),
+        "line3\nline4\nline5\n",
     )?;
 
     let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
-
-    // branch one test.txt has just the 1st and 3rd hunks applied
-    let commit2 = &branch1.commits[0].id;
-    let commit2 = project_repository
-        .git_repository
-        .find_commit(*commit2)
-        .expect("failed to get commit object");
 
-    let tree = commit1.tree().expect("failed to get tree");
-    let file_list = tree_to_file_list(&project_repository.git_repository, &tree);
-    assert_eq!(file_list, vec!["test.txt", "test2.txt"]);
+    // everything fits in a single chunk
+    let chunks = stream::chunks(branches.clone(), stream::MAX_CHUNK_BYTES).collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 3);
+    assert!(
+        matches!(&chunks[0], stream::Chunk::Branches { branches } if branches[0].files.is_empty())
+    );
+    assert!(
+        matches!(&chunks[1], stream::Chunk::Files { branch_id: id, files } if *id == branch_id && files.len() == 2)
+    );
+    assert_eq!(chunks[2], stream::Chunk::Done);
 
-    // get the tree
-    let tree = commit2.tree().expect("failed to get tree");
-    let file_list = tree_to_file_list(&project_repository.git_repository, &tree);
-    assert_eq!(file_list, vec!["test.txt", "test3.txt"]);
+    // one file per chunk
+    let chunks = stream::chunks(branches, 1).collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 4);
+    assert!(matches!(&chunks[1], stream::Chunk::Files { files, .. } if files.len() == 1));
+    assert!(matches!(&chunks[2], stream::Chunk::Files { files, .. } if files.len() == 1));
 
     Ok(())
 }
 
 #[test]
-#[cfg(target_family = "unix")]
-fn test_commit_executable_and_symlinks() -> Result<()> {
+fn test_get_project_state() -> Result<()> {
     let Case {
-        project_repository,
         project,
+        project_repository,
         gb_repository,
         ..
-    } = Suite::default().new_case_with_files(HashMap::from([
-        (path::PathBuf::from("test.txt"), "file1\n"),
-        (path::PathBuf::from("test2.txt"), "file2\n"),
-    ]));
+    } = Suite::default().new_case_with_files(HashMap::from([(
+        path::PathBuf::from("test.txt"),
+        "line1\nline2\n",
+    )]));
+
+    // nothing but the version is known before the base branch is set
+    let state = get_project_state(&gb_repository, &project_repository, 1)?;
+    assert_eq!(state.version, 1);
+    assert!(state.base_branch.is_none());
+    assert!(state.virtual_branches.is_empty());
 
     set_test_target(&gb_repository, &project_repository)?;
 
-    // add symlinked file
-    let file_path3 = std::path::Path::new("test3.txt");
-    let src = std::path::Path::new(&project.path).join("test2.txt");
-    let dst = std::path::Path::new(&project.path).join(file_path3);
-    symlink(src, dst)?;
+    std::fs::write(
+        std::path::Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\n",
+    )?;
 
-    // add executable
-    let file_path4 = std::path::Path::new("test4.bin");
-    let exec = std::path::Path::new(&project.path).join(file_path4);
-    std::fs::write(&exec, "exec\n")?;
-    let permissions = fs::metadata(&exec)?.permissions();
-    let new_permissions = Permissions::from_mode(permissions.mode() | 0o111); // Add execute permission
-    fs::set_permissions(&exec, new_permissions)?;
+    let next_state = get_project_state(&gb_repository, &project_repository, 2)?;
+    assert_eq!(next_state.version, 2);
+    assert!(next_state.base_branch.is_some());
+    assert_eq!(
+        next_state.virtual_branches,
+        list_virtual_branches(&gb_repository, &project_repository)?
+    );
+    assert_eq!(
+        next_state.remote_branches,
+        list_remote_branches(&gb_repository, &project_repository)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_case_only_rename() -> Result<()> {
+    let Case {
+        project,
+        project_repository,
+        gb_repository,
+        ..
+    } = Suite::default()
+        .new_case_with_files(HashMap::from([(path::PathBuf::from("Foo.txt"), "foo\n")]));
+
+    set_test_target(&gb_repository, &project_repository)?;
+
+    // this is how git configures repositories on case insensitive filesystems. with it, the
+    // workdir diff no longer tells `Foo.txt` and `foo.txt` apart, just like on macOS.
+    project_repository
+        .git_repository
+        .config()?
+        .set_bool("core.ignorecase", true)?;
 
     let branch1_id = create_virtual_branch(
         &gb_repository,
@@ -2298,359 +3768,574 @@ fn test_commit_executable_and_symlinks() -> Result<()> {
     .expect("failed to create virtual branch")
     .id;
 
-    // commit
-    commit(
+    fs::write(project.path.join("Foo.txt"), "foo\nbar\n")?;
+    get_status_by_branch(&gb_repository, &project_repository)?;
+
+    let branch2_id = create_virtual_branch(
         &gb_repository,
         &project_repository,
-        &branch1_id,
-        "branch1 commit",
-        None,
-        None,
-        None,
-        false,
-    )?;
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )
+    .expect("failed to create virtual branch")
+    .id;
 
-    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+    fs::rename(project.path.join("Foo.txt"), project.path.join("foo.txt"))?;
 
-    let commit = &branch1.commits[0].id;
-    let commit = project_repository
-        .git_repository
-        .find_commit(*commit)
-        .expect("failed to get commit object");
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch1 = branches.iter().find(|b| b.id == branch1_id).unwrap();
+    let branch2 = branches.iter().find(|b| b.id == branch2_id).unwrap();
 
-    let tree = commit.tree().expect("failed to get tree");
+    // both sides of the rename stay on the branch that owned the file
+    let mut paths = branch1
+        .files
+        .iter()
+        .map(|file| file.path.clone())
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            path::PathBuf::from("Foo.txt"),
+            path::PathBuf::from("foo.txt")
+        ]
+    );
+    assert!(branch2.files.is_empty());
 
-    let list = tree_to_entry_list(&project_repository.git_repository, &tree);
-    assert_eq!(list[0].0, "test.txt");
-    assert_eq!(list[0].1, "100644");
-    assert_eq!(list[1].0, "test2.txt");
-    assert_eq!(list[1].1, "100644");
-    assert_eq!(list[2].0, "test3.txt");
-    assert_eq!(list[2].1, "120000");
-    assert_eq!(list[2].2, "test2.txt");
-    assert_eq!(list[3].0, "test4.bin");
-    assert_eq!(list[3].1, "100755");
+    // the branch tree only has the new name
+    let current_session = gb_repository.get_or_create_current_session()?;
+    let current_session_reader = sessions::Reader::open(&gb_repository, &current_session)?;
+    let branch1 = branch::Reader::new(&current_session_reader).read(&branch1_id)?;
+    let tree = project_repository.git_repository.find_tree(branch1.tree)?;
+    assert!(tree.get_path(path::Path::new("Foo.txt")).is_err());
+    assert!(tree.get_path(path::Path::new("foo.txt")).is_ok());
 
     Ok(())
 }
 
-fn tree_to_file_list(repository: &git::Repository, tree: &git::Tree) -> Vec<String> {
-    let mut file_list = Vec::new();
-    tree.walk(|_, entry| {
-        let path = entry.name().unwrap();
-        let entry = tree.get_path(std::path::Path::new(path)).unwrap();
-        let object = entry.to_object(repository).unwrap();
-        if object.kind() == Some(git2::ObjectType::Blob) {
-            file_list.push(path.to_string());
-        }
-        git::TreeWalkResult::Continue
-    })
-    .expect("failed to walk tree");
-    file_list
-}
+#[test]
+fn test_authors_through_mailmap() -> Result<()> {
+    let suite = Suite::default();
+    let (
+        Case {
+            project,
+            gb_repository,
+            project_repository,
+            ..
+        },
+        branch1_id,
+        commit1_id,
+    ) = new_case_with_commit(&suite)?;
 
-fn tree_to_entry_list(
-    repository: &git::Repository,
-    tree: &git::Tree,
-) -> Vec<(String, String, String, String)> {
-    let mut file_list = Vec::new();
-    tree.walk(|_root, entry| {
-        let path = entry.name().unwrap();
-        let entry = tree.get_path(std::path::Path::new(path)).unwrap();
-        let object = entry.to_object(repository).unwrap();
-        let blob = object.as_blob().expect("failed to get blob");
-        // convert content to string
-        let octal_mode = format!("{:o}", entry.filemode());
-        if let Ok(content) =
-            std::str::from_utf8(blob.content()).context("failed to convert content to string")
-        {
-            file_list.push((
-                path.to_string(),
-                octal_mode,
-                content.to_string(),
-                blob.id().to_string(),
-            ));
-        } else {
-            file_list.push((
-                path.to_string(),
-                octal_mode,
-                "BINARY".to_string(),
-                blob.id().to_string(),
-            ));
-        }
-        git::TreeWalkResult::Continue
-    })
-    .expect("failed to walk tree");
-    file_list
+    let email = project_repository
+        .git_repository
+        .find_commit(commit1_id)?
+        .author()
+        .email()
+        .unwrap_or_default()
+        .to_string();
+    std::fs::write(
+        project.path.join(".mailmap"),
+        format!("Canonical Name <canonical@example.com> <{email}>\n"),
+    )?;
+
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch.commits.len(), 1);
+    assert_eq!(branch.commits[0].author.name, "Canonical Name");
+    assert_eq!(branch.commits[0].author.email, "canonical@example.com");
+
+    // like git, the mailmap `mailmap.file` names is read too, and it goes over the worktree one
+    let mailmap_file = project.path.join(".git").join("configured.mailmap");
+    std::fs::write(
+        &mailmap_file,
+        format!("Configured Name <configured@example.com> <{email}>\n"),
+    )?;
+    project_repository
+        .git_repository
+        .config()?
+        .set_str("mailmap.file", &mailmap_file.display().to_string())?;
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch.commits[0].author.name, "Configured Name");
+    assert_eq!(branch.commits[0].author.email, "configured@example.com");
+
+    Ok(())
 }
 
 #[test]
-fn test_verify_branch_commits_to_integration() -> Result<()> {
-    let Case {
-        project_repository,
-        project,
-        gb_repository,
-        ..
-    } = Suite::default().new_case();
+fn test_blame() -> Result<()> {
+    let suite = Suite::default();
+    let (
+        Case {
+            project,
+            gb_repository,
+            project_repository,
+            ..
+        },
+        branch1_id,
+        commit1_id,
+    ) = new_case_with_commit(&suite)?;
+    std::fs::write(
+        project.path.join("test.txt"),
+        "line0\nline1\nline2\nline3\n",
+    )?;
+    let commit2_id = commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "second\n\nsome details",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    let path = path::Path::new("test.txt");
+    let blame = blame(&project_repository, path, commit2_id, None)?;
+    assert_eq!(blame.total_lines, 4);
+    let blamed = |line: usize| {
+        blame
+            .hunks
+            .iter()
+            .find(|hunk| (hunk.start_line..hunk.start_line + hunk.lines).contains(&line))
+            .map(|hunk| hunk.commit_id)
+    };
+    assert_eq!(blamed(1), Some(commit1_id));
+    assert_ne!(blamed(2), Some(commit1_id));
+    assert_eq!(blamed(4), Some(commit2_id));
+
+    // every line goes to the commit `git blame` says it does, which marks the root one with ^
+    let output = std::process::Command::new("git")
+        .args([
+            "blame",
+            "-l",
+            "-s",
+            &commit2_id.to_string(),
+            "--",
+            "test.txt",
+        ])
+        .current_dir(&project.path)
+        .output()?;
+    let git_blamed = String::from_utf8(output.stdout)?
+        .lines()
+        .map(|line| {
+            line.split(' ')
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches('^')
+                .parse()
+        })
+        .collect::<Result<Vec<git::Oid>, _>>()?;
+    assert_eq!(
+        (1..=4).map(blamed).collect::<Vec<_>>(),
+        git_blamed.into_iter().map(Some).collect::<Vec<_>>()
+    );
+
+    // only the lines asked for are blamed
+    let blame = super::blame(&project_repository, path, commit2_id, Some(4..=10))?;
+    assert_eq!(blame.hunks.len(), 1);
+    assert_eq!(blame.hunks[0].start_line, 4);
+    assert_eq!(blame.hunks[0].commit_id, commit2_id);
+    assert_eq!(blame.hunks[0].summary, "second");
+
+    assert!(
+        super::blame(&project_repository, path, commit2_id, Some(5..=6))?
+            .hunks
+            .is_empty()
+    );
+    assert!(matches!(
+        super::blame(&project_repository, path, commit2_id, Some(0..=1)),
+        Err(errors::BlameError::InvalidLines { start: 0, end: 1 })
+    ));
+    assert!(matches!(
+        super::blame(
+            &project_repository,
+            path::Path::new("nope.txt"),
+            commit1_id,
+            None
+        ),
+        Err(errors::BlameError::FileNotFound(_))
+    ));
+
+    Ok(())
+}
 
-    set_test_target(&gb_repository, &project_repository)?;
+#[test]
+fn test_commit_notes() -> Result<()> {
+    let suite = Suite::default();
+    let (
+        Case {
+            project_repository, ..
+        },
+        _,
+        commit1_id,
+    ) = new_case_with_commit(&suite)?;
 
-    integration::verify_branch(&gb_repository, &project_repository).unwrap();
+    assert!(list_notes(&project_repository, commit1_id)?.is_empty());
 
-    //  write two commits
-    let file_path2 = std::path::Path::new("test2.txt");
-    std::fs::write(std::path::Path::new(&project.path).join(file_path2), "file")?;
-    test_utils::commit_all(&project_repository.git_repository);
-    std::fs::write(
-        std::path::Path::new(&project.path).join(file_path2),
-        "update",
+    set_note(&project_repository, commit1_id, None, "reviewed", None)?;
+    set_note(
+        &project_repository,
+        commit1_id,
+        Some("refs/notes/ci"),
+        "passed",
+        None,
     )?;
-    test_utils::commit_all(&project_repository.git_repository);
-
-    // verify puts commits onto the virtual branch
-    integration::verify_branch(&gb_repository, &project_repository).unwrap();
-
-    // one virtual branch with two commits was created
-    let virtual_branches = list_virtual_branches(&gb_repository, &project_repository)?;
-    assert_eq!(virtual_branches.len(), 1);
+    let notes = list_notes(&project_repository, commit1_id)?;
+    assert_eq!(
+        notes
+            .iter()
+            .map(|note| (note.notes_ref.as_str(), note.message.trim()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("refs/notes/ci", "passed"),
+            ("refs/notes/commits", "reviewed")
+        ]
+    );
 
-    let branch = &virtual_branches.first().unwrap();
-    assert_eq!(branch.commits.len(), 2);
-    assert_eq!(branch.commits.len(), 2);
+    // editing replaces the note, an empty message removes it
+    set_note(&project_repository, commit1_id, None, "approved", None)?;
+    set_note(
+        &project_repository,
+        commit1_id,
+        Some("refs/notes/ci"),
+        "",
+        None,
+    )?;
+    let notes = list_notes(&project_repository, commit1_id)?;
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].notes_ref, "refs/notes/commits");
+    assert_eq!(notes[0].message.trim(), "approved");
+
+    assert!(matches!(
+        set_note(
+            &project_repository,
+            commit1_id,
+            Some("refs/heads/master"),
+            "nope",
+            None
+        ),
+        Err(errors::SetNoteError::InvalidNotesRef(_))
+    ));
 
     Ok(())
 }
 
 #[test]
-fn test_verify_branch_not_integration() -> Result<()> {
-    let Case {
-        project_repository,
-        gb_repository,
-        ..
-    } = Suite::default().new_case();
+fn test_merge_fetched_notes() -> Result<()> {
+    let suite = Suite::default();
+    let (
+        Case {
+            project,
+            gb_repository,
+            project_repository,
+            ..
+        },
+        branch1_id,
+        commit1_id,
+    ) = new_case_with_commit(&suite)?;
+    std::fs::write(
+        project.path.join("test.txt"),
+        "line0\nline1\nline2\nline3\n",
+    )?;
+    let commit2_id = commit(
+        &gb_repository,
+        &project_repository,
+        &branch1_id,
+        "second",
+        None,
+        None,
+        None,
+        false,
+    )?;
 
-    set_test_target(&gb_repository, &project_repository)?;
+    // notes written here that weren't pushed, and others fetched from the remote
+    set_note(&project_repository, commit1_id, None, "reviewed", None)?;
+    let repo = &project_repository.git_repository;
+    let signature = git::Signature::now("test", "test@email.com")?;
+    repo.note(
+        &signature,
+        "refs/notes/remotes/origin/commits",
+        commit1_id,
+        "passed",
+    )?;
+    repo.note(
+        &signature,
+        "refs/notes/remotes/origin/commits",
+        commit2_id,
+        "failed",
+    )?;
 
-    integration::verify_branch(&gb_repository, &project_repository).unwrap();
+    notes::merge_fetched_notes(&project_repository, "origin", None)?;
 
-    project_repository
-        .git_repository
-        .set_head(&"refs/heads/master".parse().unwrap())?;
+    let notes = list_notes(&project_repository, commit1_id)?;
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].notes_ref, "refs/notes/commits");
+    assert_eq!(notes[0].message, "reviewed\n\npassed");
+    let notes = list_notes(&project_repository, commit2_id)?;
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].message, "failed");
 
-    let verify_result = integration::verify_branch(&gb_repository, &project_repository);
-    assert!(verify_result.is_err());
+    // the merge has the fetched notes in its history, pushing it is a fast-forward
+    let local = repo
+        .find_reference(&git::Refname::Other("refs/notes/commits".to_string()))?
+        .target()
+        .unwrap();
+    let fetched = repo
+        .find_reference(&git::Refname::Other(
+            "refs/notes/remotes/origin/commits".to_string(),
+        ))?
+        .target()
+        .unwrap();
+    assert!(repo.is_descendant_of(local, fetched)?);
+
+    // merging again changes nothing
+    notes::merge_fetched_notes(&project_repository, "origin", None)?;
     assert_eq!(
-        verify_result.unwrap_err().to_string(),
-        "head is refs/heads/master"
+        repo.find_reference(&git::Refname::Other("refs/notes/commits".to_string()))?
+            .target(),
+        Some(local)
     );
 
     Ok(())
 }
 
 #[test]
-fn test_pre_commit_hook_rejection() -> Result<()> {
+fn test_bundle_round_trip() -> Result<()> {
     let suite = Suite::default();
-    let Case {
-        project,
-        gb_repository,
-        project_repository,
-        ..
-    } = suite.new_case_with_files(HashMap::from([
-        (
-            path::PathBuf::from("test.txt"),
-            "line1\nline2\nline3\nline4\n",
-        ),
-        (
-            path::PathBuf::from("test2.txt"),
-            "line5\nline6\nline7\nline8\n",
-        ),
-    ]));
+    let (
+        Case {
+            project,
+            gb_repository,
+            project_repository,
+            ..
+        },
+        branch1_id,
+        commit1_id,
+    ) = new_case_with_commit(&suite)?;
+    std::fs::write(project.path.join("test.txt"), "uncommitted\n")?;
+    get_status_by_branch(&gb_repository, &project_repository)?;
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("branches.bundle");
+    export_bundle(&gb_repository, &project_repository, &[], &path)?;
+
+    // it's a bundle git reads, with the branch and its wip commit
+    let output = std::process::Command::new("git")
+        .args(["bundle", "list-heads"])
+        .arg(&path)
+        .current_dir(&project.path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?.lines().count(), 1);
+
+    delete_branch(&gb_repository, &project_repository, &branch1_id)?;
+    assert_eq!(
+        std::fs::read_to_string(project.path.join("test.txt"))?,
+        "line1\nline2\n"
+    );
 
-    set_test_target(&gb_repository, &project_repository)?;
+    let imported = import_bundle(&gb_repository, &project_repository, &path, None, None)?;
+    assert_eq!(imported.len(), 1);
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = branches.iter().find(|b| b.id == imported[0]).unwrap();
+    assert!(branch.active);
+    // the wip commit is uncommitted again
+    assert_eq!(branch.commits.len(), 1);
+    assert_eq!(branch.commits[0].id, commit1_id);
+    assert_eq!(
+        std::fs::read_to_string(project.path.join("test.txt"))?,
+        "uncommitted\n"
+    );
 
-    let branch1_id = create_virtual_branch(
-        &gb_repository,
-        &project_repository,
-        &BranchCreateRequest::default(),
-    )
-    .expect("failed to create virtual branch")
-    .id;
+    assert!(matches!(
+        import_bundle(
+            &gb_repository,
+            &project_repository,
+            &dir.path().join("nope.bundle"),
+            None,
+            None
+        ),
+        Err(errors::ImportBundleError::InvalidBundle(_))
+    ));
+
+    Ok(())
+}
 
+#[test]
+fn test_bundle_keeps_commits_titled_like_wip() -> Result<()> {
+    let suite = Suite::default();
+    let (
+        Case {
+            project,
+            gb_repository,
+            project_repository,
+            ..
+        },
+        branch1_id,
+        _,
+    ) = new_case_with_commit(&suite)?;
     std::fs::write(
-        std::path::Path::new(&project.path).join("test.txt"),
-        "line0\nline1\nline2\nline3\nline4\n",
+        project.path.join("test.txt"),
+        "line0\nline1\nline2\nline3\n",
     )?;
-
-    let hook = b"#!/bin/sh
-    echo 'rejected'
-    exit 1
-            ";
-
-    git2_hooks::create_hook(
-        (&project_repository.git_repository).into(),
-        git2_hooks::HOOK_PRE_COMMIT,
-        hook,
-    );
-
-    let res = commit(
+    let commit2_id = commit(
         &gb_repository,
         &project_repository,
         &branch1_id,
-        "test commit",
+        "GitButler WIP Commit, but mine",
         None,
-        Some(suite.keys.get_or_create()?).as_ref(),
         None,
-        true,
-    );
-
-    let error = res.unwrap_err();
-
-    assert!(matches!(error, CommitError::CommitHookRejected(_)));
+        None,
+        false,
+    )?;
 
-    let CommitError::CommitHookRejected(output) = error else {
-        unreachable!()
+    let repo = &project_repository.git_repository;
+    let targets = || -> Result<Vec<_>> {
+        Ok(repo
+            .references_glob("refs/gitbutler/*")?
+            .map(|reference| reference.map(|reference| reference.target()))
+            .collect::<Result<Vec<_>, _>>()?)
     };
-
-    assert_eq!(&output, "rejected\n");
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("branches.bundle");
+    export_bundle(&gb_repository, &project_repository, &[], &path)?;
+    let exported = targets()?;
+    // exporting again leaves the references as they are
+    export_bundle(&gb_repository, &project_repository, &[], &path)?;
+    assert_eq!(targets()?, exported);
+
+    delete_branch(&gb_repository, &project_repository, &branch1_id)?;
+    let imported = import_bundle(&gb_repository, &project_repository, &path, None, None)?;
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let branch = branches.iter().find(|b| b.id == imported[0]).unwrap();
+    assert_eq!(branch.commits.len(), 2);
+    assert_eq!(branch.commits[0].id, commit2_id);
 
     Ok(())
 }
 
 #[test]
-fn test_post_commit_hook() -> Result<()> {
+fn test_failed_import_deletes_imported_branches() -> Result<()> {
     let suite = Suite::default();
     let Case {
-        project,
         gb_repository,
         project_repository,
         ..
-    } = suite.new_case_with_files(HashMap::from([
-        (
-            path::PathBuf::from("test.txt"),
-            "line1\nline2\nline3\nline4\n",
-        ),
-        (
-            path::PathBuf::from("test2.txt"),
-            "line5\nline6\nline7\nline8\n",
-        ),
-    ]));
+    } = suite.new_case();
 
     set_test_target(&gb_repository, &project_repository)?;
 
-    let branch1_id = create_virtual_branch(
-        &gb_repository,
-        &project_repository,
-        &BranchCreateRequest::default(),
-    )
-    .expect("failed to create virtual branch")
-    .id;
-
-    std::fs::write(
-        std::path::Path::new(&project.path).join("test.txt"),
-        "line0\nline1\nline2\nline3\nline4\n",
-    )?;
-
-    let hook = b"#!/bin/sh
-    touch hook_ran
-            ";
-
-    git2_hooks::create_hook(
-        (&project_repository.git_repository).into(),
-        git2_hooks::HOOK_POST_COMMIT,
-        hook,
-    );
-
-    let hook_ran_proof = project_repository
-        .git_repository
-        .path()
-        .parent()
-        .unwrap()
-        .join("hook_ran");
-
-    assert!(!hook_ran_proof.exists());
-
-    commit(
+    let result = super::bundle::import_branches(
         &gb_repository,
         &project_repository,
-        &branch1_id,
-        "test commit",
         None,
-        Some(suite.keys.get_or_create()?).as_ref(),
         None,
-        true,
-    )?;
-
-    assert!(hook_ran_proof.exists());
+        |imported| {
+            let branch_id = create_virtual_branch(
+                &gb_repository,
+                &project_repository,
+                &BranchCreateRequest::default(),
+            )
+            .expect("failed to create virtual branch")
+            .id;
+            imported.push((branch_id, true));
+            Err(errors::ImportBundleError::InvalidBundle(
+                "broken halfway".to_string(),
+            ))
+        },
+    );
+    assert!(matches!(
+        result,
+        Err(errors::ImportBundleError::InvalidBundle(_))
+    ));
+    assert!(list_virtual_branches(&gb_repository, &project_repository)?.is_empty());
 
     Ok(())
 }
 
 #[test]
-fn test_commit_msg_hook_rejection() -> Result<()> {
+fn test_virtual_branches_archive_round_trip() -> Result<()> {
     let suite = Suite::default();
     let Case {
         project,
         gb_repository,
         project_repository,
         ..
-    } = suite.new_case_with_files(HashMap::from([
-        (
-            path::PathBuf::from("test.txt"),
-            "line1\nline2\nline3\nline4\n",
-        ),
-        (
-            path::PathBuf::from("test2.txt"),
-            "line5\nline6\nline7\nline8\n",
-        ),
-    ]));
+    } = suite.new_case();
 
     set_test_target(&gb_repository, &project_repository)?;
 
     let branch1_id = create_virtual_branch(
         &gb_repository,
         &project_repository,
-        &BranchCreateRequest::default(),
+        &BranchCreateRequest {
+            name: Some("applied".to_string()),
+            ..Default::default()
+        },
     )
     .expect("failed to create virtual branch")
     .id;
-
-    std::fs::write(
-        std::path::Path::new(&project.path).join("test.txt"),
-        "line0\nline1\nline2\nline3\nline4\n",
+    std::fs::write(project.path.join("file.txt"), "applied")?;
+    get_status_by_branch(&gb_repository, &project_repository)?;
+    update_branch(
+        &gb_repository,
+        &project_repository,
+        branch::BranchUpdateRequest {
+            id: branch1_id,
+            notes: Some("some notes".to_string()),
+            ..Default::default()
+        },
     )?;
 
-    let hook = b"#!/bin/sh
-    echo 'rejected'
-    exit 1
-            ";
-
-    git2_hooks::create_hook(
-        (&project_repository.git_repository).into(),
-        git2_hooks::HOOK_COMMIT_MSG,
-        hook,
-    );
-
-    let res = commit(
+    let branch2_id = create_virtual_branch(
         &gb_repository,
         &project_repository,
-        &branch1_id,
-        "test commit",
-        None,
-        Some(suite.keys.get_or_create()?).as_ref(),
-        None,
-        true,
-    );
-
-    let error = res.unwrap_err();
-
-    assert!(matches!(error, CommitError::CommitMsgHookRejected(_)));
+        &BranchCreateRequest {
+            name: Some("unapplied".to_string()),
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )
+    .expect("failed to create virtual branch")
+    .id;
+    std::fs::write(project.path.join("file2.txt"), "unapplied")?;
+    get_status_by_branch(&gb_repository, &project_repository)?;
+    unapply_branch(&gb_repository, &project_repository, &branch2_id)?;
+    assert!(!project.path.join("file2.txt").exists());
 
-    let CommitError::CommitMsgHookRejected(output) = error else {
-        unreachable!()
-    };
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("virtual-branches.gbarchive");
+    export_virtual_branches(&gb_repository, &project_repository, &path)?;
+    delete_branch(&gb_repository, &project_repository, &branch1_id)?;
+    delete_branch(&gb_repository, &project_repository, &branch2_id)?;
+    assert!(!project.path.join("file.txt").exists());
 
-    assert_eq!(&output, "rejected\n");
+    let imported = import_virtual_branches(&gb_repository, &project_repository, &path, None, None)?;
+    assert_eq!(imported.len(), 2);
+    let branches = list_virtual_branches(&gb_repository, &project_repository)?;
+    let applied = branches.iter().find(|b| b.name == "applied").unwrap();
+    assert!(applied.active);
+    assert_eq!(applied.notes, "some notes");
+    assert_eq!(applied.files.len(), 1);
+    assert_eq!(
+        std::fs::read_to_string(project.path.join("file.txt"))?,
+        "applied"
+    );
+    let unapplied = branches.iter().find(|b| b.name == "unapplied").unwrap();
+    assert!(!unapplied.active);
+    assert!(!project.path.join("file2.txt").exists());
+
+    // bundles aren't archives
+    let bundle = dir.path().join("branches.bundle");
+    export_bundle(&gb_repository, &project_repository, &[], &bundle)?;
+    assert!(matches!(
+        import_virtual_branches(&gb_repository, &project_repository, &bundle, None, None),
+        Err(errors::ImportBundleError::InvalidBundle(_))
+    ));
 
     Ok(())
 }
@@ -0,0 +1,454 @@
+//! Discarded changes are kept in a trash of the project for `gitbutler.trashRetentionDays`
+//! days, two weeks if it isn't set, so that discarding a hunk or a file, or deleting a branch
+//! with changes that aren't committed, can be undone. An entry of the trash holds every file
+//! a discard changed, as it was and as it was left, next to the hunks of it that were
+//! discarded.
+//!
+//! Restoring an entry merges the discarded changes back into the files, like
+//! `git merge-file` does, so that what changed in them since is kept. Symlinks and
+//! submodules aren't kept in the trash.
+
+use std::{collections::BTreeSet, fs, io, path, process::Command, time};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    gb_repository,
+    git::{self, diff},
+    id::Id,
+    project_repository,
+};
+
+use super::{branch, errors};
+
+pub type TrashEntryId = Id<TrashEntry>;
+
+const TRASH_DIR: &str = "trash";
+const ENTRY_FILE: &str = "entry.json";
+const DEFAULT_RETENTION_DAYS: i64 = 14;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashEntry {
+    pub id: TrashEntryId,
+    pub created_timestamp_ms: u128,
+    pub files: Vec<TrashedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedFile {
+    #[serde(with = "crate::git::path::escaped")]
+    pub path: path::PathBuf,
+    /// The diffs of the hunks that were discarded.
+    pub hunks: Vec<String>,
+    /// Whether the file was executable before the discard, it is again once restored.
+    #[serde(default)]
+    pub executable: bool,
+}
+
+// a file of the trash, with its content before the discard and after it
+type Trashed = (TrashedFile, Option<Vec<u8>>, Option<Vec<u8>>);
+
+// the content of the file number `index` of an entry, before the discard or after it. a
+// file that didn't exist has none.
+fn content_path(entry_dir: &path::Path, index: usize, side: &str) -> path::PathBuf {
+    entry_dir.join(format!("{index}.{side}"))
+}
+
+fn trash_dir(gb_repository: &gb_repository::Repository) -> path::PathBuf {
+    gb_repository.root().join(TRASH_DIR)
+}
+
+fn now_ms() -> u128 {
+    time::UNIX_EPOCH
+        .elapsed()
+        .map_or(0, |elapsed| elapsed.as_millis())
+}
+
+/// Puts what discarding `discarded` throws away in the trash: the files of the working
+/// directory as they are, and as they are in `tree`, the tree the working directory is
+/// checked out to next.
+pub(super) fn trash_discarded(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    tree: &git::Tree,
+    discarded: &[(path::PathBuf, diff::Hunk)],
+) -> Result<()> {
+    if !purge_before_trashing(gb_repository, project_repository)? {
+        return Ok(());
+    }
+
+    // the old path of a renamed file is put back too
+    let paths = discarded
+        .iter()
+        .flat_map(|(path, hunk)| [Some(path.clone()), hunk.renamed_from()])
+        .flatten()
+        .collect::<BTreeSet<_>>();
+
+    let repo = &project_repository.git_repository;
+    let mut files = vec![];
+    for path in paths {
+        let Some(before) = read_workdir(project_repository, &path)? else {
+            continue;
+        };
+        let Some((after, after_executable)) = read_tree(repo, tree, &path)? else {
+            continue;
+        };
+        let executable = is_executable(&project_repository.path().join(&path));
+        // like the mode only
+        if before == after && executable == after_executable {
+            continue;
+        }
+        let hunks = discarded
+            .iter()
+            .filter(|(hunk_path, _)| *hunk_path == path)
+            .map(|(_, hunk)| hunk.diff.clone())
+            .collect();
+        files.push((
+            TrashedFile {
+                path,
+                hunks,
+                executable,
+            },
+            before,
+            after,
+        ));
+    }
+    write_entry(gb_repository, &files)
+}
+
+/// Puts the changes of `branch` that aren't committed in the trash before it's deleted: the
+/// files as they are in its tree, and as they are in the tree of its head, without them.
+/// Unapplied, which it is by then, its changes are only in its tree, the working directory
+/// has nothing to do with them.
+pub(super) fn trash_branch(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch: &branch::Branch,
+) -> Result<()> {
+    if !purge_before_trashing(gb_repository, project_repository)? {
+        return Ok(());
+    }
+
+    let repo = &project_repository.git_repository;
+    let head_tree = repo
+        .find_commit(branch.head)
+        .and_then(|commit| commit.tree())
+        .context("failed to find branch head tree")?;
+    let branch_tree = repo
+        .find_tree(branch.tree)
+        .context("failed to find branch tree")?;
+    let changes =
+        diff::trees(repo, &head_tree, &branch_tree).context("failed to diff branch tree")?;
+
+    let mut files = vec![];
+    for (path, hunks) in changes {
+        let mut paths = vec![path.clone()];
+        paths.extend(hunks.iter().find_map(diff::Hunk::renamed_from));
+        for path in paths {
+            let Some((before, executable)) = read_tree(repo, &branch_tree, &path)? else {
+                continue;
+            };
+            let Some((after, after_executable)) = read_tree(repo, &head_tree, &path)? else {
+                continue;
+            };
+            if before == after && executable == after_executable {
+                continue;
+            }
+            let hunks = hunks.iter().map(|hunk| hunk.diff.clone()).collect();
+            files.push((
+                TrashedFile {
+                    path,
+                    hunks,
+                    executable,
+                },
+                before,
+                after,
+            ));
+        }
+    }
+    write_entry(gb_repository, &files)
+}
+
+// purges the entries of the trash that expired, `false` if nothing is kept in it at all
+fn purge_before_trashing(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<bool> {
+    let retention_days = retention_days(project_repository)?;
+    purge_expired(gb_repository, retention_days)?;
+    Ok(retention_days > 0)
+}
+
+fn write_entry(gb_repository: &gb_repository::Repository, files: &[Trashed]) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let entry = TrashEntry {
+        id: TrashEntryId::generate(),
+        created_timestamp_ms: now_ms(),
+        files: files.iter().map(|(file, _, _)| file.clone()).collect(),
+    };
+    let entry_dir = trash_dir(gb_repository).join(entry.id.to_string());
+    fs::create_dir_all(&entry_dir).context("failed to create trash entry")?;
+    for (index, (_, before, after)) in files.iter().enumerate() {
+        for (side, content) in [("before", before), ("after", after)] {
+            if let Some(content) = content {
+                fs::write(content_path(&entry_dir, index, side), content)
+                    .context("failed to write trashed file")?;
+            }
+        }
+    }
+    // written last, an entry without it is incomplete
+    fs::write(
+        entry_dir.join(ENTRY_FILE),
+        serde_json::to_vec(&entry).context("failed to serialize trash entry")?,
+    )
+    .context("failed to write trash entry")?;
+    Ok(())
+}
+
+// the content of a file of the working directory, `Some(None)` if it doesn't exist and
+// `None` if it isn't kept in the trash
+fn read_workdir(
+    project_repository: &project_repository::Repository,
+    path: &path::Path,
+) -> Result<Option<Option<Vec<u8>>>> {
+    let full_path = project_repository.path().join(path);
+    match fs::symlink_metadata(&full_path) {
+        Ok(metadata) if metadata.is_file() => fs::read(&full_path)
+            .map(|content| Some(Some(content)))
+            .with_context(|| format!("failed to read {}", full_path.display())),
+        Ok(_) => Ok(None),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Some(None)),
+        Err(error) => Err(error).with_context(|| format!("failed to read {}", full_path.display())),
+    }
+}
+
+// the content of a file of `tree` and whether it's executable, `Some((None, false))` if it
+// isn't in it and `None` if it isn't kept in the trash
+fn read_tree(
+    repo: &git::Repository,
+    tree: &git::Tree,
+    path: &path::Path,
+) -> Result<Option<(Option<Vec<u8>>, bool)>> {
+    let Ok(entry) = tree.get_path(path) else {
+        return Ok(Some((None, false)));
+    };
+    let executable = match git::FileMode::try_from(entry.filemode()) {
+        Ok(git::FileMode::Blob) => false,
+        Ok(git::FileMode::BlobExecutable) => true,
+        _ => return Ok(None),
+    };
+    let blob = repo
+        .find_blob(entry.id())
+        .context("failed to find trashed blob")?;
+    Ok(Some((Some(blob.content().to_vec()), executable)))
+}
+
+#[cfg(unix)]
+fn is_executable(full_path: &path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(full_path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+// without execute bits, like on windows, no file is
+#[cfg(not(unix))]
+fn is_executable(_full_path: &path::Path) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn set_executable(full_path: &path::Path, executable: bool) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(full_path)?.permissions();
+    let mode = permissions.mode();
+    // the execute bits follow the read bits, like git checks files out
+    permissions.set_mode(if executable {
+        mode | ((mode & 0o444) >> 2)
+    } else {
+        mode & !0o111
+    });
+    fs::set_permissions(full_path, permissions)
+}
+
+#[cfg(not(unix))]
+fn set_executable(_full_path: &path::Path, _executable: bool) -> io::Result<()> {
+    Ok(())
+}
+
+fn retention_days(project_repository: &project_repository::Repository) -> Result<i64> {
+    Ok(project_repository
+        .config()
+        .trash_retention_days()
+        .context("failed to read trash retention config")?
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+        .max(0))
+}
+
+fn read_entries(gb_repository: &gb_repository::Repository) -> Result<Vec<TrashEntry>> {
+    let trash_dir = trash_dir(gb_repository);
+    let dir = match fs::read_dir(&trash_dir) {
+        Ok(dir) => dir,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error).context("failed to read trash"),
+    };
+    let mut entries = vec![];
+    for dir_entry in dir {
+        let entry_file = dir_entry
+            .context("failed to read trash")?
+            .path()
+            .join(ENTRY_FILE);
+        let Ok(entry) = fs::read(&entry_file) else {
+            continue;
+        };
+        match serde_json::from_slice::<TrashEntry>(&entry) {
+            Ok(entry) => entries.push(entry),
+            Err(error) => {
+                tracing::warn!(?error, path = %entry_file.display(), "malformed trash entry");
+            }
+        }
+    }
+    entries.sort_by(|a, b| b.created_timestamp_ms.cmp(&a.created_timestamp_ms));
+    Ok(entries)
+}
+
+fn purge_expired(gb_repository: &gb_repository::Repository, retention_days: i64) -> Result<()> {
+    let retention_ms = u128::try_from(retention_days).unwrap_or(0) * 24 * 60 * 60 * 1000;
+    let now = now_ms();
+    for entry in read_entries(gb_repository)? {
+        if entry.created_timestamp_ms + retention_ms <= now {
+            remove_entry(gb_repository, &entry.id)?;
+        }
+    }
+    Ok(())
+}
+
+fn remove_entry(gb_repository: &gb_repository::Repository, id: &TrashEntryId) -> Result<()> {
+    match fs::remove_dir_all(trash_dir(gb_repository).join(id.to_string())) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => {
+            Err(error).context("failed to remove trash entry")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The entries of the trash, the newest first.
+pub fn list_trash(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<Vec<TrashEntry>, errors::TrashError> {
+    purge_expired(gb_repository, retention_days(project_repository)?)?;
+    read_entries(gb_repository).map_err(Into::into)
+}
+
+/// Removes the entry `entry_id` from the trash, or every entry if it's `None`.
+pub fn purge_trash(
+    gb_repository: &gb_repository::Repository,
+    entry_id: Option<&TrashEntryId>,
+) -> Result<(), errors::TrashError> {
+    match entry_id {
+        Some(entry_id) => remove_entry(gb_repository, entry_id)?,
+        None => {
+            for entry in read_entries(gb_repository)? {
+                remove_entry(gb_repository, &entry.id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Puts the changes of the trash entry `entry_id` back in the working directory, and takes
+/// the entry out of the trash. Nothing is restored if a file changed since in a way the
+/// discarded changes can't be merged with.
+pub fn restore_from_trash(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    entry_id: &TrashEntryId,
+) -> Result<(), errors::TrashError> {
+    let entry = read_entries(gb_repository)?
+        .into_iter()
+        .find(|entry| entry.id == *entry_id)
+        .ok_or(errors::TrashError::EntryNotFound(*entry_id))?;
+    let entry_dir = trash_dir(gb_repository).join(entry.id.to_string());
+
+    let mut restored = vec![];
+    let mut conflicts = vec![];
+    for (index, file) in entry.files.iter().enumerate() {
+        let read = |side| {
+            let content_path = content_path(&entry_dir, index, side);
+            match fs::read(&content_path) {
+                Ok(content) => Ok(Some(content)),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(error) => Err(error).context("failed to read trashed file"),
+            }
+        };
+        let (before, after) = (read("before")?, read("after")?);
+        let current = read_workdir(project_repository, &file.path)?;
+        let restored_content = match current {
+            // changed to a symlink or a directory since
+            None => None,
+            Some(current) if current == after => Some(before),
+            Some(current) if current == before => continue,
+            Some(Some(current)) => match (&before, &after) {
+                (Some(_), Some(_)) => merge(&entry_dir, index, &current)?.map(Some),
+                _ => None,
+            },
+            Some(None) => None,
+        };
+        match restored_content {
+            Some(content) => restored.push((file, content)),
+            None => conflicts.push(file.path.clone()),
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(errors::TrashError::Conflict(conflicts));
+    }
+
+    for (file, content) in restored {
+        let full_path = project_repository.path().join(&file.path);
+        let result = match content {
+            Some(content) => {
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent).context("failed to create directory")?;
+                }
+                fs::write(&full_path, content)
+                    .and_then(|()| set_executable(&full_path, file.executable))
+            }
+            None => fs::remove_file(&full_path),
+        };
+        result.with_context(|| format!("failed to restore {}", full_path.display()))?;
+    }
+    remove_entry(gb_repository, &entry.id)?;
+    Ok(())
+}
+
+// merges the discarded changes of the file number `index` into `current`, `None` if they
+// conflict
+fn merge(entry_dir: &path::Path, index: usize, current: &[u8]) -> Result<Option<Vec<u8>>> {
+    let current_path = content_path(entry_dir, index, "current");
+    fs::write(&current_path, current).context("failed to write current file")?;
+    let output = Command::new("git")
+        .args(["merge-file", "-p", "--quiet"])
+        .arg(&current_path)
+        .arg(content_path(entry_dir, index, "after"))
+        .arg(content_path(entry_dir, index, "before"))
+        .output()
+        .context("failed to run git, is it installed?");
+    fs::remove_file(&current_path).context("failed to remove current file")?;
+    let output = output?;
+    // the exit code is the number of conflicts, or negative if merging failed
+    match output.status.code() {
+        Some(0) => Ok(Some(output.stdout)),
+        Some(code) if code > 0 => Ok(None),
+        _ => anyhow::bail!(
+            "git merge-file failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
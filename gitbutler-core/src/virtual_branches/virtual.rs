@@ -1,13 +1,22 @@
-use std::{collections::HashMap, path, time, vec};
+use std::{
+    collections::{HashMap, HashSet},
+    path,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time, vec,
+};
 
 #[cfg(target_family = "unix")]
 use std::os::unix::prelude::*;
 
 use anyhow::{bail, Context, Result};
+use bstr::ByteSlice;
 use diffy::{apply_bytes, Patch};
 use git2_hooks::HookResult;
+use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::instrument;
 
 use crate::{
     dedup::{dedup, dedup_fmt},
@@ -15,12 +24,15 @@ use crate::{
     git::{self, diff, show, Commit, Refname, RemoteRefname},
     keys,
     project_repository::{self, conflicts, LogUntil},
+    projects::ProjectId,
     reader, sessions, users,
 };
 
 use super::{
     branch::{self, Branch, BranchCreateRequest, BranchId, FileOwnership, Hunk, Ownership},
-    branch_to_remote_branch, context, errors, target, Iterator, RemoteBranch,
+    branch_to_remote_branch, context, errors, find_conflict_markers, pre_push, target, trash,
+    Iterator, LargeFile, LargeFilesCheck, LargeFilesPolicy, PrePushProgress, PrePushReport,
+    Progress, RemoteBranch, Scanner, SecretFinding,
 };
 
 type AppliedStatuses = Vec<(branch::Branch, HashMap<path::PathBuf, Vec<diff::Hunk>>)>;
@@ -94,11 +106,19 @@ pub struct VirtualBranchCommit {
 #[serde(rename_all = "camelCase")]
 pub struct VirtualBranchFile {
     pub id: String,
+    #[serde(with = "crate::git::path::escaped")]
     pub path: path::PathBuf,
+    /// The path the file was renamed from, if it was.
+    #[serde(with = "crate::git::path::escaped::option")]
+    pub renamed_from: Option<path::PathBuf>,
+    /// The path of the file it is a copy of, if it is one.
+    #[serde(with = "crate::git::path::escaped::option")]
+    pub copied_from: Option<path::PathBuf>,
     pub hunks: Vec<VirtualBranchHunk>,
     pub modified_at: u128,
     pub conflicted: bool,
     pub binary: bool,
+    pub generated: bool,
 }
 
 // this struct is a mapping to the view `Hunk` type in Typescript
@@ -115,12 +135,14 @@ pub struct VirtualBranchHunk {
     pub id: String,
     pub diff: String,
     pub modified_at: u128,
+    #[serde(with = "crate::git::path::escaped")]
     pub file_path: path::PathBuf,
     pub hash: String,
     pub old_start: u32,
     pub start: u32,
     pub end: u32,
     pub binary: bool,
+    pub generated: bool,
     pub locked: bool,
     pub locked_to: Option<git::Oid>,
     pub change_type: diff::ChangeType,
@@ -153,6 +175,16 @@ impl From<git::Signature<'_>> for Author {
     }
 }
 
+impl Author {
+    /// The author of `commit` by the name and email the mailmap says they go by.
+    pub fn of(commit: &git::Commit, mailmap: &git::Mailmap) -> Result<Self> {
+        let signature = mailmap
+            .resolve(&commit.author())
+            .context("failed to resolve author")?;
+        Ok(signature.into())
+    }
+}
+
 pub fn normalize_branch_name(name: &str) -> String {
     // Technically this pattern should include ".", but it makes libgit2 throws an error
     let pattern = Regex::new("[^A-Za-z0-9_/]+").unwrap();
@@ -278,10 +310,9 @@ pub fn apply_branch(
             let mut merge_conflicts = Vec::new();
             for path in conflicts.flatten() {
                 if let Some(ours) = path.our {
-                    let path = std::str::from_utf8(&ours.path)
-                        .context("failed to convert path to utf8")?
-                        .to_string();
-                    merge_conflicts.push(path);
+                    merge_conflicts.push(git::path::escape(&git::path::from_bytes(
+                        ours.path.as_bstr(),
+                    )));
                 }
             }
             conflicts::mark(
@@ -514,10 +545,28 @@ pub fn unapply_ownership(
         .flatten()
         .collect::<Vec<_>>();
 
+    // a renamed file is put back at its old path as it was, and a copy is removed, rather than
+    // reversed hunk by hunk
+    let renames = hunks_to_unapply
+        .iter()
+        .filter_map(|(path, hunk)| {
+            if let Some(old_path) = hunk.renamed_from() {
+                Some((path.clone(), Some(old_path)))
+            } else {
+                hunk.copied_from().map(|_| (path.clone(), None))
+            }
+        })
+        .collect::<HashMap<_, _>>();
+
     let mut diff = HashMap::new();
-    for h in hunks_to_unapply {
+    for h in &hunks_to_unapply {
+        if renames.contains_key(&h.0) {
+            continue;
+        }
         if let Some(reversed_hunk) = diff::reverse_hunk(&h.1) {
-            diff.entry(h.0).or_insert_with(Vec::new).push(reversed_hunk);
+            diff.entry(h.0.clone())
+                .or_insert_with(Vec::new)
+                .push(reversed_hunk);
         } else {
             return Err(errors::UnapplyOwnershipError::Other(anyhow::anyhow!(
                 "failed to reverse hunk"
@@ -545,16 +594,44 @@ pub fn unapply_ownership(
         },
     )?;
 
-    let final_tree_oid = write_tree_onto_tree(project_repository, &final_tree, &diff)?;
+    let mut final_tree_oid = write_tree_onto_tree(project_repository, &final_tree, &diff)?;
+    if !renames.is_empty() {
+        let final_tree = repo
+            .find_tree(final_tree_oid)
+            .context("failed to find tree")?;
+        let mut builder = repo.treebuilder(Some(&final_tree));
+        for (new_path, old_path) in &renames {
+            builder.remove(new_path);
+            let Some(old_path) = old_path else {
+                continue;
+            };
+            if let Ok(entry) = base_tree.get_path(old_path) {
+                builder.upsert(
+                    old_path,
+                    entry.id(),
+                    git::FileMode::try_from(entry.filemode()).context("failed to get filemode")?,
+                );
+            }
+        }
+        final_tree_oid = builder.write().context("failed to write tree")?;
+    }
     let final_tree = repo
         .find_tree(final_tree_oid)
         .context("failed to find tree")?;
 
-    repo.checkout_tree(&final_tree)
-        .force()
-        .remove_untracked()
-        .checkout()
-        .context("failed to checkout tree")?;
+    trash::trash_discarded(
+        gb_repository,
+        project_repository,
+        &final_tree,
+        &hunks_to_unapply,
+    )
+    .context("failed to put discarded changes in the trash")?;
+
+    checkout_owned(
+        project_repository,
+        &final_tree,
+        hunks_to_unapply.iter().map(|(path, _)| path.as_path()),
+    )?;
 
     super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
 
@@ -567,6 +644,8 @@ pub fn unapply_branch(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
 ) -> Result<Option<branch::Branch>, errors::UnapplyBranchError> {
+    // the files of the branch, that are removed unless another branch has them too
+    let mut owned_paths = vec![];
     let session = &gb_repository
         .get_or_create_current_session()
         .context("failed to get or create currnt session")?;
@@ -655,6 +734,7 @@ pub fn unapply_branch(
             }
 
             target_branch.tree = write_tree(project_repository, &default_target, files)?;
+            owned_paths.extend(files.keys().cloned());
             target_branch.applied = false;
             target_branch.selected_for_changes = None;
             branch_writer.write(&mut target_branch)?;
@@ -692,39 +772,72 @@ pub fn unapply_branch(
     };
 
     // checkout final_tree into the working directory
-    repo.checkout_tree(&final_tree)
-        .force()
-        .remove_untracked()
-        .checkout()
-        .context("failed to checkout tree")?;
+    checkout_owned(
+        project_repository,
+        &final_tree,
+        owned_paths.iter().map(path::PathBuf::as_path),
+    )?;
 
     super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
 
     Ok(Some(target_branch))
 }
 
-fn find_base_tree<'a>(
-    repo: &'a git::Repository,
-    branch_commit: &'a git::Commit<'a>,
-    target_commit: &'a git::Commit<'a>,
-) -> Result<git::Tree<'a>> {
-    // find merge base between target_commit and branch_commit
-    let merge_base = repo
-        .merge_base(target_commit.id(), branch_commit.id())
-        .context("failed to find merge base")?;
-    // turn oid into a commit
-    let merge_base_commit = repo
-        .find_commit(merge_base)
-        .context("failed to find merge base commit")?;
-    let base_tree = merge_base_commit
-        .tree()
-        .context("failed to get base tree object")?;
-    Ok(base_tree)
+// checks `tree` out over the working directory, removing the files of `owned_paths` it doesn't
+// have, like those a branch that is unapplied added. Untracked files no branch owns, like
+// those of unmanaged paths, are left alone.
+fn checkout_owned<'p>(
+    project_repository: &project_repository::Repository,
+    tree: &git::Tree,
+    owned_paths: impl IntoIterator<Item = &'p path::Path>,
+) -> Result<()> {
+    project_repository
+        .git_repository
+        .checkout_tree(tree)
+        .force()
+        .checkout()
+        .context("failed to checkout tree")?;
+    let root = project_repository.path();
+    for owned_path in owned_paths {
+        if tree.get_path(owned_path).is_ok() {
+            continue;
+        }
+        let path = root.join(owned_path);
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if !metadata.is_dir() => std::fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?,
+            _ => continue,
+        }
+        // and the directories that were only there for it
+        for parent in owned_path.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() || std::fs::remove_dir(root.join(parent)).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
 }
 
 pub fn list_virtual_branches(
     gb_repository: &gb_repository::Repository,
     project_repository: &project_repository::Repository,
+) -> Result<Vec<VirtualBranch>, errors::ListVirtualBranchesError> {
+    list_virtual_branches_cancellable(
+        gb_repository,
+        project_repository,
+        &CancellationToken::new(),
+        &Progress::default(),
+    )
+}
+
+// same as list_virtual_branches, but checks `cancel` between the expensive steps
+// so that a superseded recomputation stops early, and reports diff progress to `progress`.
+#[instrument(skip_all, fields(project_id = %project_repository.project().id), level = "debug")]
+pub fn list_virtual_branches_cancellable(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    cancel: &CancellationToken,
+    progress: &Progress,
 ) -> Result<Vec<VirtualBranch>, errors::ListVirtualBranchesError> {
     let mut branches: Vec<VirtualBranch> = Vec::new();
 
@@ -739,13 +852,25 @@ pub fn list_virtual_branches(
             )
         })?;
 
-    let statuses = get_status_by_branch(gb_repository, project_repository)?;
+    if cancel.is_cancelled() {
+        return Err(errors::ListVirtualBranchesError::Cancelled);
+    }
+
+    let statuses = get_status_by_branch_with_progress(gb_repository, project_repository, progress)?;
+    let mailmap = project_repository
+        .git_repository
+        .mailmap()
+        .context("failed to read mailmap")?;
     let max_selected_for_changes = statuses
         .iter()
         .filter_map(|(branch, _)| branch.selected_for_changes)
         .max()
         .unwrap_or(-1);
     for (branch, files) in &statuses {
+        if cancel.is_cancelled() {
+            return Err(errors::ListVirtualBranchesError::Cancelled);
+        }
+
         // check if head tree does not match target tree
         // if so, we diff the head tree and the new write_tree output to see what is new and filter the hunks to just those
         let files =
@@ -816,6 +941,7 @@ pub fn list_virtual_branches(
 
                 commit_to_vbranch_commit(
                     project_repository,
+                    &mailmap,
                     branch,
                     commit,
                     is_integrated,
@@ -828,9 +954,7 @@ pub fn list_virtual_branches(
         let mut base_current = true;
         if !branch.applied {
             // determine if this branch is up to date with the target/base
-            let merge_base = repo
-                .merge_base(default_target.sha, branch.head)
-                .context("failed to find merge base")?;
+            let merge_base = project_repository.merge_base(default_target.sha, branch.head)?;
             if merge_base != default_target.sha {
                 base_current = false;
             }
@@ -881,15 +1005,18 @@ pub fn list_virtual_branches(
             selected_for_changes: branch.selected_for_changes == Some(max_selected_for_changes),
             head: branch.head,
         };
+        progress.branch_done(&branch);
         branches.push(branch);
     }
 
     let mut branches = branches_with_hunk_locks(branches, project_repository)?;
+    let context_lines =
+        diff::Options::from(project_repository.project().diff_options).context_lines;
     for branch in &mut branches {
         branch.files = files_with_hunk_context(
             &project_repository.git_repository,
             branch.files.clone(),
-            3,
+            context_lines as usize,
             branch.head,
         )
         .context("failed to add hunk context")?;
@@ -966,9 +1093,15 @@ fn files_with_hunk_context(
         // Get file content as it looked before the diffs
         let branch_head_commit = repository.find_commit(branch_head)?;
         let head_tree = branch_head_commit.tree()?;
-        let file_content_before =
-            show::show_file_at_tree(repository, file.path.clone(), &head_tree)
-                .context("failed to get file contents at base")?;
+        let file_content_before = show::show_file_at_tree(
+            repository,
+            file.renamed_from
+                .as_ref()
+                .or(file.copied_from.as_ref())
+                .unwrap_or(&file.path),
+            &head_tree,
+        )
+        .context("failed to get file contents at base")?;
         let file_lines_before = file_content_before.split('\n').collect::<Vec<_>>();
 
         // Update each hunk with contex lines before & after
@@ -976,7 +1109,7 @@ fn files_with_hunk_context(
             .hunks
             .iter()
             .map(|hunk| {
-                if hunk.diff.is_empty() {
+                if hunk.diff.is_empty() || diff::is_header(&hunk.diff) {
                     // noop on empty diff
                     Ok(hunk.clone())
                 } else {
@@ -1091,7 +1224,7 @@ pub fn calculate_non_commited_diffs(
                 }
             }
             if !conflicted {
-                conflicts::resolve(project_repository, &file_path.display().to_string()).unwrap();
+                conflicts::resolve(project_repository, &git::path::escape(&file_path)).unwrap();
             }
         }
     }
@@ -1127,13 +1260,14 @@ fn list_virtual_commit_files(
 
 fn commit_to_vbranch_commit(
     repository: &project_repository::Repository,
+    mailmap: &git::Mailmap,
     branch: &branch::Branch,
     commit: &git::Commit,
     is_integrated: bool,
     is_remote: bool,
 ) -> Result<VirtualBranchCommit> {
     let timestamp = u128::try_from(commit.time().seconds())?;
-    let signature = commit.author();
+    let author = Author::of(commit, mailmap)?;
     let message = commit.message().unwrap().to_string();
 
     let files =
@@ -1144,7 +1278,7 @@ fn commit_to_vbranch_commit(
     let commit = VirtualBranchCommit {
         id: commit.id(),
         created_at: timestamp * 1000,
-        author: Author::from(signature),
+        author,
         description: message,
         is_remote,
         files,
@@ -1399,10 +1533,9 @@ pub fn merge_virtual_branch_upstream(
         let mut merge_conflicts = Vec::new();
         for path in conflicts.flatten() {
             if let Some(ours) = path.our {
-                let path = std::str::from_utf8(&ours.path)
-                    .context("failed to convert path to utf8")?
-                    .to_string();
-                merge_conflicts.push(path);
+                merge_conflicts.push(git::path::escape(&git::path::from_bytes(
+                    ours.path.as_bstr(),
+                )));
             }
         }
         conflicts::mark(
@@ -1574,9 +1707,18 @@ pub fn delete_branch(
     }
     .context("failed to read branch")?;
 
-    if branch.applied && unapply_branch(gb_repository, project_repository, branch_id)?.is_none() {
-        return Ok(());
-    }
+    // unapplied first, its tree has all of its changes
+    let branch = if branch.applied {
+        match unapply_branch(gb_repository, project_repository, branch_id)? {
+            Some(branch) => branch,
+            None => return Ok(()),
+        }
+    } else {
+        branch
+    };
+
+    trash::trash_branch(gb_repository, project_repository, &branch)
+        .context("failed to put the changes of the branch in the trash")?;
 
     branch_writer
         .delete(&branch)
@@ -1710,6 +1852,7 @@ pub fn virtual_hunks_by_filepath(
                     start: hunk.new_start,
                     end: hunk.new_start + hunk.new_lines,
                     binary: hunk.binary,
+                    generated: hunk.generated,
                     hash: diff_hash(&hunk.diff),
                     locked: false,
                     locked_to: None,
@@ -1727,6 +1870,15 @@ pub type BranchStatus = HashMap<path::PathBuf, Vec<diff::Hunk>>;
 pub fn get_status_by_branch(
     gb_repository: &gb_repository::Repository,
     project_repository: &project_repository::Repository,
+) -> Result<Vec<(branch::Branch, BranchStatus)>> {
+    get_status_by_branch_with_progress(gb_repository, project_repository, &Progress::default())
+}
+
+#[instrument(skip_all, fields(project_id = %project_repository.project().id), level = "debug")]
+pub fn get_status_by_branch_with_progress(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    progress: &Progress,
 ) -> Result<Vec<(branch::Branch, BranchStatus)>> {
     let latest_session = gb_repository
         .get_latest_session()
@@ -1754,11 +1906,12 @@ pub fn get_status_by_branch(
         .cloned()
         .collect::<Vec<_>>();
 
-    let applied_status = get_applied_status(
+    let applied_status = get_applied_status_with_progress(
         gb_repository,
         project_repository,
         &default_target,
         applied_virtual_branches,
+        progress,
     )?;
 
     let non_applied_virtual_branches = virtual_branches
@@ -1782,6 +1935,7 @@ pub fn get_status_by_branch(
 // virtual branch latest tree
 //
 // ownerships are not taken into account here, as they are not relevant for non applied branches
+#[instrument(skip_all, fields(project_id = %project_repository.project().id), level = "debug")]
 fn get_non_applied_status(
     project_repository: &project_repository::Repository,
     default_target: &target::Target,
@@ -1823,13 +1977,42 @@ fn get_non_applied_status(
 //
 // ownerships are updated if nessessary
 fn get_applied_status(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    default_target: &target::Target,
+    virtual_branches: Vec<branch::Branch>,
+) -> Result<AppliedStatuses> {
+    get_applied_status_with_progress(
+        gb_repository,
+        project_repository,
+        default_target,
+        virtual_branches,
+        &Progress::default(),
+    )
+}
+
+#[instrument(skip_all, fields(project_id = %project_repository.project().id), level = "debug")]
+fn get_applied_status_with_progress(
     gb_repository: &gb_repository::Repository,
     project_repository: &project_repository::Repository,
     default_target: &target::Target,
     mut virtual_branches: Vec<branch::Branch>,
+    progress: &Progress,
 ) -> Result<AppliedStatuses> {
-    let mut diff = diff::workdir(&project_repository.git_repository, &default_target.sha)
-        .context("failed to diff workdir")?;
+    // whitespace is never ignored here, the hunks are what gets committed
+    let mut diff = diff::workdir_with_progress(
+        &project_repository.git_repository,
+        &default_target.sha,
+        &diff::Options {
+            detect_copies: project_repository.project().detect_copies.unwrap_or(false),
+            algorithm: project_repository.project().diff_options.algorithm,
+            // unmanaged files are left as they are, none of their hunks are owned
+            unmanaged: project_repository.project().unmanaged(),
+            ..Default::default()
+        },
+        &|scanned, total| progress.set(scanned, total),
+    )
+    .context("failed to diff workdir")?;
 
     // sort by order, so that the default branch is first (left in the ui)
     virtual_branches.sort_by(|a, b| a.order.cmp(&b.order));
@@ -1856,6 +2039,16 @@ fn get_applied_status(
 
     let mut mtimes = HashMap::new();
 
+    // on case insensitive filesystems, both halves of a case only rename (the deletion of the
+    // old name and the addition of the new one) stay together, on the branch that owned the
+    // file before it was renamed
+    let case_only_renames_owners = if project_repository.git_repository.ignore_case() {
+        case_only_renames_owners(&virtual_branches, diff.keys())
+    } else {
+        HashMap::new()
+    };
+    let renames_owners = renames_owners(&virtual_branches, &diff);
+
     for branch in &mut virtual_branches {
         if !branch.applied {
             bail!("branch {} is not applied", branch.name);
@@ -1868,6 +2061,13 @@ fn get_applied_status(
                 .files
                 .iter()
                 .filter_map(|file_owership| {
+                    if renames_owners
+                        .get(&file_owership.file_path)
+                        .is_some_and(|owner| *owner != branch.id)
+                    {
+                        // the renamed file is all on the branch of the rename, below
+                        return None;
+                    }
                     let current_hunks = match diff.get_mut(&file_owership.file_path) {
                         None => {
                             // if the file is not in the diff, we don't want it
@@ -1961,17 +2161,20 @@ fn get_applied_status(
 
     // put the remaining hunks into the default (first) branch
     for (filepath, hunks) in diff {
+        let vbranch_pos = renames_owners
+            .get(&filepath)
+            .or_else(|| case_only_renames_owners.get(&crate::fs::normalize_case(&filepath)))
+            .and_then(|branch_id| virtual_branches.iter().position(|b| b.id == *branch_id))
+            .unwrap_or(default_vbranch_pos);
         for hunk in hunks {
-            virtual_branches[default_vbranch_pos]
-                .ownership
-                .put(&FileOwnership {
-                    file_path: filepath.clone(),
-                    hunks: vec![Hunk::from(&hunk)
-                        .with_timestamp(get_mtime(&mut mtimes, &filepath))
-                        .with_hash(diff_hash(hunk.diff.as_str()).as_str())],
-                });
+            virtual_branches[vbranch_pos].ownership.put(&FileOwnership {
+                file_path: filepath.clone(),
+                hunks: vec![Hunk::from(&hunk)
+                    .with_timestamp(get_mtime(&mut mtimes, &filepath))
+                    .with_hash(diff_hash(hunk.diff.as_str()).as_str())],
+            });
             hunks_by_branch_id
-                .entry(virtual_branches[default_vbranch_pos].id)
+                .entry(virtual_branches[vbranch_pos].id)
                 .or_default()
                 .entry(filepath.clone())
                 .or_default()
@@ -1997,17 +2200,105 @@ fn get_applied_status(
     if !project_repository.is_resolving() {
         let branch_writer =
             branch::Writer::new(gb_repository).context("failed to create writer")?;
+        let base_tree = project_repository
+            .git_repository
+            .find_commit(default_target.sha)?
+            .tree()?;
+        let project_id = project_repository.project().id;
+        // of the branches that aren't applied anymore, deleted ones included, nothing is kept
+        let mut previously_written = written_trees().remove(&project_id).unwrap_or_default();
+        let mut written_now = HashMap::new();
         for (vbranch, files) in &mut hunks_by_branch {
-            vbranch.tree = write_tree(project_repository, default_target, files)?;
+            let written = previously_written.remove(&vbranch.id);
+            vbranch.tree = match written {
+                Some(written) if written.base == base_tree.id() && written.tree == vbranch.tree => {
+                    let previous_tree =
+                        project_repository.git_repository.find_tree(vbranch.tree)?;
+                    write_tree_onto_tree_incremental(
+                        project_repository,
+                        &base_tree,
+                        &previous_tree,
+                        &written.paths,
+                        files,
+                    )?
+                }
+                // written by another operation, or before the app started
+                _ => write_tree(project_repository, default_target, files)?,
+            };
+            written_now.insert(
+                vbranch.id,
+                WrittenTree {
+                    base: base_tree.id(),
+                    tree: vbranch.tree,
+                    paths: written_paths(files),
+                },
+            );
             branch_writer
                 .write(vbranch)
                 .context(format!("failed to write virtual branch {}", vbranch.name))?;
         }
+        written_trees().insert(project_id, written_now);
     }
 
     Ok(hunks_by_branch)
 }
 
+// returns the owner of every case only renamed file in `paths`, keyed by normalized path.
+// a file has been renamed when it appears more than once in `paths`, under different cases.
+fn case_only_renames_owners<'a>(
+    virtual_branches: &[branch::Branch],
+    paths: impl Iterator<Item = &'a path::PathBuf>,
+) -> HashMap<path::PathBuf, BranchId> {
+    let mut variants = HashMap::<path::PathBuf, usize>::new();
+    for path in paths {
+        *variants.entry(crate::fs::normalize_case(path)).or_default() += 1;
+    }
+    virtual_branches
+        .iter()
+        .flat_map(|branch| {
+            branch
+                .ownership
+                .files
+                .iter()
+                .map(move |file| (crate::fs::normalize_case(&file.file_path), branch.id))
+        })
+        .filter(|(path, _)| variants.get(path).is_some_and(|count| *count > 1))
+        .collect()
+}
+
+// the branch each renamed file is kept on whole, the rename with the changes made to the file:
+// the one that owned it before it was renamed, or the one it was last moved to otherwise
+fn renames_owners(
+    virtual_branches: &[branch::Branch],
+    files: &HashMap<path::PathBuf, Vec<diff::Hunk>>,
+) -> HashMap<path::PathBuf, BranchId> {
+    let owner_of = |path: &path::Path| {
+        virtual_branches
+            .iter()
+            .filter_map(|branch| {
+                let file = branch
+                    .ownership
+                    .files
+                    .iter()
+                    .find(|file| file.file_path == path)?;
+                Some((
+                    file.hunks.iter().filter_map(Hunk::timestam_ms).max(),
+                    branch.id,
+                ))
+            })
+            .max_by_key(|(timestamp_ms, _)| *timestamp_ms)
+            .map(|(_, branch_id)| branch_id)
+    };
+    files
+        .iter()
+        .filter_map(|(path, hunks)| {
+            let renamed_from = hunks.iter().find_map(diff::Hunk::renamed_from)?;
+            let owner = owner_of(&renamed_from).or_else(|| owner_of(path))?;
+            Some((path.clone(), owner))
+        })
+        .collect()
+}
+
 fn virtual_hunks_to_virtual_files(
     project_repository: &project_repository::Repository,
     hunks: &[VirtualBranchHunk],
@@ -2022,14 +2313,17 @@ fn virtual_hunks_to_virtual_files(
         })
         .into_iter()
         .map(|(file_path, hunks)| VirtualBranchFile {
-            id: file_path.display().to_string(),
+            id: git::path::escape(&file_path),
             path: file_path.clone(),
+            renamed_from: hunks.iter().find_map(|hunk| diff::renamed_from(&hunk.diff)),
+            copied_from: hunks.iter().find_map(|hunk| diff::copied_from(&hunk.diff)),
             hunks: hunks.clone(),
             binary: hunks.iter().any(|h| h.binary),
+            generated: hunks.iter().any(|h| h.generated),
             modified_at: hunks.iter().map(|h| h.modified_at).max().unwrap_or(0),
             conflicted: conflicts::is_conflicting(
                 project_repository,
-                Some(&file_path.display().to_string()),
+                Some(&git::path::escape(&file_path)),
             )
             .unwrap_or(false),
         })
@@ -2142,91 +2436,244 @@ pub fn write_tree_onto_tree(
     let mut builder = git_repository.treebuilder(Some(base_tree));
     // now update the index with content in the working directory for each file
     for (filepath, hunks) in files {
-        // convert this string to a Path
         let rel_path = std::path::Path::new(&filepath);
-        let full_path = project_repository.path().join(rel_path);
+        if let Some(renamed_from) = hunks.iter().find_map(diff::Hunk::renamed_from) {
+            builder.remove(&renamed_from);
+        }
+        match tree_change(project_repository, base_tree, rel_path, hunks)? {
+            TreeChange::Upsert(oid, filemode) => builder.upsert(rel_path, oid, filemode),
+            TreeChange::Remove => builder.remove(rel_path),
+            TreeChange::None => {}
+        }
+    }
 
-        let is_submodule =
-            full_path.is_dir() && hunks.len() == 1 && hunks[0].diff.contains("Subproject commit");
+    // now write out the tree
+    let tree_oid = builder.write().context("failed to write updated tree")?;
 
-        // if file exists
-        if full_path.exists() {
-            // if file is executable, use 755, otherwise 644
-            let mut filemode = git::FileMode::Blob;
-            // check if full_path file is executable
-            if let Ok(metadata) = std::fs::symlink_metadata(&full_path) {
-                #[cfg(target_family = "unix")]
-                {
-                    if metadata.permissions().mode() & 0o111 != 0 {
-                        filemode = git::FileMode::BlobExecutable;
-                    }
+    Ok(tree_oid)
+}
+
+// the last tree the status wrote of every applied branch, by project, with what it was
+// written from, to write the next one from it
+type WrittenTrees = HashMap<ProjectId, HashMap<BranchId, WrittenTree>>;
+static WRITTEN_TREES: Lazy<Mutex<WrittenTrees>> = Lazy::new(Mutex::default);
+
+struct WrittenTree {
+    base: git::Oid,
+    tree: git::Oid,
+    paths: HashSet<path::PathBuf>,
+}
+
+fn written_trees() -> MutexGuard<'static, WrittenTrees> {
+    WRITTEN_TREES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Drops what is kept in memory about the project `project_id`, once it's deleted.
+pub(crate) fn forget_project(project_id: &ProjectId) {
+    written_trees().remove(project_id);
+    super::integration::forget_written_index(project_id);
+}
+
+// the paths a tree written from `files` differs from its base tree at, the old paths of
+// renamed files included
+pub fn written_paths(files: &HashMap<path::PathBuf, Vec<diff::Hunk>>) -> HashSet<path::PathBuf> {
+    files
+        .iter()
+        .flat_map(|(path, hunks)| {
+            std::iter::once(path.clone()).chain(hunks.iter().find_map(diff::Hunk::renamed_from))
+        })
+        .collect()
+}
+
+// same as write_tree_onto_tree, but starts from `previous_tree`, a tree that was
+// previously written on top of `base_tree` from the files at `previously_changed`. only
+// entries that actually changed since then are touched, unchanged subtrees are reused as
+// is, and if nothing changed no new objects are written at all.
+pub fn write_tree_onto_tree_incremental(
+    project_repository: &project_repository::Repository,
+    base_tree: &git::Tree,
+    previous_tree: &git::Tree,
+    previously_changed: &HashSet<path::PathBuf>,
+    files: &HashMap<path::PathBuf, Vec<diff::Hunk>>,
+) -> Result<git::Oid> {
+    let git_repository = &project_repository.git_repository;
+
+    let mut builder = git_repository.treebuilder(Some(previous_tree));
+    let mut changed = false;
+
+    // the old paths of renamed files stay removed
+    let renamed_from = files
+        .values()
+        .filter_map(|hunks| hunks.iter().find_map(diff::Hunk::renamed_from))
+        .collect::<HashSet<_>>();
+    for old_path in &renamed_from {
+        if previous_tree.get_path(old_path).is_ok() {
+            builder.remove(old_path);
+            changed = true;
+        }
+    }
+
+    for (filepath, hunks) in files {
+        let rel_path = std::path::Path::new(&filepath);
+        let previous_entry = previous_tree.get_path(rel_path).ok();
+        match tree_change(project_repository, base_tree, rel_path, hunks)? {
+            TreeChange::Upsert(oid, filemode) => {
+                let unchanged = previous_entry.is_some_and(|entry| {
+                    entry.id() == oid && entry.filemode() == i32::from(filemode)
+                });
+                if !unchanged {
+                    builder.upsert(rel_path, oid, filemode);
+                    changed = true;
                 }
-                #[cfg(target_os = "windows")]
-                {
-                    // TODO(qix-): Pull from `core.filemode` config option to determine
-                    // TODO(qix-): the behavior on windows. For now, we set this to true.
-                    // TODO(qix-): It's not ideal, but it gets us to a windows build faster.
-                    filemode = git::FileMode::BlobExecutable;
+            }
+            TreeChange::Remove => {
+                if previous_entry.is_some() {
+                    builder.remove(rel_path);
+                    changed = true;
                 }
+            }
+            TreeChange::None => {}
+        }
+    }
 
-                if metadata.file_type().is_symlink() {
-                    filemode = git::FileMode::Link;
-                }
+    // restore files that are no longer changed to their base state
+    for rel_path in previously_changed {
+        if files.contains_key(rel_path) || renamed_from.contains(rel_path) {
+            continue;
+        }
+        match base_tree.get_path(rel_path) {
+            Ok(entry) => {
+                let filemode = git::FileMode::try_from(entry.filemode())?;
+                builder.upsert(rel_path, entry.id(), filemode);
             }
+            Err(git::Error::NotFound(_)) => builder.remove(rel_path),
+            Err(error) => return Err(error.into()),
+        }
+        changed = true;
+    }
 
-            // get the blob
-            if filemode == git::FileMode::Link {
-                // it's a symlink, make the content the path of the link
-                let link_target = std::fs::read_link(&full_path)?;
+    if !changed {
+        return Ok(previous_tree.id());
+    }
 
-                // if the link target is inside the project repository, make it relative
-                let link_target = link_target
-                    .strip_prefix(project_repository.path())
-                    .unwrap_or(&link_target);
-
-                let blob_oid = git_repository.blob(
-                    link_target
-                        .to_str()
-                        .ok_or_else(|| Error::InvalidUnicodePath(link_target.into()))?
-                        .as_bytes(),
-                )?;
-                builder.upsert(rel_path, blob_oid, filemode);
-            } else if let Ok(tree_entry) = base_tree.get_path(rel_path) {
-                if hunks.len() == 1 && hunks[0].binary {
-                    let new_blob_oid = &hunks[0].diff;
-                    // convert string to Oid
-                    let new_blob_oid = new_blob_oid.parse().context("failed to diff as oid")?;
-                    builder.upsert(rel_path, new_blob_oid, filemode);
-                } else {
-                    // blob from tree_entry
-                    let blob = tree_entry
-                        .to_object(git_repository)
-                        .unwrap()
-                        .peel_to_blob()
-                        .context("failed to get blob")?;
-
-                    // get the contents
-                    let mut blob_contents = blob.content().to_vec();
-
-                    let mut hunks = hunks.clone();
-                    hunks.sort_by_key(|hunk| hunk.new_start);
-                    for hunk in hunks {
-                        let patch = format!("--- original\n+++ modified\n{}", hunk.diff);
-                        let patch_bytes = patch.as_bytes();
-                        let patch = Patch::from_bytes(patch_bytes)?;
-                        blob_contents = apply_bytes(&blob_contents, &patch)
-                            .context(format!("failed to apply {}", &hunk.diff))?;
-                    }
+    let tree_oid = builder.write().context("failed to write updated tree")?;
 
-                    // create a blob
-                    let new_blob_oid = git_repository.blob(&blob_contents)?;
-                    // upsert into the builder
-                    builder.upsert(rel_path, new_blob_oid, filemode);
+    Ok(tree_oid)
+}
+
+enum TreeChange {
+    Upsert(git::Oid, git::FileMode),
+    Remove,
+    None,
+}
+
+// figures out what the tree entry for `rel_path` should be, given the hunks
+// of the file relative to `base_tree`
+fn tree_change(
+    project_repository: &project_repository::Repository,
+    base_tree: &git::Tree,
+    rel_path: &path::Path,
+    hunks: &[diff::Hunk],
+) -> Result<TreeChange> {
+    let git_repository = &project_repository.git_repository;
+    let full_path = project_repository.path().join(rel_path);
+
+    // the mode of the file only changes with the hunk that changes it, and a renamed or copied
+    // file is patched from the file it came from. the other hunks are patches.
+    let mode_change = hunks.iter().find_map(diff::Hunk::mode_change);
+    let source_path = hunks
+        .iter()
+        .find_map(|hunk| hunk.renamed_from().or_else(|| hunk.copied_from()));
+    let base_path = source_path.as_deref().unwrap_or(rel_path);
+    let hunks = hunks
+        .iter()
+        .filter(|hunk| !hunk.is_header())
+        .cloned()
+        .collect::<Vec<_>>();
+    let hunks = hunks.as_slice();
+
+    // a submodule is recorded as the commit it points to, whatever is checked out in it
+    if let [hunk] = hunks {
+        if hunk.is_submodule() && !full_path.is_file() {
+            return Ok(match hunk.submodule_commit() {
+                Some(commit_oid) => TreeChange::Upsert(commit_oid, git::FileMode::Commit),
+                None if base_tree.get_path(rel_path).is_ok() => TreeChange::Remove,
+                None => TreeChange::None,
+            });
+        }
+    }
+
+    // if file exists, dangling symlinks included. on case insensitive filesystems, the old
+    // side of a case only rename still "exists", as it resolves to its new name.
+    let exists = full_path.symlink_metadata().is_ok()
+        && (!git_repository.ignore_case() || crate::fs::exists_with_exact_case(&full_path));
+    if exists {
+        let base_filemode = base_tree
+            .get_path(base_path)
+            .ok()
+            .and_then(|entry| git::FileMode::try_from(entry.filemode()).ok());
+        let mut filemode = git::FileMode::Blob;
+        if let Ok(metadata) = std::fs::symlink_metadata(&full_path) {
+            if metadata.file_type().is_symlink() {
+                filemode = git::FileMode::Link;
+            } else if let Some((_, new_filemode)) = mode_change {
+                filemode = new_filemode;
+            } else if let Some(
+                base_filemode @ (git::FileMode::Blob | git::FileMode::BlobExecutable),
+            ) = base_filemode
+            {
+                filemode = base_filemode;
+            } else if base_filemode == Some(git::FileMode::Link)
+                && !git_repository.supports_symlinks()
+            {
+                // checked out as a file holding the target, it's still a link
+                filemode = git::FileMode::Link;
+            } else {
+                // a new file is executable if any of its execute bits is set. without them,
+                // like on windows, it isn't.
+                #[cfg(target_family = "unix")]
+                if metadata.permissions().mode() & 0o111 != 0 {
+                    filemode = git::FileMode::BlobExecutable;
                 }
-            } else if is_submodule {
-                let mut blob_contents = vec![];
+            }
+        }
+
+        // get the blob
+        if filemode == git::FileMode::Link {
+            // the content of a link is the path it points to
+            let blob_oid = if full_path.is_symlink() {
+                let link_target = crate::fs::relative_link_target(
+                    project_repository.path(),
+                    &full_path,
+                    std::fs::read_link(&full_path)?,
+                );
+                let link_target = link_target
+                    .to_str()
+                    .ok_or_else(|| Error::InvalidUnicodePath(link_target.clone()))?;
+                git_repository.blob(crate::fs::link_target_to_git(link_target).as_bytes())?
+            } else {
+                git_repository.blob_path(&full_path)?
+            };
+            Ok(TreeChange::Upsert(blob_oid, filemode))
+        } else if let Ok(tree_entry) = base_tree.get_path(base_path) {
+            if hunks.len() == 1 && hunks[0].binary {
+                let new_blob_oid = &hunks[0].diff;
+                // convert string to Oid
+                let new_blob_oid = new_blob_oid.parse().context("failed to diff as oid")?;
+                Ok(TreeChange::Upsert(new_blob_oid, filemode))
+            } else {
+                // blob from tree_entry
+                git_repository.fetch_missing_objects(&[tree_entry.id()])?;
+                let blob = tree_entry
+                    .to_object(git_repository)
+                    .unwrap()
+                    .peel_to_blob()
+                    .context("failed to get blob")?;
 
-                let mut hunks = hunks.clone();
+                // get the contents
+                let mut blob_contents = blob.content().to_vec();
+
+                let mut hunks = hunks.to_vec();
                 hunks.sort_by_key(|hunk| hunk.new_start);
                 for hunk in hunks {
                     let patch = format!("--- original\n+++ modified\n{}", hunk.diff);
@@ -2238,28 +2685,39 @@ pub fn write_tree_onto_tree(
 
                 // create a blob
                 let new_blob_oid = git_repository.blob(&blob_contents)?;
-                // upsert into the builder
-                builder.upsert(rel_path, new_blob_oid, filemode);
-            } else {
-                // create a git blob from a file on disk
-                let blob_oid = git_repository
-                    .blob_path(&full_path)
-                    .context(format!("failed to create blob from path {:?}", &full_path))?;
-                builder.upsert(rel_path, blob_oid, filemode);
+                // upsert it into the tree
+                Ok(TreeChange::Upsert(new_blob_oid, filemode))
             }
-        } else if base_tree.get_path(rel_path).is_ok() {
-            // remove file from index if it exists in the base tree
-            builder.remove(rel_path);
+        } else if hunks.len() == 1 && hunks[0].binary {
+            // a new binary file, or the pointer of a new lfs file, is already in the odb
+            let new_blob_oid = hunks[0].diff.parse().context("failed to diff as oid")?;
+            Ok(TreeChange::Upsert(new_blob_oid, filemode))
         } else {
-            // file not in index or base tree, do nothing
-            // this is the
+            // create a git blob from a file on disk, as it is committed. a file new to the
+            // base can still be committed on a branch, its line endings are converted like
+            // the diff against the head did.
+            let committed = git_repository
+                .head()
+                .and_then(|head| head.peel_to_tree())
+                .and_then(|head_tree| head_tree.get_path(rel_path))
+                .ok()
+                .map(|entry| git_repository.find_blob(entry.id()))
+                .transpose()?;
+            let blob_oid = git::filters::blob(
+                git_repository,
+                rel_path,
+                committed.as_ref().map(git::Blob::content),
+            )
+            .context(format!("failed to create blob from path {:?}", &full_path))?;
+            Ok(TreeChange::Upsert(blob_oid, filemode))
         }
+    } else if base_tree.get_path(rel_path).is_ok() {
+        // remove file from index if it exists in the base tree
+        Ok(TreeChange::Remove)
+    } else {
+        // file not in index or base tree, do nothing
+        Ok(TreeChange::None)
     }
-
-    // now write out the tree
-    let tree_oid = builder.write().context("failed to write updated tree")?;
-
-    Ok(tree_oid)
 }
 
 fn _print_tree(repo: &git2::Repository, tree: &git2::Tree) -> Result<()> {
@@ -2350,8 +2808,8 @@ pub fn commit(
         ));
     }
 
-    let tree_oid = if let Some(ownership) = ownership {
-        let files = files
+    let files = if let Some(ownership) = ownership {
+        files
             .iter()
             .filter_map(|(filepath, hunks)| {
                 let hunks = hunks
@@ -2376,12 +2834,38 @@ pub fn commit(
                     Some((filepath.clone(), hunks))
                 }
             })
-            .collect::<HashMap<_, _>>();
-        write_tree_onto_commit(project_repository, branch.head, &files)?
+            .collect::<HashMap<_, _>>()
     } else {
-        write_tree_onto_commit(project_repository, branch.head, &files)?
+        files
     };
 
+    if let Some(scanner) = Scanner::open(project_repository)? {
+        let findings = scanner.scan(&files);
+        if !findings.is_empty() {
+            return Err(errors::CommitError::SecretsFound(findings));
+        }
+    }
+
+    let conflict_markers = find_conflict_markers(project_repository, &files)?;
+    if !conflict_markers.is_empty() {
+        return Err(errors::CommitError::ConflictMarkers(conflict_markers));
+    }
+
+    let large_files = LargeFilesCheck::open(project_repository)?;
+    let found = large_files.check(project_repository, &files)?;
+    if !found.is_empty() {
+        if large_files.policy == LargeFilesPolicy::Block {
+            return Err(errors::CommitError::LargeFiles(found));
+        }
+        tracing::warn!(
+            project_id = %project_repository.project().id,
+            files = ?found,
+            "committing large files"
+        );
+    }
+
+    let tree_oid = write_tree_onto_commit(project_repository, branch.head, &files)?;
+
     let git_repository = &project_repository.git_repository;
     let parent_commit = git_repository
         .find_commit(branch.head)
@@ -2431,12 +2915,53 @@ pub fn commit(
     Ok(commit_oid)
 }
 
+/// The trailer of the commits made with [`commit_no_verify`].
+pub const NO_VERIFY_TRAILER: &str = "Verification-Skipped: true";
+
+/// Same as `commit`, but the hooks are skipped on purpose, like `git commit --no-verify`, and
+/// the message says so with a [`NO_VERIFY_TRAILER`] for reviewers.
+pub fn commit_no_verify(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    message: &str,
+    ownership: Option<&branch::Ownership>,
+    signing_key: Option<&keys::PrivateKey>,
+    user: Option<&users::User>,
+) -> Result<git::Oid, errors::CommitError> {
+    commit(
+        gb_repository,
+        project_repository,
+        branch_id,
+        &with_trailer(message, NO_VERIFY_TRAILER),
+        ownership,
+        signing_key,
+        user,
+        false,
+    )
+}
+
+// the trailer goes with the ones the message ends with, if it has any, like `git interpret-trailers`.
+// libgit2 finds those the way git does.
+fn with_trailer(message: &str, trailer: &str) -> String {
+    let message = message.trim_end();
+    let ends_with_trailers =
+        git2::message_trailers_strs(message).is_ok_and(|trailers| trailers.iter().next().is_some());
+    if ends_with_trailers {
+        format!("{message}\n{trailer}")
+    } else {
+        format!("{message}\n\n{trailer}")
+    }
+}
+
+#[instrument(skip(project_repository, gb_repository, credentials), fields(project_id = %project_repository.project().id), level = "debug")]
 pub fn push(
     project_repository: &project_repository::Repository,
     gb_repository: &gb_repository::Repository,
     branch_id: &BranchId,
     with_force: bool,
     credentials: &git::credentials::Helper,
+    progress: &PrePushProgress,
 ) -> Result<(), errors::PushError> {
     let current_session = gb_repository
         .get_or_create_current_session()
@@ -2446,21 +2971,98 @@ pub fn push(
         .context("failed to open current session")
         .map_err(errors::PushError::Other)?;
 
-    let branch_reader = branch::Reader::new(&current_session_reader);
     let branch_writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
 
-    let mut vbranch = branch_reader.read(branch_id).map_err(|error| match error {
-        reader::Error::NotFound => errors::PushError::BranchNotFound(errors::BranchNotFoundError {
-            project_id: project_repository.project().id,
-            branch_id: *branch_id,
-        }),
-        error => errors::PushError::Other(error.into()),
-    })?;
+    let mut vbranch = read_push_branch(project_repository, &current_session_reader, branch_id)?;
+    let remote_branch = push_remote_branch(project_repository, &current_session_reader, &vbranch)?;
 
+    if let Some(scanner) = Scanner::open(project_repository)? {
+        let findings = scanner.scan_commits(
+            &project_repository.git_repository,
+            push_base(project_repository, &current_session_reader, &vbranch)?,
+            vbranch.head,
+        )?;
+        if !findings.is_empty() {
+            return Err(errors::PushError::SecretsFound(findings));
+        }
+    }
+
+    let report = pre_push::run(
+        project_repository,
+        &vbranch,
+        &remote_branch,
+        with_force,
+        progress,
+    )?;
+    if !report.passed() {
+        return Err(errors::PushError::ChecksFailed(report));
+    }
+
+    project_repository.push(&vbranch.head, &remote_branch, with_force, credentials)?;
+
+    vbranch.upstream = Some(remote_branch.clone());
+    vbranch.upstream_head = Some(vbranch.head);
+    branch_writer
+        .write(&mut vbranch)
+        .context("failed to write target branch after push")?;
+
+    project_repository.fetch(remote_branch.remote(), credentials)?;
+
+    Ok(())
+}
+
+/// Runs the checks pushing the branch runs, without pushing it.
+pub fn check_push(
+    project_repository: &project_repository::Repository,
+    gb_repository: &gb_repository::Repository,
+    branch_id: &BranchId,
+    with_force: bool,
+    progress: &PrePushProgress,
+) -> Result<PrePushReport, errors::PushError> {
+    let current_session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let current_session_reader = sessions::Reader::open(gb_repository, &current_session)
+        .context("failed to open current session")?;
+    let vbranch = read_push_branch(project_repository, &current_session_reader, branch_id)?;
+    let remote_branch = push_remote_branch(project_repository, &current_session_reader, &vbranch)?;
+    pre_push::run(
+        project_repository,
+        &vbranch,
+        &remote_branch,
+        with_force,
+        progress,
+    )
+}
+
+fn read_push_branch(
+    project_repository: &project_repository::Repository,
+    session_reader: &sessions::Reader,
+    branch_id: &BranchId,
+) -> Result<branch::Branch, errors::PushError> {
+    branch::Reader::new(session_reader)
+        .read(branch_id)
+        .map_err(|error| match error {
+            reader::Error::NotFound => {
+                errors::PushError::BranchNotFound(errors::BranchNotFoundError {
+                    project_id: project_repository.project().id,
+                    branch_id: *branch_id,
+                })
+            }
+            error => errors::PushError::Other(error.into()),
+        })
+}
+
+// the upstream of the branch, or a new branch of the push remote named after it
+fn push_remote_branch(
+    project_repository: &project_repository::Repository,
+    session_reader: &sessions::Reader,
+    vbranch: &branch::Branch,
+) -> Result<git::RemoteRefname, errors::PushError> {
     let remote_branch = if let Some(upstream_branch) = vbranch.upstream.as_ref() {
         upstream_branch.clone()
     } else {
-        let default_target = get_default_target(&current_session_reader)
+        let default_target = get_default_target(session_reader)
             .context("failed to get default target")?
             .ok_or_else(|| {
                 errors::PushError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
@@ -2468,9 +3070,15 @@ pub fn push(
                 })
             })?;
 
+        let push_remote = project_repository
+            .config()
+            .push_remote()
+            .context("failed to get push remote")?;
         let remote_branch = format!(
             "refs/remotes/{}/{}",
-            default_target.branch.remote(),
+            push_remote
+                .as_deref()
+                .unwrap_or_else(|| default_target.branch.remote()),
             normalize_branch_name(&vbranch.name)
         )
         .parse::<git::RemoteRefname>()
@@ -2492,21 +3100,116 @@ pub fn push(
             "-",
         ))
     };
+    Ok(remote_branch)
+}
 
-    project_repository.push(&vbranch.head, &remote_branch, with_force, credentials)?;
+// the commits a push sends are the ones since the last push, or the ones since the branch
+// left the target
+fn push_base(
+    project_repository: &project_repository::Repository,
+    session_reader: &sessions::Reader,
+    branch: &branch::Branch,
+) -> Result<Option<git::Oid>> {
+    let repo = &project_repository.git_repository;
+    if let Some(upstream_head) = branch
+        .upstream_head
+        .filter(|upstream_head| repo.find_commit(*upstream_head).is_ok())
+    {
+        return Ok(Some(upstream_head));
+    }
+    let Some(default_target) =
+        get_default_target(session_reader).context("failed to get default target")?
+    else {
+        return Ok(None);
+    };
+    Ok(repo.merge_base(default_target.sha, branch.head).ok())
+}
 
-    vbranch.upstream = Some(remote_branch.clone());
-    vbranch.upstream_head = Some(vbranch.head);
-    branch_writer
-        .write(&mut vbranch)
-        .context("failed to write target branch after push")?;
+/// The secrets that committing the changes of the branch, and pushing it, would be refused
+/// for. Empty if the project doesn't scan for secrets.
+pub fn scan_secrets(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<Vec<SecretFinding>, errors::ScanSecretsError> {
+    let Some(scanner) = Scanner::open(project_repository)? else {
+        return Ok(vec![]);
+    };
+    let current_session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let session_reader = sessions::Reader::open(gb_repository, &current_session)
+        .context("failed to open current session")?;
+    let default_target = get_default_target(&session_reader)
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::ScanSecretsError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
 
-    project_repository.fetch(remote_branch.remote(), credentials)?;
+    let branch = branch::Reader::new(&session_reader)
+        .read(branch_id)
+        .map_err(|error| match error {
+            reader::Error::NotFound => {
+                errors::ScanSecretsError::BranchNotFound(errors::BranchNotFoundError {
+                    project_id: project_repository.project().id,
+                    branch_id: *branch_id,
+                })
+            }
+            error => errors::ScanSecretsError::Other(error.into()),
+        })?;
+    let mut findings = scanner.scan_commits(
+        &project_repository.git_repository,
+        push_base(project_repository, &session_reader, &branch)?,
+        branch.head,
+    )?;
+    if branch.applied {
+        let statuses = get_status_by_branch(gb_repository, project_repository)
+            .context("failed to get status by branch")?;
+        if let Some((branch, files)) = statuses.iter().find(|(branch, _)| branch.id == *branch_id) {
+            let files =
+                calculate_non_commited_diffs(project_repository, branch, &default_target, files)?;
+            findings.extend(scanner.scan(&files));
+        }
+    }
+    Ok(findings)
+}
 
-    Ok(())
+/// The large files of the uncommitted changes of the branch, that committing would warn
+/// about or be refused for.
+pub fn list_large_files(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<Vec<LargeFile>, errors::ListLargeFilesError> {
+    let default_target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::ListLargeFilesError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+    let statuses = get_status_by_branch(gb_repository, project_repository)
+        .context("failed to get status by branch")?;
+    let (branch, files) = statuses
+        .iter()
+        .find(|(branch, _)| branch.id == *branch_id)
+        .ok_or_else(|| {
+            errors::ListLargeFilesError::BranchNotFound(errors::BranchNotFoundError {
+                project_id: project_repository.project().id,
+                branch_id: *branch_id,
+            })
+        })?;
+    let files = calculate_non_commited_diffs(project_repository, branch, &default_target, files)?;
+    LargeFilesCheck::open(project_repository)?
+        .check(project_repository, &files)
+        .map_err(Into::into)
 }
 
-pub fn mark_all_unapplied(gb_repository: &gb_repository::Repository) -> Result<()> {
+/// Unapplies every branch, and returns the ones that were applied.
+pub fn mark_all_unapplied(gb_repository: &gb_repository::Repository) -> Result<Vec<BranchId>> {
     let current_session = gb_repository.get_or_create_current_session()?;
     let session_reader = sessions::Reader::open(gb_repository, &current_session)?;
     let branch_iterator = super::Iterator::new(&session_reader)?;
@@ -2519,11 +3222,10 @@ pub fn mark_all_unapplied(gb_repository: &gb_repository::Repository) -> Result<(
         .filter(|branch| branch.applied)
         .map(|mut branch| {
             branch.applied = false;
-            branch_writer.write(&mut branch)
+            branch_writer.write(&mut branch).map(|()| branch.id)
         })
         .collect::<Result<Vec<_>, _>>()
-        .context("failed to write branches")?;
-    Ok(())
+        .context("failed to write branches")
 }
 
 fn is_commit_integrated(
@@ -2535,22 +3237,21 @@ fn is_commit_integrated(
         .git_repository
         .find_branch(&target.branch.clone().into())?;
     let remote_head = remote_branch.peel_to_commit()?;
-    let upstream_commits = project_repository.l(
-        remote_head.id(),
-        project_repository::LogUntil::Commit(target.sha),
-    )?;
 
     if target.sha.eq(&commit.id()) {
         // could not be integrated if heads are the same.
         return Ok(false);
     }
 
-    if upstream_commits.is_empty() {
+    if project_repository.distance(remote_head.id(), target.sha)? == 0 {
         // could not be integrated - there is nothing new upstream.
         return Ok(false);
     }
 
-    if upstream_commits.contains(&commit.id()) {
+    if project_repository.is_reachable_from(remote_head.id(), commit.id())?
+        && !project_repository.is_reachable_from(target.sha, commit.id())?
+    {
+        // commit is one of the new upstream commits
         return Ok(true);
     }
 
@@ -2641,11 +3342,7 @@ pub fn is_remote_branch_mergeable(
         .find_commit(branch_oid)
         .context("failed to find branch commit")?;
 
-    let base_tree = find_base_tree(
-        &project_repository.git_repository,
-        &branch_commit,
-        &target_commit,
-    )?;
+    let base_tree = project_repository.merge_base_tree(target_commit.id(), branch_commit.id())?;
 
     let wd_tree = project_repository.get_wd_tree()?;
 
@@ -2698,30 +3395,13 @@ pub fn is_virtual_branch_mergeable(
         })?;
 
     // determine if this branch is up to date with the target/base
-    let merge_base = project_repository
-        .git_repository
-        .merge_base(default_target.sha, branch.head)
-        .context("failed to find merge base")?;
+    let merge_base = project_repository.merge_base(default_target.sha, branch.head)?;
 
     if merge_base != default_target.sha {
         return Ok(false);
     }
 
-    let branch_commit = project_repository
-        .git_repository
-        .find_commit(branch.head)
-        .context("failed to find branch commit")?;
-
-    let target_commit = project_repository
-        .git_repository
-        .find_commit(default_target.sha)
-        .context("failed to find target commit")?;
-
-    let base_tree = find_base_tree(
-        &project_repository.git_repository,
-        &branch_commit,
-        &target_commit,
-    )?;
+    let base_tree = project_repository.merge_base_tree(default_target.sha, branch.head)?;
 
     let wd_tree = project_repository.get_wd_tree()?;
 
@@ -3037,10 +3717,9 @@ pub fn cherry_pick(
         let mut merge_conflicts = Vec::new();
         for path in conflicts.flatten() {
             if let Some(ours) = path.our {
-                let path = std::str::from_utf8(&ours.path)
-                    .context("failed to convert path")?
-                    .to_string();
-                merge_conflicts.push(path);
+                merge_conflicts.push(git::path::escape(&git::path::from_bytes(
+                    ours.path.as_bstr(),
+                )));
             }
         }
         conflicts::mark(project_repository, &merge_conflicts, Some(branch.head))?;
@@ -3077,7 +3756,6 @@ pub fn cherry_pick(
             .git_repository
             .checkout_tree(&merge_tree)
             .force()
-            .remove_untracked()
             .checkout()
             .context("failed to checkout final tree")?;
 
@@ -3573,7 +4251,7 @@ pub fn create_virtual_branch_from_branch(
         branch::Ownership::default(),
         |mut ownership, hunk| {
             ownership.put(
-                &format!("{}:{}", hunk.file_path.display(), hunk.id)
+                &format!("{}:{}", git::path::escape(&hunk.file_path), hunk.id)
                     .parse()
                     .unwrap(),
             );
@@ -3627,6 +4305,8 @@ pub fn create_virtual_branch_from_branch(
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+
     use super::*;
     #[test]
     fn joined_test() {
@@ -3640,4 +4320,35 @@ mod tests {
         assert!(joined(10, 13, 13, 16));
         assert!(!joined(10, 13, 14, 17));
     }
+
+    #[test]
+    fn with_trailer_test() {
+        for message in [
+            "subject",
+            "subject\n\nbody",
+            "subject\n\nbody\n\nSigned-off-by: A <a@example.com>",
+            "subject\n\nCo-authored-by: A\n  <a@example.com>",
+            "subject\n\nSigned-off-by: A <a@example.com>\nsome prose\nthat goes on",
+            "subject\n\nNote: this is prose\nthat goes on",
+            "Fixes: the title is not a trailer",
+        ] {
+            let mut git = std::process::Command::new("git")
+                .args(["interpret-trailers", "--trailer", NO_VERIFY_TRAILER])
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+            git.stdin
+                .take()
+                .unwrap()
+                .write_all(format!("{message}\n").as_bytes())
+                .unwrap();
+            let output = git.wait_with_output().unwrap();
+            assert_eq!(
+                with_trailer(message, NO_VERIFY_TRAILER),
+                String::from_utf8(output.stdout).unwrap().trim_end(),
+                "{message}"
+            );
+        }
+    }
 }
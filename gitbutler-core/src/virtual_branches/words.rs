@@ -0,0 +1,144 @@
+use std::ops::Range;
+
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+// diffing longer lines costs more than highlighting them is worth, they are left whole
+const MAX_LINE_LENGTH: usize = 1000;
+// below this, a removed line and the added line it is paired with are different lines
+// rather than one changed line, and highlighting what changed would be noise
+const MIN_SIMILARITY: f32 = 0.5;
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineChanges {
+    /// The index of the line in the diff of the hunk, its header being the first line.
+    pub line: usize,
+    /// What changed in the line, in characters from after its `-` or `+`.
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// The words that changed within the lines of `diff`, the text of a hunk.
+///
+/// The removed lines of a change are paired with the lines added right after them, in order,
+/// and only lines that are similar to the line they are paired with have changes.
+pub fn line_changes(diff: &str) -> Vec<LineChanges> {
+    let lines = diff.lines().collect::<Vec<_>>();
+    let mut changes = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let removed_start = i;
+        while i < lines.len() && lines[i].starts_with('-') {
+            i += 1;
+        }
+        let added_start = i;
+        while i < lines.len() && lines[i].starts_with('+') {
+            i += 1;
+        }
+        if i == removed_start {
+            i += 1;
+            continue;
+        }
+        for (removed, added) in (removed_start..added_start).zip(added_start..i) {
+            let (Some(old), Some(new)) = (
+                lines[removed].strip_prefix('-'),
+                lines[added].strip_prefix('+'),
+            ) else {
+                continue;
+            };
+            let Some((removed_ranges, added_ranges)) = word_changes(old, new) else {
+                continue;
+            };
+            if !removed_ranges.is_empty() {
+                changes.push(LineChanges {
+                    line: removed,
+                    ranges: removed_ranges,
+                });
+            }
+            if !added_ranges.is_empty() {
+                changes.push(LineChanges {
+                    line: added,
+                    ranges: added_ranges,
+                });
+            }
+        }
+    }
+    changes.sort_by_key(|changes| changes.line);
+    changes
+}
+
+fn word_changes(old: &str, new: &str) -> Option<(Vec<Range<usize>>, Vec<Range<usize>>)> {
+    if old.len() > MAX_LINE_LENGTH || new.len() > MAX_LINE_LENGTH {
+        return None;
+    }
+    let diff = TextDiff::configure().diff_unicode_words(old, new);
+    if diff.ratio() < MIN_SIMILARITY {
+        return None;
+    }
+
+    let mut old_ranges = vec![];
+    let mut new_ranges = vec![];
+    let mut old_offset = 0;
+    let mut new_offset = 0;
+    for change in diff.iter_all_changes() {
+        let len = change.value().chars().count();
+        match change.tag() {
+            ChangeTag::Equal => {
+                old_offset += len;
+                new_offset += len;
+            }
+            ChangeTag::Delete => {
+                push_range(&mut old_ranges, old_offset..old_offset + len);
+                old_offset += len;
+            }
+            ChangeTag::Insert => {
+                push_range(&mut new_ranges, new_offset..new_offset + len);
+                new_offset += len;
+            }
+        }
+    }
+    Some((old_ranges, new_ranges))
+}
+
+// words that changed next to each other are one range
+fn push_range(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_word() {
+        let diff = "@@ -1,2 +1,2 @@\n-let a = 1;\n-let b = 2;\n+let a = 10;\n+let b = 2;\n";
+        assert_eq!(
+            line_changes(diff),
+            vec![
+                LineChanges {
+                    line: 1,
+                    ranges: vec![8..9],
+                },
+                LineChanges {
+                    line: 3,
+                    ranges: vec![8..10],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn different_lines() {
+        let diff = "@@ -1 +1 @@\n-fn main() {}\n+// nothing in common\n";
+        assert!(line_changes(diff).is_empty());
+    }
+
+    #[test]
+    fn added_lines() {
+        let diff = "@@ -1,0 +2,2 @@\n+one\n+two\n";
+        assert!(line_changes(diff).is_empty());
+    }
+}
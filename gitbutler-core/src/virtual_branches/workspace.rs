@@ -0,0 +1,217 @@
+//! Getting back to the workspace after something else than its branch, `gitbutler/integration`
+//! unless it's configured otherwise, was checked out with `git`: a branch, a tag or a commit,
+//! without losing what was done there.
+//!
+//! What's checked out is put aside the way `git stash` would, the commit and the changes of
+//! the working directory on top of it. The workspace is then checked out again with the
+//! branches that were applied in it, and what was put aside is brought back as a branch of
+//! its own.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    gb_repository,
+    git::{self, diff},
+    keys, project_repository, users,
+};
+
+use super::{branch, errors, integration, BranchId};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceRecovery {
+    /// What was checked out instead of the workspace, `None` if the workspace was.
+    pub previous_head: Option<String>,
+    /// The branch made of what was checked out, if it had commits or changes of its own.
+    pub recovered_branch: Option<BranchId>,
+    /// Whether the recovered branch could be applied, it's left unapplied if it conflicts
+    /// with the branches of the workspace.
+    pub recovered_branch_applied: bool,
+    /// The branches of the workspace that were applied again.
+    pub reapplied: Vec<BranchId>,
+    /// The branches of the workspace that were left unapplied, conflicting with the others.
+    pub not_reapplied: Vec<BranchId>,
+}
+
+/// Checks the workspace out again after something else was, see the [module docs](self).
+/// Nothing is done if the workspace is checked out.
+pub fn recover_workspace(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    signing_key: Option<&keys::PrivateKey>,
+    user: Option<&users::User>,
+) -> Result<WorkspaceRecovery, errors::RecoverWorkspaceError> {
+    let repo = &project_repository.git_repository;
+    let head = repo.head().context("failed to get head")?;
+    let head_name = head.name();
+    let workspace = integration::workspace_reference(project_repository)?;
+    if head_name
+        .as_ref()
+        .is_some_and(|name| name.to_string() == workspace.to_string())
+    {
+        return Ok(WorkspaceRecovery::default());
+    }
+
+    let target = gb_repository
+        .default_target()
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::RecoverWorkspaceError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let head_commit = head
+        .peel_to_commit()
+        .context("failed to peel head to commit")?;
+    let (previous_head, branch_name, local_branch) = match &head_name {
+        Some(git::Refname::Local(local)) => (
+            local.to_string(),
+            local.branch().to_string(),
+            Some(local.clone()),
+        ),
+        Some(name) => (name.to_string(), name.to_string(), None),
+        None => (
+            head_commit.id().to_string(),
+            detached_branch_name(repo, head_commit.id())?,
+            None,
+        ),
+    };
+
+    // put aside the changes of the working directory, on top of what's checked out
+    let wd_diff = diff::workdir_with_progress(
+        repo,
+        &head_commit.id(),
+        &diff::Options {
+            unmanaged: project_repository.project().unmanaged(),
+            ..Default::default()
+        },
+        &|_, _| {},
+    )
+    .context("failed to diff working directory")?;
+    let has_commits = head_commit.id() != target.sha
+        && !repo
+            .is_descendant_of(target.sha, head_commit.id())
+            .context("failed to check if head is integrated")?;
+
+    let writer = branch::Writer::new(gb_repository).context("failed to create branch writer")?;
+    let recovered_branch = if has_commits || !wd_diff.is_empty() {
+        let mut branch = super::branch_from_head(
+            project_repository,
+            &target.branch,
+            branch_name,
+            local_branch.as_ref(),
+            head_commit.id(),
+            &wd_diff,
+        )?;
+        branch.applied = false;
+        writer
+            .write(&mut branch)
+            .context("failed to write branch")?;
+        Some(branch.id)
+    } else {
+        None
+    };
+
+    // the workspace again, with nothing applied, the recovered branch is kept by its ref
+    let unapplied = super::reconcile::take_unapplied_by_checkout(gb_repository)?;
+    super::mark_all_unapplied(gb_repository)?;
+    let target_commit = repo
+        .find_commit(target.sha)
+        .context("failed to find target commit")?;
+    let target_tree = target_commit.tree().context("failed to get target tree")?;
+    // only the paths that were put aside or that the target has otherwise, the files of
+    // unmanaged paths and those untracked are left as they are
+    let unmanaged = project_repository.project().unmanaged();
+    let head_tree = head_commit.tree().context("failed to get head tree")?;
+    let mut paths = diff::trees(repo, &head_tree, &target_tree)
+        .context("failed to diff head and target")?
+        .into_iter()
+        .flat_map(|(path, hunks)| {
+            let renamed_from = hunks.iter().find_map(diff::Hunk::renamed_from);
+            std::iter::once(path).chain(renamed_from)
+        })
+        .chain(wd_diff.keys().cloned())
+        .filter(|path| !unmanaged.contains(path))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+    if !paths.is_empty() {
+        repo.checkout_tree(&target_tree)
+            .force()
+            .paths(paths.iter().map(std::path::PathBuf::as_path))
+            .checkout()
+            .context("failed to checkout target")?;
+    }
+    integration::update_gitbutler_integration(gb_repository, project_repository)?;
+
+    let mut recovery = WorkspaceRecovery {
+        previous_head: Some(previous_head),
+        recovered_branch,
+        ..Default::default()
+    };
+    for branch_id in unapplied {
+        if apply(
+            gb_repository,
+            project_repository,
+            &branch_id,
+            signing_key,
+            user,
+        )? {
+            recovery.reapplied.push(branch_id);
+        } else {
+            recovery.not_reapplied.push(branch_id);
+        }
+    }
+    if let Some(branch_id) = &recovery.recovered_branch {
+        recovery.recovered_branch_applied = apply(
+            gb_repository,
+            project_repository,
+            branch_id,
+            signing_key,
+            user,
+        )?;
+    }
+    Ok(recovery)
+}
+
+// applies the branch `branch_id`, false if it conflicts with the applied ones or is gone
+fn apply(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    signing_key: Option<&keys::PrivateKey>,
+    user: Option<&users::User>,
+) -> Result<bool> {
+    match super::apply_branch(
+        gb_repository,
+        project_repository,
+        branch_id,
+        signing_key,
+        user,
+    ) {
+        Ok(()) => Ok(true),
+        Err(
+            errors::ApplyBranchError::BranchConflicts(_)
+            | errors::ApplyBranchError::BranchNotFound(_),
+        ) => Ok(false),
+        Err(error) => Err(anyhow::Error::new(error).context("failed to apply branch")),
+    }
+}
+
+// a tag pointing at the detached `head` names the branch, its commit does otherwise
+fn detached_branch_name(repo: &git::Repository, head: git::Oid) -> Result<String> {
+    for tag in repo.tag_names(None).context("failed to list tags")? {
+        if let Ok((commit, _)) = repo.find_tag(&tag) {
+            if commit == head {
+                return Ok(tag);
+            }
+        }
+    }
+    let head = head.to_string();
+    Ok(format!(
+        "detached at {}",
+        head.get(..7).unwrap_or(head.as_str())
+    ))
+}
@@ -2,88 +2,158 @@ mod dispatchers;
 mod events;
 mod handlers;
 
-use std::{collections::HashMap, path, sync::Arc, time};
+use std::{
+    collections::HashMap,
+    path,
+    sync::{Arc, Mutex},
+    time,
+};
 
 pub use events::Event;
 
 use anyhow::{Context, Result};
-use tauri::AppHandle;
+use async_trait::async_trait;
 use tokio::{
-    sync::{
-        mpsc::{unbounded_channel, UnboundedSender},
-        Mutex,
-    },
+    sync::mpsc::{unbounded_channel, UnboundedSender},
     task,
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::projects::{self, ProjectId};
+use crate::{
+    analytics, assets, database, events as app_events,
+    projects::{self, ProjectId},
+    settings, users, virtual_branches,
+};
+
+/// Everything the handlers of the watchers run with, the same controllers the app or the
+/// CLI use for everything else.
+#[derive(Clone)]
+pub struct Services {
+    pub local_data_dir: path::PathBuf,
+    pub database: database::Database,
+    pub projects: projects::Controller,
+    pub users: users::Controller,
+    pub settings: settings::Controller,
+    pub vbranches: virtual_branches::Controller,
+    pub assets_proxy: assets::Proxy,
+    pub analytics: analytics::Client,
+    pub events: app_events::Bus,
+}
 
+/// The watchers of every active project. Any number of projects can be active at once,
+/// each with a watcher of its own, which is never waited on while the registry is locked.
 #[derive(Clone)]
 pub struct Watchers {
-    app_handle: AppHandle,
+    // resolved when a project is watched, the app only has its services once it's set up
+    services: Arc<dyn Fn() -> Result<Services> + Send + Sync>,
     watchers: Arc<Mutex<HashMap<ProjectId, Watcher>>>,
 }
 
-impl TryFrom<&AppHandle> for Watchers {
-    type Error = anyhow::Error;
+impl From<Services> for Watchers {
+    fn from(value: Services) -> Self {
+        Self::new(move || Ok(value.clone()))
+    }
+}
 
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        Ok(Self {
-            app_handle: value.clone(),
+impl Watchers {
+    pub fn new(services: impl Fn() -> Result<Services> + Send + Sync + 'static) -> Self {
+        Self {
+            services: Arc::new(services),
             watchers: Arc::new(Mutex::new(HashMap::new())),
-        })
+        }
+    }
+
+    pub async fn post(&self, event: Event) -> Result<()> {
+        let watcher = self
+            .watchers
+            .lock()
+            .unwrap()
+            .get(event.project_id())
+            .cloned();
+        if let Some(watcher) = watcher {
+            watcher.post(event).await.context("failed to post event")
+        } else {
+            Err(anyhow::anyhow!("watcher not found",))
+        }
+    }
+
+    // forgets the watcher of `project_id` if it's still `watcher`, and not one that
+    // replaced it since
+    fn remove(&self, project_id: &ProjectId, watcher: &Watcher) {
+        let mut watchers = self.watchers.lock().unwrap();
+        if watchers
+            .get(project_id)
+            .is_some_and(|current| Arc::ptr_eq(&current.inner, &watcher.inner))
+        {
+            watchers.remove(project_id);
+        }
     }
 }
 
-impl Watchers {
-    pub fn watch(&self, project: &projects::Project) -> Result<()> {
-        let watcher = Watcher::try_from(&self.app_handle)?;
+#[async_trait]
+impl projects::Watchers for Watchers {
+    fn watch(&self, project: &projects::Project) -> Result<()> {
+        let watcher = Watcher::from(&(self.services)().context("failed to get services")?);
 
         let project_id = project.id;
         let project_path = project.path.clone();
+        // the settings are read once, the project is watched again when they change
+        let unmanaged = project.unmanaged();
+
+        // registered right away, so that it can be stopped while it's starting. a project
+        // is watched once, watching it again replaces its watcher
+        if let Some(previous) = self
+            .watchers
+            .lock()
+            .unwrap()
+            .insert(project_id, watcher.clone())
+        {
+            previous.stop();
+        }
 
         task::Builder::new()
             .name(&format!("{} watcher", project_id))
             .spawn({
-                let watchers = Arc::clone(&self.watchers);
-                let watcher = watcher.clone();
+                let watchers = self.clone();
                 async move {
-                    watchers.lock().await.insert(project_id, watcher.clone());
-                    match watcher.run(&project_path, &project_id).await {
+                    match watcher.run(&project_path, &project_id, unmanaged).await {
                         Ok(()) => {
                             tracing::debug!(%project_id, "watcher stopped");
                         },
                         Err(RunError::PathNotFound(path)) => {
                             tracing::warn!(%project_id, path = %path.display(), "watcher stopped: project path not found");
-                            watchers.lock().await.remove(&project_id);
                         }
                         Err(error) => {
                             tracing::error!(?error, %project_id, "watcher error");
-                            watchers.lock().await.remove(&project_id);
                         }
                     }
+                    // however it stopped, a watcher that isn't running is not active
+                    watchers.remove(&project_id, &watcher);
                 }
             })?;
 
         Ok(())
     }
 
-    pub async fn post(&self, event: Event) -> Result<()> {
-        let watchers = self.watchers.lock().await;
-        if let Some(watcher) = watchers.get(event.project_id()) {
-            watcher.post(event).await.context("failed to post event")
-        } else {
-            Err(anyhow::anyhow!("watcher not found",))
-        }
-    }
-
-    pub async fn stop(&self, project_id: &ProjectId) -> Result<()> {
-        if let Some((_, watcher)) = self.watchers.lock().await.remove_entry(project_id) {
+    async fn stop(&self, project_id: &ProjectId) -> Result<()> {
+        let watcher = self.watchers.lock().unwrap().remove(project_id);
+        if let Some(watcher) = watcher {
             watcher.stop();
         };
         Ok(())
     }
+
+    fn active(&self) -> Vec<ProjectId> {
+        self.watchers.lock().unwrap().keys().copied().collect()
+    }
+
+    async fn fetch_gb_data(&self, project_id: &ProjectId) -> Result<()> {
+        self.post(Event::FetchGitbutlerData(*project_id)).await
+    }
+
+    async fn push_gb_data(&self, project_id: &ProjectId) -> Result<()> {
+        self.post(Event::PushGitbutlerData(*project_id)).await
+    }
 }
 
 #[derive(Clone)]
@@ -91,13 +161,11 @@ struct Watcher {
     inner: Arc<WatcherInner>,
 }
 
-impl TryFrom<&AppHandle> for Watcher {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        Ok(Self {
-            inner: Arc::new(WatcherInner::try_from(value)?),
-        })
+impl From<&Services> for Watcher {
+    fn from(value: &Services) -> Self {
+        Self {
+            inner: Arc::new(WatcherInner::from(value)),
+        }
     }
 }
 
@@ -122,8 +190,9 @@ impl Watcher {
         &self,
         path: P,
         project_id: &ProjectId,
+        unmanaged: projects::UnmanagedPaths,
     ) -> Result<(), RunError> {
-        self.inner.run(path, project_id).await
+        self.inner.run(path, project_id, unmanaged).await
     }
 }
 
@@ -135,16 +204,14 @@ struct WatcherInner {
     proxy_tx: Arc<tokio::sync::Mutex<Option<UnboundedSender<Event>>>>,
 }
 
-impl TryFrom<&AppHandle> for WatcherInner {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        Ok(Self {
-            handler: handlers::Handler::try_from(value)?,
+impl From<&Services> for WatcherInner {
+    fn from(value: &Services) -> Self {
+        Self {
+            handler: handlers::Handler::from(value),
             dispatcher: dispatchers::Dispatcher::new(),
             cancellation_token: CancellationToken::new(),
             proxy_tx: Arc::new(tokio::sync::Mutex::new(None)),
-        })
+        }
     }
 }
 
@@ -170,12 +237,13 @@ impl WatcherInner {
         &self,
         path: P,
         project_id: &ProjectId,
+        unmanaged: projects::UnmanagedPaths,
     ) -> Result<(), RunError> {
         let (proxy_tx, mut proxy_rx) = unbounded_channel();
         self.proxy_tx.lock().await.replace(proxy_tx.clone());
 
         let dispatcher = self.dispatcher.clone();
-        let mut dispatcher_rx = match dispatcher.run(project_id, path.as_ref()) {
+        let mut dispatcher_rx = match dispatcher.run(project_id, path.as_ref(), unmanaged) {
             Ok(dispatcher_rx) => Ok(dispatcher_rx),
             Err(dispatchers::RunError::PathNotFound(path)) => Err(RunError::PathNotFound(path)),
             Err(error) => Err(error).context("failed to run dispatcher")?,
@@ -11,7 +11,7 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::projects::ProjectId;
+use crate::projects::{ProjectId, UnmanagedPaths};
 
 use super::events;
 
@@ -48,10 +48,12 @@ impl Dispatcher {
         self,
         project_id: &ProjectId,
         path: P,
+        unmanaged: UnmanagedPaths,
     ) -> Result<Receiver<events::Event>, RunError> {
         let path = path.as_ref();
 
-        let mut file_change_rx = match self.file_change_dispatcher.run(project_id, path) {
+        let mut file_change_rx = match self.file_change_dispatcher.run(project_id, path, unmanaged)
+        {
             Ok(file_change_rx) => Ok(file_change_rx),
             Err(file_change::RunError::PathNotFound(path)) => Err(RunError::PathNotFound(path)),
             Err(error) => Err(error).context("failed to run file change dispatcher")?,
@@ -0,0 +1,257 @@
+use std::{
+    path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use futures::executor::block_on;
+use notify::{PollWatcher, RecommendedWatcher, Watcher};
+use notify_debouncer_full::{new_debouncer, new_debouncer_opt, Debouncer, FileIdMap};
+use tokio::{
+    sync::mpsc::{channel, Receiver},
+    task,
+};
+
+use crate::{
+    fs, git,
+    projects::{ProjectId, UnmanagedPaths},
+    watcher::events,
+};
+
+#[derive(Debug, Clone)]
+pub struct Dispatcher {
+    watcher: Arc<Mutex<Option<FileWatcher>>>,
+}
+
+/// The timeout for debouncing file change events.
+/// This is used to prevent multiple events from being sent for a single file change.
+static DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// How often projects on remote filesystems are scanned for changes.
+static POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Network drives and WSL don't notify of the changes made from the other side of them,
+/// so projects on them are polled instead.
+#[derive(Debug)]
+enum FileWatcher {
+    Native(Debouncer<RecommendedWatcher, FileIdMap>),
+    Polling(Debouncer<PollWatcher, FileIdMap>),
+}
+
+impl FileWatcher {
+    fn new(
+        path: &path::Path,
+        notify_tx: std::sync::mpsc::Sender<notify_debouncer_full::DebounceEventResult>,
+    ) -> Result<Self> {
+        let kind = fs::filesystem_kind(path);
+        let watcher = if kind.is_remote() {
+            tracing::info!(path = %path.display(), ?kind, "polling remote filesystem for changes");
+            new_debouncer_opt(
+                DEBOUNCE_TIMEOUT,
+                None,
+                notify_tx,
+                FileIdMap::new(),
+                notify::Config::default().with_poll_interval(POLL_INTERVAL),
+            )
+            .map(Self::Polling)
+        } else {
+            new_debouncer(DEBOUNCE_TIMEOUT, None, notify_tx).map(Self::Native)
+        };
+        watcher.context("failed to create debouncer")
+    }
+
+    fn watcher(&mut self) -> &mut dyn Watcher {
+        match self {
+            Self::Native(debouncer) => debouncer.watcher(),
+            Self::Polling(debouncer) => debouncer.watcher(),
+        }
+    }
+
+    fn is_polling(&self) -> bool {
+        matches!(self, Self::Polling(_))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RunError {
+    #[error("{0} not found")]
+    PathNotFound(path::PathBuf),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self {
+            watcher: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.watcher.lock().unwrap().take();
+    }
+
+    pub fn run(
+        self,
+        project_id: &ProjectId,
+        path: &path::Path,
+        unmanaged: UnmanagedPaths,
+    ) -> Result<Receiver<events::Event>, RunError> {
+        let (notify_tx, notify_rx) = std::sync::mpsc::channel();
+        let mut debouncer = FileWatcher::new(path, notify_tx)?;
+        let polling = debouncer.is_polling();
+
+        let policy = backoff::ExponentialBackoffBuilder::new()
+            .with_max_elapsed_time(Some(std::time::Duration::from_secs(30)))
+            .build();
+
+        backoff::retry(policy, || {
+            debouncer
+                .watcher()
+                .watch(path, notify::RecursiveMode::Recursive)
+                .map_err(|error| match error.kind {
+                    notify::ErrorKind::PathNotFound => {
+                        backoff::Error::permanent(RunError::PathNotFound(path.to_path_buf()))
+                    }
+                    notify::ErrorKind::Io(_) | notify::ErrorKind::InvalidConfig(_) => {
+                        backoff::Error::permanent(RunError::Other(error.into()))
+                    }
+                    _ => backoff::Error::transient(RunError::Other(error.into())),
+                })
+        })
+        .context("failed to start watcher")?;
+
+        let repo = git::Repository::open(path).context(format!(
+            "failed to open project repository: {}",
+            path.display()
+        ))?;
+
+        // the git directory of a linked worktree is in the one of the main worktree
+        let git_dir = repo.path().to_path_buf();
+        if !git_dir.starts_with(path) {
+            debouncer
+                .watcher()
+                .watch(&git_dir, notify::RecursiveMode::Recursive)
+                .context("failed to watch git directory")?;
+        }
+        // the refs are shared by every worktree, in the git directory of the main one, where
+        // fetching from another worktree updates the remote branches
+        let common_dir = repo.common_dir().to_path_buf();
+        let common_refs = common_dir.join("refs");
+        if !common_refs.starts_with(&git_dir) && !common_refs.starts_with(path) {
+            debouncer
+                .watcher()
+                .watch(&common_refs, notify::RecursiveMode::Recursive)
+                .context("failed to watch common refs directory")?;
+        }
+
+        self.watcher.lock().unwrap().replace(debouncer);
+
+        tracing::debug!(%project_id, "file watcher started");
+
+        let (tx, rx) = channel(1);
+        task::Builder::new()
+            .name(&format!("{} file watcher", project_id))
+            .spawn_blocking({
+                let path = path.to_path_buf();
+                let project_id = *project_id;
+                move || {
+                    for result in notify_rx {
+                        match result {
+                            Err(errors) => {
+                                tracing::error!(?errors, "file watcher error");
+                            }
+                            Ok(events) => {
+                                let file_paths = events.into_iter().filter(|event| is_interesting_kind(event.kind, polling)).flat_map(|event| event.paths.clone()).filter(|file| is_interesting_file(&repo, file));
+                                for file_path in file_paths {
+                                    // in linked worktrees, the git directory is not the .git of the project
+                                    let git_file_path = file_path.strip_prefix(&git_dir).ok().or_else(|| {
+                                        file_path.strip_prefix(&common_dir).ok().filter(|relative_file_path| relative_file_path.starts_with("refs"))
+                                    });
+                                    let event = if let Some(relative_file_path) = git_file_path {
+                                        tracing::info!(
+                                            %project_id,
+                                            file_path = %relative_file_path.display(),
+                                            "git file change",
+                                        );
+                                        events::Event::GitFileChange(
+                                            project_id,
+                                            relative_file_path.to_path_buf(),
+                                        )
+                                    } else {
+                                        match file_path.strip_prefix(&path) {
+                                            Ok(relative_file_path) if relative_file_path.display().to_string().is_empty() => continue,
+                                            // the changes of unmanaged files are none of our business
+                                            Ok(relative_file_path) if unmanaged.contains(relative_file_path) => continue,
+                                            Ok(relative_file_path) => {
+                                                tracing::info!(
+                                                    %project_id,
+                                                    file_path = %relative_file_path.display(),
+                                                    "project file change",
+                                                );
+                                                events::Event::ProjectFileChange(
+                                                    project_id,
+                                                    relative_file_path.to_path_buf(),
+                                                )
+                                            }
+                                            Err(error) => {
+                                                tracing::error!(%project_id, ?error, "failed to strip prefix");
+                                                continue;
+                                            }
+                                        }
+                                    };
+                                    if let Err(error) = block_on(tx.send(event)) {
+                                        tracing::error!(
+                                            %project_id,
+                                            ?error,
+                                            "failed to send file change event",
+                                        );
+                                    }
+                                }
+                    }
+                }
+                }
+                    tracing::debug!(%project_id, "file watcher stopped");
+                }
+
+            }).context(format!("{}: failed to start file watcher thread", project_id))?;
+
+        Ok(rx)
+    }
+}
+
+fn is_interesting_kind(kind: notify::EventKind, polling: bool) -> bool {
+    match kind {
+        notify::EventKind::Create(notify::event::CreateKind::File)
+        | notify::EventKind::Modify(
+            notify::event::ModifyKind::Data(_) | notify::event::ModifyKind::Name(_),
+        )
+        | notify::EventKind::Remove(notify::event::RemoveKind::File) => true,
+        // polling can't tell what a path is or how it changed, only that it did
+        notify::EventKind::Create(notify::event::CreateKind::Any)
+        | notify::EventKind::Modify(notify::event::ModifyKind::Metadata(
+            notify::event::MetadataKind::WriteTime,
+        ))
+        | notify::EventKind::Remove(notify::event::RemoveKind::Any) => polling,
+        _ => false,
+    }
+}
+
+fn is_interesting_file(git_repo: &git::Repository, file_path: &path::Path) -> bool {
+    if file_path.starts_with(git_repo.path()) {
+        let check_file_path = file_path.strip_prefix(git_repo.path()).unwrap();
+        check_file_path.ends_with("FETCH_HEAD")
+            || check_file_path.eq(path::Path::new("logs/HEAD"))
+            || check_file_path.eq(path::Path::new("HEAD"))
+            || check_file_path.eq(path::Path::new("GB_FLUSH"))
+            || check_file_path.eq(path::Path::new("index"))
+    } else if file_path.starts_with(git_repo.common_dir().join("refs")) {
+        // only those of linked worktrees are watched
+        file_path
+            .strip_prefix(git_repo.common_dir())
+            .is_ok_and(|check_file_path| check_file_path.starts_with("refs/remotes"))
+    } else {
+        !git_repo.is_path_ignored(file_path).unwrap_or(false)
+    }
+}
@@ -13,12 +13,11 @@ mod tick_handler;
 use std::time;
 
 use anyhow::{Context, Result};
-use tauri::AppHandle;
 use tracing::instrument;
 
-use crate::events as app_events;
+use crate::{events as app_events, git};
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
@@ -34,29 +33,25 @@ pub struct Handler {
     calculate_vbranches_handler: caltulate_virtual_branches_handler::Handler,
     calculate_deltas_handler: calculate_deltas_handler::Handler,
 
-    events_sender: app_events::Sender,
+    events: app_events::Bus,
 }
 
-impl TryFrom<&AppHandle> for Handler {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> Result<Self, Self::Error> {
-        Ok(Self {
-            events_sender: app_events::Sender::from(value),
-            tick_handler: tick_handler::Handler::try_from(value)?,
-            git_file_change_handler: git_file_change::Handler::try_from(value)?,
-            index_handler: index_handler::Handler::try_from(value)?,
-            flush_session_handler: flush_session::Handler::try_from(value)?,
-            push_gitbutler_handler: push_gitbutler_data::Handler::try_from(value)?,
-            fetch_project_handler: fetch_project_data::Handler::try_from(value)?,
-            fetch_gitbutler_handler: fetch_gitbutler_data::Handler::try_from(value)?,
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            events: value.events.clone(),
+            tick_handler: tick_handler::Handler::from(value),
+            git_file_change_handler: git_file_change::Handler::from(value),
+            index_handler: index_handler::Handler::from(value),
+            flush_session_handler: flush_session::Handler::from(value),
+            push_gitbutler_handler: push_gitbutler_data::Handler::from(value),
+            fetch_project_handler: fetch_project_data::Handler::from(value),
+            fetch_gitbutler_handler: fetch_gitbutler_data::Handler::from(value),
             analytics_handler: analytics_handler::Handler::from(value),
-            push_project_to_gitbutler: push_project_to_gitbutler::Handler::try_from(value)?,
-            calculate_vbranches_handler: caltulate_virtual_branches_handler::Handler::try_from(
-                value,
-            )?,
-            calculate_deltas_handler: calculate_deltas_handler::Handler::try_from(value)?,
-        })
+            push_project_to_gitbutler: push_project_to_gitbutler::Handler::from(value),
+            calculate_vbranches_handler: caltulate_virtual_branches_handler::Handler::from(value),
+            calculate_deltas_handler: calculate_deltas_handler::Handler::from(value),
+        }
     }
 }
 
@@ -115,7 +110,7 @@ impl Handler {
                 Ok(vec![events::Event::Emit(app_events::Event::file(
                     project_id,
                     session_id,
-                    &file_path.display().to_string(),
+                    &git::path::escape(file_path),
                     contents.as_ref(),
                 ))])
             }
@@ -148,9 +143,7 @@ impl Handler {
                 )),
 
             events::Event::Emit(event) => {
-                self.events_sender
-                    .send(event)
-                    .context("failed to send event")?;
+                self.events.publish(event.clone());
                 Ok(vec![])
             }
 
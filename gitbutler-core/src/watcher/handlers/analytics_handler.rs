@@ -1,9 +1,8 @@
 use anyhow::{Context, Result};
-use tauri::{AppHandle, Manager};
 
 use crate::{analytics, users};
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
@@ -11,17 +10,11 @@ pub struct Handler {
     client: analytics::Client,
 }
 
-impl From<&AppHandle> for Handler {
-    fn from(value: &AppHandle) -> Self {
-        let client = value
-            .try_state::<analytics::Client>()
-            .map_or(analytics::Client::default(), |client| {
-                client.inner().clone()
-            });
-
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
         Self {
-            client,
-            users: users::Controller::from(value),
+            client: value.analytics.clone(),
+            users: value.users.clone(),
         }
     }
 }
@@ -1,15 +1,15 @@
 use std::{path, vec};
 
 use anyhow::{Context, Result};
-use tauri::AppHandle;
+use tracing::instrument;
 
 use crate::{
     deltas, gb_repository, project_repository,
     projects::{self, ProjectId},
-    reader, sessions, users,
+    reader, sessions, users, virtual_branches,
 };
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
@@ -28,19 +28,13 @@ impl From<&path::PathBuf> for Handler {
     }
 }
 
-impl TryFrom<&AppHandle> for Handler {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> Result<Self, Self::Error> {
-        let path = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Ok(Self {
-            local_data_dir: path,
-            projects: projects::Controller::try_from(value)?,
-            users: users::Controller::from(value),
-        })
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            local_data_dir: value.local_data_dir.clone(),
+            projects: value.projects.clone(),
+            users: value.users.clone(),
+        }
     }
 }
 
@@ -60,6 +54,7 @@ impl Handler {
         reader::Content::try_from(&full_path).map_err(Into::into)
     }
 
+    #[instrument(skip(self, path), fields(%project_id, path = %path.as_ref().display()), level = "debug")]
     pub fn handle<P: AsRef<std::path::Path>>(
         &self,
         path: P,
@@ -93,6 +88,13 @@ impl Handler {
                 gb_repository
                     .flush_session(&project_repository, &session, user.as_ref())
                     .context(format!("failed to flush session {}", session.id))?;
+                virtual_branches::record_operation(
+                    &self.local_data_dir,
+                    project_id,
+                    user.as_ref(),
+                    "flush_session",
+                    virtual_branches::Requester::Background,
+                );
             }
         }
 
@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use tokio_util::sync::CancellationToken;
+use tracing::instrument;
+
+use crate::{
+    assets, events as app_events,
+    projects::ProjectId,
+    virtual_branches::{
+        self, controller::ControllerError, errors::ListVirtualBranchesError, stream,
+    },
+};
+
+use super::{events, Services};
+
+// how long to wait for more events before recomputing
+const DEBOUNCE: Duration = Duration::from_millis(100);
+// how often to report progress of a run that is taking a while
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+// after this long, the frontend is told to stop waiting and show what it has
+const SOFT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Recomputes virtual branches for a project.
+//
+// Every request supersedes the previous one for the same project: the previous run is
+// cancelled, wherever it is in the pipeline, and only the latest result is reported
+// to the frontend. Runs that take a while report their progress as they go, and once
+// they take longer than the soft timeout, stream the branches that are done so far.
+#[derive(Clone)]
+pub struct Handler {
+    inner: Arc<HandlerInner>,
+    runs: Arc<Mutex<Runs>>,
+    events: app_events::Bus,
+}
+
+#[derive(Default)]
+struct Runs {
+    generation: u64,
+    by_project_id: HashMap<ProjectId, (u64, CancellationToken)>,
+}
+
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            inner: Arc::new(HandlerInner {
+                vbranch_controller: value
+                    .vbranches
+                    .requested_by(virtual_branches::Requester::Background),
+                assets_proxy: value.assets_proxy.clone(),
+            }),
+            runs: Arc::new(Mutex::new(Runs::default())),
+            events: value.events.clone(),
+        }
+    }
+}
+
+impl Handler {
+    #[instrument(skip(self), fields(%project_id), level = "debug")]
+    pub async fn handle(&self, project_id: &ProjectId) -> Result<Vec<events::Event>> {
+        let (generation, cancel) = self.start(project_id);
+
+        tokio::select! {
+            () = cancel.cancelled() => return Ok(vec![]),
+            () = tokio::time::sleep(DEBOUNCE) => {},
+        }
+
+        let progress = virtual_branches::Progress::default();
+        // listing blocks on the repository all the way, out of the way of the runtime
+        let mut run = tokio::task::spawn_blocking({
+            let runtime = tokio::runtime::Handle::current();
+            let inner = Arc::clone(&self.inner);
+            let project_id = *project_id;
+            let cancel = cancel.clone();
+            let progress = progress.clone();
+            move || runtime.block_on(inner.handle(&project_id, &cancel, &progress))
+        });
+
+        let started_at = Instant::now();
+        let mut ticks = tokio::time::interval_at(
+            tokio::time::Instant::now() + PROGRESS_INTERVAL,
+            PROGRESS_INTERVAL,
+        );
+        let mut reported = false;
+        let mut streamed = 0;
+        let result = loop {
+            tokio::select! {
+                result = &mut run => break result.context("failed to join virtual branches run")?,
+                _ = ticks.tick() => {
+                    if cancel.is_cancelled() {
+                        continue;
+                    }
+                    let timed_out = started_at.elapsed() >= SOFT_TIMEOUT;
+                    self.events.publish(app_events::Event::virtual_branches_progress(
+                        project_id,
+                        &progress.snapshot(),
+                        true,
+                        timed_out,
+                    ));
+                    reported = true;
+                    if timed_out && progress.done_count() > streamed {
+                        let done = progress.done_branches();
+                        streamed = done.len();
+                        self.stream_done(project_id, done).await;
+                    }
+                }
+            }
+        };
+        self.finish(project_id, generation);
+
+        if cancel.is_cancelled() {
+            // superseded while running, a newer run will report
+            return Ok(vec![]);
+        }
+
+        let mut events = result?;
+        if reported {
+            events.push(events::Event::Emit(
+                app_events::Event::virtual_branches_progress(
+                    project_id,
+                    &progress.snapshot(),
+                    false,
+                    false,
+                ),
+            ));
+        }
+        Ok(events)
+    }
+
+    // the branches that are done so far, for the frontend to show while it waits for the rest
+    async fn stream_done(
+        &self,
+        project_id: &ProjectId,
+        done: Vec<virtual_branches::VirtualBranch>,
+    ) {
+        let done = self.inner.assets_proxy.proxy_virtual_branches(done).await;
+        for chunk in stream::chunks(done, stream::MAX_CHUNK_BYTES) {
+            self.events
+                .publish(app_events::Event::virtual_branches_chunk(
+                    project_id,
+                    stream::STATUS_STREAM,
+                    &chunk,
+                ));
+        }
+    }
+
+    fn start(&self, project_id: &ProjectId) -> (u64, CancellationToken) {
+        let mut runs = self.runs.lock().unwrap();
+        runs.generation += 1;
+        let generation = runs.generation;
+        let cancel = CancellationToken::new();
+        if let Some((_, previous)) = runs
+            .by_project_id
+            .insert(*project_id, (generation, cancel.clone()))
+        {
+            previous.cancel();
+        }
+        (generation, cancel)
+    }
+
+    fn finish(&self, project_id: &ProjectId, generation: u64) {
+        let mut runs = self.runs.lock().unwrap();
+        if runs
+            .by_project_id
+            .get(project_id)
+            .is_some_and(|(current, _)| *current == generation)
+        {
+            runs.by_project_id.remove(project_id);
+        }
+    }
+}
+
+struct HandlerInner {
+    vbranch_controller: virtual_branches::Controller,
+    assets_proxy: assets::Proxy,
+}
+
+impl HandlerInner {
+    #[instrument(skip(self, cancel, progress), fields(%project_id), level = "debug")]
+    pub async fn handle(
+        &self,
+        project_id: &ProjectId,
+        cancel: &CancellationToken,
+        progress: &virtual_branches::Progress,
+    ) -> Result<Vec<events::Event>> {
+        match self
+            .vbranch_controller
+            .list_virtual_branches_cancellable(project_id, cancel, progress)
+            .await
+        {
+            Ok(branches) => Ok(vec![events::Event::Emit(
+                app_events::Event::virtual_branches(
+                    project_id,
+                    &self.assets_proxy.proxy_virtual_branches(branches).await,
+                ),
+            )]),
+            Err(
+                ControllerError::VerifyError(_)
+                | ControllerError::Action(ListVirtualBranchesError::Cancelled),
+            ) => Ok(vec![]),
+            Err(error) => Err(error).context("failed to list virtual branches"),
+        }
+    }
+}
@@ -1,30 +1,30 @@
 use std::{path, sync::Arc, time};
 
 use anyhow::{Context, Result};
-use tauri::AppHandle;
 use tokio::sync::Mutex;
+use tracing::instrument;
 
-use crate::{gb_repository, project_repository, projects, projects::ProjectId, users};
+use crate::{
+    gb_repository, project_repository, projects, projects::ProjectId, users, virtual_branches,
+};
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
     inner: Arc<Mutex<HandlerInner>>,
 }
 
-impl TryFrom<&AppHandle> for Handler {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        let inner = HandlerInner::try_from(value)?;
-        Ok(Self {
-            inner: Arc::new(Mutex::new(inner)),
-        })
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(HandlerInner::from(value))),
+        }
     }
 }
 
 impl Handler {
+    #[instrument(skip(self), fields(%project_id), level = "debug")]
     pub async fn handle(
         &self,
         project_id: &ProjectId,
@@ -44,19 +44,13 @@ struct HandlerInner {
     users: users::Controller,
 }
 
-impl TryFrom<&AppHandle> for HandlerInner {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        let local_data_dir = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Ok(Self {
-            local_data_dir,
-            projects: projects::Controller::try_from(value)?,
-            users: users::Controller::from(value),
-        })
+impl From<&Services> for HandlerInner {
+    fn from(value: &Services) -> Self {
+        Self {
+            local_data_dir: value.local_data_dir.clone(),
+            projects: value.projects.clone(),
+            users: value.users.clone(),
+        }
     }
 }
 
@@ -76,6 +70,9 @@ impl HandlerInner {
         if !project.api.as_ref().map(|api| api.sync).unwrap_or_default() {
             anyhow::bail!("sync disabled");
         }
+        if project.read_only {
+            return Ok(vec![]);
+        }
 
         let project_repository =
             project_repository::Repository::open(&project).context("failed to open repository")?;
@@ -106,7 +103,16 @@ impl HandlerInner {
                 }
             })
         }) {
-            Ok(()) => projects::FetchResult::Fetched { timestamp: *now },
+            Ok(()) => {
+                virtual_branches::record_operation(
+                    &self.local_data_dir,
+                    project_id,
+                    user.as_ref(),
+                    "fetch_gitbutler_data",
+                    virtual_branches::Requester::Background,
+                );
+                projects::FetchResult::Fetched { timestamp: *now }
+            }
             Err(backoff::Error::Permanent(gb_repository::RemoteError::Network)) => {
                 projects::FetchResult::Error {
                     timestamp: *now,
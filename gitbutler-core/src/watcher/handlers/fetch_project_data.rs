@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::sync::Mutex;
+use tracing::instrument;
+
+use crate::{
+    events as app_events,
+    project_repository::RemoteError,
+    projects::{self, ProjectId},
+    virtual_branches,
+};
+
+use super::{events, Services};
+
+#[derive(Clone)]
+pub struct Handler {
+    inner: Arc<Mutex<HandlerInner>>,
+}
+
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(HandlerInner::from(value))),
+        }
+    }
+}
+
+impl Handler {
+    #[instrument(skip(self), fields(%project_id), level = "debug")]
+    pub async fn handle(&self, project_id: &ProjectId) -> Result<Vec<events::Event>> {
+        if let Ok(inner) = self.inner.try_lock() {
+            inner.handle(project_id).await
+        } else {
+            Ok(vec![])
+        }
+    }
+}
+
+struct HandlerInner {
+    projects: projects::Controller,
+    vbranches: virtual_branches::Controller,
+}
+
+impl From<&Services> for HandlerInner {
+    fn from(value: &Services) -> Self {
+        Self {
+            projects: value.projects.clone(),
+            vbranches: value
+                .vbranches
+                .requested_by(virtual_branches::Requester::Background),
+        }
+    }
+}
+
+impl HandlerInner {
+    pub async fn handle(&self, project_id: &ProjectId) -> Result<Vec<events::Event>> {
+        // read-only projects are only fetched when asked to, which fails
+        if self
+            .projects
+            .get(project_id)
+            .context("failed to get project")?
+            .read_only
+        {
+            return Ok(vec![]);
+        }
+        match self.vbranches.fetch_all_remotes(project_id).await {
+            Ok(summary) => {
+                let mut fetched = vec![
+                    events::Event::Emit(app_events::Event::git_fetch(project_id, &summary.remotes)),
+                    events::Event::CalculateVirtualBranches(*project_id),
+                ];
+                if let Some(replays) = self.replay_outbox(project_id).await? {
+                    fetched.push(events::Event::Emit(app_events::Event::outbox_replay(
+                        project_id, &replays,
+                    )));
+                }
+                Ok(fetched)
+            }
+            Err(virtual_branches::controller::ControllerError::VerifyError(_))
+            | Err(virtual_branches::controller::ControllerError::Action(
+                virtual_branches::errors::FetchFromTargetError::DefaultTargetNotSet(_)
+                | virtual_branches::errors::FetchFromTargetError::Remote(RemoteError::Network)
+                | virtual_branches::errors::FetchFromTargetError::Remote(RemoteError::Auth)
+                | virtual_branches::errors::FetchFromTargetError::Remote(RemoteError::Cancelled),
+            )) => Ok(vec![events::Event::Emit(app_events::Event::git_fetch(
+                project_id,
+                &[],
+            ))]),
+            Err(error) => Err(error).context("failed to fetch project"),
+        }
+    }
+
+    // the fetch succeeding means the network is back, for what was queued while it wasn't
+    async fn replay_outbox(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Option<Vec<virtual_branches::Replay>>> {
+        let outbox = self
+            .vbranches
+            .list_outbox(project_id)
+            .await
+            .context("failed to list outbox")?;
+        if outbox.is_empty() {
+            return Ok(None);
+        }
+        match self.vbranches.replay_outbox(project_id).await {
+            Ok(replays) => Ok(Some(replays)),
+            Err(virtual_branches::controller::ControllerError::VerifyError(_))
+            | Err(virtual_branches::controller::ControllerError::Action(
+                virtual_branches::errors::OutboxError::Remote(RemoteError::Cancelled),
+            )) => Ok(None),
+            Err(error) => Err(error).context("failed to replay outbox"),
+        }
+    }
+}
@@ -1,30 +1,31 @@
 use std::{path, sync::Arc};
 
 use anyhow::{Context, Result};
-use tauri::AppHandle;
 use tokio::sync::Mutex;
+use tracing::instrument;
 
-use crate::{gb_repository, project_repository, projects, projects::ProjectId, sessions, users};
+use crate::{
+    gb_repository, project_repository, projects, projects::ProjectId, sessions, users,
+    virtual_branches,
+};
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
     inner: Arc<Mutex<HandlerInner>>,
 }
 
-impl TryFrom<&AppHandle> for Handler {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        let inner = HandlerInner::try_from(value)?;
-        Ok(Self {
-            inner: Arc::new(Mutex::new(inner)),
-        })
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(HandlerInner::from(value))),
+        }
     }
 }
 
 impl Handler {
+    #[instrument(skip(self, session), fields(%project_id, session_id = %session.id), level = "debug")]
     pub fn handle(
         &self,
         project_id: &ProjectId,
@@ -44,19 +45,13 @@ struct HandlerInner {
     users: users::Controller,
 }
 
-impl TryFrom<&AppHandle> for HandlerInner {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        let path = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Ok(Self {
-            local_data_dir: path,
-            project_store: projects::Controller::try_from(value)?,
-            users: users::Controller::from(value),
-        })
+impl From<&Services> for HandlerInner {
+    fn from(value: &Services) -> Self {
+        Self {
+            local_data_dir: value.local_data_dir.clone(),
+            project_store: value.projects.clone(),
+            users: value.users.clone(),
+        }
     }
 }
 
@@ -70,6 +65,9 @@ impl HandlerInner {
             .project_store
             .get(project_id)
             .context("failed to get project")?;
+        if project.read_only {
+            return Ok(vec![]);
+        }
 
         let user = self.users.get_user()?;
         let project_repository =
@@ -84,6 +82,13 @@ impl HandlerInner {
         let session = gb_repo
             .flush_session(&project_repository, session, user.as_ref())
             .context(format!("failed to flush session {}", session.id))?;
+        virtual_branches::record_operation(
+            &self.local_data_dir,
+            project_id,
+            user.as_ref(),
+            "flush_session",
+            virtual_branches::Requester::Background,
+        );
 
         Ok(vec![
             events::Event::Session(*project_id, session),
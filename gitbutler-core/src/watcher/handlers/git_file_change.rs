@@ -1,15 +1,15 @@
 use std::path;
 
 use anyhow::{Context, Result};
-use tauri::AppHandle;
+use tracing::instrument;
 
 use crate::{
     analytics, events as app_events, gb_repository, project_repository,
     projects::{self, ProjectId},
-    users,
+    users, virtual_branches,
 };
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
@@ -18,23 +18,18 @@ pub struct Handler {
     users: users::Controller,
 }
 
-impl TryFrom<&AppHandle> for Handler {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> Result<Self, Self::Error> {
-        let path = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Ok(Self {
-            local_data_dir: path,
-            projects: projects::Controller::try_from(value)?,
-            users: users::Controller::from(value),
-        })
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            local_data_dir: value.local_data_dir.clone(),
+            projects: value.projects.clone(),
+            users: value.users.clone(),
+        }
     }
 }
 
 impl Handler {
+    #[instrument(skip(self, path), fields(%project_id, path = %path.as_ref().display()), level = "debug")]
     pub fn handle<P: AsRef<std::path::Path>>(
         &self,
         path: P,
@@ -50,7 +45,7 @@ impl Handler {
 
         match path.as_ref().to_str().unwrap() {
             "FETCH_HEAD" => Ok(vec![
-                events::Event::Emit(app_events::Event::git_fetch(&project.id)),
+                events::Event::Emit(app_events::Event::git_fetch(&project.id, &[])),
                 events::Event::CalculateVirtualBranches(*project_id),
             ]),
             "logs/HEAD" => Ok(vec![events::Event::Emit(app_events::Event::git_activity(
@@ -65,7 +60,7 @@ impl Handler {
                 )
                 .context("failed to open repository")?;
 
-                let file_path = project.path.join(".git/GB_FLUSH");
+                let file_path = project_repository.git_repository.path().join("GB_FLUSH");
 
                 if file_path.exists() {
                     if let Err(e) = std::fs::remove_file(&file_path) {
@@ -97,14 +92,29 @@ impl Handler {
                             &project.id,
                             &head.to_string(),
                         )),
+                        // verifying the branch reconciles what was done outside of gitbutler
+                        events::Event::CalculateVirtualBranches(*project_id),
                     ])
                 } else {
                     Ok(vec![])
                 }
             }
-            "index" => Ok(vec![events::Event::Emit(app_events::Event::git_index(
-                &project.id,
-            ))]),
+            // gitbutler writes the index itself on every operation, after which there is
+            // nothing new to calculate, the index still changed for whoever listens
+            "index" if virtual_branches::is_own_index(&project_repository) => {
+                Ok(vec![events::Event::Emit(app_events::Event::git_index(
+                    &project.id,
+                ))])
+            }
+            "index" => Ok(vec![
+                events::Event::Emit(app_events::Event::git_index(&project.id)),
+                events::Event::CalculateVirtualBranches(*project_id),
+            ]),
+            // a remote branch a fetch from another worktree updated, one event for each of
+            // them which the calculation is debounced over
+            _ if path.as_ref().starts_with("refs/remotes") => {
+                Ok(vec![events::Event::CalculateVirtualBranches(*project_id)])
+            }
             _ => Ok(vec![]),
         }
     }
@@ -1,16 +1,15 @@
 use std::path;
 
 use anyhow::{Context, Result};
-use tauri::AppHandle;
 
 use crate::{
-    deltas, events as app_events, gb_repository, project_repository,
+    database, deltas, events as app_events, gb_repository, project_repository,
     projects::{self, ProjectId},
     sessions::{self, SessionId},
     users,
 };
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
@@ -21,21 +20,15 @@ pub struct Handler {
     deltas_database: deltas::Database,
 }
 
-impl TryFrom<&AppHandle> for Handler {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> Result<Self, Self::Error> {
-        let path = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Ok(Self {
-            local_data_dir: path,
-            projects: projects::Controller::try_from(value)?,
-            users: users::Controller::from(value),
-            sessions_database: sessions::Database::from(value),
-            deltas_database: deltas::Database::from(value),
-        })
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            local_data_dir: value.local_data_dir.clone(),
+            projects: value.projects.clone(),
+            users: value.users.clone(),
+            sessions_database: sessions::Database::from(value.database.clone()),
+            deltas_database: deltas::Database::from(value.database.clone()),
+        }
     }
 }
 
@@ -2,30 +2,29 @@ use std::path;
 use std::sync::{Arc, Mutex, TryLockError};
 
 use anyhow::{Context, Result};
-use tauri::AppHandle;
+use tracing::instrument;
 
 use crate::gb_repository::RemoteError;
 use crate::projects::ProjectId;
 use crate::{gb_repository, project_repository, projects, users};
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
     inner: Arc<Mutex<HandlerInner>>,
 }
 
-impl TryFrom<&AppHandle> for Handler {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        Ok(Self {
-            inner: Arc::new(Mutex::new(HandlerInner::try_from(value)?)),
-        })
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(HandlerInner::from(value))),
+        }
     }
 }
 
 impl Handler {
+    #[instrument(skip(self), fields(%project_id), level = "debug")]
     pub fn handle(&self, project_id: &ProjectId) -> Result<Vec<events::Event>> {
         match self.inner.try_lock() {
             Ok(inner) => inner.handle(project_id),
@@ -41,19 +40,13 @@ struct HandlerInner {
     users: users::Controller,
 }
 
-impl TryFrom<&AppHandle> for HandlerInner {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        let path = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Ok(Self::new(
-            path,
-            projects::Controller::try_from(value)?,
-            users::Controller::from(value),
-        ))
+impl From<&Services> for HandlerInner {
+    fn from(value: &Services) -> Self {
+        Self::new(
+            value.local_data_dir.clone(),
+            value.projects.clone(),
+            value.users.clone(),
+        )
     }
 }
 
@@ -73,6 +66,9 @@ impl HandlerInner {
     pub fn handle(&self, project_id: &ProjectId) -> Result<Vec<events::Event>> {
         let user = self.users.get_user()?;
         let project = self.projects.get(project_id)?;
+        if project.read_only {
+            return Ok(vec![]);
+        }
         let project_repository =
             project_repository::Repository::open(&project).context("failed to open repository")?;
         let gb_repo = gb_repository::Repository::open(
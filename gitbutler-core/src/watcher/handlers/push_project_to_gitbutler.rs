@@ -2,8 +2,8 @@ use std::{path, sync::Arc, time};
 
 use anyhow::{Context, Result};
 use itertools::Itertools;
-use tauri::AppHandle;
 use tokio::sync::Mutex;
+use tracing::instrument;
 
 use crate::{
     gb_repository,
@@ -13,25 +13,23 @@ use crate::{
     users,
 };
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
     inner: Arc<Mutex<HandlerInner>>,
 }
 
-impl TryFrom<&AppHandle> for Handler {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        let inner = HandlerInner::try_from(value)?;
-        Ok(Self {
-            inner: Arc::new(Mutex::new(inner)),
-        })
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(HandlerInner::from(value))),
+        }
     }
 }
 
 impl Handler {
+    #[instrument(skip(self), fields(%project_id), level = "debug")]
     pub async fn handle(&self, project_id: &ProjectId) -> Result<Vec<events::Event>> {
         if let Ok(inner) = self.inner.try_lock() {
             inner.handle(project_id).await
@@ -48,20 +46,14 @@ pub struct HandlerInner {
     batch_size: usize,
 }
 
-impl TryFrom<&AppHandle> for HandlerInner {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        let path = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Ok(Self {
+impl From<&Services> for HandlerInner {
+    fn from(value: &Services) -> Self {
+        Self {
             batch_size: 1000,
-            local_data_dir: path,
-            project_store: projects::Controller::try_from(value)?,
-            users: users::Controller::from(value),
-        })
+            local_data_dir: value.local_data_dir.clone(),
+            project_store: value.projects.clone(),
+            users: value.users.clone(),
+        }
     }
 }
 
@@ -282,8 +274,7 @@ mod test {
     use std::path::PathBuf;
 
     use crate::project_repository::LogUntil;
-    use crate::test_utils::{Case, Suite};
-    use crate::virtual_branches::set_test_target;
+    use crate::test_utils::{set_test_target, Case, Suite};
 
     use super::super::test_remote_repository;
     use super::*;
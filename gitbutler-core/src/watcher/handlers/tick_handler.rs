@@ -1,44 +1,40 @@
 use std::{path, time};
 
 use anyhow::{Context, Result};
-use tauri::AppHandle;
+use tracing::instrument;
 
 use crate::{
     gb_repository, project_repository,
     projects::{self, FetchResult, ProjectId},
-    sessions, users,
+    sessions, settings, users,
 };
 
-use super::events;
+use super::{events, Services};
 
 #[derive(Clone)]
 pub struct Handler {
     local_data_dir: path::PathBuf,
     projects: projects::Controller,
     users: users::Controller,
+    settings: settings::Controller,
 }
 
-impl TryFrom<&AppHandle> for Handler {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &AppHandle) -> std::result::Result<Self, Self::Error> {
-        let path = value
-            .path_resolver()
-            .app_data_dir()
-            .context("failed to get app data dir")?;
-        Ok(Self {
-            local_data_dir: path,
-            projects: projects::Controller::try_from(value)?,
-            users: users::Controller::from(value),
-        })
+impl From<&Services> for Handler {
+    fn from(value: &Services) -> Self {
+        Self {
+            local_data_dir: value.local_data_dir.clone(),
+            projects: value.projects.clone(),
+            users: value.users.clone(),
+            settings: value.settings.clone(),
+        }
     }
 }
 
 const GB_FETCH_INTERVAL: time::Duration = time::Duration::new(15 * 60, 0);
-const PROJECT_FETCH_INTERVAL: time::Duration = time::Duration::new(15 * 60, 0);
 const PROJECT_PUSH_INTERVAL: time::Duration = time::Duration::new(15 * 60, 0);
 
 impl Handler {
+    #[instrument(skip(self), fields(%project_id), level = "debug")]
     pub fn handle(
         &self,
         project_id: &ProjectId,
@@ -70,7 +66,8 @@ impl Handler {
             .copied()
             .unwrap_or(time::UNIX_EPOCH);
 
-        if now.duration_since(project_data_last_fetch)? > PROJECT_FETCH_INTERVAL {
+        let fetch_interval = self.settings.get()?.fetch_interval();
+        if now.duration_since(project_data_last_fetch)? > fetch_interval {
             events.push(events::Event::FetchProjectData(*project_id));
         }
 
@@ -215,6 +212,7 @@ mod test_handler {
             .await?;
 
         let listener = Handler {
+            settings: settings::Controller::from(&suite.local_app_data),
             local_data_dir: suite.local_app_data,
             projects: suite.projects,
             users: suite.users,
@@ -235,6 +233,7 @@ mod test_handler {
         let Case { project, .. } = suite.new_case();
 
         let listener = Handler {
+            settings: settings::Controller::from(&suite.local_app_data),
             local_data_dir: suite.local_app_data,
             projects: suite.projects,
             users: suite.users,
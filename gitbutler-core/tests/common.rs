@@ -1,7 +1,15 @@
 #![allow(unused)]
-use gblib::git;
+use gitbutler_core::git;
 use std::{path, str::from_utf8};
 
+/// Lets libgit2 open partial clones, like the binaries do when they start.
+pub fn enable_extensions() {
+    static ENABLED: std::sync::Once = std::sync::Once::new();
+    ENABLED.call_once(|| {
+        gitbutler_git::git2::enable_extensions().expect("failed to enable git extensions");
+    });
+}
+
 pub fn temp_dir() -> std::path::PathBuf {
     tempfile::tempdir()
         .expect("failed to create temp dir")
@@ -310,6 +318,14 @@ impl TestProject {
             .expect("failed to read references")
     }
 
+    pub fn remote_references(&self) -> Vec<git::Reference> {
+        self.remote_repository
+            .references()
+            .expect("failed to get references")
+            .collect::<Result<Vec<_>, _>>()
+            .expect("failed to read references")
+    }
+
     pub fn add_submodule(&self, url: &git::Url, path: &path::Path) {
         let mut submodule = self.local_repository.add_submodule(url, path).unwrap();
         let repo = submodule.open().unwrap();
@@ -1,7 +1,7 @@
 mod common;
 
 use self::common::{paths, TestProject};
-use gblib::{gb_repository, git, project_repository, projects};
+use gitbutler_core::{gb_repository, git, project_repository, projects};
 use std::path;
 
 mod init {
@@ -0,0 +1,973 @@
+mod common;
+
+use self::common::paths;
+use gitbutler_core::projects::Controller;
+
+pub fn new() -> Controller {
+    let data_dir = paths::data_dir();
+    Controller::from(&data_dir)
+}
+
+mod add {
+    use super::*;
+
+    #[test]
+    fn success() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let path = repository.path();
+        let project = controller.add(path).unwrap();
+        assert_eq!(project.path, path);
+        assert_eq!(project.title, path.iter().last().unwrap().to_str().unwrap());
+    }
+
+    #[test]
+    fn partial_clone() {
+        common::enable_extensions();
+        let controller = new();
+        let repository = common::TestProject::default();
+        std::fs::write(repository.path().join("file.txt"), "first").unwrap();
+        let first = repository.commit_all("first");
+        std::fs::write(repository.path().join("file.txt"), "second").unwrap();
+        let second = repository.commit_all("second");
+        git2::Repository::open(repository.path())
+            .unwrap()
+            .config()
+            .unwrap()
+            .set_bool("uploadpack.allowFilter", true)
+            .unwrap();
+
+        let path = common::temp_dir().join("partial");
+        let status = std::process::Command::new("git")
+            .args(["clone", "--quiet", "--filter=blob:none"])
+            .arg(format!("file://{}", repository.path().display()))
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let project = controller.add(&path).unwrap();
+        assert_eq!(project.path, path);
+
+        // only the blobs of what's checked out were fetched, the others are on demand
+        let cloned = gitbutler_core::git::Repository::open(&path).unwrap();
+        let first_tree = cloned.find_commit(first).unwrap().tree().unwrap();
+        let second_tree = cloned.find_commit(second).unwrap().tree().unwrap();
+        let first_blob = first_tree
+            .get_path(std::path::Path::new("file.txt"))
+            .unwrap()
+            .id();
+        assert!(cloned.find_blob(first_blob).is_err());
+        let diff = gitbutler_core::git::diff::trees(&cloned, &first_tree, &second_tree).unwrap();
+        let hunks = diff.get(std::path::Path::new("file.txt")).unwrap();
+        assert!(hunks[0].diff.contains("-first"));
+        assert!(hunks[0].diff.contains("+second"));
+
+        // and checking out a tree fetches the blobs it writes
+        cloned
+            .checkout_tree(&first_tree)
+            .force()
+            .checkout()
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path.join("file.txt")).unwrap(),
+            "first"
+        );
+    }
+
+    #[test]
+    fn linked_worktree() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        // .git of a linked worktree is a file pointing into the git directory of the main one
+        let path = common::temp_dir().join("linked");
+        git2::Repository::open(repository.path())
+            .unwrap()
+            .worktree("linked", &path, None)
+            .unwrap();
+        assert!(path.join(".git").is_file());
+
+        let project = controller.add(&path).unwrap();
+        assert_eq!(project.path, path);
+    }
+
+    #[test]
+    fn bare_repository() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let dir = common::temp_dir();
+        let path = dir.join("bare.git");
+        git2::build::RepoBuilder::new()
+            .bare(true)
+            .clone(repository.path().to_str().unwrap(), &path)
+            .unwrap();
+
+        let project = controller.add(&path).unwrap();
+        assert_eq!(project.bare_repository.as_deref(), Some(path.as_path()));
+        assert_eq!(project.path, dir.join("bare-gitbutler"));
+        assert!(project.path.join(".git").is_file());
+        assert_eq!(project.title, "bare");
+        let worktree = git2::Repository::open(&project.path).unwrap();
+        assert_eq!(
+            worktree.head().unwrap().name(),
+            Some("refs/heads/gitbutler-workspace")
+        );
+        assert!(worktree.find_reference("refs/heads/gitbutler").is_err());
+
+        assert!(matches!(
+            controller.add(&path),
+            Err(gitbutler_core::projects::AddError::AlreadyExists)
+        ));
+    }
+
+    #[test]
+    fn scoped() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let path = repository.path();
+        std::fs::create_dir_all(path.join("services/api")).unwrap();
+        std::fs::create_dir_all(path.join("services/web")).unwrap();
+
+        let whole = controller.add(path).unwrap();
+        let api = controller
+            .add_scoped(path, std::path::Path::new("./services/api/"))
+            .unwrap();
+        let web = controller
+            .add_scoped(path, std::path::Path::new("services/web"))
+            .unwrap();
+        assert_eq!(whole.scope, None);
+        assert_eq!(
+            api.scope.as_deref(),
+            Some(std::path::Path::new("services/api"))
+        );
+        assert_eq!(api.path, path);
+        assert!(api.title.ends_with("/services/api"));
+        assert_ne!(api.id, web.id);
+
+        let unmanaged = api.unmanaged();
+        assert!(!unmanaged.contains(std::path::Path::new("services/api/main.rs")));
+        assert!(unmanaged.contains(std::path::Path::new("services/web/main.rs")));
+
+        assert!(matches!(
+            controller.add_scoped(path, std::path::Path::new("services/api")),
+            Err(gitbutler_core::projects::AddError::AlreadyExists)
+        ));
+        assert!(matches!(
+            controller.add_scoped(path, std::path::Path::new("services/db")),
+            Err(gitbutler_core::projects::AddError::ScopeNotFound(_))
+        ));
+        assert!(matches!(
+            controller.add_scoped(path, std::path::Path::new("../sibling")),
+            Err(gitbutler_core::projects::AddError::InvalidScope(_))
+        ));
+    }
+
+    mod error {
+        use gitbutler_core::projects::AddError;
+
+        use super::*;
+
+        #[test]
+        fn missing() {
+            let controller = new();
+            let path = tempfile::tempdir().unwrap().into_path();
+            assert!(matches!(
+                controller.add(&path.join("missing")),
+                Err(AddError::PathNotFound)
+            ));
+        }
+
+        #[test]
+        fn not_git() {
+            let controller = new();
+            let path = tempfile::tempdir().unwrap().into_path();
+            std::fs::write(path.join("file.txt"), "hello world").unwrap();
+            assert!(matches!(
+                controller.add(&path),
+                Err(AddError::NotAGitRepository)
+            ));
+        }
+
+        #[test]
+        fn empty() {
+            let controller = new();
+            let path = tempfile::tempdir().unwrap().into_path();
+            assert!(matches!(
+                controller.add(&path),
+                Err(AddError::NotAGitRepository)
+            ));
+        }
+
+        #[test]
+        fn twice() {
+            let controller = new();
+            let repository = common::TestProject::default();
+            let path = repository.path();
+            controller.add(path).unwrap();
+            assert!(matches!(controller.add(path), Err(AddError::AlreadyExists)));
+        }
+    }
+}
+
+mod groups {
+    use gitbutler_core::projects::GroupError;
+
+    use super::*;
+
+    #[test]
+    fn group_and_reorder() {
+        let controller = new();
+        let repositories = [
+            common::TestProject::default(),
+            common::TestProject::default(),
+            common::TestProject::default(),
+        ];
+        let projects = repositories
+            .iter()
+            .map(|repository| controller.add(repository.path()).unwrap())
+            .collect::<Vec<_>>();
+
+        let work = controller.add_group("Client work").unwrap();
+        let oss = controller.add_group(" OSS ").unwrap();
+        assert_eq!(oss.name, "OSS");
+        controller.set_group(&projects[0].id, Some(oss.id)).unwrap();
+        controller.set_group(&projects[2].id, Some(oss.id)).unwrap();
+        controller
+            .reorder(&[projects[2].id, projects[0].id])
+            .unwrap();
+        controller.reorder_groups(&[oss.id, work.id]).unwrap();
+
+        let grouped = controller.list_grouped().unwrap();
+        let ids = grouped
+            .iter()
+            .map(|grouped| {
+                (
+                    grouped.group.as_ref().map(|group| group.name.as_str()),
+                    grouped
+                        .projects
+                        .iter()
+                        .map(|project| project.id)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                (Some("OSS"), vec![projects[2].id, projects[0].id]),
+                (Some("Client work"), vec![]),
+                (None, vec![projects[1].id]),
+            ]
+        );
+
+        // the projects of a deleted group are in no group
+        controller.delete_group(&oss.id).unwrap();
+        let grouped = controller.list_grouped().unwrap();
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[1].projects.len(), 3);
+        assert!(controller.get(&projects[0].id).unwrap().group.is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+        let group = controller.add_group("Experiments").unwrap();
+
+        assert!(matches!(
+            controller.add_group("  "),
+            Err(GroupError::EmptyName)
+        ));
+        controller.delete_group(&group.id).unwrap();
+        assert!(matches!(
+            controller.set_group(&project.id, Some(group.id)),
+            Err(GroupError::GroupNotFound)
+        ));
+        assert!(matches!(
+            controller.reorder(&[project.id, project.id]),
+            Err(GroupError::ProjectNotFound)
+        ));
+    }
+}
+
+mod relink {
+    use gitbutler_core::projects::{Location, RelinkError};
+
+    use super::*;
+
+    fn init(path: &std::path::Path, message: &str) {
+        let repository = git2::Repository::init(path).unwrap();
+        let tree = repository
+            .find_tree(repository.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let signature = git2::Signature::now("test", "test@email.com").unwrap();
+        repository
+            .commit(Some("HEAD"), &signature, &signature, message, &tree, &[])
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn moved() {
+        let controller = new();
+        let dir = common::temp_dir();
+        let path = dir.join("projects").join("app");
+        std::fs::create_dir_all(&path).unwrap();
+        init(&path, "initial");
+        let project = controller.add(&path).unwrap();
+        assert!(project.identity.is_some());
+        assert_eq!(controller.locate(&project.id).unwrap(), Location::Found);
+
+        let moved = dir.join("archive").join("app");
+        std::fs::create_dir_all(moved.parent().unwrap()).unwrap();
+        std::fs::rename(&path, &moved).unwrap();
+        let other = dir.join("other");
+        std::fs::create_dir_all(&other).unwrap();
+        init(&other, "other");
+
+        assert_eq!(
+            controller.locate(&project.id).unwrap(),
+            Location::Moved {
+                candidates: vec![moved.clone()]
+            }
+        );
+        assert!(matches!(
+            controller.relink(&project.id, &other).await,
+            Err(RelinkError::NotTheSameRepository)
+        ));
+
+        let project = controller.relink(&project.id, &moved).await.unwrap();
+        assert_eq!(project.path, moved);
+        assert_eq!(controller.get(&project.id).unwrap().path, moved);
+        assert_eq!(controller.locate(&project.id).unwrap(), Location::Found);
+    }
+
+    #[tokio::test]
+    async fn shallow_clone_by_remote() {
+        let controller = new();
+        let dir = common::temp_dir();
+        let path = dir.join("app");
+        std::fs::create_dir_all(&path).unwrap();
+        init(&path, "initial");
+        git2::Repository::open(&path)
+            .unwrap()
+            .remote("origin", "https://example.com/app.git")
+            .unwrap();
+        let project = controller.add(&path).unwrap();
+        std::fs::remove_dir_all(&path).unwrap();
+
+        // a shallow clone starts wherever it was cut, which isn't the root of the history
+        let clone = dir.join("clone");
+        std::fs::create_dir_all(&clone).unwrap();
+        init(&clone, "cut");
+        let repository = git2::Repository::open(&clone).unwrap();
+        repository
+            .remote("origin", "https://example.com/app")
+            .unwrap();
+        let head = repository.head().unwrap().target().unwrap();
+        std::fs::write(repository.path().join("shallow"), format!("{head}\n")).unwrap();
+        assert!(repository.is_shallow());
+
+        let project = controller.relink(&project.id, &clone).await.unwrap();
+        assert_eq!(project.path, clone);
+        assert_eq!(project.identity.unwrap().root_commit, None);
+    }
+}
+
+mod settings {
+    use gitbutler_core::{
+        projects::{
+            CommitMessageProfile, CommitMessageStyle, DiffOptions, PromptKind, PromptTemplateError,
+            PromptTemplates, SettingsUpdate, SettingsValidationError, UpdateError,
+            SETTINGS_VERSION,
+        },
+        settings::{AiProvider, AppSettingsValidationError, HttpHeader},
+    };
+
+    use super::*;
+
+    #[test]
+    fn partial_update() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+        assert_eq!(project.settings_version, SETTINGS_VERSION);
+
+        let updated = controller
+            .update_settings(
+                &project.id,
+                &SettingsUpdate {
+                    detect_copies: Some(true),
+                    diff_options: Some(DiffOptions {
+                        context_lines: Some(5),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.detect_copies, Some(true));
+        assert_eq!(updated.diff_options.context_lines, Some(5));
+        assert_eq!(*updated.ok_with_force_push, *project.ok_with_force_push);
+
+        let updated = controller
+            .update_settings(
+                &project.id,
+                &SettingsUpdate {
+                    diff_options: Some(DiffOptions {
+                        ignore_whitespace: Some(true),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.detect_copies, Some(true));
+        assert_eq!(updated.diff_options.context_lines, Some(5));
+        assert_eq!(updated.diff_options.ignore_whitespace, Some(true));
+        let stored = controller.get(&project.id).unwrap();
+        assert_eq!(stored.detect_copies, Some(true));
+        assert_eq!(stored.diff_options, updated.diff_options);
+    }
+
+    #[test]
+    fn invalid_update_applies_nothing() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+
+        assert!(matches!(
+            controller.update_settings(
+                &project.id,
+                &SettingsUpdate {
+                    detect_copies: Some(true),
+                    diff_options: Some(DiffOptions {
+                        context_lines: Some(u32::MAX),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ),
+            Err(UpdateError::Settings(
+                SettingsValidationError::ContextLines(u32::MAX)
+            ))
+        ));
+        let stored = controller.get(&project.id).unwrap();
+        assert_eq!(stored.detect_copies, project.detect_copies);
+        assert_eq!(stored.diff_options, project.diff_options);
+    }
+
+    #[test]
+    fn concurrent_updates_through_separate_controllers() {
+        let data_dir = paths::data_dir();
+        let repository = common::TestProject::default();
+        let project = Controller::from(&data_dir).add(repository.path()).unwrap();
+
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let controller = Controller::from(&data_dir);
+                scope.spawn(move || {
+                    controller
+                        .update_settings(
+                            &project.id,
+                            &SettingsUpdate {
+                                unmanaged_paths: Some(vec![format!("vendor-{i}")]),
+                                ..Default::default()
+                            },
+                        )
+                        .unwrap();
+                    controller
+                        .add(common::TestProject::default().path())
+                        .unwrap();
+                });
+            }
+        });
+
+        let projects = Controller::from(&data_dir).list().unwrap();
+        assert_eq!(projects.len(), 9);
+    }
+
+    #[test]
+    fn keeps_unknown_fields() {
+        let data_dir = paths::data_dir();
+        let controller = Controller::from(&data_dir);
+        let repository = common::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+
+        // as a newer version would have written it
+        let file = data_dir.join("projects.json");
+        let mut projects: Vec<serde_json::Value> =
+            serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        projects[0]["from_the_future"] = serde_json::json!({ "enabled": true });
+        std::fs::write(&file, serde_json::to_string(&projects).unwrap()).unwrap();
+
+        controller
+            .update_settings(
+                &project.id,
+                &SettingsUpdate {
+                    detect_copies: Some(true),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let projects: Vec<serde_json::Value> =
+            serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(
+            projects[0]["from_the_future"],
+            serde_json::json!({ "enabled": true })
+        );
+        assert_eq!(projects[0]["detect_copies"], serde_json::json!(true));
+    }
+
+    #[test]
+    fn ai_provider() {
+        gitbutler_core::secret::use_memory();
+        let data_dir = paths::data_dir();
+        let controller = Controller::from(&data_dir);
+        let repository = common::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+        assert!(project.ai_provider.is_none());
+
+        assert!(matches!(
+            controller.set_ai_provider(
+                &project.id,
+                Some(&AiProvider::OpenAiCompatible {
+                    base_url: "https://proxy.example.com/v1".to_string(),
+                    model: "gpt-4".to_string(),
+                    headers: vec![HttpHeader {
+                        name: "api key".to_string(),
+                        value: "secret".to_string(),
+                    }],
+                }),
+            ),
+            Err(UpdateError::Settings(SettingsValidationError::AiProvider(
+                AppSettingsValidationError::Header(_)
+            )))
+        ));
+        assert!(controller.get(&project.id).unwrap().ai_provider.is_none());
+
+        let updated = controller
+            .set_ai_provider(
+                &project.id,
+                Some(&AiProvider::OpenAiCompatible {
+                    base_url: " https://proxy.example.com/v1 ".to_string(),
+                    model: "gpt-4".to_string(),
+                    headers: vec![HttpHeader {
+                        name: "api-key".to_string(),
+                        value: "secret".to_string(),
+                    }],
+                }),
+            )
+            .unwrap();
+        assert_eq!(
+            updated.ai_provider,
+            Some(AiProvider::OpenAiCompatible {
+                base_url: "https://proxy.example.com/v1".to_string(),
+                model: "gpt-4".to_string(),
+                headers: vec![HttpHeader {
+                    name: "api-key".to_string(),
+                    value: "secret".to_string(),
+                }],
+            })
+        );
+        assert_eq!(
+            controller.get(&project.id).unwrap().ai_provider,
+            updated.ai_provider
+        );
+        // the headers are credentials, kept out of the file the projects are in
+        let file = std::fs::read_to_string(data_dir.join("projects.json")).unwrap();
+        assert!(file.contains("https://proxy.example.com/v1"));
+        assert!(!file.contains("secret"));
+
+        controller.set_ai_provider(&project.id, None).unwrap();
+        assert!(controller.get(&project.id).unwrap().ai_provider.is_none());
+    }
+
+    #[test]
+    fn commit_message_profiles() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+        assert_eq!(
+            project.commit_message_profiles(),
+            CommitMessageProfile::builtin()
+        );
+
+        let profile = |name: &str| CommitMessageProfile {
+            name: name.to_string(),
+            style: CommitMessageStyle::Conventional,
+            instructions: " Mention the ticket ".to_string(),
+        };
+        assert!(matches!(
+            controller.update_settings(
+                &project.id,
+                &SettingsUpdate {
+                    commit_message_profiles: Some(vec![profile("tickets"), profile(" tickets")]),
+                    ..Default::default()
+                },
+            ),
+            Err(UpdateError::Settings(
+                SettingsValidationError::DuplicateProfile(name)
+            )) if name == "tickets"
+        ));
+
+        controller
+            .update_settings(
+                &project.id,
+                &SettingsUpdate {
+                    commit_message_profiles: Some(vec![profile(" tickets ")]),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let stored = controller.get(&project.id).unwrap();
+        assert_eq!(
+            stored.commit_message_profile("tickets"),
+            Some(CommitMessageProfile {
+                name: "tickets".to_string(),
+                style: CommitMessageStyle::Conventional,
+                instructions: "Mention the ticket".to_string(),
+            })
+        );
+        assert_eq!(stored.commit_message_profiles().len(), 4);
+    }
+
+    #[test]
+    fn prompt_templates() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+        assert_eq!(project.prompt_templates, PromptTemplates::default());
+
+        assert!(matches!(
+            controller.update_settings(
+                &project.id,
+                &SettingsUpdate {
+                    prompt_templates: Some(PromptTemplates {
+                        commit_message: Some("{{diff}} for {{ticket}}".to_string()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ),
+            Err(UpdateError::Settings(SettingsValidationError::PromptTemplate {
+                kind: PromptKind::CommitMessage,
+                source: PromptTemplateError::UnknownVariable(name),
+            })) if name == "ticket"
+        ));
+
+        controller
+            .update_settings(
+                &project.id,
+                &SettingsUpdate {
+                    prompt_templates: Some(PromptTemplates {
+                        commit_message: Some(" Follow our conventions: {{diff}} ".to_string()),
+                        pull_request: Some("{{template}} {{diff}}".to_string()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let stored = controller.get(&project.id).unwrap();
+        assert_eq!(
+            stored.prompt_templates.commit_message.as_deref(),
+            Some("Follow our conventions: {{diff}}")
+        );
+
+        // templates left out stay as they are, empty ones are unset
+        let updated = controller
+            .update_settings(
+                &project.id,
+                &SettingsUpdate {
+                    prompt_templates: Some(PromptTemplates {
+                        commit_message: Some(String::new()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.prompt_templates.commit_message, None);
+        assert_eq!(
+            updated.prompt_templates.pull_request.as_deref(),
+            Some("{{template}} {{diff}}")
+        );
+    }
+}
+
+mod presets {
+    use gitbutler_core::{
+        git,
+        projects::{Preset, PresetError},
+    };
+
+    use super::*;
+
+    #[test]
+    fn trunk_based() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+
+        let (configuration, _) = controller
+            .apply_preset(&project.id, Preset::TrunkBased)
+            .unwrap();
+        assert_eq!(
+            configuration.target,
+            git::RemoteRefname::new("origin", "master")
+        );
+        assert_eq!(configuration.push_remote, "origin");
+
+        let project = controller.get(&project.id).unwrap();
+        assert!(!*project.ok_with_force_push);
+        let config = git2::Repository::open(repository.path())
+            .unwrap()
+            .config()
+            .unwrap();
+        assert_eq!(config.get_string("gitbutler.pushRemote").unwrap(), "origin");
+        // whether commits are signed is up to whoever commits
+        assert_eq!(
+            config.get_bool("gitbutler.signCommits").unwrap_err().code(),
+            git2::ErrorCode::NotFound
+        );
+    }
+
+    #[test]
+    fn release_branch() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let release: git::LocalRefname = "refs/heads/release/1.0".parse().unwrap();
+        repository.checkout(&release);
+        std::fs::write(repository.path().join("file.txt"), "released").unwrap();
+        repository.commit_all("release");
+        repository.push_branch(&release);
+        repository.checkout(&"refs/heads/master".parse().unwrap());
+        let project = controller.add(repository.path()).unwrap();
+
+        let (configuration, previous) = controller
+            .apply_preset(&project.id, Preset::ReleaseBranch)
+            .unwrap();
+        assert_eq!(
+            configuration.target,
+            git::RemoteRefname::new("origin", "release/1.0")
+        );
+        assert_eq!(configuration.push_remote, "origin");
+        assert!(!*controller.get(&project.id).unwrap().ok_with_force_push);
+        let config = || {
+            git2::Repository::open(repository.path())
+                .unwrap()
+                .config()
+                .unwrap()
+        };
+        assert!(config().get_bool("gitbutler.signCommits").unwrap());
+
+        // restoring the setup undoes all of it
+        controller.restore_setup(&project.id, &previous).unwrap();
+        assert!(*controller.get(&project.id).unwrap().ok_with_force_push);
+        assert_eq!(
+            config()
+                .get_bool("gitbutler.signCommits")
+                .unwrap_err()
+                .code(),
+            git2::ErrorCode::NotFound
+        );
+        assert_eq!(
+            config()
+                .get_string("gitbutler.pushRemote")
+                .unwrap_err()
+                .code(),
+            git2::ErrorCode::NotFound
+        );
+    }
+
+    #[test]
+    fn fork_workflow_without_upstream() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+
+        assert!(matches!(
+            controller.apply_preset(&project.id, Preset::ForkWorkflow),
+            Err(PresetError::RemoteNotFound(remote)) if remote == "upstream"
+        ));
+        // nothing was changed
+        assert!(*controller.get(&project.id).unwrap().ok_with_force_push);
+    }
+}
+
+mod clone {
+    use gitbutler_core::{
+        git,
+        project_repository::FetchProgress,
+        projects::{CloneError, CloneOptions, ProjectId},
+    };
+
+    use super::*;
+
+    #[test]
+    fn success() {
+        let data_dir = paths::data_dir();
+        let controller = Controller::from(&data_dir);
+        let credentials = git::credentials::Helper::from(&data_dir);
+        let repository = common::TestProject::default();
+        let path = common::temp_dir().join("clone");
+
+        let id = ProjectId::generate();
+        let project = controller
+            .clone_repository(
+                id,
+                repository.path().to_str().unwrap(),
+                &path,
+                &CloneOptions::default(),
+                &credentials,
+                &FetchProgress::default(),
+            )
+            .unwrap();
+        assert_eq!(project.id, id);
+        assert_eq!(project.path, path);
+        assert!(controller.get(&id).is_ok());
+
+        let cloned = git2::Repository::open(&path).unwrap();
+        assert_eq!(cloned.head().unwrap().shorthand(), Some("master"));
+        assert!(cloned.find_remote("origin").is_ok());
+        assert_eq!(
+            cloned
+                .config()
+                .unwrap()
+                .get_string("branch.master.remote")
+                .unwrap(),
+            "origin"
+        );
+    }
+
+    #[test]
+    fn checks_out_remote_head() {
+        let data_dir = paths::data_dir();
+        let controller = Controller::from(&data_dir);
+        let credentials = git::credentials::Helper::from(&data_dir);
+        let repository = common::TestProject::default();
+        let source = git2::Repository::open(repository.path()).unwrap();
+        let head = source.head().unwrap().peel_to_commit().unwrap();
+        source.branch("develop", &head, false).unwrap();
+        source.set_head("refs/heads/develop").unwrap();
+        let path = common::temp_dir().join("clone");
+
+        controller
+            .clone_repository(
+                ProjectId::generate(),
+                repository.path().to_str().unwrap(),
+                &path,
+                &CloneOptions::default(),
+                &credentials,
+                &FetchProgress::default(),
+            )
+            .unwrap();
+
+        let cloned = git2::Repository::open(&path).unwrap();
+        assert_eq!(cloned.head().unwrap().shorthand(), Some("develop"));
+    }
+
+    #[test]
+    fn partial() {
+        common::enable_extensions();
+        let data_dir = paths::data_dir();
+        let controller = Controller::from(&data_dir);
+        let credentials = git::credentials::Helper::from(&data_dir);
+        let repository = common::TestProject::default();
+        std::fs::create_dir_all(repository.path().join("dir")).unwrap();
+        std::fs::write(repository.path().join("dir/file.txt"), "content").unwrap();
+        repository.commit_all("with a file");
+        git2::Repository::open(repository.path())
+            .unwrap()
+            .config()
+            .unwrap()
+            .set_bool("uploadpack.allowFilter", true)
+            .unwrap();
+        let path = common::temp_dir().join("clone");
+
+        controller
+            .clone_repository(
+                ProjectId::generate(),
+                &format!("file://{}", repository.path().display()),
+                &path,
+                &CloneOptions {
+                    filter: Some("blob:none".to_string()),
+                    ..Default::default()
+                },
+                &credentials,
+                &FetchProgress::default(),
+            )
+            .unwrap();
+
+        let cloned = git2::Repository::open(&path).unwrap();
+        let config = cloned.config().unwrap();
+        assert_eq!(
+            config.get_string("extensions.partialClone").unwrap(),
+            "origin"
+        );
+        assert_eq!(
+            config
+                .get_string("remote.origin.partialclonefilter")
+                .unwrap(),
+            "blob:none"
+        );
+        assert_eq!(cloned.head().unwrap().shorthand(), Some("master"));
+        // the blobs of what's checked out were fetched for it
+        assert_eq!(
+            std::fs::read_to_string(path.join("dir/file.txt")).unwrap(),
+            "content"
+        );
+        assert!(cloned.statuses(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_branch_leaves_nothing() {
+        let data_dir = paths::data_dir();
+        let controller = Controller::from(&data_dir);
+        let credentials = git::credentials::Helper::from(&data_dir);
+        let repository = common::TestProject::default();
+        let path = common::temp_dir().join("clone");
+
+        assert!(matches!(
+            controller.clone_repository(
+                ProjectId::generate(),
+                repository.path().to_str().unwrap(),
+                &path,
+                &CloneOptions {
+                    branch: Some("missing".to_string()),
+                    ..Default::default()
+                },
+                &credentials,
+                &FetchProgress::default(),
+            ),
+            Err(CloneError::BranchNotFound(branch)) if branch == "missing"
+        ));
+        assert!(!path.exists());
+        assert!(controller.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn not_empty() {
+        let data_dir = paths::data_dir();
+        let controller = Controller::from(&data_dir);
+        let credentials = git::credentials::Helper::from(&data_dir);
+        let repository = common::TestProject::default();
+        let path = common::temp_dir();
+        std::fs::write(path.join("file"), "content").unwrap();
+
+        assert!(matches!(
+            controller.clone_repository(
+                ProjectId::generate(),
+                repository.path().to_str().unwrap(),
+                &path,
+                &CloneOptions::default(),
+                &credentials,
+                &FetchProgress::default(),
+            ),
+            Err(CloneError::PathNotEmpty)
+        ));
+        assert!(path.join("file").exists());
+    }
+}
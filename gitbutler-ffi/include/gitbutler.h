@@ -0,0 +1,41 @@
+/*
+ * C interface of the GitButler engine.
+ *
+ * Requests and responses are JSON. Every response is either {"ok": <result>} or
+ * {"error": {"code": "errors...", "message": "..."}}, and must be freed with
+ * gitbutler_string_free. No function returns null except gitbutler_engine_new.
+ */
+
+#ifndef GITBUTLER_H
+#define GITBUTLER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct gitbutler_engine gitbutler_engine;
+
+/* Opens the engine on the data directory of the app, or returns null if it can't. */
+gitbutler_engine *gitbutler_engine_new(const char *data_dir);
+void gitbutler_engine_free(gitbutler_engine *engine);
+
+/* {"path": ...} -> the project, added if it's not a project yet. */
+char *gitbutler_open_project(const gitbutler_engine *engine, const char *request);
+
+/* {"projectId": ...} -> the virtual branches. */
+char *gitbutler_list_branches(const gitbutler_engine *engine, const char *request);
+
+/* {"projectId": ..., "branchId": ..., "hunk": "path:start-end"} -> null. */
+char *gitbutler_move_hunk(const gitbutler_engine *engine, const char *request);
+
+/* {"projectId": ..., "branchId": ..., "message": ..., "ownership"?: ..., "runHooks"?: ..., "noVerify"?: ...}
+ * -> the id of the commit. */
+char *gitbutler_commit(const gitbutler_engine *engine, const char *request);
+
+void gitbutler_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* GITBUTLER_H */
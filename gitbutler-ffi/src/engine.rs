@@ -0,0 +1,171 @@
+use std::path;
+
+use gitbutler_core::{
+    error::{Code, Error},
+    git, keys,
+    projects::{self, Project, ProjectId},
+    users,
+    virtual_branches::{
+        self,
+        branch::{BranchId, BranchUpdateRequest, FileOwnership, Ownership},
+        VirtualBranch,
+    },
+};
+use serde::Deserialize;
+
+/// The engine of every project in one data directory.
+pub struct Engine {
+    runtime: tokio::runtime::Runtime,
+    projects: projects::Controller,
+    vbranches: virtual_branches::Controller,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenProjectRequest {
+    path: path::PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListBranchesRequest {
+    project_id: ProjectId,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveHunkRequest {
+    project_id: ProjectId,
+    /// The branch to move the hunk to.
+    branch_id: BranchId,
+    /// `path:start-end`, as listed in the ownership of the branches.
+    hunk: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitRequest {
+    project_id: ProjectId,
+    branch_id: BranchId,
+    message: String,
+    /// Defaults to everything the branch owns.
+    ownership: Option<Ownership>,
+    run_hooks: Option<bool>,
+    /// Skip the hooks that would run, noting it in a trailer of the message.
+    no_verify: Option<bool>,
+}
+
+impl Engine {
+    pub fn new(data_dir: &path::Path) -> Result<Self, Error> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(|error| invalid(format!("failed to start runtime: {error}")))?;
+
+        let data_dir = data_dir.to_path_buf();
+        let projects = projects::Controller::from(&data_dir);
+        let vbranches = virtual_branches::Controller::new(
+            &data_dir,
+            &projects,
+            &users::Controller::from(&data_dir),
+            &keys::Controller::from(&data_dir),
+            &git::credentials::Helper::from(&data_dir),
+        );
+
+        Ok(Self {
+            runtime,
+            projects,
+            vbranches,
+        })
+    }
+
+    /// Returns the project at `path`, adding it if it's not a project yet. The paths are
+    /// compared once resolved, for a project to be found however its path is spelled.
+    pub fn open_project(&self, request: OpenProjectRequest) -> Result<Project, Error> {
+        match self.projects.add(&request.path) {
+            Ok(project) => Ok(project),
+            Err(projects::AddError::AlreadyExists) => {
+                let path = canonical(&request.path);
+                self.projects
+                    .list()?
+                    .into_iter()
+                    .find(|project| canonical(&project.path) == path)
+                    .ok_or(Error::Unknown)
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn list_branches(&self, request: ListBranchesRequest) -> Result<Vec<VirtualBranch>, Error> {
+        self.runtime
+            .block_on(self.vbranches.list_virtual_branches(&request.project_id))
+            .map_err(Into::into)
+    }
+
+    /// Moves a hunk to the branch, out of whichever branch owns it now.
+    pub fn move_hunk(&self, request: MoveHunkRequest) -> Result<(), Error> {
+        let hunk = request
+            .hunk
+            .parse::<FileOwnership>()
+            .map_err(|error| invalid(format!("invalid hunk: {error}")))?;
+        let mut ownership = self
+            .list_branches(ListBranchesRequest {
+                project_id: request.project_id,
+            })?
+            .into_iter()
+            .find(|branch| branch.id == request.branch_id)
+            .map(|branch| branch.ownership)
+            .ok_or_else(|| Error::UserError {
+                code: Code::Branches,
+                message: format!("branch {} not found", request.branch_id),
+            })?;
+        ownership.put(&hunk);
+
+        self.runtime
+            .block_on(self.vbranches.update_virtual_branch(
+                &request.project_id,
+                BranchUpdateRequest {
+                    id: request.branch_id,
+                    ownership: Some(ownership),
+                    ..Default::default()
+                },
+            ))
+            .map_err(Into::into)
+    }
+
+    pub fn commit(&self, request: CommitRequest) -> Result<git::Oid, Error> {
+        let run_hooks = request.run_hooks.unwrap_or(true);
+        if run_hooks && request.no_verify.unwrap_or(false) {
+            return self
+                .runtime
+                .block_on(self.vbranches.create_commit_no_verify(
+                    &request.project_id,
+                    &request.branch_id,
+                    &request.message,
+                    request.ownership.as_ref(),
+                ))
+                .map_err(Into::into);
+        }
+        self.runtime
+            .block_on(self.vbranches.create_commit(
+                &request.project_id,
+                &request.branch_id,
+                &request.message,
+                request.ownership.as_ref(),
+                run_hooks,
+            ))
+            .map_err(Into::into)
+    }
+}
+
+pub fn invalid(message: String) -> Error {
+    Error::UserError {
+        code: Code::Validation,
+        message,
+    }
+}
+
+// the path with its links and `..` resolved, or as it is if it can't be
+fn canonical(path: &path::Path) -> path::PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
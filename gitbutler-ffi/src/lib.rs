@@ -0,0 +1,166 @@
+//! # GitButler FFI
+//!
+//! A C ABI over [`gitbutler_core`], for editor plugins that embed the engine instead of
+//! talking to the app. `include/gitbutler.h` declares it.
+//!
+//! Requests and responses are JSON. Every response is either `{"ok": <result>}` or
+//! `{"error": {"code": "errors.…", "message": "…"}}`, owned by the caller and freed with
+//! [`gitbutler_string_free`].
+
+mod engine;
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    path, ptr,
+};
+
+use gitbutler_core::error::Error;
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use engine::Engine;
+
+/// Opens the engine on `data_dir`, the data directory of the app, or returns null if it
+/// can't.
+///
+/// # Safety
+///
+/// `data_dir` must be null or a valid nul-terminated string.
+#[allow(unsafe_code)]
+#[no_mangle]
+pub unsafe extern "C" fn gitbutler_engine_new(data_dir: *const c_char) -> *mut Engine {
+    if data_dir.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(data_dir) = CStr::from_ptr(data_dir).to_str() else {
+        return ptr::null_mut();
+    };
+    panic::catch_unwind(|| Engine::new(path::Path::new(data_dir)))
+        .ok()
+        .and_then(Result::ok)
+        .map_or(ptr::null_mut(), |engine| Box::into_raw(Box::new(engine)))
+}
+
+/// # Safety
+///
+/// `engine` must be null or returned by [`gitbutler_engine_new`], and not used afterwards.
+#[allow(unsafe_code)]
+#[no_mangle]
+pub unsafe extern "C" fn gitbutler_engine_free(engine: *mut Engine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Returns the project at `{"path": …}`, adding it if it's not a project yet.
+///
+/// # Safety
+///
+/// `engine` must be returned by [`gitbutler_engine_new`], and `request` must be null or a
+/// valid nul-terminated string.
+#[allow(unsafe_code)]
+#[no_mangle]
+pub unsafe extern "C" fn gitbutler_open_project(
+    engine: *const Engine,
+    request: *const c_char,
+) -> *mut c_char {
+    call(engine, request, Engine::open_project)
+}
+
+/// Lists the virtual branches of `{"projectId": …}`.
+///
+/// # Safety
+///
+/// Same as [`gitbutler_open_project`].
+#[allow(unsafe_code)]
+#[no_mangle]
+pub unsafe extern "C" fn gitbutler_list_branches(
+    engine: *const Engine,
+    request: *const c_char,
+) -> *mut c_char {
+    call(engine, request, Engine::list_branches)
+}
+
+/// Moves `{"projectId": …, "branchId": …, "hunk": "path:start-end"}` to the branch.
+///
+/// # Safety
+///
+/// Same as [`gitbutler_open_project`].
+#[allow(unsafe_code)]
+#[no_mangle]
+pub unsafe extern "C" fn gitbutler_move_hunk(
+    engine: *const Engine,
+    request: *const c_char,
+) -> *mut c_char {
+    call(engine, request, Engine::move_hunk)
+}
+
+/// Commits `{"projectId": …, "branchId": …, "message": …}`, and optionally `"ownership"`
+/// and `"runHooks"`, returning the id of the commit.
+///
+/// # Safety
+///
+/// Same as [`gitbutler_open_project`].
+#[allow(unsafe_code)]
+#[no_mangle]
+pub unsafe extern "C" fn gitbutler_commit(
+    engine: *const Engine,
+    request: *const c_char,
+) -> *mut c_char {
+    call(engine, request, Engine::commit)
+}
+
+/// # Safety
+///
+/// `string` must be null or returned by one of the functions above, and not used
+/// afterwards.
+#[allow(unsafe_code)]
+#[no_mangle]
+pub unsafe extern "C" fn gitbutler_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Parses the request, runs `f` and returns its response. A panic in the engine is
+/// returned as an error rather than unwinding into the caller.
+#[allow(unsafe_code)]
+unsafe fn call<R: DeserializeOwned, T: Serialize>(
+    engine: *const Engine,
+    request: *const c_char,
+    f: impl FnOnce(&Engine, R) -> Result<T, Error>,
+) -> *mut c_char {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let engine = engine
+            .as_ref()
+            .ok_or_else(|| engine::invalid("engine is null".to_string()))?;
+        if request.is_null() {
+            return Err(engine::invalid("request is null".to_string()));
+        }
+        let request = CStr::from_ptr(request)
+            .to_str()
+            .map_err(|error| engine::invalid(format!("request is not utf-8: {error}")))
+            .and_then(|request| {
+                serde_json::from_str(request)
+                    .map_err(|error| engine::invalid(format!("invalid request: {error}")))
+            })?;
+        f(engine, request)
+    }))
+    .unwrap_or(Err(Error::Unknown));
+
+    let response = match result {
+        Ok(value) => serde_json::to_string(&Response::Ok(value)),
+        Err(error) => serde_json::to_string(&Response::<T>::Error(error)),
+    }
+    .or_else(|_| serde_json::to_string(&Response::<T>::Error(Error::Unknown)))
+    .unwrap_or_default();
+    // json escapes nul, so this can't fail.
+    CString::new(response).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+enum Response<T> {
+    Ok(T),
+    Error(Error),
+}
@@ -0,0 +1,108 @@
+#![allow(unsafe_code)]
+
+use std::ffi::{c_char, CStr, CString};
+
+use gitbutler_ffi::{
+    gitbutler_engine_free, gitbutler_engine_new, gitbutler_list_branches, gitbutler_open_project,
+    gitbutler_string_free,
+};
+
+fn init_repository(path: &std::path::Path) {
+    let repository = git2::Repository::init(path).unwrap();
+    let signature = git2::Signature::now("test", "test@email.com").unwrap();
+    let tree = {
+        let mut index = repository.index().unwrap();
+        let oid = index.write_tree().unwrap();
+        repository.find_tree(oid).unwrap()
+    };
+    repository
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "initial commit",
+            &tree,
+            &[],
+        )
+        .unwrap();
+}
+
+/// Takes ownership of a response and parses it.
+unsafe fn response(string: *mut c_char) -> serde_json::Value {
+    assert!(!string.is_null());
+    let value = serde_json::from_str(CStr::from_ptr(string).to_str().unwrap()).unwrap();
+    gitbutler_string_free(string);
+    value
+}
+
+#[test]
+fn open_project_then_list_branches() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let repository_dir = tempfile::tempdir().unwrap();
+    init_repository(repository_dir.path());
+
+    unsafe {
+        let data_dir = CString::new(data_dir.path().to_str().unwrap()).unwrap();
+        let engine = gitbutler_engine_new(data_dir.as_ptr());
+        assert!(!engine.is_null());
+
+        let request =
+            CString::new(serde_json::json!({ "path": repository_dir.path() }).to_string()).unwrap();
+        let first = response(gitbutler_open_project(engine, request.as_ptr()));
+        let second = response(gitbutler_open_project(engine, request.as_ptr()));
+        let id = &first["ok"]["id"];
+        assert!(id.is_string());
+        assert_eq!(&second["ok"]["id"], id);
+
+        // no default target is set yet
+        let request = CString::new(serde_json::json!({ "projectId": id }).to_string()).unwrap();
+        let branches = response(gitbutler_list_branches(engine, request.as_ptr()));
+        assert!(branches["error"]["code"].is_string());
+
+        gitbutler_engine_free(engine);
+    }
+}
+
+#[test]
+fn open_project_spelled_differently() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let repository_dir = tempfile::tempdir().unwrap();
+    init_repository(repository_dir.path());
+    std::fs::create_dir(repository_dir.path().join("dir")).unwrap();
+
+    unsafe {
+        let data_dir = CString::new(data_dir.path().to_str().unwrap()).unwrap();
+        let engine = gitbutler_engine_new(data_dir.as_ptr());
+
+        let request =
+            CString::new(serde_json::json!({ "path": repository_dir.path() }).to_string()).unwrap();
+        let first = response(gitbutler_open_project(engine, request.as_ptr()));
+        let request = CString::new(
+            serde_json::json!({ "path": repository_dir.path().join("dir").join("..") }).to_string(),
+        )
+        .unwrap();
+        let second = response(gitbutler_open_project(engine, request.as_ptr()));
+        assert!(first["ok"]["id"].is_string());
+        assert_eq!(second["ok"]["id"], first["ok"]["id"]);
+
+        gitbutler_engine_free(engine);
+    }
+}
+
+#[test]
+fn invalid_request() {
+    let data_dir = tempfile::tempdir().unwrap();
+    unsafe {
+        let data_dir = CString::new(data_dir.path().to_str().unwrap()).unwrap();
+        let engine = gitbutler_engine_new(data_dir.as_ptr());
+
+        let request = CString::new("not json").unwrap();
+        let result = response(gitbutler_open_project(engine, request.as_ptr()));
+        assert_eq!(result["error"]["code"], "errors.validation");
+
+        let result = response(gitbutler_open_project(engine, std::ptr::null()));
+        assert_eq!(result["error"]["code"], "errors.validation");
+
+        gitbutler_engine_free(engine);
+    }
+}
@@ -3,6 +3,7 @@
 //!
 //! The entry point for this module is the [`Repository`] struct.
 
+mod extensions;
 mod repository;
 mod thread_resource;
 
@@ -10,6 +11,7 @@ mod thread_resource;
 pub use self::thread_resource::tokio;
 
 pub use self::{
+    extensions::{enable_extensions, EXTENSIONS},
     repository::Repository,
     thread_resource::{ThreadedResource, ThreadedResourceHandle},
 };
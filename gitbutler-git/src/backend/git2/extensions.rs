@@ -0,0 +1,20 @@
+/// Repository format extensions that libgit2 does not know about, but that are
+/// safe for us to ignore.
+///
+/// `partialclone` marks repositories cloned with a `--filter`. libgit2 has no
+/// support for lazily fetching missing objects, so callers must make sure the
+/// objects they need are present (e.g. by fetching them with the Git CLI).
+pub const EXTENSIONS: &[&str] = &["partialclone"];
+
+/// Allows libgit2 to open repositories that use any of [`EXTENSIONS`].
+///
+/// This changes global libgit2 state and must be called once, early at
+/// startup, before any repository is opened.
+pub fn enable_extensions() -> Result<(), git2::Error> {
+    // SAFETY: the extension list is copied by libgit2, and this is called
+    // before any other thread touches libgit2.
+    #[allow(unsafe_code)]
+    unsafe {
+        git2::opts::set_extensions(EXTENSIONS)
+    }
+}
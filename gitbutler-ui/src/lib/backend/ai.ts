@@ -0,0 +1,271 @@
+import { getAppSettings, type AiProvider } from '$lib/backend/appSettings';
+import { invoke, listenNow } from '$lib/backend/ipc';
+import type { User, getCloudApiClient } from '$lib/backend/cloud';
+import type { CommitMessageProfile, Project } from '$lib/backend/projects';
+import type { Hunk, LocalFile } from '$lib/vbranches/types';
+import { nanoid } from 'nanoid';
+
+type Cloud = ReturnType<typeof getCloudApiClient>;
+
+// GitButler's servers are sent the start of diffs, the other models are sent what fits in the
+// token budget of the app settings
+const CLOUD_MAX_DIFF_LENGTH = 5000;
+
+/**
+ * `files` as a unified diff, with the headers of the files that say which file every hunk is
+ * of, and only the hunks that `includes`.
+ */
+export function toDiff(
+	files: LocalFile[],
+	includes: (file: LocalFile, hunk: Hunk) => boolean = () => true
+) {
+	return files
+		.map((file) => {
+			const hunks = file.hunks.filter((hunk) => includes(file, hunk));
+			if (hunks.length == 0) return '';
+			const header = `--- a/${file.path}\n+++ b/${file.path}\n`;
+			return header + hunks.map((hunk) => hunk.diff.replace(/\n?$/, '\n')).join('');
+		})
+		.join('');
+}
+
+/**
+ * What the prompt templates of a project are filled in with, on top of the diff.
+ */
+export type PromptContext = {
+	/**
+	 * The paths of the files that changed.
+	 */
+	files?: string[];
+	branchName?: string;
+	/**
+	 * The messages of the recent commits of the branch.
+	 */
+	commits?: string[];
+};
+
+export type PromptKind = 'commitMessage' | 'branchName' | 'pullRequest';
+
+export type PromptTemplate = {
+	kind: PromptKind;
+	/**
+	 * The template of the project, the default one if it has none.
+	 */
+	template: string;
+	defaultTemplate: string;
+	/**
+	 * What `{{name}}` can name in the template.
+	 */
+	variables: string[];
+};
+
+/**
+ * What generates the `feature` of the project `projectId`, the one of the feature in the
+ * app settings if it has one, else the provider of the project, else the one of the app
+ * settings.
+ */
+export async function getAiProvider(
+	projectId?: string,
+	feature?: PromptKind
+): Promise<AiProvider> {
+	const settings = await getAppSettings();
+	const featureProvider = feature && settings.aiFeatureProviders.value[feature];
+	if (featureProvider) return featureProvider;
+	if (projectId) {
+		const project = await invoke<Project>('get_project', { id: projectId });
+		if (project.ai_provider) return project.ai_provider;
+	}
+	return settings.aiProvider.value;
+}
+
+/**
+ * Whether commit messages and branch names can be generated, by a model configured in the
+ * settings or by GitButler's servers for a user who is logged in.
+ */
+export function canGenerate(provider: AiProvider | undefined, user: User | undefined) {
+	return (!!provider && provider.type != 'cloud') || !!user;
+}
+
+/**
+ * Makes a small request to `provider`, to find out whether it generates with the endpoint,
+ * model and headers it's configured with. Throws what went wrong if it doesn't.
+ */
+export async function checkAiProvider(provider: AiProvider) {
+	return await invoke<void>('check_ai_provider', { aiProvider: provider });
+}
+
+/**
+ * How a generation by a model other than GitButler's servers is streamed: `onText` is called
+ * with the reply so far as it's written, and aborting `signal` stops it there, with what's
+ * been written so far as the reply.
+ */
+export type StreamOptions = {
+	onText?: (text: string) => void;
+	signal?: AbortSignal;
+};
+
+// `generate` with the id of the stream of the project `projectId` the reply is sent to, if
+// it's streamed
+async function streamed<T>(
+	projectId: string | undefined,
+	{ onText, signal }: StreamOptions,
+	generate: (streamId: string | undefined) => Promise<T>
+): Promise<T> {
+	if (!projectId || (!onText && !signal)) return await generate(undefined);
+	const streamId = nanoid();
+	// the events are what was added to the reply, or all of it if it changed otherwise
+	let reply = '';
+	const unlisten = await listenNow<{ delta: string; replace: boolean }>(
+		`project://${projectId}/ai/reply/${streamId}`,
+		(event) => {
+			reply = event.payload.replace ? event.payload.delta : reply + event.payload.delta;
+			onText?.(reply);
+		}
+	);
+	const cancel = () => invoke<void>('cancel_generation', { streamId });
+	signal?.addEventListener('abort', cancel);
+	try {
+		return await generate(streamId);
+	} finally {
+		signal?.removeEventListener('abort', cancel);
+		unlisten();
+	}
+}
+
+export async function generateCommitMessage(
+	cloud: Cloud,
+	user: User | undefined,
+	params: {
+		projectId?: string;
+		diff: string;
+		uid?: string;
+		brief?: boolean;
+		emoji?: boolean;
+		/**
+		 * The name of the commit message profile to write it with, models other than
+		 * GitButler's servers only.
+		 */
+		profile?: string;
+		context?: PromptContext;
+	} & StreamOptions
+): Promise<string> {
+	const { projectId, profile, context, onText, signal, ...summarize } = params;
+	const provider = await getAiProvider(projectId, 'commitMessage');
+	if (provider.type != 'cloud') {
+		return await streamed(projectId, { onText, signal }, (streamId) =>
+			invoke<string>('generate_commit_message', {
+				projectId,
+				diff: params.diff,
+				brief: params.brief ?? false,
+				emoji: params.emoji ?? false,
+				profile,
+				context,
+				streamId
+			})
+		);
+	}
+	if (!user) throw 'Log in to generate commit messages';
+	const diff = await redactDiff(projectId, 'commitMessage', summarize.diff);
+	return (
+		await cloud.summarize.commit(user.access_token, {
+			...summarize,
+			diff: diff.slice(0, CLOUD_MAX_DIFF_LENGTH)
+		})
+	).message;
+}
+
+/**
+ * `diff` redacted like the diffs sent to the other models are, for it to be sent to GitButler's
+ * servers. It's redacted before it's cut short, for no secret to be cut in two and missed.
+ */
+async function redactDiff(projectId: string | undefined, purpose: PromptKind, diff: string) {
+	return await invoke<string>('redact_diff', { projectId, purpose, diff });
+}
+
+/**
+ * The commit message profiles of the project `projectId`, its own first, then the built-in ones
+ * it doesn't replace.
+ */
+export async function listCommitMessageProfiles(projectId: string) {
+	return await invoke<CommitMessageProfile[]>('list_commit_message_profiles', { id: projectId });
+}
+
+/**
+ * The prompt templates of the project `projectId`, with the default ones they replace.
+ */
+export async function listPromptTemplates(projectId: string) {
+	return await invoke<PromptTemplate[]>('list_prompt_templates', { projectId });
+}
+
+export type Redaction = {
+	kind: 'secret' | 'environment' | 'envFile' | 'path';
+	/**
+	 * The rule or the variable of what was redacted, if any.
+	 */
+	name?: string;
+	filePath?: string;
+	count: number;
+};
+
+export type RedactionLogEntry = {
+	timestampMs: number;
+	purpose: PromptKind;
+	redactions: Redaction[];
+};
+
+/**
+ * What was redacted from what was sent to models for the project `projectId`, the oldest first.
+ */
+export async function listRedactions(projectId: string) {
+	return await invoke<RedactionLogEntry[]>('list_redactions', { projectId });
+}
+
+/**
+ * A name for the branch of `diff`, none if there is nothing to generate it with.
+ */
+export async function generateBranchName(
+	cloud: Cloud,
+	user: User | undefined,
+	diff: string,
+	projectId?: string,
+	context?: PromptContext
+): Promise<string | undefined> {
+	const provider = await getAiProvider(projectId, 'branchName');
+	if (provider.type != 'cloud') {
+		return await invoke<string>('generate_branch_name', { projectId, diff, context });
+	}
+	if (!user) return;
+	const redacted = await redactDiff(projectId, 'branchName', diff);
+	const cloudDiff = redacted.slice(0, CLOUD_MAX_DIFF_LENGTH);
+	return (await cloud.summarize.branch(user.access_token, { diff: cloudDiff })).message;
+}
+
+/**
+ * Two or three names for the branch `branchId`, from its uncommitted changes. A model that is
+ * configured generates them if `useModel`, they are made up from the directories and words the
+ * changes touch the most otherwise, and when the model is GitButler's servers.
+ */
+export async function suggestBranchNames(projectId: string, branchId: string, useModel: boolean) {
+	return await invoke<string[]>('suggest_branch_names', { projectId, branchId, useModel });
+}
+
+export type PullRequestContent = {
+	title: string;
+	body: string;
+};
+
+/**
+ * The title and description of the pull request of the branch `branchId`, generated by the
+ * model of the project from the commits of the branch, the description following the pull
+ * request template. The reply streamed so far is the title on its first line, then the
+ * description.
+ */
+export async function generatePrContent(
+	projectId: string,
+	branchId: string,
+	stream: StreamOptions = {}
+) {
+	return await streamed(projectId, stream, (streamId) =>
+		invoke<PullRequestContent>('generate_pr_content', { projectId, branchId, streamId })
+	);
+}
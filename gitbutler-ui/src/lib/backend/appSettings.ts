@@ -0,0 +1,152 @@
+import { invoke } from '$lib/backend/ipc';
+import type { getCloudApiClient } from '$lib/backend/cloud';
+
+/**
+ * A setting, with when it was last changed on any installation.
+ */
+export type Synced<T> = {
+	value: T;
+	updatedAtMs: number;
+};
+
+export type ForgeKind = 'github' | 'gitlab' | 'bitbucket' | 'gitea';
+
+export type ForgeHost = {
+	host: string;
+	kind: ForgeKind;
+};
+
+export type LocalModelApi = 'ollama' | 'openAiCompatible';
+
+export type HttpHeader = {
+	name: string;
+	value: string;
+};
+
+/**
+ * What generates commit messages and branch names, GitButler's servers, a model served
+ * locally, like with Ollama or llama.cpp, for the diffs not to leave the machine, an API like
+ * OpenAI's, or Anthropic's.
+ */
+export type AiProvider =
+	| { type: 'cloud' }
+	| { type: 'local'; api: LocalModelApi; endpoint: string; model: string }
+	| {
+			type: 'openAiCompatible';
+			/**
+			 * What `/chat/completions` is appended to, like `https://api.openai.com/v1`.
+			 */
+			baseUrl: string;
+			model: string;
+			/**
+			 * Sent with every request, they aren't synced between installations.
+			 */
+			headers: HttpHeader[];
+	  }
+	| {
+			type: 'anthropic';
+			model: string;
+			/**
+			 * It isn't synced between installations.
+			 */
+			apiKey: string;
+	  };
+
+/**
+ * The providers that generate some things instead of the one of the app settings.
+ */
+export type AiFeatureProviders = {
+	commitMessage?: AiProvider;
+	branchName?: AiProvider;
+	pullRequest?: AiProvider;
+};
+
+export type AppSettings = {
+	version: number;
+	fetchIntervalSecs: Synced<number>;
+	signCommits: Synced<boolean>;
+	forgeHosts: Synced<ForgeHost[]>;
+	aiProvider: Synced<AiProvider>;
+	/**
+	 * How many tokens prompts are cut down to, for them to fit in the context of the model.
+	 */
+	aiTokenBudget: Synced<number>;
+	aiFeatureProviders: Synced<AiFeatureProviders>;
+};
+
+export type AppSettingsUpdate = {
+	fetch_interval_secs?: number;
+	sign_commits?: boolean;
+	forge_hosts?: ForgeHost[];
+	ai_provider?: AiProvider;
+	ai_token_budget?: number;
+	ai_feature_providers?: AiFeatureProviders;
+};
+
+export type SyncRepository = {
+	url: string;
+};
+
+export type SyncResult = {
+	settings: AppSettings;
+	/**
+	 * Whether the merged settings were pushed to the sync repository.
+	 */
+	pushed: boolean;
+};
+
+export async function getAppSettings(): Promise<AppSettings> {
+	return await invoke<AppSettings>('get_app_settings');
+}
+
+export async function updateAppSettings(update: AppSettingsUpdate): Promise<AppSettings> {
+	return await invoke<AppSettings>('update_app_settings', { update });
+}
+
+export async function getSyncRepository(): Promise<SyncRepository | null> {
+	return await invoke<SyncRepository | null>('get_settings_sync_repository');
+}
+
+/**
+ * Sets the git repository settings are synced through, or turns syncing off.
+ */
+export async function setSyncRepository(repository?: SyncRepository) {
+	await invoke<void>('set_settings_sync_repository', { repository });
+}
+
+/**
+ * Merges the settings of the sync repository with the ones of this installation, the
+ * setting changed last winning, and pushes the result back.
+ */
+export async function syncAppSettings(): Promise<SyncResult> {
+	return await invoke<SyncResult>('sync_app_settings');
+}
+
+export async function getSyncWithCloud(): Promise<boolean> {
+	return await invoke<boolean>('get_settings_sync_with_cloud');
+}
+
+/**
+ * Turns syncing the settings through the user's GitButler account on or off.
+ */
+export async function setSyncWithCloud(syncWithCloud: boolean) {
+	await invoke<void>('set_settings_sync_with_cloud', { syncWithCloud });
+}
+
+/**
+ * Like `syncAppSettings`, through the GitButler account of the user whose access token is
+ * `token`: the settings are got from and put back to GitButler's servers from here, and
+ * merged by the backend.
+ */
+export async function syncAppSettingsWithCloud(
+	cloud: ReturnType<typeof getCloudApiClient>,
+	token: string
+): Promise<SyncResult> {
+	const remote = await cloud.user.settings.get(token);
+	const result = await invoke<{ settings: AppSettings; upload: AppSettings | null }>(
+		'merge_cloud_app_settings',
+		{ remote }
+	);
+	if (result.upload) await cloud.user.settings.put(token, result.upload);
+	return { settings: result.settings, pushed: !!result.upload };
+}
@@ -0,0 +1,65 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type BranchState = {
+	name: string;
+	applied: boolean;
+	head: string;
+	upstreamHead?: string;
+};
+
+export type BranchChange = {
+	branchId: string;
+	/**
+	 * Missing for a branch the operation created.
+	 */
+	before?: BranchState;
+	/**
+	 * Missing for a branch the operation deleted.
+	 */
+	after?: BranchState;
+	/**
+	 * The commits the operation made on the branch, the most recent first.
+	 */
+	commits: string[];
+};
+
+export type AuditEntry = {
+	/**
+	 * When the operation started, once it was its turn.
+	 */
+	timestampMs: number;
+	durationMs: number;
+	/**
+	 * What the operation is, the name of its command, like `create_commit`.
+	 */
+	kind: string;
+	requestedBy: 'user' | 'background';
+	/**
+	 * The email of the user signed in to GitButler, if any.
+	 */
+	user?: string;
+	branches: BranchChange[];
+	/**
+	 * Where the base branch moved from and to, if it did.
+	 */
+	base?: { before?: string; after?: string };
+};
+
+export type AuditQuery = {
+	sinceMs?: number;
+	untilMs?: number;
+	branchId?: string;
+	kind?: string;
+	/**
+	 * Only the most recent entries that match, at most this many.
+	 */
+	limit?: number;
+};
+
+/**
+ * What GitButler did to the project, in the order the operations ran. Every operation that
+ * changes it is recorded, with who asked for it and the branches and commits it changed.
+ */
+export async function listAuditEntries(projectId: string, query?: AuditQuery) {
+	return await invoke<AuditEntry[]>('list_audit_entries', { projectId, query });
+}
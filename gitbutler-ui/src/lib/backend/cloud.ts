@@ -174,6 +174,25 @@ export function getCloudApiClient(
 					},
 					body: formData
 				}).then(parseResponseJSON);
+			},
+			settings: {
+				// the app settings synced through the account, null until some were put
+				get: (token: string): Promise<unknown | null> =>
+					fetch(getUrl(`user/app_settings.json`), {
+						method: 'GET',
+						headers: {
+							'X-Auth-Token': token
+						}
+					}).then((response) => (response.status === 404 ? null : parseResponseJSON(response))),
+				put: (token: string, settings: unknown): Promise<void> =>
+					fetch(getUrl(`user/app_settings.json`), {
+						method: 'PUT',
+						headers: {
+							'Content-Type': 'application/json',
+							'X-Auth-Token': token
+						},
+						body: JSON.stringify(settings)
+					}).then(parseResponseJSON)
 			}
 		},
 		summarize: {
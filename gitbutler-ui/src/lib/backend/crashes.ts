@@ -0,0 +1,46 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type CrashReport = {
+	id: string;
+	/**
+	 * Milliseconds since the epoch.
+	 */
+	createdAt: number;
+	version: string;
+	os: string;
+	arch: string;
+	thread?: string;
+	message: string;
+	/**
+	 * Where in the code it panicked.
+	 */
+	location?: string;
+	backtrace: string;
+};
+
+/**
+ * What a crash report that is sent keeps. Paths and the contents of strings, which can be those
+ * of files, are left out unless allowed.
+ */
+export type CrashReportAllow = {
+	paths: boolean;
+	contents: boolean;
+};
+
+/**
+ * The crash reports waiting for the user to send or dismiss them, as they would be sent.
+ */
+export function listCrashReports(allow?: CrashReportAllow) {
+	return invoke<CrashReport[]>('list_crash_reports', { allow });
+}
+
+/**
+ * Reports are never sent without the user agreeing to, for every crash.
+ */
+export function sendCrashReport(id: string, allow: CrashReportAllow) {
+	return invoke<void>('send_crash_report', { id, allow });
+}
+
+export function dismissCrashReport(id: string) {
+	return invoke<void>('dismiss_crash_report', { id });
+}
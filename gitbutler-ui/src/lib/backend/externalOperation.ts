@@ -0,0 +1,33 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type ExternalOperation = {
+	kind:
+		| 'merge'
+		| 'rebase'
+		| 'cherryPick'
+		| 'revert'
+		| 'bisect'
+		| 'applyMailbox'
+		| 'applyMailboxOrRebase';
+	/**
+	 * Whether it can be continued, a bisect can only be ended. What's left of an `am` or a
+	 * rebase that git can't tell apart can neither be continued nor aborted from GitButler.
+	 */
+	canContinue: boolean;
+	/**
+	 * The files with conflicts that are left to resolve before it can be continued.
+	 */
+	conflictedFiles: string[];
+};
+
+/**
+ * The merge, rebase or bisect started outside of GitButler the project is in the middle of.
+ * Changing the project is refused with `Code.ProjectExternalOperation` until it's finished.
+ */
+export async function getExternalOperation(projectId: string) {
+	return await invoke<ExternalOperation | null>('get_external_operation', { projectId });
+}
+
+export async function finishExternalOperation(projectId: string, action: 'continue' | 'abort') {
+	return await invoke<void>('finish_external_operation', { projectId, action });
+}
@@ -3,31 +3,62 @@ import { invoke as invokeTauri } from '@tauri-apps/api/tauri';
 import { writable } from 'svelte/store';
 import type { EventCallback, EventName } from '@tauri-apps/api/event';
 
+/**
+ * What the errors of commands are told apart by. Codes are stable across releases, they are
+ * only ever added.
+ */
 export enum Code {
 	Unknown = 'errors.unknown',
 	Validation = 'errors.validation',
 	Projects = 'errors.projects',
+	Branches = 'errors.branches',
 	ProjectsGitAuth = 'errors.projects.git.auth',
 	ProjectsGitRemote = 'errors.projects.git.remote',
 	ProjectHead = 'errors.projects.head',
-	ProjectConflict = 'errors.projects.conflict'
+	ProjectConflict = 'errors.projects.conflict',
+	ProjectShallow = 'errors.projects.shallow',
+	ProjectReadOnly = 'errors.projects.read_only',
+	ProjectLocked = 'errors.projects.locked',
+	PreCommitHook = 'errors.hook',
+	CommitMsgHook = 'errors.hooks.commit.msg',
+	PrePushHook = 'errors.hooks.push.pre',
+	SecretsFound = 'errors.secrets',
+	LargeFiles = 'errors.large_files',
+	ConflictMarkers = 'errors.conflict_markers',
+	ProjectExternalOperation = 'errors.projects.external_operation',
+	Cancelled = 'errors.cancelled',
+	LocalModel = 'errors.local_model',
+	Network = 'errors.network',
+	CorruptState = 'errors.corrupt_state',
+	Menu = 'errors.menu'
 }
 
 export class UserError extends Error {
 	code!: Code;
 	cause: Error | undefined;
+	/**
+	 * What the error is about, like the `projectId` and `branchId` it's about, for it not to
+	 * have to be made out of the message.
+	 */
+	context: Record<string, string>;
 
-	constructor(message: string, code: Code, cause: Error | undefined) {
+	constructor(
+		message: string,
+		code: Code,
+		cause: Error | undefined,
+		context: Record<string, string> = {}
+	) {
 		super(message);
 		this.cause = cause;
 		this.code = code;
+		this.context = context;
 	}
 
 	static fromError(error: any): UserError {
 		const cause = error instanceof Error ? error : undefined;
 		const code = error.code ?? Code.Unknown;
 		const message = error.message ?? error;
-		return new UserError(message, code, cause);
+		return new UserError(message, code, cause, error.context ?? {});
 	}
 }
 
@@ -92,3 +123,11 @@ export function listen<T>(event: EventName, handle: EventCallback<T>) {
 	const unlisten = listenTauri(event, handle);
 	return () => unlisten.then((unlistenFn) => unlistenFn());
 }
+
+/**
+ * Like `listen`, once `handle` is listening: for the events of a command that's invoked
+ * next, which are sent before `listen` would be.
+ */
+export async function listenNow<T>(event: EventName, handle: EventCallback<T>) {
+	return await listenTauri(event, handle);
+}
@@ -0,0 +1,47 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type QueuedOperation = {
+	id: number;
+	/**
+	 * What the operation is, the name of its command, like `fetch_from_target`.
+	 */
+	kind: string;
+	requestedBy: 'user' | 'background';
+	/**
+	 * Whether it's the one running, the others wait for it to finish.
+	 */
+	running: boolean;
+	/**
+	 * Whether it can be cancelled, only network operations can.
+	 */
+	cancellable: boolean;
+	/**
+	 * Since it was requested.
+	 */
+	elapsedMs: number;
+};
+
+/**
+ * The operations changing the project run one at a time, in the order they were requested.
+ * These are the one running and those waiting for their turn behind it.
+ */
+export async function listOperations(projectId: string) {
+	return await invoke<QueuedOperation[]>('list_operations', { projectId });
+}
+
+/**
+ * Cancels a fetch or a push, running or waiting for its turn. It fails with `Code.Cancelled`.
+ */
+export async function cancelOperation(projectId: string, operationId: number) {
+	return await invoke<void>('cancel_operation', { projectId, operationId });
+}
+
+/**
+ * What an operation waits for, like "waiting for fetch from target to finish", if it's
+ * queued behind another one.
+ */
+export function waitingFor(operations: QueuedOperation[]): string | undefined {
+	const running = operations.find((operation) => operation.running);
+	if (!running || operations.length < 2) return;
+	return `waiting for ${running.kind.replaceAll('_', ' ')} to finish`;
+}
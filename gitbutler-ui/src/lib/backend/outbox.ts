@@ -0,0 +1,112 @@
+import { Code, invoke, listen } from '$lib/backend/ipc';
+
+export type OutgoingOperation =
+	| {
+			type: 'push';
+			withForce: boolean;
+			/**
+			 * The head of the branch when the push was queued.
+			 */
+			head: string;
+	  }
+	| { type: 'pullRequest'; title: string; body: string; draft: boolean };
+
+export type OutboxEntry = {
+	id: string;
+	queuedTimestampMs: number;
+	branchId: string;
+	operation: OutgoingOperation;
+	/**
+	 * When the pull request was last handed over for creating.
+	 */
+	handedOverTimestampMs?: number;
+};
+
+export type ReplayOutcome =
+	| { type: 'pushed' }
+	/**
+	 * A pull request whose branch is pushed, to be created and then taken out of the outbox.
+	 */
+	| { type: 'ready' }
+	/**
+	 * A pull request that was handed over for creating, and may still be being created.
+	 */
+	| { type: 'handedOver' }
+	/**
+	 * A pull request of a branch whose push is still in the outbox.
+	 */
+	| { type: 'waiting' }
+	| { type: 'offline' }
+	| { type: 'conflict'; reason: string }
+	| { type: 'failed'; error: string };
+
+export type Replay = {
+	entry: OutboxEntry;
+	outcome: ReplayOutcome;
+};
+
+/**
+ * Whether `err`, the error of a push, means the network is down and it's worth queueing.
+ */
+export function isOffline(err: any) {
+	return !navigator.onLine || err?.code === Code.Network;
+}
+
+/**
+ * The pushes and pull requests waiting for the network, in the order they were queued.
+ */
+export async function listOutbox(projectId: string) {
+	return await invoke<OutboxEntry[]>('list_outbox', { projectId });
+}
+
+export async function queuePush(projectId: string, branchId: string, withForce: boolean) {
+	return await invoke<OutboxEntry>('queue_push', { projectId, branchId, withForce });
+}
+
+export async function queuePullRequest(
+	projectId: string,
+	branchId: string,
+	title: string,
+	body: string,
+	draft: boolean
+) {
+	return await invoke<OutboxEntry>('queue_pull_request', {
+		projectId,
+		branchId,
+		title,
+		body,
+		draft
+	});
+}
+
+/**
+ * Pushes what was queued, in order. A push is left in the outbox if the branch was rewritten
+ * or its remote branch got commits since it was queued, unless it's forced. The pull requests
+ * that are ready are for the caller to create, and take out of it with `removeOutboxEntry`.
+ */
+export async function replayOutbox(projectId: string) {
+	return await invoke<Replay[]>('replay_outbox', { projectId });
+}
+
+export async function removeOutboxEntry(projectId: string, entryId: string) {
+	return await invoke<void>('remove_outbox_entry', { projectId, entryId });
+}
+
+/**
+ * The outbox is replayed after a background fetch succeeds, this is what it did.
+ */
+export function subscribeToOutboxReplays(projectId: string, callback: (replays: Replay[]) => void) {
+	return listen<{ replays: Replay[] }>(`project://${projectId}/outbox/replay`, (event) =>
+		callback(event.payload.replays)
+	);
+}
+
+export function describeReplay(replay: Replay) {
+	const what = replay.entry.operation.type == 'push' ? 'Push' : 'Pull request';
+	switch (replay.outcome.type) {
+		case 'conflict':
+			return `${what} was not sent: ${replay.outcome.reason}`;
+		case 'failed':
+			return `${what} failed: ${replay.outcome.error}`;
+	}
+}
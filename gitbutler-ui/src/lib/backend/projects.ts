@@ -1,4 +1,4 @@
-import { invoke } from '$lib/backend/ipc';
+import { invoke, listen } from '$lib/backend/ipc';
 import { persisted } from '$lib/persisted/persisted';
 import * as toasts from '$lib/utils/toasts';
 import { open } from '@tauri-apps/api/dialog';
@@ -13,6 +13,7 @@ import {
 	switchMap
 } from 'rxjs';
 import { get } from 'svelte/store';
+import type { AiProvider } from '$lib/backend/appSettings';
 import type { Project as CloudProject } from '$lib/backend/cloud';
 import { goto } from '$app/navigation';
 
@@ -24,15 +25,167 @@ export type Key =
 			local: { private_key_path: string; passphrase?: string };
 	  };
 
+export type DiffAlgorithm = 'myers' | 'minimal' | 'patience' | 'histogram';
+
+export type DiffOptions = {
+	ignore_whitespace?: boolean;
+	ignore_blank_lines?: boolean;
+	context_lines?: number;
+	algorithm?: DiffAlgorithm;
+};
+
 export type Project = {
 	id: string;
 	title: string;
 	description?: string;
 	path: string;
+	bare_repository: string | undefined;
 	api?: CloudProject & { sync: boolean };
 	preferred_key: Key;
 	ok_with_force_push: boolean;
 	omit_certificate_check: boolean | undefined;
+	detect_copies: boolean | undefined;
+	diff_options: DiffOptions;
+	/**
+	 * Id of the group the project is in, if any.
+	 */
+	group?: string;
+	settings_version: number;
+	/**
+	 * Patterns of paths that are tracked by git but aren't watched, diffed or owned by a branch.
+	 */
+	unmanaged_paths: string[];
+	/**
+	 * Patterns of paths whose changes are never sent to a model.
+	 */
+	redacted_paths: string[];
+	/**
+	 * The directory of the repository the project is scoped to, if any.
+	 */
+	scope?: string;
+	/**
+	 * Whether changing the branches, commits or base of the project is refused.
+	 */
+	read_only: boolean;
+	/**
+	 * Fingerprints of the secrets that are allowed to be committed and pushed.
+	 */
+	allowed_secrets: string[];
+	/**
+	 * Whether commits and pushes are scanned for secrets, the `gitbutler.scanSecrets` git config
+	 * of the repository if it's not set.
+	 */
+	scan_secrets?: boolean;
+	/**
+	 * What generates the commit messages and branch names of the project, the one of the app
+	 * settings if it's not set.
+	 */
+	ai_provider?: AiProvider;
+	/**
+	 * What generated pull request descriptions follow, the template of the repository if it's
+	 * not set.
+	 */
+	pr_template?: string;
+	/**
+	 * The commit message profiles of the project, on top of the built-in ones.
+	 */
+	commit_message_profiles: CommitMessageProfile[];
+	/**
+	 * What models are asked to generate from, instead of the default prompts.
+	 */
+	prompt_templates: PromptTemplates;
+};
+
+/**
+ * Prompt templates, where `{{name}}` is replaced by the variable `name`, like `{{diff}}`.
+ */
+export type PromptTemplates = {
+	commit_message?: string;
+	branch_name?: string;
+	pull_request?: string;
+};
+
+export type CommitMessageStyle = 'conventional' | 'oneLine' | 'detailed';
+
+/**
+ * How generated commit messages are written, picked by its name.
+ */
+export type CommitMessageProfile = {
+	name: string;
+	style: CommitMessageStyle;
+	/**
+	 * Given to the model on top of what the style asks for.
+	 */
+	instructions: string;
+};
+
+/**
+ * A partial update of the settings of a project, the ones left out stay as they are.
+ */
+export type SettingsUpdate = {
+	preferred_key?: Key;
+	ok_with_force_push?: boolean;
+	omit_certificate_check?: boolean;
+	detect_copies?: boolean;
+	diff_options?: DiffOptions;
+	unmanaged_paths?: string[];
+	redacted_paths?: string[];
+	read_only?: boolean;
+	allowed_secrets?: string[];
+	scan_secrets?: boolean;
+	/**
+	 * An empty template unsets it.
+	 */
+	pr_template?: string;
+	commit_message_profiles?: CommitMessageProfile[];
+	/**
+	 * The templates that are set replace the ones of the project, empty ones unset them.
+	 */
+	prompt_templates?: PromptTemplates;
+};
+
+/**
+ * A way of working that sets up the target, push remote, force-push policy and commit
+ * signing of a project in one step.
+ */
+export type Preset = 'forkWorkflow' | 'trunkBased' | 'releaseBranch';
+
+export type CloneOptions = {
+	/**
+	 * The branch to check out, the default branch of the remote if it's not set.
+	 */
+	branch?: string;
+	/**
+	 * How many commits of history to fetch, all of them if it's not set.
+	 */
+	depth?: number;
+};
+
+export interface CloneProgress {
+	projectId: string;
+	receivedObjects: number;
+	totalObjects: number;
+	receivedBytes: number;
+	cloning: boolean;
+}
+
+/**
+ * Where the repository of a project is. When it's not at the path of the project anymore,
+ * `candidates` are the same repository found elsewhere.
+ */
+export type Location = { status: 'found' } | { status: 'moved'; candidates: string[] };
+
+export type Group = {
+	id: string;
+	name: string;
+};
+
+export type ProjectGroup = {
+	/**
+	 * Unset for the projects that are in no group, which come last.
+	 */
+	group?: Group;
+	projects: Project[];
 };
 
 export class ProjectService {
@@ -49,6 +202,15 @@ export class ProjectService {
 		})
 	);
 
+	groups$ = this.reload$.pipe(
+		switchMap(() => from(invoke<ProjectGroup[]>('list_project_groups'))),
+		shareReplay(1),
+		catchError((e) => {
+			this.error$.next(e);
+			return [];
+		})
+	);
+
 	constructor(private homeDir: string | undefined) {}
 
 	getProject(projectId: string) {
@@ -77,16 +239,107 @@ export class ProjectService {
 		this.reload();
 	}
 
-	async add(path: string) {
-		const project = await invoke<Project>('add_project', { path });
+	/**
+	 * Applies `settings` to the project, all of them or, if any is invalid, none.
+	 */
+	async updateSettings(id: string, settings: SettingsUpdate) {
+		const project = await invoke<Project>('update_project_settings', { id, settings });
+		this.reload();
+		return project;
+	}
+
+	/**
+	 * Adds the repository at `path`, scoped to the directory `scope` of it if it's set, so
+	 * that each component of a monorepo can be its own project.
+	 */
+	async add(path: string, scope?: string) {
+		const project = await invoke<Project>('add_project', { path, scope });
 		await this.reload();
 		return project;
 	}
 
+	/**
+	 * Clones `url` into `path`, which must be empty or not exist, and adds it as a project.
+	 */
+	async clone(
+		url: string,
+		path: string,
+		options?: CloneOptions,
+		onProgress?: (progress: CloneProgress) => void
+	) {
+		const unlisten = listen<CloneProgress>('clone://progress', (event) =>
+			onProgress?.(event.payload)
+		);
+		try {
+			const project = await invoke<Project>('clone_repository', { url, path, options });
+			await this.reload();
+			return project;
+		} finally {
+			unlisten();
+		}
+	}
+
 	async deleteProject(id: string) {
 		await invoke('delete_project', { id });
 	}
 
+	async locate(id: string) {
+		return await invoke<Location>('locate_project', { id });
+	}
+
+	/**
+	 * Points the project at the repository at `path`, where it moved to.
+	 */
+	async relink(id: string, path: string) {
+		const project = await invoke<Project>('relink_project', { id, path });
+		await this.reload();
+		return project;
+	}
+
+	async addGroup(name: string) {
+		const group = await invoke<Group>('add_project_group', { name });
+		this.reload();
+		return group;
+	}
+
+	async renameGroup(id: string, name: string) {
+		await invoke<Group>('rename_project_group', { id, name });
+		this.reload();
+	}
+
+	/**
+	 * Deletes the group, its projects are left in no group.
+	 */
+	async deleteGroup(id: string) {
+		await invoke<void>('delete_project_group', { id });
+		this.reload();
+	}
+
+	async setGroup(id: string, groupId: string | undefined) {
+		await invoke<Project>('set_project_group', { id, groupId });
+		this.reload();
+	}
+
+	async setAiProvider(id: string, aiProvider: AiProvider | undefined) {
+		const project = await invoke<Project>('set_project_ai_provider', { id, aiProvider });
+		this.reload();
+		return project;
+	}
+
+	/**
+	 * Moves the projects `ids` into the positions they are at between them, in the order
+	 * of `ids`.
+	 */
+	async reorder(ids: string[]) {
+		await invoke<void>('reorder_projects', { ids });
+		this.reload();
+	}
+
+	async reorderGroups(ids: string[]) {
+		await invoke<void>('reorder_project_groups', { ids });
+		this.reload();
+	}
+
 	async reload(): Promise<Project[]> {
 		const projects = firstValueFrom(this.projects$.pipe(skip(1)));
 		this.reload$.next();
@@ -0,0 +1,30 @@
+import { listen } from '$lib/backend/ipc';
+
+export type Retry = {
+	projectId: string;
+	operation: 'fetch' | 'push';
+	remote: string;
+	/**
+	 * The attempt that's made next, the first retry is the second attempt.
+	 */
+	attempt: number;
+	maxAttempts: number;
+	delayMs: number;
+	/**
+	 * Why the previous attempt failed.
+	 */
+	error: string;
+};
+
+/**
+ * Fetches and pushes that fail for a network error are tried again, after a delay that doubles
+ * with every attempt.
+ */
+export function subscribeToRetries(projectId: string, callback: (retry: Retry) => void) {
+	return listen<Retry>(`project://${projectId}/git/retry`, (event) => callback(event.payload));
+}
+
+export function describeRetry(retry: Retry) {
+	const seconds = Math.round(retry.delayMs / 1000);
+	return `Failed to ${retry.operation} ${retry.remote}: ${retry.error}. Retrying in ${seconds}s (attempt ${retry.attempt} of ${retry.maxAttempts})`;
+}
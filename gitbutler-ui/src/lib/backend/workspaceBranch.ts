@@ -0,0 +1,34 @@
+import { invoke } from '$lib/backend/ipc';
+import { distinctUntilChanged, from, shareReplay, switchMap, type Observable } from 'rxjs';
+
+export const DEFAULT_WORKSPACE_BRANCH = 'gitbutler/integration';
+
+/**
+ * The name of the branch the workspace is checked out on, `gitbutler/integration` unless
+ * the project configures another one.
+ */
+export async function getWorkspaceBranch(projectId: string) {
+	return await invoke<string>('get_workspace_branch', { projectId });
+}
+
+/**
+ * Renames the branch the workspace is checked out on.
+ */
+export async function setWorkspaceBranch(projectId: string, name: string) {
+	return await invoke<void>('set_workspace_branch', { projectId, name });
+}
+
+/**
+ * The name of the workspace branch, looked up again whenever `HEAD` changes since renaming
+ * the branch checks it out under its new name.
+ */
+export function getWorkspaceBranches(
+	projectId: string,
+	heads$: Observable<string>
+): Observable<string> {
+	return heads$.pipe(
+		switchMap(() => from(getWorkspaceBranch(projectId).catch(() => DEFAULT_WORKSPACE_BRANCH))),
+		distinctUntilChanged(),
+		shareReplay(1)
+	);
+}
@@ -1,7 +1,11 @@
 import { capture } from '$lib/analytics/posthog';
+import { queuePullRequest, queuePush, removeOutboxEntry } from '$lib/backend/outbox';
 import { CombinedBranch } from '$lib/branches/types';
+import * as toasts from '$lib/utils/toasts';
 import { Observable, combineLatest } from 'rxjs';
 import { startWith, switchMap } from 'rxjs/operators';
+import type { PullRequestContent } from '$lib/backend/ai';
+import type { Replay } from '$lib/backend/outbox';
 import type { GitHubService } from '$lib/github/service';
 import type { PullRequest } from '$lib/github/types';
 import type { RemoteBranchService } from '$lib/stores/remoteBranches';
@@ -13,6 +17,7 @@ export class BranchService {
 	public branches$: Observable<CombinedBranch[]>;
 
 	constructor(
+		private projectId: string,
 		private vbranchService: VirtualBranchService,
 		remoteBranchService: RemoteBranchService,
 		private githubService: GitHubService
@@ -40,13 +45,28 @@ export class BranchService {
 		branch: Branch,
 		baseBranch: string,
 		draft: boolean,
-		sentryTxn: Transaction
+		sentryTxn: Transaction,
+		content?: PullRequestContent,
+		signal?: AbortSignal
 	): Promise<PullRequest | undefined> {
+		const title = content?.title ?? branch.name;
+		const body = content?.body ?? branch.notes;
+
 		// Using this mutable variable while investigating why branch variable
 		// does not seem to update reliably.
 		// TODO: This needs to be fixed and removed.
 		let newBranch: Branch | undefined;
 
+		// Queue the pull request for when the network is back, behind the push it needs
+		if (!navigator.onLine) {
+			if (branch.commits.some((c) => !c.isRemote)) {
+				await queuePush(this.projectId, branch.id, branch.requiresForce);
+			}
+			await queuePullRequest(this.projectId, branch.id, title, body, draft);
+			toasts.success('You are offline, the pull request will be created once you are back online');
+			return;
+		}
+
 		// Push if local commits
 		if (branch.commits.some((c) => !c.isRemote)) {
 			const pushBranchSpan = sentryTxn.startChild({ op: 'branch_push' });
@@ -71,11 +91,12 @@ export class BranchService {
 		try {
 			const resp = await this.githubService.createPullRequest(
 				baseBranch,
-				newBranch.name,
-				newBranch.notes,
+				content?.title ?? newBranch.name,
+				content?.body ?? newBranch.notes,
 				newBranch.id,
 				newBranch.upstreamName,
-				draft
+				draft,
+				signal
 			);
 			if ('pr' in resp) return resp.pr;
 			if ('err' in resp) throw resp.err;
@@ -84,6 +105,37 @@ export class BranchService {
 		}
 	}
 
+	/**
+	 * Creates the pull requests replaying the outbox found ready, their branch is pushed, and
+	 * takes them out of it. Those that fail are left in it, to be handed over again later.
+	 */
+	async createQueuedPrs(replays: Replay[], baseBranch: string) {
+		for (const { entry, outcome } of replays) {
+			if (entry.operation.type != 'pullRequest' || outcome.type != 'ready') continue;
+			const branch = await this.vbranchService.getById(entry.branchId);
+			if (!branch?.upstreamName) continue;
+			let err: string | undefined;
+			try {
+				const resp = await this.githubService.createPullRequest(
+					baseBranch,
+					entry.operation.title,
+					entry.operation.body,
+					branch.id,
+					branch.upstreamName,
+					entry.operation.draft
+				);
+				if ('err' in resp) err = resp.err;
+			} catch (e: any) {
+				err = e?.message ?? String(e);
+			}
+			if (err === undefined) {
+				await removeOutboxEntry(this.projectId, entry.id);
+				continue;
+			}
+			toasts.error(`Failed to create queued pull request: ${err}`);
+		}
+	}
+
 	async reloadVirtualBranches() {
 		await this.vbranchService.reload();
 	}
@@ -35,6 +35,25 @@ export function appErrorReportingEnabled() {
 	return persisted(true, 'appErrorReportingEnabled');
 }
 
+export type CacheBudgets = {
+	mergeBasesMb: number;
+	repositoriesMb: number;
+	cleanedContentsMb: number;
+	treesMb: number;
+};
+
+/**
+ * Provides a writable store for the memory budgets of the in-process caches, in megabytes.
+ * The backend reads them on startup and evicts cached entries once a cache goes over its budget.
+ * @returns A writable store with the appCacheBudgets config.
+ */
+export function appCacheBudgets() {
+	return persisted<CacheBudgets>(
+		{ mergeBasesMb: 4, repositoriesMb: 256, cleanedContentsMb: 4, treesMb: 16 },
+		'appCacheBudgets'
+	);
+}
+
 function persisted<T>(initial: T, key: string): Writable<T> & { onDisk: () => Promise<T> } {
 	const setAndPersist = async (value: T, set: (value: T) => void) => {
 		await store.set(key, value);
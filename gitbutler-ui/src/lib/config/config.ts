@@ -20,6 +20,15 @@ export function projectCommitGenerationUseEmojis(projectId: string): Persisted<b
 	return persisted(false, key + projectId);
 }
 
+/**
+ * The name of the commit message profile messages are generated with, none for the style the
+ * "Extra concise" and emoji options ask for.
+ */
+export function projectCommitMessageProfile(projectId: string): Persisted<string | undefined> {
+	const key = 'projectCommitMessageProfile_';
+	return persisted(undefined, key + projectId);
+}
+
 export enum ListPRsFilter {
 	All = 'ALL',
 	ExcludeBots = 'EXCLUDE_BOTS',
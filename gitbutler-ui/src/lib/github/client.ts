@@ -1,9 +1,54 @@
+import { sleep } from '$lib/utils/sleep';
 import { Octokit } from '@octokit/rest';
 
+/**
+ * How many times a call to GitHub is made before it fails, the first attempt included.
+ */
+export const MAX_ATTEMPTS = 3;
+const INITIAL_DELAY_MS = 1000;
+const MAX_DELAY_MS = 30000;
+
+/**
+ * Whether a call to GitHub that failed with `status` is worth making again, by the decision
+ * table of fetches and pushes in `project_repository/retry.rs`: the server is there but can't
+ * answer now. Credentials aren't tried again, there's only the one token.
+ */
+export function isTransientStatus(status: number | undefined) {
+	return status !== undefined && [408, 425, 429, 500, 502, 503, 504].includes(status);
+}
+
+/**
+ * How long to wait before `attempt`, the first retry being the second attempt: a delay that
+ * doubles with every attempt, jittered by up to half of it.
+ */
+export function retryDelayMs(attempt: number) {
+	const delay = Math.min(INITIAL_DELAY_MS * 2 ** Math.max(attempt - 2, 0), MAX_DELAY_MS);
+	return delay / 2 + (Math.random() * delay) / 2;
+}
+
 export function newClient(authToken: string) {
-	return new Octokit({
+	const octokit = new Octokit({
 		auth: authToken,
 		userAgent: 'GitButler Client',
 		baseUrl: 'https://api.github.com'
 	});
+	// Reads are tried again when they fail for a transient error. Writes aren't, they could
+	// have been done already, those that can be retried do it themselves.
+	octokit.hook.wrap('request', async (request, options) => {
+		if (options.method != 'GET' && options.method != 'HEAD') return await request(options);
+		for (let attempt = 1; ; attempt++) {
+			try {
+				return await request(options);
+			} catch (err: any) {
+				const signal = options.request?.signal as AbortSignal | undefined;
+				if (attempt >= MAX_ATTEMPTS || signal?.aborted || !isTransientStatus(err.status)) {
+					throw err;
+				}
+				const delayMs = retryDelayMs(attempt + 1);
+				console.warn(`${options.method} ${options.url} failed, retrying in ${delayMs}ms`, err);
+				await sleep(delayMs);
+			}
+		}
+	});
+	return octokit;
 }
@@ -1,4 +1,4 @@
-import { newClient } from '$lib/github/client';
+import { MAX_ATTEMPTS, isTransientStatus, newClient, retryDelayMs } from '$lib/github/client';
 import {
 	type PullRequest,
 	type GitHubIntegrationContext,
@@ -19,7 +19,8 @@ import {
 	combineLatest,
 	defer,
 	TimeoutError,
-	throwError
+	throwError,
+	timer
 } from 'rxjs';
 import {
 	catchError,
@@ -161,7 +162,8 @@ export class GitHubService {
 		body: string,
 		branchId: string,
 		upstreamName: string,
-		draft: boolean
+		draft: boolean,
+		signal?: AbortSignal
 	): Promise<{ pr: PullRequest } | { err: string }> {
 		if (!this.enabled) {
 			throw "Can't create PR when service not enabled";
@@ -184,12 +186,14 @@ export class GitHubService {
 								base,
 								title,
 								body,
-								draft
+								draft,
+								request: { signal }
 							});
 							await this.reload();
 							return { pr: ghResponseToInstance(rsp.data) };
 						} catch (err: any) {
 							// Any error that should not be retried needs to be handled here.
+							if (signal?.aborted) return { err: 'Creating the pull request was cancelled' };
 							if (
 								err.status == 422 &&
 								err.message.includes('Draft pull requests are not supported')
@@ -200,9 +204,18 @@ export class GitHubService {
 					})
 				)
 			).pipe(
+				// GitHub refuses a pull request that was already created, so one that failed
+				// after being created isn't created twice.
 				retry({
-					count: 2,
-					delay: 500
+					count: MAX_ATTEMPTS - 1,
+					delay: (err, retryCount) => {
+						if (signal?.aborted || !isTransientStatus(err.status)) return throwError(() => err);
+						const delayMs = retryDelayMs(retryCount + 1);
+						toasts.error(
+							`Failed to create the pull request: ${err.message}. Retrying in ${Math.round(delayMs / 1000)}s (attempt ${retryCount + 1} of ${MAX_ATTEMPTS})`
+						);
+						return timer(delayMs);
+					}
 				}),
 				timeout(60000), // 60 second total timeout
 				catchError((err) => {
@@ -280,14 +293,15 @@ export class GitHubService {
 		});
 	}
 
-	async merge(pullNumber: number, method: MergeMethod) {
+	async merge(pullNumber: number, method: MergeMethod, signal?: AbortSignal) {
 		if (!this.octokit || !this.ctx) return;
 		try {
 			await this.octokit.pulls.merge({
 				owner: this.ctx.owner,
 				repo: this.ctx.repo,
 				pull_number: pullNumber,
-				merge_method: method
+				merge_method: method,
+				request: { signal }
 			});
 			await this.baseBranchService.fetchFromTarget();
 		} finally {
@@ -1,8 +1,10 @@
-import { RemoteFile, type AnyFile } from '$lib/vbranches/types';
+import { LocalFile, RemoteFile, type AnyFile } from '$lib/vbranches/types';
 
-export type FileStatus = 'A' | 'M' | 'D';
+export type FileStatus = 'A' | 'M' | 'D' | 'R';
 
 export function computeFileStatus(file: AnyFile): FileStatus {
+	if (file.renamedFrom) return 'R';
+	if (file instanceof LocalFile && file.copiedFrom) return 'A';
 	if (file instanceof RemoteFile) {
 		if (file.hunks.length == 1) {
 			const diff = file.hunks[0].diff;
@@ -9,6 +9,10 @@ export function error(msg: string, options: ToastOptions = {}) {
 	return toast.error(msg, { ...defaultOptions, ...options });
 }
 
+export function warning(msg: string, options: ToastOptions = {}) {
+	return toast(msg, { icon: '⚠️', ...defaultOptions, ...options });
+}
+
 export function success(msg: string, options: ToastOptions = {}) {
 	return toast.success(msg, { ...defaultOptions, ...options });
 }
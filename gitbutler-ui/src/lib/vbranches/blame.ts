@@ -0,0 +1,66 @@
+import { invoke } from '$lib/backend/ipc';
+import { Transform, Type, plainToInstance } from 'class-transformer';
+import type { Author } from './types';
+
+// files are blamed this many lines at a time, the first lines show before the whole history
+// of a large file is walked
+const CHUNK_LINES = 500;
+
+/**
+ * Lines of a file that were last changed by the same commit.
+ */
+export class BlameHunk {
+	/**
+	 * The first of the lines, counting from 1.
+	 */
+	startLine!: number;
+	lines!: number;
+	commitId!: string;
+	author!: Author;
+	@Transform((obj) => new Date(obj.value))
+	createdAt!: Date;
+	summary!: string;
+	boundary!: boolean;
+}
+
+export class Blame {
+	@Type(() => BlameHunk)
+	hunks!: BlameHunk[];
+	totalLines!: number;
+}
+
+/**
+ * Blames the file at `path` in the commit `rev`, only the lines from `startLine` to `endLine`
+ * if either is given.
+ */
+export async function blame(
+	projectId: string,
+	path: string,
+	rev: string,
+	startLine?: number,
+	endLine?: number
+) {
+	return plainToInstance(
+		Blame,
+		await invoke<any>('blame', { projectId, path, rev, startLine, endLine })
+	);
+}
+
+/**
+ * Blames the `ranges` of lines of the file, inclusive and counting from 1, a chunk of lines
+ * at a time, handing the hunks of every chunk to `onHunks` as they come.
+ */
+export async function blameIncrementally(
+	projectId: string,
+	path: string,
+	rev: string,
+	ranges: [number, number][],
+	onHunks: (hunks: BlameHunk[]) => void
+) {
+	for (const [firstLine, lastLine] of ranges) {
+		for (let startLine = firstLine; startLine <= lastLine; startLine += CHUNK_LINES) {
+			const endLine = Math.min(startLine + CHUNK_LINES - 1, lastLine);
+			onHunks((await blame(projectId, path, rev, startLine, endLine)).hunks);
+		}
+	}
+}
@@ -1,5 +1,13 @@
-import { invoke } from '$lib/backend/ipc';
+import { Code, invoke } from '$lib/backend/ipc';
+import { isOffline, queuePush } from '$lib/backend/outbox';
 import * as toasts from '$lib/utils/toasts';
+import {
+	promptExportVirtualBranches,
+	promptImportBundle,
+	promptImportVirtualBranches
+} from '$lib/vbranches/bundle';
+import { recoverWorkspace } from '$lib/vbranches/workspace';
+import type { Preset } from '$lib/backend/projects';
 import type { RemoteBranchService } from '$lib/stores/remoteBranches';
 import type { BaseBranchService, VirtualBranchService } from './branchStoresCache';
 import type { Branch, Hunk } from './types';
@@ -24,6 +32,34 @@ export class BranchController {
 		}
 	}
 
+	async recoverWorkspace() {
+		try {
+			const recovery = await recoverWorkspace(this.projectId);
+			const leftUnapplied =
+				recovery.notReapplied.length > 0 ||
+				(!!recovery.recoveredBranch && !recovery.recoveredBranchApplied);
+			if (leftUnapplied) {
+				toasts.error('Some branches conflict with each other and were left unapplied');
+			}
+		} catch (err: any) {
+			toasts.error(err.message ?? 'Failed to go back to the workspace');
+		} finally {
+			this.targetBranchService.reload();
+			this.vbranchService.reload();
+		}
+	}
+
+	async applyPreset(preset: Preset) {
+		try {
+			await this.targetBranchService.applyPreset(preset);
+		} catch (err: any) {
+			toasts.error(err.message ?? 'Failed to apply preset');
+		} finally {
+			this.targetBranchService.reload();
+			this.vbranchService.reload();
+		}
+	}
+
 	async resetBranch(branchId: string, targetCommitOid: string) {
 		try {
 			await invoke<void>('reset_virtual_branch', {
@@ -48,7 +84,8 @@ export class BranchController {
 		branch: string,
 		message: string,
 		ownership: string | undefined = undefined,
-		runHooks = false
+		runHooks = false,
+		noVerify = false
 	) {
 		try {
 			await invoke<void>('commit_virtual_branch', {
@@ -56,7 +93,8 @@ export class BranchController {
 				branch,
 				message,
 				ownership,
-				runHooks: runHooks
+				runHooks: runHooks,
+				noVerify
 			});
 		} catch (err) {
 			toasts.error('Failed to commit branch');
@@ -156,6 +194,18 @@ export class BranchController {
 		}
 	}
 
+	/**
+	 * Writes the files back with the line endings and byte order mark of the base branch, they
+	 * must have no other changes.
+	 */
+	async normalizeLineEndings(paths: string[]) {
+		try {
+			await invoke<void>('normalize_line_endings', { projectId: this.projectId, paths });
+		} catch (err) {
+			toasts.error('Failed to normalize line endings');
+		}
+	}
+
 	async updateBranchOwnership(branchId: string, ownership: string) {
 		try {
 			await invoke<void>('update_virtual_branch', {
@@ -177,8 +227,11 @@ export class BranchController {
 			await this.vbranchService.reload();
 			return await this.vbranchService.getById(branchId);
 		} catch (err: any) {
-			if (err.code === 'errors.git.authentication') {
+			if (err.code === Code.ProjectsGitAuth) {
 				toasts.error('Failed to authenticate. Did you setup GitButler ssh keys?');
+			} else if (isOffline(err)) {
+				await queuePush(this.projectId, branchId, withForce);
+				toasts.success('You are offline, the branch will be pushed once you are back online');
 			} else {
 				toasts.error(`Failed to push branch: ${err.message}`);
 			}
@@ -219,6 +272,53 @@ export class BranchController {
 		}
 	}
 
+	async recoverCommit(branchId: string, commitOid: string) {
+		try {
+			await invoke<string>('recover_commit', {
+				projectId: this.projectId,
+				branchId,
+				commitOid
+			});
+		} catch (err: any) {
+			toasts.error(`Failed to recover commit: ${err.message}`);
+		}
+	}
+
+	/**
+	 * Asks for a bundle file and adds its branches, applying the ones that don't conflict.
+	 */
+	async importBundle() {
+		try {
+			const branchIds = await promptImportBundle(this.projectId);
+			if (branchIds) toasts.success(`Imported ${branchIds.length} branches`);
+		} catch (err: any) {
+			toasts.error(`Failed to import bundle: ${err.message}`);
+		}
+	}
+
+	/**
+	 * Asks where to write all branches to, with their state and uncommitted changes.
+	 */
+	async exportVirtualBranches() {
+		try {
+			if (await promptExportVirtualBranches(this.projectId)) toasts.success('Exported branches');
+		} catch (err: any) {
+			toasts.error(`Failed to export branches: ${err.message}`);
+		}
+	}
+
+	/**
+	 * Asks for an archive of branches and restores them, applying the ones that were applied.
+	 */
+	async importVirtualBranches() {
+		try {
+			const branchIds = await promptImportVirtualBranches(this.projectId);
+			if (branchIds) toasts.success(`Imported ${branchIds.length} branches`);
+		} catch (err: any) {
+			toasts.error(`Failed to import branches: ${err.message}`);
+		}
+	}
+
 	async cherryPick(branchId: string, targetCommitOid: string) {
 		try {
 			await invoke<void>('cherry_pick_onto_virtual_branch', {
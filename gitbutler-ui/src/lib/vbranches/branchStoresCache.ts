@@ -1,7 +1,11 @@
 import { BaseBranch, Branch } from './types';
-import { invoke, listen } from '$lib/backend/ipc';
+import { Code, invoke, listen } from '$lib/backend/ipc';
+import { isOffline, queuePush } from '$lib/backend/outbox';
+import type { Preset } from '$lib/backend/projects';
+import { listen as listenTauri } from '@tauri-apps/api/event';
 import * as toasts from '$lib/utils/toasts';
 import { plainToInstance } from 'class-transformer';
+import { nanoid } from 'nanoid';
 import {
 	switchMap,
 	Observable,
@@ -10,7 +14,6 @@ import {
 	BehaviorSubject,
 	debounceTime,
 	concat,
-	from,
 	tap,
 	map,
 	firstValueFrom,
@@ -38,7 +41,7 @@ export class VirtualBranchService {
 			switchMap((gbBranchActive) =>
 				gbBranchActive
 					? concat(
-							from(listVirtualBranches({ projectId })),
+							streamVirtualBranches({ projectId }),
 							new Observable<Branch[]>((subscriber) => {
 								return subscribeToVirtualBranches(projectId, (branches) =>
 									subscriber.next(branches)
@@ -111,8 +114,11 @@ export class VirtualBranchService {
 			await this.reload();
 			return await this.getById(branchId);
 		} catch (err: any) {
-			if (err.code === 'errors.git.authentication') {
+			if (err.code === Code.ProjectsGitAuth) {
 				toasts.error('Failed to authenticate. Did you setup GitButler ssh keys?');
+			} else if (isOffline(err)) {
+				await queuePush(this.projectId, branchId, withForce);
+				toasts.success('You are offline, the branch will be pushed once you are back online');
 			} else {
 				toasts.error(`Failed to push branch: ${err.message}`);
 			}
@@ -121,15 +127,42 @@ export class VirtualBranchService {
 }
 
 function subscribeToVirtualBranches(projectId: string, callback: (branches: Branch[]) => void) {
-	return listen<any[]>(`project://${projectId}/virtual-branches`, (event) =>
+	const unsubscribe = listen<any[]>(`project://${projectId}/virtual-branches`, (event) =>
 		callback(plainToInstance(Branch, event.payload))
 	);
+	// the branches that are done so far, when listing all of them takes a while
+	let partial: any[] = [];
+	const unsubscribePartial = listen<BranchesChunk>(
+		`project://${projectId}/virtual-branches/stream/status`,
+		(event) => {
+			const chunk = event.payload;
+			if (chunk.type == 'branches') {
+				partial = chunk.branches;
+			} else if (chunk.type == 'files') {
+				partial.find((b) => b.id == chunk.branchId)?.files.push(...chunk.files);
+			} else {
+				callback(plainToInstance(Branch, partial));
+			}
+		}
+	);
+	return () => {
+		unsubscribe();
+		unsubscribePartial();
+	};
+}
+
+export interface DeepenProgress {
+	receivedObjects: number;
+	totalObjects: number;
+	receivedBytes: number;
+	fetching: boolean;
 }
 
 export class BaseBranchService {
 	base$: Observable<BaseBranch | null | undefined>;
 	busy$ = new BehaviorSubject(false);
 	error$ = new BehaviorSubject<any>(undefined);
+	deepenProgress$ = new BehaviorSubject<DeepenProgress | undefined>(undefined);
 	private reload$ = new BehaviorSubject<void>(undefined);
 
 	constructor(
@@ -159,7 +192,7 @@ export class BaseBranchService {
 			// trigger a base branch reload. It feels a bit awkward and should be improved.
 			await invoke<void>('fetch_from_target', { projectId: this.projectId });
 		} catch (err: any) {
-			if (err.code === 'errors.git.authentication') {
+			if (err.code === Code.ProjectsGitAuth) {
 				toasts.error('Failed to authenticate. Did you setup GitButler ssh keys?');
 			} else {
 				toasts.error(`Failed to fetch branch: ${err.message}`);
@@ -167,12 +200,40 @@ export class BaseBranchService {
 		}
 	}
 
+	/**
+	 * Fetches `depth` more commits of the history of the trunk when the project is a shallow
+	 * clone, or all of it when `depth` is left out.
+	 */
+	async deepenHistory(depth?: number) {
+		const unlisten = listen<DeepenProgress>(`project://${this.projectId}/git/deepen`, (event) =>
+			this.deepenProgress$.next(event.payload.fetching ? event.payload : undefined)
+		);
+		try {
+			await invoke<BaseBranch>('deepen_history', { projectId: this.projectId, depth });
+			this.reload();
+		} catch (err: any) {
+			toasts.error(`Failed to fetch more history: ${err.message}`);
+		} finally {
+			unlisten();
+			this.deepenProgress$.next(undefined);
+		}
+	}
+
 	async setTarget(branch: string) {
 		this.busy$.next(true);
 		await invoke<BaseBranch>('set_base_branch', { projectId: this.projectId, branch });
 		await this.fetchFromTarget();
 	}
 
+	/**
+	 * Sets the project up the way `preset` works, the target included.
+	 */
+	async applyPreset(preset: Preset) {
+		this.busy$.next(true);
+		await invoke<BaseBranch>('apply_project_preset', { projectId: this.projectId, preset });
+		await this.fetchFromTarget();
+	}
+
 	reload() {
 		this.busy$.next(true);
 		this.reload$.next();
@@ -183,6 +244,40 @@ export async function listVirtualBranches(params: { projectId: string }): Promis
 	return plainToInstance(Branch, await invoke<any[]>('list_virtual_branches', params));
 }
 
+type BranchesChunk =
+	| { type: 'branches'; branches: any[] }
+	| { type: 'files'; branchId: string; files: any[] }
+	| { type: 'done' };
+
+/**
+ * Same as `listVirtualBranches`, but branches are received in chunks and emitted as they
+ * fill up, so that very large diffs can be rendered progressively.
+ */
+export function streamVirtualBranches(params: { projectId: string }): Observable<Branch[]> {
+	return new Observable<Branch[]>((subscriber) => {
+		const streamId = nanoid();
+		let branches: any[] = [];
+		const unlisten = listenTauri<BranchesChunk>(
+			`project://${params.projectId}/virtual-branches/stream/${streamId}`,
+			(event) => {
+				const chunk = event.payload;
+				if (chunk.type == 'branches') {
+					branches = chunk.branches;
+				} else if (chunk.type == 'files') {
+					branches.find((b) => b.id == chunk.branchId)?.files.push(...chunk.files);
+				}
+				subscriber.next(plainToInstance(Branch, branches));
+				if (chunk.type == 'done') subscriber.complete();
+			}
+		);
+		// only start streaming once we are listening, chunks could be missed otherwise
+		unlisten
+			.then(() => invoke<void>('stream_virtual_branches', { ...params, streamId }))
+			.catch((err) => subscriber.error(err));
+		return () => unlisten.then((unlistenFn) => unlistenFn());
+	});
+}
+
 export async function getRemoteBranches(projectId: string | undefined) {
 	if (!projectId) return [];
 	return await invoke<Array<string>>('git_remote_branches', { projectId }).then((branches) =>
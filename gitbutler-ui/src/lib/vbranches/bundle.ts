@@ -0,0 +1,88 @@
+import { invoke } from '$lib/backend/ipc';
+import { open, save } from '@tauri-apps/api/dialog';
+
+const BUNDLE_FILTERS = [{ name: 'Git bundle', extensions: ['bundle'] }];
+const ARCHIVE_FILTERS = [{ name: 'GitButler archive', extensions: ['gbarchive'] }];
+
+/**
+ * Writes the branches, or all of them when `branchIds` is empty, with their uncommitted
+ * changes to a bundle file. The history of the target branch is left out.
+ */
+export async function exportBundle(projectId: string, branchIds: string[], path: string) {
+	await invoke<void>('export_bundle', { projectId, branchIds, path });
+}
+
+/**
+ * Adds a virtual branch for every branch of the bundle file, returning their ids.
+ */
+export async function importBundle(projectId: string, path: string): Promise<string[]> {
+	return await invoke<string[]>('import_bundle', { projectId, path });
+}
+
+/**
+ * Asks where to export the branches to, then exports them. Resolves to false if the user
+ * cancelled.
+ */
+export async function promptExportBundle(
+	projectId: string,
+	branchIds: string[],
+	defaultPath = 'branches.bundle'
+): Promise<boolean> {
+	const path = await save({ defaultPath, filters: BUNDLE_FILTERS });
+	if (!path) return false;
+	await exportBundle(projectId, branchIds, path);
+	return true;
+}
+
+/**
+ * Asks for a bundle file, then imports it. Resolves to the ids of the new branches, or
+ * undefined if the user cancelled.
+ */
+export async function promptImportBundle(projectId: string): Promise<string[] | undefined> {
+	const path = await open({ multiple: false, filters: BUNDLE_FILTERS });
+	if (!path || Array.isArray(path)) return;
+	return await importBundle(projectId, path);
+}
+
+
+/**
+ * Writes every virtual branch to an archive file, with its notes, ownership, uncommitted
+ * changes and whether it is applied, for another clone of the repository to import.
+ */
+export async function exportVirtualBranches(projectId: string, path: string) {
+	await invoke<void>('export_virtual_branches', { projectId, path });
+}
+
+/**
+ * Restores the virtual branches of an archive file next to the ones of the project,
+ * returning their ids.
+ */
+export async function importVirtualBranches(projectId: string, path: string): Promise<string[]> {
+	return await invoke<string[]>('import_virtual_branches', { projectId, path });
+}
+
+/**
+ * Asks where to write the archive to, then exports all branches. Resolves to false if the
+ * user cancelled.
+ */
+export async function promptExportVirtualBranches(
+	projectId: string,
+	defaultPath = 'virtual-branches.gbarchive'
+): Promise<boolean> {
+	const path = await save({ defaultPath, filters: ARCHIVE_FILTERS });
+	if (!path) return false;
+	await exportVirtualBranches(projectId, path);
+	return true;
+}
+
+/**
+ * Asks for an archive file, then imports it. Resolves to the ids of the new branches, or
+ * undefined if the user cancelled.
+ */
+export async function promptImportVirtualBranches(
+	projectId: string
+): Promise<string[] | undefined> {
+	const path = await open({ multiple: false, filters: ARCHIVE_FILTERS });
+	if (!path || Array.isArray(path)) return;
+	return await importVirtualBranches(projectId, path);
+}
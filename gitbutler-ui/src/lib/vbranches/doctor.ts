@@ -0,0 +1,28 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type Severity = 'info' | 'warning' | 'error';
+
+export type Problem =
+	| { kind: 'unreadableData'; message: string }
+	| { kind: 'missingObject'; branchId?: string; oid: string }
+	| { kind: 'danglingReference'; name: string }
+	| { kind: 'orphanedOwnership'; branchId: string; filePath: string }
+	| { kind: 'staleLock'; path: string; ageSecs: number };
+
+export type Issue = {
+	severity: Severity;
+	problem: Problem;
+	/**
+	 * Whether the problem can be fixed without losing anything.
+	 */
+	fixable: boolean;
+	fixed: boolean;
+};
+
+/**
+ * Checks the project for problems, the most severe first. The fixable ones are fixed if
+ * `fix` is set.
+ */
+export async function repairProject(projectId: string, fix = false): Promise<Issue[]> {
+	return await invoke<Issue[]>('repair_project', { projectId, fix });
+}
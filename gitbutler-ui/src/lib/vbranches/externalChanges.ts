@@ -0,0 +1,52 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type ReconcileAction = 'dismiss' | 'uncommit' | 'returnToWorkspace' | 'popStash';
+
+export type ExternalChangeKind =
+	| {
+			/**
+			 * Commits made on top of the workspace, moved onto the branch `branchId`.
+			 */
+			type: 'commits';
+			commits: string[];
+			branchId: string;
+			previousHead: string;
+			/**
+			 * Whether the branch was created for them, no applied branch owning all of their files.
+			 */
+			createdBranch: boolean;
+	  }
+	| {
+			/**
+			 * Something else than the workspace was checked out, and the branches were unapplied.
+			 */
+			type: 'checkout';
+			head: string;
+	  }
+	| {
+			type: 'stash';
+			stash: string;
+	  };
+
+export type ExternalChange = {
+	id: string;
+	timestampMs: number;
+	kind: ExternalChangeKind;
+	actions: ReconcileAction[];
+};
+
+/**
+ * What `git commit`, `git checkout` or `git stash` run outside of GitButler did to the
+ * workspace, with what can be done about it.
+ */
+export async function listExternalChanges(projectId: string) {
+	return await invoke<ExternalChange[]>('list_external_changes', { projectId });
+}
+
+export async function reconcileExternalChange(
+	projectId: string,
+	changeId: string,
+	action: ReconcileAction
+) {
+	return await invoke<void>('reconcile_external_change', { projectId, changeId, action });
+}
@@ -0,0 +1,22 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type LargeFile = {
+	filePath: string;
+	size: number;
+	/**
+	 * `size` if the file is over the size limit, `binary` if it's a binary file that isn't
+	 * tracked by LFS over the limit for those.
+	 */
+	reason: 'size' | 'binary';
+	/**
+	 * The command that tracks files like it with LFS.
+	 */
+	lfsTrack: string;
+};
+
+/**
+ * The large files of the uncommitted changes of the branch, to warn about before committing.
+ */
+export async function listLargeFiles(projectId: string, branchId: string) {
+	return await invoke<LargeFile[]>('list_large_files', { projectId, branchId });
+}
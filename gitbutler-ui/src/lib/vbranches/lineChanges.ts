@@ -0,0 +1,28 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type LineChanges = {
+	/**
+	 * Index of the line in the diff of the hunk, its header being the first line.
+	 */
+	line: number;
+	/**
+	 * What changed in the line, in characters from after its `-` or `+`.
+	 */
+	ranges: { start: number; end: number }[];
+};
+
+const cache = new Map<string, Promise<LineChanges[]>>();
+
+/**
+ * The words that changed within the lines of a hunk. They are computed when asked for, so
+ * this should only be called for hunks that are shown.
+ */
+export function hunkLineChanges(diff: string): Promise<LineChanges[]> {
+	let changes = cache.get(diff);
+	if (!changes) {
+		changes = invoke<LineChanges[]>('hunk_line_changes', { diff });
+		changes.catch(() => cache.delete(diff));
+		cache.set(diff, changes);
+	}
+	return changes;
+}
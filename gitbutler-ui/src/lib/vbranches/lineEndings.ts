@@ -0,0 +1,25 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type Churn = {
+	lineEndings: boolean;
+	byteOrderMark: boolean;
+};
+
+export type FileLineEndings = {
+	path: string;
+	/**
+	 * Set when the file only differs from the base branch in its line endings or byte order
+	 * mark, changes that editors make without being asked.
+	 */
+	churn?: Churn;
+};
+
+export async function listLineEndings(projectId: string): Promise<FileLineEndings[]> {
+	return await invoke<FileLineEndings[]>('list_line_endings', { projectId });
+}
+
+export function describeChurn(churn: Churn): string {
+	if (churn.lineEndings && churn.byteOrderMark) return 'Line endings and byte order mark only';
+	if (churn.byteOrderMark) return 'Byte order mark only';
+	return 'Line endings only';
+}
@@ -0,0 +1,30 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type RestoredMetadata = {
+	/**
+	 * The branches that were added or updated.
+	 */
+	restored: string[];
+	/**
+	 * The names of the branches whose commits aren't in the repository.
+	 */
+	skipped: string[];
+};
+
+/**
+ * Pushes the names, notes, order and owned files of the virtual branches to `remote`, the
+ * remote of the target branch when it is left out, for other clones to restore them.
+ */
+export async function pushBranchMetadata(projectId: string, remote?: string) {
+	await invoke<void>('push_branch_metadata', { projectId, remote });
+}
+
+/**
+ * Fetches the branch metadata of `remote` and restores the branches whose commits are here.
+ */
+export async function fetchBranchMetadata(
+	projectId: string,
+	remote?: string
+): Promise<RestoredMetadata> {
+	return await invoke<RestoredMetadata>('fetch_branch_metadata', { projectId, remote });
+}
@@ -0,0 +1,39 @@
+import { invoke } from '$lib/backend/ipc';
+
+export const DEFAULT_NOTES_REF = 'refs/notes/commits';
+
+export type Note = {
+	/**
+	 * The notes ref the note is in, like `refs/notes/commits`.
+	 */
+	notesRef: string;
+	message: string;
+};
+
+export async function listNotes(projectId: string, commitOid: string): Promise<Note[]> {
+	return await invoke<Note[]>('list_commit_notes', { projectId, commitOid });
+}
+
+/**
+ * Adds or edits the note of a commit, in the default notes ref when `notesRef` is left out.
+ * An empty `message` removes the note.
+ */
+export async function setNote(
+	projectId: string,
+	commitOid: string,
+	message: string,
+	notesRef?: string
+) {
+	await invoke<void>('set_commit_note', { projectId, commitOid, notesRef, message });
+}
+
+/**
+ * Pushes the notes refs to `remote`, the remote of the target branch when it is left out.
+ */
+export async function pushNotes(projectId: string, remote?: string) {
+	await invoke<void>('push_notes', { projectId, remote });
+}
+
+export async function fetchNotes(projectId: string, remote?: string) {
+	await invoke<void>('fetch_notes', { projectId, remote });
+}
@@ -0,0 +1,43 @@
+import { invoke, listen } from '$lib/backend/ipc';
+
+export type PrePushCheck = 'hook' | 'command' | 'branchName';
+
+export type CheckResult = {
+	check: PrePushCheck;
+	/**
+	 * `skipped` if the project doesn't have the check.
+	 */
+	status: 'passed' | 'failed' | 'skipped';
+	output: string;
+	durationMs: number;
+};
+
+export type PrePushReport = {
+	checks: CheckResult[];
+};
+
+export type OutputLine = {
+	check: PrePushCheck;
+	line: string;
+};
+
+/**
+ * Runs the checks pushing the branch runs, without pushing it.
+ */
+export async function checkPush(projectId: string, branchId: string, withForce = false) {
+	return await invoke<PrePushReport>('check_push', { projectId, branchId, withForce });
+}
+
+/**
+ * Calls `callback` with the output of the pre-push checks of the project as they run, while
+ * checking or pushing a branch.
+ */
+export function subscribeToPrePushOutput(
+	projectId: string,
+	callback: (branchId: string, lines: OutputLine[]) => void
+) {
+	return listen<{ branchId: string; lines: OutputLine[] }>(
+		`project://${projectId}/pre-push`,
+		(event) => callback(event.payload.branchId, event.payload.lines)
+	);
+}
@@ -0,0 +1,23 @@
+import { RemoteCommit } from './types';
+import { invoke } from '$lib/backend/ipc';
+import { Type, plainToInstance } from 'class-transformer';
+
+export class LostCommit {
+	@Type(() => RemoteCommit)
+	commit!: RemoteCommit;
+	/**
+	 * The reference whose reflog has the commit, or the GitButler session that recorded it.
+	 */
+	source!: string;
+}
+
+/**
+ * The commits of the branch that a reset or a rebase done outside of GitButler left behind,
+ * the newest first.
+ */
+export async function listLostCommits(projectId: string, branchId: string) {
+	return plainToInstance(
+		LostCommit,
+		await invoke<any[]>('list_lost_commits', { projectId, branchId })
+	);
+}
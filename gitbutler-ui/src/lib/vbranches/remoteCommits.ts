@@ -3,15 +3,25 @@
  * it's here is because the type is in this package.
  */
 import { RemoteFile } from './types';
+import type { DiffOptions } from '$lib/backend/projects';
 import { ContentSection, HunkSection, parseFileSections } from '$lib/utils/fileSections';
 import { invoke } from '@tauri-apps/api/tauri';
 import { plainToInstance } from 'class-transformer';
 
-export async function listRemoteCommitFiles(projectId: string, commitOid: string) {
-	return plainToInstance(
+/**
+ * Options that are left out are the ones of the project.
+ */
+export async function listRemoteCommitFiles(
+	projectId: string,
+	commitOid: string,
+	options?: DiffOptions
+) {
+	const files = plainToInstance(
 		RemoteFile,
-		await invoke<any[]>('list_remote_commit_files', { projectId, commitOid })
-	).sort((a, b) => a.path?.localeCompare(b.path));
+		await invoke<any[]>('list_remote_commit_files', { projectId, commitOid, options })
+	);
+	files.forEach((file) => (file.commitId = commitOid));
+	return files.sort((a, b) => a.path?.localeCompare(b.path));
 }
 
 export function parseRemoteFiles(files: RemoteFile[]) {
@@ -0,0 +1,40 @@
+import { invoke } from '$lib/backend/ipc';
+import type { Project, ProjectService } from '$lib/backend/projects';
+
+export type SecretFinding = {
+	ruleId: string;
+	description: string;
+	filePath: string;
+	/**
+	 * The line of the file in the change the secret was found in.
+	 */
+	line: number;
+	/**
+	 * The secret, with all but its first characters hidden.
+	 */
+	excerpt: string;
+	/**
+	 * What the finding is allowed by.
+	 */
+	fingerprint: string;
+};
+
+/**
+ * The secrets committing the changes of the branch and pushing it would be refused for.
+ */
+export async function scanSecrets(projectId: string, branchId: string) {
+	return await invoke<SecretFinding[]>('scan_secrets', { projectId, branchId });
+}
+
+/**
+ * Allows `findings` to be committed and pushed in the project from now on.
+ */
+export async function allowSecrets(
+	projectService: ProjectService,
+	project: Project,
+	findings: SecretFinding[]
+) {
+	const allowed = new Set(project.allowed_secrets);
+	findings.forEach((finding) => allowed.add(finding.fingerprint));
+	return await projectService.updateSettings(project.id, { allowed_secrets: [...allowed] });
+}
@@ -0,0 +1,71 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type TagKind = 'lightweight' | 'annotated' | 'signed';
+
+export type Tag = {
+	name: string;
+	/**
+	 * Id of the commit the tag points to.
+	 */
+	target: string;
+	/**
+	 * Set for annotated tags, without the signature of signed ones.
+	 */
+	message?: string;
+	signed: boolean;
+};
+
+export type BranchTags = {
+	branchId: string;
+	/**
+	 * The tags of the commits reachable from the head of the branch.
+	 */
+	tags: Tag[];
+};
+
+/**
+ * Tags a commit of a virtual branch or of the target branch.
+ */
+export async function createTag(
+	projectId: string,
+	name: string,
+	target: string,
+	kind: TagKind = 'lightweight',
+	message = ''
+): Promise<Tag> {
+	return await invoke<Tag>('create_tag', {
+		projectId,
+		request: { name, target, kind, message }
+	});
+}
+
+/**
+ * Pushes the tags to `remote`, the remote of the target branch when it is left out.
+ */
+export async function pushTags(projectId: string, names: string[], remote?: string) {
+	await invoke<void>('push_tags', { projectId, names, remote });
+}
+
+export async function listBranchTags(projectId: string): Promise<BranchTags[]> {
+	return await invoke<BranchTags[]>('list_branch_tags', { projectId });
+}
+
+/**
+ * The tags of the project, only the ones matching the glob `pattern` when it is given.
+ */
+export async function listTags(projectId: string, pattern?: string): Promise<Tag[]> {
+	return await invoke<Tag[]>('list_tags', { projectId, pattern });
+}
+
+/**
+ * Deletes the tag, and from `fromRemote` as well when it is given, for everyone who fetches
+ * from it. This can't be undone, callers should ask first, and ask again for the remote.
+ */
+export async function deleteTag(projectId: string, name: string, fromRemote?: string) {
+	await invoke<void>('delete_tag', {
+		projectId,
+		name,
+		remote: fromRemote,
+		deleteRemote: fromRemote !== undefined
+	});
+}
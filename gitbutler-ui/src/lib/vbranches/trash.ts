@@ -0,0 +1,37 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type TrashedFile = {
+	path: string;
+	/**
+	 * The diffs of the hunks of the file that were discarded.
+	 */
+	hunks: string[];
+	/**
+	 * Whether the file was executable before the discard.
+	 */
+	executable: boolean;
+};
+
+export type TrashEntry = {
+	id: string;
+	createdTimestampMs: number;
+	files: TrashedFile[];
+};
+
+/**
+ * The discarded changes that can still be restored, the newest first.
+ */
+export async function listTrash(projectId: string) {
+	return await invoke<TrashEntry[]>('list_trash', { projectId });
+}
+
+export async function restoreFromTrash(projectId: string, entryId: string) {
+	return await invoke<void>('restore_from_trash', { projectId, entryId });
+}
+
+/**
+ * Removes an entry of the trash, or every entry without `entryId`.
+ */
+export async function purgeTrash(projectId: string, entryId?: string) {
+	return await invoke<void>('purge_trash', { projectId, entryId });
+}
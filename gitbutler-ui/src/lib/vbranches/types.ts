@@ -28,6 +28,8 @@ export type AnyFile = LocalFile | RemoteFile;
 export class LocalFile {
 	id!: string;
 	path!: string;
+	renamedFrom?: string;
+	copiedFrom?: string;
 	@Type(() => Hunk)
 	hunks!: Hunk[];
 	expanded?: boolean;
@@ -39,6 +41,8 @@ export class LocalFile {
 	conflicted!: boolean;
 	content!: string;
 	binary!: boolean;
+	// This is true for files marked as `linguist-generated` in `.gitattributes`, which are not diffed.
+	generated!: boolean;
 	large!: boolean;
 
 	get filename(): string {
@@ -126,12 +130,19 @@ export class RemoteCommit {
 	description!: string;
 	@Transform((obj) => new Date(obj.value * 1000))
 	createdAt!: Date;
+	signature?: CommitSignature;
 
 	get isLocal() {
 		return false;
 	}
 }
 
+export interface CommitSignature {
+	status: 'trusted' | 'untrusted' | 'invalid' | 'unsigned';
+	key?: string;
+	signer?: string;
+}
+
 export class RemoteHunk {
 	diff!: string;
 
@@ -146,9 +157,15 @@ export class RemoteHunk {
 
 export class RemoteFile {
 	path!: string;
+	/**
+	 * The commit the file was listed for, set by `listRemoteCommitFiles`.
+	 */
+	commitId?: string;
+	renamedFrom?: string;
 	@Type(() => RemoteHunk)
 	hunks!: RemoteHunk[];
 	binary!: boolean;
+	generated!: boolean;
 
 	get id(): string {
 		return this.path;
@@ -200,6 +217,10 @@ export class RemoteBranchData {
 	@Type(() => RemoteCommit)
 	commits!: RemoteCommit[];
 	isMergeable!: boolean | undefined;
+	/**
+	 * Set when the commits or the count stopped at the end of a shallow clone.
+	 */
+	historyTruncated!: boolean;
 
 	get ahead(): number {
 		return this.commits.length;
@@ -238,6 +259,10 @@ export class BaseBranch {
 	@Type(() => RemoteCommit)
 	recentCommits!: RemoteCommit[];
 	lastFetchedMs?: number;
+	/**
+	 * Set when the upstream or recent commits stopped at the end of a shallow clone.
+	 */
+	historyTruncated!: boolean;
 
 	get lastFetched(): Date | undefined {
 		return this.lastFetchedMs ? new Date(this.lastFetchedMs) : undefined;
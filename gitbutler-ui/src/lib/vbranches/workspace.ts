@@ -0,0 +1,29 @@
+import { invoke } from '$lib/backend/ipc';
+
+export type WorkspaceRecovery = {
+	/**
+	 * What was checked out instead of the workspace, `undefined` if the workspace was.
+	 */
+	previousHead?: string;
+	/**
+	 * The branch made of what was checked out, if it had commits or changes of its own.
+	 */
+	recoveredBranch?: string;
+	/**
+	 * Whether the recovered branch could be applied, it's left unapplied if it conflicts.
+	 */
+	recoveredBranchApplied: boolean;
+	reapplied: string[];
+	/**
+	 * The branches that were left unapplied, conflicting with the others.
+	 */
+	notReapplied: string[];
+};
+
+/**
+ * Checks the workspace out again after something else was, bringing what was checked out
+ * along as a branch.
+ */
+export async function recoverWorkspace(projectId: string) {
+	return await invoke<WorkspaceRecovery>('recover_workspace', { projectId });
+}
@@ -1,11 +1,13 @@
 import { BranchService } from '$lib/branches/service';
+import { getWorkspaceBranches } from '$lib/backend/workspaceBranch';
 import { GitHubService } from '$lib/github/service';
 import { getFetchNotifications } from '$lib/stores/fetches';
 import { getHeads } from '$lib/stores/head';
 import { RemoteBranchService } from '$lib/stores/remoteBranches';
 import { BranchController } from '$lib/vbranches/branchController';
 import { BaseBranchService, VirtualBranchService } from '$lib/vbranches/branchStoresCache';
-import { map } from 'rxjs';
+import { combineLatest, map } from 'rxjs';
+import type { Location } from '$lib/backend/projects';
 import type { LayoutLoad } from './$types';
 
 export const prerender = false;
@@ -14,9 +16,16 @@ export const load: LayoutLoad = async ({ params, parent }) => {
 	const { user$, projectService, userService } = await parent();
 	const projectId = params.projectId;
 	const project$ = projectService.getProject(projectId);
+	// a project whose repository moved is relinked before anything is loaded from it
+	const location = await projectService
+		.locate(projectId)
+		.catch((): Location => ({ status: 'found' }));
 	const fetches$ = getFetchNotifications(projectId);
 	const heads$ = getHeads(projectId);
-	const gbBranchActive$ = heads$.pipe(map((head) => head == 'gitbutler/integration'));
+	const workspaceBranch$ = getWorkspaceBranches(projectId, heads$);
+	const gbBranchActive$ = combineLatest([heads$, workspaceBranch$]).pipe(
+		map(([head, workspaceBranch]) => head == workspaceBranch)
+	);
 	const baseBranchService = new BaseBranchService(projectId, fetches$, heads$);
 	const vbranchService = new VirtualBranchService(projectId, gbBranchActive$);
 
@@ -34,10 +43,11 @@ export const load: LayoutLoad = async ({ params, parent }) => {
 	);
 
 	const githubService = new GitHubService(userService, baseBranchService);
-	const branchService = new BranchService(vbranchService, remoteBranchService, githubService);
+	const branchService = new BranchService(projectId, vbranchService, remoteBranchService, githubService);
 
 	return {
 		projectId,
+		location,
 		branchController,
 		baseBranchService,
 		githubService,
@@ -46,6 +56,7 @@ export const load: LayoutLoad = async ({ params, parent }) => {
 		user$,
 		project$,
 		branchService,
-		gbBranchActive$
+		gbBranchActive$,
+		workspaceBranch$
 	};
 };